@@ -1,11 +1,14 @@
 use {
+    crate::squads::SquadsProposer,
     anchor_client::{Client, Cluster, Program},
-    clap::Parser,
+    clap::{clap_derive::ArgEnum, Parser},
     serde::{Deserialize, Serialize},
     solana_sdk::{
         commitment_config::CommitmentConfig,
+        compute_budget::{self, ComputeBudgetInstruction},
+        instruction::Instruction,
         pubkey::Pubkey,
-        signature::{Keypair, Signer},
+        signature::Signer,
     },
     std::str::FromStr,
 };
@@ -36,22 +39,78 @@ pub struct GlobalOptions {
         default_value_t = false
     )]
     pub skip_confirmation: bool,
+
+    /// Encoding of the transactions printed in multisig mode.
+    #[clap(
+        global = true,
+        long = "tx-encoding",
+        arg_enum,
+        default_value = "base58"
+    )]
+    pub tx_encoding: TxEncoding,
+
+    /// Squads v4 multisig of the profile multisig vault, multisig transactions are proposed to it
+    /// (by the default Solana CLI keypair) instead of being printed.
+    #[clap(global = true, long = "squads-multisig")]
+    pub squads_multisig: Option<Pubkey>,
+
+    #[clap(global = true, long = "squads-vault-index", default_value_t = 0)]
+    pub squads_vault_index: u8,
+
+    /// Output format of read commands.
+    #[clap(global = true, long = "output", arg_enum, default_value = "human")]
+    pub output: OutputFormat,
+
+    /// Compute unit price in micro-lamports, added to every transaction.
+    #[clap(global = true, long = "priority-fee")]
+    pub priority_fee: Option<u64>,
+
+    /// Compute unit limit of every transaction, except split transactions which use their estimate.
+    #[clap(global = true, long = "compute-units")]
+    pub compute_units: Option<u32>,
+}
+
+#[derive(Copy, Clone, Debug, Default, ArgEnum)]
+pub enum TxEncoding {
+    #[default]
+    Base58,
+    Base64,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ArgEnum)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    /// Pubkeys as strings and token amounts in UI units.
+    Json,
 }
 
 #[derive(Copy, Clone, Debug)]
-pub enum TxMode {
+pub enum TxMode<'a> {
     DryRun,
-    Multisig,
+    Multisig {
+        encoding: TxEncoding,
+        proposer: Option<SquadsProposer<'a>>,
+    },
     Normal,
 }
 
 pub struct Config {
     pub cluster: Cluster,
-    pub fee_payer: Keypair,
+    /// Keypair file or hardware wallet.
+    pub fee_payer: Box<dyn Signer>,
     pub multisig: Option<Pubkey>,
     pub program_id: Pubkey,
     pub commitment: CommitmentConfig,
     pub dry_run: bool,
+    pub tx_encoding: TxEncoding,
+    pub squads_multisig: Option<Pubkey>,
+    pub squads_vault_index: u8,
+    pub priority_fee: Option<u64>,
+    pub compute_units: Option<u32>,
+    pub output: OutputFormat,
+    /// Address lookup table of the profile, see [`crate::utils::process_instructions`].
+    pub lookup_table: Option<Pubkey>,
     pub client: Client,
     pub mfi_program: Program,
     pub lip_program: Program,
@@ -72,29 +131,73 @@ impl Config {
         }
     }
 
-    pub fn get_tx_mode(&self) -> TxMode {
+    pub fn json_output(&self) -> bool {
+        self.output == OutputFormat::Json
+    }
+
+    pub fn get_tx_mode(&self) -> TxMode<'_> {
         if self.dry_run {
             TxMode::DryRun
         } else if self.multisig.is_some() {
-            TxMode::Multisig
+            TxMode::Multisig {
+                encoding: self.tx_encoding,
+                proposer: self.squads_multisig.map(|multisig| SquadsProposer {
+                    multisig,
+                    vault_index: self.squads_vault_index,
+                    creator: self.fee_payer.as_ref(),
+                }),
+            }
         } else {
             TxMode::Normal
         }
     }
 
-    pub fn get_signers(&self, explicit_fee_payer: bool) -> Vec<&Keypair> {
+    /// Prepend the compute budget instructions of the `--priority-fee` and `--compute-units`
+    /// options to `ixs`, in place of the compute budget instructions `ixs` already sets.
+    pub fn with_compute_budget(&self, ixs: &[Instruction]) -> Vec<Instruction> {
+        let mut budget_ixs = vec![];
+        if let Some(compute_units) = self.compute_units {
+            budget_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(
+                compute_units,
+            ));
+        }
+        if let Some(priority_fee) = self.priority_fee {
+            budget_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(
+                priority_fee,
+            ));
+        }
+
+        // A transaction can set each compute budget parameter only once
+        let is_overridden = |ix: &Instruction| {
+            ix.program_id == compute_budget::ID
+                && budget_ixs
+                    .iter()
+                    .any(|budget_ix| budget_ix.data.first() == ix.data.first())
+        };
+
+        let ixs = ixs
+            .iter()
+            .filter(|ix| !is_overridden(ix))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        budget_ixs.extend(ixs);
+        budget_ixs
+    }
+
+    pub fn get_signers(&self, explicit_fee_payer: bool) -> Vec<&dyn Signer> {
         if explicit_fee_payer || self.multisig.is_none() {
-            vec![&self.fee_payer]
+            vec![self.fee_payer.as_ref()]
         } else {
             vec![]
         }
     }
 
-    /// Get the authority keypair for signing transactions.
+    /// Get the authority signer for signing transactions.
     /// This errors if the authority is a multisig.
-    pub fn get_non_ms_authority_keypair(&self) -> anyhow::Result<&Keypair> {
+    pub fn get_non_ms_authority_keypair(&self) -> anyhow::Result<&dyn Signer> {
         if self.multisig.is_none() {
-            Ok(&self.fee_payer)
+            Ok(self.fee_payer.as_ref())
         } else {
             Err(anyhow::anyhow!("Cannot get authority keypair for multisig"))
         }
@@ -0,0 +1,536 @@
+//! Human-readable summaries of the marginfi and LIP instructions of a transaction,
+//! and of the events marginfi emitted while executing it.
+
+use {
+    crate::utils::EXP_10_I80F48,
+    anchor_client::anchor_lang::{AnchorDeserialize, Discriminator},
+    base64::{engine::general_purpose, Engine},
+    fixed::types::I80F48,
+    marginfi::{events::*, state::marginfi_group::Bank},
+    solana_sdk::pubkey::Pubkey,
+    std::{collections::HashMap, str::FromStr},
+};
+
+const PROGRAM_LOG_PREFIX: &str = "Program ";
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Program ids and banks used to describe instructions and events.
+pub struct DecodeContext {
+    pub marginfi_program_id: Pubkey,
+    pub lip_program_id: Pubkey,
+    /// Banks referenced by the transaction, to show amounts in UI units.
+    pub banks: HashMap<Pubkey, Bank>,
+}
+
+impl DecodeContext {
+    /// `amount` of the mint of `bank_pk`, in UI units if the bank is known.
+    fn amount(&self, bank_pk: &Pubkey, amount: u64) -> String {
+        match self.banks.get(bank_pk) {
+            Some(bank) => format!(
+                "{} {}",
+                I80F48::from_num(amount) / EXP_10_I80F48[bank.mint_decimals as usize],
+                bank.mint
+            ),
+            None => format!("{} (native) in bank {}", amount, bank_pk),
+        }
+    }
+}
+
+macro_rules! describe {
+    ($data:expr, $($kind:ty => |$decoded:pat_param| $description:expr),* $(,)?) => {{
+        if $data.len() < 8 {
+            return None;
+        }
+
+        let (discriminator, mut payload) = $data.split_at(8);
+        $(
+            if discriminator == <$kind>::discriminator() {
+                return Some(match <$kind>::deserialize(&mut payload) {
+                    Ok($decoded) => $description,
+                    Err(_) => format!("{} (malformed)", stringify!($kind)),
+                });
+            }
+        )*
+        None
+    }};
+}
+
+/// Describe an instruction of the marginfi or LIP program, `None` for other programs and
+/// unknown instructions. `accounts` are the instruction accounts, in order.
+pub fn describe_instruction(
+    ctx: &DecodeContext,
+    program_id: &Pubkey,
+    accounts: &[Pubkey],
+    data: &[u8],
+) -> Option<String> {
+    let account = |index: usize| {
+        accounts
+            .get(index)
+            .map(|account| account.to_string())
+            .unwrap_or_else(|| "?".to_owned())
+    };
+    let amount = |bank_index: usize, amount: u64| match accounts.get(bank_index) {
+        Some(bank_pk) => ctx.amount(bank_pk, amount),
+        None => format!("{} (native)", amount),
+    };
+
+    if *program_id == ctx.marginfi_program_id {
+        use marginfi::instruction::*;
+
+        describe!(
+            data,
+            MarginfiGroupInitialize => |_| format!("Create group {}", account(0)),
+            MarginfiGroupConfigure => |_| format!("Configure group {}", account(0)),
+            MarginfiGroupSetPendingAdmin => |ix| format!(
+                "Propose {} as admin of group {}",
+                ix.pending_admin,
+                account(0)
+            ),
+            MarginfiGroupAcceptAdmin => |_| format!("Accept admin of group {}", account(0)),
+            LendingPoolAddBank => |_| format!("Add bank {} to group {}", account(4), account(0)),
+            LendingPoolConfigureBank => |_| format!("Configure bank {}", account(2)),
+            LendingPoolSetupEmissions => |ix| format!(
+                "Setup emissions of bank {} (rate: {}, total: {})",
+                account(2),
+                ix.rate,
+                ix.total_emissions
+            ),
+            LendingPoolUpdateEmissionsParameters => |_| {
+                format!("Update emissions of bank {}", account(2))
+            },
+            LendingPoolFundEmissions => |ix| format!(
+                "Fund emissions of bank {} with {}",
+                account(1),
+                ix.amount
+            ),
+            LendingPoolSetupReceiptMint => |_| format!("Setup receipt mint of bank {}", account(2)),
+            LendingPoolHandleBankruptcy => |_| format!(
+                "Handle bankruptcy of account {} in bank {}",
+                account(3),
+                account(2)
+            ),
+            LendingPoolForceCloseBalance => |_| format!(
+                "Force close balance of account {} in bank {}",
+                account(3),
+                account(2)
+            ),
+            MarginfiAccountInitialize => |_| format!(
+                "{} creates account {} in group {}",
+                account(2),
+                account(1),
+                account(0)
+            ),
+            MarginfiAccountInitializeSubAccount => |ix| format!(
+                "{} creates sub-account #{} {} in group {}",
+                account(3),
+                ix.account_index,
+                account(1),
+                account(0)
+            ),
+            LendingAccountDeposit => |ix| format!(
+                "{} deposits {} into account {}",
+                account(2),
+                amount(3, ix.amount),
+                account(1)
+            ),
+            LendingAccountRepay => |ix| format!(
+                "{} repays {} to account {}",
+                account(2),
+                if ix.repay_all.unwrap_or(false) {
+                    format!("all liabilities in bank {}", account(3))
+                } else {
+                    amount(3, ix.amount)
+                },
+                account(1)
+            ),
+            LendingAccountWithdraw => |ix| format!(
+                "{} withdraws {} from account {}",
+                account(2),
+                if ix.withdraw_all.unwrap_or(false) {
+                    format!("all assets in bank {}", account(3))
+                } else {
+                    amount(3, ix.amount)
+                },
+                account(1)
+            ),
+            LendingAccountDepositNative => |ix| format!(
+                "{} deposits {} of native SOL into account {}",
+                account(2),
+                amount(3, ix.amount),
+                account(1)
+            ),
+            LendingAccountRepayNative => |ix| format!(
+                "{} repays {} with native SOL to account {}",
+                account(2),
+                if ix.repay_all.unwrap_or(false) {
+                    format!("all liabilities in bank {}", account(3))
+                } else {
+                    amount(3, ix.amount)
+                },
+                account(1)
+            ),
+            LendingAccountWithdrawNative => |ix| format!(
+                "{} withdraws {} as native SOL from account {}",
+                account(2),
+                if ix.withdraw_all.unwrap_or(false) {
+                    format!("all assets in bank {}", account(3))
+                } else {
+                    amount(3, ix.amount)
+                },
+                account(1)
+            ),
+            LendingAccountBorrow => |ix| format!(
+                "{} borrows {} with account {}",
+                account(2),
+                amount(3, ix.amount),
+                account(1)
+            ),
+            LendingAccountCloseBalance => |_| format!(
+                "{} closes the balance of account {} in bank {}",
+                account(2),
+                account(1),
+                account(3)
+            ),
+            LendingAccountPulseHealth => |_| format!("Pulse health of account {}", account(0)),
+            LendingAccountWithdrawEmissions => |_| format!(
+                "{} withdraws the emissions of account {} in bank {}",
+                account(2),
+                account(1),
+                account(3)
+            ),
+            LendingAccountSettleEmissions => |_| format!(
+                "Settle the emissions of account {} in bank {}",
+                account(0),
+                account(1)
+            ),
+            LendingAccountMintReceipt => |ix| format!(
+                "{} mints {} of receipt tokens of account {}",
+                account(2),
+                amount(3, ix.amount),
+                account(1)
+            ),
+            LendingAccountRedeemReceipt => |ix| format!(
+                "{} redeems {} of receipt tokens",
+                account(1),
+                amount(2, ix.amount)
+            ),
+            LendingAccountSplitPosition => |ix| format!(
+                "{} splits the liability of account {} in bank {} and {} collateral balances into account {}",
+                account(3),
+                account(1),
+                account(4),
+                ix.collateral.len(),
+                account(2)
+            ),
+            LendingAccountLiquidate => |ix| format!(
+                "{} liquidates {} of account {} with account {}, repaying liabilities in bank {}",
+                account(4),
+                amount(1, ix.asset_amount),
+                account(5),
+                account(3),
+                account(2)
+            ),
+            MarginfiAccountConfigureAutoDeleverage => |ix| match ix.config {
+                Some(config) => format!(
+                    "Allow {} to deleverage account {} below health {:.2}",
+                    config.keeper,
+                    account(0),
+                    I80F48::from(config.health_buffer).to_num::<f64>()
+                ),
+                None => format!("Disallow deleveraging of account {}", account(0)),
+            },
+            LendingAccountDeleverage => |ix| format!(
+                "{} deleverages account {}, taking over {} with account {} against liabilities in bank {}",
+                account(4),
+                account(5),
+                amount(1, ix.asset_amount),
+                account(3),
+                account(2)
+            ),
+            LendingPoolAccrueBankInterest => |_| format!("Accrue interest of bank {}", account(1)),
+            LendingPoolAccrueBanks => |_| format!(
+                "Accrue interest of banks {}",
+                accounts
+                    .get(1..)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|bank| bank.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            LendingPoolSyncBorrowValue => |_| format!(
+                "Sync borrow value of banks {}",
+                accounts
+                    .get(1..)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|bank| bank.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            LendingPoolWithdrawReferralFees => |_| format!(
+                "{} withdraws its referral fees in bank {}",
+                account(1),
+                account(2)
+            ),
+            LendingPoolCollectBankFees => |_| format!("Collect fees of bank {}", account(1)),
+            FeeStateInitialize => |ix| format!(
+                "Initialize the program fees, administered by {} and paid to {}",
+                ix.global_fee_admin, ix.global_fee_wallet
+            ),
+            FeeStateConfigure => |ix| format!("Configure the program fees: {:?}", ix.config),
+            LendingPoolSweepProgramFees => |_| format!(
+                "Sweep the program fees of bank {} to {}",
+                account(1),
+                account(5)
+            ),
+            MigrateAccount => |_| format!("Migrate account {} to its latest version", account(0)),
+        )
+    } else if *program_id == ctx.lip_program_id {
+        use liquidity_incentive_program::instruction::*;
+
+        describe!(
+            data,
+            CreateCampaign => |ix| format!(
+                "Create campaign {} (max deposits: {}, max rewards: {}, lockup: {} days)",
+                account(0),
+                ix.max_deposits,
+                ix.max_rewards,
+                ix.lockup_period / (24 * 60 * 60)
+            ),
+            CreateDeposit => |ix| format!(
+                "{} deposits {} in campaign {} (lockup tier: {})",
+                account(1),
+                amount(9, ix.amount),
+                account(0),
+                ix.lockup_tier
+            ),
+            EndDeposit => |_| format!("End deposit {}", account(4)),
+            EarlyEndDeposit => |_| format!("Early end deposit {}", account(4)),
+            ClaimDepositEmissions => |_| format!("Claim the emissions of deposit {}", account(2)),
+            TransferDeposit => |_| format!("Transfer deposit {} to {}", account(0), account(2)),
+            EndCampaign => |_| format!("End campaign {}", account(0)),
+            ReconcileExitedDeposits => |_| format!(
+                "Reconcile the exited deposits of campaign {}",
+                account(0)
+            ),
+            PauseCampaign => |_| format!("Pause campaign {}", account(0)),
+            ResumeCampaign => |_| format!("Resume campaign {}", account(0)),
+            UpdateCampaign => |ix| format!(
+                "Update campaign {} (additional rewards: {})",
+                account(0),
+                ix.additional_rewards
+            ),
+        )
+    } else {
+        None
+    }
+}
+
+/// Describe the events emitted by the marginfi program, in emission order.
+/// Events emitted by other programs in the same transaction are ignored.
+pub fn describe_events(ctx: &DecodeContext, logs: &[String]) -> Vec<String> {
+    event_data(&ctx.marginfi_program_id, logs)
+        .iter()
+        .filter_map(|data| describe_event(ctx, data))
+        .collect()
+}
+
+/// Raw data (discriminator and payload) of the events emitted by `program_id`, in emission order.
+pub fn event_data(program_id: &Pubkey, logs: &[String]) -> Vec<Vec<u8>> {
+    let mut invocation_stack: Vec<Pubkey> = vec![];
+    let mut events = vec![];
+
+    for log in logs {
+        if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if invocation_stack.last() != Some(program_id) {
+                continue;
+            }
+
+            if let Ok(data) = general_purpose::STANDARD.decode(data) {
+                events.push(data);
+            }
+        } else if let Some(rest) = log.strip_prefix(PROGRAM_LOG_PREFIX) {
+            let mut parts = rest.split_whitespace();
+            let (program, action) = match (parts.next().map(Pubkey::from_str), parts.next()) {
+                (Some(Ok(program)), Some(action)) => (program, action),
+                _ => continue,
+            };
+
+            match action {
+                "invoke" => invocation_stack.push(program),
+                "success" | "failed:" => {
+                    invocation_stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    events
+}
+
+fn describe_event(ctx: &DecodeContext, data: &[u8]) -> Option<String> {
+    describe!(
+        data,
+        MarginfiGroupCreateEvent => |event| {
+            format!("Group {} created", event.header.marginfi_group)
+        },
+        MarginfiGroupConfigureEvent => |event| {
+            format!("Group {} configured", event.header.marginfi_group)
+        },
+        MarginfiGroupSetPendingAdminEvent => |event| format!(
+            "Group {} pending admin set to {}",
+            event.header.marginfi_group, event.pending_admin
+        ),
+        MarginfiGroupAcceptAdminEvent => |event| format!(
+            "Group {} admin changed from {} to {}",
+            event.header.marginfi_group, event.previous_admin, event.admin
+        ),
+        LendingPoolBankCreateEvent => |event| {
+            format!("Bank {} created for mint {}", event.bank, event.mint)
+        },
+        LendingPoolBankConfigureEvent => |event| format!("Bank {} configured", event.bank),
+        LendingPoolBankAccrueInterestEvent => |event| format!(
+            "Bank {} accrued {}s of interest (fees: {}, insurance: {})",
+            event.bank, event.delta, event.fees_collected, event.insurance_collected
+        ),
+        LendingPoolBankFundEmissionsEvent => |event| format!(
+            "Bank {} emissions funded with {} of {}, {} remaining until {}",
+            event.bank,
+            event.amount,
+            event.emissions_mint,
+            event.emissions_remaining,
+            event.emissions_end_timestamp
+        ),
+        LendingPoolBankKeeperTipEvent => |event| format!(
+            "Bank {} tipped {} to keeper {}",
+            event.bank,
+            ctx.amount(&event.bank, event.amount),
+            event.keeper_token_account
+        ),
+        LendingPoolBankCollectFeesEvent => |event| format!(
+            "Bank {} fees collected (group: {}, insurance: {})",
+            event.bank, event.group_fees_collected, event.insurance_fees_collected
+        ),
+        LendingPoolBankWithdrawReferralFeesEvent => |event| format!(
+            "Referrer {} withdrew {} of referral fees from bank {}",
+            event.header.signer.map_or("-".to_string(), |signer| signer.to_string()),
+            ctx.amount(&event.bank, event.amount),
+            event.bank
+        ),
+        LendingPoolBankHandleBankruptcyEvent => |event| format!(
+            "Bankruptcy of account {} in bank {}: bad debt {}, covered {}, socialized {}",
+            event.header.marginfi_account,
+            event.bank,
+            ctx.amount(&event.bank, I80F48::from(event.bad_debt).to_num()),
+            ctx.amount(&event.bank, I80F48::from(event.covered_amount).to_num()),
+            ctx.amount(&event.bank, I80F48::from(event.socialized_amount).to_num())
+        ),
+        LendingPoolBankForceCloseBalanceEvent => |event| format!(
+            "Balance of account {} in bank {} force closed",
+            event.header.marginfi_account, event.bank
+        ),
+        MarginfiAccountCreateEvent => |event| format!(
+            "Account {} created by {}",
+            event.header.marginfi_account, event.header.marginfi_account_authority
+        ),
+        LendingAccountDepositEvent => |event| format!(
+            "Account {} deposited {}",
+            event.header.marginfi_account,
+            ctx.amount(&event.bank, event.amount)
+        ),
+        LendingAccountRepayEvent => |event| format!(
+            "Account {} repaid {}{}",
+            event.header.marginfi_account,
+            ctx.amount(&event.bank, event.amount),
+            if event.close_balance { ", balance closed" } else { "" }
+        ),
+        LendingAccountBorrowEvent => |event| {
+            let mut fees = vec![];
+            if event.origination_fee > 0 {
+                fees.push(format!(
+                    "origination fee: {}",
+                    ctx.amount(&event.bank, event.origination_fee)
+                ));
+            }
+            if event.program_fee > 0 {
+                fees.push(format!(
+                    "program fee: {}",
+                    ctx.amount(&event.bank, event.program_fee)
+                ));
+            }
+            if event.flat_fee > 0 {
+                fees.push(format!("flat fee: {} lamports", event.flat_fee));
+            }
+
+            format!(
+                "Account {} borrowed {}{}",
+                event.header.marginfi_account,
+                ctx.amount(&event.bank, event.amount),
+                if fees.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", fees.join(", "))
+                }
+            )
+        },
+        LendingAccountWithdrawEvent => |event| format!(
+            "Account {} withdrew {}{}",
+            event.header.marginfi_account,
+            ctx.amount(&event.bank, event.amount),
+            if event.close_balance { ", balance closed" } else { "" }
+        ),
+        LendingAccountWithdrawEmissionsEvent => |event| format!(
+            "Account {} withdrew {} (native) of {} emissions from bank {}",
+            event.header.marginfi_account, event.amount, event.emissions_mint, event.bank
+        ),
+        LendingAccountSplitPositionEvent => |event| format!(
+            "Account {} split its liability in bank {} into account {}",
+            event.header.marginfi_account,
+            event.liability_bank,
+            event.destination_marginfi_account
+        ),
+        LendingAccountLiquidateEvent => |event| format!(
+            "Account {} liquidated account {}, seizing {} at ${:.4} against {} at ${:.4} (insurance fee: {}{}), health {:.2} -> {:.2}",
+            event.header.marginfi_account,
+            event.liquidatee_marginfi_account,
+            ctx.amount(&event.asset_bank, event.asset_amount),
+            event.asset_price,
+            ctx.amount(
+                &event.liability_bank,
+                I80F48::from(event.liability_amount_liquidator).to_num()
+            ),
+            event.liability_price,
+            ctx.amount(&event.liability_bank, I80F48::from(event.insurance_fee).to_num()),
+            if event.program_fee > 0 {
+                format!(", program fee: {}", ctx.amount(&event.asset_bank, event.program_fee))
+            } else {
+                String::new()
+            },
+            event.liquidatee_pre_health,
+            event.liquidatee_post_health
+        ),
+        LendingAccountDeleverageEvent => |event| format!(
+            "Account {} deleveraged account {}, taking over {} against {}, health ratio {:.2} -> {:.2}",
+            event.keeper_marginfi_account,
+            event.header.marginfi_account,
+            ctx.amount(&event.asset_bank, event.asset_amount),
+            ctx.amount(&event.liability_bank, event.liability_amount as u64),
+            event.pre_health_ratio,
+            event.post_health_ratio
+        ),
+        FeeStateConfigureEvent => |event| format!(
+            "Program fees configured by {}: {:?}",
+            event.signer, event.config
+        ),
+        LendingPoolSweepProgramFeesEvent => |event| format!(
+            "Bank {} program fees swept: {}, outstanding: {}",
+            event.bank, event.program_fees_swept, event.program_fees_outstanding
+        ),
+        MigrateAccountEvent => |event| format!(
+            "Account {} migrated from version {} to {}",
+            event.account, event.previous_version, event.version
+        ),
+    )
+}
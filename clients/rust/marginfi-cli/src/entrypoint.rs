@@ -1,7 +1,7 @@
 use crate::{
     config::GlobalOptions,
     processor,
-    profile::{load_profile, Profile},
+    profile::{get_cli_config_dir, load_profile, Profile},
 };
 use anchor_client::Cluster;
 use anyhow::Result;
@@ -22,7 +22,8 @@ use marginfi::{
         marginfi_group::{BankConfig, InterestRateConfig, OracleConfig, WrappedI80F48},
     },
 };
-use solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey};
+use solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey, signature::Signature};
+use std::path::PathBuf;
 
 #[cfg(feature = "dev")]
 use type_layout::TypeLayout;
@@ -60,6 +61,22 @@ pub enum Command {
         #[clap(subcommand)]
         subcmd: AccountCommand,
     },
+    Oracle {
+        #[clap(subcommand)]
+        subcmd: OracleCommand,
+    },
+    Risk {
+        #[clap(subcommand)]
+        subcmd: RiskCommand,
+    },
+    Decode {
+        #[clap(subcommand)]
+        subcmd: DecodeCommand,
+    },
+    Batch {
+        #[clap(subcommand)]
+        subcmd: BatchCommand,
+    },
     #[cfg(feature = "lip")]
     Lip {
         #[clap(subcommand)]
@@ -75,6 +92,21 @@ pub enum GroupCommand {
         marginfi_group: Option<Pubkey>,
     },
     GetAll {},
+    /// Dump the group and all its banks (with their mints, vaults and oracles) into
+    /// JSON fixtures loadable with `solana-test-validator --account` or `account import`
+    Dump {
+        marginfi_group: Option<Pubkey>,
+        #[clap(long = "out", default_value = "snapshot")]
+        out_dir: PathBuf,
+    },
+    /// Create, or extend with its new banks, the address lookup table of the group, and send
+    /// the transactions of the profile as v0 transactions using it
+    LookupTable {
+        marginfi_group: Option<Pubkey>,
+        /// Existing lookup table to extend, defaults to the one of the profile
+        #[clap(long)]
+        lookup_table: Option<Pubkey>,
+    },
     #[cfg(feature = "admin")]
     Create {
         admin: Option<Pubkey>,
@@ -84,7 +116,34 @@ pub enum GroupCommand {
     #[cfg(feature = "admin")]
     Update {
         admin: Option<Pubkey>,
+        #[clap(long, help = "Restrict liquidations to the liquidator allowlist")]
+        liquidator_allowlist_enabled: Option<bool>,
+        #[clap(
+            long = "liquidator",
+            help = "Liquidator to allow, can be repeated (replaces the current allowlist)"
+        )]
+        liquidators: Vec<Pubkey>,
+        #[clap(
+            long,
+            help = "Max USD value borrowed across the group banks, 0 to disable"
+        )]
+        total_borrow_value_limit: Option<u64>,
+        #[clap(
+            long = "referrer",
+            help = "Referrer to register, can be repeated (replaces the current referrers)"
+        )]
+        referrers: Vec<Pubkey>,
     },
+    /// Propose a new group admin, who must accept with `accept-admin`
+    #[cfg(feature = "admin")]
+    SetPendingAdmin {
+        pending_admin: Pubkey,
+    },
+    /// Accept the group admin role, signed by the pending admin
+    #[cfg(feature = "admin")]
+    AcceptAdmin {},
+    /// Add a bank to the group, writing a manifest of its derived addresses
+    /// to the profile directory
     #[cfg(feature = "admin")]
     AddBank {
         #[clap(long)]
@@ -122,10 +181,22 @@ pub enum GroupCommand {
         #[clap(long, arg_enum)]
         oracle_type: OracleTypeArg,
     },
+    /// Diff the bank configs of a TOML file against the group banks, and apply the changes
+    #[cfg(feature = "admin")]
+    ApplyConfig {
+        file: PathBuf,
+    },
     #[cfg(feature = "admin")]
     HandleBankruptcy {
         accounts: Vec<Pubkey>,
     },
+    #[cfg(feature = "admin")]
+    ForceCloseBalance {
+        #[clap(long)]
+        account: Pubkey,
+        #[clap(long)]
+        bank: Pubkey,
+    },
 }
 
 #[derive(Clone, Copy, Debug, Parser, ArgEnum)]
@@ -175,6 +246,7 @@ impl From<BankOperationalStateArg> for BankOperationalState {
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Parser)]
 pub enum BankCommand {
     Get {
@@ -183,6 +255,16 @@ pub enum BankCommand {
     GetAll {
         marginfi_group: Option<Pubkey>,
     },
+    /// Show the utilization, lending and borrowing rates, USD totals and vault balances of a bank
+    Stats {
+        bank: Pubkey,
+    },
+    /// Create a bank in the profile group, walking through its config and checking the oracle price
+    #[cfg(feature = "admin")]
+    Create {
+        #[clap(long)]
+        interactive: bool,
+    },
     #[cfg(feature = "admin")]
     Update {
         bank_pk: Pubkey,
@@ -219,6 +301,8 @@ pub enum BankCommand {
         pf_fa: Option<f64>,
         #[clap(long, help = "Protocol IR fee")]
         pf_ir: Option<f64>,
+        #[clap(long, help = "Share of the protocol IR fee paid to referrers")]
+        referral_fee_share: Option<f64>,
         #[clap(long, arg_enum, help = "Bank risk tier")]
         risk_tier: Option<RiskTierArg>,
         #[clap(long, arg_enum, help = "Bank oracle type")]
@@ -227,6 +311,33 @@ pub enum BankCommand {
         oracle_key: Option<Pubkey>,
         #[clap(long, help = "Soft USD init limit")]
         usd_init_limit: Option<u64>,
+        #[clap(long, help = "Max net outflow per window in UI units, 0 to disable")]
+        outflow_limit_ui: Option<f64>,
+        #[clap(long, help = "Outflow limit window in seconds")]
+        outflow_window: Option<u64>,
+        #[clap(
+            long,
+            help = "Tip paid to permissionless crank keepers in UI units, 0 to disable"
+        )]
+        keeper_tip_ui: Option<f64>,
+        #[clap(long, help = "Min seconds between two keeper tips")]
+        keeper_tip_interval: Option<u64>,
+        #[clap(long, help = "Borrow origination fee, in bps of the amount borrowed")]
+        origination_fee_bps: Option<u64>,
+        #[clap(long, help = "Min accepted oracle price in USD, 0 to disable")]
+        oracle_price_floor: Option<f64>,
+        #[clap(long, help = "Max accepted oracle price in USD, 0 to disable")]
+        oracle_price_ceiling: Option<f64>,
+        #[clap(
+            long,
+            help = "Share of the liquidated collateral value kept by liquidators"
+        )]
+        liquidation_liquidator_fee: Option<f64>,
+        #[clap(
+            long,
+            help = "Share of the liquidated collateral value paid to the insurance fund"
+        )]
+        liquidation_insurance_fee: Option<f64>,
     },
     #[cfg(feature = "dev")]
     InspectPriceOracle {
@@ -259,11 +370,40 @@ pub enum BankCommand {
         rate: Option<f64>,
         #[clap(long)]
         additional_amount_ui: Option<f64>,
+        #[clap(long, help = "Unix timestamp emissions end at, 0 removes the end time")]
+        end_timestamp: Option<u64>,
+    },
+    /// Upgrade a bank to its latest layout version
+    Migrate {
+        bank: Pubkey,
+    },
+    /// Fund the emissions of a bank from the signer token account, extending their end time
+    FundEmissions {
+        bank: Pubkey,
+        #[clap(long)]
+        amount_ui: f64,
     },
     #[cfg(feature = "admin")]
     SettleAllEmissions {
         bank: Pubkey,
     },
+    /// Long running crank accruing interest on banks, through a persistent retry queue
+    CrankInterest {
+        #[clap(help = "Banks to crank, defaults to all banks of the profile group")]
+        banks: Vec<Pubkey>,
+        #[clap(long, default_value_t = 3600, value_parser = clap::value_parser!(u64).range(1..))]
+        interval_secs: u64,
+        #[clap(
+            long,
+            help = "Retry queue location, defaults to the CLI config directory"
+        )]
+        queue_path: Option<PathBuf>,
+        #[clap(
+            long,
+            help = "Collect keeper tips into the authority associated token accounts"
+        )]
+        collect_tips: bool,
+    },
 }
 
 #[derive(Debug, Parser)]
@@ -273,6 +413,7 @@ pub enum ProfileCommand {
         name: String,
         #[clap(long)]
         cluster: Cluster,
+        /// Keypair file, or hardware wallet URI like `usb://ledger?key=0/0`.
         #[clap(long)]
         keypair_path: Option<String>,
         #[clap(long)]
@@ -297,6 +438,7 @@ pub enum ProfileCommand {
         name: String,
         #[clap(long)]
         cluster: Option<Cluster>,
+        /// Keypair file, or hardware wallet URI like `usb://ledger?key=0/0`.
         #[clap(long)]
         keypair_path: Option<String>,
         #[clap(long)]
@@ -323,6 +465,20 @@ pub enum AccountCommand {
     Get {
         account: Option<Pubkey>,
     },
+    /// Print the balances of an account with their USD value and weights, and its health
+    Show {
+        account: Option<Pubkey>,
+    },
+    /// Follow the health of an account over websocket, alerting when it crosses the threshold
+    Watch {
+        account: Option<Pubkey>,
+        /// Maintenance health factor (weighted assets / weighted liabilities) to alert on
+        #[clap(long, default_value = "1.1")]
+        threshold: f64,
+        /// URL the alerts are posted to, as JSON
+        #[clap(long)]
+        webhook_url: Option<String>,
+    },
     Deposit {
         bank: Pubkey,
         ui_amount: f64,
@@ -337,17 +493,139 @@ pub enum AccountCommand {
         bank: Pubkey,
         ui_amount: f64,
     },
+    /// Liquidate an unhealthy account, seizing `amount` of its assets in the asset bank
     Liquidate {
-        #[clap(long)]
+        #[clap(long = "liquidatee", alias = "liquidatee-marginfi-account")]
         liquidatee_marginfi_account: Pubkey,
         #[clap(long)]
         asset_bank: Pubkey,
         #[clap(long)]
         liability_bank: Pubkey,
+        /// Amount of the asset to seize, in UI units
+        #[clap(long = "amount", alias = "ui-asset-amount")]
+        ui_asset_amount: f64,
+    },
+    /// Allow a keeper to deleverage the account below a maintenance health ratio, or disallow it
+    AutoDeleverage {
+        #[clap(long, required_unless_present = "disable")]
+        keeper: Option<Pubkey>,
+        /// Maintenance health ratio (weighted assets / weighted liabilities) below which the
+        /// keeper can deleverage the account
+        #[clap(long, default_value = "1.05")]
+        health_buffer: f64,
+        #[clap(long, conflicts_with = "keeper")]
+        disable: bool,
+    },
+    /// As the keeper of an account, repay part of its liability with `amount` of its assets,
+    /// taken over by the profile account
+    Deleverage {
+        #[clap(long)]
+        account: Pubkey,
+        #[clap(long)]
+        asset_bank: Pubkey,
         #[clap(long)]
+        liability_bank: Pubkey,
+        /// Amount of the asset to take over, in UI units
+        #[clap(long = "amount")]
         ui_asset_amount: f64,
     },
-    Create,
+    /// Upgrade an account, the profile account by default, to its latest layout version
+    Migrate {
+        #[clap(long)]
+        account: Option<Pubkey>,
+    },
+    Create {
+        /// Create the next sub-account of the authority, at an address derived from the group,
+        /// the authority and its index, instead of a new keypair
+        #[clap(long)]
+        sub_account: bool,
+        /// Referrer of the account, earning a share of the protocol fees on its liabilities
+        #[clap(long)]
+        referrer: Option<Pubkey>,
+    },
+    /// Show the outstanding emissions of the account and withdraw them to the authority's ATAs
+    ClaimEmissions {
+        /// Bank to claim the emissions of
+        bank: Option<Pubkey>,
+        /// Claim the emissions of every balance
+        #[clap(long, conflicts_with = "bank")]
+        all: bool,
+    },
+    /// Rebuild the ledger of deposits, borrows, liquidations and emissions claimed of an account
+    /// from its transactions, with the interest earned or paid in each bank
+    History {
+        account: Option<Pubkey>,
+        /// First slot of the history, 0 for the whole history
+        #[clap(long = "from", default_value = "0")]
+        from_slot: u64,
+        /// Also write the ledger entries to this CSV file
+        #[clap(long)]
+        csv: Option<PathBuf>,
+    },
+    /// Dump an account, its group and banks (with their mints, vaults and oracles) into JSON fixtures
+    Export {
+        account: Option<Pubkey>,
+        #[clap(long, default_value = "snapshot")]
+        out_dir: PathBuf,
+    },
+    /// Load exported fixtures into the localnet harness
+    Import {
+        dir: PathBuf,
+        /// Append the fixtures to the validator accounts of this Anchor.toml,
+        /// instead of printing the `solana-test-validator` flags
+        #[clap(long)]
+        anchor_toml: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub enum OracleCommand {
+    /// Check the freshness and confidence of every oracle used by the group's banks
+    Status {
+        #[clap(long)]
+        marginfi_group: Option<Pubkey>,
+        /// Reference price API, `{mint}` is replaced with the bank mint
+        /// and the first `price` field of the JSON response is used
+        #[clap(long)]
+        reference_url: Option<String>,
+        #[clap(long, default_value = "0.05")]
+        max_confidence_ratio: f64,
+        #[clap(long, default_value = "0.02")]
+        max_reference_deviation: f64,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub enum DecodeCommand {
+    /// Summarize the marginfi and LIP instructions and events of a transaction
+    Tx {
+        /// Without a signature, a transaction printed in multisig mode is read from stdin
+        signature: Option<Signature>,
+    },
+}
+
+#[derive(Debug, Parser)]
+pub enum BatchCommand {
+    /// Run the deposits, withdrawals and bank config changes of a YAML or JSON file, in order,
+    /// packed in as few transactions as possible
+    Run { file: PathBuf },
+}
+
+#[derive(Debug, Parser)]
+pub enum RiskCommand {
+    /// List the accounts below a maintenance health factor, sorted by seizable value
+    Scan {
+        #[clap(long)]
+        marginfi_group: Option<Pubkey>,
+        #[clap(long, default_value = "1.0")]
+        max_health_factor: f64,
+        /// Accounts loaded per `getMultipleAccounts` request, at most 100
+        #[clap(long, default_value = "100")]
+        page_size: usize,
+        /// Max number of accounts printed
+        #[clap(long)]
+        limit: Option<usize>,
+    },
 }
 
 #[derive(Debug, Parser)]
@@ -355,6 +633,41 @@ pub enum AccountCommand {
 pub enum LipCommand {
     ListCampaigns,
     ListDeposits,
+    /// Deactivate a campaign, sweeping leftover rewards once all deposits are closed
+    EndCampaign {
+        campaign: Pubkey,
+    },
+    /// Recompute the deposits exited from an ended campaign from its open deposits, so that its leftover rewards can be swept
+    ReconcileCampaign {
+        campaign: Pubkey,
+    },
+    /// Halt new deposits into a campaign, outstanding deposits can still be closed
+    PauseCampaign {
+        campaign: Pubkey,
+    },
+    /// Resume the deposits of a paused campaign
+    ResumeCampaign {
+        campaign: Pubkey,
+    },
+    /// Top up the rewards of an active campaign, optionally raising its capacity or extending its lockup
+    UpdateCampaign {
+        campaign: Pubkey,
+        #[clap(
+            long,
+            default_value = "0",
+            help = "Reward tokens to add to the vault (UI amount)"
+        )]
+        additional_rewards: f64,
+        #[clap(long, help = "New total deposit capacity (UI amount)")]
+        max_deposits: Option<f64>,
+        #[clap(long, help = "New lockup period in days")]
+        lockup_period_days: Option<u64>,
+    },
+    /// Transfer a deposit owned by the authority to a new wallet
+    TransferDeposit {
+        deposit: Pubkey,
+        new_owner: Pubkey,
+    },
 }
 
 pub fn entry(opts: Opts) -> Result<()> {
@@ -367,6 +680,10 @@ pub fn entry(opts: Opts) -> Result<()> {
         #[cfg(feature = "dev")]
         Command::InspectPadding {} => inspect_padding(),
         Command::Account { subcmd } => process_account_subcmd(subcmd, &opts.cfg_override),
+        Command::Oracle { subcmd } => process_oracle_subcmd(subcmd, &opts.cfg_override),
+        Command::Risk { subcmd } => process_risk_subcmd(subcmd, &opts.cfg_override),
+        Command::Decode { subcmd } => process_decode_subcmd(subcmd, &opts.cfg_override),
+        Command::Batch { subcmd } => process_batch_subcmd(subcmd, &opts.cfg_override),
         #[cfg(feature = "lip")]
         Command::Lip { subcmd } => process_lip_subcmd(subcmd, &opts.cfg_override),
         #[cfg(feature = "dev")]
@@ -441,6 +758,11 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
         match subcmd {
             GroupCommand::Get { marginfi_group: _ } => (),
             GroupCommand::GetAll {} => (),
+            GroupCommand::Dump { .. } => (),
+            GroupCommand::LookupTable { .. } => get_consent(&subcmd, &profile)?,
+            // Asks for consent once the diff is printed
+            #[cfg(feature = "admin")]
+            GroupCommand::ApplyConfig { .. } => (),
             #[cfg(feature = "admin")]
             _ => get_consent(&subcmd, &profile)?,
         }
@@ -451,13 +773,54 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
             processor::group_get(config, marginfi_group.or(profile.marginfi_group))
         }
         GroupCommand::GetAll {} => processor::group_get_all(config),
+        GroupCommand::Dump {
+            marginfi_group,
+            out_dir,
+        } => processor::snapshot::marginfi_group_dump(
+            &config,
+            marginfi_group
+                .or(profile.marginfi_group)
+                .expect("marginfi group not set"),
+            out_dir,
+        ),
+        GroupCommand::LookupTable {
+            marginfi_group,
+            lookup_table,
+        } => processor::lookup_table::group_update_lookup_table(
+            &profile,
+            &config,
+            marginfi_group
+                .or(profile.marginfi_group)
+                .expect("marginfi group not set"),
+            lookup_table,
+        ),
         #[cfg(feature = "admin")]
         GroupCommand::Create {
             admin,
             override_existing_profile_group,
         } => processor::group_create(config, profile, admin, override_existing_profile_group),
         #[cfg(feature = "admin")]
-        GroupCommand::Update { admin } => processor::group_configure(config, profile, admin),
+        GroupCommand::Update {
+            admin,
+            liquidator_allowlist_enabled,
+            liquidators,
+            total_borrow_value_limit,
+            referrers,
+        } => processor::group_configure(
+            config,
+            profile,
+            admin,
+            liquidator_allowlist_enabled,
+            liquidators,
+            total_borrow_value_limit,
+            referrers,
+        ),
+        #[cfg(feature = "admin")]
+        GroupCommand::SetPendingAdmin { pending_admin } => {
+            processor::group_set_pending_admin(config, profile, pending_admin)
+        }
+        #[cfg(feature = "admin")]
+        GroupCommand::AcceptAdmin {} => processor::group_accept_admin(config, profile),
         #[cfg(feature = "admin")]
         GroupCommand::AddBank {
             mint: bank_mint,
@@ -499,9 +862,25 @@ fn group(subcmd: GroupCommand, global_options: &GlobalOptions) -> Result<()> {
             risk_tier,
         ),
         #[cfg(feature = "admin")]
+        GroupCommand::ApplyConfig { ref file } => {
+            let marginfi_group = profile.marginfi_group.expect("marginfi group not set");
+            let bank_changes =
+                processor::bank_config::diff_group_config(&config, marginfi_group, file)?;
+
+            if !bank_changes.is_empty() && !global_options.skip_confirmation {
+                get_consent(&subcmd, &profile)?;
+            }
+
+            processor::bank_config::apply_group_config(&config, marginfi_group, bank_changes)
+        }
+        #[cfg(feature = "admin")]
         GroupCommand::HandleBankruptcy { accounts } => {
             processor::handle_bankruptcy_for_accounts(&config, &profile, accounts)
         }
+        #[cfg(feature = "admin")]
+        GroupCommand::ForceCloseBalance { account, bank } => {
+            processor::group_force_close_balance(&config, &profile, account, bank)
+        }
     }
 }
 
@@ -511,9 +890,12 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
 
     if !global_options.skip_confirmation {
         match subcmd {
-            BankCommand::Get { .. } | BankCommand::GetAll { .. } => (),
+            BankCommand::Get { .. } | BankCommand::GetAll { .. } | BankCommand::Stats { .. } => (),
             #[cfg(feature = "dev")]
             BankCommand::InspectPriceOracle { .. } => (),
+            // The wizard asks for confirmation once the config is known
+            #[cfg(feature = "admin")]
+            BankCommand::Create { .. } => (),
             #[allow(unreachable_patterns)]
             _ => get_consent(&subcmd, &profile)?,
         }
@@ -522,6 +904,17 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
     match subcmd {
         BankCommand::Get { bank } => processor::bank_get(config, bank),
         BankCommand::GetAll { marginfi_group } => processor::bank_get_all(config, marginfi_group),
+        BankCommand::Stats { bank } => processor::bank_stats(&config, bank),
+        #[cfg(feature = "admin")]
+        BankCommand::Create { interactive } => {
+            if !interactive {
+                anyhow::bail!(
+                    "Only --interactive is supported, see `group add-bank` to pass the config as arguments"
+                );
+            }
+
+            processor::bank_wizard::bank_create_interactive(config, profile)
+        }
         #[cfg(feature = "admin")]
         BankCommand::Update {
             asset_weight_init,
@@ -539,10 +932,20 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
             if_ir,
             pf_fa,
             pf_ir,
+            referral_fee_share,
             risk_tier,
             oracle_type,
             oracle_key,
             usd_init_limit,
+            outflow_limit_ui,
+            outflow_window,
+            keeper_tip_ui,
+            keeper_tip_interval,
+            origination_fee_bps,
+            oracle_price_floor,
+            oracle_price_ceiling,
+            liquidation_liquidator_fee,
+            liquidation_insurance_fee,
         } => {
             let bank = config
                 .mfi_program
@@ -586,9 +989,25 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
                         insurance_ir_fee: if_ir.map(|x| I80F48::from_num(x).into()),
                         protocol_fixed_fee_apr: pf_fa.map(|x| I80F48::from_num(x).into()),
                         protocol_ir_fee: pf_ir.map(|x| I80F48::from_num(x).into()),
+                        referral_fee_share: referral_fee_share.map(|x| I80F48::from_num(x).into()),
                     }),
                     risk_tier: risk_tier.map(|x| x.into()),
                     total_asset_value_init_limit: usd_init_limit,
+                    outflow_limit: outflow_limit_ui.map(|ui_amount| {
+                        spl_token::ui_amount_to_amount(ui_amount, bank.mint_decimals)
+                    }),
+                    outflow_window,
+                    keeper_tip: keeper_tip_ui.map(|ui_amount| {
+                        spl_token::ui_amount_to_amount(ui_amount, bank.mint_decimals)
+                    }),
+                    keeper_tip_interval,
+                    origination_fee_bps,
+                    oracle_price_floor: oracle_price_floor.map(|x| I80F48::from_num(x).into()),
+                    oracle_price_ceiling: oracle_price_ceiling.map(|x| I80F48::from_num(x).into()),
+                    liquidation_liquidator_fee: liquidation_liquidator_fee
+                        .map(|x| I80F48::from_num(x).into()),
+                    liquidation_insurance_fee: liquidation_insurance_fee
+                        .map(|x| I80F48::from_num(x).into()),
                 },
             )
         }
@@ -615,6 +1034,7 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
             disable,
             rate,
             additional_amount_ui,
+            end_timestamp,
         } => processor::bank_update_emissions(
             &config,
             &profile,
@@ -624,11 +1044,29 @@ fn bank(subcmd: BankCommand, global_options: &GlobalOptions) -> Result<()> {
             disable,
             rate,
             additional_amount_ui,
+            end_timestamp,
         ),
+        BankCommand::Migrate { bank } => processor::migrate_account(&config, bank),
+        BankCommand::FundEmissions { bank, amount_ui } => {
+            processor::bank_fund_emissions(&config, &profile, bank, amount_ui)
+        }
         #[cfg(feature = "admin")]
         BankCommand::SettleAllEmissions { bank } => {
             processor::emissions::claim_all_emissions_for_bank(&config, &profile, bank)
         }
+        BankCommand::CrankInterest {
+            banks,
+            interval_secs,
+            queue_path,
+            collect_tips,
+        } => processor::crank::crank_accrue_interest(
+            &config,
+            &profile,
+            banks,
+            interval_secs,
+            &queue_path.unwrap_or_else(|| get_cli_config_dir().join("retry-queue")),
+            collect_tips,
+        ),
     }
 }
 
@@ -682,7 +1120,13 @@ fn process_account_subcmd(subcmd: AccountCommand, global_options: &GlobalOptions
 
     if !global_options.skip_confirmation {
         match subcmd {
-            AccountCommand::Get { .. } | AccountCommand::List => (),
+            AccountCommand::Get { .. }
+            | AccountCommand::Show { .. }
+            | AccountCommand::Watch { .. }
+            | AccountCommand::History { .. }
+            | AccountCommand::List
+            | AccountCommand::Export { .. }
+            | AccountCommand::Import { .. } => (),
             _ => get_consent(&subcmd, &profile)?,
         }
     }
@@ -695,6 +1139,20 @@ fn process_account_subcmd(subcmd: AccountCommand, global_options: &GlobalOptions
         AccountCommand::Get { account } => {
             processor::marginfi_account_get(profile, &config, account)
         }
+        AccountCommand::Show { account } => {
+            processor::health::marginfi_account_show(&profile, &config, account)
+        }
+        AccountCommand::Watch {
+            account,
+            threshold,
+            webhook_url,
+        } => processor::health::marginfi_account_watch(
+            &profile,
+            &config,
+            account,
+            threshold,
+            webhook_url,
+        ),
         AccountCommand::Deposit { bank, ui_amount } => {
             processor::marginfi_account_deposit(&profile, &config, bank, ui_amount)
         }
@@ -719,12 +1177,134 @@ fn process_account_subcmd(subcmd: AccountCommand, global_options: &GlobalOptions
             liability_bank_pk,
             ui_asset_amount,
         ),
-        AccountCommand::Create => processor::marginfi_account_create(&profile, &config),
+        AccountCommand::AutoDeleverage {
+            keeper,
+            health_buffer,
+            disable,
+        } => processor::marginfi_account_configure_auto_deleverage(
+            &profile,
+            &config,
+            keeper.filter(|_| !disable),
+            health_buffer,
+        ),
+        AccountCommand::Deleverage {
+            account,
+            asset_bank,
+            liability_bank,
+            ui_asset_amount,
+        } => processor::marginfi_account_deleverage(
+            &profile,
+            &config,
+            account,
+            asset_bank,
+            liability_bank,
+            ui_asset_amount,
+        ),
+        AccountCommand::Migrate { account } => processor::migrate_account(
+            &config,
+            account.unwrap_or_else(|| profile.get_marginfi_account()),
+        ),
+        AccountCommand::Create {
+            sub_account,
+            referrer,
+        } => processor::marginfi_account_create(&profile, &config, sub_account, referrer),
+        AccountCommand::ClaimEmissions { bank, all } => {
+            processor::marginfi_account_claim_emissions(&profile, &config, bank, all)
+        }
+        AccountCommand::History {
+            account,
+            from_slot,
+            csv,
+        } => processor::history::marginfi_account_history(
+            &profile,
+            &config,
+            account,
+            from_slot,
+            csv.as_deref(),
+        ),
+        AccountCommand::Export { account, out_dir } => {
+            processor::snapshot::marginfi_account_export(&profile, &config, account, out_dir)
+        }
+        AccountCommand::Import { dir, anchor_toml } => {
+            processor::snapshot::marginfi_account_import(dir, anchor_toml)
+        }
     }?;
 
     Ok(())
 }
 
+fn process_oracle_subcmd(subcmd: OracleCommand, global_options: &GlobalOptions) -> Result<()> {
+    let profile = load_profile()?;
+    let config = profile.get_config(Some(global_options))?;
+
+    match subcmd {
+        OracleCommand::Status {
+            marginfi_group,
+            reference_url,
+            max_confidence_ratio,
+            max_reference_deviation,
+        } => processor::oracle::oracle_status(
+            &config,
+            marginfi_group
+                .or(profile.marginfi_group)
+                .expect("marginfi group not set"),
+            reference_url,
+            processor::oracle::OracleStatusThresholds {
+                max_confidence_ratio,
+                max_reference_deviation,
+            },
+        ),
+    }
+}
+
+fn process_risk_subcmd(subcmd: RiskCommand, global_options: &GlobalOptions) -> Result<()> {
+    let profile = load_profile()?;
+    let config = profile.get_config(Some(global_options))?;
+
+    match subcmd {
+        RiskCommand::Scan {
+            marginfi_group,
+            max_health_factor,
+            page_size,
+            limit,
+        } => processor::health::risk_scan(
+            &config,
+            marginfi_group
+                .or(profile.marginfi_group)
+                .expect("marginfi group not set"),
+            max_health_factor,
+            page_size,
+            limit,
+        ),
+    }
+}
+
+fn process_decode_subcmd(subcmd: DecodeCommand, global_options: &GlobalOptions) -> Result<()> {
+    let profile = load_profile()?;
+    let config = profile.get_config(Some(global_options))?;
+
+    match subcmd {
+        DecodeCommand::Tx { signature } => processor::decode_transaction(&config, signature),
+    }
+}
+
+fn process_batch_subcmd(subcmd: BatchCommand, global_options: &GlobalOptions) -> Result<()> {
+    let profile = load_profile()?;
+    let config = profile.get_config(Some(global_options))?;
+
+    match subcmd {
+        BatchCommand::Run { ref file } => {
+            let plan = processor::batch::plan_batch(&profile, &config, file)?;
+
+            if !global_options.dry_run && !global_options.skip_confirmation {
+                get_consent(&subcmd, &profile)?;
+            }
+
+            processor::batch::run_batch(&config, plan)
+        }
+    }
+}
+
 #[cfg(feature = "lip")]
 fn process_lip_subcmd(
     subcmd: LipCommand,
@@ -736,6 +1316,33 @@ fn process_lip_subcmd(
     match subcmd {
         LipCommand::ListCampaigns => processor::process_list_lip_campaigns(&config),
         LipCommand::ListDeposits => processor::process_list_deposits(&config),
+        LipCommand::EndCampaign { campaign } => {
+            processor::process_end_lip_campaign(&config, campaign)?
+        }
+        LipCommand::ReconcileCampaign { campaign } => {
+            processor::process_reconcile_lip_campaign(&config, campaign)?
+        }
+        LipCommand::PauseCampaign { campaign } => {
+            processor::process_set_lip_campaign_paused(&config, campaign, true)?
+        }
+        LipCommand::ResumeCampaign { campaign } => {
+            processor::process_set_lip_campaign_paused(&config, campaign, false)?
+        }
+        LipCommand::UpdateCampaign {
+            campaign,
+            additional_rewards,
+            max_deposits,
+            lockup_period_days,
+        } => processor::process_update_lip_campaign(
+            &config,
+            campaign,
+            additional_rewards,
+            max_deposits,
+            lockup_period_days,
+        )?,
+        LipCommand::TransferDeposit { deposit, new_owner } => {
+            processor::process_transfer_lip_deposit(&config, deposit, new_owner)?
+        }
     }
 
     Ok(())
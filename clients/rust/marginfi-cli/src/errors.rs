@@ -0,0 +1,451 @@
+use {
+    liquidity_incentive_program::errors::LIPError,
+    marginfi::errors::MarginfiError,
+    num_traits::FromPrimitive,
+    solana_client::{
+        client_error::{ClientError as RpcClientError, ClientErrorKind},
+        rpc_request::{RpcError, RpcResponseErrorData},
+    },
+    solana_sdk::{
+        instruction::InstructionError,
+        pubkey::Pubkey,
+        system_instruction::SystemError,
+        system_program,
+        transaction::{Transaction, TransactionError, VersionedTransaction},
+    },
+    spl_token::error::TokenError,
+    std::fmt::{Display, Formatter},
+    thiserror::Error,
+};
+
+/// Anchor programs start their custom error codes at this offset,
+/// lower codes are anchor framework errors (account constraints, deserialization, ...).
+const ANCHOR_ERROR_CODE_OFFSET: u32 = 6000;
+
+const ANCHOR_ERROR_ACCOUNT_LOG_PREFIX: &str = "AnchorError caused by account: ";
+
+/// Lists every variant of an anchor `#[error_code]` enum, to map error codes back to variants.
+/// Keep in sync with the program error enums.
+macro_rules! error_from_code {
+    ($code:expr, $error:ident, [$($variant:ident),* $(,)?]) => {{
+        let code = $code;
+        $(
+            if code == u32::from($error::$variant) {
+                return Some($error::$variant);
+            }
+        )*
+        None
+    }};
+}
+
+pub fn marginfi_error_from_code(code: u32) -> Option<MarginfiError> {
+    error_from_code!(
+        code,
+        MarginfiError,
+        [
+            MathError,
+            BankNotFound,
+            LendingAccountBalanceNotFound,
+            BankAssetCapacityExceeded,
+            InvalidTransfer,
+            MissingPythOrBankAccount,
+            MissingPythAccount,
+            InvalidOracleAccount,
+            MissingBankAccount,
+            InvalidBankAccount,
+            BadAccountHealth,
+            LendingAccountBalanceSlotsFull,
+            BankAlreadyExists,
+            IllegalLiquidation,
+            AccountNotBankrupt,
+            BalanceNotBadDebt,
+            InvalidConfig,
+            StaleOracle,
+            BankPaused,
+            BankReduceOnly,
+            BankAccoutNotFound,
+            OperationDepositOnly,
+            OperationWithdrawOnly,
+            OperationBorrowOnly,
+            OperationRepayOnly,
+            NoAssetFound,
+            NoLiabilityFound,
+            InvalidOracleSetup,
+            IllegalUtilizationRatio,
+            BankLiabilityCapacityExceeded,
+            InvalidPrice,
+            IsolatedAccountIllegalState,
+            EmissionsAlreadySetup,
+            OracleNotSetup,
+            InvalidSwitchboardDecimalConversion,
+            CannotCloseOutstandingEmissions,
+            EmissionsUpdateError,
+            AccountDisabled,
+            AccountTempActiveBalanceLimitExceeded,
+            IllegalBalanceState,
+            LiquidatorNotAllowed,
+            ReceiptMintNotSetup,
+            ReceiptMintAlreadySetup,
+            InvalidNativeSolOperation,
+            InvalidEmissionsDestinationAccount,
+            BalanceNotEmpty,
+            OutflowLimitExceeded,
+            InvalidPendingAdmin,
+            InvalidKeeperTipAccounts,
+            InvalidPositionSplit,
+            InvalidSubAccountIndex,
+            GroupBorrowValueLimitExceeded,
+            DeleverageNotAllowed,
+            IllegalDeleverage,
+            InvalidDeleverageHealthBuffer,
+            OraclePriceOutOfBand,
+            AccountMigrationError,
+            Unauthorized,
+            InvalidGlobalFeeWallet,
+            InvalidReferrer,
+        ]
+    )
+}
+
+pub fn lip_error_from_code(code: u32) -> Option<LIPError> {
+    error_from_code!(
+        code,
+        LIPError,
+        [
+            CampaignNotActive,
+            DepositAmountTooLarge,
+            DepositNotMature,
+            InvalidCampaignParameters,
+            CampaignAprOutOfBounds,
+            DepositAlreadyMature,
+            UserDepositCapExceeded,
+            CampaignRewardsBelowCommitted,
+            InvalidLockupTier,
+            MissingEmissionsAccounts,
+            CampaignPaused,
+            DepositDeadlinePassed,
+        ]
+    )
+}
+
+/// Custom error returned by a program, decoded from its error code.
+#[derive(Debug, Clone)]
+pub enum ProgramFailure {
+    Marginfi(MarginfiError),
+    Lip(LIPError),
+    Token(TokenError),
+    System(SystemError),
+    /// Anchor framework error of the marginfi or LIP program.
+    Anchor(u32),
+    /// Code not known for the failing program.
+    Unknown(u32),
+}
+
+impl ProgramFailure {
+    /// Decode a custom error `code` returned by `program_id`.
+    pub fn from_code(program_id: Option<&Pubkey>, code: u32) -> Self {
+        let program_id = match program_id {
+            Some(program_id) => program_id,
+            None => return Self::Unknown(code),
+        };
+
+        if *program_id == marginfi::id() || *program_id == liquidity_incentive_program::id() {
+            if code < ANCHOR_ERROR_CODE_OFFSET {
+                return Self::Anchor(code);
+            }
+
+            let failure = if *program_id == marginfi::id() {
+                marginfi_error_from_code(code).map(Self::Marginfi)
+            } else {
+                lip_error_from_code(code).map(Self::Lip)
+            };
+
+            return failure.unwrap_or(Self::Unknown(code));
+        }
+
+        if *program_id == spl_token::id() {
+            return TokenError::from_u32(code).map_or(Self::Unknown(code), Self::Token);
+        }
+
+        if *program_id == system_program::id() {
+            return SystemError::from_u32(code).map_or(Self::Unknown(code), Self::System);
+        }
+
+        Self::Unknown(code)
+    }
+
+    /// Program error code, `from_code(program_id, failure.code())` gives back the same failure.
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::Marginfi(error) => u32::from(*error),
+            Self::Lip(error) => u32::from(*error),
+            Self::Token(error) => error.clone() as u32,
+            Self::System(error) => error.clone() as u32,
+            Self::Anchor(code) | Self::Unknown(code) => *code,
+        }
+    }
+}
+
+impl Display for ProgramFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Marginfi(error) => write!(f, "{} ({})", error.name(), error),
+            Self::Lip(error) => write!(f, "{} ({})", error.name(), error),
+            Self::Token(error) => write!(f, "token error: {}", error),
+            Self::System(error) => write!(f, "system error: {}", error),
+            Self::Anchor(code) => write!(f, "anchor error {}", code),
+            Self::Unknown(code) => write!(f, "custom program error {:#x}", code),
+        }
+    }
+}
+
+/// Transaction failure, decoded down to the program error of the failing instruction when possible.
+///
+/// `process_transaction` returns these wrapped in `anyhow::Error`, callers can branch on them
+/// with `err.downcast_ref::<ClientError>()`.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error(
+        "instruction {instruction_index} ({}) failed: {failure}{}",
+        fmt_program_id(program_id),
+        fmt_account(account)
+    )]
+    Program {
+        instruction_index: u8,
+        program_id: Option<Pubkey>,
+        failure: ProgramFailure,
+        /// Account named in the anchor error log, if any.
+        account: Option<String>,
+    },
+    #[error(
+        "instruction {instruction_index} ({}) failed: {error}",
+        fmt_program_id(program_id)
+    )]
+    Instruction {
+        instruction_index: u8,
+        program_id: Option<Pubkey>,
+        error: InstructionError,
+    },
+    #[error("transaction failed: {0}")]
+    Transaction(TransactionError),
+    #[error(transparent)]
+    Rpc(Box<RpcClientError>),
+}
+
+/// Transactions whose failed instruction can be mapped back to its program.
+pub trait InstructionProgramId {
+    fn instruction_program_id(&self, instruction_index: u8) -> Option<Pubkey>;
+}
+
+impl InstructionProgramId for Transaction {
+    fn instruction_program_id(&self, instruction_index: u8) -> Option<Pubkey> {
+        self.message
+            .instructions
+            .get(instruction_index as usize)
+            .map(|ix| *ix.program_id(&self.message.account_keys))
+    }
+}
+
+impl InstructionProgramId for VersionedTransaction {
+    fn instruction_program_id(&self, instruction_index: u8) -> Option<Pubkey> {
+        // Programs can't be loaded from lookup tables, they are always static keys
+        self.message
+            .instructions()
+            .get(instruction_index as usize)
+            .and_then(|ix| {
+                self.message
+                    .static_account_keys()
+                    .get(ix.program_id_index as usize)
+            })
+            .copied()
+    }
+}
+
+impl ClientError {
+    /// Decode an rpc error returned while sending or simulating `transaction`.
+    pub fn from_rpc_error(
+        err: RpcClientError,
+        transaction: Option<&dyn InstructionProgramId>,
+    ) -> Self {
+        let (tx_error, logs) = match err.kind() {
+            ClientErrorKind::TransactionError(tx_error) => (tx_error.clone(), None),
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+                ..
+            }) => match result.err.clone() {
+                Some(tx_error) => (tx_error, result.logs.clone()),
+                None => return Self::Rpc(Box::new(err)),
+            },
+            _ => return Self::Rpc(Box::new(err)),
+        };
+
+        Self::from_transaction_error(tx_error, transaction, logs.as_deref())
+    }
+
+    pub fn from_transaction_error(
+        tx_error: TransactionError,
+        transaction: Option<&dyn InstructionProgramId>,
+        logs: Option<&[String]>,
+    ) -> Self {
+        let (instruction_index, instruction_error) = match tx_error {
+            TransactionError::InstructionError(instruction_index, instruction_error) => {
+                (instruction_index, instruction_error)
+            }
+            tx_error => return Self::Transaction(tx_error),
+        };
+
+        let program_id = transaction.and_then(|tx| tx.instruction_program_id(instruction_index));
+
+        match instruction_error {
+            InstructionError::Custom(code) => Self::Program {
+                instruction_index,
+                program_id,
+                failure: ProgramFailure::from_code(program_id.as_ref(), code),
+                account: logs.and_then(find_error_account),
+            },
+            error => Self::Instruction {
+                instruction_index,
+                program_id,
+                error,
+            },
+        }
+    }
+
+    pub fn marginfi_error(&self) -> Option<MarginfiError> {
+        match self {
+            Self::Program {
+                failure: ProgramFailure::Marginfi(error),
+                ..
+            } => Some(*error),
+            _ => None,
+        }
+    }
+
+    pub fn lip_error(&self) -> Option<LIPError> {
+        match self {
+            Self::Program {
+                failure: ProgramFailure::Lip(error),
+                ..
+            } => Some(*error),
+            _ => None,
+        }
+    }
+
+    /// Check whether the transaction failed with the marginfi error `error`.
+    pub fn is_marginfi_error(&self, error: MarginfiError) -> bool {
+        self.marginfi_error()
+            .map_or(false, |e| u32::from(e) == u32::from(error))
+    }
+
+    pub fn instruction_index(&self) -> Option<u8> {
+        match self {
+            Self::Program {
+                instruction_index, ..
+            }
+            | Self::Instruction {
+                instruction_index, ..
+            } => Some(*instruction_index),
+            _ => None,
+        }
+    }
+}
+
+/// Account named by the last anchor error log line, e.g.
+/// `AnchorError caused by account: bank. Error Code: ConstraintHasOne. ...`.
+fn find_error_account(logs: &[String]) -> Option<String> {
+    logs.iter().rev().find_map(|log| {
+        let (_, rest) = log.split_once(ANCHOR_ERROR_ACCOUNT_LOG_PREFIX)?;
+        rest.split('.').next().map(|account| account.to_string())
+    })
+}
+
+fn fmt_program_id(program_id: &Option<Pubkey>) -> String {
+    program_id.map_or("unknown program".to_string(), |id| id.to_string())
+}
+
+fn fmt_account(account: &Option<String>) -> String {
+    account
+        .as_ref()
+        .map_or(String::new(), |account| format!(", account: {}", account))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marginfi_error_code_round_trip() {
+        let code = u32::from(MarginfiError::BadAccountHealth);
+        let failure = ProgramFailure::from_code(Some(&marginfi::id()), code);
+
+        assert!(matches!(
+            failure,
+            ProgramFailure::Marginfi(MarginfiError::BadAccountHealth)
+        ));
+        assert_eq!(failure.code(), code);
+
+        // Codes are contiguous, every code up to the last variant decodes
+        let last_code = u32::from(MarginfiError::InvalidReferrer);
+        for code in u32::from(MarginfiError::MathError)..=last_code {
+            assert_eq!(marginfi_error_from_code(code).map(u32::from), Some(code));
+        }
+        assert!(marginfi_error_from_code(last_code + 1).is_none());
+    }
+
+    #[test]
+    fn lip_and_native_error_code_round_trip() {
+        let code = u32::from(LIPError::DepositNotMature);
+        let failure = ProgramFailure::from_code(Some(&liquidity_incentive_program::id()), code);
+        assert!(matches!(
+            failure,
+            ProgramFailure::Lip(LIPError::DepositNotMature)
+        ));
+        assert_eq!(failure.code(), code);
+
+        let last_code = u32::from(LIPError::DepositDeadlinePassed);
+        for code in u32::from(LIPError::CampaignNotActive)..=last_code {
+            assert_eq!(lip_error_from_code(code).map(u32::from), Some(code));
+        }
+        assert!(lip_error_from_code(last_code + 1).is_none());
+
+        let code = TokenError::InsufficientFunds as u32;
+        let failure = ProgramFailure::from_code(Some(&spl_token::id()), code);
+        assert!(matches!(
+            failure,
+            ProgramFailure::Token(TokenError::InsufficientFunds)
+        ));
+        assert_eq!(failure.code(), code);
+
+        let failure = ProgramFailure::from_code(Some(&marginfi::id()), 2006);
+        assert!(matches!(failure, ProgramFailure::Anchor(2006)));
+
+        let failure = ProgramFailure::from_code(Some(&Pubkey::new_unique()), 6001);
+        assert!(matches!(failure, ProgramFailure::Unknown(6001)));
+    }
+
+    #[test]
+    fn transaction_error_context() {
+        let logs = vec![
+            "Program log: AnchorError caused by account: bank. Error Code: ConstraintRaw. Error Number: 2003.".to_string(),
+        ];
+
+        let err = ClientError::from_transaction_error(
+            TransactionError::InstructionError(1, InstructionError::Custom(2003)),
+            None,
+            Some(&logs),
+        );
+
+        assert_eq!(err.instruction_index(), Some(1));
+        match err {
+            ClientError::Program { account, .. } => assert_eq!(account.as_deref(), Some("bank")),
+            _ => panic!("expected a program error"),
+        }
+
+        let err = ClientError::from_transaction_error(
+            TransactionError::InstructionError(0, InstructionError::Custom(6010)),
+            None,
+            None,
+        );
+        assert!(err.marginfi_error().is_none());
+    }
+}
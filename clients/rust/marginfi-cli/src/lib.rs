@@ -1,8 +1,14 @@
 mod config;
+mod decoder;
 mod entrypoint;
+pub mod errors;
 mod macros;
+mod output;
 mod processor;
 mod profile;
+mod retry_queue;
+mod squads;
+pub mod tx_splitter;
 mod utils;
 
 pub use entrypoint::*;
@@ -0,0 +1,478 @@
+//! Machine-readable views of the accounts printed by read commands, for `--output json`.
+//!
+//! Pubkeys are base58 strings and token amounts are in UI units of their mint.
+
+use {
+    crate::utils::EXP_10_I80F48,
+    anyhow::{anyhow, Result},
+    fixed::types::I80F48,
+    marginfi::state::{
+        amounts::ShareAmount,
+        marginfi_account::{BalanceSide, MarginfiAccount},
+        marginfi_group::{Bank, MarginfiGroup, WrappedI80F48, LIQUIDATOR_ALLOWLIST_FLAG},
+    },
+    marginfi_sdk::math::{apr_to_apy, calc_bank_rates},
+    serde::Serialize,
+    solana_account_decoder::parse_token::UiTokenAmount,
+    solana_sdk::pubkey::Pubkey,
+    std::collections::HashMap,
+};
+
+#[cfg(feature = "lip")]
+use liquidity_incentive_program::state::{Campaign, Deposit};
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn to_f64(value: WrappedI80F48) -> f64 {
+    I80F48::from(value).to_num()
+}
+
+fn to_ui(native_amount: I80F48, mint_decimals: u8) -> f64 {
+    (native_amount / EXP_10_I80F48[mint_decimals as usize]).to_num()
+}
+
+fn optional_pubkey(pubkey: &Pubkey) -> Option<String> {
+    (*pubkey != Pubkey::default()).then(|| pubkey.to_string())
+}
+
+#[derive(Serialize)]
+pub struct GroupOutput {
+    pub address: String,
+    pub admin: String,
+    pub pending_admin: Option<String>,
+    pub liquidator_allowlist_enabled: bool,
+    pub liquidator_allowlist: Vec<String>,
+    pub referrers: Vec<String>,
+    pub bankruptcy_count: u64,
+    /// In USD.
+    pub socialized_loss_value: f64,
+    /// In USD, at the price of each borrow.
+    pub total_borrow_value: f64,
+    /// In USD, 0 if borrows are not limited.
+    pub total_borrow_value_limit: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banks: Option<Vec<BankOutput>>,
+}
+
+impl GroupOutput {
+    pub fn new(address: &Pubkey, group: &MarginfiGroup) -> Self {
+        Self {
+            address: address.to_string(),
+            admin: group.admin.to_string(),
+            pending_admin: optional_pubkey(&group.pending_admin),
+            liquidator_allowlist_enabled: group.get_flag(LIQUIDATOR_ALLOWLIST_FLAG),
+            liquidator_allowlist: group
+                .liquidator_allowlist
+                .iter()
+                .filter_map(optional_pubkey)
+                .collect(),
+            referrers: group.referrers.iter().filter_map(optional_pubkey).collect(),
+            bankruptcy_count: group.bankruptcy_count,
+            socialized_loss_value: to_f64(group.socialized_loss_value),
+            total_borrow_value: to_f64(group.total_borrow_value),
+            total_borrow_value_limit: group.total_borrow_value_limit,
+            banks: None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BankOutput {
+    pub address: String,
+    pub group: String,
+    pub mint: String,
+    pub mint_decimals: u8,
+    pub total_deposits: f64,
+    pub total_liabilities: f64,
+    pub insured_bad_debt: f64,
+    pub socialized_loss: f64,
+    pub liquidation_insurance_fees: f64,
+    pub outflow_accumulator: f64,
+    pub config: BankConfigOutput,
+    pub emissions: EmissionsOutput,
+    pub last_update: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vaults: Option<VaultBalancesOutput>,
+}
+
+#[derive(Serialize)]
+pub struct BankConfigOutput {
+    pub operational_state: String,
+    pub risk_tier: String,
+    pub asset_weight_init: f64,
+    pub asset_weight_maint: f64,
+    pub liability_weight_init: f64,
+    pub liability_weight_maint: f64,
+    pub deposit_limit: f64,
+    pub borrow_limit: f64,
+    /// In USD.
+    pub total_asset_value_init_limit: u64,
+    pub outflow_limit: f64,
+    pub outflow_window: u64,
+    pub keeper_tip: f64,
+    pub keeper_tip_interval: u64,
+    pub origination_fee_bps: u64,
+    /// Oracle price band in USD, 0 if unbounded.
+    pub oracle_price_floor: f64,
+    pub oracle_price_ceiling: f64,
+    pub liquidation_liquidator_fee: f64,
+    pub liquidation_insurance_fee: f64,
+    pub interest_rate: InterestRateOutput,
+    pub oracle_setup: String,
+    pub oracle_keys: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct InterestRateOutput {
+    pub optimal_utilization_rate: f64,
+    pub plateau_interest_rate: f64,
+    pub max_interest_rate: f64,
+    pub insurance_fee_fixed_apr: f64,
+    pub insurance_ir_fee: f64,
+    pub protocol_fixed_fee_apr: f64,
+    pub protocol_ir_fee: f64,
+    pub referral_fee_share: f64,
+}
+
+#[derive(Serialize)]
+pub struct EmissionsOutput {
+    pub flags: u64,
+    pub rate: u64,
+    pub mint: Option<String>,
+    pub remaining: f64,
+    pub end_timestamp: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct VaultBalancesOutput {
+    pub liquidity: Option<f64>,
+    pub fee: Option<f64>,
+    pub insurance: Option<f64>,
+}
+
+impl VaultBalancesOutput {
+    pub fn new(liquidity: &UiTokenAmount, fee: &UiTokenAmount, insurance: &UiTokenAmount) -> Self {
+        Self {
+            liquidity: liquidity.ui_amount,
+            fee: fee.ui_amount,
+            insurance: insurance.ui_amount,
+        }
+    }
+}
+
+impl BankOutput {
+    pub fn new(address: &Pubkey, bank: &Bank) -> Result<Self> {
+        let decimals = bank.mint_decimals;
+        let config = &bank.config;
+        let ir_config = &config.interest_rate_config;
+        let native = |amount: u64| to_ui(I80F48::from_num(amount), decimals);
+
+        Ok(Self {
+            address: address.to_string(),
+            group: bank.group.to_string(),
+            mint: bank.mint.to_string(),
+            mint_decimals: decimals,
+            total_deposits: to_ui(
+                bank.asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+                    .value(),
+                decimals,
+            ),
+            total_liabilities: to_ui(
+                bank.liability_amount(ShareAmount::new(bank.total_liability_shares.into()))?
+                    .value(),
+                decimals,
+            ),
+            insured_bad_debt: to_ui(bank.insured_bad_debt.into(), decimals),
+            socialized_loss: to_ui(bank.socialized_loss.into(), decimals),
+            liquidation_insurance_fees: to_ui(bank.liquidation_insurance_fees.into(), decimals),
+            outflow_accumulator: to_ui(bank.outflow_accumulator.into(), decimals),
+            config: BankConfigOutput {
+                operational_state: format!("{:?}", config.operational_state),
+                risk_tier: format!("{:?}", config.risk_tier),
+                asset_weight_init: to_f64(config.asset_weight_init),
+                asset_weight_maint: to_f64(config.asset_weight_maint),
+                liability_weight_init: to_f64(config.liability_weight_init),
+                liability_weight_maint: to_f64(config.liability_weight_maint),
+                deposit_limit: native(config.deposit_limit),
+                borrow_limit: native(config.borrow_limit),
+                total_asset_value_init_limit: config.total_asset_value_init_limit,
+                outflow_limit: native(config.outflow_limit),
+                outflow_window: config.outflow_window,
+                keeper_tip: native(config.keeper_tip),
+                keeper_tip_interval: config.keeper_tip_interval,
+                origination_fee_bps: config.origination_fee_bps,
+                oracle_price_floor: to_f64(config.oracle_price_floor),
+                oracle_price_ceiling: to_f64(config.oracle_price_ceiling),
+                liquidation_liquidator_fee: to_f64(config.liquidation_liquidator_fee),
+                liquidation_insurance_fee: to_f64(config.liquidation_insurance_fee),
+                interest_rate: InterestRateOutput {
+                    optimal_utilization_rate: to_f64(ir_config.optimal_utilization_rate),
+                    plateau_interest_rate: to_f64(ir_config.plateau_interest_rate),
+                    max_interest_rate: to_f64(ir_config.max_interest_rate),
+                    insurance_fee_fixed_apr: to_f64(ir_config.insurance_fee_fixed_apr),
+                    insurance_ir_fee: to_f64(ir_config.insurance_ir_fee),
+                    protocol_fixed_fee_apr: to_f64(ir_config.protocol_fixed_fee_apr),
+                    protocol_ir_fee: to_f64(ir_config.protocol_ir_fee),
+                    referral_fee_share: to_f64(ir_config.referral_fee_share),
+                },
+                oracle_setup: format!("{:?}", config.oracle_setup),
+                oracle_keys: config
+                    .oracle_keys
+                    .iter()
+                    .filter_map(optional_pubkey)
+                    .collect(),
+            },
+            emissions: EmissionsOutput {
+                flags: bank.emissions_flags,
+                rate: bank.emissions_rate,
+                mint: optional_pubkey(&bank.emissions_mint),
+                remaining: to_f64(bank.emissions_remaining),
+                end_timestamp: (bank.emissions_end_timestamp != 0)
+                    .then_some(bank.emissions_end_timestamp),
+            },
+            last_update: bank.last_update,
+            vaults: None,
+        })
+    }
+}
+
+/// Utilization, rates and totals of a bank, rates are derived from its interest rate config.
+#[derive(Serialize)]
+pub struct BankStatsOutput {
+    pub address: String,
+    pub mint: String,
+    pub total_deposits: f64,
+    pub total_liabilities: f64,
+    /// Liabilities over deposits.
+    pub utilization: f64,
+    pub lending_apr: f64,
+    pub lending_apy: f64,
+    pub borrowing_apr: f64,
+    pub borrowing_apy: f64,
+    /// In USD, `None` if the oracle can't be loaded.
+    pub price: Option<f64>,
+    pub stale_oracle: bool,
+    pub total_deposits_value: Option<f64>,
+    pub total_liabilities_value: Option<f64>,
+    pub vaults: VaultBalancesOutput,
+    pub last_update: i64,
+}
+
+impl BankStatsOutput {
+    /// `price` is the oracle price of the mint and whether it is stale.
+    pub fn new(
+        address: &Pubkey,
+        bank: &Bank,
+        price: Option<(I80F48, bool)>,
+        vaults: VaultBalancesOutput,
+    ) -> Result<Self> {
+        let total_deposits = bank
+            .asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+            .value();
+        let total_liabilities = bank
+            .liability_amount(ShareAmount::new(bank.total_liability_shares.into()))?
+            .value();
+
+        let rates = calc_bank_rates(bank).map_err(|err| anyhow!("Bank {}: {}", address, err))?;
+        let (lending_apr, borrowing_apr) =
+            (rates.lending_apr.to_num(), rates.borrowing_apr.to_num());
+
+        let total_deposits = to_ui(total_deposits, bank.mint_decimals);
+        let total_liabilities = to_ui(total_liabilities, bank.mint_decimals);
+        let price_value = price.map(|(price, _)| price.to_num::<f64>());
+
+        Ok(Self {
+            address: address.to_string(),
+            mint: bank.mint.to_string(),
+            total_deposits,
+            total_liabilities,
+            utilization: rates.utilization.to_num(),
+            lending_apr,
+            lending_apy: apr_to_apy(lending_apr),
+            borrowing_apr,
+            borrowing_apy: apr_to_apy(borrowing_apr),
+            price: price_value,
+            stale_oracle: price.map_or(false, |(_, stale)| stale),
+            total_deposits_value: price_value.map(|price| total_deposits * price),
+            total_liabilities_value: price_value.map(|price| total_liabilities * price),
+            vaults,
+            last_update: bank.last_update,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct MarginfiAccountOutput {
+    pub address: String,
+    pub group: String,
+    pub authority: String,
+    pub default: bool,
+    pub balances: Vec<BalanceOutput>,
+}
+
+#[derive(Serialize)]
+pub struct BalanceOutput {
+    pub bank: String,
+    pub mint: String,
+    /// `assets` or `liabilities`.
+    pub side: String,
+    pub amount: f64,
+    pub emissions_outstanding: f64,
+}
+
+impl MarginfiAccountOutput {
+    /// `emissions_outstanding` of the balances are expected to be settled, as `print_account` does.
+    pub fn new(
+        address: &Pubkey,
+        marginfi_account: &MarginfiAccount,
+        banks: &HashMap<Pubkey, Bank>,
+        default: bool,
+    ) -> Result<Self> {
+        let balances = marginfi_account
+            .lending_account
+            .get_active_balances_iter()
+            .map(|balance| {
+                let bank = banks
+                    .get(&balance.bank_pk)
+                    .ok_or_else(|| anyhow::anyhow!("Bank {} not found", balance.bank_pk))?;
+
+                let (side, native_amount) = if !balance.is_empty(BalanceSide::Assets) {
+                    (
+                        "assets",
+                        bank.asset_amount(ShareAmount::new(balance.asset_shares.into()))?
+                            .value(),
+                    )
+                } else {
+                    (
+                        "liabilities",
+                        bank.liability_amount(ShareAmount::new(balance.liability_shares.into()))?
+                            .value(),
+                    )
+                };
+
+                Ok(BalanceOutput {
+                    bank: balance.bank_pk.to_string(),
+                    mint: bank.mint.to_string(),
+                    side: side.to_string(),
+                    amount: to_ui(native_amount, bank.mint_decimals),
+                    emissions_outstanding: to_f64(balance.emissions_outstanding),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            address: address.to_string(),
+            group: marginfi_account.group.to_string(),
+            authority: marginfi_account.authority.to_string(),
+            default,
+            balances,
+        })
+    }
+}
+
+#[cfg(feature = "lip")]
+#[derive(Serialize)]
+pub struct CampaignOutput {
+    pub address: String,
+    pub bank: String,
+    pub mint: String,
+    pub total_capacity: f64,
+    pub remaining_capacity: f64,
+    pub outstanding_deposits: f64,
+    /// In seconds.
+    pub lockup_period: u64,
+    pub max_rewards: f64,
+    pub guaranteed_apr: Option<f64>,
+    pub early_withdrawal_reward_bps: u64,
+    pub max_deposit_per_user: Option<f64>,
+    pub lockup_tiers: Vec<LockupTierOutput>,
+    pub active: bool,
+    pub paused: bool,
+    /// Unix timestamp after which deposits are rejected, if any.
+    pub deposit_deadline: Option<i64>,
+}
+
+#[cfg(feature = "lip")]
+#[derive(Serialize)]
+pub struct LockupTierOutput {
+    /// Index of the tier in deposit instructions, tier 0 is the campaign lockup.
+    pub index: u64,
+    pub lockup_period: u64,
+    pub reward_bps: u64,
+}
+
+#[cfg(feature = "lip")]
+impl CampaignOutput {
+    pub fn new(address: &Pubkey, campaign: &Campaign, bank: &Bank) -> Self {
+        let native = |amount: u64| to_ui(I80F48::from_num(amount), bank.mint_decimals);
+
+        Self {
+            address: address.to_string(),
+            bank: campaign.marginfi_bank_pk.to_string(),
+            mint: bank.mint.to_string(),
+            total_capacity: native(campaign.max_deposits),
+            remaining_capacity: native(campaign.remaining_capacity),
+            outstanding_deposits: native(campaign.outstanding_deposits()),
+            lockup_period: campaign.lockup_period,
+            max_rewards: native(campaign.max_rewards),
+            guaranteed_apr: campaign.apr().map(|apr| apr.to_num()),
+            early_withdrawal_reward_bps: campaign.early_withdrawal_reward_bps,
+            max_deposit_per_user: (campaign.max_deposit_per_user > 0)
+                .then(|| native(campaign.max_deposit_per_user)),
+            lockup_tiers: campaign
+                .lockup_tiers
+                .iter()
+                .enumerate()
+                .filter(|(_, tier)| tier.lockup_period > 0)
+                .map(|(i, tier)| LockupTierOutput {
+                    index: i as u64 + 1,
+                    lockup_period: tier.lockup_period,
+                    reward_bps: tier.reward_bps,
+                })
+                .collect(),
+            active: campaign.active,
+            paused: campaign.paused,
+            deposit_deadline: (campaign.deposit_deadline > 0).then_some(campaign.deposit_deadline),
+        }
+    }
+}
+
+#[cfg(feature = "lip")]
+#[derive(Serialize)]
+pub struct DepositOutput {
+    pub address: String,
+    pub campaign: String,
+    pub mint: String,
+    pub owner: String,
+    pub amount: f64,
+    pub lockup_tier: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub mature: bool,
+}
+
+#[cfg(feature = "lip")]
+impl DepositOutput {
+    pub fn new(
+        address: &Pubkey,
+        deposit: &Deposit,
+        bank: &Bank,
+        end_time: i64,
+        current_timestamp: i64,
+    ) -> Self {
+        Self {
+            address: address.to_string(),
+            campaign: deposit.campaign.to_string(),
+            mint: bank.mint.to_string(),
+            owner: deposit.owner.to_string(),
+            amount: to_ui(I80F48::from_num(deposit.amount), bank.mint_decimals),
+            lockup_tier: deposit.lockup_tier,
+            start_time: deposit.start_time,
+            end_time,
+            mature: current_timestamp > end_time,
+        }
+    }
+}
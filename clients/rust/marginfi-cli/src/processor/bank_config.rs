@@ -0,0 +1,593 @@
+use {
+    super::load_bank,
+    crate::{
+        config::Config,
+        tx_splitter::{IxGroup, TxSplitter},
+        utils::{create_oracle_key_array, process_transaction},
+    },
+    anyhow::{bail, Result},
+    fixed::types::I80F48,
+    marginfi::state::{
+        marginfi_group::{
+            Bank, BankConfigOpt, BankOperationalState, InterestRateConfigOpt, OracleConfig,
+            RiskTier, WrappedI80F48,
+        },
+        price::OracleSetup,
+    },
+    marginfi_sdk::instructions::configure_bank,
+    serde::Deserialize,
+    solana_sdk::pubkey::Pubkey,
+    std::{fmt::Display, fs, path::Path, str::FromStr},
+};
+
+/// Estimated compute units of a single configure bank instruction.
+const CONFIGURE_BANK_COMPUTE_UNITS: u32 = 30_000;
+
+/// Weights and rates closer than this to the on-chain value are considered unchanged,
+/// as values written from `f32` CLI flags don't round trip exactly.
+const RATE_TOLERANCE: f64 = 1e-6;
+
+/// Desired config of the banks of a group.
+///
+/// Omitted fields are left unchanged, so a file only needs the fields it manages.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfigFile {
+    /// Checked against the profile group, if set.
+    pub marginfi_group: Option<String>,
+    #[serde(default, rename = "bank")]
+    pub banks: Vec<BankConfigEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BankConfigEntry {
+    pub address: String,
+    /// Label of the bank in the diff, e.g. the token symbol.
+    pub name: Option<String>,
+
+    pub asset_weight_init: Option<f64>,
+    pub asset_weight_maint: Option<f64>,
+    pub liability_weight_init: Option<f64>,
+    pub liability_weight_maint: Option<f64>,
+
+    /// In UI units of the bank mint.
+    pub deposit_limit: Option<f64>,
+    /// In UI units of the bank mint.
+    pub borrow_limit: Option<f64>,
+    /// In USD.
+    pub total_asset_value_init_limit: Option<u64>,
+
+    pub operational_state: Option<OperationalStateEntry>,
+    pub risk_tier: Option<RiskTierEntry>,
+
+    /// In UI units of the bank mint.
+    pub outflow_limit: Option<f64>,
+    /// In seconds.
+    pub outflow_window: Option<u64>,
+    /// In UI units of the bank mint.
+    pub keeper_tip: Option<f64>,
+    /// In seconds.
+    pub keeper_tip_interval: Option<u64>,
+    /// In bps of the amount borrowed.
+    pub origination_fee_bps: Option<u64>,
+    /// In USD, 0 to disable.
+    pub oracle_price_floor: Option<f64>,
+    /// In USD, 0 to disable.
+    pub oracle_price_ceiling: Option<f64>,
+    pub liquidation_liquidator_fee: Option<f64>,
+    pub liquidation_insurance_fee: Option<f64>,
+
+    pub oracle: Option<OracleEntry>,
+    pub interest_rate: Option<InterestRateEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OracleEntry {
+    pub setup: OracleSetupEntry,
+    pub key: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InterestRateEntry {
+    pub optimal_utilization_rate: Option<f64>,
+    pub plateau_interest_rate: Option<f64>,
+    pub max_interest_rate: Option<f64>,
+    pub insurance_fee_fixed_apr: Option<f64>,
+    pub insurance_ir_fee: Option<f64>,
+    pub protocol_fixed_fee_apr: Option<f64>,
+    pub protocol_ir_fee: Option<f64>,
+    pub referral_fee_share: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationalStateEntry {
+    Paused,
+    Operational,
+    ReduceOnly,
+}
+
+impl From<BankOperationalState> for OperationalStateEntry {
+    fn from(value: BankOperationalState) -> Self {
+        match value {
+            BankOperationalState::Paused => Self::Paused,
+            BankOperationalState::Operational => Self::Operational,
+            BankOperationalState::ReduceOnly => Self::ReduceOnly,
+        }
+    }
+}
+
+impl From<OperationalStateEntry> for BankOperationalState {
+    fn from(value: OperationalStateEntry) -> Self {
+        match value {
+            OperationalStateEntry::Paused => Self::Paused,
+            OperationalStateEntry::Operational => Self::Operational,
+            OperationalStateEntry::ReduceOnly => Self::ReduceOnly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskTierEntry {
+    Collateral,
+    Isolated,
+}
+
+impl From<RiskTier> for RiskTierEntry {
+    fn from(value: RiskTier) -> Self {
+        match value {
+            RiskTier::Collateral => Self::Collateral,
+            RiskTier::Isolated => Self::Isolated,
+        }
+    }
+}
+
+impl From<RiskTierEntry> for RiskTier {
+    fn from(value: RiskTierEntry) -> Self {
+        match value {
+            RiskTierEntry::Collateral => Self::Collateral,
+            RiskTierEntry::Isolated => Self::Isolated,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OracleSetupEntry {
+    None,
+    PythEma,
+    SwitchboardV2,
+}
+
+impl From<OracleSetup> for OracleSetupEntry {
+    fn from(value: OracleSetup) -> Self {
+        match value {
+            OracleSetup::None => Self::None,
+            OracleSetup::PythEma => Self::PythEma,
+            OracleSetup::SwitchboardV2 => Self::SwitchboardV2,
+        }
+    }
+}
+
+impl From<OracleSetupEntry> for OracleSetup {
+    fn from(value: OracleSetupEntry) -> Self {
+        match value {
+            OracleSetupEntry::None => Self::None,
+            OracleSetupEntry::PythEma => Self::PythEma,
+            OracleSetupEntry::SwitchboardV2 => Self::SwitchboardV2,
+        }
+    }
+}
+
+/// Changes to apply to a bank, with a `field: current -> desired` line per changed field.
+pub struct BankConfigChange {
+    pub bank_pk: Pubkey,
+    pub label: String,
+    pub changes: Vec<String>,
+    pub bank_config_opt: BankConfigOpt,
+}
+
+/// Diff the banks of `config_file` against their on-chain config, and print the changes.
+///
+/// Only banks with at least one changed field are returned.
+pub fn diff_group_config(
+    config: &Config,
+    marginfi_group: Pubkey,
+    config_file: &Path,
+) -> Result<Vec<BankConfigChange>> {
+    let group_config: GroupConfigFile = toml::from_str(&fs::read_to_string(config_file)?)?;
+
+    if let Some(file_group) = &group_config.marginfi_group {
+        if Pubkey::from_str(file_group)? != marginfi_group {
+            bail!(
+                "Config file is for group {}, profile group is {}",
+                file_group,
+                marginfi_group
+            );
+        }
+    }
+
+    let mut bank_changes = vec![];
+    for entry in group_config.banks.iter() {
+        let bank_pk = Pubkey::from_str(&entry.address)?;
+        let bank = load_bank(config, bank_pk)?;
+
+        if bank.group != marginfi_group {
+            bail!(
+                "Bank {} does not belong to group {}",
+                bank_pk,
+                marginfi_group
+            );
+        }
+
+        let label = entry
+            .name
+            .as_ref()
+            .map(|name| format!("{} ({})", name, bank_pk))
+            .unwrap_or_else(|| bank_pk.to_string());
+
+        let bank_change = diff_bank_config(bank_pk, label, &bank, entry)?;
+        if !bank_change.changes.is_empty() {
+            bank_changes.push(bank_change);
+        }
+    }
+
+    if bank_changes.is_empty() {
+        println!(
+            "All {} banks match {}",
+            group_config.banks.len(),
+            config_file.display()
+        );
+    }
+
+    for bank_change in bank_changes.iter() {
+        println!("Bank {}", bank_change.label);
+        for change in bank_change.changes.iter() {
+            println!("\t{}", change);
+        }
+    }
+
+    Ok(bank_changes)
+}
+
+/// Send a configure bank instruction per changed bank, packed in as few transactions as possible.
+pub fn apply_group_config(
+    config: &Config,
+    marginfi_group: Pubkey,
+    bank_changes: Vec<BankConfigChange>,
+) -> Result<()> {
+    if bank_changes.is_empty() {
+        return Ok(());
+    }
+
+    let rpc_client = config.mfi_program.rpc();
+    let signing_keypairs = config.get_signers(false);
+
+    let groups = bank_changes
+        .into_iter()
+        .map(|bank_change| {
+            let ix = configure_bank(
+                &config.program_id,
+                marginfi_group,
+                config.authority(),
+                bank_change.bank_pk,
+                bank_change.bank_config_opt,
+            );
+
+            IxGroup::new(vec![ix], CONFIGURE_BANK_COMPUTE_UNITS)
+        })
+        .collect::<Vec<_>>();
+
+    let mut splitter = TxSplitter::new(config.authority());
+    splitter.compute_unit_price = config.priority_fee;
+    let plan = splitter.split(groups)?;
+    let txs_count = plan.transactions.len();
+
+    println!("Sending {} txs", txs_count);
+
+    for (i, tx) in plan.transactions.iter().enumerate() {
+        let blockhash = rpc_client.get_latest_blockhash()?;
+
+        let mut transaction = splitter.to_transaction(tx);
+        transaction.partial_sign(&signing_keypairs, blockhash);
+
+        let sig = process_transaction(&transaction, &rpc_client, config.get_tx_mode())?;
+
+        println!("Sent [{}/{}] {}", i + 1, txs_count, sig);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn diff_bank_config(
+    bank_pk: Pubkey,
+    label: String,
+    bank: &Bank,
+    entry: &BankConfigEntry,
+) -> Result<BankConfigChange> {
+    let bank_config = &bank.config;
+    let mut changes = vec![];
+
+    let mut rate = |field: &str, current: WrappedI80F48, desired: Option<f64>| {
+        diff_rate(&mut changes, field, current, desired)
+    };
+
+    let asset_weight_init = rate(
+        "asset_weight_init",
+        bank_config.asset_weight_init,
+        entry.asset_weight_init,
+    );
+    let asset_weight_maint = rate(
+        "asset_weight_maint",
+        bank_config.asset_weight_maint,
+        entry.asset_weight_maint,
+    );
+    let liability_weight_init = rate(
+        "liability_weight_init",
+        bank_config.liability_weight_init,
+        entry.liability_weight_init,
+    );
+    let liability_weight_maint = rate(
+        "liability_weight_maint",
+        bank_config.liability_weight_maint,
+        entry.liability_weight_maint,
+    );
+    let oracle_price_floor = rate(
+        "oracle_price_floor",
+        bank_config.oracle_price_floor,
+        entry.oracle_price_floor,
+    );
+    let oracle_price_ceiling = rate(
+        "oracle_price_ceiling",
+        bank_config.oracle_price_ceiling,
+        entry.oracle_price_ceiling,
+    );
+    let liquidation_liquidator_fee = rate(
+        "liquidation_liquidator_fee",
+        bank_config.liquidation_liquidator_fee,
+        entry.liquidation_liquidator_fee,
+    );
+    let liquidation_insurance_fee = rate(
+        "liquidation_insurance_fee",
+        bank_config.liquidation_insurance_fee,
+        entry.liquidation_insurance_fee,
+    );
+
+    let interest_rate = entry.interest_rate.as_ref();
+    let ir_config = &bank_config.interest_rate_config;
+    let interest_rate_config_opt = InterestRateConfigOpt {
+        optimal_utilization_rate: rate(
+            "interest_rate.optimal_utilization_rate",
+            ir_config.optimal_utilization_rate,
+            interest_rate.and_then(|ir| ir.optimal_utilization_rate),
+        ),
+        plateau_interest_rate: rate(
+            "interest_rate.plateau_interest_rate",
+            ir_config.plateau_interest_rate,
+            interest_rate.and_then(|ir| ir.plateau_interest_rate),
+        ),
+        max_interest_rate: rate(
+            "interest_rate.max_interest_rate",
+            ir_config.max_interest_rate,
+            interest_rate.and_then(|ir| ir.max_interest_rate),
+        ),
+        insurance_fee_fixed_apr: rate(
+            "interest_rate.insurance_fee_fixed_apr",
+            ir_config.insurance_fee_fixed_apr,
+            interest_rate.and_then(|ir| ir.insurance_fee_fixed_apr),
+        ),
+        insurance_ir_fee: rate(
+            "interest_rate.insurance_ir_fee",
+            ir_config.insurance_ir_fee,
+            interest_rate.and_then(|ir| ir.insurance_ir_fee),
+        ),
+        protocol_fixed_fee_apr: rate(
+            "interest_rate.protocol_fixed_fee_apr",
+            ir_config.protocol_fixed_fee_apr,
+            interest_rate.and_then(|ir| ir.protocol_fixed_fee_apr),
+        ),
+        protocol_ir_fee: rate(
+            "interest_rate.protocol_ir_fee",
+            ir_config.protocol_ir_fee,
+            interest_rate.and_then(|ir| ir.protocol_ir_fee),
+        ),
+        referral_fee_share: rate(
+            "interest_rate.referral_fee_share",
+            ir_config.referral_fee_share,
+            interest_rate.and_then(|ir| ir.referral_fee_share),
+        ),
+    };
+    let interest_rate_changed = interest_rate_config_opt.optimal_utilization_rate.is_some()
+        || interest_rate_config_opt.plateau_interest_rate.is_some()
+        || interest_rate_config_opt.max_interest_rate.is_some()
+        || interest_rate_config_opt.insurance_fee_fixed_apr.is_some()
+        || interest_rate_config_opt.insurance_ir_fee.is_some()
+        || interest_rate_config_opt.protocol_fixed_fee_apr.is_some()
+        || interest_rate_config_opt.protocol_ir_fee.is_some()
+        || interest_rate_config_opt.referral_fee_share.is_some();
+
+    let mut amount = |field: &str, current: u64, desired_ui: Option<f64>| {
+        diff_amount(&mut changes, field, current, desired_ui, bank.mint_decimals)
+    };
+
+    let deposit_limit = amount(
+        "deposit_limit",
+        bank_config.deposit_limit,
+        entry.deposit_limit,
+    );
+    let borrow_limit = amount("borrow_limit", bank_config.borrow_limit, entry.borrow_limit);
+    let outflow_limit = amount(
+        "outflow_limit",
+        bank_config.outflow_limit,
+        entry.outflow_limit,
+    );
+    let keeper_tip = amount("keeper_tip", bank_config.keeper_tip, entry.keeper_tip);
+
+    let total_asset_value_init_limit = diff_value(
+        &mut changes,
+        "total_asset_value_init_limit",
+        bank_config.total_asset_value_init_limit,
+        entry.total_asset_value_init_limit,
+    );
+    let outflow_window = diff_value(
+        &mut changes,
+        "outflow_window",
+        bank_config.outflow_window,
+        entry.outflow_window,
+    );
+    let keeper_tip_interval = diff_value(
+        &mut changes,
+        "keeper_tip_interval",
+        bank_config.keeper_tip_interval,
+        entry.keeper_tip_interval,
+    );
+    let origination_fee_bps = diff_value(
+        &mut changes,
+        "origination_fee_bps",
+        bank_config.origination_fee_bps,
+        entry.origination_fee_bps,
+    );
+
+    let operational_state = diff_enum(
+        &mut changes,
+        "operational_state",
+        OperationalStateEntry::from(bank_config.operational_state),
+        entry.operational_state,
+    );
+    let risk_tier = diff_enum(
+        &mut changes,
+        "risk_tier",
+        RiskTierEntry::from(bank_config.risk_tier),
+        entry.risk_tier,
+    );
+
+    let oracle = match &entry.oracle {
+        Some(oracle) => {
+            let current_setup = OracleSetupEntry::from(bank_config.oracle_setup);
+            let current_key = bank_config.oracle_keys[0];
+            let desired_key = Pubkey::from_str(&oracle.key)?;
+
+            if current_setup != oracle.setup || current_key != desired_key {
+                changes.push(format!(
+                    "oracle: {:?} {} -> {:?} {}",
+                    current_setup, current_key, oracle.setup, desired_key
+                ));
+
+                Some(OracleConfig {
+                    setup: oracle.setup.into(),
+                    keys: create_oracle_key_array(desired_key),
+                })
+            } else {
+                None
+            }
+        }
+        None => None,
+    };
+
+    Ok(BankConfigChange {
+        bank_pk,
+        label,
+        changes,
+        bank_config_opt: BankConfigOpt {
+            asset_weight_init,
+            asset_weight_maint,
+            liability_weight_init,
+            liability_weight_maint,
+            deposit_limit,
+            borrow_limit,
+            operational_state: operational_state.map(|state| state.into()),
+            oracle,
+            interest_rate_config: interest_rate_changed.then_some(interest_rate_config_opt),
+            risk_tier: risk_tier.map(|tier| tier.into()),
+            total_asset_value_init_limit,
+            outflow_limit,
+            outflow_window,
+            keeper_tip,
+            keeper_tip_interval,
+            origination_fee_bps,
+            oracle_price_floor,
+            oracle_price_ceiling,
+            liquidation_liquidator_fee,
+            liquidation_insurance_fee,
+        },
+    })
+}
+
+fn diff_rate(
+    changes: &mut Vec<String>,
+    field: &str,
+    current: WrappedI80F48,
+    desired: Option<f64>,
+) -> Option<WrappedI80F48> {
+    let desired = desired?;
+    let current = I80F48::from(current).to_num::<f64>();
+
+    if (current - desired).abs() < RATE_TOLERANCE {
+        return None;
+    }
+
+    changes.push(format!("{}: {} -> {}", field, current, desired));
+
+    Some(I80F48::from_num(desired).into())
+}
+
+fn diff_amount(
+    changes: &mut Vec<String>,
+    field: &str,
+    current: u64,
+    desired_ui: Option<f64>,
+    mint_decimals: u8,
+) -> Option<u64> {
+    let desired = spl_token::ui_amount_to_amount(desired_ui?, mint_decimals);
+
+    if current == desired {
+        return None;
+    }
+
+    changes.push(format!(
+        "{}: {} -> {}",
+        field,
+        spl_token::amount_to_ui_amount(current, mint_decimals),
+        spl_token::amount_to_ui_amount(desired, mint_decimals)
+    ));
+
+    Some(desired)
+}
+
+fn diff_value<T: PartialEq + Display>(
+    changes: &mut Vec<String>,
+    field: &str,
+    current: T,
+    desired: Option<T>,
+) -> Option<T> {
+    let desired = desired?;
+
+    if current == desired {
+        return None;
+    }
+
+    changes.push(format!("{}: {} -> {}", field, current, desired));
+
+    Some(desired)
+}
+
+fn diff_enum<T: PartialEq + std::fmt::Debug>(
+    changes: &mut Vec<String>,
+    field: &str,
+    current: T,
+    desired: Option<T>,
+) -> Option<T> {
+    let desired = desired?;
+
+    if current == desired {
+        return None;
+    }
+
+    changes.push(format!("{}: {:?} -> {:?}", field, current, desired));
+
+    Some(desired)
+}
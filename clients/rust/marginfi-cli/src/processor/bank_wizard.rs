@@ -0,0 +1,604 @@
+use {
+    super::add_bank,
+    crate::{
+        config::Config,
+        profile::Profile,
+        utils::{create_oracle_key_array, EXP_10_I80F48},
+    },
+    anchor_spl::token::spl_token,
+    anyhow::{anyhow, bail, Result},
+    fixed::types::I80F48,
+    marginfi::state::{
+        marginfi_group::{BankConfig, BankOperationalState, InterestRateConfig, RiskTier},
+        price::{OraclePriceFeedAdapter, OracleSetup, PriceAdapter},
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{account_info::IntoAccountInfo, program_pack::Pack, pubkey::Pubkey},
+    std::{
+        fmt::Display,
+        io::{self, BufRead, Write},
+        str::FromStr,
+    },
+};
+
+/// Line based prompts, asking again until the answer parses.
+struct Prompter<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompter<R, W> {
+    /// Ask for a value, an empty answer picks `default` if any.
+    fn ask<T>(&mut self, label: &str, default: Option<T>) -> Result<T>
+    where
+        T: FromStr + Display + Clone,
+        T::Err: Display,
+    {
+        loop {
+            match &default {
+                Some(default) => write!(self.output, "{} [{}]: ", label, default)?,
+                None => write!(self.output, "{}: ", label)?,
+            }
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                bail!("Input closed, bank creation aborted");
+            }
+
+            let answer = line.trim();
+
+            if answer.is_empty() {
+                if let Some(default) = &default {
+                    return Ok(default.clone());
+                }
+
+                writeln!(self.output, "A value is required")?;
+                continue;
+            }
+
+            match answer.parse::<T>() {
+                Ok(value) => return Ok(value),
+                Err(err) => writeln!(self.output, "Invalid value: {}", err)?,
+            }
+        }
+    }
+
+    /// Ask for one of `choices`, returning its index.
+    fn choose(&mut self, label: &str, choices: &[&str], default: Option<usize>) -> Result<usize> {
+        let label = format!("{} ({})", label, choices.join("/"));
+
+        loop {
+            let answer: String =
+                self.ask(&label, default.map(|default| choices[default].to_owned()))?;
+
+            match choices
+                .iter()
+                .position(|choice| choice.eq_ignore_ascii_case(&answer))
+            {
+                Some(choice) => return Ok(choice),
+                None => writeln!(self.output, "Invalid choice: {}", answer)?,
+            }
+        }
+    }
+
+    fn confirm(&mut self, label: &str) -> Result<bool> {
+        Ok(self.choose(label, &["y", "n"], Some(1))? == 0)
+    }
+}
+
+/// Bank parameters entered in the wizard, in UI units.
+#[derive(Clone, Debug, PartialEq)]
+struct BankWizardAnswers {
+    risk_tier: RiskTier,
+    asset_weight_init: f64,
+    asset_weight_maint: f64,
+    liability_weight_init: f64,
+    liability_weight_maint: f64,
+    deposit_limit_ui: f64,
+    borrow_limit_ui: f64,
+    optimal_utilization_rate: f64,
+    plateau_interest_rate: f64,
+    max_interest_rate: f64,
+    insurance_fee_fixed_apr: f64,
+    insurance_ir_fee: f64,
+    protocol_fixed_fee_apr: f64,
+    protocol_ir_fee: f64,
+    oracle_setup: OracleSetup,
+    oracle_key: Pubkey,
+}
+
+impl BankWizardAnswers {
+    fn to_bank_config(&self, mint_decimals: u8) -> BankConfig {
+        let to_native = |ui_amount: f64| {
+            (I80F48::from_num(ui_amount) * EXP_10_I80F48[mint_decimals as usize])
+                .floor()
+                .to_num::<u64>()
+        };
+
+        BankConfig {
+            asset_weight_init: I80F48::from_num(self.asset_weight_init).into(),
+            asset_weight_maint: I80F48::from_num(self.asset_weight_maint).into(),
+            liability_weight_init: I80F48::from_num(self.liability_weight_init).into(),
+            liability_weight_maint: I80F48::from_num(self.liability_weight_maint).into(),
+            deposit_limit: to_native(self.deposit_limit_ui),
+            borrow_limit: to_native(self.borrow_limit_ui),
+            interest_rate_config: InterestRateConfig {
+                optimal_utilization_rate: I80F48::from_num(self.optimal_utilization_rate).into(),
+                plateau_interest_rate: I80F48::from_num(self.plateau_interest_rate).into(),
+                max_interest_rate: I80F48::from_num(self.max_interest_rate).into(),
+                insurance_fee_fixed_apr: I80F48::from_num(self.insurance_fee_fixed_apr).into(),
+                insurance_ir_fee: I80F48::from_num(self.insurance_ir_fee).into(),
+                protocol_fixed_fee_apr: I80F48::from_num(self.protocol_fixed_fee_apr).into(),
+                protocol_ir_fee: I80F48::from_num(self.protocol_ir_fee).into(),
+                ..InterestRateConfig::default()
+            },
+            operational_state: BankOperationalState::Operational,
+            oracle_setup: self.oracle_setup,
+            oracle_keys: create_oracle_key_array(self.oracle_key),
+            risk_tier: self.risk_tier,
+            ..BankConfig::default()
+        }
+    }
+}
+
+/// Oracle setups selectable in the wizard, in prompt order.
+const ORACLE_SETUPS: [(&str, OracleSetup); 2] = [
+    ("pyth-ema", OracleSetup::PythEma),
+    ("switchboard", OracleSetup::SwitchboardV2),
+];
+
+/// Walk the admin through the config of a new bank of the profile group, checking the oracle
+/// price and the config invariants before building the `lending_pool_add_bank` transaction.
+///
+/// The transaction goes through the profile transaction mode, so multisig profiles get
+/// the transaction to propose instead of sending it.
+pub fn bank_create_interactive(config: Config, profile: Profile) -> Result<()> {
+    let marginfi_group = profile
+        .marginfi_group
+        .ok_or_else(|| anyhow!("Marginfi group not specified in profile [{}]", profile.name))?;
+
+    let rpc_client = config.mfi_program.rpc();
+    let stdin = io::stdin();
+    let mut prompter = Prompter {
+        input: stdin.lock(),
+        output: io::stdout(),
+    };
+
+    println!("Creating a bank in group {}", marginfi_group);
+
+    let bank_mint: Pubkey = prompter.ask("Mint", None)?;
+    let mint = spl_token::state::Mint::unpack(&rpc_client.get_account(&bank_mint)?.data)?;
+
+    println!("Mint decimals: {}", mint.decimals);
+
+    let mut previous = None;
+
+    let (answers, bank_config) = loop {
+        let answers = prompt_answers(&mut prompter, previous.as_ref(), |setup, key| {
+            load_oracle_price(&rpc_client, setup, key)
+        })?;
+        let bank_config = answers.to_bank_config(mint.decimals);
+        let problems = check_bank_config(&bank_config);
+
+        if problems.is_empty() {
+            break (answers, bank_config);
+        }
+
+        println!("Invalid bank config:");
+        problems
+            .iter()
+            .for_each(|problem| println!("  - {}", problem));
+
+        if !prompter.confirm("Edit the config")? {
+            bail!("Bank creation aborted");
+        }
+
+        previous = Some(answers);
+    };
+
+    println!("{}", describe_answers(&answers, &bank_mint));
+
+    if let Some(multisig) = config.multisig {
+        println!(
+            "The transaction will be output for the multisig {}",
+            multisig
+        );
+    }
+
+    if !prompter.confirm("Create the bank")? {
+        bail!("Bank creation aborted");
+    }
+
+    add_bank(&config, &profile, bank_mint, bank_config)
+}
+
+/// Prompt for the bank parameters, defaulting to the `previous` answers when editing them.
+///
+/// The price of the oracle is loaded with `load_price` and confirmed by the admin,
+/// a mistyped oracle would otherwise only show as a mispriced bank.
+fn prompt_answers<R: BufRead, W: Write>(
+    prompter: &mut Prompter<R, W>,
+    previous: Option<&BankWizardAnswers>,
+    mut load_price: impl FnMut(OracleSetup, Pubkey) -> Result<(I80F48, I80F48)>,
+) -> Result<BankWizardAnswers> {
+    let prev = |field: fn(&BankWizardAnswers) -> f64| previous.map(field);
+
+    let risk_tier = match prompter.choose(
+        "Risk tier",
+        &["collateral", "isolated"],
+        previous.map(|previous| match previous.risk_tier {
+            RiskTier::Collateral => 0,
+            RiskTier::Isolated => 1,
+        }),
+    )? {
+        0 => RiskTier::Collateral,
+        _ => RiskTier::Isolated,
+    };
+
+    // Isolated assets can't be used as collateral
+    let (asset_weight_init, asset_weight_maint) = match risk_tier {
+        RiskTier::Collateral => (
+            prompter.ask("Asset weight init", prev(|a| a.asset_weight_init))?,
+            prompter.ask("Asset weight maint", prev(|a| a.asset_weight_maint))?,
+        ),
+        RiskTier::Isolated => (0., 0.),
+    };
+
+    let liability_weight_init =
+        prompter.ask("Liability weight init", prev(|a| a.liability_weight_init))?;
+    let liability_weight_maint =
+        prompter.ask("Liability weight maint", prev(|a| a.liability_weight_maint))?;
+
+    let deposit_limit_ui =
+        prompter.ask("Deposit limit (UI amount)", prev(|a| a.deposit_limit_ui))?;
+    let borrow_limit_ui = prompter.ask("Borrow limit (UI amount)", prev(|a| a.borrow_limit_ui))?;
+
+    let optimal_utilization_rate = prompter.ask(
+        "Optimal utilization rate",
+        prev(|a| a.optimal_utilization_rate),
+    )?;
+    let plateau_interest_rate =
+        prompter.ask("Plateau interest rate", prev(|a| a.plateau_interest_rate))?;
+    let max_interest_rate = prompter.ask("Max interest rate", prev(|a| a.max_interest_rate))?;
+    let insurance_fee_fixed_apr = prompter.ask(
+        "Insurance fee fixed APR",
+        prev(|a| a.insurance_fee_fixed_apr).or(Some(0.)),
+    )?;
+    let insurance_ir_fee = prompter.ask(
+        "Insurance IR fee",
+        prev(|a| a.insurance_ir_fee).or(Some(0.)),
+    )?;
+    let protocol_fixed_fee_apr = prompter.ask(
+        "Protocol fixed fee APR",
+        prev(|a| a.protocol_fixed_fee_apr).or(Some(0.)),
+    )?;
+    let protocol_ir_fee =
+        prompter.ask("Protocol IR fee", prev(|a| a.protocol_ir_fee).or(Some(0.)))?;
+
+    let (oracle_setup, oracle_key) = loop {
+        let oracle_setup = ORACLE_SETUPS[prompter.choose(
+            "Oracle type",
+            &ORACLE_SETUPS.map(|(name, _)| name),
+            previous.and_then(|previous| {
+                ORACLE_SETUPS
+                    .iter()
+                    .position(|(_, setup)| *setup == previous.oracle_setup)
+            }),
+        )?]
+        .1;
+        let oracle_key: Pubkey =
+            prompter.ask("Oracle", previous.map(|previous| previous.oracle_key))?;
+
+        match load_price(oracle_setup, oracle_key) {
+            Ok((price, confidence_interval)) => {
+                writeln!(
+                    prompter.output,
+                    "Oracle price: ${} (confidence interval: ${})",
+                    price, confidence_interval
+                )?;
+
+                if prompter.confirm("Is this the expected price")? {
+                    break (oracle_setup, oracle_key);
+                }
+            }
+            Err(err) => writeln!(prompter.output, "{}", err)?,
+        }
+    };
+
+    Ok(BankWizardAnswers {
+        risk_tier,
+        asset_weight_init,
+        asset_weight_maint,
+        liability_weight_init,
+        liability_weight_maint,
+        deposit_limit_ui,
+        borrow_limit_ui,
+        optimal_utilization_rate,
+        plateau_interest_rate,
+        max_interest_rate,
+        insurance_fee_fixed_apr,
+        insurance_ir_fee,
+        protocol_fixed_fee_apr,
+        protocol_ir_fee,
+        oracle_setup,
+        oracle_key,
+    })
+}
+
+/// Live price and confidence interval of `oracle_key`, ignoring staleness.
+fn load_oracle_price(
+    rpc_client: &RpcClient,
+    oracle_setup: OracleSetup,
+    oracle_key: Pubkey,
+) -> Result<(I80F48, I80F48)> {
+    let bank_config = BankConfig {
+        oracle_setup,
+        oracle_keys: create_oracle_key_array(oracle_key),
+        ..BankConfig::default()
+    };
+
+    let mut oracle_account = rpc_client
+        .get_account(&oracle_key)
+        .map_err(|err| anyhow!("Failed to fetch oracle {}: {}", oracle_key, err))?;
+    let oracle_ai = (&oracle_key, &mut oracle_account).into_account_info();
+
+    let price_feed = OraclePriceFeedAdapter::try_from_bank_config_ignoring_price_band(
+        &bank_config,
+        &[oracle_ai],
+        0,
+        u64::MAX,
+    )
+    .map_err(|err| anyhow!("Failed to load oracle {}: {:?}", oracle_key, err))?;
+
+    Ok((
+        price_feed
+            .get_price()
+            .map_err(|err| anyhow!("Invalid price: {:?}", err))?,
+        price_feed
+            .get_confidence_interval()
+            .map_err(|err| anyhow!("Invalid confidence interval: {:?}", err))?,
+    ))
+}
+
+/// Problems of a new bank config, each rejected on-chain or leaving the bank mispriced.
+fn check_bank_config(bank_config: &BankConfig) -> Vec<String> {
+    let mut problems = vec![];
+
+    let asset_weight_init = I80F48::from(bank_config.asset_weight_init);
+    let asset_weight_maint = I80F48::from(bank_config.asset_weight_maint);
+    let liability_weight_init = I80F48::from(bank_config.liability_weight_init);
+    let liability_weight_maint = I80F48::from(bank_config.liability_weight_maint);
+
+    if asset_weight_init < I80F48::ZERO || asset_weight_init > asset_weight_maint {
+        problems.push(format!(
+            "Asset weight init ({}) must be between 0 and asset weight maint ({})",
+            asset_weight_init, asset_weight_maint
+        ));
+    }
+    if asset_weight_maint > I80F48::ONE {
+        problems.push(format!(
+            "Asset weight maint ({}) must be at most 1",
+            asset_weight_maint
+        ));
+    }
+    if liability_weight_maint < I80F48::ONE || liability_weight_maint > liability_weight_init {
+        problems.push(format!(
+            "Liability weight maint ({}) must be between 1 and liability weight init ({})",
+            liability_weight_maint, liability_weight_init
+        ));
+    }
+    if bank_config.risk_tier == RiskTier::Isolated
+        && (asset_weight_init != I80F48::ZERO || asset_weight_maint != I80F48::ZERO)
+    {
+        problems.push("Isolated banks must have zero asset weights".to_owned());
+    }
+
+    let ir_config = &bank_config.interest_rate_config;
+    let optimal_utilization_rate = I80F48::from(ir_config.optimal_utilization_rate);
+    let plateau_interest_rate = I80F48::from(ir_config.plateau_interest_rate);
+    let max_interest_rate = I80F48::from(ir_config.max_interest_rate);
+
+    if optimal_utilization_rate <= I80F48::ZERO || optimal_utilization_rate >= I80F48::ONE {
+        problems.push(format!(
+            "Optimal utilization rate ({}) must be strictly between 0 and 1",
+            optimal_utilization_rate
+        ));
+    }
+    if plateau_interest_rate <= I80F48::ZERO || plateau_interest_rate >= max_interest_rate {
+        problems.push(format!(
+            "Plateau interest rate ({}) must be positive and below the max interest rate ({})",
+            plateau_interest_rate, max_interest_rate
+        ));
+    }
+
+    for (name, fee) in [
+        ("Insurance fee fixed APR", ir_config.insurance_fee_fixed_apr),
+        ("Insurance IR fee", ir_config.insurance_ir_fee),
+        ("Protocol fixed fee APR", ir_config.protocol_fixed_fee_apr),
+        ("Protocol IR fee", ir_config.protocol_ir_fee),
+    ] {
+        let fee = I80F48::from(fee);
+
+        if fee < I80F48::ZERO {
+            problems.push(format!("{} ({}) must not be negative", name, fee));
+        }
+    }
+
+    // Catches the invariants not listed above
+    if problems.is_empty() {
+        if let Err(err) = bank_config.validate() {
+            problems.push(format!("Rejected by the program validation: {:?}", err));
+        }
+    }
+
+    problems
+}
+
+fn describe_answers(answers: &BankWizardAnswers, bank_mint: &Pubkey) -> String {
+    format!(
+        r#"
+Mint: {}
+Risk Tier: {:?}
+Asset Weights: init {} / maint {}
+Liability Weights: init {} / maint {}
+Deposit Limit: {}
+Borrow Limit: {}
+Interest Rate Curve: optimal utilization {}, plateau {}, max {}
+Insurance Fees: fixed APR {}, IR {}
+Protocol Fees: fixed APR {}, IR {}
+Oracle: {:?} {}"#,
+        bank_mint,
+        answers.risk_tier,
+        answers.asset_weight_init,
+        answers.asset_weight_maint,
+        answers.liability_weight_init,
+        answers.liability_weight_maint,
+        answers.deposit_limit_ui,
+        answers.borrow_limit_ui,
+        answers.optimal_utilization_rate,
+        answers.plateau_interest_rate,
+        answers.max_interest_rate,
+        answers.insurance_fee_fixed_apr,
+        answers.insurance_ir_fee,
+        answers.protocol_fixed_fee_apr,
+        answers.protocol_ir_fee,
+        answers.oracle_setup,
+        answers.oracle_key,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn test_prompter(input: &str) -> Prompter<Cursor<Vec<u8>>, Vec<u8>> {
+        Prompter {
+            input: Cursor::new(input.as_bytes().to_vec()),
+            output: vec![],
+        }
+    }
+
+    fn answers() -> BankWizardAnswers {
+        BankWizardAnswers {
+            risk_tier: RiskTier::Collateral,
+            asset_weight_init: 0.8,
+            asset_weight_maint: 0.9,
+            liability_weight_init: 1.2,
+            liability_weight_maint: 1.1,
+            deposit_limit_ui: 1_000.,
+            borrow_limit_ui: 500.,
+            optimal_utilization_rate: 0.8,
+            plateau_interest_rate: 0.1,
+            max_interest_rate: 1.,
+            insurance_fee_fixed_apr: 0.,
+            insurance_ir_fee: 0.,
+            protocol_fixed_fee_apr: 0.01,
+            protocol_ir_fee: 0.1,
+            oracle_setup: OracleSetup::PythEma,
+            oracle_key: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn prompter_asks_again_until_the_answer_parses() {
+        let mut prompter = test_prompter("abc\n\n0.5\n");
+
+        assert_eq!(prompter.ask::<f64>("Weight", None).unwrap(), 0.5);
+
+        let output = String::from_utf8(prompter.output).unwrap();
+        assert!(output.contains("Invalid value"));
+        assert!(output.contains("A value is required"));
+
+        let mut prompter = test_prompter("\n");
+        assert_eq!(prompter.ask("Weight", Some(0.25)).unwrap(), 0.25);
+
+        let mut prompter = test_prompter("");
+        assert!(prompter.ask::<f64>("Weight", None).is_err());
+    }
+
+    #[test]
+    fn prompt_answers_confirms_the_oracle_price() {
+        let expected = answers();
+        let wrong_oracle = Pubkey::new_unique();
+
+        let input = format!(
+            "collateral\n0.8\n0.9\n1.2\n1.1\n1000\n500\n0.8\n0.1\n1\n\n\n0.01\n0.1\npyth-ema\n{}\nn\nPYTH-EMA\n{}\ny\n",
+            wrong_oracle, expected.oracle_key
+        );
+        let mut prompter = test_prompter(&input);
+
+        let mut loaded = vec![];
+        let answers = prompt_answers(&mut prompter, None, |setup, key| {
+            loaded.push((setup, key));
+            Ok((I80F48::from_num(10), I80F48::from_num(0.1)))
+        })
+        .unwrap();
+
+        assert_eq!(answers, expected);
+        assert_eq!(
+            loaded,
+            vec![
+                (OracleSetup::PythEma, wrong_oracle),
+                (OracleSetup::PythEma, expected.oracle_key)
+            ]
+        );
+    }
+
+    #[test]
+    fn prompt_answers_defaults_to_previous_answers() {
+        let previous = answers();
+        let mut prompter = test_prompter(&format!("{}y\n", "\n".repeat(16)));
+
+        let edited = prompt_answers(&mut prompter, Some(&previous), |_, _| {
+            Ok((I80F48::ONE, I80F48::ZERO))
+        })
+        .unwrap();
+
+        assert_eq!(edited, previous);
+    }
+
+    #[test]
+    fn check_bank_config_reports_broken_invariants() {
+        assert!(check_bank_config(&answers().to_bank_config(6)).is_empty());
+
+        let problems = check_bank_config(
+            &BankWizardAnswers {
+                asset_weight_init: 0.95,
+                liability_weight_maint: 1.3,
+                plateau_interest_rate: 2.,
+                ..answers()
+            }
+            .to_bank_config(6),
+        );
+
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].starts_with("Asset weight init"));
+        assert!(problems[1].starts_with("Liability weight maint"));
+        assert!(problems[2].starts_with("Plateau interest rate"));
+
+        let problems = check_bank_config(
+            &BankWizardAnswers {
+                risk_tier: RiskTier::Isolated,
+                ..answers()
+            }
+            .to_bank_config(6),
+        );
+
+        assert_eq!(
+            problems,
+            vec!["Isolated banks must have zero asset weights".to_owned()]
+        );
+    }
+
+    #[test]
+    fn to_bank_config_converts_limits_to_native() {
+        let answers = answers();
+        let bank_config = answers.to_bank_config(6);
+
+        assert_eq!(bank_config.deposit_limit, 1_000_000_000);
+        assert_eq!(bank_config.borrow_limit, 500_000_000);
+        assert_eq!(bank_config.oracle_keys[0], answers.oracle_key);
+    }
+}
@@ -0,0 +1,549 @@
+use {
+    super::load_all_banks,
+    crate::{
+        config::Config,
+        output::print_json,
+        profile::Profile,
+        tx_splitter::{IxGroup, PlannedTx, TxSplitter},
+        utils::process_transaction,
+    },
+    anyhow::{anyhow, bail, Result},
+    marginfi::state::{
+        fee_state::FeeState, marginfi_account::MarginfiAccount, marginfi_group::Bank,
+    },
+    marginfi_sdk::{
+        accounts::load_fee_state, instructions::MarginfiAccountContext, math::ui_to_native,
+    },
+    serde::{Deserialize, Serialize},
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    std::{collections::HashMap, fs, path::Path, str::FromStr},
+};
+
+#[cfg(feature = "admin")]
+use {
+    super::bank_config::{diff_bank_config, BankConfigEntry},
+    marginfi_sdk::instructions::configure_bank,
+};
+
+/// Estimated compute units of the instructions of an action.
+const DEPOSIT_COMPUTE_UNITS: u32 = 40_000;
+const REPAY_COMPUTE_UNITS: u32 = 40_000;
+/// Withdraws and borrows check the account health, and create the destination token account.
+const WITHDRAW_COMPUTE_UNITS: u32 = 120_000;
+const BORROW_COMPUTE_UNITS: u32 = 120_000;
+#[cfg(feature = "admin")]
+const CONFIGURE_BANK_COMPUTE_UNITS: u32 = 30_000;
+
+/// Actions run in order, by `batch run`.
+///
+/// Parsed as JSON for `.json` files, as YAML otherwise.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchFile {
+    /// Checked against the profile group, if set.
+    pub marginfi_group: Option<String>,
+    /// Account of the deposits, withdrawals, borrows and repays, defaults to the profile account.
+    pub marginfi_account: Option<String>,
+    pub actions: Vec<BatchAction>,
+}
+
+/// Amounts are in UI units of the bank mint.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BatchAction {
+    Deposit {
+        bank: String,
+        amount: f64,
+    },
+    Repay {
+        bank: String,
+        amount: Option<f64>,
+        /// Repay the whole liability, closing the balance.
+        #[serde(default)]
+        all: bool,
+    },
+    Withdraw {
+        bank: String,
+        amount: Option<f64>,
+        /// Withdraw the whole deposit, closing the balance.
+        #[serde(default)]
+        all: bool,
+    },
+    Borrow {
+        bank: String,
+        amount: f64,
+    },
+    /// Fields as in the bank entries of `group apply-config`, omitted fields are left unchanged.
+    #[cfg(feature = "admin")]
+    ConfigureBank(Box<BankConfigEntry>),
+}
+
+impl BatchAction {
+    fn uses_account(&self) -> bool {
+        match self {
+            Self::Deposit { .. }
+            | Self::Repay { .. }
+            | Self::Withdraw { .. }
+            | Self::Borrow { .. } => true,
+            #[cfg(feature = "admin")]
+            Self::ConfigureBank(_) => false,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let amount = |amount: &Option<f64>, all: bool| {
+            if all {
+                "all".to_string()
+            } else {
+                amount.unwrap_or_default().to_string()
+            }
+        };
+
+        match self {
+            Self::Deposit { bank, amount } => format!("deposit {} to {}", amount, bank),
+            Self::Repay {
+                bank,
+                amount: repay_amount,
+                all,
+            } => format!("repay {} to {}", amount(repay_amount, *all), bank),
+            Self::Withdraw {
+                bank,
+                amount: withdraw_amount,
+                all,
+            } => format!("withdraw {} from {}", amount(withdraw_amount, *all), bank),
+            Self::Borrow { bank, amount } => format!("borrow {} from {}", amount, bank),
+            #[cfg(feature = "admin")]
+            Self::ConfigureBank(entry) => match &entry.name {
+                Some(name) => format!("configure {} ({})", name, entry.address),
+                None => format!("configure {}", entry.address),
+            },
+        }
+    }
+}
+
+/// Transactions of a batch, with the actions packed in each of them.
+pub struct BatchPlan {
+    splitter: TxSplitter,
+    transactions: Vec<(PlannedTx, Vec<usize>)>,
+    actions: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchActionOutput {
+    pub action: String,
+    /// Index of the transaction of the action, from 1.
+    pub transaction: usize,
+    /// `sent`, `simulated`, `failed`, or `skipped` after an earlier transaction failed.
+    pub status: String,
+    pub signature: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Build the actions of `batch_file` into as few transactions as possible, and print the plan.
+///
+/// Actions keep their file order across transactions, and the health check accounts of an
+/// action include the balances opened or closed by the actions before it.
+pub fn plan_batch(profile: &Profile, config: &Config, batch_file: &Path) -> Result<BatchPlan> {
+    let batch = load_batch_file(batch_file)?;
+
+    let marginfi_group = profile
+        .marginfi_group
+        .ok_or_else(|| anyhow!("marginfi group not set"))?;
+
+    if let Some(file_group) = &batch.marginfi_group {
+        if Pubkey::from_str(file_group)? != marginfi_group {
+            bail!(
+                "Batch file is for group {}, profile group is {}",
+                file_group,
+                marginfi_group
+            );
+        }
+    }
+
+    if batch.actions.is_empty() {
+        bail!("No actions in {}", batch_file.display());
+    }
+
+    let banks: HashMap<Pubkey, Bank> =
+        HashMap::from_iter(load_all_banks(config, Some(marginfi_group))?);
+
+    let mut marginfi_account = if batch.actions.iter().any(BatchAction::uses_account) {
+        let marginfi_account_pk = match &batch.marginfi_account {
+            Some(address) => Pubkey::from_str(address)?,
+            None => profile.get_marginfi_account(),
+        };
+        let marginfi_account = config
+            .mfi_program
+            .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+        if marginfi_account.group != marginfi_group {
+            bail!(
+                "Account {} does not belong to group {}",
+                marginfi_account_pk,
+                marginfi_group
+            );
+        }
+
+        Some((marginfi_account_pk, marginfi_account))
+    } else {
+        None
+    };
+
+    let fee_state = load_fee_state(&config.mfi_program.rpc(), &config.program_id)?;
+
+    let mut groups = vec![];
+    for (i, action) in batch.actions.iter().enumerate() {
+        let group = build_action(
+            config,
+            marginfi_group,
+            &banks,
+            fee_state.as_ref(),
+            marginfi_account.as_mut(),
+            action,
+        )
+        .map_err(|err| anyhow!("Action {} ({}): {}", i + 1, action.describe(), err))?;
+
+        groups.push(group);
+    }
+
+    let group_lens = groups
+        .iter()
+        .map(|group| group.instructions.len())
+        .collect::<Vec<_>>();
+
+    let mut splitter = TxSplitter::new(config.authority());
+    splitter.compute_unit_price = config.priority_fee;
+    // Groups all have the same priority and health impact, so the split keeps their order
+    let split_plan = splitter.split(groups)?;
+
+    let compute_budget_ixs = if splitter.compute_unit_price.is_some() {
+        2
+    } else {
+        1
+    };
+
+    let mut next_action = 0;
+    let transactions = split_plan
+        .transactions
+        .into_iter()
+        .map(|tx| {
+            let mut ixs_count = tx.instructions.len() - compute_budget_ixs;
+            let mut actions = vec![];
+            while ixs_count > 0 {
+                ixs_count -= group_lens[next_action];
+                actions.push(next_action);
+                next_action += 1;
+            }
+
+            (tx, actions)
+        })
+        .collect::<Vec<_>>();
+
+    let actions = batch
+        .actions
+        .iter()
+        .map(|action| action.describe())
+        .collect::<Vec<_>>();
+
+    println!("{} actions in {} txs", actions.len(), transactions.len());
+    for (i, (_, tx_actions)) in transactions.iter().enumerate() {
+        println!("Tx {}", i + 1);
+        for action in tx_actions.iter() {
+            println!("\t[{}] {}", action + 1, actions[*action]);
+        }
+    }
+
+    Ok(BatchPlan {
+        splitter,
+        transactions,
+        actions,
+    })
+}
+
+/// Send, or simulate with `--dry-run`, the transactions of a batch in order, and print the
+/// result of each action.
+///
+/// Sending stops at the first failed transaction, later actions are skipped. Transactions are
+/// simulated independently against the current state, so a simulated action relying on
+/// an earlier transaction of the batch, e.g. withdrawing a deposit made by it, may fail.
+pub fn run_batch(config: &Config, plan: BatchPlan) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+    let signing_keypairs = config.get_signers(false);
+    let txs_count = plan.transactions.len();
+
+    let mut results: Vec<Option<Result<Signature, String>>> = vec![None; txs_count];
+
+    for (i, (tx, _)) in plan.transactions.iter().enumerate() {
+        let blockhash = rpc_client.get_latest_blockhash()?;
+
+        let mut transaction = plan.splitter.to_transaction(tx);
+        transaction.partial_sign(&signing_keypairs, blockhash);
+
+        let result = process_transaction(&transaction, &rpc_client, config.get_tx_mode());
+
+        match &result {
+            Ok(sig) => println!("Sent [{}/{}] {}", i + 1, txs_count, sig),
+            Err(err) => println!("Failed [{}/{}]:\n{:#}", i + 1, txs_count, err),
+        }
+
+        let failed = result.is_err();
+        results[i] = Some(result.map_err(|err| format!("{:#}", err)));
+
+        if failed && !config.dry_run {
+            break;
+        }
+    }
+
+    let actions = &plan.actions;
+    let outputs = plan
+        .transactions
+        .iter()
+        .zip(results.iter())
+        .enumerate()
+        .flat_map(|(i, ((_, tx_actions), result))| {
+            tx_actions.iter().map(move |action| {
+                let (status, signature, error) = match result {
+                    Some(Ok(_)) if config.dry_run => ("simulated", None, None),
+                    Some(Ok(sig)) => ("sent", Some(sig.to_string()), None),
+                    Some(Err(err)) => ("failed", None, Some(err.clone())),
+                    None => ("skipped", None, None),
+                };
+
+                BatchActionOutput {
+                    action: actions[*action].clone(),
+                    transaction: i + 1,
+                    status: status.to_string(),
+                    signature,
+                    error,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let failed_count = outputs
+        .iter()
+        .filter(|output| output.status == "failed" || output.status == "skipped")
+        .count();
+
+    if config.json_output() {
+        print_json(&outputs)?;
+    } else {
+        for (i, output) in outputs.iter().enumerate() {
+            println!(
+                "[{}] {}: {}{}",
+                i + 1,
+                output.action,
+                output.status,
+                output
+                    .signature
+                    .as_ref()
+                    .map(|sig| format!(" {}", sig))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    if failed_count > 0 {
+        bail!(
+            "{} of {} actions failed or were skipped",
+            failed_count,
+            outputs.len()
+        );
+    }
+
+    Ok(())
+}
+
+fn load_batch_file(batch_file: &Path) -> Result<BatchFile> {
+    let content = fs::read_to_string(batch_file)?;
+
+    let batch = match batch_file
+        .extension()
+        .and_then(|extension| extension.to_str())
+    {
+        Some("json") => serde_json::from_str(&content)?,
+        _ => serde_yaml::from_str(&content)?,
+    };
+
+    Ok(batch)
+}
+
+fn build_action(
+    config: &Config,
+    marginfi_group: Pubkey,
+    banks: &HashMap<Pubkey, Bank>,
+    fee_state: Option<&FeeState>,
+    marginfi_account: Option<&mut (Pubkey, MarginfiAccount)>,
+    action: &BatchAction,
+) -> Result<IxGroup> {
+    let bank_pk = match action {
+        BatchAction::Deposit { bank, .. }
+        | BatchAction::Repay { bank, .. }
+        | BatchAction::Withdraw { bank, .. }
+        | BatchAction::Borrow { bank, .. } => Pubkey::from_str(bank)?,
+        #[cfg(feature = "admin")]
+        BatchAction::ConfigureBank(entry) => Pubkey::from_str(&entry.address)?,
+    };
+    let bank = banks
+        .get(&bank_pk)
+        .ok_or_else(|| anyhow!("Bank {} not found in group {}", bank_pk, marginfi_group))?;
+
+    #[cfg(feature = "admin")]
+    if let BatchAction::ConfigureBank(entry) = action {
+        let bank_change = diff_bank_config(bank_pk, action.describe(), bank, entry)?;
+        if bank_change.changes.is_empty() {
+            println!("{}: no changes", bank_change.label);
+        }
+        for change in bank_change.changes.iter() {
+            println!("{}: {}", bank_change.label, change);
+        }
+
+        return Ok(IxGroup::new(
+            vec![configure_bank(
+                &config.program_id,
+                marginfi_group,
+                config.authority(),
+                bank_pk,
+                bank_change.bank_config_opt,
+            )],
+            CONFIGURE_BANK_COMPUTE_UNITS,
+        ));
+    }
+
+    let (marginfi_account_pk, marginfi_account) =
+        marginfi_account.ok_or_else(|| anyhow!("No marginfi account"))?;
+    let ctx = MarginfiAccountContext::new(
+        config.program_id,
+        *marginfi_account_pk,
+        marginfi_account,
+        banks,
+    );
+    let native = |amount: f64| ui_to_native(amount, bank.mint_decimals);
+    // Amount of a repay or withdraw, ignored when closing the balance
+    let native_or_all = |amount: &Option<f64>, all: bool| -> Result<u64> {
+        match (amount, all) {
+            (_, true) => Ok(0),
+            (Some(amount), false) => Ok(native(*amount)),
+            (None, false) => bail!("Either an amount or all must be set"),
+        }
+    };
+    let create_ata_ix = create_associated_token_account_idempotent(
+        &config.authority(),
+        &marginfi_account.authority,
+        &bank.mint,
+        &spl_token::ID,
+    );
+
+    let (group, opened, closed) = match action {
+        BatchAction::Deposit { amount, .. } => (
+            IxGroup::new(
+                vec![ctx.deposit(bank_pk, native(*amount))?],
+                DEPOSIT_COMPUTE_UNITS,
+            ),
+            true,
+            false,
+        ),
+        BatchAction::Repay { amount, all, .. } => (
+            IxGroup::new(
+                vec![ctx.repay(bank_pk, native_or_all(amount, *all)?, *all)?],
+                REPAY_COMPUTE_UNITS,
+            ),
+            false,
+            *all,
+        ),
+        BatchAction::Withdraw { amount, all, .. } => (
+            IxGroup::new(
+                vec![
+                    create_ata_ix,
+                    ctx.withdraw(bank_pk, native_or_all(amount, *all)?, *all)?,
+                ],
+                WITHDRAW_COMPUTE_UNITS,
+            ),
+            false,
+            *all,
+        ),
+        BatchAction::Borrow { amount, .. } => (
+            IxGroup::new(
+                vec![
+                    create_ata_ix,
+                    ctx.borrow(bank_pk, native(*amount), fee_state)?,
+                ],
+                BORROW_COMPUTE_UNITS,
+            ),
+            true,
+            false,
+        ),
+        #[cfg(feature = "admin")]
+        BatchAction::ConfigureBank(_) => unreachable!(),
+    };
+
+    // Later actions see the balances opened or closed by this one in their health check accounts
+    let lending_account = &mut marginfi_account.lending_account;
+    let balance_index = lending_account
+        .balances
+        .iter()
+        .position(|balance| balance.active && balance.bank_pk == bank_pk);
+
+    match balance_index {
+        None if opened => {
+            let empty_index = lending_account
+                .get_first_empty_balance()
+                .ok_or_else(|| anyhow!("No empty balance slot for bank {}", bank_pk))?;
+
+            let balance = &mut lending_account.balances[empty_index];
+            balance.active = true;
+            balance.bank_pk = bank_pk;
+        }
+        Some(index) if closed => lending_account.balances[index].active = false,
+        _ => {}
+    }
+
+    Ok(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_file_parses_yaml_and_json() {
+        let yaml = r#"
+marginfi_group: 4qp6Fx6tnZkY5Wropq9wUYgtFxXKwE6viZxFHg3rdAG8
+actions:
+  - type: withdraw
+    bank: CCKtUs6Cgwo4aaQUmBPmyoApH2gUDErxNZCAntD6LYGh
+    all: true
+  - type: deposit
+    bank: 2s37akK2eyBbp8DZgCm7RtsaEz8eJP3Nxd4urLHQv7yB
+    amount: 1000
+"#;
+        let batch: BatchFile = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(batch.actions.len(), 2);
+        assert!(matches!(
+            batch.actions[0],
+            BatchAction::Withdraw {
+                amount: None,
+                all: true,
+                ..
+            }
+        ));
+        assert_eq!(
+            batch.actions[1].describe(),
+            "deposit 1000 to 2s37akK2eyBbp8DZgCm7RtsaEz8eJP3Nxd4urLHQv7yB"
+        );
+
+        let json = r#"{"actions": [{"type": "borrow", "bank": "CCKtUs6Cgwo4aaQUmBPmyoApH2gUDErxNZCAntD6LYGh", "amount": 2.5}]}"#;
+        let batch: BatchFile = serde_json::from_str(json).unwrap();
+        assert!(matches!(batch.actions[0], BatchAction::Borrow { amount, .. } if amount == 2.5));
+    }
+
+    #[test]
+    fn batch_file_rejects_unknown_actions_and_fields() {
+        let unknown_action = r#"{"actions": [{"type": "liquidate", "bank": "CCKtUs6Cgwo4aaQUmBPmyoApH2gUDErxNZCAntD6LYGh"}]}"#;
+        assert!(serde_json::from_str::<BatchFile>(unknown_action).is_err());
+
+        let unknown_field = r#"{"actions": [{"type": "deposit", "bank": "CCKtUs6Cgwo4aaQUmBPmyoApH2gUDErxNZCAntD6LYGh", "amount": 1, "all": true}]}"#;
+        assert!(serde_json::from_str::<BatchFile>(unknown_field).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use {
+    super::load_all_banks,
+    crate::{config::Config, profile::Profile, retry_queue::RetryQueue},
+    anyhow::{bail, Result},
+    log::{info, warn},
+    marginfi::state::marginfi_group::{Bank, BankVaultType},
+    marginfi_sdk::{
+        instructions::{accrue_bank_interest, accrue_banks},
+        pda::{find_bank_vault_authority_pda, find_bank_vault_pda},
+    },
+    solana_sdk::{instruction::AccountMeta, pubkey::Pubkey},
+    spl_associated_token_account::get_associated_token_address,
+    std::{
+        path::Path,
+        thread,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+const MAX_ATTEMPTS: u32 = 10;
+/// How long landed transactions are remembered for duplicate suppression.
+const LANDED_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+/// How often the queue is drained between two crank rounds.
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Banks accrued by a single `lending_pool_accrue_banks` transaction.
+const ACCRUE_BANKS_CHUNK_SIZE: usize = 20;
+
+/// Periodically accrue interest on `banks` (all group banks if empty), every `interval_secs`.
+///
+/// Crank transactions go through a persistent retry queue at `queue_path`,
+/// so a restarted crank resumes pending retries and doesn't resend a round that already landed.
+///
+/// Banks are accrued in bulk, except with `collect_tips`, where banks paying a keeper tip
+/// are accrued one by one, tipping the authority's associated token account for the bank mint,
+/// which must already exist.
+pub fn crank_accrue_interest(
+    config: &Config,
+    profile: &Profile,
+    banks: Vec<Pubkey>,
+    interval_secs: u64,
+    queue_path: &Path,
+    collect_tips: bool,
+) -> Result<()> {
+    if config.multisig.is_some() || config.dry_run {
+        bail!("Crank mode is not supported in multisig or dry run mode");
+    }
+
+    let group = profile.marginfi_group.expect("group not set");
+    let queue = RetryQueue::open(queue_path, MAX_ATTEMPTS, LANDED_RETENTION_SECS)?;
+
+    info!(
+        "Starting interest crank for group {} ({} pending txs in {})",
+        group,
+        queue.len(),
+        queue_path.display()
+    );
+
+    loop {
+        let round = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() / interval_secs;
+
+        let group_banks = load_all_banks(config, Some(group))?;
+
+        if let Some(bank) = banks
+            .iter()
+            .find(|bank| !group_banks.iter().any(|(address, _)| address == *bank))
+        {
+            bail!("Bank {} not found in group {}", bank, group);
+        }
+
+        // Sorted for the bulk accrual chunks, and their queue keys, to be stable across rounds
+        let mut selected_banks = group_banks
+            .into_iter()
+            .filter(|(address, _)| banks.is_empty() || banks.contains(address))
+            .collect::<Vec<_>>();
+        selected_banks.sort_by_key(|(address, _)| *address);
+
+        let (tipping_banks, bulk_banks): (Vec<_>, Vec<_>) = selected_banks
+            .into_iter()
+            .partition(|(_, bank)| collect_tips && bank.config.keeper_tip > 0);
+
+        for (i, chunk) in bulk_banks.chunks(ACCRUE_BANKS_CHUNK_SIZE).enumerate() {
+            let bank_pks = chunk
+                .iter()
+                .map(|(address, _)| *address)
+                .collect::<Vec<_>>();
+            let ix = accrue_banks(&config.program_id, group, &bank_pks);
+
+            if queue.enqueue(&format!("accrue_banks:{}:{}", i, round), vec![ix])? {
+                info!(
+                    "Enqueued interest accrual for {} banks (round {})",
+                    bank_pks.len(),
+                    round
+                );
+            }
+        }
+
+        for (bank_pk, bank_account) in tipping_banks {
+            let mut ix = accrue_bank_interest(&config.program_id, group, bank_pk);
+            ix.accounts
+                .extend(keeper_tip_account_metas(config, &bank_pk, &bank_account));
+
+            if queue.enqueue(&format!("accrue_interest:{}:{}", bank_pk, round), vec![ix])? {
+                info!(
+                    "Enqueued interest accrual for bank {} (round {})",
+                    bank_pk, round
+                );
+            }
+        }
+
+        // Drain the queue until the next round
+        while SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() / interval_secs == round {
+            if !queue.is_empty() {
+                let stats = queue.process(config)?;
+
+                if stats.landed + stats.retried + stats.dropped > 0 {
+                    println!(
+                        "Landed {}, retrying {}, dropped {}, pending {}",
+                        stats.landed,
+                        stats.retried,
+                        stats.dropped,
+                        queue.len()
+                    );
+                }
+            }
+
+            thread::sleep(QUEUE_POLL_INTERVAL);
+        }
+    }
+}
+
+/// Remaining accounts tipping the authority on permissionless cranks of `bank`.
+fn keeper_tip_account_metas(config: &Config, bank_pk: &Pubkey, bank: &Bank) -> Vec<AccountMeta> {
+    let keeper_token_account = get_associated_token_address(&config.authority(), &bank.mint);
+
+    if config
+        .mfi_program
+        .rpc()
+        .get_account(&keeper_token_account)
+        .is_err()
+    {
+        warn!(
+            "Keeper token account {} not found, bank {} won't tip this crank",
+            keeper_token_account, bank_pk
+        );
+        return vec![];
+    }
+
+    vec![
+        AccountMeta::new(
+            find_bank_vault_pda(bank_pk, BankVaultType::Fee, &config.program_id).0,
+            false,
+        ),
+        AccountMeta::new_readonly(
+            find_bank_vault_authority_pda(bank_pk, BankVaultType::Fee, &config.program_id).0,
+            false,
+        ),
+        AccountMeta::new(keeper_token_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]
+}
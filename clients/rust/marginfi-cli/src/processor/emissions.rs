@@ -1,15 +1,19 @@
 use {
-    crate::{config::Config, profile::Profile},
-    anchor_client::anchor_lang::{AnchorSerialize, InstructionData, ToAccountMetas},
+    crate::{
+        config::Config,
+        profile::Profile,
+        tx_splitter::{IxGroup, TxSplitter},
+    },
+    anchor_client::anchor_lang::AnchorSerialize,
     anyhow::Result,
     marginfi::state::marginfi_account::MarginfiAccount,
+    marginfi_sdk::instructions::settle_emissions,
     solana_client::rpc_filter::{Memcmp, RpcFilterType},
-    solana_sdk::{
-        instruction::Instruction, message::Message, pubkey::Pubkey, transaction::Transaction,
-    },
+    solana_sdk::pubkey::Pubkey,
 };
 
-const CHUNK_SIZE: usize = 22;
+/// Estimated compute units of a single settle emissions instruction.
+const SETTLE_EMISSIONS_COMPUTE_UNITS: u32 = 20_000;
 
 pub fn claim_all_emissions_for_bank(
     config: &Config,
@@ -30,7 +34,7 @@ pub fn claim_all_emissions_for_bank(
                 group.try_to_vec()?,
             ))])?;
 
-    let ixs = marginfi_accounts
+    let groups = marginfi_accounts
         .into_iter()
         .filter_map(|(address, account)| {
             if account
@@ -44,35 +48,33 @@ pub fn claim_all_emissions_for_bank(
                 None
             }
         })
-        .map(|address| Instruction {
-            program_id: marginfi::id(),
-            accounts: marginfi::accounts::LendingAccountSettleEmissions {
-                marginfi_account: address,
-                bank: bank_pk,
-            }
-            .to_account_metas(Some(true)),
-            data: marginfi::instruction::LendingAccountSettleEmissions {}.data(),
+        .map(|address| {
+            IxGroup::new(
+                vec![settle_emissions(&config.program_id, address, bank_pk)],
+                SETTLE_EMISSIONS_COMPUTE_UNITS,
+            )
         })
         .collect::<Vec<_>>();
 
-    println!("Found {} accounts", ixs.len());
+    println!("Found {} accounts", groups.len());
 
-    let ixs_batches = ixs.chunks(CHUNK_SIZE);
-    let ixs_batches_count = ixs_batches.len();
+    let mut splitter = TxSplitter::new(config.authority());
+    splitter.compute_unit_price = config.priority_fee;
+    let plan = splitter.split(groups)?;
+    let txs_count = plan.transactions.len();
 
     // Send txs and show progress to user [n/total]
-    println!("Sending {} txs", ixs_batches_count);
+    println!("Sending {} txs", txs_count);
 
-    for (i, ixs) in ixs_batches.enumerate() {
+    for (i, tx) in plan.transactions.iter().enumerate() {
         let blockhash = rpc_client.get_latest_blockhash()?;
 
-        let message = Message::new(ixs, Some(&config.authority()));
-        let mut transaction = Transaction::new_unsigned(message);
+        let mut transaction = splitter.to_transaction(tx);
         transaction.partial_sign(&signing_keypairs, blockhash);
 
         let sig = rpc_client.send_and_confirm_transaction_with_spinner(&transaction)?;
 
-        println!("Sent [{}/{}] {}", i + 1, ixs_batches_count, sig);
+        println!("Sent [{}/{}] {}", i + 1, txs_count, sig);
     }
 
     println!("Done!");
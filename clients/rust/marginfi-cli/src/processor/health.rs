@@ -0,0 +1,624 @@
+use {
+    super::{load_all_banks, load_bank, timestamp_to_string},
+    crate::{config::Config, profile::Profile, utils::EXP_10_I80F48},
+    anchor_client::anchor_lang::AccountDeserialize,
+    anyhow::{anyhow, bail, Result},
+    fixed::types::I80F48,
+    log::warn,
+    marginfi::{
+        constants::MAX_PRICE_AGE_SEC,
+        state::{
+            amounts::ShareAmount,
+            marginfi_account::{
+                calc_asset_value, Balance, BalanceSide, MarginfiAccount, WeightType,
+            },
+            marginfi_group::Bank,
+            price::{OraclePriceFeedAdapter, PriceAdapter},
+        },
+    },
+    marginfi_sdk::{
+        accounts::{load_multiple, MAX_MULTIPLE_ACCOUNTS},
+        math::calc_weighted_values,
+    },
+    solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig},
+    solana_client::{
+        pubsub_client::PubsubClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{
+        account::Account,
+        account_info::IntoAccountInfo,
+        clock::Clock,
+        pubkey::Pubkey,
+        sysvar::{self, Sysvar},
+    },
+    std::{
+        collections::{hash_map::Entry, HashMap},
+        mem::size_of,
+        sync::mpsc,
+        thread,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+struct BalanceHealth {
+    bank_pk: Pubkey,
+    mint: Pubkey,
+    side: BalanceSide,
+    /// Asset or liability amount, in UI units.
+    amount: I80F48,
+    price: I80F48,
+    /// Unweighted value of the balance.
+    value: I80F48,
+    stale_oracle: bool,
+    /// (asset, liability) weights.
+    init_weights: (I80F48, I80F48),
+    maint_weights: (I80F48, I80F48),
+    /// (weighted assets, weighted liabilities) values.
+    init_values: (I80F48, I80F48),
+    maint_values: (I80F48, I80F48),
+}
+
+/// Print every balance of a marginfi account with its USD value and weights,
+/// and the initial and maintenance health of the account.
+///
+/// Values are computed like the on-chain risk engine, assets at the low end of the oracle
+/// confidence range and liabilities at the high end. Oracles older than `MAX_PRICE_AGE_SEC`
+/// are still used, but flagged, as health checks would fail on-chain.
+pub fn marginfi_account_show(
+    profile: &Profile,
+    config: &Config,
+    marginfi_account_pk: Option<Pubkey>,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_account_pk = marginfi_account_pk.unwrap_or_else(|| profile.get_marginfi_account());
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    let mut clock = rpc_client.get_account(&sysvar::clock::ID)?;
+    let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
+
+    let balances = marginfi_account
+        .lending_account
+        .get_active_balances_iter()
+        .map(|balance| {
+            let bank = load_bank(config, balance.bank_pk)?;
+            let oracle_pk = bank.config.oracle_keys[0];
+            let oracle_account = rpc_client.get_account(&oracle_pk)?;
+            load_balance_health(
+                balance,
+                &bank,
+                (oracle_pk, oracle_account),
+                clock.unix_timestamp,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    println!("Address: {}", marginfi_account_pk);
+    println!("Group: {}", marginfi_account.group);
+    println!("Authority: {}", marginfi_account.authority);
+    println!("Balances:");
+
+    for balance in balances.iter() {
+        let (side, init_weight, maint_weight) = match balance.side {
+            BalanceSide::Assets => ("Asset", balance.init_weights.0, balance.maint_weights.0),
+            BalanceSide::Liabilities => {
+                ("Liability", balance.init_weights.1, balance.maint_weights.1)
+            }
+        };
+
+        println!(
+            "\t{} {:.6} (bank: {}, mint: {})\n\t\tPrice: ${:.6}{}, Value: ${:.2}, Weights: init {:.2} / maint {:.2}",
+            side,
+            balance.amount,
+            balance.bank_pk,
+            balance.mint,
+            balance.price,
+            if balance.stale_oracle { " (stale)" } else { "" },
+            balance.value,
+            init_weight,
+            maint_weight,
+        );
+    }
+
+    print_health(
+        "Initial",
+        sum_values(&balances, |balance| balance.init_values),
+    );
+    print_health(
+        "Maintenance",
+        sum_values(&balances, |balance| balance.maint_values),
+    );
+
+    if balances.iter().any(|balance| balance.stale_oracle) {
+        println!(
+            "Warning: some oracles are older than {}s or out of their bank price band, risk increasing actions would fail",
+            MAX_PRICE_AGE_SEC
+        );
+    }
+
+    Ok(())
+}
+
+struct LiquidationCandidate {
+    address: Pubkey,
+    authority: Pubkey,
+    /// Maintenance (weighted assets, weighted liabilities) values.
+    maint_values: (I80F48, I80F48),
+    /// Unweighted value of the account assets, the upper bound of what liquidators can seize.
+    seizable_value: I80F48,
+    stale_oracle: bool,
+}
+
+/// List the accounts of a group with a maintenance health factor below `max_health_factor`,
+/// sorted by seizable value.
+///
+/// Account addresses are fetched with a single `getProgramAccounts` request without their data,
+/// which is then loaded in pages of `page_size` accounts. Banks and oracles are loaded once, and
+/// health is computed off-chain like `account show` does.
+pub fn risk_scan(
+    config: &Config,
+    marginfi_group: Pubkey,
+    max_health_factor: f64,
+    page_size: usize,
+    limit: Option<usize>,
+) -> Result<()> {
+    if page_size == 0 || page_size > MAX_MULTIPLE_ACCOUNTS {
+        bail!("Page size must be between 1 and {}", MAX_MULTIPLE_ACCOUNTS);
+    }
+
+    let rpc_client = config.mfi_program.rpc();
+    let max_health_factor = I80F48::from_num(max_health_factor);
+
+    let mut clock = rpc_client.get_account(&sysvar::clock::ID)?;
+    let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
+
+    let banks = load_all_banks(config, Some(marginfi_group))?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let mut oracle_pks = banks
+        .values()
+        .map(|bank| bank.config.oracle_keys[0])
+        .collect::<Vec<_>>();
+    oracle_pks.sort();
+    oracle_pks.dedup();
+
+    let mut oracles = HashMap::new();
+    for chunk in oracle_pks.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (oracle_pk, oracle_account) in
+            chunk.iter().zip(rpc_client.get_multiple_accounts(chunk)?)
+        {
+            match oracle_account {
+                Some(oracle_account) => {
+                    oracles.insert(*oracle_pk, oracle_account);
+                }
+                None => println!("Warning: oracle {} not found", oracle_pk),
+            }
+        }
+    }
+
+    let marginfi_account_pks = rpc_client
+        .get_program_accounts_with_config(
+            &config.program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize((8 + size_of::<MarginfiAccount>()) as u64),
+                    RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                        8,
+                        marginfi_group.to_bytes().to_vec(),
+                    )),
+                ]),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig {
+                        offset: 0,
+                        length: 0,
+                    }),
+                    commitment: Some(config.commitment),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?
+        .into_iter()
+        .map(|(address, _)| address)
+        .collect::<Vec<_>>();
+
+    println!(
+        "Scanning {} accounts of group {}",
+        marginfi_account_pks.len(),
+        marginfi_group
+    );
+
+    let mut candidates = vec![];
+    let mut skipped = 0;
+    for page in marginfi_account_pks.chunks(page_size) {
+        let accounts = load_multiple::<MarginfiAccount>(&rpc_client, page)?;
+
+        for (address, marginfi_account) in page.iter().zip(accounts) {
+            // Closed since the scan started
+            let marginfi_account = match marginfi_account {
+                Some(marginfi_account) => marginfi_account,
+                None => continue,
+            };
+
+            match load_liquidation_candidate(
+                *address,
+                &marginfi_account,
+                &banks,
+                &oracles,
+                clock.unix_timestamp,
+            ) {
+                Ok(Some(candidate)) => {
+                    let (assets, liabilities) = candidate.maint_values;
+                    if assets < liabilities * max_health_factor {
+                        candidates.push(candidate);
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    skipped += 1;
+                    println!("Warning: skipping account {}: {}", address, err);
+                }
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| b.seizable_value.cmp(&a.seizable_value));
+
+    println!(
+        "{} accounts with a maintenance health factor below {}{}",
+        candidates.len(),
+        max_health_factor,
+        if skipped > 0 {
+            format!(" ({} accounts skipped)", skipped)
+        } else {
+            "".to_string()
+        }
+    );
+
+    for candidate in candidates.iter().take(limit.unwrap_or(usize::MAX)) {
+        let (assets, liabilities) = candidate.maint_values;
+
+        println!(
+            "{} (authority: {})\n\tHealth factor: {:.4}{}, Seizable value: ${:.2}, Maintenance health: ${:.2} (weighted assets: ${:.2}, weighted liabilities: ${:.2})",
+            candidate.address,
+            candidate.authority,
+            assets / liabilities,
+            if candidate.stale_oracle { " (stale oracles)" } else { "" },
+            candidate.seizable_value,
+            assets - liabilities,
+            assets,
+            liabilities,
+        );
+    }
+
+    Ok(())
+}
+
+/// Subscribe to a marginfi account and the oracles of its banks, and print its maintenance
+/// health factor on every update.
+///
+/// An alert is printed, and posted to `webhook_url` as JSON, every time the health factor
+/// crosses `threshold` in either direction. Banks are reloaded when the account opens a
+/// balance in a bank it had no position in, but not on bank config or interest updates.
+pub fn marginfi_account_watch(
+    profile: &Profile,
+    config: &Config,
+    marginfi_account_pk: Option<Pubkey>,
+    threshold: f64,
+    webhook_url: Option<String>,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+    let ws_url = websocket_url(&profile.rpc_url);
+    let threshold = I80F48::from_num(threshold);
+
+    let marginfi_account_pk = marginfi_account_pk.unwrap_or_else(|| profile.get_marginfi_account());
+    let mut marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+    let mut banks = load_all_banks(config, Some(marginfi_account.group))?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let mut oracles = HashMap::new();
+
+    let http_client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    // Every subscription forwards its updates to a single channel
+    let (sender, receiver) = mpsc::channel::<(Pubkey, Account)>();
+    let subscribe = |address: Pubkey| -> Result<()> {
+        let (subscription, updates) = PubsubClient::account_subscribe(
+            &ws_url,
+            &address,
+            Some(RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(config.commitment),
+                ..Default::default()
+            }),
+        )
+        .map_err(|err| anyhow!("Failed to subscribe to {}: {}", address, err))?;
+
+        let sender = sender.clone();
+        thread::spawn(move || {
+            // Dropping the subscription unsubscribes
+            let _subscription = subscription;
+            for update in updates.iter() {
+                if let Some(account) = update.value.decode::<Account>() {
+                    if sender.send((address, account)).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    };
+
+    subscribe(marginfi_account_pk)?;
+
+    println!(
+        "Watching account {} (threshold: {}, websocket: {})",
+        marginfi_account_pk, threshold, ws_url
+    );
+
+    let mut below_threshold = None;
+    loop {
+        // Subscribe to the oracles of the banks the account opened a position in
+        for balance in marginfi_account.lending_account.get_active_balances_iter() {
+            if !banks.contains_key(&balance.bank_pk) {
+                banks = load_all_banks(config, Some(marginfi_account.group))?
+                    .into_iter()
+                    .collect();
+            }
+            let bank = banks
+                .get(&balance.bank_pk)
+                .ok_or_else(|| anyhow!("Bank {} not found", balance.bank_pk))?;
+            let oracle_pk = bank.config.oracle_keys[0];
+
+            if let Entry::Vacant(entry) = oracles.entry(oracle_pk) {
+                entry.insert(rpc_client.get_account(&oracle_pk)?);
+                subscribe(oracle_pk)?;
+            }
+        }
+
+        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let timestamp = timestamp_to_string(current_timestamp);
+
+        match load_liquidation_candidate(
+            marginfi_account_pk,
+            &marginfi_account,
+            &banks,
+            &oracles,
+            current_timestamp,
+        )? {
+            Some(candidate) => {
+                let (assets, liabilities) = candidate.maint_values;
+                let health_factor = assets.checked_div(liabilities).unwrap_or(I80F48::MAX);
+                let is_below = health_factor < threshold;
+
+                println!(
+                    "[{}] Health factor: {:.4}{}, Maintenance health: ${:.2}",
+                    timestamp,
+                    health_factor,
+                    if candidate.stale_oracle {
+                        " (stale oracles)"
+                    } else {
+                        ""
+                    },
+                    assets - liabilities,
+                );
+
+                if below_threshold != Some(is_below) {
+                    // Only alert on the initial state when it is already below the threshold
+                    if is_below || below_threshold.is_some() {
+                        let message = if is_below {
+                            format!(
+                                "Account {} health factor {:.4} is below {}",
+                                marginfi_account_pk, health_factor, threshold
+                            )
+                        } else {
+                            format!(
+                                "Account {} health factor {:.4} is back above {}",
+                                marginfi_account_pk, health_factor, threshold
+                            )
+                        };
+
+                        warn!("{}", message);
+                        println!("[{}] ALERT: {}", timestamp, message);
+
+                        if let Some(webhook_url) = &webhook_url {
+                            let payload = serde_json::json!({
+                                "account": marginfi_account_pk.to_string(),
+                                "health_factor": health_factor.to_num::<f64>(),
+                                "threshold": threshold.to_num::<f64>(),
+                                "below_threshold": is_below,
+                                "timestamp": current_timestamp,
+                                "message": message,
+                            });
+
+                            if let Err(err) = http_client
+                                .post(webhook_url)
+                                .json(&payload)
+                                .send()
+                                .and_then(|response| response.error_for_status())
+                            {
+                                warn!("Failed to call webhook: {}", err);
+                            }
+                        }
+                    }
+
+                    below_threshold = Some(is_below);
+                }
+            }
+            None => println!("[{}] No liabilities", timestamp),
+        }
+
+        // Wait for the next update, the subscriptions only end with the process
+        let (address, account) = receiver
+            .recv()
+            .map_err(|_| anyhow!("All subscriptions closed"))?;
+
+        if address == marginfi_account_pk {
+            marginfi_account = MarginfiAccount::try_deserialize(&mut account.data.as_slice())?;
+        } else {
+            oracles.insert(address, account);
+        }
+    }
+}
+
+/// Websocket endpoint of an RPC node, on the next port for local validators.
+fn websocket_url(rpc_url: &str) -> String {
+    let ws_url = rpc_url
+        .replacen("https://", "wss://", 1)
+        .replacen("http://", "ws://", 1);
+
+    match ws_url.rsplit_once(':') {
+        Some((base, port)) if ws_url.starts_with("ws://") => match port.parse::<u16>() {
+            Ok(port) => format!("{}:{}", base, port + 1),
+            Err(_) => ws_url,
+        },
+        _ => ws_url,
+    }
+}
+
+/// Maintenance health of an account with liabilities, `None` if the account has no liabilities.
+fn load_liquidation_candidate(
+    address: Pubkey,
+    marginfi_account: &MarginfiAccount,
+    banks: &HashMap<Pubkey, Bank>,
+    oracles: &HashMap<Pubkey, Account>,
+    current_timestamp: i64,
+) -> Result<Option<LiquidationCandidate>> {
+    let balances = marginfi_account
+        .lending_account
+        .get_active_balances_iter()
+        .map(|balance| {
+            let bank = banks
+                .get(&balance.bank_pk)
+                .ok_or_else(|| anyhow!("Bank {} not found", balance.bank_pk))?;
+            let oracle_pk = bank.config.oracle_keys[0];
+            let oracle_account = oracles
+                .get(&oracle_pk)
+                .ok_or_else(|| anyhow!("Oracle {} not found", oracle_pk))?;
+
+            load_balance_health(
+                balance,
+                bank,
+                (oracle_pk, oracle_account.clone()),
+                current_timestamp,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if !balances
+        .iter()
+        .any(|balance| matches!(balance.side, BalanceSide::Liabilities))
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(LiquidationCandidate {
+        address,
+        authority: marginfi_account.authority,
+        maint_values: sum_values(&balances, |balance| balance.maint_values),
+        seizable_value: balances
+            .iter()
+            .filter(|balance| matches!(balance.side, BalanceSide::Assets))
+            .map(|balance| balance.value)
+            .sum(),
+        stale_oracle: balances.iter().any(|balance| balance.stale_oracle),
+    }))
+}
+
+fn load_balance_health(
+    balance: &Balance,
+    bank: &Bank,
+    (oracle_pk, mut oracle_account): (Pubkey, Account),
+    current_timestamp: i64,
+) -> Result<BalanceHealth> {
+    let oracle_ai = (&oracle_pk, &mut oracle_account).into_account_info();
+
+    let (price_feed, stale_oracle) = match OraclePriceFeedAdapter::try_from_bank_config(
+        &bank.config,
+        &[oracle_ai.clone()],
+        current_timestamp,
+        MAX_PRICE_AGE_SEC,
+    ) {
+        Ok(price_feed) => (price_feed, false),
+        Err(_) => (
+            OraclePriceFeedAdapter::try_from_bank_config_ignoring_price_band(
+                &bank.config,
+                &[oracle_ai],
+                0,
+                u64::MAX,
+            )
+            .map_err(|err| anyhow!("Failed to load oracle {}: {:?}", oracle_pk, err))?,
+            true,
+        ),
+    };
+
+    let (side, native_amount) = if !balance.is_empty(BalanceSide::Assets) {
+        (
+            BalanceSide::Assets,
+            bank.asset_amount(ShareAmount::new(balance.asset_shares.into()))?
+                .value(),
+        )
+    } else {
+        (
+            BalanceSide::Liabilities,
+            bank.liability_amount(ShareAmount::new(balance.liability_shares.into()))?
+                .value(),
+        )
+    };
+
+    let price = price_feed.get_price()?;
+
+    Ok(BalanceHealth {
+        bank_pk: balance.bank_pk,
+        mint: bank.mint,
+        side,
+        amount: native_amount / EXP_10_I80F48[bank.mint_decimals as usize],
+        price,
+        value: calc_asset_value(native_amount, price, bank.mint_decimals, None)?,
+        stale_oracle,
+        init_weights: bank.config.get_weights(WeightType::Initial),
+        maint_weights: bank.config.get_weights(WeightType::Maintenance),
+        init_values: calc_weighted_values(balance, bank, &price_feed, WeightType::Initial)?,
+        maint_values: calc_weighted_values(balance, bank, &price_feed, WeightType::Maintenance)?,
+    })
+}
+
+fn sum_values(
+    balances: &[BalanceHealth],
+    values: fn(&BalanceHealth) -> (I80F48, I80F48),
+) -> (I80F48, I80F48) {
+    balances
+        .iter()
+        .map(values)
+        .fold((I80F48::ZERO, I80F48::ZERO), |(assets, liabs), (a, l)| {
+            (assets + a, liabs + l)
+        })
+}
+
+fn print_health(label: &str, (assets, liabilities): (I80F48, I80F48)) {
+    let health_factor = if liabilities.is_zero() {
+        "∞".to_string()
+    } else {
+        format!("{:.4}", assets / liabilities)
+    };
+
+    println!(
+        "{} health: ${:.2} (weighted assets: ${:.2}, weighted liabilities: ${:.2}, health factor: {})",
+        label,
+        assets - liabilities,
+        assets,
+        liabilities,
+        health_factor
+    );
+}
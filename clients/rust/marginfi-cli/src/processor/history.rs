@@ -0,0 +1,556 @@
+use {
+    super::{load_all_banks, timestamp_to_string},
+    crate::{config::Config, decoder::event_data, output::print_json, profile::Profile},
+    anchor_client::anchor_lang::{AnchorDeserialize, Discriminator},
+    anyhow::{anyhow, Result},
+    fixed::types::I80F48,
+    marginfi::{
+        events::*,
+        state::{amounts::ShareAmount, marginfi_account::MarginfiAccount, marginfi_group::Bank},
+    },
+    marginfi_sdk::math::native_to_ui,
+    serde::Serialize,
+    solana_client::{
+        rpc_client::GetConfirmedSignaturesForAddress2Config, rpc_config::RpcTransactionConfig,
+    },
+    solana_sdk::{program_pack::Pack, pubkey::Pubkey, signature::Signature},
+    solana_transaction_status::UiTransactionEncoding,
+    std::{
+        collections::{BTreeMap, HashMap},
+        fs,
+        path::Path,
+        str::FromStr,
+    },
+};
+
+/// Max signatures of a `getSignaturesForAddress` page.
+const SIGNATURES_PAGE_SIZE: usize = 1000;
+
+/// A change to a balance of the account, or emissions withdrawn from it.
+#[derive(Debug, Clone, Serialize)]
+pub struct LedgerEntry {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub signature: String,
+    pub bank: String,
+    /// Bank mint, or emissions mint for `emissions` entries.
+    pub mint: String,
+    /// `deposit`, `withdraw`, `borrow`, `origination_fee`, `program_fee`, `repay`, `liquidation`,
+    /// `deleverage`, `bankruptcy` or `emissions`.
+    pub kind: &'static str,
+    /// In UI units of `mint`. Balance changes are signed, positive towards assets and negative
+    /// towards liabilities.
+    pub amount: f64,
+}
+
+/// Balance flows of the account in a bank, in UI units of the bank mint.
+#[derive(Debug, Default, Serialize)]
+pub struct BankLedgerOutput {
+    pub bank: String,
+    pub mint: String,
+    pub deposited: f64,
+    pub withdrawn: f64,
+    pub borrowed: f64,
+    pub origination_fees: f64,
+    pub program_fees: f64,
+    pub repaid: f64,
+    /// Net change from liquidations, deleverages and bankruptcies.
+    pub liquidated: f64,
+    /// Current balance, negative for a liability.
+    pub balance: f64,
+    /// Balance not explained by the ledger: interest earned if positive, paid if negative.
+    pub interest: f64,
+    pub emissions_mint: Option<String>,
+    /// In UI units of the emissions mint.
+    pub emissions_claimed: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountHistoryOutput {
+    pub marginfi_account: String,
+    pub from_slot: u64,
+    /// Whether the history reaches the creation of the account, otherwise interest includes
+    /// the balances held before `from_slot`.
+    pub complete: bool,
+    pub entries: Vec<LedgerEntry>,
+    pub banks: Vec<BankLedgerOutput>,
+}
+
+/// Balance change or emissions of an event, in native units.
+struct EventEntry {
+    bank: Pubkey,
+    kind: &'static str,
+    amount: f64,
+    /// Emissions mint of `emissions` entries.
+    emissions_mint: Option<Pubkey>,
+}
+
+/// Rebuild the ledger of a marginfi account from the events of its transactions since `from_slot`,
+/// and the interest earned or paid in each bank, as the part of its current balance not explained
+/// by the ledger.
+///
+/// With `csv`, the ledger entries are also written to that file.
+pub fn marginfi_account_history(
+    profile: &Profile,
+    config: &Config,
+    marginfi_account_pk: Option<Pubkey>,
+    from_slot: u64,
+    csv: Option<&Path>,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+    let marginfi_account_pk = marginfi_account_pk.unwrap_or_else(|| profile.get_marginfi_account());
+
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+    let banks: HashMap<Pubkey, Bank> =
+        HashMap::from_iter(load_all_banks(config, Some(marginfi_account.group))?);
+
+    // Newest first
+    let mut signatures = vec![];
+    let mut before = None;
+    loop {
+        let page = rpc_client.get_signatures_for_address_with_config(
+            &marginfi_account_pk,
+            GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: Some(SIGNATURES_PAGE_SIZE),
+                commitment: Some(config.commitment),
+            },
+        )?;
+        let page_len = page.len();
+
+        before = match page.last() {
+            Some(last) => Some(Signature::from_str(&last.signature)?),
+            None => None,
+        };
+
+        let reached_from = page.iter().any(|status| status.slot < from_slot);
+        signatures.extend(
+            page.into_iter()
+                .filter(|status| status.slot >= from_slot && status.err.is_none()),
+        );
+
+        if reached_from || page_len < SIGNATURES_PAGE_SIZE {
+            break;
+        }
+    }
+    signatures.reverse();
+
+    println!(
+        "Decoding {} transactions of {}",
+        signatures.len(),
+        marginfi_account_pk
+    );
+
+    let mut emissions_decimals: HashMap<Pubkey, u8> = HashMap::new();
+    let mut entries = vec![];
+    let mut complete = false;
+
+    for status in signatures.iter() {
+        let signature = Signature::from_str(&status.signature)?;
+        let tx = rpc_client.get_transaction_with_config(
+            &signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(config.commitment),
+                max_supported_transaction_version: Some(0),
+            },
+        )?;
+        let logs = tx
+            .transaction
+            .meta
+            .and_then(|meta| Option::<Vec<String>>::from(meta.log_messages))
+            .unwrap_or_default();
+
+        for data in event_data(&config.program_id, &logs) {
+            if decode_event::<MarginfiAccountCreateEvent>(&data).map_or(false, |event| {
+                event.header.marginfi_account == marginfi_account_pk
+            }) {
+                complete = true;
+            }
+
+            for event_entry in event_entries(&marginfi_account_pk, &data) {
+                let bank = banks
+                    .get(&event_entry.bank)
+                    .ok_or_else(|| anyhow!("Bank {} not found", event_entry.bank))?;
+
+                let (mint, decimals) = match event_entry.emissions_mint {
+                    Some(emissions_mint) => {
+                        let decimals = match emissions_decimals.get(&emissions_mint) {
+                            Some(decimals) => *decimals,
+                            None => {
+                                let mint = spl_token::state::Mint::unpack(
+                                    &rpc_client.get_account_data(&emissions_mint)?,
+                                )?;
+                                emissions_decimals.insert(emissions_mint, mint.decimals);
+                                mint.decimals
+                            }
+                        };
+
+                        (emissions_mint, decimals)
+                    }
+                    None => (bank.mint, bank.mint_decimals),
+                };
+
+                entries.push(LedgerEntry {
+                    slot: status.slot,
+                    block_time: status.block_time,
+                    signature: status.signature.clone(),
+                    bank: event_entry.bank.to_string(),
+                    mint: mint.to_string(),
+                    kind: event_entry.kind,
+                    amount: native_to_ui(I80F48::from_num(event_entry.amount), decimals),
+                });
+            }
+        }
+    }
+
+    // Banks of the ledger and of the current balances, by address for a stable order
+    let mut bank_ledgers: BTreeMap<Pubkey, BankLedgerOutput> = BTreeMap::new();
+
+    for entry in entries.iter() {
+        let ledger = bank_ledger(&mut bank_ledgers, &banks, Pubkey::from_str(&entry.bank)?)?;
+
+        match entry.kind {
+            "deposit" => ledger.deposited += entry.amount,
+            "withdraw" => ledger.withdrawn -= entry.amount,
+            "borrow" => ledger.borrowed -= entry.amount,
+            "origination_fee" => ledger.origination_fees -= entry.amount,
+            "program_fee" => ledger.program_fees -= entry.amount,
+            "repay" => ledger.repaid += entry.amount,
+            "emissions" => {
+                ledger.emissions_mint = Some(entry.mint.clone());
+                ledger.emissions_claimed += entry.amount;
+            }
+            _ => ledger.liquidated += entry.amount,
+        }
+
+        if entry.kind != "emissions" {
+            ledger.interest -= entry.amount;
+        }
+    }
+
+    for balance in marginfi_account.lending_account.get_active_balances_iter() {
+        let bank = banks
+            .get(&balance.bank_pk)
+            .ok_or_else(|| anyhow!("Bank {} not found", balance.bank_pk))?;
+        let native_balance = bank
+            .asset_amount(ShareAmount::new(balance.asset_shares.into()))?
+            .value()
+            - bank
+                .liability_amount(ShareAmount::new(balance.liability_shares.into()))?
+                .value();
+
+        let ledger = bank_ledger(&mut bank_ledgers, &banks, balance.bank_pk)?;
+        ledger.balance = native_to_ui(native_balance, bank.mint_decimals);
+        ledger.interest += ledger.balance;
+    }
+
+    if let Some(csv) = csv {
+        let mut content = String::from("slot,block_time,signature,bank,mint,kind,amount\n");
+        for entry in entries.iter() {
+            content.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                entry.slot,
+                entry
+                    .block_time
+                    .map(|block_time| block_time.to_string())
+                    .unwrap_or_default(),
+                entry.signature,
+                entry.bank,
+                entry.mint,
+                entry.kind,
+                entry.amount
+            ));
+        }
+        fs::write(csv, content)?;
+
+        println!("Wrote {} entries to {}", entries.len(), csv.display());
+    }
+
+    let output = AccountHistoryOutput {
+        marginfi_account: marginfi_account_pk.to_string(),
+        from_slot,
+        complete,
+        entries,
+        banks: bank_ledgers.into_values().collect(),
+    };
+
+    if config.json_output() {
+        return print_json(&output);
+    }
+
+    println!("Ledger:");
+    for entry in output.entries.iter() {
+        println!(
+            "\t{} [{}] {} {} {} in bank {} ({})",
+            entry
+                .block_time
+                .map(timestamp_to_string)
+                .unwrap_or_else(|| "-".to_owned()),
+            entry.slot,
+            entry.kind,
+            entry.amount,
+            entry.mint,
+            entry.bank,
+            entry.signature
+        );
+    }
+
+    println!("Banks:");
+    for ledger in output.banks.iter() {
+        println!(
+            "\tBank {} (mint: {})\n\t\tDeposited: {}, withdrawn: {}, borrowed: {}, origination fees: {}, program fees: {}, repaid: {}, liquidated: {}\n\t\tBalance: {}, interest: {}",
+            ledger.bank,
+            ledger.mint,
+            ledger.deposited,
+            ledger.withdrawn,
+            ledger.borrowed,
+            ledger.origination_fees,
+            ledger.program_fees,
+            ledger.repaid,
+            ledger.liquidated,
+            ledger.balance,
+            ledger.interest
+        );
+        if let Some(emissions_mint) = &ledger.emissions_mint {
+            println!(
+                "\t\tEmissions claimed: {} {}",
+                ledger.emissions_claimed, emissions_mint
+            );
+        }
+    }
+
+    if !output.complete {
+        println!(
+            "Warning: the history doesn't reach the creation of the account, interest includes the balances held before slot {}",
+            from_slot
+        );
+    }
+
+    Ok(())
+}
+
+fn bank_ledger<'a>(
+    bank_ledgers: &'a mut BTreeMap<Pubkey, BankLedgerOutput>,
+    banks: &HashMap<Pubkey, Bank>,
+    bank_pk: Pubkey,
+) -> Result<&'a mut BankLedgerOutput> {
+    let bank = banks
+        .get(&bank_pk)
+        .ok_or_else(|| anyhow!("Bank {} not found", bank_pk))?;
+
+    Ok(bank_ledgers
+        .entry(bank_pk)
+        .or_insert_with(|| BankLedgerOutput {
+            bank: bank_pk.to_string(),
+            mint: bank.mint.to_string(),
+            ..Default::default()
+        }))
+}
+
+fn decode_event<T: AnchorDeserialize + Discriminator>(data: &[u8]) -> Option<T> {
+    if data.len() < 8 || data[..8] != T::discriminator() {
+        return None;
+    }
+
+    T::deserialize(&mut &data[8..]).ok()
+}
+
+/// Balance changes and emissions of `marginfi_account_pk` from an event, positive towards assets.
+fn event_entries(marginfi_account_pk: &Pubkey, data: &[u8]) -> Vec<EventEntry> {
+    let is_account = |header: &AccountEventHeader| header.marginfi_account == *marginfi_account_pk;
+    let entry = |bank: Pubkey, kind: &'static str, amount: f64| EventEntry {
+        bank,
+        kind,
+        amount,
+        emissions_mint: None,
+    };
+
+    if let Some(event) = decode_event::<LendingAccountDepositEvent>(data) {
+        if is_account(&event.header) {
+            return vec![entry(event.bank, "deposit", event.amount as f64)];
+        }
+    } else if let Some(event) = decode_event::<LendingAccountWithdrawEvent>(data) {
+        if is_account(&event.header) {
+            return vec![entry(event.bank, "withdraw", -(event.amount as f64))];
+        }
+    } else if let Some(event) = decode_event::<LendingAccountBorrowEvent>(data) {
+        if is_account(&event.header) {
+            let mut entries = vec![entry(event.bank, "borrow", -(event.amount as f64))];
+            if event.origination_fee > 0 {
+                entries.push(entry(
+                    event.bank,
+                    "origination_fee",
+                    -(event.origination_fee as f64),
+                ));
+            }
+            if event.program_fee > 0 {
+                entries.push(entry(
+                    event.bank,
+                    "program_fee",
+                    -(event.program_fee as f64),
+                ));
+            }
+            return entries;
+        }
+    } else if let Some(event) = decode_event::<LendingAccountRepayEvent>(data) {
+        if is_account(&event.header) {
+            return vec![entry(event.bank, "repay", event.amount as f64)];
+        }
+    } else if let Some(event) = decode_event::<LendingAccountWithdrawEmissionsEvent>(data) {
+        if is_account(&event.header) {
+            return vec![EventEntry {
+                emissions_mint: Some(event.emissions_mint),
+                ..entry(event.bank, "emissions", event.amount as f64)
+            }];
+        }
+    } else if let Some(event) = decode_event::<LendingAccountLiquidateEvent>(data) {
+        // The liquidator takes over the seized assets, less the program fee,
+        // and part of the repaid liability
+        if is_account(&event.header) {
+            return vec![
+                entry(
+                    event.asset_bank,
+                    "liquidation",
+                    (event.asset_amount - event.program_fee) as f64,
+                ),
+                entry(
+                    event.liability_bank,
+                    "liquidation",
+                    -I80F48::from(event.liability_amount_liquidator).to_num::<f64>(),
+                ),
+            ];
+        }
+        if event.liquidatee_marginfi_account == *marginfi_account_pk {
+            return vec![
+                entry(
+                    event.asset_bank,
+                    "liquidation",
+                    -(event.asset_amount as f64),
+                ),
+                entry(
+                    event.liability_bank,
+                    "liquidation",
+                    I80F48::from(event.liability_amount_liquidatee).to_num::<f64>(),
+                ),
+            ];
+        }
+    } else if let Some(event) = decode_event::<LendingAccountDeleverageEvent>(data) {
+        if event.keeper_marginfi_account == *marginfi_account_pk {
+            return vec![
+                entry(event.asset_bank, "deleverage", event.asset_amount as f64),
+                entry(event.liability_bank, "deleverage", -event.liability_amount),
+            ];
+        }
+        if is_account(&event.header) {
+            return vec![
+                entry(event.asset_bank, "deleverage", -(event.asset_amount as f64)),
+                entry(event.liability_bank, "deleverage", event.liability_amount),
+            ];
+        }
+    } else if let Some(event) = decode_event::<LendingPoolBankHandleBankruptcyEvent>(data) {
+        if is_account(&event.header) {
+            return vec![entry(
+                event.bank,
+                "bankruptcy",
+                I80F48::from(event.bad_debt).to_num::<f64>(),
+            )];
+        }
+    }
+
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, anchor_client::anchor_lang::AnchorSerialize};
+
+    fn serialize_event<T: AnchorSerialize + Discriminator>(event: T) -> Vec<u8> {
+        let mut data = T::discriminator().to_vec();
+        event.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn header(marginfi_account: Pubkey) -> AccountEventHeader {
+        AccountEventHeader {
+            signer: None,
+            marginfi_account,
+            marginfi_account_authority: Pubkey::new_unique(),
+            marginfi_group: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn event_entries_are_signed_towards_assets() {
+        let account = Pubkey::new_unique();
+        let bank = Pubkey::new_unique();
+
+        let borrow = serialize_event(LendingAccountBorrowEvent {
+            header: header(account),
+            bank,
+            mint: Pubkey::new_unique(),
+            amount: 100,
+            origination_fee: 1,
+            program_fee: 2,
+            flat_fee: 5_000,
+        });
+        let entries = event_entries(&account, &borrow);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].amount, -100.0);
+        assert_eq!(entries[1].kind, "origination_fee");
+        assert_eq!(entries[1].amount, -1.0);
+        assert_eq!(entries[2].kind, "program_fee");
+        assert_eq!(entries[2].amount, -2.0);
+
+        // Events of other accounts are ignored
+        assert!(event_entries(&Pubkey::new_unique(), &borrow).is_empty());
+    }
+
+    #[test]
+    fn liquidation_entries_of_liquidator_and_liquidatee() {
+        let liquidator = Pubkey::new_unique();
+        let liquidatee = Pubkey::new_unique();
+        let asset_bank = Pubkey::new_unique();
+        let liability_bank = Pubkey::new_unique();
+        let balances = || LiquidationBalances {
+            liquidatee_asset_balance: 0.0,
+            liquidatee_liability_balance: 0.0,
+            liquidator_asset_balance: 0.0,
+            liquidator_liability_balance: 0.0,
+        };
+
+        let liquidation = serialize_event(LendingAccountLiquidateEvent {
+            header: header(liquidator),
+            liquidatee_marginfi_account: liquidatee,
+            liquidatee_marginfi_account_authority: Pubkey::new_unique(),
+            asset_bank,
+            asset_mint: Pubkey::new_unique(),
+            liability_bank,
+            liability_mint: Pubkey::new_unique(),
+            asset_price: 1.0,
+            liability_price: 1.0,
+            asset_amount: 10,
+            liability_amount_liquidator: I80F48::from_num(9.75).into(),
+            liability_amount_liquidatee: I80F48::from_num(9.5).into(),
+            insurance_fee: I80F48::from_num(0.25).into(),
+            liquidatee_pre_health: 0.0,
+            liquidatee_post_health: 0.0,
+            pre_balances: balances(),
+            post_balances: balances(),
+            program_fee: 1,
+        });
+
+        let liquidator_entries = event_entries(&liquidator, &liquidation);
+        assert_eq!(liquidator_entries[0].bank, asset_bank);
+        assert_eq!(liquidator_entries[0].amount, 9.0);
+        assert_eq!(liquidator_entries[1].bank, liability_bank);
+        assert_eq!(liquidator_entries[1].amount, -9.75);
+
+        let liquidatee_entries = event_entries(&liquidatee, &liquidation);
+        assert_eq!(liquidatee_entries[0].amount, -10.0);
+        assert_eq!(liquidatee_entries[1].amount, 9.5);
+    }
+}
@@ -0,0 +1,116 @@
+use {
+    super::load_all_banks,
+    crate::{
+        config::{Config, TxMode},
+        profile::Profile,
+        utils::process_transaction,
+    },
+    anyhow::Result,
+    marginfi_sdk::transaction::{
+        create_lookup_table, extend_lookup_table, group_lookup_table_addresses, load_lookup_table,
+    },
+    solana_sdk::{
+        address_lookup_table_account::AddressLookupTableAccount,
+        commitment_config::CommitmentConfig, pubkey::Pubkey, transaction::Transaction,
+    },
+    std::collections::HashMap,
+};
+
+/// Create the address lookup table of a group, or extend an existing one with the addresses of
+/// banks added since, and use it for the transactions of the profile.
+///
+/// The table holds the group, its banks with their mints, vaults and oracles, and the programs
+/// they are used with. The profile keypair is the table authority.
+pub fn group_update_lookup_table(
+    profile: &Profile,
+    config: &Config,
+    marginfi_group: Pubkey,
+    lookup_table: Option<Pubkey>,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let banks = HashMap::from_iter(load_all_banks(config, Some(marginfi_group))?);
+    let addresses = group_lookup_table_addresses(&config.program_id, &marginfi_group, &banks);
+
+    let (lookup_table, create_ix) = match lookup_table.or(profile.lookup_table) {
+        Some(address) => (load_lookup_table(&rpc_client, &address)?, None),
+        None => {
+            let recent_slot = rpc_client.get_slot_with_commitment(CommitmentConfig::finalized())?;
+            let (create_ix, address) =
+                create_lookup_table(&signer.pubkey(), &signer.pubkey(), recent_slot);
+
+            (
+                AddressLookupTableAccount {
+                    key: address,
+                    addresses: vec![],
+                },
+                Some(create_ix),
+            )
+        }
+    };
+
+    let extend_ixs = extend_lookup_table(
+        &lookup_table,
+        &signer.pubkey(),
+        &signer.pubkey(),
+        &addresses,
+    );
+    let added = addresses
+        .iter()
+        .filter(|address| !lookup_table.addresses.contains(address))
+        .count();
+
+    // The table is created along with the first batch of addresses
+    let mut txs_ixs = extend_ixs
+        .into_iter()
+        .map(|ix| vec![ix])
+        .collect::<Vec<_>>();
+    if let Some(create_ix) = create_ix {
+        match txs_ixs.first_mut() {
+            Some(ixs) => ixs.insert(0, create_ix),
+            None => txs_ixs.push(vec![create_ix]),
+        }
+    }
+
+    for (i, ixs) in txs_ixs.iter().enumerate() {
+        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(
+            &config.with_compute_budget(ixs),
+            Some(&signer.pubkey()),
+            &[signer],
+            recent_blockhash,
+        );
+
+        let sig = process_transaction(&tx, &rpc_client, config.get_tx_mode())?;
+        println!(
+            "Lookup table transaction {}/{}: {}",
+            i + 1,
+            txs_ixs.len(),
+            sig
+        );
+
+        // Later extensions can't be simulated before the table exists
+        if matches!(config.get_tx_mode(), TxMode::DryRun) {
+            return Ok(());
+        }
+    }
+
+    println!(
+        "Lookup table {} of group {}: {} addresses ({} added)",
+        lookup_table.key,
+        marginfi_group,
+        lookup_table.addresses.len() + added,
+        added
+    );
+
+    let mut profile = profile.clone();
+    profile.set_lookup_table(lookup_table.key)?;
+
+    println!(
+        "Transactions of profile {} are now sent as v0 transactions using it",
+        profile.name
+    );
+
+    Ok(())
+}
@@ -1,49 +1,76 @@
 #[cfg(feature = "admin")]
+pub mod bank_config;
+#[cfg(feature = "admin")]
+pub mod bank_wizard;
+pub mod batch;
+pub mod crank;
+#[cfg(feature = "admin")]
 pub mod emissions;
+pub mod health;
+pub mod history;
+pub mod lookup_table;
+pub mod oracle;
+pub mod snapshot;
 
 use {
     crate::{
         config::Config,
-        profile::{self, get_cli_config_dir, load_profile, CliConfig, Profile},
-        utils::{
-            find_bank_vault_authority_pda, find_bank_vault_pda, load_observation_account_metas,
-            process_transaction, EXP_10_I80F48,
+        decoder::{describe_events, describe_instruction, DecodeContext},
+        output::{
+            print_json, BankOutput, BankStatsOutput, GroupOutput, MarginfiAccountOutput,
+            VaultBalancesOutput,
         },
+        profile::{self, get_cli_config_dir, load_profile, CliConfig, Profile},
+        tx_splitter::{IxGroup, TxSplitter},
+        utils::{process_instructions, process_transaction, EXP_10_I80F48},
     },
-    anchor_client::{
-        anchor_lang::{InstructionData, ToAccountMetas},
-        Cluster,
-    },
-    anchor_spl::token::{self, spl_token},
+    anchor_client::{anchor_lang::AccountDeserialize, Cluster},
+    anchor_spl::token::spl_token,
     anyhow::{anyhow, bail, Result},
+    base64::{engine::general_purpose, Engine},
+    bincode::Options,
     fixed::types::I80F48,
     log::info,
     marginfi::{
+        instructions::AutoDeleverageConfig,
         prelude::MarginfiGroup,
         state::{
-            marginfi_account::{BankAccountWrapper, MarginfiAccount},
-            marginfi_group::{Bank, BankVaultType},
+            amounts::ShareAmount,
+            marginfi_account::{BankAccountWrapper, MarginfiAccount, SubAccountCounter},
+            marginfi_group::{Bank, LIQUIDATOR_ALLOWLIST_FLAG},
+        },
+    },
+    marginfi_sdk::{
+        accounts::{load_fee_state, load_multiple, MAX_MULTIPLE_ACCOUNTS},
+        instructions::{
+            handle_bankruptcy, initialize_marginfi_account, initialize_sub_account,
+            MarginfiAccountContext,
         },
+        pda::{find_sub_account_counter_pda, find_sub_account_pda},
+    },
+    solana_client::{
+        rpc_config::RpcTransactionConfig,
+        rpc_filter::{Memcmp, RpcFilterType},
     },
-    solana_client::rpc_filter::{Memcmp, RpcFilterType},
     solana_sdk::{
         account_info::IntoAccountInfo,
         clock::Clock,
         commitment_config::CommitmentLevel,
         compute_budget::ComputeBudgetInstruction,
-        instruction::{AccountMeta, Instruction},
-        message::Message,
+        message::{Message, VersionedMessage},
+        program_pack::Pack,
         pubkey::Pubkey,
-        signature::Keypair,
+        signature::{Keypair, Signature},
         signer::Signer,
-        system_program,
         sysvar::{self, Sysvar},
-        transaction::Transaction,
+        transaction::{Transaction, VersionedTransaction},
     },
+    solana_transaction_status::{UiLoadedAddresses, UiTransactionEncoding},
     spl_associated_token_account::instruction::create_associated_token_account_idempotent,
     std::{
         collections::HashMap,
         fs,
+        io::Read,
         mem::size_of,
         ops::{Neg, Not},
         time::{Duration, SystemTime, UNIX_EPOCH},
@@ -57,26 +84,38 @@ use solana_client::rpc_client::RpcClient;
 
 #[cfg(feature = "admin")]
 use {
-    crate::utils::{
-        calc_emissions_rate, create_oracle_key_array, find_bank_emssions_auth_pda,
-        find_bank_emssions_token_account_pda,
+    crate::{
+        config::TxMode,
+        utils::{calc_emissions_rate, create_oracle_key_array, BankManifest},
     },
     marginfi::{
-        constants::{EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE},
+        constants::{
+            EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
+            MAX_LIQUIDATOR_ALLOWLIST_LEN, MAX_REFERRERS,
+        },
         prelude::GroupConfig,
         state::marginfi_group::{
             BankConfig, BankConfigOpt, BankOperationalState, InterestRateConfig, WrappedI80F48,
         },
     },
-    solana_sdk::program_pack::Pack,
+    marginfi_sdk::instructions::{
+        accept_admin, configure_bank, configure_group, force_close_balance, initialize_group,
+        set_pending_admin, setup_emissions, update_emissions_parameters,
+    },
     spl_associated_token_account::get_associated_token_address,
     std::io,
 };
 
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+#[cfg(feature = "lip")]
+use crate::output::{CampaignOutput, DepositOutput};
+#[cfg(feature = "lip")]
+use liquidity_incentive_program::state::{Campaign, Deposit};
 #[cfg(feature = "lip")]
 use {
-    chrono::{DateTime, NaiveDateTime, Utc},
-    liquidity_incentive_program::state::{Campaign, Deposit},
+    anchor_client::anchor_lang::{InstructionData, ToAccountMetas},
+    solana_sdk::instruction::{AccountMeta, Instruction},
 };
 
 // --------------------------------------------------------------------------------------------------------------------
@@ -85,6 +124,22 @@ use {
 
 pub fn group_get(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
     if let Some(marginfi_group) = marginfi_group {
+        if config.json_output() {
+            let group = config
+                .mfi_program
+                .account::<MarginfiGroup>(marginfi_group)?;
+
+            let mut output = GroupOutput::new(&marginfi_group, &group);
+            output.banks = Some(
+                load_group_banks(&config, marginfi_group)?
+                    .iter()
+                    .map(|(address, bank)| BankOutput::new(address, bank))
+                    .collect::<Result<_>>()?,
+            );
+
+            return print_json(&output);
+        }
+
         println!("Address: {marginfi_group}");
         println!("=============");
         print_group_banks(config, marginfi_group)?;
@@ -97,6 +152,15 @@ pub fn group_get(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
 pub fn group_get_all(config: Config) -> Result<()> {
     let accounts: Vec<(Pubkey, MarginfiGroup)> = config.mfi_program.accounts(vec![])?;
 
+    if config.json_output() {
+        return print_json(
+            &accounts
+                .iter()
+                .map(|(address, group)| GroupOutput::new(address, group))
+                .collect::<Vec<_>>(),
+        );
+    }
+
     accounts
         .iter()
         .for_each(|(address, group)| print_group(address, group));
@@ -109,19 +173,62 @@ fn print_group(address: &Pubkey, group: &MarginfiGroup) {
         r#"
 Group: {}
 Admin: {}
+Pending admin: {}
+Liquidator allowlist enabled: {}
+Liquidator allowlist: {:?}
+Referrers: {:?}
+Bankruptcies: {}
+Socialized loss: ${:.2}
+Total borrow value: ${:.2} (limit: {})
 "#,
-        address, group.admin
+        address,
+        group.admin,
+        if group.pending_admin == Pubkey::default() {
+            "-".to_string()
+        } else {
+            group.pending_admin.to_string()
+        },
+        group.get_flag(LIQUIDATOR_ALLOWLIST_FLAG),
+        group
+            .liquidator_allowlist
+            .iter()
+            .filter(|liquidator| **liquidator != Pubkey::default())
+            .collect::<Vec<_>>(),
+        group
+            .referrers
+            .iter()
+            .filter(|referrer| **referrer != Pubkey::default())
+            .collect::<Vec<_>>(),
+        group.bankruptcy_count,
+        I80F48::from(group.socialized_loss_value).to_num::<f64>(),
+        I80F48::from(group.total_borrow_value).to_num::<f64>(),
+        if group.total_borrow_value_limit == 0 {
+            "none".to_string()
+        } else {
+            format!("${}", group.total_borrow_value_limit)
+        }
     );
 }
 
-pub fn print_group_banks(config: Config, marginfi_group: Pubkey) -> Result<()> {
-    let banks = config
+fn load_group_banks(config: &Config, marginfi_group: Pubkey) -> Result<Vec<(Pubkey, Bank)>> {
+    let mut banks = config
         .mfi_program
         .accounts::<Bank>(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
             8 + size_of::<Pubkey>() + size_of::<u8>(),
             marginfi_group.to_bytes().to_vec(),
         ))])?;
 
+    for (bank_pk, bank) in banks.iter_mut() {
+        bank.migrate()
+            .map_err(|err| anyhow!("Failed to migrate bank {}: {}", bank_pk, err))?;
+    }
+
+    Ok(banks)
+}
+
+pub fn print_group_banks(config: Config, marginfi_group: Pubkey) -> Result<()> {
+    let banks = load_group_banks(&config, marginfi_group)?;
+
     println!("--------\nBanks:");
 
     banks
@@ -139,10 +246,19 @@ Bank: {}
 Mint: {},
 Total Deposits: {}
 Total Liabilities: {}
+Bad Debt:
+  Insured: {}
+  Socialized: {}
+Liquidation insurance fees: {}
 Config:
   State: {:?}
   Risk Tier: {:?}
   USD Soft limit: {:?}
+  Outflow limit: {} per {}s (current: {})
+  Keeper tip: {} every {}s
+  Origination fee: {}bps
+  Oracle price band: {} - {}
+  Liquidation fees: liquidator {}, insurance {}
   Asset:
     Weight Init: {:?}, Maint: {:?}
     Limit: {}
@@ -151,7 +267,7 @@ Config:
     Limit: {}
   Interest Rate Config:
     Curve: opt_ur: {:?} pl_ir: {:?} max_ir: {:?}
-    Fees - Insurance: ir: {:?} fix: {:?}, Group: ir: {:?} fix: {:?}
+    Fees - Insurance: ir: {:?} fix: {:?}, Group: ir: {:?} fix: {:?}, Referral share: {:?}
   Oracle Setup:
     Type: {:?}
     Keys: {:#?}
@@ -160,20 +276,36 @@ Emissions:
   Rate: {:?}
   Mint: {:?}
   Remaining: {:?}
+  End: {}
 Last Update: {:?}h ago ({})
 "#,
         bank.group,
         address,
         bank.mint,
-        bank.get_asset_amount(bank.total_asset_shares.into())
+        bank.asset_amount(ShareAmount::new(bank.total_asset_shares.into()))
             .unwrap()
+            .value()
             / EXP_10_I80F48[bank.mint_decimals as usize],
-        bank.get_liability_amount(bank.total_liability_shares.into())
+        bank.liability_amount(ShareAmount::new(bank.total_liability_shares.into()))
             .unwrap()
+            .value()
             / EXP_10_I80F48[bank.mint_decimals as usize],
+        I80F48::from(bank.insured_bad_debt) / EXP_10_I80F48[bank.mint_decimals as usize],
+        I80F48::from(bank.socialized_loss) / EXP_10_I80F48[bank.mint_decimals as usize],
+        I80F48::from(bank.liquidation_insurance_fees) / EXP_10_I80F48[bank.mint_decimals as usize],
         bank.config.operational_state,
         bank.config.risk_tier,
         bank.config.total_asset_value_init_limit,
+        I80F48::from_num(bank.config.outflow_limit) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.config.outflow_window,
+        I80F48::from(bank.outflow_accumulator) / EXP_10_I80F48[bank.mint_decimals as usize],
+        I80F48::from_num(bank.config.keeper_tip) / EXP_10_I80F48[bank.mint_decimals as usize],
+        bank.config.keeper_tip_interval,
+        bank.config.origination_fee_bps,
+        I80F48::from(bank.config.oracle_price_floor),
+        I80F48::from(bank.config.oracle_price_ceiling),
+        I80F48::from(bank.config.liquidation_liquidator_fee),
+        I80F48::from(bank.config.liquidation_insurance_fee),
         bank.config.asset_weight_init,
         bank.config.asset_weight_maint,
         I80F48::from_num(bank.config.deposit_limit) / EXP_10_I80F48[bank.mint_decimals as usize],
@@ -187,12 +319,18 @@ Last Update: {:?}h ago ({})
         bank.config.interest_rate_config.insurance_fee_fixed_apr,
         bank.config.interest_rate_config.protocol_ir_fee,
         bank.config.interest_rate_config.protocol_fixed_fee_apr,
+        bank.config.interest_rate_config.referral_fee_share,
         bank.config.oracle_setup,
         bank.config.oracle_keys,
         bank.emissions_flags,
         I80F48::from(bank.emissions_rate),
         bank.emissions_mint,
         I80F48::from(bank.emissions_remaining),
+        if bank.emissions_end_timestamp == 0 {
+            "None".to_string()
+        } else {
+            timestamp_to_string(bank.emissions_end_timestamp as i64)
+        },
         SystemTime::now()
             .duration_since(UNIX_EPOCH + Duration::from_secs(bank.last_update as u64))
             .unwrap()
@@ -221,22 +359,20 @@ pub fn group_create(
 
     let marginfi_group_keypair = Keypair::new();
 
-    let mut init_marginfi_group_ixs_builder = config.mfi_program.request();
-
     let mut signing_keypairs = config.get_signers(false);
     signing_keypairs.push(&marginfi_group_keypair);
 
-    let init_marginfi_group_ixs = init_marginfi_group_ixs_builder
-        .accounts(marginfi::accounts::MarginfiGroupInitialize {
-            marginfi_group: marginfi_group_keypair.pubkey(),
-            admin,
-            system_program: system_program::id(),
-        })
-        .args(marginfi::instruction::MarginfiGroupInitialize {})
-        .instructions()?;
+    let init_marginfi_group_ixs = vec![initialize_group(
+        &config.program_id,
+        marginfi_group_keypair.pubkey(),
+        admin,
+    )];
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&init_marginfi_group_ixs, Some(&config.authority()));
+    let message = Message::new(
+        &config.with_compute_budget(&init_marginfi_group_ixs),
+        Some(&config.authority()),
+    );
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
@@ -255,31 +391,67 @@ pub fn group_create(
 }
 
 #[cfg(feature = "admin")]
-pub fn group_configure(config: Config, profile: Profile, admin: Option<Pubkey>) -> Result<()> {
+pub fn group_configure(
+    config: Config,
+    profile: Profile,
+    admin: Option<Pubkey>,
+    liquidator_allowlist_enabled: Option<bool>,
+    liquidators: Vec<Pubkey>,
+    total_borrow_value_limit: Option<u64>,
+    referrers: Vec<Pubkey>,
+) -> Result<()> {
     let rpc_client = config.mfi_program.rpc();
 
     if profile.marginfi_group.is_none() {
         bail!("Marginfi group not specified in profile [{}]", profile.name);
     }
 
-    let mut signing_keypairs = config.get_signers(false);
-    let mut configure_marginfi_group_ixs_builder = config
-        .mfi_program
-        .request()
-        .signer(*signing_keypairs.first().unwrap());
+    if liquidators.len() > MAX_LIQUIDATOR_ALLOWLIST_LEN {
+        bail!(
+            "Liquidator allowlist can contain at most {} liquidators",
+            MAX_LIQUIDATOR_ALLOWLIST_LEN
+        );
+    }
 
-    let configure_marginfi_group_ixs = configure_marginfi_group_ixs_builder
-        .accounts(marginfi::accounts::MarginfiGroupConfigure {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
-        })
-        .args(marginfi::instruction::MarginfiGroupConfigure {
-            config: GroupConfig { admin },
-        })
-        .instructions()?;
+    let liquidator_allowlist = if liquidators.is_empty() {
+        None
+    } else {
+        let mut allowlist = [Pubkey::default(); MAX_LIQUIDATOR_ALLOWLIST_LEN];
+        allowlist[..liquidators.len()].copy_from_slice(&liquidators);
+        Some(allowlist)
+    };
+
+    if referrers.len() > MAX_REFERRERS {
+        bail!("At most {} referrers can be registered", MAX_REFERRERS);
+    }
+
+    let registered_referrers = if referrers.is_empty() {
+        None
+    } else {
+        let mut registered_referrers = [Pubkey::default(); MAX_REFERRERS];
+        registered_referrers[..referrers.len()].copy_from_slice(&referrers);
+        Some(registered_referrers)
+    };
+
+    let signing_keypairs = config.get_signers(false);
+    let configure_marginfi_group_ixs = vec![configure_group(
+        &config.program_id,
+        profile.marginfi_group.unwrap(),
+        config.authority(),
+        GroupConfig {
+            admin,
+            liquidator_allowlist_enabled,
+            liquidator_allowlist,
+            total_borrow_value_limit,
+            referrers: registered_referrers,
+        },
+    )];
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&configure_marginfi_group_ixs, Some(&config.authority()));
+    let message = Message::new(
+        &config.with_compute_budget(&configure_marginfi_group_ixs),
+        Some(&config.authority()),
+    );
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
@@ -291,6 +463,88 @@ pub fn group_configure(config: Config, profile: Profile, admin: Option<Pubkey>)
     Ok(())
 }
 
+/// Propose `pending_admin` as the next admin of the profile group.
+#[cfg(feature = "admin")]
+pub fn group_set_pending_admin(
+    config: Config,
+    profile: Profile,
+    pending_admin: Pubkey,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    if profile.marginfi_group.is_none() {
+        bail!("Marginfi group not specified in profile [{}]", profile.name);
+    }
+
+    let signing_keypairs = config.get_signers(false);
+    let set_pending_admin_ixs = vec![set_pending_admin(
+        &config.program_id,
+        profile.marginfi_group.unwrap(),
+        config.authority(),
+        pending_admin,
+    )];
+
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&set_pending_admin_ixs),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!(
+            "Pending admin set to {}, waiting for it to accept (sig: {})",
+            pending_admin, sig
+        ),
+        Err(err) => println!("Error during pending admin update:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
+/// Accept the admin role of the profile group, the authority must be the pending admin.
+#[cfg(feature = "admin")]
+pub fn group_accept_admin(config: Config, profile: Profile) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_group = match profile.marginfi_group {
+        Some(marginfi_group) => marginfi_group,
+        None => bail!("Marginfi group not specified in profile [{}]", profile.name),
+    };
+
+    let group: MarginfiGroup = config.mfi_program.account(marginfi_group)?;
+    if group.pending_admin != config.authority() {
+        bail!(
+            "Authority {} is not the pending admin of group {}",
+            config.authority(),
+            marginfi_group
+        );
+    }
+
+    let signing_keypairs = config.get_signers(false);
+    let accept_admin_ixs = vec![accept_admin(
+        &config.program_id,
+        marginfi_group,
+        config.authority(),
+    )];
+
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&accept_admin_ixs),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!("Group admin transferred (sig: {})", sig),
+        Err(err) => println!("Error during admin transfer:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 #[cfg(feature = "admin")]
 pub fn group_add_bank(
@@ -350,92 +604,92 @@ pub fn group_add_bank(
         ..InterestRateConfig::default()
     };
 
-    let bank_keypair = Keypair::new();
+    let bank_config = BankConfig {
+        asset_weight_init,
+        asset_weight_maint,
+        liability_weight_init,
+        liability_weight_maint,
+        deposit_limit,
+        borrow_limit,
+        interest_rate_config,
+        operational_state: BankOperationalState::Operational,
+        oracle_setup: oracle_setup.into(),
+        oracle_keys: create_oracle_key_array(oracle_key),
+        risk_tier: risk_tier.into(),
+        ..BankConfig::default()
+    };
+
+    add_bank(&config, &profile, bank_mint, bank_config)
+}
+
+/// Add a bank for `bank_mint` to the profile group, and write its manifest once created.
+#[cfg(feature = "admin")]
+pub(crate) fn add_bank(
+    config: &Config,
+    profile: &Profile,
+    bank_mint: Pubkey,
+    bank_config: BankConfig,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
 
-    let mut add_bank_ixs_builder = config.mfi_program.request();
+    let marginfi_group = profile
+        .marginfi_group
+        .ok_or_else(|| anyhow!("Marginfi group not specified in profile [{}]", profile.name))?;
+
+    let bank_keypair = Keypair::new();
 
     let mut signing_keypairs = config.get_signers(true);
     signing_keypairs.push(&bank_keypair);
 
-    let add_bank_ixs = add_bank_ixs_builder
-        .accounts(marginfi::accounts::LendingPoolAddBank {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
-            bank: bank_keypair.pubkey(),
-            bank_mint,
-            fee_vault: find_bank_vault_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Fee,
-                &config.program_id,
-            )
-            .0,
-            fee_vault_authority: find_bank_vault_authority_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Fee,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_keypair.pubkey(),
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            rent: sysvar::rent::id(),
-            token_program: token::ID,
-            system_program: system_program::id(),
-            fee_payer: config.explicit_fee_payer(),
-        })
-        .accounts(AccountMeta::new_readonly(oracle_key, false))
-        .args(marginfi::instruction::LendingPoolAddBank {
-            bank_config: BankConfig {
-                asset_weight_init,
-                asset_weight_maint,
-                liability_weight_init,
-                liability_weight_maint,
-                deposit_limit,
-                borrow_limit,
-                interest_rate_config,
-                operational_state: BankOperationalState::Operational,
-                oracle_setup: oracle_setup.into(),
-                oracle_keys: create_oracle_key_array(oracle_key),
-                risk_tier: risk_tier.into(),
-                ..BankConfig::default()
-            }
-            .into(),
-        })
-        .instructions()?;
+    let add_bank_ixs = vec![marginfi_sdk::instructions::add_bank(
+        &config.program_id,
+        marginfi_group,
+        config.authority(),
+        config.explicit_fee_payer(),
+        bank_keypair.pubkey(),
+        bank_mint,
+        bank_config,
+    )];
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&add_bank_ixs, Some(&config.explicit_fee_payer()));
+    let message = Message::new(
+        &config.with_compute_budget(&add_bank_ixs),
+        Some(&config.explicit_fee_payer()),
+    );
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
-        Ok(sig) => println!("bank created (sig: {})", sig),
-        Err(err) => println!("Error during bank creation:\n{:#?}", err),
+    let bank_created = match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => {
+            println!("bank created (sig: {})", sig);
+            true
+        }
+        Err(err) => {
+            println!("Error during bank creation:\n{:#?}", err);
+            false
+        }
     };
 
     println!("New {} bank: {}", bank_mint, bank_keypair.pubkey());
 
+    let manifest = BankManifest::new(
+        &config.program_id,
+        &marginfi_group,
+        &bank_keypair.pubkey(),
+        &bank_config,
+        &bank_mint,
+    );
+
+    println!("{}", serde_json::to_string_pretty(&manifest)?);
+
+    // Simulated banks are never created, only persist the addresses of real (or proposed) ones
+    if bank_created && !matches!(config.get_tx_mode(), TxMode::DryRun) {
+        let manifest_path = profile.get_bank_manifest_path(&bank_keypair.pubkey());
+        manifest.write_to_file(&manifest_path)?;
+
+        println!("Bank manifest written to {}", manifest_path.display());
+    }
+
     Ok(())
 }
 
@@ -464,7 +718,6 @@ pub fn group_handle_bankruptcy(
 
     handle_bankruptcy_for_an_account(
         config,
-        &profile,
         &rpc_client,
         &banks,
         marginfi_account_pk,
@@ -475,6 +728,45 @@ pub fn group_handle_bankruptcy(
     Ok(())
 }
 
+#[cfg(feature = "admin")]
+pub fn group_force_close_balance(
+    config: &Config,
+    profile: &Profile,
+    marginfi_account_pk: Pubkey,
+    bank_pk: Pubkey,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    if profile.marginfi_group.is_none() {
+        bail!("Marginfi group not specified in profile [{}]", profile.name);
+    }
+
+    let ix = force_close_balance(
+        &config.program_id,
+        profile.marginfi_group.unwrap(),
+        config.authority(),
+        bank_pk,
+        marginfi_account_pk,
+    );
+
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let signing_keypairs = config.get_signers(false);
+
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!("Balance force closed (sig: {})", sig),
+        Err(err) => println!("Error during balance force close:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
 pub fn group_auto_handle_bankruptcy_for_an_account(
     config: &Config,
     profile: Profile,
@@ -503,8 +795,9 @@ pub fn group_auto_handle_bankruptcy_for_an_account(
                 && banks
                     .get(&b.bank_pk)
                     .unwrap()
-                    .get_liability_amount(b.liability_shares.into())
+                    .liability_amount(ShareAmount::new(b.liability_shares.into()))
                     .unwrap()
+                    .value()
                     .is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD)
         })
         .map(|b| b.bank_pk)
@@ -513,7 +806,6 @@ pub fn group_auto_handle_bankruptcy_for_an_account(
         .for_each(|bank_pk| {
             handle_bankruptcy_for_an_account(
                 config,
-                &profile,
                 &rpc_client,
                 &banks,
                 marginfi_account_pk,
@@ -528,7 +820,6 @@ pub fn group_auto_handle_bankruptcy_for_an_account(
 
 fn handle_bankruptcy_for_an_account(
     config: &Config,
-    profile: &Profile,
     rpc_client: &RpcClient,
     banks: &HashMap<Pubkey, Bank>,
     marginfi_account_pk: Pubkey,
@@ -536,45 +827,14 @@ fn handle_bankruptcy_for_an_account(
     bank_pk: Pubkey,
 ) -> Result<()> {
     println!("Handling bankruptcy for bank {}", bank_pk);
-    let mut handle_bankruptcy_ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingPoolHandleBankruptcy {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
-            bank: bank_pk,
-            marginfi_account: marginfi_account_pk,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolHandleBankruptcy {}.data(),
-    };
-
-    handle_bankruptcy_ix
-        .accounts
-        .extend(load_observation_account_metas(
-            &marginfi_account,
-            &banks,
-            vec![bank_pk],
-            vec![],
-        ));
+    let handle_bankruptcy_ix = handle_bankruptcy(
+        &config.program_id,
+        config.authority(),
+        marginfi_account_pk,
+        marginfi_account,
+        banks,
+        bank_pk,
+    );
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
 
@@ -584,7 +844,7 @@ fn handle_bankruptcy_for_an_account(
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
-    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+    match process_transaction(&transaction, rpc_client, config.get_tx_mode()) {
         Ok(sig) => println!("Bankruptcy handled (sig: {})", sig),
         Err(err) => println!("Error during bankruptcy handling:\n{:#?}", err),
     };
@@ -624,25 +884,24 @@ pub fn handle_bankruptcy_for_accounts(
                     && banks
                         .get(&b.bank_pk)
                         .unwrap()
-                        .get_liability_amount(b.liability_shares.into())
+                        .liability_amount(ShareAmount::new(b.liability_shares.into()))
                         .unwrap()
+                        .value()
                         .is_positive_with_tolerance(ZERO_AMOUNT_THRESHOLD)
             })
             .map(|b| b.bank_pk)
             .collect::<Vec<Pubkey>>()
             .iter()
             .for_each(|bank_pk| {
-                instructions.push(
-                    make_bankruptcy_ix(
-                        config,
-                        profile,
-                        &banks,
-                        account,
-                        &marginfi_account,
-                        *bank_pk,
-                    )
-                    .unwrap(),
-                );
+                println!("Handling bankruptcy for bank {}", bank_pk);
+                instructions.push(handle_bankruptcy(
+                    &config.program_id,
+                    config.fee_payer.pubkey(),
+                    account,
+                    &marginfi_account,
+                    &banks,
+                    *bank_pk,
+                ));
             });
     }
 
@@ -658,7 +917,7 @@ pub fn handle_bankruptcy_for_accounts(
         let mut ixs = vec![ComputeBudgetInstruction::set_compute_unit_limit(1_400_000)];
         ixs.extend_from_slice(chunk);
 
-        let message = Message::new(&ixs, Some(&config.authority()));
+        let message = Message::new(&config.with_compute_budget(&ixs), Some(&config.authority()));
 
         let mut transaction = Transaction::new_unsigned(message);
         transaction.partial_sign(&signing_keypairs, recent_blockhash);
@@ -671,58 +930,6 @@ pub fn handle_bankruptcy_for_accounts(
 
     Ok(())
 }
-#[cfg(feature = "admin")]
-fn make_bankruptcy_ix(
-    config: &Config,
-    profile: &Profile,
-    banks: &HashMap<Pubkey, Bank>,
-    marginfi_account_pk: Pubkey,
-    marginfi_account: &MarginfiAccount,
-    bank_pk: Pubkey,
-) -> Result<Instruction> {
-    println!("Handling bankruptcy for bank {}", bank_pk);
-    let mut handle_bankruptcy_ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingPoolHandleBankruptcy {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.fee_payer.pubkey(),
-            bank: bank_pk,
-            marginfi_account: marginfi_account_pk,
-            liquidity_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault: find_bank_vault_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            insurance_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Insurance,
-                &config.program_id,
-            )
-            .0,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolHandleBankruptcy {}.data(),
-    };
-
-    handle_bankruptcy_ix
-        .accounts
-        .extend(load_observation_account_metas(
-            &marginfi_account,
-            &banks,
-            vec![bank_pk],
-            vec![],
-        ));
-
-    Ok(handle_bankruptcy_ix)
-}
 
 // --------------------------------------------------------------------------------------------------------------------
 // bank
@@ -732,8 +939,7 @@ pub fn bank_get(config: Config, bank_pk: Option<Pubkey>) -> Result<()> {
     let rpc_client = config.mfi_program.rpc();
 
     if let Some(address) = bank_pk {
-        let bank: Bank = config.mfi_program.account(address)?;
-        print_bank(&address, &bank);
+        let bank = load_bank(&config, address)?;
 
         let liquidity_vault_balance =
             rpc_client.get_token_account_balance(&bank.liquidity_vault)?;
@@ -741,6 +947,19 @@ pub fn bank_get(config: Config, bank_pk: Option<Pubkey>) -> Result<()> {
         let insurance_vault_balance =
             rpc_client.get_token_account_balance(&bank.insurance_vault)?;
 
+        if config.json_output() {
+            let mut output = BankOutput::new(&address, &bank)?;
+            output.vaults = Some(VaultBalancesOutput::new(
+                &liquidity_vault_balance,
+                &fee_vault_balance,
+                &insurance_vault_balance,
+            ));
+
+            return print_json(&output);
+        }
+
+        print_bank(&address, &bank);
+
         println!("=============");
         println!("Token balances:");
         println!(
@@ -764,30 +983,159 @@ pub fn bank_get(config: Config, bank_pk: Option<Pubkey>) -> Result<()> {
     Ok(())
 }
 
-fn load_all_banks(config: &Config, marginfi_group: Option<Pubkey>) -> Result<Vec<(Pubkey, Bank)>> {
-    info!("Loading banks for group {:?}", marginfi_group);
-    let filters = match marginfi_group {
-        Some(marginfi_group) => vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
-            8 + size_of::<Pubkey>() + size_of::<u8>(),
-            marginfi_group.to_bytes().to_vec(),
-        ))],
-        None => vec![],
+/// Print the utilization, lending and borrowing rates, USD totals and vault balances of a bank,
+/// with interest accrued to the current time.
+pub fn bank_stats(config: &Config, bank_pk: Pubkey) -> Result<()> {
+    use marginfi::{
+        constants::MAX_PRICE_AGE_SEC,
+        state::price::{OraclePriceFeedAdapter, PriceAdapter},
     };
 
-    let mut clock = config.mfi_program.rpc().get_account(&sysvar::clock::ID)?;
+    let rpc_client = config.mfi_program.rpc();
+
+    let mut clock = rpc_client.get_account(&sysvar::clock::ID)?;
     let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
 
-    let mut banks_with_addresses = config.mfi_program.accounts::<Bank>(filters)?;
+    let mut bank: Bank = config.mfi_program.account(bank_pk)?;
+    bank.accrue_interest(clock.unix_timestamp)?;
+
+    // Stale and out of band prices are still shown, but flagged
+    let oracle_pk = bank.config.oracle_keys[0];
+    let price = rpc_client
+        .get_account(&oracle_pk)
+        .ok()
+        .and_then(|mut oracle_account| {
+            let oracle_ai = (&oracle_pk, &mut oracle_account).into_account_info();
+
+            match OraclePriceFeedAdapter::try_from_bank_config(
+                &bank.config,
+                &[oracle_ai.clone()],
+                clock.unix_timestamp,
+                MAX_PRICE_AGE_SEC,
+            ) {
+                Ok(price_feed) => Some((price_feed, false)),
+                Err(_) => OraclePriceFeedAdapter::try_from_bank_config_ignoring_price_band(
+                    &bank.config,
+                    &[oracle_ai],
+                    0,
+                    u64::MAX,
+                )
+                .ok()
+                .map(|price_feed| (price_feed, true)),
+            }
+        })
+        .and_then(|(price_feed, stale)| price_feed.get_price().ok().map(|price| (price, stale)));
 
-    banks_with_addresses.iter_mut().for_each(|(_, bank)| {
-        bank.accrue_interest(clock.unix_timestamp).unwrap();
-    });
+    let vaults = VaultBalancesOutput::new(
+        &rpc_client.get_token_account_balance(&bank.liquidity_vault)?,
+        &rpc_client.get_token_account_balance(&bank.fee_vault)?,
+        &rpc_client.get_token_account_balance(&bank.insurance_vault)?,
+    );
 
-    Ok(banks_with_addresses)
-}
+    let stats = BankStatsOutput::new(&bank_pk, &bank, price, vaults)?;
 
-pub fn bank_get_all(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
+    if config.json_output() {
+        return print_json(&stats);
+    }
+
+    let usd = |value: Option<f64>| {
+        value
+            .map(|value| format!("${:.2}", value))
+            .unwrap_or_else(|| "n/a".to_owned())
+    };
+    let ui = |amount: Option<f64>| {
+        amount
+            .map(|amount| amount.to_string())
+            .unwrap_or_else(|| "n/a".to_owned())
+    };
+
+    println!(
+        r#"
+Bank: {}
+Mint: {}
+Price: {}{}
+Total Deposits: {} ({})
+Total Liabilities: {} ({})
+Utilization: {:.2}%
+Lending: {:.2}% APR, {:.2}% APY
+Borrowing: {:.2}% APR, {:.2}% APY
+Vaults:
+  Liquidity: {}
+  Insurance: {}
+  Fee: {}
+"#,
+        stats.address,
+        stats.mint,
+        stats
+            .price
+            .map(|price| format!("${:.6}", price))
+            .unwrap_or_else(|| "n/a".to_owned()),
+        if stats.stale_oracle { " (stale)" } else { "" },
+        stats.total_deposits,
+        usd(stats.total_deposits_value),
+        stats.total_liabilities,
+        usd(stats.total_liabilities_value),
+        stats.utilization * 100.0,
+        stats.lending_apr * 100.0,
+        stats.lending_apy * 100.0,
+        stats.borrowing_apr * 100.0,
+        stats.borrowing_apy * 100.0,
+        ui(stats.vaults.liquidity),
+        ui(stats.vaults.insurance),
+        ui(stats.vaults.fee),
+    );
+
+    Ok(())
+}
+
+pub(crate) fn load_all_banks(
+    config: &Config,
+    marginfi_group: Option<Pubkey>,
+) -> Result<Vec<(Pubkey, Bank)>> {
+    info!("Loading banks for group {:?}", marginfi_group);
+    let filters = match marginfi_group {
+        Some(marginfi_group) => vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            8 + size_of::<Pubkey>() + size_of::<u8>(),
+            marginfi_group.to_bytes().to_vec(),
+        ))],
+        None => vec![],
+    };
+
+    let mut clock = config.mfi_program.rpc().get_account(&sysvar::clock::ID)?;
+    let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
+
+    let mut banks_with_addresses = config.mfi_program.accounts::<Bank>(filters)?;
+
+    banks_with_addresses.iter_mut().for_each(|(_, bank)| {
+        bank.migrate().unwrap();
+        bank.accrue_interest(clock.unix_timestamp).unwrap();
+    });
+
+    Ok(banks_with_addresses)
+}
+
+/// Load a bank, upgraded in memory to the latest layout version so that the fields introduced
+/// by a migration read as the program sees them, even if the bank isn't migrated on-chain yet.
+pub(crate) fn load_bank(config: &Config, bank_pk: Pubkey) -> Result<Bank> {
+    let mut bank = config.mfi_program.account::<Bank>(bank_pk)?;
+    bank.migrate()
+        .map_err(|err| anyhow!("Failed to migrate bank {}: {}", bank_pk, err))?;
+
+    Ok(bank)
+}
+
+pub fn bank_get_all(config: Config, marginfi_group: Option<Pubkey>) -> Result<()> {
     let accounts = load_all_banks(&config, marginfi_group)?;
+
+    if config.json_output() {
+        return print_json(
+            &accounts
+                .iter()
+                .map(|(address, bank)| BankOutput::new(address, bank))
+                .collect::<Result<Vec<_>>>()?,
+        );
+    }
+
     for (address, state) in accounts {
         print_bank(&address, &state);
     }
@@ -804,9 +1152,13 @@ pub fn bank_inspect_price_oracle(config: Config, bank_pk: Pubkey) -> Result<()>
     let price_oracle_ai =
         (&bank.config.oracle_keys[0], &mut price_oracle_account).into_account_info();
 
-    let opfa =
-        OraclePriceFeedAdapter::try_from_bank_config(&bank.config, &[price_oracle_ai], 0, u64::MAX)
-            .unwrap();
+    let opfa = OraclePriceFeedAdapter::try_from_bank_config_ignoring_price_band(
+        &bank.config,
+        &[price_oracle_ai],
+        0,
+        u64::MAX,
+    )
+    .unwrap();
 
     let (worst, best) = opfa.get_price_range().unwrap();
     let keys = bank
@@ -885,37 +1237,25 @@ pub fn bank_setup_emissions(
         return Ok(());
     }
 
-    let ix = Instruction {
-        program_id: marginfi::id(),
-        accounts: marginfi::accounts::LendingPoolSetupEmissions {
-            marginfi_group: profile.marginfi_group.expect("marginfi group not set"),
-            admin: config.authority(),
-            bank,
-            emissions_mint: mint,
-            emissions_auth: find_bank_emssions_auth_pda(bank, mint, marginfi::id()).0,
-            emissions_token_account: find_bank_emssions_token_account_pda(
-                bank,
-                mint,
-                marginfi::id(),
-            )
-            .0,
-            emissions_funding_account: funding_account_ata,
-            token_program: spl_token::id(),
-            system_program: system_program::id(),
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolSetupEmissions {
-            flags,
-            rate,
-            total_emissions,
-        }
-        .data(),
-    };
+    let ix = setup_emissions(
+        &config.program_id,
+        profile.marginfi_group.expect("marginfi group not set"),
+        config.authority(),
+        bank,
+        mint,
+        funding_account_ata,
+        flags,
+        rate,
+        total_emissions,
+    );
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
     let signing_keypairs = config.get_signers(false);
 
-    let message = Message::new(&[ix], Some(&config.authority()));
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
@@ -927,6 +1267,7 @@ pub fn bank_setup_emissions(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 #[cfg(feature = "admin")]
 pub fn bank_update_emissions(
     config: &Config,
@@ -937,6 +1278,7 @@ pub fn bank_update_emissions(
     disable: bool,
     rate: Option<f64>,
     additional_emissions: Option<f64>,
+    emissions_end_timestamp: Option<u64>,
 ) -> Result<()> {
     assert!(!(disable && (deposits || borrows)));
 
@@ -982,10 +1324,11 @@ pub fn bank_update_emissions(
     };
 
     println!(
-        "Changes:\n\tRate: {:?}\n\tAdditional emissions: {:?}\n\tFlags: {:?}",
+        "Changes:\n\tRate: {:?}\n\tAdditional emissions: {:?}\n\tFlags: {:?}\n\tEnd timestamp: {:?}",
         emissions_rate.map(|rate| format!("{} tokens per 1M bank tokens per YEAR", rate)),
         additional_emissions,
         emissions_flags.map(|flags| format!("{:b}", flags)),
+        emissions_end_timestamp,
     );
 
     // Get (y or n) input from user
@@ -1000,35 +1343,26 @@ pub fn bank_update_emissions(
         return Ok(());
     }
 
-    let ix = Instruction {
-        program_id: marginfi::id(),
-        accounts: marginfi::accounts::LendingPoolUpdateEmissionsParameters {
-            marginfi_group: profile.marginfi_group.expect("marginfi group not set"),
-            admin: config.authority(),
-            bank: bank_pk,
-            emissions_mint: emission_mint,
-            emissions_token_account: find_bank_emssions_token_account_pda(
-                bank_pk,
-                emission_mint,
-                marginfi::id(),
-            )
-            .0,
-            emissions_funding_account: funding_account_ata,
-            token_program: spl_token::id(),
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingPoolUpdateEmissionsParameters {
-            emissions_flags,
-            emissions_rate,
-            additional_emissions,
-        }
-        .data(),
-    };
+    let ix = update_emissions_parameters(
+        &config.program_id,
+        profile.marginfi_group.expect("marginfi group not set"),
+        config.authority(),
+        bank_pk,
+        emission_mint,
+        funding_account_ata,
+        emissions_flags,
+        emissions_rate,
+        additional_emissions,
+        emissions_end_timestamp,
+    );
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
     let signing_keypairs = config.get_signers(false);
 
-    let message = Message::new(&[ix], Some(&config.authority()));
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
@@ -1040,6 +1374,48 @@ pub fn bank_update_emissions(
     Ok(())
 }
 
+pub fn bank_fund_emissions(
+    config: &Config,
+    profile: &Profile,
+    bank_pk: Pubkey,
+    amount_ui: f64,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let bank = config.mfi_program.account::<Bank>(bank_pk)?;
+
+    if bank.emissions_mint == Pubkey::default() {
+        bail!("Bank {} has no emissions", bank_pk)
+    }
+
+    let emissions_mint_decimals = spl_token::state::Mint::unpack_from_slice(
+        &rpc_client.get_account(&bank.emissions_mint)?.data,
+    )?
+    .decimals;
+    let amount = (I80F48::from_num(amount_ui) * EXP_10_I80F48[emissions_mint_decimals as usize])
+        .floor()
+        .to_num::<u64>();
+
+    let ix = marginfi_sdk::instructions::fund_emissions(
+        &config.program_id,
+        profile.marginfi_group.expect("marginfi group not set"),
+        bank_pk,
+        bank.emissions_mint,
+        signer.pubkey(),
+        anchor_spl::associated_token::get_associated_token_address(
+            &signer.pubkey(),
+            &bank.emissions_mint,
+        ),
+        amount,
+    );
+
+    let sig = process_instructions(config, &signer.pubkey(), &[ix], &[signer])?;
+    println!("Emissions funded: {sig}");
+
+    Ok(())
+}
+
 #[cfg(feature = "admin")]
 pub fn bank_configure(
     config: Config,
@@ -1049,28 +1425,21 @@ pub fn bank_configure(
 ) -> Result<()> {
     let rpc_client = config.mfi_program.rpc();
 
-    let mut configure_bank_ixs_builder = config.mfi_program.request();
     let signing_keypairs = config.get_signers(false);
 
-    let mut configure_bank_ixs = configure_bank_ixs_builder
-        .accounts(marginfi::accounts::LendingPoolConfigureBank {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            admin: config.authority(),
-            bank: bank_pk,
-        })
-        .args(marginfi::instruction::LendingPoolConfigureBank {
-            bank_config_opt: bank_config_opt.clone(),
-        })
-        .instructions()?;
-
-    if let Some(oracle) = &bank_config_opt.oracle {
-        configure_bank_ixs[0]
-            .accounts
-            .push(AccountMeta::new_readonly(oracle.keys[0], false));
-    }
+    let configure_bank_ixs = vec![configure_bank(
+        &config.program_id,
+        profile.marginfi_group.unwrap(),
+        config.authority(),
+        bank_pk,
+        bank_config_opt,
+    )];
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let message = Message::new(&configure_bank_ixs, Some(&config.authority()));
+    let message = Message::new(
+        &config.with_compute_budget(&configure_bank_ixs),
+        Some(&config.authority()),
+    );
     let mut transaction = Transaction::new_unsigned(message);
     transaction.partial_sign(&signing_keypairs, recent_blockhash);
 
@@ -1244,6 +1613,22 @@ pub fn marginfi_account_list(profile: Profile, config: &Config) -> Result<()> {
         RpcFilterType::Memcmp(Memcmp::new_raw_bytes(8 + 32, authority.to_bytes().to_vec())),
     ])?;
 
+    if config.json_output() {
+        return print_json(
+            &accounts
+                .iter()
+                .map(|(address, marginfi_account)| {
+                    MarginfiAccountOutput::new(
+                        address,
+                        &settle_account_emissions(marginfi_account, &banks)?,
+                        &banks,
+                        profile.marginfi_account == Some(*address),
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?,
+        );
+    }
+
     if accounts.is_empty() {
         println!("No marginfi accounts found");
     }
@@ -1262,6 +1647,36 @@ pub fn marginfi_account_list(profile: Profile, config: &Config) -> Result<()> {
     Ok(())
 }
 
+/// Copy of the account with the emissions of its balances settled to now, as `print_account` shows them.
+fn settle_account_emissions(
+    marginfi_account: &MarginfiAccount,
+    banks: &HashMap<Pubkey, Bank>,
+) -> Result<MarginfiAccount> {
+    let mut marginfi_account = *marginfi_account;
+    let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let referrer = marginfi_account.lending_account.referrer;
+
+    for balance in marginfi_account
+        .lending_account
+        .balances
+        .iter_mut()
+        .filter(|balance| balance.active)
+    {
+        let mut bank = *banks
+            .get(&balance.bank_pk)
+            .ok_or_else(|| anyhow!("Bank {} not found", balance.bank_pk))?;
+
+        BankAccountWrapper {
+            bank: &mut bank,
+            balance,
+            referrer,
+        }
+        .claim_emissions(current_timestamp)?;
+    }
+
+    Ok(marginfi_account)
+}
+
 pub fn print_account(
     address: Pubkey,
     marginfi_account: MarginfiAccount,
@@ -1283,7 +1698,10 @@ pub fn print_account(
                 .is_empty(marginfi::state::marginfi_account::BalanceSide::Assets)
                 .not()
             {
-                let native_value = bank.get_asset_amount(balance.asset_shares.into()).unwrap();
+                let native_value = bank
+                    .asset_amount(ShareAmount::new(balance.asset_shares.into()))
+                    .unwrap()
+                    .value();
 
                 native_value / EXP_10_I80F48[bank.mint_decimals as usize]
             } else if balance
@@ -1291,8 +1709,9 @@ pub fn print_account(
                 .not()
             {
                 let native_value = bank
-                    .get_liability_amount(balance.liability_shares.into())
-                    .unwrap();
+                    .liability_amount(ShareAmount::new(balance.liability_shares.into()))
+                    .unwrap()
+                    .value();
 
                 (native_value / EXP_10_I80F48[bank.mint_decimals as usize]).neg()
             } else {
@@ -1305,6 +1724,7 @@ pub fn print_account(
             let mut baw = BankAccountWrapper {
                 bank: &mut bank,
                 balance: &mut balance,
+                referrer: marginfi_account.lending_account.referrer,
             };
 
             // Current timestamp
@@ -1381,6 +1801,15 @@ pub fn marginfi_account_get(
 
     let banks = HashMap::from_iter(load_all_banks(config, Some(group))?);
 
+    if config.json_output() {
+        return print_json(&MarginfiAccountOutput::new(
+            &marginfi_account_pk,
+            &settle_account_emissions(&marginfi_account, &banks)?,
+            &banks,
+            profile.marginfi_account == Some(marginfi_account_pk),
+        )?);
+    }
+
     print_account(marginfi_account_pk, marginfi_account, banks, false)?;
 
     Ok(())
@@ -1407,27 +1836,22 @@ pub fn marginfi_account_deposit(
         bail!("Bank does not belong to group")
     }
 
-    let deposit_ata =
-        anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+    let banks = HashMap::from([(bank_pk, bank)]);
 
-    let ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountDeposit {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            bank: bank_pk,
-            signer_token_account: deposit_ata,
-            bank_liquidity_vault: bank.liquidity_vault,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountDeposit { amount }.data(),
-    };
+    let ix = MarginfiAccountContext::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &banks,
+    )
+    .deposit(bank_pk, amount)?;
 
     let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
     let tx = Transaction::new_signed_with_payer(
-        &[ix],
+        &config.with_compute_budget(&[ix]),
         Some(&signer.pubkey()),
         &[signer],
         recent_blockhash,
@@ -1450,8 +1874,6 @@ pub fn marginfi_account_withdraw(
 ) -> Result<()> {
     let signer = config.get_non_ms_authority_keypair()?;
 
-    let rpc_client = config.mfi_program.rpc();
-
     let marginfi_account_pk = profile.get_marginfi_account();
 
     let banks = HashMap::from_iter(load_all_banks(
@@ -1473,40 +1895,13 @@ pub fn marginfi_account_withdraw(
         bail!("Bank does not belong to group")
     }
 
-    let withdraw_ata =
-        anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
-
-    let mut ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountWithdraw {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            bank: bank_pk,
-            bank_liquidity_vault: bank.liquidity_vault,
-            token_program: token::ID,
-            destination_token_account: withdraw_ata,
-            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountWithdraw {
-            amount,
-            withdraw_all: if withdraw_all { Some(true) } else { None },
-        }
-        .data(),
-    };
-
-    ix.accounts.extend(load_observation_account_metas(
+    let ix = MarginfiAccountContext::new(
+        config.program_id,
+        marginfi_account_pk,
         &marginfi_account,
         &banks,
-        vec![],
-        if withdraw_all { vec![bank_pk] } else { vec![] },
-    ));
+    )
+    .withdraw(bank_pk, amount, withdraw_all)?;
 
     let create_ide_ata_ix = create_associated_token_account_idempotent(
         &signer.pubkey(),
@@ -1515,15 +1910,12 @@ pub fn marginfi_account_withdraw(
         &spl_token::ID,
     );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_instructions(
+        config,
+        &signer.pubkey(),
         &[create_ide_ata_ix, ix],
-        Some(&signer.pubkey()),
         &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &rpc_client, config.get_tx_mode()) {
+    ) {
         Ok(sig) => println!("Withdraw successful: {sig}"),
         Err(err) => println!("Error during withdraw:\n{err:#?}"),
     }
@@ -1539,8 +1931,6 @@ pub fn marginfi_account_borrow(
 ) -> Result<()> {
     let signer = config.get_non_ms_authority_keypair()?;
 
-    let rpc_client = config.mfi_program.rpc();
-
     let marginfi_account_pk = profile.get_marginfi_account();
 
     let banks = HashMap::from_iter(load_all_banks(
@@ -1562,36 +1952,15 @@ pub fn marginfi_account_borrow(
         bail!("Bank does not belong to group")
     }
 
-    let withdraw_ata =
-        anchor_spl::associated_token::get_associated_token_address(&signer.pubkey(), &bank.mint);
-
-    let mut ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountBorrow {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            bank: bank_pk,
-            bank_liquidity_vault: bank.liquidity_vault,
-            token_program: token::ID,
-            destination_token_account: withdraw_ata,
-            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
-                &bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountBorrow { amount }.data(),
-    };
+    let fee_state = load_fee_state(&config.mfi_program.rpc(), &config.program_id)?;
 
-    ix.accounts.extend(load_observation_account_metas(
+    let ix = MarginfiAccountContext::new(
+        config.program_id,
+        marginfi_account_pk,
         &marginfi_account,
         &banks,
-        vec![bank_pk],
-        vec![],
-    ));
+    )
+    .borrow(bank_pk, amount, fee_state.as_ref())?;
 
     let create_ide_ata_ix = create_associated_token_account_idempotent(
         &signer.pubkey(),
@@ -1600,15 +1969,12 @@ pub fn marginfi_account_borrow(
         &spl_token::ID,
     );
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
+    match process_instructions(
+        config,
+        &signer.pubkey(),
         &[create_ide_ata_ix, ix],
-        Some(&signer.pubkey()),
         &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &rpc_client, config.get_tx_mode()) {
+    ) {
         Ok(sig) => println!("Borrow successful: {sig}"),
         Err(err) => println!("Error during borrow:\n{err:#?}"),
     }
@@ -1626,8 +1992,6 @@ pub fn marginfi_account_liquidate(
 ) -> Result<()> {
     let signer = config.get_non_ms_authority_keypair()?;
 
-    let rpc_client = config.mfi_program.rpc();
-
     let marginfi_account_pk = profile.get_marginfi_account();
 
     let banks = HashMap::from_iter(load_all_banks(
@@ -1659,64 +2023,53 @@ pub fn marginfi_account_liquidate(
     if liability_bank.group != profile.marginfi_group.unwrap() {
         bail!("Liability bank does not belong to group")
     }
+    if liquidatee_marginfi_account.group != profile.marginfi_group.unwrap() {
+        bail!("Liquidatee account does not belong to group")
+    }
 
-    let mut ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::LendingAccountLiquidate {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            asset_bank: asset_bank_pk,
-            liab_bank: liability_bank_pk,
-            liquidator_marginfi_account: marginfi_account_pk,
-            signer: signer.pubkey(),
-            liquidatee_marginfi_account: liquidatee_marginfi_account_pk,
-            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
-                &liability_bank_pk,
-                BankVaultType::Liquidity,
-                &config.program_id,
-            )
-            .0,
-            bank_liquidity_vault: liability_bank.liquidity_vault,
-            bank_insurance_vault: liability_bank.insurance_vault,
-            token_program: token::ID,
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::LendingAccountLiquidate { asset_amount }.data(),
+    // Check that the liquidatee holds both sides of the liquidation
+    let liquidatee_balance = |bank_pk: Pubkey| {
+        liquidatee_marginfi_account
+            .lending_account
+            .get_active_balances_iter()
+            .find(|balance| balance.bank_pk == bank_pk)
     };
+    if !liquidatee_balance(asset_bank_pk)
+        .map(|balance| !balance.is_empty(marginfi::state::marginfi_account::BalanceSide::Assets))
+        .unwrap_or(false)
+    {
+        bail!("Liquidatee has no assets in the asset bank")
+    }
+    if !liquidatee_balance(liability_bank_pk)
+        .map(|balance| {
+            !balance.is_empty(marginfi::state::marginfi_account::BalanceSide::Liabilities)
+        })
+        .unwrap_or(false)
+    {
+        bail!("Liquidatee has no liabilities in the liability bank")
+    }
 
-    ix.accounts.push(AccountMeta {
-        pubkey: asset_bank.config.oracle_keys[0],
-        is_signer: false,
-        is_writable: false,
-    });
-    ix.accounts.push(AccountMeta {
-        pubkey: liability_bank.config.oracle_keys[0],
-        is_signer: false,
-        is_writable: false,
-    });
-    ix.accounts.extend(load_observation_account_metas(
+    let ix = MarginfiAccountContext::new(
+        config.program_id,
+        marginfi_account_pk,
         &marginfi_account,
         &banks,
-        vec![liability_bank_pk, asset_bank_pk],
-        vec![],
-    ));
-    ix.accounts.extend(load_observation_account_metas(
-        &liquidatee_marginfi_account,
-        &banks,
-        vec![],
-        vec![],
-    ));
+    )
+    .liquidate(
+        &MarginfiAccountContext::new(
+            config.program_id,
+            liquidatee_marginfi_account_pk,
+            &liquidatee_marginfi_account,
+            &banks,
+        ),
+        asset_bank_pk,
+        liability_bank_pk,
+        asset_amount,
+    )?;
 
     let cu_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
 
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
-        &[ix, cu_ix],
-        Some(&signer.pubkey()),
-        &[signer],
-        recent_blockhash,
-    );
-
-    match process_transaction(&tx, &config.mfi_program.rpc(), config.get_tx_mode()) {
+    match process_instructions(config, &signer.pubkey(), &[ix, cu_ix], &[signer]) {
         Ok(sig) => println!("Liquidation successful: {sig}"),
         Err(err) => println!("Error during liquidation:\n{err:#?}"),
     }
@@ -1724,53 +2077,365 @@ pub fn marginfi_account_liquidate(
     Ok(())
 }
 
-pub fn marginfi_account_create(profile: &Profile, config: &Config) -> Result<()> {
+/// Opt the profile account in to automatic deleveraging by `keeper` below `health_buffer`,
+/// or opt out without a keeper.
+pub fn marginfi_account_configure_auto_deleverage(
+    profile: &Profile,
+    config: &Config,
+    keeper: Option<Pubkey>,
+    health_buffer: f64,
+) -> Result<()> {
     let signer = config.get_non_ms_authority_keypair()?;
 
-    let rpc_client = config.mfi_program.rpc();
-
-    let marginfi_account_key = Keypair::new();
-
-    let ix = Instruction {
-        program_id: config.program_id,
-        accounts: marginfi::accounts::MarginfiAccountInitialize {
-            marginfi_group: profile.marginfi_group.unwrap(),
-            marginfi_account: marginfi_account_key.pubkey(),
-            system_program: system_program::ID,
-            authority: signer.pubkey(),
-            fee_payer: signer.pubkey(),
-        }
-        .to_account_metas(Some(true)),
-        data: marginfi::instruction::MarginfiAccountInitialize.data(),
-    };
-
-    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
-    let tx = Transaction::new_signed_with_payer(
-        &[ix],
-        Some(&signer.pubkey()),
-        &[signer, &marginfi_account_key],
-        recent_blockhash,
-    );
-
-    let marginfi_account_pk = marginfi_account_key.pubkey();
-
-    match process_transaction(&tx, &config.mfi_program.rpc(), config.get_tx_mode()) {
-        Ok(_sig) => print!("{marginfi_account_pk}"),
-        Err(err) => println!("Error during initialize:\n{err:#?}"),
+    if keeper.is_some() && health_buffer <= 1.0 {
+        bail!("Health buffer must be above 1");
     }
 
-    let mut profile = profile.clone();
+    let marginfi_account_pk = profile.get_marginfi_account();
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+    let banks = HashMap::new();
 
-    profile.config(
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        None,
-        Some(marginfi_account_key.pubkey()),
-    )?;
+    let ix = MarginfiAccountContext::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &banks,
+    )
+    .configure_auto_deleverage(keeper.map(|keeper| AutoDeleverageConfig {
+        keeper,
+        health_buffer: I80F48::from_num(health_buffer).into(),
+    }));
+
+    match process_instructions(config, &signer.pubkey(), &[ix], &[signer]) {
+        Ok(sig) => match keeper {
+            Some(keeper) => {
+                println!("Automatic deleveraging by {keeper} below {health_buffer} enabled: {sig}")
+            }
+            None => println!("Automatic deleveraging disabled: {sig}"),
+        },
+        Err(err) => println!("Error during auto deleverage configuration:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+/// Deleverage `marginfi_account_pk`, of which the profile authority is the keeper, with the
+/// profile account taking over `ui_asset_amount` of its assets against its liability.
+pub fn marginfi_account_deleverage(
+    profile: &Profile,
+    config: &Config,
+    marginfi_account_pk: Pubkey,
+    asset_bank_pk: Pubkey,
+    liability_bank_pk: Pubkey,
+    ui_asset_amount: f64,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let keeper_marginfi_account_pk = profile.get_marginfi_account();
+
+    let banks = HashMap::from_iter(load_all_banks(
+        config,
+        Some(profile.marginfi_group.unwrap()),
+    )?);
+    let asset_bank = banks.get(&asset_bank_pk).expect("Asset bank not found");
+
+    let keeper_marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(keeper_marginfi_account_pk)?;
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    if marginfi_account.group != profile.marginfi_group.unwrap() {
+        bail!("Account does not belong to group")
+    }
+    if !marginfi_account.is_deleverage_keeper(&signer.pubkey()) {
+        bail!(
+            "Account has not enabled automatic deleveraging by {}",
+            signer.pubkey()
+        )
+    }
+
+    let asset_amount = (I80F48::from_num(ui_asset_amount)
+        * EXP_10_I80F48[asset_bank.mint_decimals as usize])
+        .floor()
+        .to_num::<u64>();
+
+    let ix = MarginfiAccountContext::new(
+        config.program_id,
+        keeper_marginfi_account_pk,
+        &keeper_marginfi_account,
+        &banks,
+    )
+    .deleverage(
+        &MarginfiAccountContext::new(
+            config.program_id,
+            marginfi_account_pk,
+            &marginfi_account,
+            &banks,
+        ),
+        asset_bank_pk,
+        liability_bank_pk,
+        asset_amount,
+    )?;
+
+    let cu_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+    match process_instructions(config, &signer.pubkey(), &[ix, cu_ix], &[signer]) {
+        Ok(sig) => println!("Deleverage successful: {sig}"),
+        Err(err) => println!("Error during deleverage:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+/// Upgrade a marginfi account or a bank to its latest layout version.
+pub fn migrate_account(config: &Config, address: Pubkey) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let ix =
+        marginfi_sdk::instructions::migrate_account(&config.program_id, address, signer.pubkey());
+
+    match process_instructions(config, &signer.pubkey(), &[ix], &[signer]) {
+        Ok(sig) => println!("Migration of {address} successful: {sig}"),
+        Err(err) => println!("Error during migration:\n{err:#?}"),
+    }
+
+    Ok(())
+}
+
+/// Estimated compute units of a withdraw emissions instruction, including the destination ATA creation.
+const WITHDRAW_EMISSIONS_COMPUTE_UNITS: u32 = 60_000;
+
+/// Print the outstanding emissions of every balance of an account, and withdraw them to the
+/// authority's associated token accounts, in as few transactions as possible.
+///
+/// Without `all`, only the emissions of `bank_pk` are withdrawn. Missing associated token
+/// accounts are created by the withdraw instructions.
+pub fn marginfi_account_claim_emissions(
+    profile: &Profile,
+    config: &Config,
+    bank_pk: Option<Pubkey>,
+    all: bool,
+) -> Result<()> {
+    if all == bank_pk.is_some() {
+        bail!("Either a bank or --all must be set");
+    }
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_account_pk = profile.get_marginfi_account();
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    if marginfi_account.authority != config.authority() {
+        bail!(
+            "Authority {} is not the authority of account {}",
+            config.authority(),
+            marginfi_account_pk
+        );
+    }
+
+    let banks = HashMap::from_iter(load_all_banks(config, Some(marginfi_account.group))?);
+    let settled_account = settle_account_emissions(&marginfi_account, &banks)?;
+
+    // (bank, emissions mint, outstanding native amount)
+    let claims = settled_account
+        .lending_account
+        .get_active_balances_iter()
+        .filter_map(|balance| {
+            let bank = banks.get(&balance.bank_pk)?;
+            if bank.emissions_mint == Pubkey::default() {
+                return None;
+            }
+
+            let outstanding = I80F48::from(balance.emissions_outstanding)
+                .floor()
+                .to_num::<u64>();
+
+            Some((balance.bank_pk, bank.emissions_mint, outstanding))
+        })
+        .filter(|(bank, _, _)| bank_pk.map_or(true, |bank_pk| bank_pk == *bank))
+        .collect::<Vec<_>>();
+
+    println!("Outstanding emissions of account {}:", marginfi_account_pk);
+    for (bank, emissions_mint, outstanding) in claims.iter() {
+        let decimals = rpc_client
+            .get_token_supply(emissions_mint)
+            .map(|supply| supply.decimals)
+            .unwrap_or(0);
+
+        println!(
+            "\tBank: {}, Emissions mint: {}, Outstanding: {}",
+            bank,
+            emissions_mint,
+            *outstanding as f64 / 10f64.powi(decimals as i32)
+        );
+    }
+
+    let claims = claims
+        .into_iter()
+        .filter(|(_, _, outstanding)| *outstanding > 0)
+        .collect::<Vec<_>>();
+
+    if claims.is_empty() {
+        println!("No emissions to claim");
+        return Ok(());
+    }
+
+    let destination_accounts = claims
+        .iter()
+        .map(|(_, emissions_mint, _)| {
+            spl_associated_token_account::get_associated_token_address(
+                &marginfi_account.authority,
+                emissions_mint,
+            )
+        })
+        .collect::<Vec<_>>();
+    let existing_destination_accounts = destination_accounts
+        .chunks(MAX_MULTIPLE_ACCOUNTS)
+        .map(|chunk| rpc_client.get_multiple_accounts(chunk))
+        .collect::<std::result::Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .map(|account| account.is_some())
+        .collect::<Vec<_>>();
+
+    let account_ctx = MarginfiAccountContext::new(
+        config.program_id,
+        marginfi_account_pk,
+        &marginfi_account,
+        &banks,
+    );
+    let groups = claims
+        .iter()
+        .zip(existing_destination_accounts)
+        .map(|((bank, _, _), exists)| {
+            Ok(IxGroup::new(
+                vec![account_ctx.withdraw_emissions(*bank, !exists)?],
+                WITHDRAW_EMISSIONS_COMPUTE_UNITS,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let signing_keypairs = config.get_signers(false);
+
+    let mut splitter = TxSplitter::new(config.authority());
+    splitter.compute_unit_price = config.priority_fee;
+    let plan = splitter.split(groups)?;
+    let txs_count = plan.transactions.len();
+
+    println!(
+        "Claiming emissions of {} banks in {} txs",
+        claims.len(),
+        txs_count
+    );
+
+    for (i, tx) in plan.transactions.iter().enumerate() {
+        let blockhash = rpc_client.get_latest_blockhash()?;
+
+        let mut transaction = splitter.to_transaction(tx);
+        transaction.partial_sign(&signing_keypairs, blockhash);
+
+        let sig = process_transaction(&transaction, &rpc_client, config.get_tx_mode())?;
+
+        println!("Sent [{}/{}] {}", i + 1, txs_count, sig);
+    }
+
+    Ok(())
+}
+
+pub fn marginfi_account_create(
+    profile: &Profile,
+    config: &Config,
+    sub_account: bool,
+    referrer: Option<Pubkey>,
+) -> Result<()> {
+    let signer = config.get_non_ms_authority_keypair()?;
+
+    let rpc_client = config.mfi_program.rpc();
+
+    let marginfi_group = profile.marginfi_group.unwrap();
+    let marginfi_account_key = Keypair::new();
+
+    let (ix, marginfi_account_pk) = if sub_account {
+        let (sub_account_counter, _) =
+            find_sub_account_counter_pda(&marginfi_group, &signer.pubkey(), &config.program_id);
+        // The counter is created along with the first sub-account
+        let account_index =
+            load_multiple::<SubAccountCounter>(&rpc_client, &[sub_account_counter])?
+                .pop()
+                .flatten()
+                .map(|sub_account_counter| sub_account_counter.account_count)
+                .unwrap_or_default();
+        let account_index = u16::try_from(account_index)
+            .map_err(|_| anyhow!("No sub-account index left for {}", signer.pubkey()))?;
+
+        (
+            initialize_sub_account(
+                &config.program_id,
+                marginfi_group,
+                signer.pubkey(),
+                signer.pubkey(),
+                account_index,
+                referrer,
+            ),
+            find_sub_account_pda(
+                &marginfi_group,
+                &signer.pubkey(),
+                account_index,
+                &config.program_id,
+            )
+            .0,
+        )
+    } else {
+        (
+            initialize_marginfi_account(
+                &config.program_id,
+                marginfi_group,
+                marginfi_account_key.pubkey(),
+                signer.pubkey(),
+                signer.pubkey(),
+                referrer,
+            ),
+            marginfi_account_key.pubkey(),
+        )
+    };
+
+    // Sub-accounts are PDAs, only new keypair accounts sign their creation
+    let mut signers: Vec<&dyn Signer> = vec![signer];
+    if !sub_account {
+        signers.push(&marginfi_account_key);
+    }
+
+    let recent_blockhash = rpc_client.get_latest_blockhash().unwrap();
+    let tx = Transaction::new_signed_with_payer(
+        &config.with_compute_budget(&[ix]),
+        Some(&signer.pubkey()),
+        &signers,
+        recent_blockhash,
+    );
+
+    match process_transaction(&tx, &config.mfi_program.rpc(), config.get_tx_mode()) {
+        Ok(_sig) => print!("{marginfi_account_pk}"),
+        Err(err) => println!("Error during initialize:\n{err:#?}"),
+    }
+
+    let mut profile = profile.clone();
+
+    profile.config(
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(marginfi_account_key.pubkey()),
+    )?;
 
     Ok(())
 }
@@ -1781,6 +2446,23 @@ pub fn marginfi_account_create(profile: &Profile, config: &Config) -> Result<()>
 pub fn process_list_lip_campaigns(config: &Config) {
     let campaings = config.lip_program.accounts::<Campaign>(vec![]).unwrap();
 
+    if config.json_output() {
+        let output = campaings
+            .iter()
+            .map(|(address, campaign)| {
+                let bank = config
+                    .mfi_program
+                    .account::<Bank>(campaign.marginfi_bank_pk)
+                    .unwrap();
+
+                CampaignOutput::new(address, campaign, &bank)
+            })
+            .collect::<Vec<_>>();
+
+        print_json(&output).unwrap();
+        return;
+    }
+
     print!("Found {} campaigns", campaings.len());
 
     campaings.iter().for_each(|(address, campaign)| {
@@ -1798,6 +2480,14 @@ Total Capacity: {}
 Remaining Capacity: {}
 Lockup Period: {} days
 Max Rewards: {}
+Guaranteed APR: {}
+Early Withdrawal Reward Share: {:.2}%
+Max Deposit Per User: {}
+Lockup Tiers: {}
+Deposit Deadline: {}
+Active: {}
+Paused: {}
+Outstanding Deposits: {}
 "#,
             address,
             campaign.marginfi_bank_pk,
@@ -1806,10 +2496,387 @@ Max Rewards: {}
             campaign.remaining_capacity as f32 / 10.0_f32.powi(bank.mint_decimals as i32),
             campaign.lockup_period / (24 * 60 * 60),
             campaign.max_rewards as f32 / 10.0_f32.powi(bank.mint_decimals as i32),
+            campaign
+                .apr()
+                .map(|apr| format!("{:.2}%", apr.to_num::<f64>() * 100.0))
+                .unwrap_or_else(|| "n/a".to_owned()),
+            campaign.early_withdrawal_reward_bps as f64 / 100.0,
+            if campaign.max_deposit_per_user > 0 {
+                format!(
+                    "{}",
+                    campaign.max_deposit_per_user as f32 / 10.0_f32.powi(bank.mint_decimals as i32)
+                )
+            } else {
+                "none".to_owned()
+            },
+            {
+                let tiers = campaign
+                    .lockup_tiers
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, tier)| tier.lockup_period > 0)
+                    .map(|(i, tier)| {
+                        format!(
+                            "[{}] {} days at {:.2}%",
+                            i + 1,
+                            tier.lockup_period / (24 * 60 * 60),
+                            tier.reward_bps as f64 / 100.0
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                if tiers.is_empty() {
+                    "none".to_owned()
+                } else {
+                    tiers.join(", ")
+                }
+            },
+            if campaign.deposit_deadline > 0 {
+                format!("{}", campaign.deposit_deadline)
+            } else {
+                "none".to_owned()
+            },
+            campaign.active,
+            campaign.paused,
+            campaign.outstanding_deposits() as f32 / 10.0_f32.powi(bank.mint_decimals as i32),
         );
     });
 }
 
+/// Deactivate `campaign_pk`, sweeping the leftover rewards to the authority's associated
+/// token account once all deposits are closed.
+#[cfg(feature = "lip")]
+pub fn process_end_lip_campaign(config: &Config, campaign_pk: Pubkey) -> Result<()> {
+    use liquidity_incentive_program::constants::{CAMPAIGN_AUTH_SEED, CAMPAIGN_SEED};
+
+    let rpc_client = config.lip_program.rpc();
+
+    let campaign = config.lip_program.account::<Campaign>(campaign_pk)?;
+    let bank = config
+        .mfi_program
+        .account::<Bank>(campaign.marginfi_bank_pk)?;
+
+    if campaign.admin != config.authority() {
+        bail!(
+            "Authority {} is not the admin of campaign {}",
+            config.authority(),
+            campaign_pk
+        );
+    }
+
+    let destination_account =
+        spl_associated_token_account::get_associated_token_address(&config.authority(), &bank.mint);
+
+    let ix = Instruction {
+        program_id: liquidity_incentive_program::id(),
+        accounts: liquidity_incentive_program::accounts::EndCampaign {
+            campaign: campaign_pk,
+            campaign_reward_vault: Pubkey::find_program_address(
+                &[CAMPAIGN_SEED.as_bytes(), campaign_pk.as_ref()],
+                &liquidity_incentive_program::id(),
+            )
+            .0,
+            campaign_reward_vault_authority: Pubkey::find_program_address(
+                &[CAMPAIGN_AUTH_SEED.as_bytes(), campaign_pk.as_ref()],
+                &liquidity_incentive_program::id(),
+            )
+            .0,
+            admin: config.authority(),
+            destination_account,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: liquidity_incentive_program::instruction::EndCampaign {}.data(),
+    };
+
+    let signing_keypairs = config.get_signers(false);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => {
+            if campaign.outstanding_deposits() > 0 {
+                println!(
+                    "Campaign deactivated, rewards can be swept once the outstanding deposits are closed (sig: {})",
+                    sig
+                );
+            } else {
+                println!(
+                    "Campaign ended, rewards swept to {} (sig: {})",
+                    destination_account, sig
+                );
+            }
+        }
+        Err(err) => println!("Error during campaign end:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
+/// Recompute the deposits exited from the ended `campaign_pk` from its open deposits,
+/// so that deposits closed before exits were tracked don't keep its leftover rewards from being swept.
+#[cfg(feature = "lip")]
+pub fn process_reconcile_lip_campaign(config: &Config, campaign_pk: Pubkey) -> Result<()> {
+    let rpc_client = config.lip_program.rpc();
+
+    let campaign = config.lip_program.account::<Campaign>(campaign_pk)?;
+
+    if campaign.admin != config.authority() {
+        bail!(
+            "Authority {} is not the admin of campaign {}",
+            config.authority(),
+            campaign_pk
+        );
+    }
+
+    if campaign.active {
+        bail!(
+            "Campaign {} must be ended before being reconciled",
+            campaign_pk
+        );
+    }
+
+    // Deposit::campaign follows the owner, amount and start time
+    let open_deposits = config
+        .lip_program
+        .accounts::<Deposit>(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            8 + 32 + 8 + 8,
+            campaign_pk.to_bytes().to_vec(),
+        ))])?;
+
+    let mut ix = Instruction {
+        program_id: liquidity_incentive_program::id(),
+        accounts: liquidity_incentive_program::accounts::ReconcileExitedDeposits {
+            campaign: campaign_pk,
+            admin: config.authority(),
+        }
+        .to_account_metas(Some(true)),
+        data: liquidity_incentive_program::instruction::ReconcileExitedDeposits {}.data(),
+    };
+    ix.accounts.extend(
+        open_deposits
+            .iter()
+            .map(|(deposit_pk, _)| AccountMeta::new_readonly(*deposit_pk, false)),
+    );
+
+    let signing_keypairs = config.get_signers(false);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!(
+            "Campaign reconciled with {} open deposits (sig: {})",
+            open_deposits.len(),
+            sig
+        ),
+        Err(err) => println!("Error during campaign reconciliation:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
+/// Pause the deposits of `campaign_pk`, or resume them.
+#[cfg(feature = "lip")]
+pub fn process_set_lip_campaign_paused(
+    config: &Config,
+    campaign_pk: Pubkey,
+    paused: bool,
+) -> Result<()> {
+    let rpc_client = config.lip_program.rpc();
+
+    let campaign = config.lip_program.account::<Campaign>(campaign_pk)?;
+
+    if campaign.admin != config.authority() {
+        bail!(
+            "Authority {} is not the admin of campaign {}",
+            config.authority(),
+            campaign_pk
+        );
+    }
+
+    let ix = if paused {
+        Instruction {
+            program_id: liquidity_incentive_program::id(),
+            accounts: liquidity_incentive_program::accounts::PauseCampaign {
+                campaign: campaign_pk,
+                admin: config.authority(),
+            }
+            .to_account_metas(Some(true)),
+            data: liquidity_incentive_program::instruction::PauseCampaign {}.data(),
+        }
+    } else {
+        Instruction {
+            program_id: liquidity_incentive_program::id(),
+            accounts: liquidity_incentive_program::accounts::ResumeCampaign {
+                campaign: campaign_pk,
+                admin: config.authority(),
+            }
+            .to_account_metas(Some(true)),
+            data: liquidity_incentive_program::instruction::ResumeCampaign {}.data(),
+        }
+    };
+
+    let signing_keypairs = config.get_signers(false);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!(
+            "Campaign {} (sig: {})",
+            if paused { "paused" } else { "resumed" },
+            sig
+        ),
+        Err(err) => println!(
+            "Error during campaign {}:\n{:#?}",
+            if paused { "pause" } else { "resume" },
+            err
+        ),
+    };
+
+    Ok(())
+}
+
+/// Top up the rewards of `campaign_pk` from the authority's associated token account,
+/// optionally raising its capacity or extending its lockup.
+#[cfg(feature = "lip")]
+pub fn process_update_lip_campaign(
+    config: &Config,
+    campaign_pk: Pubkey,
+    additional_rewards_ui: f64,
+    max_deposits_ui: Option<f64>,
+    lockup_period_days: Option<u64>,
+) -> Result<()> {
+    use liquidity_incentive_program::constants::CAMPAIGN_SEED;
+    use solana_sdk::clock::SECONDS_PER_DAY;
+
+    let rpc_client = config.lip_program.rpc();
+
+    let campaign = config.lip_program.account::<Campaign>(campaign_pk)?;
+    let bank = config
+        .mfi_program
+        .account::<Bank>(campaign.marginfi_bank_pk)?;
+
+    if campaign.admin != config.authority() {
+        bail!(
+            "Authority {} is not the admin of campaign {}",
+            config.authority(),
+            campaign_pk
+        );
+    }
+
+    let to_native = |ui_amount: f64| {
+        (I80F48::from_num(ui_amount) * EXP_10_I80F48[bank.mint_decimals as usize])
+            .floor()
+            .to_num::<u64>()
+    };
+
+    let additional_rewards = to_native(additional_rewards_ui);
+    let max_deposits = max_deposits_ui.map(to_native);
+    let lockup_period = lockup_period_days.map(|days| days * SECONDS_PER_DAY);
+
+    let funding_account =
+        spl_associated_token_account::get_associated_token_address(&config.authority(), &bank.mint);
+
+    let ix = Instruction {
+        program_id: liquidity_incentive_program::id(),
+        accounts: liquidity_incentive_program::accounts::UpdateCampaign {
+            campaign: campaign_pk,
+            campaign_reward_vault: Pubkey::find_program_address(
+                &[CAMPAIGN_SEED.as_bytes(), campaign_pk.as_ref()],
+                &liquidity_incentive_program::id(),
+            )
+            .0,
+            admin: config.authority(),
+            funding_account,
+            token_program: spl_token::id(),
+        }
+        .to_account_metas(Some(true)),
+        data: liquidity_incentive_program::instruction::UpdateCampaign {
+            additional_rewards,
+            max_deposits,
+            lockup_period,
+        }
+        .data(),
+    };
+
+    let signing_keypairs = config.get_signers(false);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!("Campaign updated (sig: {})", sig),
+        Err(err) => println!("Error during campaign update:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
+#[cfg(feature = "lip")]
+pub fn process_transfer_lip_deposit(
+    config: &Config,
+    deposit_pk: Pubkey,
+    new_owner: Pubkey,
+) -> Result<()> {
+    let rpc_client = config.lip_program.rpc();
+
+    let deposit = config.lip_program.account::<Deposit>(deposit_pk)?;
+
+    if deposit.owner != config.authority() {
+        bail!(
+            "Authority {} is not the owner of deposit {}",
+            config.authority(),
+            deposit_pk
+        );
+    }
+
+    let ix = Instruction {
+        program_id: liquidity_incentive_program::id(),
+        accounts: liquidity_incentive_program::accounts::TransferDeposit {
+            deposit: deposit_pk,
+            owner: config.authority(),
+            new_owner,
+        }
+        .to_account_metas(Some(true)),
+        data: liquidity_incentive_program::instruction::TransferDeposit {}.data(),
+    };
+
+    let signing_keypairs = config.get_signers(false);
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let message = Message::new(
+        &config.with_compute_budget(&[ix]),
+        Some(&config.authority()),
+    );
+    let mut transaction = Transaction::new_unsigned(message);
+    transaction.partial_sign(&signing_keypairs, recent_blockhash);
+
+    match process_transaction(&transaction, &rpc_client, config.get_tx_mode()) {
+        Ok(sig) => println!("Deposit transferred to {} (sig: {})", new_owner, sig),
+        Err(err) => println!("Error during deposit transfer:\n{:#?}", err),
+    };
+
+    Ok(())
+}
+
 #[cfg(feature = "lip")]
 pub fn process_list_deposits(config: &Config) {
     use solana_sdk::clock::SECONDS_PER_DAY;
@@ -1823,6 +2890,31 @@ pub fn process_list_deposits(config: &Config) {
 
     deposits.sort_by(|(_, a), (_, b)| a.start_time.cmp(&b.start_time));
 
+    if config.json_output() {
+        let time_now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let output = deposits
+            .iter()
+            .map(|(address, deposit)| {
+                let campaign = campaings.get(&deposit.campaign).unwrap();
+                let bank = banks.get(&campaign.marginfi_bank_pk).unwrap();
+
+                let lockup_period = deposit
+                    .lockup_period(campaign)
+                    .unwrap_or(campaign.lockup_period);
+                let end_time = deposit.start_time + lockup_period as i64;
+
+                DepositOutput::new(address, deposit, bank, end_time, time_now)
+            })
+            .collect::<Vec<_>>();
+
+        print_json(&output).unwrap();
+        return;
+    }
+
     deposits.iter().for_each(|(address, deposit)| {
         let campaign = campaings.get(&deposit.campaign).unwrap();
         let bank = banks.get(&campaign.marginfi_bank_pk).unwrap();
@@ -1832,7 +2924,10 @@ pub fn process_list_deposits(config: &Config) {
             .unwrap()
             .as_secs();
 
-        let end_time = deposit.start_time as u64 + campaign.lockup_period;
+        let lockup_period = deposit
+            .lockup_period(campaign)
+            .unwrap_or(campaign.lockup_period);
+        let end_time = deposit.start_time as u64 + lockup_period;
         let maturity_string = {
             if time_now > end_time {
                 "mature".to_owned()
@@ -1849,6 +2944,7 @@ Campaign: {},
 Asset Mint: {},
 Owner: {},
 Amount: {},
+Lockup Tier: {},
 Deposit start {}, end {} ({})
 "#,
             address,
@@ -1856,6 +2952,7 @@ Deposit start {}, end {} ({})
             bank.mint,
             deposit.owner,
             deposit.amount as f32 / 10.0_f32.powi(bank.mint_decimals as i32),
+            deposit.lockup_tier,
             timestamp_to_string(deposit.start_time),
             timestamp_to_string(end_time as i64),
             maturity_string,
@@ -1863,8 +2960,7 @@ Deposit start {}, end {} ({})
     })
 }
 
-#[cfg(feature = "lip")]
-fn timestamp_to_string(timestamp: i64) -> String {
+pub(crate) fn timestamp_to_string(timestamp: i64) -> String {
     DateTime::<Utc>::from_utc(
         NaiveDateTime::from_timestamp_opt(timestamp, 0).unwrap(),
         Utc,
@@ -1873,6 +2969,143 @@ fn timestamp_to_string(timestamp: i64) -> String {
     .to_string()
 }
 
+/// Print a summary of the marginfi and LIP instructions of a transaction, and of the events
+/// marginfi emitted while executing it.
+///
+/// Without a signature, a transaction or message printed in multisig mode (base58 or base64)
+/// is read from stdin, only its instructions are summarized as it didn't execute yet.
+pub fn decode_transaction(config: &Config, signature: Option<Signature>) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let (message, mut account_keys, logs) = match signature {
+        Some(signature) => {
+            let tx = rpc_client
+                .get_transaction_with_config(
+                    &signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::Base64),
+                        commitment: Some(config.commitment),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )?
+                .transaction;
+
+            let message = tx
+                .transaction
+                .decode()
+                .ok_or_else(|| anyhow!("Failed to decode transaction {}", signature))?
+                .message;
+            let meta = tx
+                .meta
+                .ok_or_else(|| anyhow!("Transaction {} has no status", signature))?;
+
+            println!("Transaction: {}", signature);
+            match meta.err {
+                Some(err) => println!("Status: failed ({})", err),
+                None => println!("Status: success"),
+            }
+
+            // Accounts loaded from lookup tables, writable first
+            let loaded_addresses = Option::<UiLoadedAddresses>::from(meta.loaded_addresses)
+                .map(|loaded_addresses| {
+                    loaded_addresses
+                        .writable
+                        .iter()
+                        .chain(loaded_addresses.readonly.iter())
+                        .map(|address| address.parse::<Pubkey>())
+                        .collect::<std::result::Result<Vec<_>, _>>()
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            (
+                message,
+                loaded_addresses,
+                Option::<Vec<String>>::from(meta.log_messages).unwrap_or_default(),
+            )
+        }
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+            let input = input.trim();
+
+            let bytes = general_purpose::STANDARD
+                .decode(input)
+                .or_else(|_| bs58::decode(input).into_vec())
+                .map_err(|_| anyhow!("Input is neither base64 nor base58"))?;
+
+            let bincode_options = bincode::DefaultOptions::new()
+                .with_fixint_encoding()
+                .reject_trailing_bytes();
+            let message = match bincode_options.deserialize::<VersionedTransaction>(&bytes) {
+                Ok(transaction) => transaction.message,
+                Err(_) => bincode_options
+                    .deserialize::<VersionedMessage>(&bytes)
+                    .map_err(|_| anyhow!("Input is neither a transaction nor a message"))?,
+            };
+
+            if message
+                .address_table_lookups()
+                .map_or(false, |lookups| !lookups.is_empty())
+            {
+                println!("Warning: accounts loaded from lookup tables are not resolved");
+            }
+
+            (message, vec![], vec![])
+        }
+    };
+
+    account_keys.splice(0..0, message.static_account_keys().iter().copied());
+
+    // Banks referenced by the transaction, to show amounts in UI units
+    let mut banks = HashMap::new();
+    for chunk in account_keys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (address, account) in chunk.iter().zip(rpc_client.get_multiple_accounts(chunk)?) {
+            if let Some(account) = account.filter(|account| account.owner == config.program_id) {
+                if let Ok(bank) = Bank::try_deserialize(&mut account.data.as_slice()) {
+                    banks.insert(*address, bank);
+                }
+            }
+        }
+    }
+
+    let ctx = DecodeContext {
+        marginfi_program_id: config.program_id,
+        lip_program_id: config.lip_program.id(),
+        banks,
+    };
+
+    println!("Instructions:");
+    for (i, ix) in message.instructions().iter().enumerate() {
+        let key = |index: u8| {
+            account_keys
+                .get(index as usize)
+                .copied()
+                .unwrap_or_default()
+        };
+        let program_id = key(ix.program_id_index);
+        let accounts = ix
+            .accounts
+            .iter()
+            .map(|index| key(*index))
+            .collect::<Vec<_>>();
+
+        match describe_instruction(&ctx, &program_id, &accounts, &ix.data) {
+            Some(description) => println!("\t[{}] {}", i, description),
+            None => println!("\t[{}] Program {}", i, program_id),
+        }
+    }
+
+    if signature.is_some() {
+        println!("Events:");
+        for event in describe_events(&ctx, &logs) {
+            println!("\t{}", event);
+        }
+    }
+
+    Ok(())
+}
+
 // Switchboard tests
 #[cfg(feature = "dev")]
 pub fn process_inspect_switchboard_feed(config: &Config, aggregator_pk: &Pubkey) {
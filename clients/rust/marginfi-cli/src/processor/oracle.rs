@@ -0,0 +1,339 @@
+use {
+    super::{load_all_banks, timestamp_to_string},
+    crate::config::Config,
+    anyhow::{anyhow, bail, Result},
+    fixed::types::I80F48,
+    marginfi::{
+        constants::{MAX_PRICE_AGE_SEC, PYTH_ID},
+        state::{
+            marginfi_group::Bank,
+            price::{OraclePriceFeedAdapter, OracleSetup, PriceAdapter},
+        },
+    },
+    solana_sdk::{
+        account::Account,
+        account_info::IntoAccountInfo,
+        clock::Clock,
+        pubkey::Pubkey,
+        sysvar::{self, Sysvar},
+    },
+    std::{collections::HashMap, time::Duration},
+};
+
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Placeholder substituted with the bank mint in the reference price API url.
+const REFERENCE_URL_MINT_PLACEHOLDER: &str = "{mint}";
+
+pub struct OracleStatusThresholds {
+    /// Confidence interval over price above which a feed is flagged.
+    pub max_confidence_ratio: f64,
+    /// Relative deviation from the reference price above which a feed is flagged.
+    pub max_reference_deviation: f64,
+}
+
+struct OracleStatus {
+    bank: Pubkey,
+    mint: Pubkey,
+    oracle: Pubkey,
+    setup: OracleSetup,
+    last_update: i64,
+    staleness: i64,
+    price: f64,
+    confidence_ratio: f64,
+    reference_price: Option<f64>,
+    problems: Vec<String>,
+}
+
+/// Print the freshness of every oracle referenced by the banks of `marginfi_group`.
+///
+/// Staleness is measured against the cluster clock and compared with `MAX_PRICE_AGE_SEC`,
+/// the max age enforced on-chain. When `reference_url` is set (e.g. `https://price.jup.ag/v4/price?ids={mint}`),
+/// the on-chain price is also compared with the first `price` field of the JSON response.
+pub fn oracle_status(
+    config: &Config,
+    marginfi_group: Pubkey,
+    reference_url: Option<String>,
+    thresholds: OracleStatusThresholds,
+) -> Result<()> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let banks = load_all_banks(config, Some(marginfi_group))?;
+    if banks.is_empty() {
+        bail!("No banks found for group {}", marginfi_group);
+    }
+
+    let mut clock = rpc_client.get_account(&sysvar::clock::ID)?;
+    let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
+
+    let oracle_keys = banks
+        .iter()
+        .map(|(_, bank)| bank.config.oracle_keys[0])
+        .collect::<Vec<_>>();
+    let oracle_accounts = rpc_client
+        .get_multiple_accounts(&oracle_keys)?
+        .into_iter()
+        .zip(oracle_keys.iter())
+        .filter_map(|(account, key)| account.map(|account| (*key, account)))
+        .collect::<HashMap<_, _>>();
+
+    let http_client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+
+    let mut n_problems = 0;
+
+    println!(
+        "Oracle status for group {} ({} banks)",
+        marginfi_group,
+        banks.len()
+    );
+    println!("Max price age: {}s", MAX_PRICE_AGE_SEC);
+
+    for (bank_pk, bank) in banks.iter() {
+        let oracle_account = oracle_accounts.get(&bank.config.oracle_keys[0]).cloned();
+
+        let status = match load_oracle_status(*bank_pk, bank, oracle_account, clock.unix_timestamp)
+        {
+            Ok(mut status) => {
+                check_status(&mut status, &thresholds);
+
+                if let Some(url) = reference_url.as_ref() {
+                    match fetch_reference_price(&http_client, url, &bank.mint) {
+                        Ok(reference_price) => {
+                            status.reference_price = Some(reference_price);
+                            check_reference_price(&mut status, &thresholds);
+                        }
+                        Err(err) => status
+                            .problems
+                            .push(format!("reference price unavailable: {}", err)),
+                    }
+                }
+
+                status
+            }
+            Err(err) => {
+                n_problems += 1;
+                println!(
+                    "\n{}Bank {} (mint {}): {}{}",
+                    RED, bank_pk, bank.mint, err, RESET
+                );
+                continue;
+            }
+        };
+
+        if !status.problems.is_empty() {
+            n_problems += 1;
+        }
+
+        print_status(&status);
+    }
+
+    if n_problems > 0 {
+        println!(
+            "\n{}{} of {} oracles have problems{}",
+            RED,
+            n_problems,
+            banks.len(),
+            RESET
+        );
+    } else {
+        println!("\nAll {} oracles healthy", banks.len());
+    }
+
+    Ok(())
+}
+
+fn load_oracle_status(
+    bank_pk: Pubkey,
+    bank: &Bank,
+    oracle_account: Option<Account>,
+    current_timestamp: i64,
+) -> Result<OracleStatus> {
+    if bank.config.oracle_setup == OracleSetup::None {
+        bail!("Oracle not set up");
+    }
+
+    let oracle_pk = bank.config.oracle_keys[0];
+    let mut oracle_account =
+        oracle_account.ok_or_else(|| anyhow!("Oracle account {} not found", oracle_pk))?;
+
+    let last_update = match bank.config.oracle_setup {
+        OracleSetup::None => unreachable!(),
+        OracleSetup::PythEma => {
+            if oracle_account.owner != PYTH_ID {
+                bail!("Oracle {} is not owned by the pyth program", oracle_pk);
+            }
+
+            let oracle_ai = (&oracle_pk, &mut oracle_account).into_account_info();
+            pyth_sdk_solana::load_price_feed_from_account_info(&oracle_ai)
+                .map_err(|err| anyhow!("Invalid pyth price feed {}: {:?}", oracle_pk, err))?
+                .get_ema_price_unchecked()
+                .publish_time
+        }
+        OracleSetup::SwitchboardV2 => {
+            switchboard_v2::AggregatorAccountData::new_from_bytes(&oracle_account.data)
+                .map_err(|err| anyhow!("Invalid switchboard feed {}: {:?}", oracle_pk, err))?
+                .latest_confirmed_round
+                .round_open_timestamp
+        }
+    };
+
+    // Staleness and the price band are reported separately, so the feed is loaded without them
+    let oracle_ai = (&oracle_pk, &mut oracle_account).into_account_info();
+    let price_feed = OraclePriceFeedAdapter::try_from_bank_config_ignoring_price_band(
+        &bank.config,
+        &[oracle_ai],
+        0,
+        u64::MAX,
+    )
+    .map_err(|err| anyhow!("Failed to load oracle {}: {:?}", oracle_pk, err))?;
+
+    let price = price_feed
+        .get_price()
+        .map_err(|err| anyhow!("Invalid price: {:?}", err))?;
+    let confidence_interval = price_feed
+        .get_confidence_interval()
+        .map_err(|err| anyhow!("Invalid confidence interval: {:?}", err))?;
+
+    let confidence_ratio = confidence_interval
+        .checked_div(price.abs())
+        .map_or(f64::INFINITY, |ratio| ratio.to_num::<f64>());
+
+    // Prices out of the bank price band are rejected on-chain
+    let mut problems = vec![];
+    let oracle_price_floor = I80F48::from(bank.config.oracle_price_floor);
+    let oracle_price_ceiling = I80F48::from(bank.config.oracle_price_ceiling);
+    let spot_price = price_feed
+        .get_price_non_weighted(None)
+        .map_err(|err| anyhow!("Invalid price: {:?}", err))?;
+    for price in [price, spot_price] {
+        if (oracle_price_floor != I80F48::ZERO && price < oracle_price_floor)
+            || (oracle_price_ceiling != I80F48::ZERO && price > oracle_price_ceiling)
+        {
+            problems.push(format!(
+                "price {} outside of band [{}, {}]",
+                price, oracle_price_floor, oracle_price_ceiling
+            ));
+            break;
+        }
+    }
+
+    Ok(OracleStatus {
+        bank: bank_pk,
+        mint: bank.mint,
+        oracle: oracle_pk,
+        setup: bank.config.oracle_setup,
+        last_update,
+        staleness: current_timestamp - last_update,
+        price: price.to_num::<f64>(),
+        confidence_ratio,
+        reference_price: None,
+        problems,
+    })
+}
+
+fn check_status(status: &mut OracleStatus, thresholds: &OracleStatusThresholds) {
+    if status.staleness > MAX_PRICE_AGE_SEC as i64 {
+        status.problems.push(format!(
+            "stale: last update {}s ago, max age {}s",
+            status.staleness, MAX_PRICE_AGE_SEC
+        ));
+    }
+
+    if status.price <= 0.0 {
+        status.problems.push("non-positive price".to_string());
+    }
+
+    if status.confidence_ratio > thresholds.max_confidence_ratio {
+        status.problems.push(format!(
+            "confidence interval {:.2}% of price, max {:.2}%",
+            status.confidence_ratio * 100.0,
+            thresholds.max_confidence_ratio * 100.0
+        ));
+    }
+}
+
+fn check_reference_price(status: &mut OracleStatus, thresholds: &OracleStatusThresholds) {
+    let reference_price = match status.reference_price {
+        Some(reference_price) if reference_price > 0.0 => reference_price,
+        _ => return,
+    };
+
+    let deviation = (status.price - reference_price).abs() / reference_price;
+
+    if deviation > thresholds.max_reference_deviation {
+        status.problems.push(format!(
+            "price deviates {:.2}% from reference, max {:.2}%",
+            deviation * 100.0,
+            thresholds.max_reference_deviation * 100.0
+        ));
+    }
+}
+
+fn fetch_reference_price(
+    http_client: &reqwest::blocking::Client,
+    url: &str,
+    mint: &Pubkey,
+) -> Result<f64> {
+    let url = url.replace(REFERENCE_URL_MINT_PLACEHOLDER, &mint.to_string());
+    let response: serde_json::Value = http_client.get(url).send()?.error_for_status()?.json()?;
+
+    find_price_field(&response).ok_or_else(|| anyhow!("No price field in response"))
+}
+
+/// Depth-first search for the first `price` field, accepting both numbers and numeric strings.
+fn find_price_field(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Object(map) => {
+            if let Some(price) = map.get("price").and_then(|price| match price {
+                serde_json::Value::Number(n) => n.as_f64(),
+                serde_json::Value::String(s) => s.parse().ok(),
+                _ => None,
+            }) {
+                return Some(price);
+            }
+
+            map.values().find_map(find_price_field)
+        }
+        serde_json::Value::Array(values) => values.iter().find_map(find_price_field),
+        _ => None,
+    }
+}
+
+fn print_status(status: &OracleStatus) {
+    let color = if status.problems.is_empty() { "" } else { RED };
+    let reset = if status.problems.is_empty() {
+        ""
+    } else {
+        RESET
+    };
+
+    println!(
+        r#"
+{}Bank {} (mint {}){}
+  Oracle: {} ({:?})
+  Last update: {} ({}s ago)
+  Price: {}
+  Confidence ratio: {:.4}%
+  Reference price: {}"#,
+        color,
+        status.bank,
+        status.mint,
+        reset,
+        status.oracle,
+        status.setup,
+        timestamp_to_string(status.last_update),
+        status.staleness,
+        status.price,
+        status.confidence_ratio * 100.0,
+        status
+            .reference_price
+            .map_or("-".to_string(), |price| price.to_string()),
+    );
+
+    for problem in status.problems.iter() {
+        println!("  {}! {}{}", RED, problem, RESET);
+    }
+}
@@ -0,0 +1,303 @@
+use {
+    crate::{
+        config::{AccountEntry, Config},
+        processor::load_all_banks,
+        profile::Profile,
+    },
+    anyhow::{anyhow, bail, Result},
+    marginfi::state::{marginfi_account::MarginfiAccount, marginfi_group::Bank},
+    marginfi_sdk::accounts::MAX_MULTIPLE_ACCOUNTS,
+    serde::{Deserialize, Serialize},
+    solana_account_decoder::{UiAccount, UiAccountEncoding},
+    solana_sdk::{
+        account::Account,
+        account_info::IntoAccountInfo,
+        clock::Clock,
+        pubkey::Pubkey,
+        sysvar::{self, Sysvar},
+    },
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Manifest written next to the account fixtures of a snapshot.
+pub const SNAPSHOT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Account fixture, in the format of `solana account --output json`,
+/// which is also what `solana-test-validator --account` and Anchor.toml expect.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountFixture {
+    pub pubkey: String,
+    pub account: UiAccount,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Program the snapshot was taken from, PDAs (e.g. bank vaults) are only valid under this id.
+    pub program_id: String,
+    pub slot: u64,
+    pub unix_timestamp: i64,
+    /// Not set for group dumps.
+    #[serde(default)]
+    pub marginfi_account: Option<String>,
+    pub marginfi_group: String,
+    pub banks: Vec<String>,
+    /// Every account of the snapshot, with a `<pubkey>.json` fixture file each.
+    pub accounts: Vec<String>,
+}
+
+/// Dump a marginfi account, its group, and the banks of its active balances
+/// (with their mints, vaults and oracles) into `out_dir`.
+///
+/// All accounts are fetched at the same slot, so that the snapshot is consistent.
+pub fn marginfi_account_export(
+    profile: &Profile,
+    config: &Config,
+    marginfi_account_pk: Option<Pubkey>,
+    out_dir: PathBuf,
+) -> Result<()> {
+    let marginfi_account_pk = marginfi_account_pk.unwrap_or_else(|| profile.get_marginfi_account());
+    let marginfi_account = config
+        .mfi_program
+        .account::<MarginfiAccount>(marginfi_account_pk)?;
+
+    let bank_pks = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| balance.active)
+        .map(|balance| balance.bank_pk)
+        .collect::<Vec<_>>();
+
+    let mut addresses = vec![marginfi_account_pk, marginfi_account.group];
+    for bank_pk in bank_pks.iter() {
+        let bank = config.mfi_program.account::<Bank>(*bank_pk)?;
+        addresses.extend(bank_addresses(bank_pk, &bank));
+    }
+
+    let (slot, unix_timestamp) = write_fixtures(config, &mut addresses, &out_dir)?;
+
+    let manifest = SnapshotManifest {
+        program_id: config.program_id.to_string(),
+        slot,
+        unix_timestamp,
+        marginfi_account: Some(marginfi_account_pk.to_string()),
+        marginfi_group: marginfi_account.group.to_string(),
+        banks: bank_pks.iter().map(|pk| pk.to_string()).collect(),
+        accounts: addresses.iter().map(|pk| pk.to_string()).collect(),
+    };
+
+    fs::write(
+        out_dir.join(SNAPSHOT_MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    println!(
+        "Exported {} accounts of {} at slot {} to {}",
+        addresses.len(),
+        marginfi_account_pk,
+        manifest.slot,
+        out_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Dump a group and all of its banks (with their mints, vaults and oracles) into `out_dir`,
+/// in the same format as `marginfi_account_export`.
+pub fn marginfi_group_dump(
+    config: &Config,
+    marginfi_group: Pubkey,
+    out_dir: PathBuf,
+) -> Result<()> {
+    let banks = load_all_banks(config, Some(marginfi_group))?;
+
+    let mut addresses = vec![marginfi_group];
+    for (bank_pk, bank) in banks.iter() {
+        addresses.extend(bank_addresses(bank_pk, bank));
+    }
+
+    let (slot, unix_timestamp) = write_fixtures(config, &mut addresses, &out_dir)?;
+
+    let manifest = SnapshotManifest {
+        program_id: config.program_id.to_string(),
+        slot,
+        unix_timestamp,
+        marginfi_account: None,
+        marginfi_group: marginfi_group.to_string(),
+        banks: banks.iter().map(|(pk, _)| pk.to_string()).collect(),
+        accounts: addresses.iter().map(|pk| pk.to_string()).collect(),
+    };
+
+    fs::write(
+        out_dir.join(SNAPSHOT_MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    println!(
+        "Dumped {} accounts of group {} ({} banks) at slot {} to {}",
+        addresses.len(),
+        marginfi_group,
+        banks.len(),
+        manifest.slot,
+        out_dir.display()
+    );
+
+    Ok(())
+}
+
+/// A bank with its mint, vaults and oracles.
+fn bank_addresses(bank_pk: &Pubkey, bank: &Bank) -> Vec<Pubkey> {
+    let mut addresses = vec![
+        *bank_pk,
+        bank.mint,
+        bank.liquidity_vault,
+        bank.insurance_vault,
+        bank.fee_vault,
+    ];
+    addresses.extend(
+        bank.config
+            .oracle_keys
+            .iter()
+            .filter(|key| **key != Pubkey::default()),
+    );
+
+    addresses
+}
+
+/// Deduplicate `addresses`, then fetch them all at the same slot, so that the snapshot is
+/// consistent, and write a fixture per account into `out_dir`.
+///
+/// Returns the slot and unix timestamp of the snapshot.
+fn write_fixtures(
+    config: &Config,
+    addresses: &mut Vec<Pubkey>,
+    out_dir: &Path,
+) -> Result<(u64, i64)> {
+    let rpc_client = config.mfi_program.rpc();
+
+    let mut unique = vec![];
+    addresses.retain(|address| {
+        if unique.contains(address) {
+            false
+        } else {
+            unique.push(*address);
+            true
+        }
+    });
+
+    let mut slot = None;
+    let mut accounts = Vec::with_capacity(addresses.len());
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let response =
+            rpc_client.get_multiple_accounts_with_commitment(chunk, config.commitment)?;
+
+        if *slot.get_or_insert(response.context.slot) != response.context.slot {
+            bail!("Accounts fetched at different slots, retry the export");
+        }
+
+        for (address, account) in chunk.iter().zip(response.value) {
+            let account = account.ok_or_else(|| anyhow!("Account {} not found", address))?;
+            accounts.push((*address, account));
+        }
+    }
+
+    let mut clock = rpc_client.get_account(&sysvar::clock::ID)?;
+    let clock = Clock::from_account_info(&(&sysvar::clock::ID, &mut clock).into_account_info())?;
+
+    fs::create_dir_all(out_dir)?;
+
+    for (address, account) in accounts.iter() {
+        let fixture = AccountFixture {
+            pubkey: address.to_string(),
+            account: UiAccount::encode(address, account, UiAccountEncoding::Base64, None, None),
+        };
+
+        fs::write(
+            fixture_path(out_dir, address),
+            serde_json::to_string_pretty(&fixture)?,
+        )?;
+    }
+
+    Ok((slot.unwrap_or_default(), clock.unix_timestamp))
+}
+
+/// Load the snapshot in `dir` into the localnet harness.
+///
+/// Every fixture is decoded to check that the snapshot is complete. The `[[test.validator.account]]`
+/// entries are appended to `anchor_toml` if provided, otherwise the `solana-test-validator` flags are printed.
+/// The marginfi program must be deployed at the program id of the snapshot for its PDAs to be valid.
+pub fn marginfi_account_import(dir: PathBuf, anchor_toml: Option<PathBuf>) -> Result<()> {
+    let manifest: SnapshotManifest =
+        serde_json::from_str(&fs::read_to_string(dir.join(SNAPSHOT_MANIFEST_FILE))?)?;
+
+    let mut fixtures = vec![];
+    for address in manifest.accounts.iter() {
+        let address = Pubkey::from_str(address)?;
+        let path = fixture_path(&dir, &address);
+
+        let fixture: AccountFixture = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        if fixture.pubkey != address.to_string() {
+            bail!(
+                "Fixture {} holds account {}",
+                path.display(),
+                fixture.pubkey
+            );
+        }
+        fixture
+            .account
+            .decode::<Account>()
+            .ok_or_else(|| anyhow!("Failed to decode fixture {}", path.display()))?;
+
+        fixtures.push(AccountEntry {
+            address: address.to_string(),
+            filename: path.display().to_string(),
+        });
+    }
+
+    match anchor_toml {
+        Some(anchor_toml) => {
+            let mut content = fs::read_to_string(&anchor_toml)?;
+
+            let mut added = 0;
+            for entry in fixtures.iter() {
+                if content.contains(&format!("address = \"{}\"", entry.address)) {
+                    continue;
+                }
+
+                content.push_str("\n[[test.validator.account]]\n");
+                content.push_str(&toml::to_string(entry)?);
+                added += 1;
+            }
+
+            fs::write(&anchor_toml, content)?;
+
+            println!(
+                "Added {} accounts to {}, deploy marginfi at {}",
+                added,
+                anchor_toml.display(),
+                manifest.program_id
+            );
+        }
+        None => {
+            println!("solana-test-validator \\");
+            println!(
+                "  --bpf-program {} target/deploy/marginfi.so \\",
+                manifest.program_id
+            );
+            for entry in fixtures.iter() {
+                println!("  --account {} {} \\", entry.address, entry.filename);
+            }
+            println!("  --warp-slot {}", manifest.slot);
+        }
+    }
+
+    Ok(())
+}
+
+fn fixture_path(dir: &Path, address: &Pubkey) -> PathBuf {
+    dir.join(format!("{address}.json"))
+}
@@ -1,5 +1,8 @@
 use {
-    crate::config::{Config, GlobalOptions},
+    crate::{
+        config::{Config, GlobalOptions, WalletPath},
+        utils::signer_from_path,
+    },
     anchor_client::{Client, Cluster},
     anyhow::{anyhow, bail, Result},
     dirs::home_dir,
@@ -8,7 +11,7 @@ use {
         commitment_config::{CommitmentConfig, CommitmentLevel},
         pubkey,
         pubkey::Pubkey,
-        signature::{read_keypair_file, Keypair},
+        signature::{read_keypair_file, Keypair, Signer},
     },
     std::{fs, path::PathBuf, rc::Rc},
 };
@@ -24,6 +27,9 @@ pub struct Profile {
     pub commitment: Option<CommitmentLevel>,
     pub marginfi_group: Option<Pubkey>,
     pub marginfi_account: Option<Pubkey>,
+    /// Address lookup table of the group, transactions are sent as v0 transactions using it.
+    #[serde(default)]
+    pub lookup_table: Option<Pubkey>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -62,20 +68,25 @@ impl Profile {
             commitment,
             marginfi_group,
             marginfi_account,
+            lookup_table: None,
         }
     }
 
     pub fn get_config(&self, global_options: Option<&GlobalOptions>) -> Result<Config> {
-        let fee_payer =
-            read_keypair_file(&*shellexpand::tilde(&self.keypair_path.clone().unwrap()))
-                .expect("Example requires a keypair file");
+        let fee_payer: Box<dyn Signer> = match &self.keypair_path {
+            Some(keypair_path) => signer_from_path(keypair_path)?,
+            // Multisig profiles don't sign, the default keypair is only used to propose to Squads
+            None => Box::new(
+                read_keypair_file(WalletPath::default().to_string())
+                    .unwrap_or_else(|_| Keypair::new()),
+            ),
+        };
 
         let multisig = self.multisig;
 
-        let dry_run = match global_options {
-            Some(options) => options.dry_run,
-            None => false,
-        };
+        let default_options = GlobalOptions::default();
+        let global_options = global_options.unwrap_or(&default_options);
+        let dry_run = global_options.dry_run;
         let cluster = self.cluster.clone();
         let program_id = match self.program_id {
         Some(pid) => pid,
@@ -113,6 +124,13 @@ impl Profile {
             program_id,
             commitment,
             dry_run,
+            tx_encoding: global_options.tx_encoding,
+            squads_multisig: global_options.squads_multisig,
+            squads_vault_index: global_options.squads_vault_index,
+            priority_fee: global_options.priority_fee,
+            compute_units: global_options.compute_units,
+            output: global_options.output,
+            lookup_table: self.lookup_table,
             client,
             mfi_program: program,
             lip_program,
@@ -187,6 +205,23 @@ impl Profile {
         Ok(())
     }
 
+    pub fn set_lookup_table(&mut self, address: Pubkey) -> Result<()> {
+        self.lookup_table = Some(address);
+        self.write_to_file()?;
+
+        Ok(())
+    }
+
+    /// Path of the address manifest written when `bank_pk` is added with this profile.
+    #[cfg(feature = "admin")]
+    pub fn get_bank_manifest_path(&self, bank_pk: &Pubkey) -> PathBuf {
+        get_cli_config_dir()
+            .join("profiles")
+            .join(&self.name)
+            .join("banks")
+            .join(format!("{bank_pk}.json"))
+    }
+
     fn write_to_file(&self) -> Result<()> {
         let cli_config_dir = get_cli_config_dir();
         let cli_profiles_dir = cli_config_dir.join("profiles");
@@ -264,6 +299,7 @@ Profile:
     Authority: {}
     Keypair: {}
     Multisig: {}
+    Lookup Table: {}
         "#,
             self.name,
             config.program_id,
@@ -283,6 +319,9 @@ Profile:
             self.multisig
                 .map(|x| x.to_string())
                 .unwrap_or_else(|| "None".to_owned()),
+            self.lookup_table
+                .map(|x| x.to_string())
+                .unwrap_or_else(|| "None".to_owned()),
         )?;
 
         Ok(())
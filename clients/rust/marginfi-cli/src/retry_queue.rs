@@ -0,0 +1,359 @@
+use {
+    crate::{config::Config, errors},
+    anyhow::Result,
+    log::{error, info, warn},
+    reqwest::StatusCode,
+    serde::{Deserialize, Serialize},
+    solana_client::{
+        client_error::{ClientError, ClientErrorKind},
+        rpc_client::RpcClient,
+        rpc_custom_error::{
+            JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+        },
+        rpc_request::{RpcError, RpcResponseErrorData},
+    },
+    solana_sdk::{
+        instruction::Instruction, message::Message, signature::Signature, transaction::Transaction,
+    },
+    std::{
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+const PENDING_TREE: &str = "pending";
+const LANDED_TREE: &str = "landed";
+
+/// Base delay before retrying a failed transaction, doubled on every attempt.
+const RETRY_BASE_DELAY_SECS: u64 = 5;
+const RETRY_MAX_DELAY_SECS: u64 = 10 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingTx {
+    pub instructions: Vec<Instruction>,
+    pub attempts: u32,
+    pub enqueued_at: u64,
+    pub next_attempt_at: u64,
+    /// Signature of the last sent attempt, checked before resending
+    /// so that a transaction that landed right before a restart isn't sent twice.
+    pub last_signature: Option<Signature>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LandedTx {
+    pub signature: Signature,
+    pub landed_at: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ProcessStats {
+    pub landed: usize,
+    pub retried: usize,
+    pub dropped: usize,
+}
+
+/// Persistent transaction queue for long running crank modes.
+///
+/// Transactions are enqueued under a caller provided key. A key that is already pending,
+/// or that landed within the retention window, is not enqueued again, which suppresses
+/// duplicates across restarts. Transactions rejected by a rate limited or lagging RPC node
+/// are retried with exponential backoff, up to `max_attempts`, any other failure drops them.
+pub struct RetryQueue {
+    db: sled::Db,
+    pending: sled::Tree,
+    landed: sled::Tree,
+    max_attempts: u32,
+    landed_retention_secs: u64,
+}
+
+impl RetryQueue {
+    pub fn open(path: &Path, max_attempts: u32, landed_retention_secs: u64) -> Result<Self> {
+        Self::from_db(sled::open(path)?, max_attempts, landed_retention_secs)
+    }
+
+    fn from_db(db: sled::Db, max_attempts: u32, landed_retention_secs: u64) -> Result<Self> {
+        let pending = db.open_tree(PENDING_TREE)?;
+        let landed = db.open_tree(LANDED_TREE)?;
+
+        Ok(Self {
+            db,
+            pending,
+            landed,
+            max_attempts,
+            landed_retention_secs,
+        })
+    }
+
+    /// Enqueue `instructions` under `key`, returns false if the key is a duplicate.
+    pub fn enqueue(&self, key: &str, instructions: Vec<Instruction>) -> Result<bool> {
+        if self.pending.contains_key(key)? || self.landed.contains_key(key)? {
+            return Ok(false);
+        }
+
+        let now = now();
+        let entry = PendingTx {
+            instructions,
+            attempts: 0,
+            enqueued_at: now,
+            next_attempt_at: now,
+            last_signature: None,
+            last_error: None,
+        };
+
+        self.pending.insert(key, bincode::serialize(&entry)?)?;
+        self.db.flush()?;
+
+        Ok(true)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Send every due pending transaction once.
+    pub fn process(&self, config: &Config) -> Result<ProcessStats> {
+        let rpc_client = config.mfi_program.rpc();
+        let signers = config.get_signers(false);
+        let now = now();
+        let mut stats = ProcessStats::default();
+
+        for item in self.pending.iter() {
+            let (key, value) = item?;
+            let key_str = String::from_utf8_lossy(&key).to_string();
+            let mut entry: PendingTx = bincode::deserialize(&value)?;
+
+            if entry.next_attempt_at > now {
+                continue;
+            }
+
+            // The previous attempt may have landed without us seeing the confirmation
+            if let Some(signature) = entry.last_signature {
+                match rpc_client.get_signature_status(&signature)? {
+                    Some(Ok(())) => {
+                        self.mark_landed(&key, signature)?;
+                        stats.landed += 1;
+                        continue;
+                    }
+                    Some(Err(err)) => {
+                        let transaction = Transaction::new_unsigned(Message::new(
+                            &entry.instructions,
+                            Some(&config.authority()),
+                        ));
+                        error!(
+                            "{} failed permanently: {}",
+                            key_str,
+                            errors::ClientError::from_transaction_error(
+                                err,
+                                Some(&transaction),
+                                None
+                            )
+                        );
+                        self.pending.remove(&key)?;
+                        stats.dropped += 1;
+                        continue;
+                    }
+                    None => {}
+                }
+            }
+
+            let blockhash = rpc_client.get_latest_blockhash()?;
+            let message = Message::new(
+                &config.with_compute_budget(&entry.instructions),
+                Some(&config.authority()),
+            );
+            let mut transaction = Transaction::new_unsigned(message);
+            transaction.partial_sign(&signers, blockhash);
+
+            let signature = transaction.signatures[0];
+
+            entry.attempts += 1;
+            entry.last_signature = Some(signature);
+            self.pending.insert(&key, bincode::serialize(&entry)?)?;
+            self.db.flush()?;
+
+            match send(&rpc_client, &transaction) {
+                Ok(signature) => {
+                    info!("{} landed: {}", key_str, signature);
+                    self.mark_landed(&key, signature)?;
+                    stats.landed += 1;
+                }
+                Err(err) if is_retryable(&err) && entry.attempts < self.max_attempts => {
+                    let delay =
+                        (RETRY_BASE_DELAY_SECS << entry.attempts.min(16)).min(RETRY_MAX_DELAY_SECS);
+
+                    warn!(
+                        "{} failed (attempt {}/{}), retrying in {}s: {}",
+                        key_str, entry.attempts, self.max_attempts, delay, err
+                    );
+
+                    entry.next_attempt_at = now + delay;
+                    entry.last_error = Some(err.to_string());
+                    self.pending.insert(&key, bincode::serialize(&entry)?)?;
+                    stats.retried += 1;
+                }
+                Err(err) => {
+                    error!(
+                        "{} dropped after {} attempts: {}",
+                        key_str,
+                        entry.attempts,
+                        errors::ClientError::from_rpc_error(err, Some(&transaction))
+                    );
+                    self.pending.remove(&key)?;
+                    stats.dropped += 1;
+                }
+            }
+        }
+
+        self.prune_landed()?;
+        self.db.flush()?;
+
+        Ok(stats)
+    }
+
+    fn mark_landed(&self, key: &[u8], signature: Signature) -> Result<()> {
+        let landed = LandedTx {
+            signature,
+            landed_at: now(),
+        };
+
+        self.landed.insert(key, bincode::serialize(&landed)?)?;
+        self.pending.remove(key)?;
+
+        Ok(())
+    }
+
+    fn prune_landed(&self) -> Result<()> {
+        let cutoff = now().saturating_sub(self.landed_retention_secs);
+
+        for item in self.landed.iter() {
+            let (key, value) = item?;
+            let landed: LandedTx = bincode::deserialize(&value)?;
+
+            if landed.landed_at < cutoff {
+                self.landed.remove(key)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn send(rpc_client: &RpcClient, transaction: &Transaction) -> Result<Signature, ClientError> {
+    rpc_client.send_and_confirm_transaction(transaction)
+}
+
+/// Errors worth retrying: the RPC node rate limited the request, or is behind the cluster.
+pub fn is_retryable(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Reqwest(err) => err.status() == Some(StatusCode::TOO_MANY_REQUESTS),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, data, .. }) => {
+            matches!(data, RpcResponseErrorData::NodeUnhealthy { .. })
+                || *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+                || *code == JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+        }
+        _ => false,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE,
+        solana_sdk::{instruction::InstructionError, transaction::TransactionError},
+    };
+
+    const LANDED_RETENTION_SECS: u64 = 60;
+
+    fn queue() -> RetryQueue {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        RetryQueue::from_db(db, 3, LANDED_RETENTION_SECS).unwrap()
+    }
+
+    fn rpc_response_error(code: i64, data: RpcResponseErrorData) -> ClientError {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code,
+            message: String::new(),
+            data,
+        })
+        .into()
+    }
+
+    #[test]
+    fn enqueue_suppresses_pending_and_landed_duplicates() {
+        let queue = queue();
+
+        assert!(queue.enqueue("a", vec![]).unwrap());
+        assert!(!queue.enqueue("a", vec![]).unwrap());
+        assert_eq!(queue.len(), 1);
+
+        queue.mark_landed(b"a", Signature::default()).unwrap();
+        assert!(queue.is_empty());
+        assert!(!queue.enqueue("a", vec![]).unwrap());
+
+        assert!(queue.enqueue("b", vec![]).unwrap());
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn prune_landed_forgets_expired_keys() {
+        let queue = queue();
+
+        let expired = LandedTx {
+            signature: Signature::default(),
+            landed_at: now() - LANDED_RETENTION_SECS - 1,
+        };
+        queue
+            .landed
+            .insert("expired", bincode::serialize(&expired).unwrap())
+            .unwrap();
+        queue.mark_landed(b"recent", Signature::default()).unwrap();
+
+        queue.prune_landed().unwrap();
+
+        assert!(queue.enqueue("expired", vec![]).unwrap());
+        assert!(!queue.enqueue("recent", vec![]).unwrap());
+    }
+
+    #[test]
+    fn only_rate_limit_and_node_behind_errors_are_retryable() {
+        assert!(is_retryable(&rpc_response_error(
+            JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+            RpcResponseErrorData::NodeUnhealthy {
+                num_slots_behind: Some(100),
+            },
+        )));
+        assert!(is_retryable(&rpc_response_error(
+            JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+            RpcResponseErrorData::Empty,
+        )));
+
+        assert!(!is_retryable(&rpc_response_error(
+            JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE,
+            RpcResponseErrorData::Empty,
+        )));
+        assert!(!is_retryable(
+            &ClientErrorKind::TransactionError(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(6009),
+            ))
+            .into()
+        ));
+        assert!(!is_retryable(
+            &ClientErrorKind::Custom("confirmation timed out".to_string()).into()
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+//! Minimal Squads v4 client, to propose the transactions built in multisig mode.
+//!
+//! Only the `vault_transaction_create` and `proposal_create` instructions are supported,
+//! members still review, approve and execute proposals in the Squads app.
+
+use {
+    anyhow::{anyhow, bail, Result},
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{
+        hash::hashv,
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+        pubkey,
+        pubkey::Pubkey,
+        signature::{Signature, Signer},
+        system_program,
+        transaction::Transaction,
+    },
+};
+
+pub const SQUADS_V4_PROGRAM_ID: Pubkey = pubkey!("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf");
+
+const SEED_PREFIX: &[u8] = b"multisig";
+const SEED_VAULT: &[u8] = b"vault";
+const SEED_TRANSACTION: &[u8] = b"transaction";
+const SEED_PROPOSAL: &[u8] = b"proposal";
+
+/// Offset of `transaction_index` in the multisig account:
+/// discriminator, create_key, config_authority, threshold (u16), time_lock (u32).
+const MULTISIG_TRANSACTION_INDEX_OFFSET: usize = 8 + 32 + 32 + 2 + 4;
+
+/// Squads multisig and member used to propose multisig transactions.
+#[derive(Copy, Clone)]
+pub struct SquadsProposer<'a> {
+    /// Multisig (settings) account, not the vault.
+    pub multisig: Pubkey,
+    pub vault_index: u8,
+    /// Member with the initiate permission, also pays for the proposal accounts.
+    pub creator: &'a dyn Signer,
+}
+
+impl std::fmt::Debug for SquadsProposer<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SquadsProposer")
+            .field("multisig", &self.multisig)
+            .field("vault_index", &self.vault_index)
+            .field("creator", &self.creator.pubkey())
+            .finish()
+    }
+}
+
+pub fn find_vault_pda(multisig: &Pubkey, vault_index: u8) -> Pubkey {
+    Pubkey::find_program_address(
+        &[SEED_PREFIX, multisig.as_ref(), SEED_VAULT, &[vault_index]],
+        &SQUADS_V4_PROGRAM_ID,
+    )
+    .0
+}
+
+pub fn find_transaction_pda(multisig: &Pubkey, transaction_index: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+        ],
+        &SQUADS_V4_PROGRAM_ID,
+    )
+    .0
+}
+
+pub fn find_proposal_pda(multisig: &Pubkey, transaction_index: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+            SEED_PROPOSAL,
+        ],
+        &SQUADS_V4_PROGRAM_ID,
+    )
+    .0
+}
+
+/// Create a vault transaction executing the instructions of `tx` and an active proposal for it.
+///
+/// `tx` must be paid for by the vault, like every transaction built in multisig mode.
+/// Returns the signature and the index of the new multisig transaction.
+pub fn propose_transaction(
+    tx: &Transaction,
+    rpc_client: &RpcClient,
+    proposer: SquadsProposer,
+) -> Result<(Signature, u64)> {
+    let vault = find_vault_pda(&proposer.multisig, proposer.vault_index);
+    if tx.message.account_keys.first() != Some(&vault) {
+        bail!(
+            "Transaction must be paid for by vault {} of multisig {}",
+            vault,
+            proposer.multisig
+        );
+    }
+
+    let multisig_account = rpc_client.get_account(&proposer.multisig)?;
+    if multisig_account.owner != SQUADS_V4_PROGRAM_ID {
+        bail!("{} is not a Squads v4 multisig", proposer.multisig);
+    }
+    let transaction_index = multisig_account
+        .data
+        .get(MULTISIG_TRANSACTION_INDEX_OFFSET..MULTISIG_TRANSACTION_INDEX_OFFSET + 8)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| anyhow!("Invalid multisig account {}", proposer.multisig))?
+        + 1;
+
+    let transaction_message = serialize_transaction_message(&tx.message)?;
+
+    // VaultTransactionCreateArgs { vault_index, ephemeral_signers, transaction_message, memo }
+    let mut vault_transaction_create_data = instruction_discriminator("vault_transaction_create");
+    vault_transaction_create_data.push(proposer.vault_index);
+    vault_transaction_create_data.push(0);
+    vault_transaction_create_data.extend((transaction_message.len() as u32).to_le_bytes());
+    vault_transaction_create_data.extend(transaction_message);
+    vault_transaction_create_data.push(0);
+
+    // ProposalCreateArgs { transaction_index, draft }
+    let mut proposal_create_data = instruction_discriminator("proposal_create");
+    proposal_create_data.extend(transaction_index.to_le_bytes());
+    proposal_create_data.push(0);
+
+    let creator = proposer.creator.pubkey();
+    let ixs = vec![
+        Instruction {
+            program_id: SQUADS_V4_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new(proposer.multisig, false),
+                AccountMeta::new(
+                    find_transaction_pda(&proposer.multisig, transaction_index),
+                    false,
+                ),
+                AccountMeta::new_readonly(creator, true),
+                AccountMeta::new(creator, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: vault_transaction_create_data,
+        },
+        Instruction {
+            program_id: SQUADS_V4_PROGRAM_ID,
+            accounts: vec![
+                AccountMeta::new_readonly(proposer.multisig, false),
+                AccountMeta::new(
+                    find_proposal_pda(&proposer.multisig, transaction_index),
+                    false,
+                ),
+                AccountMeta::new_readonly(creator, true),
+                AccountMeta::new(creator, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: proposal_create_data,
+        },
+    ];
+
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let proposal_tx = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&creator),
+        &[proposer.creator],
+        recent_blockhash,
+    );
+
+    let sig = rpc_client.send_and_confirm_transaction_with_spinner(&proposal_tx)?;
+
+    Ok((sig, transaction_index))
+}
+
+fn instruction_discriminator(name: &str) -> Vec<u8> {
+    hashv(&[format!("global:{name}").as_bytes()]).to_bytes()[..8].to_vec()
+}
+
+/// Serialize a legacy message in the `TransactionMessage` format of Squads v4,
+/// which uses u8 length prefixes (u16 for instruction data) instead of short vecs.
+fn serialize_transaction_message(message: &Message) -> Result<Vec<u8>> {
+    let header = &message.header;
+    let num_signers = header.num_required_signatures;
+    let num_writable_signers = num_signers - header.num_readonly_signed_accounts;
+    let num_writable_non_signers = u8::try_from(message.account_keys.len())?
+        - num_signers
+        - header.num_readonly_unsigned_accounts;
+
+    let mut data = vec![num_signers, num_writable_signers, num_writable_non_signers];
+
+    data.push(u8::try_from(message.account_keys.len())?);
+    for key in message.account_keys.iter() {
+        data.extend(key.as_ref());
+    }
+
+    data.push(u8::try_from(message.instructions.len())?);
+    for ix in message.instructions.iter() {
+        data.push(ix.program_id_index);
+        data.push(u8::try_from(ix.accounts.len())?);
+        data.extend(ix.accounts.iter());
+        data.extend(u16::try_from(ix.data.len())?.to_le_bytes());
+        data.extend(ix.data.iter());
+    }
+
+    // No address lookup tables
+    data.push(0);
+
+    Ok(data)
+}
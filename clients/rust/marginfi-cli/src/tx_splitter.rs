@@ -0,0 +1,369 @@
+use {
+    anyhow::{bail, Result},
+    solana_sdk::{
+        address_lookup_table_account::AddressLookupTableAccount,
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        instruction::Instruction,
+        message::{v0, Message, VersionedMessage},
+        packet::PACKET_DATA_SIZE,
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{Transaction, VersionedTransaction},
+    },
+    std::collections::HashSet,
+};
+
+/// Compute unit limit of a single transaction.
+pub const MAX_TX_COMPUTE_UNITS: u32 = 1_400_000;
+/// Max number of distinct accounts a transaction can lock.
+pub const MAX_TX_ACCOUNT_LOCKS: usize = 64;
+/// Compute units consumed by the compute budget instructions themselves.
+const COMPUTE_BUDGET_IX_UNITS: u32 = 150;
+
+/// Effect of an instruction group on the health of the marginfi account it operates on.
+///
+/// Health is checked on-chain at the end of every risk increasing instruction, so groups
+/// lowering health must land after the groups making room for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthImpact {
+    /// e.g. deposit or repay
+    Improves,
+    /// e.g. settling or withdrawing emissions
+    Neutral,
+    /// e.g. withdraw or borrow
+    Lowers,
+}
+
+/// Instructions that must land together in the same transaction.
+#[derive(Debug, Clone)]
+pub struct IxGroup {
+    pub instructions: Vec<Instruction>,
+    /// Estimated compute units consumed by the group.
+    pub compute_units: u32,
+    pub health_impact: HealthImpact,
+    /// Groups with a lower priority are sent first.
+    pub priority: u8,
+}
+
+impl IxGroup {
+    pub fn new(instructions: Vec<Instruction>, compute_units: u32) -> Self {
+        Self {
+            instructions,
+            compute_units,
+            health_impact: HealthImpact::Neutral,
+            priority: 0,
+        }
+    }
+
+    pub fn with_health_impact(mut self, health_impact: HealthImpact) -> Self {
+        self.health_impact = health_impact;
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedTx {
+    /// Instructions of the transaction, starting with its compute budget instructions.
+    pub instructions: Vec<Instruction>,
+    pub compute_units: u32,
+    /// Serialized size of the signed transaction.
+    pub size: usize,
+    /// Most risk increasing impact of the groups in the transaction.
+    pub health_impact: HealthImpact,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SplitPlan {
+    /// Transactions, in the order they must be sent.
+    pub transactions: Vec<PlannedTx>,
+    /// Health lowering groups packed before a later group improving health,
+    /// which may fail their health check if they land first.
+    pub health_warnings: Vec<String>,
+}
+
+/// Splits a logical operation, too large for a single transaction, into the minimal number of
+/// transactions that fit the size, compute and account lock limits.
+///
+/// Groups are ordered by priority, health improving groups first within a priority,
+/// and packed greedily in that order, which is minimal for an ordered split.
+/// With lookup tables, sizes are measured for v0 transactions resolving accounts through them.
+pub struct TxSplitter {
+    pub payer: Pubkey,
+    /// Micro-lamports per compute unit, no priority fee instruction if `None`.
+    pub compute_unit_price: Option<u64>,
+    pub lookup_tables: Vec<AddressLookupTableAccount>,
+}
+
+impl TxSplitter {
+    pub fn new(payer: Pubkey) -> Self {
+        Self {
+            payer,
+            compute_unit_price: None,
+            lookup_tables: vec![],
+        }
+    }
+
+    pub fn split(&self, mut groups: Vec<IxGroup>) -> Result<SplitPlan> {
+        groups.sort_by_key(|group| (group.priority, group.health_impact));
+
+        let mut plan = SplitPlan::default();
+        let mut current: Vec<&IxGroup> = vec![];
+
+        for group in groups.iter() {
+            if let Err(err) = self.build(&[group]) {
+                bail!(
+                    "Instruction group of {} instructions doesn't fit in a single transaction: {}",
+                    group.instructions.len(),
+                    err
+                );
+            }
+
+            current.push(group);
+
+            if self.build(&current).is_err() {
+                current.pop();
+                plan.transactions.push(self.build(&current)?);
+                current = vec![group];
+            }
+        }
+
+        if !current.is_empty() {
+            plan.transactions.push(self.build(&current)?);
+        }
+
+        plan.health_warnings = Self::check_health_ordering(&plan.transactions);
+
+        Ok(plan)
+    }
+
+    /// Compile a planned transaction into a legacy transaction, ignoring the lookup tables.
+    pub fn to_transaction(&self, tx: &PlannedTx) -> Transaction {
+        Transaction::new_unsigned(Message::new(&tx.instructions, Some(&self.payer)))
+    }
+
+    /// Compile a planned transaction into a v0 transaction resolving accounts through the lookup tables.
+    pub fn to_versioned_message(
+        &self,
+        tx: &PlannedTx,
+        recent_blockhash: Hash,
+    ) -> Result<VersionedMessage> {
+        Ok(VersionedMessage::V0(v0::Message::try_compile(
+            &self.payer,
+            &tx.instructions,
+            &self.lookup_tables,
+            recent_blockhash,
+        )?))
+    }
+
+    fn build(&self, groups: &[&IxGroup]) -> Result<PlannedTx> {
+        let compute_units =
+            groups.iter().map(|group| group.compute_units).sum::<u32>() + COMPUTE_BUDGET_IX_UNITS;
+
+        if compute_units > MAX_TX_COMPUTE_UNITS {
+            bail!(
+                "{} compute units over the {} limit",
+                compute_units,
+                MAX_TX_COMPUTE_UNITS
+            );
+        }
+
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            compute_units,
+        )];
+        if let Some(price) = self.compute_unit_price {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+        instructions.extend(
+            groups
+                .iter()
+                .flat_map(|group| group.instructions.iter().cloned()),
+        );
+
+        let account_locks = instructions
+            .iter()
+            .flat_map(|ix| {
+                ix.accounts
+                    .iter()
+                    .map(|meta| meta.pubkey)
+                    .chain(std::iter::once(ix.program_id))
+            })
+            .chain(std::iter::once(self.payer))
+            .collect::<HashSet<_>>()
+            .len();
+
+        if account_locks > MAX_TX_ACCOUNT_LOCKS {
+            bail!(
+                "{} accounts over the {} account locks limit",
+                account_locks,
+                MAX_TX_ACCOUNT_LOCKS
+            );
+        }
+
+        let size = if self.lookup_tables.is_empty() {
+            bincode::serialized_size(&Transaction::new_unsigned(Message::new(
+                &instructions,
+                Some(&self.payer),
+            )))? as usize
+        } else {
+            let message = VersionedMessage::V0(v0::Message::try_compile(
+                &self.payer,
+                &instructions,
+                &self.lookup_tables,
+                Hash::default(),
+            )?);
+
+            bincode::serialized_size(&VersionedTransaction {
+                signatures: vec![
+                    Signature::default();
+                    message.header().num_required_signatures as usize
+                ],
+                message,
+            })? as usize
+        };
+
+        if size > PACKET_DATA_SIZE {
+            bail!("{} bytes over the {} bytes limit", size, PACKET_DATA_SIZE);
+        }
+
+        Ok(PlannedTx {
+            instructions,
+            compute_units,
+            size,
+            health_impact: groups
+                .iter()
+                .map(|group| group.health_impact)
+                .max()
+                .unwrap_or(HealthImpact::Neutral),
+        })
+    }
+
+    fn check_health_ordering(transactions: &[PlannedTx]) -> Vec<String> {
+        let mut warnings = vec![];
+
+        for (i, tx) in transactions.iter().enumerate() {
+            if tx.health_impact != HealthImpact::Lowers {
+                continue;
+            }
+
+            if let Some(j) = transactions[i + 1..]
+                .iter()
+                .position(|later| later.health_impact == HealthImpact::Improves)
+            {
+                warnings.push(format!(
+                    "Transaction {} lowers health before transaction {} improves it, it may fail its health check",
+                    i + 1,
+                    i + j + 2
+                ));
+            }
+        }
+
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, solana_sdk::instruction::AccountMeta};
+
+    fn ix(accounts: usize) -> Instruction {
+        Instruction {
+            program_id: marginfi::id(),
+            accounts: (0..accounts)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+            data: vec![0; 8],
+        }
+    }
+
+    #[test]
+    fn split_is_ordered_and_within_limits() {
+        let splitter = TxSplitter::new(Pubkey::new_unique());
+
+        let groups = (0..40)
+            .map(|i| {
+                let group = IxGroup::new(vec![ix(2)], 20_000);
+                if i % 2 == 0 {
+                    group.with_health_impact(HealthImpact::Lowers)
+                } else {
+                    group.with_health_impact(HealthImpact::Improves)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let plan = splitter.split(groups).unwrap();
+
+        assert!(plan.transactions.len() > 1);
+        assert!(plan
+            .transactions
+            .iter()
+            .all(|tx| tx.size <= PACKET_DATA_SIZE && tx.compute_units <= MAX_TX_COMPUTE_UNITS));
+
+        let ixs = plan
+            .transactions
+            .iter()
+            .map(|tx| tx.instructions.len() - 1)
+            .sum::<usize>();
+        assert_eq!(ixs, 40);
+
+        // Health improving groups are all sent first
+        assert!(plan
+            .transactions
+            .windows(2)
+            .all(|txs| txs[0].health_impact <= txs[1].health_impact));
+        assert!(plan.health_warnings.is_empty());
+    }
+
+    #[test]
+    fn split_respects_compute_limit() {
+        let splitter = TxSplitter::new(Pubkey::new_unique());
+
+        let groups = (0..5)
+            .map(|_| IxGroup::new(vec![ix(1)], 600_000))
+            .collect::<Vec<_>>();
+
+        let plan = splitter.split(groups).unwrap();
+        assert_eq!(plan.transactions.len(), 3);
+
+        let res = splitter.split(vec![IxGroup::new(vec![ix(1)], 1_500_000)]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn lookup_tables_pack_more_groups() {
+        let payer = Pubkey::new_unique();
+        let groups = (0..30)
+            .map(|_| IxGroup::new(vec![ix(3)], 10_000))
+            .collect::<Vec<_>>();
+
+        let legacy_plan = TxSplitter::new(payer).split(groups.clone()).unwrap();
+
+        let addresses = groups
+            .iter()
+            .flat_map(|group| {
+                group.instructions[0]
+                    .accounts
+                    .iter()
+                    .map(|meta| meta.pubkey)
+            })
+            .collect::<Vec<_>>();
+
+        let mut splitter = TxSplitter::new(payer);
+        splitter.lookup_tables = addresses
+            .chunks(256)
+            .map(|addresses| AddressLookupTableAccount {
+                key: Pubkey::new_unique(),
+                addresses: addresses.to_vec(),
+            })
+            .collect();
+
+        let lut_plan = splitter.split(groups).unwrap();
+
+        assert!(lut_plan.transactions.len() < legacy_plan.transactions.len());
+    }
+}
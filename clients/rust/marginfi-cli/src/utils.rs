@@ -1,53 +1,91 @@
 use {
-    anyhow::{bail, Result},
+    anyhow::{anyhow, bail, Result},
+    base64::{engine::general_purpose, Engine},
     fixed::types::I80F48,
     fixed_macro::types::I80F48,
     log::error,
-    marginfi::{
-        bank_authority_seed,
-        state::{
-            marginfi_account::MarginfiAccount,
-            marginfi_group::{Bank, BankVaultType},
-        },
+    marginfi_sdk::transaction::{build_versioned_transaction, load_lookup_table},
+    solana_client::rpc_client::{RpcClient, SerializableTransaction},
+    solana_remote_wallet::{
+        locator::Locator, remote_keypair::generate_remote_keypair,
+        remote_wallet::maybe_wallet_manager,
     },
-    solana_client::rpc_client::RpcClient,
     solana_sdk::{
-        instruction::AccountMeta, pubkey::Pubkey, signature::Signature, transaction::Transaction,
+        derivation_path::DerivationPath,
+        instruction::Instruction,
+        pubkey::Pubkey,
+        signature::{read_keypair_file, Signature, Signer},
+        signers::Signers,
+        transaction::{Transaction, VersionedTransaction},
     },
-    std::collections::HashMap,
+    uriparse::URIReference,
 };
 
-use marginfi::{
-    bank_seed,
-    constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED, MAX_ORACLE_KEYS},
+#[cfg(feature = "admin")]
+use {
+    marginfi::{constants::MAX_ORACLE_KEYS, state::marginfi_group::BankVaultType},
+    marginfi_sdk::pda::{find_bank_vault_authority_pda, find_bank_vault_pda},
+};
+
+use crate::{
+    config::{Config, TxEncoding, TxMode},
+    errors::{ClientError, InstructionProgramId},
+    squads,
 };
 
-use crate::config::TxMode;
+/// Load the signer at `path`: a keypair file, or a hardware wallet URI such as
+/// `usb://ledger` or `usb://ledger?key=0/0` (with an explicit derivation path).
+pub fn signer_from_path(path: &str) -> Result<Box<dyn Signer>> {
+    if !path.starts_with("usb://") {
+        let keypair = read_keypair_file(&*shellexpand::tilde(path))
+            .map_err(|e| anyhow!("Failed to read keypair file {}: {}", path, e))?;
+
+        return Ok(Box::new(keypair));
+    }
+
+    let uri = URIReference::try_from(path)?;
+    let locator = Locator::new_from_uri(&uri)?;
+    let derivation_path = DerivationPath::from_uri_key_query(&uri)?.unwrap_or_default();
+
+    let wallet_manager =
+        maybe_wallet_manager()?.ok_or_else(|| anyhow!("No hardware wallet found for {}", path))?;
+    let remote_keypair =
+        generate_remote_keypair(locator, derivation_path, &wallet_manager, false, "signer")?;
 
+    Ok(Box::new(remote_keypair))
+}
+
+/// Failed transactions return a [`ClientError`], decoding the program error when possible.
 pub fn process_transaction(
     tx: &Transaction,
     rpc_client: &RpcClient,
     tx_mode: TxMode,
 ) -> Result<Signature> {
     match tx_mode {
-        TxMode::DryRun => match rpc_client.simulate_transaction(tx) {
-            Ok(response) => {
-                println!("------- program logs -------");
-                response
-                    .value
-                    .logs
-                    .unwrap()
-                    .into_iter()
-                    .for_each(|line| println!("{line}"));
-                println!("----------------------------");
-                Ok(Signature::default())
-            }
-            Err(err) => bail!(err),
-        },
-        TxMode::Multisig => {
+        TxMode::DryRun => simulate_transaction(tx, rpc_client),
+        TxMode::Multisig {
+            proposer: Some(proposer),
+            ..
+        } => {
+            let (sig, transaction_index) = squads::propose_transaction(tx, rpc_client, proposer)?;
+
+            println!(
+                "Proposed transaction #{} to multisig {} (sig: {})",
+                transaction_index, proposer.multisig, sig
+            );
+
+            Ok(Signature::default())
+        }
+        TxMode::Multisig {
+            encoding,
+            proposer: None,
+        } => {
             let bytes = bincode::serialize(tx)?;
             let tx_size = bytes.len();
-            let tx_serialized = bs58::encode(bytes).into_string();
+            let tx_serialized = match encoding {
+                TxEncoding::Base58 => bs58::encode(bytes).into_string(),
+                TxEncoding::Base64 => general_purpose::STANDARD.encode(bytes),
+            };
 
             println!("tx size: {} bytes", tx_size);
             println!("------- transaction -------");
@@ -56,62 +94,151 @@ pub fn process_transaction(
 
             Ok(Signature::default())
         }
-        TxMode::Normal => match rpc_client.send_and_confirm_transaction_with_spinner(tx) {
-            Ok(sig) => Ok(sig),
-            Err(err) => {
-                error!("transaction failed: {:?}", err);
-                bail!(err);
-            }
-        },
+        TxMode::Normal => send_transaction(tx, rpc_client),
     }
 }
 
-pub fn find_bank_vault_pda(
-    bank_pk: &Pubkey,
-    vault_type: BankVaultType,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(bank_seed!(vault_type, bank_pk), program_id)
+/// Same as [`process_transaction`], for v0 transactions. Multisig transactions must be legacy ones.
+pub fn process_versioned_transaction(
+    tx: &VersionedTransaction,
+    rpc_client: &RpcClient,
+    tx_mode: TxMode,
+) -> Result<Signature> {
+    match tx_mode {
+        TxMode::DryRun => simulate_transaction(tx, rpc_client),
+        TxMode::Multisig { .. } => bail!("Multisig transactions can't be versioned transactions"),
+        TxMode::Normal => send_transaction(tx, rpc_client),
+    }
 }
 
-pub fn find_bank_vault_authority_pda(
-    bank_pk: &Pubkey,
-    vault_type: BankVaultType,
-    program_id: &Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(bank_authority_seed!(vault_type, bank_pk), program_id)
+/// Sign and process `ixs`, as a v0 transaction using the lookup table of the profile if any,
+/// in which case the accounts of the transaction aren't limited by the legacy transaction size.
+///
+/// Multisig transactions stay legacy ones, to be proposed or printed.
+pub fn process_instructions<T: Signers>(
+    config: &Config,
+    payer: &Pubkey,
+    ixs: &[Instruction],
+    signers: &T,
+) -> Result<Signature> {
+    let rpc_client = config.mfi_program.rpc();
+    let recent_blockhash = rpc_client.get_latest_blockhash()?;
+    let ixs = config.with_compute_budget(ixs);
+
+    match (config.lookup_table, config.get_tx_mode()) {
+        (Some(lookup_table), tx_mode @ (TxMode::DryRun | TxMode::Normal)) => {
+            let lookup_table = load_lookup_table(&rpc_client, &lookup_table)?;
+            let tx = build_versioned_transaction(
+                payer,
+                &ixs,
+                &[lookup_table],
+                recent_blockhash,
+                signers,
+            )?;
+
+            process_versioned_transaction(&tx, &rpc_client, tx_mode)
+        }
+        (_, tx_mode) => {
+            let tx =
+                Transaction::new_signed_with_payer(&ixs, Some(payer), signers, recent_blockhash);
+
+            process_transaction(&tx, &rpc_client, tx_mode)
+        }
+    }
 }
 
+fn simulate_transaction<T: SerializableTransaction + InstructionProgramId>(
+    tx: &T,
+    rpc_client: &RpcClient,
+) -> Result<Signature> {
+    match rpc_client.simulate_transaction(tx) {
+        Ok(response) => {
+            let logs = response.value.logs.unwrap_or_default();
+
+            println!("------- program logs -------");
+            logs.iter().for_each(|line| println!("{line}"));
+            println!("----------------------------");
+
+            if let Some(err) = response.value.err {
+                return Err(ClientError::from_transaction_error(err, Some(tx), Some(&logs)).into());
+            }
+
+            Ok(Signature::default())
+        }
+        Err(err) => Err(ClientError::from_rpc_error(err, Some(tx)).into()),
+    }
+}
+
+fn send_transaction<T: SerializableTransaction + InstructionProgramId>(
+    tx: &T,
+    rpc_client: &RpcClient,
+) -> Result<Signature> {
+    match rpc_client.send_and_confirm_transaction_with_spinner(tx) {
+        Ok(sig) => Ok(sig),
+        Err(err) => {
+            error!("transaction failed: {:?}", err);
+            Err(ClientError::from_rpc_error(err, Some(tx)).into())
+        }
+    }
+}
+
+/// Every address derived for a bank, so that indexers and monitors can be configured
+/// without re-deriving the PDAs.
 #[cfg(feature = "admin")]
-pub fn find_bank_emssions_auth_pda(
-    bank: Pubkey,
-    emissions_mint: Pubkey,
-    program_id: Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[
-            EMISSIONS_AUTH_SEED.as_bytes(),
-            bank.as_ref(),
-            emissions_mint.as_ref(),
-        ],
-        &program_id,
-    )
+#[derive(Debug, serde::Serialize)]
+pub struct BankManifest {
+    pub program_id: String,
+    pub marginfi_group: String,
+    pub bank: String,
+    pub mint: String,
+    pub oracle_setup: String,
+    pub oracle: String,
+    pub liquidity_vault: String,
+    pub liquidity_vault_authority: String,
+    pub insurance_vault: String,
+    pub insurance_vault_authority: String,
+    pub fee_vault: String,
+    pub fee_vault_authority: String,
 }
 
 #[cfg(feature = "admin")]
-pub fn find_bank_emssions_token_account_pda(
-    bank: Pubkey,
-    emissions_mint: Pubkey,
-    program_id: Pubkey,
-) -> (Pubkey, u8) {
-    Pubkey::find_program_address(
-        &[
-            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
-            bank.as_ref(),
-            emissions_mint.as_ref(),
-        ],
-        &program_id,
-    )
+impl BankManifest {
+    pub fn new(
+        program_id: &Pubkey,
+        marginfi_group: &Pubkey,
+        bank_pk: &Pubkey,
+        bank_config: &marginfi::state::marginfi_group::BankConfig,
+        mint: &Pubkey,
+    ) -> Self {
+        let vault = |vault_type| find_bank_vault_pda(bank_pk, vault_type, program_id).0;
+        let vault_authority =
+            |vault_type| find_bank_vault_authority_pda(bank_pk, vault_type, program_id).0;
+
+        Self {
+            program_id: program_id.to_string(),
+            marginfi_group: marginfi_group.to_string(),
+            bank: bank_pk.to_string(),
+            mint: mint.to_string(),
+            oracle_setup: format!("{:?}", bank_config.oracle_setup),
+            oracle: bank_config.oracle_keys[0].to_string(),
+            liquidity_vault: vault(BankVaultType::Liquidity).to_string(),
+            liquidity_vault_authority: vault_authority(BankVaultType::Liquidity).to_string(),
+            insurance_vault: vault(BankVaultType::Insurance).to_string(),
+            insurance_vault_authority: vault_authority(BankVaultType::Insurance).to_string(),
+            fee_vault: vault(BankVaultType::Fee).to_string(),
+            fee_vault_authority: vault_authority(BankVaultType::Fee).to_string(),
+        }
+    }
+
+    pub fn write_to_file(&self, path: &std::path::Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
 }
 
 #[cfg(feature = "admin")]
@@ -139,54 +266,6 @@ pub const EXP_10_I80F48: [I80F48; 15] = [
     I80F48!(100_000_000_000_000),
 ];
 
-pub fn load_observation_account_metas(
-    marginfi_account: &MarginfiAccount,
-    banks_map: &HashMap<Pubkey, Bank>,
-    include_banks: Vec<Pubkey>,
-    exclude_banks: Vec<Pubkey>,
-) -> Vec<AccountMeta> {
-    let mut bank_pks = marginfi_account
-        .lending_account
-        .balances
-        .iter()
-        .filter_map(|balance| balance.active.then_some(balance.bank_pk))
-        .collect::<Vec<_>>();
-
-    for bank_pk in include_banks {
-        if !bank_pks.contains(&bank_pk) {
-            bank_pks.push(bank_pk);
-        }
-    }
-
-    bank_pks.retain(|bank_pk| !exclude_banks.contains(bank_pk));
-
-    let mut banks = vec![];
-    for bank_pk in bank_pks.clone() {
-        let bank = banks_map.get(&bank_pk).unwrap();
-        banks.push(bank);
-    }
-
-    let account_metas = banks
-        .iter()
-        .zip(bank_pks.iter())
-        .flat_map(|(bank, bank_pk)| {
-            vec![
-                AccountMeta {
-                    pubkey: *bank_pk,
-                    is_signer: false,
-                    is_writable: false,
-                },
-                AccountMeta {
-                    pubkey: bank.config.oracle_keys[0],
-                    is_signer: false,
-                    is_writable: false,
-                },
-            ]
-        })
-        .collect::<Vec<_>>();
-    account_metas
-}
-
 #[cfg(feature = "admin")]
 pub fn calc_emissions_rate(ui_rate: f64, emissions_mint_decimals: u8) -> u64 {
     (ui_rate * 10u64.pow(emissions_mint_decimals as u32) as f64) as u64
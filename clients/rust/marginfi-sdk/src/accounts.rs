@@ -0,0 +1,199 @@
+use {
+    crate::pda::{find_fee_state_pda, find_sub_account_counter_pda, find_sub_account_pda},
+    anchor_lang::{AccountDeserialize, Discriminator},
+    anyhow::{anyhow, Result},
+    marginfi::state::{
+        fee_state::FeeState,
+        marginfi_account::{MarginfiAccount, SubAccountCounter},
+        marginfi_group::{Bank, MarginfiGroup},
+    },
+    solana_account_decoder::UiAccountEncoding,
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+        rpc_filter::{Memcmp, RpcFilterType},
+    },
+    solana_sdk::{
+        account_info::IntoAccountInfo,
+        clock::Clock,
+        pubkey::Pubkey,
+        sysvar::{self, Sysvar},
+    },
+    std::{collections::HashMap, mem::size_of},
+};
+
+/// Max number of accounts fetched by a single `getMultipleAccounts` request.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Offset of the group of a bank, after the discriminator, mint and mint decimals.
+const BANK_GROUP_OFFSET: usize = 8 + size_of::<Pubkey>() + size_of::<u8>();
+/// Offset of the group of a marginfi account, right after the discriminator.
+const MARGINFI_ACCOUNT_GROUP_OFFSET: usize = 8;
+/// Offset of the authority of a marginfi account, after the discriminator and group.
+const MARGINFI_ACCOUNT_AUTHORITY_OFFSET: usize = 8 + size_of::<Pubkey>();
+
+/// Deserialize a marginfi account (group, bank, marginfi account, ...), checking its discriminator.
+pub fn deserialize<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..]).map_err(|err| anyhow!("Failed to deserialize: {}", err))
+}
+
+pub fn load<T: AccountDeserialize>(rpc_client: &RpcClient, address: &Pubkey) -> Result<T> {
+    let account = rpc_client.get_account(address)?;
+
+    deserialize(&account.data).map_err(|err| anyhow!("{}: {}", address, err))
+}
+
+/// Load `addresses` in batches of [`MAX_MULTIPLE_ACCOUNTS`], `None` for missing accounts.
+pub fn load_multiple<T: AccountDeserialize>(
+    rpc_client: &RpcClient,
+    addresses: &[Pubkey],
+) -> Result<Vec<Option<T>>> {
+    let mut accounts = Vec::with_capacity(addresses.len());
+
+    for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        for (address, account) in chunk.iter().zip(rpc_client.get_multiple_accounts(chunk)?) {
+            accounts.push(
+                account
+                    .map(|account| {
+                        deserialize(&account.data).map_err(|err| anyhow!("{}: {}", address, err))
+                    })
+                    .transpose()?,
+            );
+        }
+    }
+
+    Ok(accounts)
+}
+
+pub fn load_clock(rpc_client: &RpcClient) -> Result<Clock> {
+    let mut clock = rpc_client.get_account(&sysvar::clock::ID)?;
+
+    Ok(Clock::from_account_info(
+        &(&sysvar::clock::ID, &mut clock).into_account_info(),
+    )?)
+}
+
+/// The program fee state, `None` until it is initialized.
+pub fn load_fee_state(rpc_client: &RpcClient, program_id: &Pubkey) -> Result<Option<FeeState>> {
+    Ok(
+        load_multiple(rpc_client, &[find_fee_state_pda(program_id).0])?
+            .pop()
+            .flatten(),
+    )
+}
+
+pub fn load_group(rpc_client: &RpcClient, marginfi_group: &Pubkey) -> Result<MarginfiGroup> {
+    load(rpc_client, marginfi_group)
+}
+
+/// Load every bank of `marginfi_group`, migrated to the latest layout version and with interest
+/// accrued up to the current cluster time, so that they match what the next instruction will see.
+pub fn load_banks(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    marginfi_group: &Pubkey,
+) -> Result<HashMap<Pubkey, Bank>> {
+    let clock = load_clock(rpc_client)?;
+
+    load_program_accounts::<Bank>(
+        rpc_client,
+        program_id,
+        vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            BANK_GROUP_OFFSET,
+            marginfi_group.to_bytes().to_vec(),
+        ))],
+    )?
+    .into_iter()
+    .map(|(bank_pk, mut bank)| {
+        bank.migrate()
+            .map_err(|err| anyhow!("Failed to migrate bank {}: {}", bank_pk, err))?;
+        bank.accrue_interest(clock.unix_timestamp)
+            .map_err(|err| anyhow!("Failed to accrue interest of bank {}: {}", bank_pk, err))?;
+
+        Ok((bank_pk, bank))
+    })
+    .collect()
+}
+
+/// Load the marginfi accounts of `marginfi_group`, only those of `authority` if provided.
+pub fn load_marginfi_accounts(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    marginfi_group: &Pubkey,
+    authority: Option<&Pubkey>,
+) -> Result<Vec<(Pubkey, MarginfiAccount)>> {
+    let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+        MARGINFI_ACCOUNT_GROUP_OFFSET,
+        marginfi_group.to_bytes().to_vec(),
+    ))];
+    if let Some(authority) = authority {
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            MARGINFI_ACCOUNT_AUTHORITY_OFFSET,
+            authority.to_bytes().to_vec(),
+        )));
+    }
+
+    load_program_accounts(rpc_client, program_id, filters)
+}
+
+/// Sub-accounts of `authority` in `marginfi_group`, by index. Empty if it has none yet.
+pub fn load_sub_accounts(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    marginfi_group: &Pubkey,
+    authority: &Pubkey,
+) -> Result<Vec<(Pubkey, MarginfiAccount)>> {
+    let (counter_pk, _) = find_sub_account_counter_pda(marginfi_group, authority, program_id);
+    let account_count = match load_multiple::<SubAccountCounter>(rpc_client, &[counter_pk])?.pop() {
+        Some(Some(counter)) => counter.account_count,
+        _ => return Ok(vec![]),
+    };
+
+    let addresses = (0..account_count)
+        .map(|account_index| {
+            find_sub_account_pda(marginfi_group, authority, account_index as u16, program_id).0
+        })
+        .collect::<Vec<_>>();
+
+    addresses
+        .iter()
+        .zip(load_multiple::<MarginfiAccount>(rpc_client, &addresses)?)
+        .map(|(address, account)| {
+            account
+                .map(|account| (*address, account))
+                .ok_or_else(|| anyhow!("Sub-account {} not found", address))
+        })
+        .collect()
+}
+
+fn load_program_accounts<T: AccountDeserialize + Discriminator>(
+    rpc_client: &RpcClient,
+    program_id: &Pubkey,
+    mut filters: Vec<RpcFilterType>,
+) -> Result<Vec<(Pubkey, T)>> {
+    filters.insert(
+        0,
+        RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, T::discriminator().to_vec())),
+    );
+
+    rpc_client
+        .get_program_accounts_with_config(
+            program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?
+        .into_iter()
+        .map(|(address, account)| {
+            let account =
+                deserialize(&account.data).map_err(|err| anyhow!("{}: {}", address, err))?;
+
+            Ok((address, account))
+        })
+        .collect()
+}
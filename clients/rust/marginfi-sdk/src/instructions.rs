@@ -0,0 +1,1350 @@
+//! Builders for every marginfi instruction.
+//!
+//! Group and admin instructions are free functions. Instructions acting on a marginfi account
+//! are methods of [`MarginfiAccountContext`], signed by the account authority, which resolves
+//! the health check remaining accounts from the balances of the account.
+
+use {
+    crate::pda::{
+        find_bank_emissions_auth_pda, find_bank_emissions_token_account_pda,
+        find_bank_vault_authority_pda, find_bank_vault_pda, find_fee_state_pda,
+        find_program_data_pda, find_receipt_mint_authority_pda, find_receipt_mint_pda,
+        find_sub_account_counter_pda, find_sub_account_pda,
+    },
+    anchor_lang::{InstructionData, ToAccountMetas},
+    anchor_spl::{
+        associated_token::{self, get_associated_token_address},
+        token::{self, spl_token::native_mint},
+    },
+    anyhow::{anyhow, Result},
+    marginfi::{
+        instructions::{AutoDeleverageConfig, SplitPositionCollateral},
+        state::{
+            fee_state::{FeeState, FeeStateConfig},
+            marginfi_account::MarginfiAccount,
+            marginfi_group::{Bank, BankConfig, BankConfigOpt, BankVaultType, GroupConfig},
+        },
+    },
+    solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+        system_program, sysvar,
+    },
+    std::collections::HashMap,
+};
+
+// --------------------------------------------------------------------------------------------------------------------
+// Remaining accounts
+// --------------------------------------------------------------------------------------------------------------------
+
+/// (bank, oracle) pairs of the active balances of `marginfi_account`, as expected by the risk engine.
+///
+/// `include_banks` are appended when the account has no balance in them yet, e.g. the bank
+/// of a first borrow, and `exclude_banks` are skipped, e.g. the bank of a balance closed by
+/// the instruction.
+pub fn load_observation_account_metas(
+    marginfi_account: &MarginfiAccount,
+    banks_map: &HashMap<Pubkey, Bank>,
+    include_banks: Vec<Pubkey>,
+    exclude_banks: Vec<Pubkey>,
+) -> Vec<AccountMeta> {
+    let mut bank_pks = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .filter_map(|balance| balance.active.then_some(balance.bank_pk))
+        .collect::<Vec<_>>();
+
+    for bank_pk in include_banks {
+        if !bank_pks.contains(&bank_pk) {
+            bank_pks.push(bank_pk);
+        }
+    }
+
+    bank_pks.retain(|bank_pk| !exclude_banks.contains(bank_pk));
+
+    let mut banks = vec![];
+    for bank_pk in bank_pks.clone() {
+        let bank = banks_map.get(&bank_pk).unwrap();
+        banks.push(bank);
+    }
+
+    let account_metas = banks
+        .iter()
+        .zip(bank_pks.iter())
+        .flat_map(|(bank, bank_pk)| {
+            vec![
+                AccountMeta {
+                    pubkey: *bank_pk,
+                    is_signer: false,
+                    is_writable: false,
+                },
+                AccountMeta {
+                    pubkey: bank.config.oracle_keys[0],
+                    is_signer: false,
+                    is_writable: false,
+                },
+            ]
+        })
+        .collect::<Vec<_>>();
+    account_metas
+}
+
+/// Observation account metas of a marginfi account once it holds balances in `new_bank_pks`.
+///
+/// The risk engine expects banks in balance slot order, and new balances take
+/// the first empty slot, so banks can't simply be appended like `include_banks` does.
+pub fn load_post_balances_account_metas(
+    marginfi_account: &MarginfiAccount,
+    banks_map: &HashMap<Pubkey, Bank>,
+    new_bank_pks: &[Pubkey],
+) -> Result<Vec<AccountMeta>> {
+    let mut marginfi_account = *marginfi_account;
+    let lending_account = &mut marginfi_account.lending_account;
+
+    for bank_pk in new_bank_pks {
+        if lending_account
+            .balances
+            .iter()
+            .any(|balance| balance.active && balance.bank_pk == *bank_pk)
+        {
+            continue;
+        }
+
+        let empty_index = lending_account
+            .get_first_empty_balance()
+            .ok_or_else(|| anyhow!("No empty balance slot for bank {}", bank_pk))?;
+
+        let balance = &mut lending_account.balances[empty_index];
+        balance.active = true;
+        balance.bank_pk = *bank_pk;
+    }
+
+    Ok(load_observation_account_metas(
+        &marginfi_account,
+        banks_map,
+        vec![],
+        vec![],
+    ))
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+// Group
+// --------------------------------------------------------------------------------------------------------------------
+
+/// `marginfi_group` is a new keypair, signing the transaction along with `admin`.
+pub fn initialize_group(program_id: &Pubkey, marginfi_group: Pubkey, admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MarginfiGroupInitialize {
+            marginfi_group,
+            admin,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MarginfiGroupInitialize {}.data(),
+    }
+}
+
+pub fn configure_group(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    config: GroupConfig,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MarginfiGroupConfigure {
+            marginfi_group,
+            admin,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MarginfiGroupConfigure { config }.data(),
+    }
+}
+
+pub fn set_pending_admin(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    pending_admin: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MarginfiGroupSetPendingAdmin {
+            marginfi_group,
+            admin,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MarginfiGroupSetPendingAdmin { pending_admin }.data(),
+    }
+}
+
+pub fn accept_admin(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    pending_admin: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MarginfiGroupAcceptAdmin {
+            marginfi_group,
+            pending_admin,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MarginfiGroupAcceptAdmin {}.data(),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+// Bank
+// --------------------------------------------------------------------------------------------------------------------
+
+/// `bank` is a new keypair, signing the transaction along with `admin` and `fee_payer`.
+/// The oracle of `bank_config` is passed for its setup to be validated.
+pub fn add_bank(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    fee_payer: Pubkey,
+    bank: Pubkey,
+    bank_mint: Pubkey,
+    bank_config: BankConfig,
+) -> Instruction {
+    let vault = |vault_type| find_bank_vault_pda(&bank, vault_type, program_id).0;
+    let vault_authority =
+        |vault_type| find_bank_vault_authority_pda(&bank, vault_type, program_id).0;
+
+    let mut accounts = marginfi::accounts::LendingPoolAddBank {
+        marginfi_group,
+        admin,
+        fee_payer,
+        bank_mint,
+        bank,
+        liquidity_vault_authority: vault_authority(BankVaultType::Liquidity),
+        liquidity_vault: vault(BankVaultType::Liquidity),
+        insurance_vault_authority: vault_authority(BankVaultType::Insurance),
+        insurance_vault: vault(BankVaultType::Insurance),
+        fee_vault_authority: vault_authority(BankVaultType::Fee),
+        fee_vault: vault(BankVaultType::Fee),
+        rent: sysvar::rent::ID,
+        token_program: token::ID,
+        system_program: system_program::ID,
+    }
+    .to_account_metas(Some(true));
+    accounts.push(AccountMeta::new_readonly(bank_config.oracle_keys[0], false));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: marginfi::instruction::LendingPoolAddBank {
+            bank_config: bank_config.into(),
+        }
+        .data(),
+    }
+}
+
+/// The new oracle, if any, is passed for its setup to be validated.
+pub fn configure_bank(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    bank: Pubkey,
+    bank_config_opt: BankConfigOpt,
+) -> Instruction {
+    let mut accounts = marginfi::accounts::LendingPoolConfigureBank {
+        marginfi_group,
+        admin,
+        bank,
+    }
+    .to_account_metas(Some(true));
+    if let Some(oracle) = &bank_config_opt.oracle {
+        accounts.push(AccountMeta::new_readonly(oracle.keys[0], false));
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: marginfi::instruction::LendingPoolConfigureBank { bank_config_opt }.data(),
+    }
+}
+
+/// Emissions are funded from `emissions_funding_account`, a token account of `admin`.
+#[allow(clippy::too_many_arguments)]
+pub fn setup_emissions(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    bank: Pubkey,
+    emissions_mint: Pubkey,
+    emissions_funding_account: Pubkey,
+    flags: u64,
+    rate: u64,
+    total_emissions: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolSetupEmissions {
+            marginfi_group,
+            admin,
+            bank,
+            emissions_mint,
+            emissions_auth: find_bank_emissions_auth_pda(&bank, &emissions_mint, program_id).0,
+            emissions_token_account: find_bank_emissions_token_account_pda(
+                &bank,
+                &emissions_mint,
+                program_id,
+            )
+            .0,
+            emissions_funding_account,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolSetupEmissions {
+            flags,
+            rate,
+            total_emissions,
+        }
+        .data(),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_emissions_parameters(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    bank: Pubkey,
+    emissions_mint: Pubkey,
+    emissions_funding_account: Pubkey,
+    emissions_flags: Option<u64>,
+    emissions_rate: Option<u64>,
+    additional_emissions: Option<u64>,
+    emissions_end_timestamp: Option<u64>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolUpdateEmissionsParameters {
+            marginfi_group,
+            admin,
+            bank,
+            emissions_mint,
+            emissions_token_account: find_bank_emissions_token_account_pda(
+                &bank,
+                &emissions_mint,
+                program_id,
+            )
+            .0,
+            emissions_funding_account,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolUpdateEmissionsParameters {
+            emissions_flags,
+            emissions_rate,
+            additional_emissions,
+            emissions_end_timestamp,
+        }
+        .data(),
+    }
+}
+
+/// Permissionless top-up of the emissions of `bank` by `funder`, from its `emissions_funding_account`.
+pub fn fund_emissions(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    bank: Pubkey,
+    emissions_mint: Pubkey,
+    funder: Pubkey,
+    emissions_funding_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolFundEmissions {
+            marginfi_group,
+            bank,
+            emissions_mint,
+            emissions_token_account: find_bank_emissions_token_account_pda(
+                &bank,
+                &emissions_mint,
+                program_id,
+            )
+            .0,
+            funder,
+            emissions_funding_account,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolFundEmissions { amount }.data(),
+    }
+}
+
+pub fn setup_receipt_mint(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    bank: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolSetupReceiptMint {
+            marginfi_group,
+            admin,
+            bank,
+            receipt_mint_authority: find_receipt_mint_authority_pda(&bank, program_id).0,
+            receipt_mint: find_receipt_mint_pda(&bank, program_id).0,
+            rent: sysvar::rent::ID,
+            token_program: token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolSetupReceiptMint {}.data(),
+    }
+}
+
+pub fn accrue_bank_interest(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    bank: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolAccrueBankInterest {
+            marginfi_group,
+            bank,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolAccrueBankInterest {}.data(),
+    }
+}
+
+/// Banks accrued at the current timestamp are skipped, and no keeper tip is paid.
+pub fn accrue_banks(program_id: &Pubkey, marginfi_group: Pubkey, banks: &[Pubkey]) -> Instruction {
+    let mut accounts =
+        marginfi::accounts::LendingPoolAccrueBanks { marginfi_group }.to_account_metas(Some(true));
+    accounts.extend(banks.iter().map(|bank| AccountMeta::new(*bank, false)));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: marginfi::instruction::LendingPoolAccrueBanks {}.data(),
+    }
+}
+
+/// Syncs the borrow value released in the banks into the group total borrow value.
+pub fn sync_borrow_value(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    banks: &[Pubkey],
+) -> Instruction {
+    let mut accounts = marginfi::accounts::LendingPoolSyncBorrowValue { marginfi_group }
+        .to_account_metas(Some(true));
+    accounts.extend(banks.iter().map(|bank| AccountMeta::new(*bank, false)));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: marginfi::instruction::LendingPoolSyncBorrowValue {}.data(),
+    }
+}
+
+/// With `keeper_token_account`, a token account of the bank mint, the keeper tip of the bank
+/// is paid to it from the fee vault.
+pub fn collect_bank_fees(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    bank_pk: Pubkey,
+    keeper_token_account: Option<Pubkey>,
+) -> Instruction {
+    let mut accounts = marginfi::accounts::LendingPoolCollectBankFees {
+        marginfi_group,
+        bank: bank_pk,
+        liquidity_vault_authority: find_bank_vault_authority_pda(
+            &bank_pk,
+            BankVaultType::Liquidity,
+            program_id,
+        )
+        .0,
+        liquidity_vault: find_bank_vault_pda(&bank_pk, BankVaultType::Liquidity, program_id).0,
+        insurance_vault: find_bank_vault_pda(&bank_pk, BankVaultType::Insurance, program_id).0,
+        fee_vault: find_bank_vault_pda(&bank_pk, BankVaultType::Fee, program_id).0,
+        token_program: token::ID,
+    }
+    .to_account_metas(Some(true));
+
+    if let Some(keeper_token_account) = keeper_token_account {
+        accounts.extend([
+            AccountMeta::new(
+                find_bank_vault_pda(&bank_pk, BankVaultType::Fee, program_id).0,
+                false,
+            ),
+            AccountMeta::new_readonly(
+                find_bank_vault_authority_pda(&bank_pk, BankVaultType::Fee, program_id).0,
+                false,
+            ),
+            AccountMeta::new(keeper_token_account, false),
+            AccountMeta::new_readonly(token::ID, false),
+        ]);
+    }
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: marginfi::instruction::LendingPoolCollectBankFees {}.data(),
+    }
+}
+
+/// Socialize the bad debt of `marginfi_account` in `bank_pk`.
+pub fn handle_bankruptcy(
+    program_id: &Pubkey,
+    admin: Pubkey,
+    marginfi_account_pk: Pubkey,
+    marginfi_account: &MarginfiAccount,
+    banks: &HashMap<Pubkey, Bank>,
+    bank_pk: Pubkey,
+) -> Instruction {
+    let mut accounts = marginfi::accounts::LendingPoolHandleBankruptcy {
+        marginfi_group: marginfi_account.group,
+        admin,
+        bank: bank_pk,
+        marginfi_account: marginfi_account_pk,
+        liquidity_vault: find_bank_vault_pda(&bank_pk, BankVaultType::Liquidity, program_id).0,
+        insurance_vault: find_bank_vault_pda(&bank_pk, BankVaultType::Insurance, program_id).0,
+        insurance_vault_authority: find_bank_vault_authority_pda(
+            &bank_pk,
+            BankVaultType::Insurance,
+            program_id,
+        )
+        .0,
+        token_program: token::ID,
+    }
+    .to_account_metas(Some(true));
+    accounts.extend(load_observation_account_metas(
+        marginfi_account,
+        banks,
+        vec![bank_pk],
+        vec![],
+    ));
+
+    Instruction {
+        program_id: *program_id,
+        accounts,
+        data: marginfi::instruction::LendingPoolHandleBankruptcy {}.data(),
+    }
+}
+
+pub fn force_close_balance(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    admin: Pubkey,
+    bank: Pubkey,
+    marginfi_account: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolForceCloseBalance {
+            marginfi_group,
+            admin,
+            bank,
+            marginfi_account,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolForceCloseBalance {}.data(),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+// Program fees
+// --------------------------------------------------------------------------------------------------------------------
+
+/// Only succeeds once per program, `payer` must be the program upgrade authority
+/// and covers the rent of the fee state.
+pub fn initialize_fee_state(
+    program_id: &Pubkey,
+    payer: Pubkey,
+    global_fee_admin: Pubkey,
+    global_fee_wallet: Pubkey,
+    flat_sol_fee: u64,
+    program_fee_bps: u64,
+    fee_flags: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::FeeStateInitialize {
+            fee_state: find_fee_state_pda(program_id).0,
+            payer,
+            program_data: find_program_data_pda(program_id).0,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::FeeStateInitialize {
+            global_fee_admin,
+            global_fee_wallet,
+            flat_sol_fee,
+            program_fee_bps,
+            fee_flags,
+        }
+        .data(),
+    }
+}
+
+pub fn configure_fee_state(
+    program_id: &Pubkey,
+    global_fee_admin: Pubkey,
+    config: FeeStateConfig,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::FeeStateConfigure {
+            fee_state: find_fee_state_pda(program_id).0,
+            global_fee_admin,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::FeeStateConfigure { config }.data(),
+    }
+}
+
+/// `fee_wallet_token_account` is a token account of the bank mint owned by the global fee wallet.
+pub fn sweep_program_fees(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    bank_pk: Pubkey,
+    fee_wallet_token_account: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolSweepProgramFees {
+            marginfi_group,
+            bank: bank_pk,
+            fee_state: find_fee_state_pda(program_id).0,
+            liquidity_vault_authority: find_bank_vault_authority_pda(
+                &bank_pk,
+                BankVaultType::Liquidity,
+                program_id,
+            )
+            .0,
+            liquidity_vault: find_bank_vault_pda(&bank_pk, BankVaultType::Liquidity, program_id).0,
+            fee_wallet_token_account,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolSweepProgramFees {}.data(),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+// Migration
+// --------------------------------------------------------------------------------------------------------------------
+
+/// Upgrade `account`, a marginfi account or a bank, to its latest layout version.
+/// `payer` covers the rent of a reallocation.
+pub fn migrate_account(program_id: &Pubkey, account: Pubkey, payer: Pubkey) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MigrateAccount {
+            account,
+            payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MigrateAccount {}.data(),
+    }
+}
+
+// --------------------------------------------------------------------------------------------------------------------
+// Marginfi account
+// --------------------------------------------------------------------------------------------------------------------
+
+/// `marginfi_account` is a new keypair, signing the transaction along with `authority` and `fee_payer`.
+///
+/// `referrer` earns a share of the protocol fees on the liabilities of the account.
+pub fn initialize_marginfi_account(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    marginfi_account: Pubkey,
+    authority: Pubkey,
+    fee_payer: Pubkey,
+    referrer: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MarginfiAccountInitialize {
+            marginfi_group,
+            marginfi_account,
+            authority,
+            fee_payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MarginfiAccountInitialize { referrer }.data(),
+    }
+}
+
+/// Sub-account `account_index` of `authority`, which must be the sub-account count of its
+/// [`SubAccountCounter`](marginfi::state::marginfi_account::SubAccountCounter).
+pub fn initialize_sub_account(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    authority: Pubkey,
+    fee_payer: Pubkey,
+    account_index: u16,
+    referrer: Option<Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::MarginfiAccountInitializeSubAccount {
+            marginfi_group,
+            marginfi_account: find_sub_account_pda(
+                &marginfi_group,
+                &authority,
+                account_index,
+                program_id,
+            )
+            .0,
+            sub_account_counter: find_sub_account_counter_pda(
+                &marginfi_group,
+                &authority,
+                program_id,
+            )
+            .0,
+            authority,
+            fee_payer,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::MarginfiAccountInitializeSubAccount {
+            account_index,
+            referrer,
+        }
+        .data(),
+    }
+}
+
+/// Permissionless settlement of the emissions of `marginfi_account` in `bank_pk`.
+pub fn settle_emissions(
+    program_id: &Pubkey,
+    marginfi_account: Pubkey,
+    bank_pk: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingAccountSettleEmissions {
+            marginfi_account,
+            bank: bank_pk,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingAccountSettleEmissions {}.data(),
+    }
+}
+
+/// Withdraw the referral fees `referrer` earned in `bank_pk` to `destination_token_account`,
+/// signed by the referrer.
+pub fn withdraw_referral_fees(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    referrer: Pubkey,
+    bank_pk: Pubkey,
+    bank: &Bank,
+    destination_token_account: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingPoolWithdrawReferralFees {
+            marginfi_group,
+            referrer,
+            bank: bank_pk,
+            destination_token_account,
+            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
+                &bank_pk,
+                BankVaultType::Liquidity,
+                program_id,
+            )
+            .0,
+            bank_liquidity_vault: bank.liquidity_vault,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingPoolWithdrawReferralFees {}.data(),
+    }
+}
+
+/// Burn receipt tokens of `bank_pk` held by `signer`, and withdraw the underlying deposit
+/// to `destination_token_account`.
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_receipt(
+    program_id: &Pubkey,
+    marginfi_group: Pubkey,
+    signer: Pubkey,
+    bank_pk: Pubkey,
+    bank: &Bank,
+    source_receipt_account: Pubkey,
+    destination_token_account: Pubkey,
+    amount: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: marginfi::accounts::LendingAccountRedeemReceipt {
+            marginfi_group,
+            signer,
+            bank: bank_pk,
+            receipt_mint: find_receipt_mint_pda(&bank_pk, program_id).0,
+            source_receipt_account,
+            destination_token_account,
+            bank_liquidity_vault_authority: find_bank_vault_authority_pda(
+                &bank_pk,
+                BankVaultType::Liquidity,
+                program_id,
+            )
+            .0,
+            bank_liquidity_vault: bank.liquidity_vault,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true)),
+        data: marginfi::instruction::LendingAccountRedeemReceipt { amount }.data(),
+    }
+}
+
+/// A marginfi account with the banks of its group, to build the instructions of its authority.
+///
+/// Token accounts default to the associated token accounts of the authority.
+pub struct MarginfiAccountContext<'a> {
+    pub program_id: Pubkey,
+    pub address: Pubkey,
+    pub account: &'a MarginfiAccount,
+    pub banks: &'a HashMap<Pubkey, Bank>,
+}
+
+impl<'a> MarginfiAccountContext<'a> {
+    pub fn new(
+        program_id: Pubkey,
+        address: Pubkey,
+        account: &'a MarginfiAccount,
+        banks: &'a HashMap<Pubkey, Bank>,
+    ) -> Self {
+        Self {
+            program_id,
+            address,
+            account,
+            banks,
+        }
+    }
+
+    fn bank(&self, bank_pk: &Pubkey) -> Result<&'a Bank> {
+        self.banks
+            .get(bank_pk)
+            .ok_or_else(|| anyhow!("Bank {} not found", bank_pk))
+    }
+
+    fn authority_ata(&self, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address(&self.account.authority, mint)
+    }
+
+    fn liquidity_vault_authority(&self, bank_pk: &Pubkey) -> Pubkey {
+        find_bank_vault_authority_pda(bank_pk, BankVaultType::Liquidity, &self.program_id).0
+    }
+
+    pub fn deposit(&self, bank_pk: Pubkey, amount: u64) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: marginfi::accounts::LendingAccountDeposit {
+                marginfi_group: self.account.group,
+                marginfi_account: self.address,
+                signer: self.account.authority,
+                bank: bank_pk,
+                signer_token_account: self.authority_ata(&bank.mint),
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountDeposit { amount }.data(),
+        })
+    }
+
+    /// Deposit native SOL into a native SOL bank, the missing SOL is wrapped from the authority
+    /// wallet into its wSOL associated token account, created if needed.
+    pub fn deposit_native(&self, bank_pk: Pubkey, amount: u64) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: marginfi::accounts::LendingAccountDepositNative {
+                marginfi_group: self.account.group,
+                marginfi_account: self.address,
+                signer: self.account.authority,
+                bank: bank_pk,
+                native_mint: native_mint::ID,
+                signer_token_account: self.authority_ata(&native_mint::ID),
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountDepositNative { amount }.data(),
+        })
+    }
+
+    pub fn repay(&self, bank_pk: Pubkey, amount: u64, repay_all: bool) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: marginfi::accounts::LendingAccountRepay {
+                marginfi_group: self.account.group,
+                marginfi_account: self.address,
+                signer: self.account.authority,
+                bank: bank_pk,
+                signer_token_account: self.authority_ata(&bank.mint),
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountRepay {
+                amount,
+                repay_all: repay_all.then_some(true),
+            }
+            .data(),
+        })
+    }
+
+    /// Repay a native SOL bank liability with native SOL, see `deposit_native`.
+    pub fn repay_native(
+        &self,
+        bank_pk: Pubkey,
+        amount: u64,
+        repay_all: bool,
+    ) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: marginfi::accounts::LendingAccountRepayNative {
+                marginfi_group: self.account.group,
+                marginfi_account: self.address,
+                signer: self.account.authority,
+                bank: bank_pk,
+                native_mint: native_mint::ID,
+                signer_token_account: self.authority_ata(&native_mint::ID),
+                bank_liquidity_vault: bank.liquidity_vault,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountRepayNative {
+                amount,
+                repay_all: repay_all.then_some(true),
+            }
+            .data(),
+        })
+    }
+
+    /// The destination associated token account must exist.
+    pub fn withdraw(
+        &self,
+        bank_pk: Pubkey,
+        amount: u64,
+        withdraw_all: bool,
+    ) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        let mut accounts = marginfi::accounts::LendingAccountWithdraw {
+            marginfi_group: self.account.group,
+            marginfi_account: self.address,
+            signer: self.account.authority,
+            bank: bank_pk,
+            destination_token_account: self.authority_ata(&bank.mint),
+            bank_liquidity_vault_authority: self.liquidity_vault_authority(&bank_pk),
+            bank_liquidity_vault: bank.liquidity_vault,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(self.withdraw_observation_account_metas(bank_pk, withdraw_all));
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountWithdraw {
+                amount,
+                withdraw_all: withdraw_all.then_some(true),
+            }
+            .data(),
+        })
+    }
+
+    /// Withdraw from a native SOL bank as native SOL, through the wSOL associated token account
+    /// of the authority, created if needed and closed: any wSOL it held is unwrapped too.
+    pub fn withdraw_native(
+        &self,
+        bank_pk: Pubkey,
+        amount: u64,
+        withdraw_all: bool,
+    ) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        let mut accounts = marginfi::accounts::LendingAccountWithdrawNative {
+            marginfi_group: self.account.group,
+            marginfi_account: self.address,
+            signer: self.account.authority,
+            bank: bank_pk,
+            native_mint: native_mint::ID,
+            destination_token_account: self.authority_ata(&native_mint::ID),
+            bank_liquidity_vault_authority: self.liquidity_vault_authority(&bank_pk),
+            bank_liquidity_vault: bank.liquidity_vault,
+            token_program: token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(self.withdraw_observation_account_metas(bank_pk, withdraw_all));
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountWithdrawNative {
+                amount,
+                withdraw_all: withdraw_all.then_some(true),
+            }
+            .data(),
+        })
+    }
+
+    /// A withdraw all closes the balance, which is then left out of the health check.
+    fn withdraw_observation_account_metas(
+        &self,
+        bank_pk: Pubkey,
+        withdraw_all: bool,
+    ) -> Vec<AccountMeta> {
+        load_observation_account_metas(
+            self.account,
+            self.banks,
+            vec![],
+            if withdraw_all { vec![bank_pk] } else { vec![] },
+        )
+    }
+
+    /// The destination associated token account must exist.
+    /// `fee_state` is the program fee state, `None` if it isn't initialized (see `load_fee_state`).
+    pub fn borrow(
+        &self,
+        bank_pk: Pubkey,
+        amount: u64,
+        fee_state: Option<&FeeState>,
+    ) -> Result<Instruction> {
+        let bank = self.bank(&bank_pk)?;
+
+        let mut accounts = marginfi::accounts::LendingAccountBorrow {
+            marginfi_group: self.account.group,
+            marginfi_account: self.address,
+            signer: self.account.authority,
+            bank: bank_pk,
+            destination_token_account: self.authority_ata(&bank.mint),
+            bank_liquidity_vault_authority: self.liquidity_vault_authority(&bank_pk),
+            bank_liquidity_vault: bank.liquidity_vault,
+            token_program: token::ID,
+            fee_state: find_fee_state_pda(&self.program_id).0,
+            global_fee_wallet: fee_state
+                .map(|fee_state| fee_state.global_fee_wallet)
+                .unwrap_or(self.account.authority),
+            system_program: system_program::ID,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(load_post_balances_account_metas(
+            self.account,
+            self.banks,
+            &[bank_pk],
+        )?);
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountBorrow { amount }.data(),
+        })
+    }
+
+    pub fn close_balance(&self, bank_pk: Pubkey) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: marginfi::accounts::LendingAccountCloseBalance {
+                marginfi_group: self.account.group,
+                marginfi_account: self.address,
+                signer: self.account.authority,
+                bank: bank_pk,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountCloseBalance {}.data(),
+        }
+    }
+
+    pub fn pulse_health(&self) -> Instruction {
+        let mut accounts = marginfi::accounts::LendingAccountPulseHealth {
+            marginfi_account: self.address,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(load_observation_account_metas(
+            self.account,
+            self.banks,
+            vec![],
+            vec![],
+        ));
+
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountPulseHealth {}.data(),
+        }
+    }
+
+    /// Opt the account in to automatic deleveraging by a keeper, or opt out with `None`.
+    pub fn configure_auto_deleverage(&self, config: Option<AutoDeleverageConfig>) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts: marginfi::accounts::MarginfiAccountConfigureAutoDeleverage {
+                marginfi_account: self.address,
+                signer: self.account.authority,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiAccountConfigureAutoDeleverage { config }.data(),
+        }
+    }
+
+    pub fn settle_emissions(&self, bank_pk: Pubkey) -> Instruction {
+        settle_emissions(&self.program_id, self.address, bank_pk)
+    }
+
+    /// Withdraw the emissions of `bank_pk` to the associated token account of the authority,
+    /// created by the instruction with `create_destination_ata`.
+    pub fn withdraw_emissions(
+        &self,
+        bank_pk: Pubkey,
+        create_destination_ata: bool,
+    ) -> Result<Instruction> {
+        let emissions_mint = self.bank(&bank_pk)?.emissions_mint;
+
+        let mut accounts = marginfi::accounts::LendingAccountWithdrawEmissions {
+            marginfi_group: self.account.group,
+            marginfi_account: self.address,
+            signer: self.account.authority,
+            bank: bank_pk,
+            emissions_mint,
+            emissions_auth: find_bank_emissions_auth_pda(
+                &bank_pk,
+                &emissions_mint,
+                &self.program_id,
+            )
+            .0,
+            emissions_vault: find_bank_emissions_token_account_pda(
+                &bank_pk,
+                &emissions_mint,
+                &self.program_id,
+            )
+            .0,
+            destination_account: self.authority_ata(&emissions_mint),
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true));
+        if create_destination_ata {
+            accounts.extend([
+                AccountMeta::new_readonly(self.account.authority, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ]);
+        }
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountWithdrawEmissions {
+                create_destination_ata: create_destination_ata.then_some(true),
+            }
+            .data(),
+        })
+    }
+
+    /// Mint receipt tokens for `amount` of the deposit in `bank_pk`, to the associated token
+    /// account of the authority, which must exist.
+    pub fn mint_receipt(&self, bank_pk: Pubkey, amount: u64) -> Instruction {
+        let receipt_mint = find_receipt_mint_pda(&bank_pk, &self.program_id).0;
+
+        let mut accounts = marginfi::accounts::LendingAccountMintReceipt {
+            marginfi_group: self.account.group,
+            marginfi_account: self.address,
+            signer: self.account.authority,
+            bank: bank_pk,
+            receipt_mint,
+            receipt_mint_authority: find_receipt_mint_authority_pda(&bank_pk, &self.program_id).0,
+            destination_receipt_account: self.authority_ata(&receipt_mint),
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(load_observation_account_metas(
+            self.account,
+            self.banks,
+            vec![],
+            vec![],
+        ));
+
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountMintReceipt { amount }.data(),
+        }
+    }
+
+    /// Move the liability in `liability_bank` and `collateral` into `destination`, an empty
+    /// account of the same authority.
+    ///
+    /// The health check accounts of both accounts are resolved as of after the split.
+    pub fn split_position(
+        &self,
+        destination: &MarginfiAccountContext,
+        liability_bank: Pubkey,
+        collateral: Vec<SplitPositionCollateral>,
+    ) -> Result<Instruction> {
+        let mut source_account = *self.account;
+        let mut destination_account = *destination.account;
+
+        let split_error =
+            |err: anchor_lang::error::Error| anyhow!("Invalid position split: {}", err);
+        source_account
+            .lending_account
+            .split_liability(&mut destination_account.lending_account, &liability_bank)
+            .map_err(split_error)?;
+        for SplitPositionCollateral { bank, asset_shares } in collateral.iter() {
+            source_account
+                .lending_account
+                .split_asset_shares(
+                    &mut destination_account.lending_account,
+                    bank,
+                    (*asset_shares).into(),
+                )
+                .map_err(split_error)?;
+        }
+
+        let mut accounts = marginfi::accounts::LendingAccountSplitPosition {
+            marginfi_group: self.account.group,
+            marginfi_account: self.address,
+            destination_marginfi_account: destination.address,
+            signer: self.account.authority,
+            liability_bank,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(load_observation_account_metas(
+            &source_account,
+            self.banks,
+            vec![],
+            vec![],
+        ));
+        accounts.extend(load_observation_account_metas(
+            &destination_account,
+            self.banks,
+            vec![],
+            vec![],
+        ));
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountSplitPosition { collateral }.data(),
+        })
+    }
+
+    /// Liquidate `asset_amount` of the assets of `liquidatee` in `asset_bank_pk`, taking on
+    /// its liability in `liab_bank_pk`.
+    pub fn liquidate(
+        &self,
+        liquidatee: &MarginfiAccountContext,
+        asset_bank_pk: Pubkey,
+        liab_bank_pk: Pubkey,
+        asset_amount: u64,
+    ) -> Result<Instruction> {
+        let asset_bank = self.bank(&asset_bank_pk)?;
+        let liab_bank = self.bank(&liab_bank_pk)?;
+
+        let mut accounts = marginfi::accounts::LendingAccountLiquidate {
+            marginfi_group: self.account.group,
+            asset_bank: asset_bank_pk,
+            liab_bank: liab_bank_pk,
+            liquidator_marginfi_account: self.address,
+            signer: self.account.authority,
+            liquidatee_marginfi_account: liquidatee.address,
+            bank_liquidity_vault_authority: self.liquidity_vault_authority(&liab_bank_pk),
+            bank_liquidity_vault: liab_bank.liquidity_vault,
+            bank_insurance_vault: liab_bank.insurance_vault,
+            token_program: token::ID,
+            fee_state: find_fee_state_pda(&self.program_id).0,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend([
+            AccountMeta::new_readonly(asset_bank.config.oracle_keys[0], false),
+            AccountMeta::new_readonly(liab_bank.config.oracle_keys[0], false),
+        ]);
+        // The liquidator takes the liability first, then the assets
+        accounts.extend(load_post_balances_account_metas(
+            self.account,
+            self.banks,
+            &[liab_bank_pk, asset_bank_pk],
+        )?);
+        accounts.extend(load_observation_account_metas(
+            liquidatee.account,
+            liquidatee.banks,
+            vec![],
+            vec![],
+        ));
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountLiquidate { asset_amount }.data(),
+        })
+    }
+
+    /// Deleverage `account`, of which the authority of this account is the keeper, taking over
+    /// `asset_amount` of its collateral in `asset_bank_pk` against its liability in `liab_bank_pk`.
+    pub fn deleverage(
+        &self,
+        account: &MarginfiAccountContext,
+        asset_bank_pk: Pubkey,
+        liab_bank_pk: Pubkey,
+        asset_amount: u64,
+    ) -> Result<Instruction> {
+        let asset_bank = self.bank(&asset_bank_pk)?;
+        let liab_bank = self.bank(&liab_bank_pk)?;
+
+        let mut accounts = marginfi::accounts::LendingAccountDeleverage {
+            marginfi_group: self.account.group,
+            asset_bank: asset_bank_pk,
+            liab_bank: liab_bank_pk,
+            keeper_marginfi_account: self.address,
+            signer: self.account.authority,
+            marginfi_account: account.address,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend([
+            AccountMeta::new_readonly(asset_bank.config.oracle_keys[0], false),
+            AccountMeta::new_readonly(liab_bank.config.oracle_keys[0], false),
+        ]);
+        // The keeper takes the liability first, then the assets
+        accounts.extend(load_post_balances_account_metas(
+            self.account,
+            self.banks,
+            &[liab_bank_pk, asset_bank_pk],
+        )?);
+        accounts.extend(load_observation_account_metas(
+            account.account,
+            account.banks,
+            vec![],
+            vec![],
+        ));
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: marginfi::instruction::LendingAccountDeleverage { asset_amount }.data(),
+        })
+    }
+}
@@ -0,0 +1,18 @@
+//! Off-chain building blocks for marginfi integrators.
+//!
+//! - [`instructions`]: builders for every marginfi instruction, resolving PDAs and remaining accounts
+//! - [`accounts`]: deserialization and RPC loading of groups, banks and marginfi accounts
+//! - [`math`]: health and interest computations, matching the on-chain risk engine
+//! - [`pda`]: bank vault, emissions, receipt mint and sub-account addresses
+//! - [`transaction`]: v0 transactions using an address lookup table of the group accounts
+//!
+//! Program types (`Bank`, `MarginfiAccount`, configs, ...) are those of the `marginfi` crate,
+//! re-exported as [`marginfi`].
+
+pub mod accounts;
+pub mod instructions;
+pub mod math;
+pub mod pda;
+pub mod transaction;
+
+pub use marginfi;
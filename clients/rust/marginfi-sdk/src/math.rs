@@ -0,0 +1,227 @@
+use {
+    anyhow::{anyhow, Result},
+    fixed::types::I80F48,
+    marginfi::{
+        constants::{EXP_10_I80F48, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE},
+        state::{
+            amounts::ShareAmount,
+            marginfi_account::{calc_asset_value, Balance, MarginfiAccount, WeightType},
+            marginfi_group::Bank,
+            price::{OraclePriceFeedAdapter, PriceAdapter},
+        },
+    },
+    solana_sdk::{account::Account, account_info::IntoAccountInfo, pubkey::Pubkey},
+    std::collections::HashMap,
+};
+
+/// Load the price feed of `bank` from its oracle account.
+///
+/// Feeds older than `max_age` seconds are rejected, as the risk engine does.
+pub fn load_price_feed(
+    bank: &Bank,
+    (oracle_pk, mut oracle_account): (Pubkey, Account),
+    current_timestamp: i64,
+    max_age: u64,
+) -> Result<OraclePriceFeedAdapter> {
+    let oracle_ai = (&oracle_pk, &mut oracle_account).into_account_info();
+
+    OraclePriceFeedAdapter::try_from_bank_config(
+        &bank.config,
+        &[oracle_ai],
+        current_timestamp,
+        max_age,
+    )
+    .map_err(|err| anyhow!("Failed to load oracle {}: {}", oracle_pk, err))
+}
+
+/// Weighted (assets, liabilities) values of a marginfi account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AccountHealth {
+    pub assets: I80F48,
+    pub liabilities: I80F48,
+}
+
+impl AccountHealth {
+    /// Health as checked by the risk engine, the account is healthy if positive.
+    pub fn health(&self) -> I80F48 {
+        self.assets - self.liabilities
+    }
+
+    /// Weighted assets over weighted liabilities, `None` without liabilities.
+    pub fn health_factor(&self) -> Option<I80F48> {
+        if self.liabilities.is_zero() {
+            None
+        } else {
+            self.assets.checked_div(self.liabilities)
+        }
+    }
+}
+
+/// Health of `marginfi_account`, computed like the on-chain risk engine.
+///
+/// `price_feeds` holds the price feed of every bank of the active balances, keyed by bank.
+pub fn calc_account_health(
+    marginfi_account: &MarginfiAccount,
+    banks: &HashMap<Pubkey, Bank>,
+    price_feeds: &HashMap<Pubkey, OraclePriceFeedAdapter>,
+    weight_type: WeightType,
+) -> Result<AccountHealth> {
+    marginfi_account
+        .lending_account
+        .get_active_balances_iter()
+        .try_fold(AccountHealth::default(), |health, balance| {
+            let bank = banks
+                .get(&balance.bank_pk)
+                .ok_or_else(|| anyhow!("Bank {} not found", balance.bank_pk))?;
+            let price_feed = price_feeds
+                .get(&balance.bank_pk)
+                .ok_or_else(|| anyhow!("Price feed of bank {} not found", balance.bank_pk))?;
+
+            let (assets, liabilities) =
+                calc_weighted_values(balance, bank, price_feed, weight_type)?;
+
+            Ok(AccountHealth {
+                assets: health.assets + assets,
+                liabilities: health.liabilities + liabilities,
+            })
+        })
+}
+
+/// Weighted (assets, liabilities) values of a balance, as computed by the risk engine.
+///
+/// Assets are valued at the low end of the oracle confidence range and liabilities at the high end.
+pub fn calc_weighted_values(
+    balance: &Balance,
+    bank: &Bank,
+    price_feed: &OraclePriceFeedAdapter,
+    weight_type: WeightType,
+) -> Result<(I80F48, I80F48)> {
+    let (worst_price, best_price) = price_feed.get_price_range()?;
+    let (mut asset_weight, liability_weight) = bank.config.get_weights(weight_type);
+
+    if matches!(weight_type, WeightType::Initial)
+        && bank.config.total_asset_value_init_limit != TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE
+    {
+        let bank_total_assets_value = calc_asset_value(
+            bank.asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+                .value(),
+            worst_price,
+            bank.mint_decimals,
+            None,
+        )?;
+        let total_asset_value_init_limit =
+            I80F48::from_num(bank.config.total_asset_value_init_limit);
+
+        if bank_total_assets_value > total_asset_value_init_limit {
+            asset_weight *= total_asset_value_init_limit / bank_total_assets_value;
+        }
+    }
+
+    Ok((
+        calc_asset_value(
+            bank.asset_amount(ShareAmount::new(balance.asset_shares.into()))?
+                .value(),
+            worst_price,
+            bank.mint_decimals,
+            Some(asset_weight),
+        )?,
+        calc_asset_value(
+            bank.liability_amount(ShareAmount::new(balance.liability_shares.into()))?
+                .value(),
+            best_price,
+            bank.mint_decimals,
+            Some(liability_weight),
+        )?,
+    ))
+}
+
+/// Interest rates of a bank at its current utilization, as APRs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankRates {
+    pub utilization: I80F48,
+    pub lending_apr: I80F48,
+    pub borrowing_apr: I80F48,
+    pub group_fees_apr: I80F48,
+    pub insurance_fees_apr: I80F48,
+}
+
+/// Total liabilities over total deposits of a bank.
+pub fn calc_utilization(bank: &Bank) -> Result<I80F48> {
+    let total_deposits = bank
+        .asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+        .value();
+    let total_liabilities = bank
+        .liability_amount(ShareAmount::new(bank.total_liability_shares.into()))?
+        .value();
+
+    Ok(if total_deposits > I80F48::ZERO {
+        total_liabilities / total_deposits
+    } else {
+        I80F48::ZERO
+    })
+}
+
+pub fn calc_bank_rates(bank: &Bank) -> Result<BankRates> {
+    let utilization = calc_utilization(bank)?;
+
+    let (lending_apr, borrowing_apr, group_fees_apr, insurance_fees_apr) = bank
+        .config
+        .interest_rate_config
+        .calc_interest_rate(utilization)
+        .ok_or_else(|| anyhow!("Failed to compute the interest rates"))?;
+
+    Ok(BankRates {
+        utilization,
+        lending_apr,
+        borrowing_apr,
+        group_fees_apr,
+        insurance_fees_apr,
+    })
+}
+
+/// Interest accrues on every bank update, compounding is approximated as continuous.
+pub fn apr_to_apy(apr: f64) -> f64 {
+    apr.exp_m1()
+}
+
+/// Native amount of `ui_amount` tokens, rounded down.
+pub fn ui_to_native(ui_amount: f64, mint_decimals: u8) -> u64 {
+    (I80F48::from_num(ui_amount) * EXP_10_I80F48[mint_decimals as usize])
+        .floor()
+        .to_num()
+}
+
+pub fn native_to_ui(native_amount: I80F48, mint_decimals: u8) -> f64 {
+    (native_amount / EXP_10_I80F48[mint_decimals as usize]).to_num()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, fixed_macro::types::I80F48};
+
+    #[test]
+    fn ui_amounts_round_trip() {
+        assert_eq!(ui_to_native(1.5, 6), 1_500_000);
+        assert_eq!(ui_to_native(0.0000001, 6), 0);
+        assert_eq!(native_to_ui(I80F48!(1_500_000), 6), 1.5);
+    }
+
+    #[test]
+    fn health_factor_without_liabilities() {
+        let health = AccountHealth {
+            assets: I80F48!(10),
+            liabilities: I80F48::ZERO,
+        };
+
+        assert_eq!(health.health(), I80F48!(10));
+        assert_eq!(health.health_factor(), None);
+
+        let health = AccountHealth {
+            assets: I80F48!(10),
+            liabilities: I80F48!(8),
+        };
+
+        assert_eq!(health.health(), I80F48!(2));
+        assert_eq!(health.health_factor(), Some(I80F48!(1.25)));
+    }
+}
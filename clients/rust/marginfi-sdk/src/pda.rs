@@ -0,0 +1,113 @@
+use {
+    marginfi::{
+        bank_authority_seed, bank_seed,
+        constants::{
+            EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED, FEE_STATE_SEED,
+            MARGINFI_ACCOUNT_SEED, RECEIPT_MINT_AUTHORITY_SEED, RECEIPT_MINT_SEED,
+            SUB_ACCOUNT_COUNTER_SEED,
+        },
+        state::marginfi_group::BankVaultType,
+    },
+    solana_sdk::{bpf_loader_upgradeable, pubkey::Pubkey},
+};
+
+pub fn find_bank_vault_pda(
+    bank_pk: &Pubkey,
+    vault_type: BankVaultType,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(bank_seed!(vault_type, bank_pk), program_id)
+}
+
+pub fn find_bank_vault_authority_pda(
+    bank_pk: &Pubkey,
+    vault_type: BankVaultType,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(bank_authority_seed!(vault_type, bank_pk), program_id)
+}
+
+pub fn find_bank_emissions_auth_pda(
+    bank_pk: &Pubkey,
+    emissions_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EMISSIONS_AUTH_SEED.as_bytes(),
+            bank_pk.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_bank_emissions_token_account_pda(
+    bank_pk: &Pubkey,
+    emissions_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+            bank_pk.as_ref(),
+            emissions_mint.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_receipt_mint_pda(bank_pk: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RECEIPT_MINT_SEED.as_bytes(), bank_pk.as_ref()],
+        program_id,
+    )
+}
+
+pub fn find_sub_account_pda(
+    marginfi_group: &Pubkey,
+    authority: &Pubkey,
+    account_index: u16,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            MARGINFI_ACCOUNT_SEED.as_bytes(),
+            marginfi_group.as_ref(),
+            authority.as_ref(),
+            &account_index.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_sub_account_counter_pda(
+    marginfi_group: &Pubkey,
+    authority: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SUB_ACCOUNT_COUNTER_SEED.as_bytes(),
+            marginfi_group.as_ref(),
+            authority.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+pub fn find_receipt_mint_authority_pda(bank_pk: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[RECEIPT_MINT_AUTHORITY_SEED.as_bytes(), bank_pk.as_ref()],
+        program_id,
+    )
+}
+
+pub fn find_fee_state_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[FEE_STATE_SEED.as_bytes()], program_id)
+}
+
+/// Program data account of the upgradeable deploy of `program_id`, holding its upgrade authority.
+pub fn find_program_data_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::ID)
+}
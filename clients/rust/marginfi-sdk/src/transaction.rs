@@ -0,0 +1,152 @@
+//! Versioned transactions, with an address lookup table holding the accounts of a group.
+//!
+//! Legacy transactions are limited to ~35 accounts. Health checks pass a (bank, oracle) pair
+//! per balance, so accounts borrowing from many banks, and liquidations which check two accounts,
+//! quickly exceed that limit. Addresses in a lookup table only take a byte of the transaction.
+
+use {
+    crate::pda::find_bank_vault_authority_pda,
+    anchor_spl::{associated_token, token},
+    anyhow::{anyhow, Result},
+    marginfi::state::marginfi_group::{Bank, BankVaultType},
+    solana_address_lookup_table_program::{
+        instruction as lookup_table_instruction, state::AddressLookupTable,
+    },
+    solana_client::rpc_client::RpcClient,
+    solana_sdk::{
+        address_lookup_table_account::AddressLookupTableAccount,
+        hash::Hash,
+        instruction::Instruction,
+        message::{v0, VersionedMessage},
+        pubkey::Pubkey,
+        signers::Signers,
+        system_program, sysvar,
+        transaction::VersionedTransaction,
+    },
+    std::collections::HashMap,
+};
+
+/// Addresses added by a single extend instruction, keeping its transaction under the size limit.
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+/// Every address shared by the instructions of `marginfi_group`: the group, its banks with their
+/// mints, oracles, vaults and liquidity vault authorities, and the programs they are used with.
+///
+/// Addresses are deduplicated, in a stable order.
+pub fn group_lookup_table_addresses(
+    program_id: &Pubkey,
+    marginfi_group: &Pubkey,
+    banks: &HashMap<Pubkey, Bank>,
+) -> Vec<Pubkey> {
+    let mut bank_pks = banks.keys().collect::<Vec<_>>();
+    bank_pks.sort();
+
+    let mut addresses = vec![
+        *marginfi_group,
+        *program_id,
+        token::ID,
+        associated_token::ID,
+        system_program::ID,
+        sysvar::rent::ID,
+    ];
+    for bank_pk in bank_pks {
+        let bank = &banks[bank_pk];
+
+        addresses.extend([
+            *bank_pk,
+            bank.mint,
+            bank.liquidity_vault,
+            find_bank_vault_authority_pda(bank_pk, BankVaultType::Liquidity, program_id).0,
+            bank.insurance_vault,
+            bank.fee_vault,
+        ]);
+        addresses.extend(
+            bank.config
+                .oracle_keys
+                .iter()
+                .filter(|key| **key != Pubkey::default()),
+        );
+        if bank.emissions_mint != Pubkey::default() {
+            addresses.push(bank.emissions_mint);
+        }
+    }
+
+    let mut unique = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        if !unique.contains(&address) {
+            unique.push(address);
+        }
+    }
+
+    unique
+}
+
+/// Create a lookup table owned by `authority`, returning the instruction and the table address.
+///
+/// `recent_slot` must be a recent finalized slot, it seeds the table address.
+pub fn create_lookup_table(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    recent_slot: u64,
+) -> (Instruction, Pubkey) {
+    lookup_table_instruction::create_lookup_table(*authority, *payer, recent_slot)
+}
+
+/// Instructions adding the `addresses` missing from `lookup_table`, by batches of
+/// [`MAX_ADDRESSES_PER_EXTEND`].
+///
+/// Added addresses can only be looked up from the next slot on.
+pub fn extend_lookup_table(
+    lookup_table: &AddressLookupTableAccount,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Vec<Instruction> {
+    let missing_addresses = addresses
+        .iter()
+        .filter(|address| !lookup_table.addresses.contains(address))
+        .copied()
+        .collect::<Vec<_>>();
+
+    missing_addresses
+        .chunks(MAX_ADDRESSES_PER_EXTEND)
+        .map(|chunk| {
+            lookup_table_instruction::extend_lookup_table(
+                lookup_table.key,
+                *authority,
+                Some(*payer),
+                chunk.to_vec(),
+            )
+        })
+        .collect()
+}
+
+pub fn load_lookup_table(
+    rpc_client: &RpcClient,
+    address: &Pubkey,
+) -> Result<AddressLookupTableAccount> {
+    let account = rpc_client.get_account(address)?;
+    let lookup_table = AddressLookupTable::deserialize(&account.data)
+        .map_err(|err| anyhow!("Invalid lookup table {}: {}", address, err))?;
+
+    Ok(AddressLookupTableAccount {
+        key: *address,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+/// Compile `instructions` into a signed v0 transaction, looking up its accounts in `lookup_tables`.
+pub fn build_versioned_transaction<T: Signers>(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+    signers: &T,
+) -> Result<VersionedTransaction> {
+    let message = v0::Message::try_compile(payer, instructions, lookup_tables, recent_blockhash)?;
+
+    Ok(VersionedTransaction::try_new(
+        VersionedMessage::V0(message),
+        signers,
+    )?)
+}
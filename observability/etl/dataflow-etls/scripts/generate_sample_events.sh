@@ -108,10 +108,10 @@ mfi account deposit "$usdc_bank" 0.01 -y
 
 echo "-> Liquidator liquidates liquidatee for half its assets"
 mfi account liquidate \
-    --liquidatee-marginfi-account="$liquidatee_account" \
+    --liquidatee="$liquidatee_account" \
     --asset-bank="$sol_bank" \
     --liability-bank="$usdc_bank" \
-    --ui-asset-amount=0.0001 \
+    --amount=0.0001 \
     -y
 
 echo "-> Admin handles remainder of bad debt through handle bankruptcy"
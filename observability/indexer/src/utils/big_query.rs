@@ -77,6 +77,8 @@ lazy_static! {
         TableFieldSchema::float("liquidity_vault_balance"),
         TableFieldSchema::float("insurance_vault_balance"),
         TableFieldSchema::float("fee_vault_balance"),
+        TableFieldSchema::float("insured_bad_debt_in_tokens"),
+        TableFieldSchema::float("socialized_loss_in_tokens"),
     ]);
     pub static ref METRIC_MARGINFI_ACCOUNT_SCHEMA: TableSchema = TableSchema::new(vec![
         TableFieldSchema::string("id"),
@@ -0,0 +1,203 @@
+use anchor_client::anchor_lang::{AnchorDeserialize, Discriminator};
+use base64::{engine::general_purpose, Engine};
+use marginfi::events::*;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+const PROGRAM_LOG_PREFIX: &str = "Program ";
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+pub enum MarginfiEvent {
+    MarginfiGroupCreate(MarginfiGroupCreateEvent),
+    MarginfiGroupConfigure(MarginfiGroupConfigureEvent),
+    LendingPoolBankCreate(LendingPoolBankCreateEvent),
+    LendingPoolBankConfigure(LendingPoolBankConfigureEvent),
+    LendingPoolBankAccrueInterest(LendingPoolBankAccrueInterestEvent),
+    LendingPoolBankCollectFees(LendingPoolBankCollectFeesEvent),
+    LendingPoolBankHandleBankruptcy(LendingPoolBankHandleBankruptcyEvent),
+    LendingPoolBankForceCloseBalance(LendingPoolBankForceCloseBalanceEvent),
+    MarginfiAccountCreate(MarginfiAccountCreateEvent),
+    LendingAccountDeposit(LendingAccountDepositEvent),
+    LendingAccountRepay(LendingAccountRepayEvent),
+    LendingAccountBorrow(LendingAccountBorrowEvent),
+    LendingAccountWithdraw(LendingAccountWithdrawEvent),
+    LendingAccountLiquidate(LendingAccountLiquidateEvent),
+}
+
+macro_rules! decode_event {
+    ($data:expr, $($event:ident => $variant:ident),* $(,)?) => {{
+        let (discriminator, mut payload) = $data.split_at(8);
+        $(
+            if discriminator == $event::discriminator() {
+                return $event::deserialize(&mut payload).ok().map(MarginfiEvent::$variant);
+            }
+        )*
+        None
+    }};
+}
+
+impl MarginfiEvent {
+    /// Decode a raw anchor event (discriminator + borsh payload).
+    /// Returns `None` for unknown or malformed events.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+
+        decode_event!(
+            data,
+            MarginfiGroupCreateEvent => MarginfiGroupCreate,
+            MarginfiGroupConfigureEvent => MarginfiGroupConfigure,
+            LendingPoolBankCreateEvent => LendingPoolBankCreate,
+            LendingPoolBankConfigureEvent => LendingPoolBankConfigure,
+            LendingPoolBankAccrueInterestEvent => LendingPoolBankAccrueInterest,
+            LendingPoolBankCollectFeesEvent => LendingPoolBankCollectFees,
+            LendingPoolBankHandleBankruptcyEvent => LendingPoolBankHandleBankruptcy,
+            LendingPoolBankForceCloseBalanceEvent => LendingPoolBankForceCloseBalance,
+            MarginfiAccountCreateEvent => MarginfiAccountCreate,
+            LendingAccountDepositEvent => LendingAccountDeposit,
+            LendingAccountRepayEvent => LendingAccountRepay,
+            LendingAccountBorrowEvent => LendingAccountBorrow,
+            LendingAccountWithdrawEvent => LendingAccountWithdraw,
+            LendingAccountLiquidateEvent => LendingAccountLiquidate,
+        )
+    }
+
+    /// Extract the marginfi events emitted by `program_id` from a transaction's log messages,
+    /// in emission order. Events emitted by other programs in the same transaction are ignored.
+    pub fn parse_from_logs(logs: &[String], program_id: &Pubkey) -> Vec<Self> {
+        let mut invocation_stack: Vec<Pubkey> = vec![];
+        let mut events = vec![];
+
+        for log in logs {
+            if let Some(data) = log.strip_prefix(PROGRAM_DATA_PREFIX) {
+                if invocation_stack.last() != Some(program_id) {
+                    continue;
+                }
+
+                if let Some(event) = general_purpose::STANDARD
+                    .decode(data)
+                    .ok()
+                    .and_then(|data| Self::decode(&data))
+                {
+                    events.push(event);
+                }
+            } else if let Some(rest) = log.strip_prefix(PROGRAM_LOG_PREFIX) {
+                let mut parts = rest.split_whitespace();
+                let (program, action) = match (parts.next().map(Pubkey::from_str), parts.next()) {
+                    (Some(Ok(program)), Some(action)) => (program, action),
+                    _ => continue,
+                };
+
+                match action {
+                    "invoke" => invocation_stack.push(program),
+                    "success" | "failed:" => {
+                        invocation_stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        events
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MarginfiEvent::MarginfiGroupCreate(_) => "MarginfiGroupCreate",
+            MarginfiEvent::MarginfiGroupConfigure(_) => "MarginfiGroupConfigure",
+            MarginfiEvent::LendingPoolBankCreate(_) => "LendingPoolBankCreate",
+            MarginfiEvent::LendingPoolBankConfigure(_) => "LendingPoolBankConfigure",
+            MarginfiEvent::LendingPoolBankAccrueInterest(_) => "LendingPoolBankAccrueInterest",
+            MarginfiEvent::LendingPoolBankCollectFees(_) => "LendingPoolBankCollectFees",
+            MarginfiEvent::LendingPoolBankHandleBankruptcy(_) => "LendingPoolBankHandleBankruptcy",
+            MarginfiEvent::LendingPoolBankForceCloseBalance(_) => {
+                "LendingPoolBankForceCloseBalance"
+            }
+            MarginfiEvent::MarginfiAccountCreate(_) => "MarginfiAccountCreate",
+            MarginfiEvent::LendingAccountDeposit(_) => "LendingAccountDeposit",
+            MarginfiEvent::LendingAccountRepay(_) => "LendingAccountRepay",
+            MarginfiEvent::LendingAccountBorrow(_) => "LendingAccountBorrow",
+            MarginfiEvent::LendingAccountWithdraw(_) => "LendingAccountWithdraw",
+            MarginfiEvent::LendingAccountLiquidate(_) => "LendingAccountLiquidate",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_client::anchor_lang::AnchorSerialize;
+
+    fn encode<T: AnchorSerialize + Discriminator>(event: T) -> Vec<u8> {
+        let mut data = T::discriminator().to_vec();
+        event.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn deposit_event(amount: u64) -> Vec<u8> {
+        encode(LendingAccountDepositEvent {
+            header: AccountEventHeader {
+                signer: None,
+                marginfi_account: Pubkey::new_unique(),
+                marginfi_account_authority: Pubkey::new_unique(),
+                marginfi_group: Pubkey::new_unique(),
+            },
+            bank: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            amount,
+        })
+    }
+
+    fn data_log(data: &[u8]) -> String {
+        format!(
+            "{}{}",
+            PROGRAM_DATA_PREFIX,
+            general_purpose::STANDARD.encode(data)
+        )
+    }
+
+    #[test]
+    fn decode_event() {
+        match MarginfiEvent::decode(&deposit_event(42)) {
+            Some(MarginfiEvent::LendingAccountDeposit(e)) => assert_eq!(e.amount, 42),
+            _ => panic!("Expected a deposit event"),
+        }
+
+        // Unknown discriminator, truncated payload and short data
+        let mut unknown = deposit_event(42);
+        unknown[0] ^= 0xff;
+        assert!(MarginfiEvent::decode(&unknown).is_none());
+        let truncated = deposit_event(42);
+        assert!(MarginfiEvent::decode(&truncated[..truncated.len() - 1]).is_none());
+        assert!(MarginfiEvent::decode(&[0; 4]).is_none());
+    }
+
+    #[test]
+    fn parse_from_logs_ignores_other_programs() {
+        let program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            data_log(&deposit_event(1)),
+            format!("Program {} invoke [2]", other_program_id),
+            // Same layout emitted by another program
+            data_log(&deposit_event(2)),
+            format!("Program {} success", other_program_id),
+            "Program log: Instruction: LendingAccountDeposit".to_string(),
+            data_log(&deposit_event(3)),
+            format!("Program {} success", program_id),
+            data_log(&deposit_event(4)),
+        ];
+
+        let amounts = MarginfiEvent::parse_from_logs(&logs, &program_id)
+            .into_iter()
+            .map(|event| match event {
+                MarginfiEvent::LendingAccountDeposit(e) => e.amount,
+                _ => panic!("Expected a deposit event"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(amounts, vec![1, 3]);
+    }
+}
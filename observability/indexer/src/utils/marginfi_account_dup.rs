@@ -2,6 +2,7 @@ use fixed::types::I80F48;
 use marginfi::{
     constants::TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
     state::{
+        amounts::ShareAmount,
         marginfi_account::{
             calc_asset_value, Balance, BalanceSide, MarginfiAccount, RiskRequirementType,
             WeightType,
@@ -56,10 +57,12 @@ impl BankAccountWithPriceFeed2 {
 
         let asset_amount = self
             .bank
-            .get_asset_amount(self.balance.asset_shares.into())?;
+            .asset_amount(ShareAmount::new(self.balance.asset_shares.into()))?
+            .value();
         let liability_amount = self
             .bank
-            .get_liability_amount(self.balance.liability_shares.into())?;
+            .liability_amount(ShareAmount::new(self.balance.liability_shares.into()))?
+            .value();
 
         if matches!(weight_type, WeightType::Initial)
             && self.bank.config.total_asset_value_init_limit
@@ -67,7 +70,8 @@ impl BankAccountWithPriceFeed2 {
         {
             let bank_total_assets_value = calc_asset_value(
                 self.bank
-                    .get_asset_amount(self.bank.total_asset_shares.into())?,
+                    .asset_amount(ShareAmount::new(self.bank.total_asset_shares.into()))?
+                    .value(),
                 worst_price,
                 mint_decimals,
                 None,
@@ -8,6 +8,7 @@ use fixed_macro::types::I80F48;
 use itertools::Itertools;
 use marginfi::constants::ZERO_AMOUNT_THRESHOLD;
 use marginfi::prelude::MarginfiGroup;
+use marginfi::state::amounts::ShareAmount;
 use marginfi::state::marginfi_account::{
     calc_asset_value, MarginfiAccount, RiskRequirementType, WeightType,
 };
@@ -88,7 +89,8 @@ impl MarginfiGroupMetrics {
                 let asset_value_usd = calc_asset_value(
                     bank_accounts
                         .bank
-                        .get_asset_amount(total_asset_share.into())?,
+                        .asset_amount(ShareAmount::new(total_asset_share.into()))?
+                        .value(),
                     price,
                     bank_accounts.bank.mint_decimals,
                     None,
@@ -97,7 +99,8 @@ impl MarginfiGroupMetrics {
                 let asset_value_usd_maint = calc_asset_value(
                     bank_accounts
                         .bank
-                        .get_asset_amount(total_asset_share.into())?,
+                        .asset_amount(ShareAmount::new(total_asset_share.into()))?
+                        .value(),
                     price,
                     bank_accounts.bank.mint_decimals,
                     Some(asset_weight),
@@ -106,7 +109,8 @@ impl MarginfiGroupMetrics {
                 let liability_value_usd = calc_asset_value(
                     bank_accounts
                         .bank
-                        .get_liability_amount(total_liability_share.into())?,
+                        .liability_amount(ShareAmount::new(total_liability_share.into()))?
+                        .value(),
                     price,
                     bank_accounts.bank.mint_decimals,
                     None,
@@ -115,7 +119,8 @@ impl MarginfiGroupMetrics {
                 let liability_value_usd_maint = calc_asset_value(
                     bank_accounts
                         .bank
-                        .get_liability_amount(total_liability_share.into())?,
+                        .liability_amount(ShareAmount::new(total_liability_share.into()))?
+                        .value(),
                     price,
                     bank_accounts.bank.mint_decimals,
                     Some(liability_weight),
@@ -194,6 +199,8 @@ pub struct LendingPoolBankMetricsRow {
     pub liquidity_vault_balance: f64,
     pub insurance_vault_balance: f64,
     pub fee_vault_balance: f64,
+    pub insured_bad_debt_in_tokens: f64,
+    pub socialized_loss_in_tokens: f64,
 }
 
 #[derive(Debug)]
@@ -225,6 +232,8 @@ pub struct LendingPoolBankMetrics {
     pub liquidity_vault_balance: f64,
     pub insurance_vault_balance: f64,
     pub fee_vault_balance: f64,
+    pub insured_bad_debt_in_tokens: f64,
+    pub socialized_loss_in_tokens: f64,
 }
 
 impl LendingPoolBankMetrics {
@@ -272,13 +281,15 @@ impl LendingPoolBankMetrics {
 
         let asset_amount = bank_accounts
             .bank
-            .get_asset_amount(total_asset_share.into())?;
+            .asset_amount(ShareAmount::new(total_asset_share.into()))?
+            .value();
         let asset_value_usd =
             calc_asset_value(asset_amount, price, bank_accounts.bank.mint_decimals, None)?
                 .to_num::<f64>();
         let liability_amount = bank_accounts
             .bank
-            .get_liability_amount(total_liability_share.into())?;
+            .liability_amount(ShareAmount::new(total_liability_share.into()))?
+            .value();
         let liability_value_usd = calc_asset_value(
             liability_amount,
             price,
@@ -359,6 +370,12 @@ impl LendingPoolBankMetrics {
                 / (10i64.pow(bank_accounts.bank.mint_decimals as u32) as f64),
             fee_vault_balance: (bank_accounts.fee_vault_token_account.amount as f64)
                 / (10i64.pow(bank_accounts.bank.mint_decimals as u32) as f64),
+            insured_bad_debt_in_tokens: I80F48::from(bank_accounts.bank.insured_bad_debt)
+                .to_num::<f64>()
+                / (10i64.pow(bank_accounts.bank.mint_decimals as u32) as f64),
+            socialized_loss_in_tokens: I80F48::from(bank_accounts.bank.socialized_loss)
+                .to_num::<f64>()
+                / (10i64.pow(bank_accounts.bank.mint_decimals as u32) as f64),
         })
     }
 
@@ -396,6 +413,8 @@ impl LendingPoolBankMetrics {
             liquidity_vault_balance: self.liquidity_vault_balance,
             insurance_vault_balance: self.insurance_vault_balance,
             fee_vault_balance: self.fee_vault_balance,
+            insured_bad_debt_in_tokens: self.insured_bad_debt_in_tokens,
+            socialized_loss_in_tokens: self.socialized_loss_in_tokens,
         }
     }
 }
@@ -523,17 +542,19 @@ impl MarginfiAccountMetrics {
 
                 let (amount, weight_maintenance, weight_initial) = if is_asset {
                     (
-                        bank.get_asset_amount(asset_shares)
+                        bank.asset_amount(ShareAmount::new(asset_shares))
                             .map_err(|_| anyhow!("Bad math during positions summarizing"))
-                            .unwrap(),
+                            .unwrap()
+                            .value(),
                         asset_weight_maintenance,
                         asset_weight_initial,
                     )
                 } else {
                     (
-                        bank.get_asset_amount(liability_shares)
+                        bank.liability_amount(ShareAmount::new(liability_shares))
                             .map_err(|_| anyhow!("Bad math during positions summarizing"))
-                            .unwrap(),
+                            .unwrap()
+                            .value(),
                         liability_weight_maintenance,
                         liability_weight_initial,
                     )
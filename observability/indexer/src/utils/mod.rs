@@ -1,8 +1,10 @@
 pub mod big_query;
 pub mod errors;
+pub mod events;
 pub mod geyser_client;
 pub mod marginfi_account_dup;
 pub mod metrics;
 pub mod protos;
+pub mod replay;
 pub mod snapshot;
 pub mod transactions_crawler;
@@ -0,0 +1,702 @@
+use crate::utils::{events::MarginfiEvent, snapshot::Snapshot};
+use anyhow::{anyhow, bail, Result};
+use bytemuck::Zeroable;
+use fixed::types::I80F48;
+use marginfi::state::{
+    amounts::TokenAmount,
+    marginfi_account::{Balance, BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
+    marginfi_group::{Bank, BankConfigOpt},
+};
+use solana_sdk::{clock::Clock, program_stubs, pubkey::Pubkey, signature::Signature};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Once,
+    },
+};
+use tracing::{debug, warn};
+
+static REPLAY_TIMESTAMP: AtomicI64 = AtomicI64::new(0);
+static INIT_REPLAY_STUBS: Once = Once::new();
+
+/// Syscall stubs letting the program's own accounting logic run off-chain,
+/// with the clock pinned to the block time of the event being replayed.
+struct ReplaySyscallStubs;
+
+impl program_stubs::SyscallStubs for ReplaySyscallStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut _ as *mut Clock) = Clock {
+                unix_timestamp: REPLAY_TIMESTAMP.load(Ordering::Relaxed),
+                ..Clock::default()
+            };
+        }
+        solana_sdk::entrypoint::SUCCESS
+    }
+}
+
+pub struct ReplayEvent {
+    pub slot: u64,
+    pub timestamp: i64,
+    pub signature: Signature,
+    pub event: MarginfiEvent,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayFailure {
+    pub slot: u64,
+    pub signature: Signature,
+    pub event: &'static str,
+    pub error: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum ReplayDiscrepancy {
+    MissingBank(Pubkey),
+    MissingMarginfiAccount(Pubkey),
+    Bank {
+        bank: Pubkey,
+        field: &'static str,
+        replayed: I80F48,
+        expected: I80F48,
+    },
+    Balance {
+        marginfi_account: Pubkey,
+        bank: Pubkey,
+        field: &'static str,
+        replayed: I80F48,
+        expected: I80F48,
+    },
+}
+
+/// Reconstructs bank and marginfi account state from an ordered stream of program events.
+///
+/// The replayer is seeded with the state at a known slot (usually an on-chain snapshot),
+/// and rolls it forward by re-running the program's accounting logic for every event.
+/// Overriding bank configs before replaying allows backtesting risk parameter changes
+/// against real history; historical operations rejected under the new parameters
+/// are recorded in `failures` instead of aborting the replay.
+pub struct StateReplayer {
+    pub slot: u64,
+    pub banks: HashMap<Pubkey, Bank>,
+    pub marginfi_accounts: HashMap<Pubkey, MarginfiAccount>,
+    pub failures: Vec<ReplayFailure>,
+    config_overrides: HashMap<Pubkey, BankConfigOpt>,
+}
+
+impl StateReplayer {
+    pub fn new(
+        slot: u64,
+        banks: HashMap<Pubkey, Bank>,
+        marginfi_accounts: HashMap<Pubkey, MarginfiAccount>,
+    ) -> Self {
+        INIT_REPLAY_STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(ReplaySyscallStubs));
+        });
+
+        Self {
+            slot,
+            banks,
+            marginfi_accounts,
+            failures: vec![],
+            config_overrides: HashMap::new(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot, slot: u64) -> Self {
+        Self::new(
+            slot,
+            snapshot
+                .banks
+                .iter()
+                .map(|(address, bank_accounts)| (*address, bank_accounts.bank))
+                .collect(),
+            snapshot.marginfi_accounts.clone(),
+        )
+    }
+
+    /// Apply a hypothetical config to a bank for the rest of the replay.
+    /// The override is re-applied on top of any later historical config change.
+    pub fn override_bank_config(&mut self, bank_pk: Pubkey, config: BankConfigOpt) -> Result<()> {
+        let bank = self
+            .banks
+            .get_mut(&bank_pk)
+            .ok_or_else(|| anyhow!("Bank {} not found", bank_pk))?;
+        bank.configure(&config)
+            .map_err(|e| anyhow!("Invalid config override for bank {}: {:?}", bank_pk, e))?;
+
+        self.config_overrides.insert(bank_pk, config);
+
+        Ok(())
+    }
+
+    /// Replay `events` up to and including `until_slot`.
+    ///
+    /// Events at or before the replayer's current slot are assumed to be reflected
+    /// in the seed state already and are skipped.
+    pub fn replay<I: IntoIterator<Item = ReplayEvent>>(
+        &mut self,
+        events: I,
+        until_slot: u64,
+    ) -> Result<()> {
+        let mut last_slot = self.slot;
+
+        for event in events {
+            if event.slot > until_slot {
+                break;
+            }
+
+            if event.slot < last_slot {
+                bail!(
+                    "Events out of order: slot {} after slot {} ({})",
+                    event.slot,
+                    last_slot,
+                    event.signature
+                );
+            }
+            last_slot = event.slot;
+
+            if event.slot <= self.slot {
+                continue;
+            }
+
+            if let Err(e) = self.apply(&event) {
+                warn!(
+                    "Failed to replay {} at slot {} ({}): {:?}",
+                    event.event.name(),
+                    event.slot,
+                    event.signature,
+                    e
+                );
+                self.failures.push(ReplayFailure {
+                    slot: event.slot,
+                    signature: event.signature,
+                    event: event.event.name(),
+                    error: e.to_string(),
+                });
+            }
+        }
+
+        self.slot = until_slot;
+
+        Ok(())
+    }
+
+    /// Apply a single event to the replayed state.
+    pub fn apply(&mut self, event: &ReplayEvent) -> Result<()> {
+        REPLAY_TIMESTAMP.store(event.timestamp, Ordering::Relaxed);
+
+        match &event.event {
+            MarginfiEvent::LendingPoolBankConfigure(e) => {
+                let bank = self.get_bank_mut(&e.bank)?;
+                bank.configure(&e.config)
+                    .map_err(|e| anyhow!("Bank configure failed: {:?}", e))?;
+                if let Some(config) = self.config_overrides.get(&e.bank).cloned() {
+                    self.override_bank_config(e.bank, config)?;
+                }
+            }
+            MarginfiEvent::LendingPoolBankAccrueInterest(e) => {
+                self.accrue_interest(&e.bank, event.timestamp)?;
+            }
+            MarginfiEvent::MarginfiAccountCreate(e) => {
+                let mut marginfi_account: MarginfiAccount = Zeroable::zeroed();
+                marginfi_account
+                    .initialize(e.header.marginfi_group, e.header.marginfi_account_authority);
+                self.marginfi_accounts
+                    .insert(e.header.marginfi_account, marginfi_account);
+            }
+            MarginfiEvent::LendingAccountDeposit(e) => {
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.bank,
+                    event.timestamp,
+                    |bank_account| bank_account.deposit(TokenAmount::from_native(e.amount)),
+                )?;
+            }
+            MarginfiEvent::LendingAccountRepay(e) => {
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.bank,
+                    event.timestamp,
+                    |bank_account| {
+                        if e.close_balance {
+                            bank_account.repay_all().map(|_| ())
+                        } else {
+                            bank_account.repay(TokenAmount::from_native(e.amount))
+                        }
+                    },
+                )?;
+            }
+            MarginfiEvent::LendingAccountWithdraw(e) => {
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.bank,
+                    event.timestamp,
+                    |bank_account| {
+                        if e.close_balance {
+                            bank_account.withdraw_all().map(|_| ())
+                        } else {
+                            bank_account.withdraw(TokenAmount::from_native(e.amount))
+                        }
+                    },
+                )?;
+            }
+            MarginfiEvent::LendingAccountBorrow(e) => {
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.bank,
+                    event.timestamp,
+                    // The fees are borrowed along with the amount
+                    |bank_account| {
+                        bank_account.borrow(TokenAmount::from_native(
+                            e.amount + e.origination_fee + e.program_fee,
+                        ))
+                    },
+                )?;
+            }
+            MarginfiEvent::LendingAccountLiquidate(e) => {
+                // Replayed from the exact amounts moved by the liquidation, the balance
+                // deltas don't tell a liability paid from a deposit apart from a new one
+                let asset_amount = TokenAmount::from_native(e.asset_amount);
+
+                // Liquidator pays off liability
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.liability_bank,
+                    event.timestamp,
+                    |bank_account| {
+                        bank_account.decrease_balance_in_liquidation(TokenAmount::new(
+                            e.liability_amount_liquidator.into(),
+                        ))
+                    },
+                )?;
+                // Liquidatee pays off collateral
+                self.with_bank_account(
+                    &e.liquidatee_marginfi_account,
+                    &e.asset_bank,
+                    event.timestamp,
+                    |bank_account| bank_account.withdraw(asset_amount),
+                )?;
+                // Liquidator receives collateral, minus the program fee
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.asset_bank,
+                    event.timestamp,
+                    |bank_account| {
+                        bank_account.increase_balance(
+                            asset_amount.checked_sub(TokenAmount::from_native(e.program_fee))?,
+                        )?;
+                        bank_account.bank.record_program_fee(e.program_fee)
+                    },
+                )?;
+                // Liquidatee receives liability payment
+                self.with_bank_account(
+                    &e.liquidatee_marginfi_account,
+                    &e.liability_bank,
+                    event.timestamp,
+                    |bank_account| {
+                        bank_account.increase_balance(TokenAmount::new(
+                            e.liability_amount_liquidatee.into(),
+                        ))
+                    },
+                )?;
+            }
+            MarginfiEvent::LendingPoolBankHandleBankruptcy(e) => {
+                self.accrue_interest(&e.bank, event.timestamp)?;
+                self.get_bank_mut(&e.bank)?
+                    .socialize_loss(e.socialized_amount.into())
+                    .map_err(|e| anyhow!("Loss socialization failed: {:?}", e))?;
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.bank,
+                    event.timestamp,
+                    |bank_account| bank_account.repay(TokenAmount::new(e.bad_debt.into())),
+                )?;
+                self.get_marginfi_account_mut(&e.header.marginfi_account)?
+                    .set_flag(DISABLED_FLAG);
+            }
+            MarginfiEvent::LendingPoolBankForceCloseBalance(e) => {
+                self.with_bank_account(
+                    &e.header.marginfi_account,
+                    &e.bank,
+                    event.timestamp,
+                    |bank_account| bank_account.force_close_dust_balance().map(|_| ()),
+                )?;
+            }
+            MarginfiEvent::LendingPoolBankCreate(e) => {
+                // Bank creation events don't carry the bank config,
+                // banks created after the seed slot can't be replayed.
+                debug!("Bank {} created after seed slot, skipping", e.bank);
+            }
+            MarginfiEvent::MarginfiGroupCreate(_)
+            | MarginfiEvent::MarginfiGroupConfigure(_)
+            | MarginfiEvent::LendingPoolBankCollectFees(_) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Compare the replayed state with an on-chain snapshot taken at the replayer's slot.
+    /// Share values and share counts are compared with an absolute `tolerance`.
+    pub fn validate(&self, snapshot: &Snapshot, tolerance: I80F48) -> Vec<ReplayDiscrepancy> {
+        let mut discrepancies = vec![];
+
+        for (bank_pk, bank) in self.banks.iter() {
+            let expected = match snapshot.banks.get(bank_pk) {
+                Some(bank_accounts) => &bank_accounts.bank,
+                None => {
+                    discrepancies.push(ReplayDiscrepancy::MissingBank(*bank_pk));
+                    continue;
+                }
+            };
+
+            for (field, replayed, expected) in [
+                (
+                    "asset_share_value",
+                    bank.asset_share_value,
+                    expected.asset_share_value,
+                ),
+                (
+                    "liability_share_value",
+                    bank.liability_share_value,
+                    expected.liability_share_value,
+                ),
+                (
+                    "total_asset_shares",
+                    bank.total_asset_shares,
+                    expected.total_asset_shares,
+                ),
+                (
+                    "total_liability_shares",
+                    bank.total_liability_shares,
+                    expected.total_liability_shares,
+                ),
+            ] {
+                let (replayed, expected): (I80F48, I80F48) = (replayed.into(), expected.into());
+                if (replayed - expected).abs() > tolerance {
+                    discrepancies.push(ReplayDiscrepancy::Bank {
+                        bank: *bank_pk,
+                        field,
+                        replayed,
+                        expected,
+                    });
+                }
+            }
+        }
+
+        for (marginfi_account_pk, marginfi_account) in self.marginfi_accounts.iter() {
+            let expected = match snapshot.marginfi_accounts.get(marginfi_account_pk) {
+                Some(marginfi_account) => marginfi_account,
+                None => {
+                    discrepancies.push(ReplayDiscrepancy::MissingMarginfiAccount(
+                        *marginfi_account_pk,
+                    ));
+                    continue;
+                }
+            };
+
+            let mut bank_pks = marginfi_account
+                .lending_account
+                .get_active_balances_iter()
+                .chain(expected.lending_account.get_active_balances_iter())
+                .map(|balance| balance.bank_pk)
+                .collect::<Vec<_>>();
+            bank_pks.sort();
+            bank_pks.dedup();
+
+            for bank_pk in bank_pks {
+                let replayed_balance = marginfi_account.lending_account.get_balance(&bank_pk);
+                let expected_balance = expected.lending_account.get_balance(&bank_pk);
+
+                let shares = |balance: Option<&Balance>| -> (I80F48, I80F48) {
+                    balance
+                        .map(|b| (b.asset_shares.into(), b.liability_shares.into()))
+                        .unwrap_or((I80F48::ZERO, I80F48::ZERO))
+                };
+                let (replayed_assets, replayed_liabilities) = shares(replayed_balance);
+                let (expected_assets, expected_liabilities) = shares(expected_balance);
+
+                for (field, replayed, expected) in [
+                    ("asset_shares", replayed_assets, expected_assets),
+                    (
+                        "liability_shares",
+                        replayed_liabilities,
+                        expected_liabilities,
+                    ),
+                ] {
+                    if (replayed - expected).abs() > tolerance {
+                        discrepancies.push(ReplayDiscrepancy::Balance {
+                            marginfi_account: *marginfi_account_pk,
+                            bank: bank_pk,
+                            field,
+                            replayed,
+                            expected,
+                        });
+                    }
+                }
+            }
+        }
+
+        discrepancies
+    }
+
+    fn accrue_interest(&mut self, bank_pk: &Pubkey, timestamp: i64) -> Result<()> {
+        let bank = self.get_bank_mut(bank_pk)?;
+        if timestamp > bank.last_update {
+            bank.accrue_interest(timestamp)
+                .map_err(|e| anyhow!("Interest accrual failed: {:?}", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn with_bank_account<F>(
+        &mut self,
+        marginfi_account_pk: &Pubkey,
+        bank_pk: &Pubkey,
+        timestamp: i64,
+        op: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut BankAccountWrapper) -> marginfi::prelude::MarginfiResult,
+    {
+        self.accrue_interest(bank_pk, timestamp)?;
+
+        let bank = self
+            .banks
+            .get_mut(bank_pk)
+            .ok_or_else(|| anyhow!("Bank {} not found", bank_pk))?;
+        let marginfi_account = self
+            .marginfi_accounts
+            .get_mut(marginfi_account_pk)
+            .ok_or_else(|| anyhow!("Marginfi account {} not found", marginfi_account_pk))?;
+
+        let mut bank_account = BankAccountWrapper::find_or_create(
+            bank_pk,
+            bank,
+            &mut marginfi_account.lending_account,
+        )
+        .map_err(|e| anyhow!("Balance not found: {:?}", e))?;
+
+        op(&mut bank_account).map_err(|e| anyhow!("{:?}", e))
+    }
+
+    fn get_bank_mut(&mut self, bank_pk: &Pubkey) -> Result<&mut Bank> {
+        self.banks
+            .get_mut(bank_pk)
+            .ok_or_else(|| anyhow!("Bank {} not found", bank_pk))
+    }
+
+    fn get_marginfi_account_mut(
+        &mut self,
+        marginfi_account_pk: &Pubkey,
+    ) -> Result<&mut MarginfiAccount> {
+        self.marginfi_accounts
+            .get_mut(marginfi_account_pk)
+            .ok_or_else(|| anyhow!("Marginfi account {} not found", marginfi_account_pk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixed_macro::types::I80F48;
+    use marginfi::{
+        events::*,
+        state::marginfi_group::{BankConfig, BankOperationalState},
+    };
+
+    fn bank() -> Bank {
+        Bank {
+            asset_share_value: I80F48::ONE.into(),
+            liability_share_value: I80F48::ONE.into(),
+            config: BankConfig {
+                operational_state: BankOperationalState::Operational,
+                deposit_limit: u64::MAX,
+                borrow_limit: u64::MAX,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn header(marginfi_account: Pubkey) -> AccountEventHeader {
+        AccountEventHeader {
+            signer: None,
+            marginfi_account,
+            marginfi_account_authority: Pubkey::new_unique(),
+            marginfi_group: Pubkey::default(),
+        }
+    }
+
+    fn replay_event(slot: u64, event: MarginfiEvent) -> ReplayEvent {
+        ReplayEvent {
+            slot,
+            timestamp: 0,
+            signature: Signature::default(),
+            event,
+        }
+    }
+
+    fn shares(
+        replayer: &StateReplayer,
+        marginfi_account: &Pubkey,
+        bank: &Pubkey,
+    ) -> (I80F48, I80F48) {
+        replayer.marginfi_accounts[marginfi_account]
+            .lending_account
+            .get_balance(bank)
+            .map(|b| (b.asset_shares.into(), b.liability_shares.into()))
+            .unwrap_or((I80F48::ZERO, I80F48::ZERO))
+    }
+
+    /// Liquidatee with a deposit in `asset_bank` and a borrow in `liability_bank`,
+    /// liquidator with a deposit in `liability_bank`.
+    fn setup() -> (StateReplayer, [Pubkey; 4]) {
+        let [asset_bank, liability_bank, liquidator, liquidatee] =
+            [(); 4].map(|_| Pubkey::new_unique());
+
+        let mut replayer = StateReplayer::new(
+            0,
+            HashMap::from([(asset_bank, bank()), (liability_bank, bank())]),
+            HashMap::new(),
+        );
+
+        let deposit = |marginfi_account, bank, amount| {
+            MarginfiEvent::LendingAccountDeposit(LendingAccountDepositEvent {
+                header: header(marginfi_account),
+                bank,
+                mint: Pubkey::default(),
+                amount,
+            })
+        };
+        let events = vec![
+            MarginfiEvent::MarginfiAccountCreate(MarginfiAccountCreateEvent {
+                header: header(liquidator),
+            }),
+            MarginfiEvent::MarginfiAccountCreate(MarginfiAccountCreateEvent {
+                header: header(liquidatee),
+            }),
+            deposit(liquidator, liability_bank, 100),
+            deposit(liquidatee, asset_bank, 100),
+            MarginfiEvent::LendingAccountBorrow(LendingAccountBorrowEvent {
+                header: header(liquidatee),
+                bank: liability_bank,
+                mint: Pubkey::default(),
+                amount: 49,
+                origination_fee: 1,
+                program_fee: 0,
+                flat_fee: 0,
+            }),
+        ];
+
+        replayer
+            .replay(
+                events
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, event)| replay_event(i as u64 + 1, event)),
+                10,
+            )
+            .unwrap();
+        assert!(replayer.failures.is_empty());
+
+        (
+            replayer,
+            [asset_bank, liability_bank, liquidator, liquidatee],
+        )
+    }
+
+    #[test]
+    fn liquidation_paid_from_liquidator_deposit() {
+        let (mut replayer, [asset_bank, liability_bank, liquidator, liquidatee]) = setup();
+
+        // The liquidator's liability balance is zero before and after,
+        // the liability is paid from its deposit
+        let balances = || LiquidationBalances {
+            liquidatee_asset_balance: 0.0,
+            liquidatee_liability_balance: 0.0,
+            liquidator_asset_balance: 0.0,
+            liquidator_liability_balance: 0.0,
+        };
+        let liquidation = MarginfiEvent::LendingAccountLiquidate(LendingAccountLiquidateEvent {
+            header: header(liquidator),
+            liquidatee_marginfi_account: liquidatee,
+            liquidatee_marginfi_account_authority: Pubkey::new_unique(),
+            asset_bank,
+            asset_mint: Pubkey::default(),
+            liability_bank,
+            liability_mint: Pubkey::default(),
+            asset_price: 1.0,
+            liability_price: 1.0,
+            asset_amount: 10,
+            liability_amount_liquidator: I80F48!(9.75).into(),
+            liability_amount_liquidatee: I80F48!(9.5).into(),
+            insurance_fee: I80F48!(0.25).into(),
+            liquidatee_pre_health: 0.0,
+            liquidatee_post_health: 0.0,
+            pre_balances: balances(),
+            post_balances: balances(),
+            program_fee: 1,
+        });
+        replayer.apply(&replay_event(11, liquidation)).unwrap();
+
+        assert_eq!(
+            shares(&replayer, &liquidator, &liability_bank),
+            (I80F48!(90.25), I80F48::ZERO)
+        );
+        assert_eq!(
+            shares(&replayer, &liquidator, &asset_bank),
+            (I80F48!(9), I80F48::ZERO)
+        );
+        assert_eq!(
+            shares(&replayer, &liquidatee, &asset_bank),
+            (I80F48!(90), I80F48::ZERO)
+        );
+        assert_eq!(
+            shares(&replayer, &liquidatee, &liability_bank),
+            (I80F48::ZERO, I80F48!(40.5))
+        );
+        assert_eq!(
+            I80F48::from(replayer.banks[&asset_bank].collected_program_fees_outstanding),
+            I80F48!(1)
+        );
+    }
+
+    #[test]
+    fn bankruptcy_socializes_exact_loss() {
+        let (mut replayer, [_, liability_bank, liquidator, liquidatee]) = setup();
+
+        let bankruptcy =
+            MarginfiEvent::LendingPoolBankHandleBankruptcy(LendingPoolBankHandleBankruptcyEvent {
+                header: header(liquidatee),
+                bank: liability_bank,
+                mint: Pubkey::default(),
+                bad_debt: I80F48!(50).into(),
+                covered_amount: I80F48::ZERO.into(),
+                socialized_amount: I80F48!(50).into(),
+                socialized_value: 50.0,
+                bank_insured_bad_debt: 0.0,
+                bank_socialized_loss: 50.0,
+                group_bankruptcy_count: 1,
+                group_socialized_loss_value: 50.0,
+            });
+        replayer.apply(&replay_event(11, bankruptcy)).unwrap();
+
+        let bank = &replayer.banks[&liability_bank];
+        assert_eq!(I80F48::from(bank.asset_share_value), I80F48!(0.5));
+        assert_eq!(I80F48::from(bank.total_liability_shares), I80F48::ZERO);
+        assert_eq!(
+            shares(&replayer, &liquidatee, &liability_bank),
+            (I80F48::ZERO, I80F48::ZERO)
+        );
+        assert_eq!(
+            shares(&replayer, &liquidator, &liability_bank),
+            (I80F48!(100), I80F48::ZERO)
+        );
+        assert!(replayer.marginfi_accounts[&liquidatee].get_flag(DISABLED_FLAG));
+    }
+}
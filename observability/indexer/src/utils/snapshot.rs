@@ -137,7 +137,7 @@ impl Snapshot {
         if account.owner == self.program_id {
             let discriminator = &account.data[..8];
             if discriminator == Bank::discriminator() {
-                let bank = Bank::try_deserialize(&mut (&account.data as &[u8])).unwrap();
+                let bank = deserialize_bank(&account.data);
                 self.routing_lookup.insert(
                     *account_pubkey,
                     AccountRoutingType::Bank(*account_pubkey, BankUpdateRoutingType::State),
@@ -259,8 +259,7 @@ impl Snapshot {
                     SplAccount::unpack_from_slice(&account.data as &[u8]).unwrap();
             }
             AccountRoutingType::Bank(bank_pk, BankUpdateRoutingType::State) => {
-                self.banks.get_mut(bank_pk).unwrap().bank =
-                    Bank::try_deserialize(&mut (&account.data as &[u8])).unwrap();
+                self.banks.get_mut(bank_pk).unwrap().bank = deserialize_bank(&account.data);
             }
             AccountRoutingType::MarginfiAccount => {
                 self.marginfi_accounts.insert(
@@ -284,3 +283,11 @@ impl Snapshot {
         }
     }
 }
+
+/// Banks are upgraded in memory to the latest layout version, so that the fields introduced by
+/// a migration read as the program sees them, even for banks not migrated on-chain yet.
+fn deserialize_bank(data: &[u8]) -> Bank {
+    let mut bank = Bank::try_deserialize(&mut &data[..]).unwrap();
+    bank.migrate().unwrap();
+    bank
+}
@@ -1,4 +1,6 @@
 use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use fixed_macro::types::I80F48;
 
 #[constant]
 pub const CAMPAIGN_SEED: &str = "campaign";
@@ -10,3 +12,15 @@ pub const DEPOSIT_MFI_AUTH_SIGNER_SEED: &str = "deposit_mfi_auth";
 pub const TEMP_TOKEN_ACCOUNT_AUTH_SEED: &str = "ephemeral_token_account_auth";
 #[constant]
 pub const MARGINFI_ACCOUNT_SEED: &str = "marginfi_account";
+#[constant]
+pub const USER_DEPOSITS_SEED: &str = "user_deposits";
+
+pub const MAX_BPS: u64 = 10_000;
+
+/// Max number of lockup tiers of a campaign, besides its base lockup period.
+pub const MAX_LOCKUP_TIERS: usize = 4;
+
+/// Lowest guaranteed APR a campaign can be created with (0.01%)
+pub const MIN_CAMPAIGN_APR: I80F48 = I80F48!(0.0001);
+/// Highest guaranteed APR a campaign can be created with (1000%)
+pub const MAX_CAMPAIGN_APR: I80F48 = I80F48!(10);
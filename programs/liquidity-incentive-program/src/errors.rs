@@ -8,4 +8,26 @@ pub enum LIPError {
     DepositAmountTooLarge,
     #[msg("Deposit hasn't matured yet")]
     DepositNotMature,
+    #[msg("Invalid campaign parameters")]
+    InvalidCampaignParameters,
+    #[msg("Campaign APR is out of bounds")]
+    CampaignAprOutOfBounds,
+    #[msg("Deposit has already matured")]
+    DepositAlreadyMature,
+    #[msg("Deposit exceeds the campaign per-user cap")]
+    UserDepositCapExceeded,
+    #[msg("Campaign rewards would fall below the rewards committed to deposits")]
+    CampaignRewardsBelowCommitted,
+    #[msg("Invalid lockup tier")]
+    InvalidLockupTier,
+    #[msg("Emissions accounts of the campaign bank are missing")]
+    MissingEmissionsAccounts,
+    #[msg("Campaign is paused")]
+    CampaignPaused,
+    #[msg("Campaign deposit deadline has passed")]
+    DepositDeadlinePassed,
+    #[msg("Campaign is still active")]
+    CampaignActive,
+    #[msg("Invalid campaign deposit accounts")]
+    InvalidDepositAccounts,
 }
@@ -0,0 +1,122 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Token;
+use marginfi::{program::Marginfi, state::marginfi_group::Bank};
+
+use crate::{
+    constants::{DEPOSIT_MFI_AUTH_SIGNER_SEED, MARGINFI_ACCOUNT_SEED},
+    state::{Campaign, Deposit},
+};
+
+/// Claims the marginfi bank emissions accrued by a deposit, before or after the end of its lockup.
+///
+/// The principal of a deposit sits in a marginfi account owned by the program,
+/// which accrues the emissions of the campaign bank like any other lender.
+///
+/// # Arguments
+/// * ctx: Context of the deposit whose emissions are claimed
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the emissions are transferred to the destination account.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the deposit owner
+/// * The emissions accounts don't match the emissions of the campaign bank
+pub fn process(ctx: Context<ClaimDepositEmissions>) -> Result<()> {
+    withdraw_deposit_emissions(
+        ctx.accounts.marginfi_program.to_account_info(),
+        marginfi::cpi::accounts::LendingAccountWithdrawEmissions {
+            marginfi_group: ctx.accounts.marginfi_group.to_account_info(),
+            marginfi_account: ctx.accounts.marginfi_account.to_account_info(),
+            signer: ctx.accounts.mfi_pda_signer.to_account_info(),
+            bank: ctx.accounts.marginfi_bank.to_account_info(),
+            emissions_mint: ctx.accounts.emissions_mint.to_account_info(),
+            emissions_auth: ctx.accounts.emissions_auth.to_account_info(),
+            emissions_vault: ctx.accounts.emissions_vault.to_account_info(),
+            destination_account: ctx.accounts.destination_account.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        },
+        ctx.accounts.deposit.key(),
+        *ctx.bumps.get("mfi_pda_signer").unwrap(),
+    )
+}
+
+/// Settles the emissions of the deposit marginfi account and withdraws them, signing as the deposit PDA.
+pub(crate) fn withdraw_deposit_emissions<'info>(
+    marginfi_program: AccountInfo<'info>,
+    accounts: marginfi::cpi::accounts::LendingAccountWithdrawEmissions<'info>,
+    deposit: Pubkey,
+    mfi_pda_signer_bump: u8,
+) -> Result<()> {
+    marginfi::cpi::lending_account_withdraw_emissions(
+        CpiContext::new_with_signer(
+            marginfi_program,
+            accounts,
+            &[&[
+                DEPOSIT_MFI_AUTH_SIGNER_SEED.as_bytes(),
+                deposit.as_ref(),
+                &[mfi_pda_signer_bump],
+            ]],
+        ),
+        None,
+    )
+}
+
+#[derive(Accounts)]
+pub struct ClaimDepositEmissions<'info> {
+    #[account(address = deposit.campaign)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(address = deposit.owner)]
+    pub signer: Signer<'info>,
+
+    pub deposit: Box<Account<'info, Deposit>>,
+
+    #[account(
+        seeds = [
+            DEPOSIT_MFI_AUTH_SIGNER_SEED.as_bytes(),
+            deposit.key().as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: Asserted by PDA derivation
+    pub mfi_pda_signer: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            MARGINFI_ACCOUNT_SEED.as_bytes(),
+            deposit.key().as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: Asserted by PDA derivation
+    pub marginfi_account: AccountInfo<'info>,
+
+    /// CHECK: Asserted by CPI call
+    pub marginfi_group: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = campaign.marginfi_bank_pk,
+    )]
+    pub marginfi_bank: AccountLoader<'info, Bank>,
+
+    /// CHECK: Asserted by CPI call
+    pub emissions_mint: AccountInfo<'info>,
+
+    /// CHECK: Asserted by CPI call
+    pub emissions_auth: AccountInfo<'info>,
+
+    /// CHECK: Asserted by CPI call
+    #[account(mut)]
+    pub emissions_vault: AccountInfo<'info>,
+
+    /// CHECK: Asserted by CPI call
+    #[account(mut)]
+    pub destination_account: AccountInfo<'info>,
+
+    pub marginfi_program: Program<'info, Marginfi>,
+    pub token_program: Program<'info, Token>,
+}
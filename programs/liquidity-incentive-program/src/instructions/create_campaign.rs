@@ -1,20 +1,85 @@
 use crate::{
-    constants::{CAMPAIGN_AUTH_SEED, CAMPAIGN_SEED},
-    state::Campaign,
+    constants::{
+        CAMPAIGN_AUTH_SEED, CAMPAIGN_SEED, MAX_BPS, MAX_CAMPAIGN_APR, MAX_LOCKUP_TIERS,
+        MIN_CAMPAIGN_APR,
+    },
+    errors::LIPError,
+    state::{calc_campaign_apr, Campaign, LockupTier},
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::{transfer, Token, TokenAccount, Transfer};
 use marginfi::state::marginfi_group::Bank;
 use std::mem::size_of;
 
+#[allow(clippy::too_many_arguments)]
 pub fn process(
     ctx: Context<CreateCampaign>,
     lockup_period: u64,
     max_deposits: u64,
     max_rewards: u64,
+    early_withdrawal_reward_bps: u64,
+    max_deposit_per_user: u64,
+    lockup_tiers: Vec<LockupTier>,
+    deposit_deadline: i64,
 ) -> Result<()> {
     require_gt!(max_deposits, 0);
 
+    if deposit_deadline > 0 {
+        require_gt!(
+            deposit_deadline,
+            Clock::get()?.unix_timestamp,
+            LIPError::InvalidCampaignParameters
+        );
+    }
+    require_gte!(
+        MAX_BPS,
+        early_withdrawal_reward_bps,
+        LIPError::InvalidCampaignParameters
+    );
+
+    let apr = calc_campaign_apr(max_rewards, max_deposits, lockup_period)
+        .ok_or(LIPError::InvalidCampaignParameters)?;
+
+    msg!("Campaign APR: {}", apr);
+
+    require!(
+        (MIN_CAMPAIGN_APR..=MAX_CAMPAIGN_APR).contains(&apr),
+        LIPError::CampaignAprOutOfBounds
+    );
+
+    require_gte!(
+        MAX_LOCKUP_TIERS,
+        lockup_tiers.len(),
+        LIPError::InvalidLockupTier
+    );
+
+    for tier in lockup_tiers.iter() {
+        // Tiers trade a shorter lockup for a share of the rewards,
+        // so that deposits never draw more than their share of `max_rewards`
+        require!(
+            tier.lockup_period > 0 && tier.lockup_period < lockup_period,
+            LIPError::InvalidLockupTier
+        );
+        require!(
+            tier.reward_bps > 0 && tier.reward_bps <= MAX_BPS,
+            LIPError::InvalidLockupTier
+        );
+
+        let tier_apr = tier
+            .apr(max_rewards, max_deposits)
+            .ok_or(LIPError::InvalidLockupTier)?;
+
+        msg!("Tier lockup: {}s, APR: {}", tier.lockup_period, tier_apr);
+
+        require!(
+            (MIN_CAMPAIGN_APR..=MAX_CAMPAIGN_APR).contains(&tier_apr),
+            LIPError::CampaignAprOutOfBounds
+        );
+    }
+
+    let mut tiers = [LockupTier::default(); MAX_LOCKUP_TIERS];
+    tiers[..lockup_tiers.len()].copy_from_slice(&lockup_tiers);
+
     transfer(
         CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
@@ -35,7 +100,14 @@ pub fn process(
         remaining_capacity: max_deposits,
         max_rewards,
         marginfi_bank_pk: ctx.accounts.marginfi_bank.key(),
-        _padding: [0; 16],
+        exited_deposits: 0,
+        early_withdrawal_reward_bps,
+        max_deposit_per_user,
+        lockup_tiers: tiers,
+        deposit_deadline,
+        paused: false,
+        original_lockup_period: 0,
+        _padding: [0; 2],
     });
 
     Ok(())
@@ -1,7 +1,7 @@
 use crate::{
-    constants::{DEPOSIT_MFI_AUTH_SIGNER_SEED, MARGINFI_ACCOUNT_SEED},
+    constants::{DEPOSIT_MFI_AUTH_SIGNER_SEED, MARGINFI_ACCOUNT_SEED, USER_DEPOSITS_SEED},
     errors::LIPError,
-    state::{Campaign, Deposit},
+    state::{Campaign, Deposit, UserDeposits},
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::{
@@ -15,15 +15,30 @@ use std::mem::size_of;
 /// # Arguments
 /// * `ctx`: Context struct containing the relevant accounts for the new deposit
 /// * `amount`: The amount of tokens to be deposited.
+/// * `lockup_tier`: The lockup tier of the deposit, 0 for the campaign lockup period.
 ///
 /// # Returns
 /// * `Ok(())` if the deposit was successfully made, or an error otherwise.
 ///
 /// # Errors
 /// * `LIPError::CampaignNotActive` if the relevant campaign is not active.
+/// * `LIPError::CampaignPaused` if the campaign is paused by its admin.
+/// * `LIPError::DepositDeadlinePassed` if the campaign deposit deadline has passed.
 /// * `LIPError::DepositAmountTooLarge` is the deposit amount exceeds the amount of remaining deposits that can be made into the campaign.
-pub fn process(ctx: Context<CreateDeposit>, amount: u64) -> Result<()> {
-    require!(ctx.accounts.campaign.active, LIPError::CampaignNotActive);
+/// * `LIPError::UserDepositCapExceeded` if the signer's cumulative deposits would exceed the campaign per-user cap.
+/// * `LIPError::InvalidLockupTier` if the lockup tier isn't set on the campaign.
+pub fn process(ctx: Context<CreateDeposit>, amount: u64, lockup_tier: u64) -> Result<()> {
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    ctx.accounts
+        .campaign
+        .check_deposits_open(current_timestamp)?;
+
+    let (lockup_period, _) = ctx
+        .accounts
+        .campaign
+        .lockup_tier(lockup_tier)
+        .ok_or(LIPError::InvalidLockupTier)?;
 
     require_gte!(
         ctx.accounts.campaign.remaining_capacity,
@@ -33,6 +48,22 @@ pub fn process(ctx: Context<CreateDeposit>, amount: u64) -> Result<()> {
 
     require_gt!(amount, 0);
 
+    let total_user_deposits = ctx
+        .accounts
+        .user_deposits
+        .total_deposits
+        .checked_add(amount)
+        .unwrap();
+
+    let max_deposit_per_user = ctx.accounts.campaign.max_deposit_per_user;
+    if max_deposit_per_user > 0 {
+        require_gte!(
+            max_deposit_per_user,
+            total_user_deposits,
+            LIPError::UserDepositCapExceeded
+        );
+    }
+
     msg!("User depositing {} tokens", amount);
 
     transfer(
@@ -53,24 +84,27 @@ pub fn process(ctx: Context<CreateDeposit>, amount: u64) -> Result<()> {
         &[*ctx.bumps.get("mfi_pda_signer").unwrap()],
     ];
 
-    marginfi::cpi::marginfi_account_initialize(CpiContext::new_with_signer(
-        ctx.accounts.marginfi_program.to_account_info(),
-        marginfi::cpi::accounts::MarginfiAccountInitialize {
-            marginfi_group: ctx.accounts.marginfi_group.to_account_info(),
-            authority: ctx.accounts.mfi_pda_signer.to_account_info(),
-            marginfi_account: ctx.accounts.marginfi_account.to_account_info(),
-            system_program: ctx.accounts.system_program.to_account_info(),
-            fee_payer: ctx.accounts.signer.to_account_info(),
-        },
-        &[
-            mfi_signer_seeds,
+    marginfi::cpi::marginfi_account_initialize(
+        CpiContext::new_with_signer(
+            ctx.accounts.marginfi_program.to_account_info(),
+            marginfi::cpi::accounts::MarginfiAccountInitialize {
+                marginfi_group: ctx.accounts.marginfi_group.to_account_info(),
+                authority: ctx.accounts.mfi_pda_signer.to_account_info(),
+                marginfi_account: ctx.accounts.marginfi_account.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+                fee_payer: ctx.accounts.signer.to_account_info(),
+            },
             &[
-                MARGINFI_ACCOUNT_SEED.as_bytes(),
-                &ctx.accounts.deposit.key().to_bytes(),
-                &[*ctx.bumps.get("marginfi_account").unwrap()],
+                mfi_signer_seeds,
+                &[
+                    MARGINFI_ACCOUNT_SEED.as_bytes(),
+                    &ctx.accounts.deposit.key().to_bytes(),
+                    &[*ctx.bumps.get("marginfi_account").unwrap()],
+                ],
             ],
-        ],
-    ))?;
+        ),
+        None,
+    )?;
 
     marginfi::cpi::lending_account_deposit(
         CpiContext::new_with_signer(
@@ -103,8 +137,10 @@ pub fn process(ctx: Context<CreateDeposit>, amount: u64) -> Result<()> {
         owner: ctx.accounts.signer.key(),
         campaign: ctx.accounts.campaign.key(),
         amount,
-        start_time: Clock::get()?.unix_timestamp,
-        _padding: [0; 16],
+        start_time: current_timestamp,
+        lockup_tier,
+        lockup_period,
+        _padding: [0; 14],
     });
 
     ctx.accounts.campaign.remaining_capacity = ctx
@@ -114,6 +150,11 @@ pub fn process(ctx: Context<CreateDeposit>, amount: u64) -> Result<()> {
         .checked_sub(amount)
         .unwrap();
 
+    let user_deposits = &mut ctx.accounts.user_deposits;
+    user_deposits.campaign = ctx.accounts.campaign.key();
+    user_deposits.owner = ctx.accounts.signer.key();
+    user_deposits.total_deposits = total_user_deposits;
+
     Ok(())
 }
 
@@ -132,6 +173,19 @@ pub struct CreateDeposit<'info> {
     )]
     pub deposit: Box<Account<'info, Deposit>>,
 
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = size_of::<UserDeposits>() + 8,
+        seeds = [
+            USER_DEPOSITS_SEED.as_bytes(),
+            campaign.key().as_ref(),
+            signer.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub user_deposits: Box<Account<'info, UserDeposits>>,
+
     #[account(
         seeds = [
             DEPOSIT_MFI_AUTH_SIGNER_SEED.as_bytes(),
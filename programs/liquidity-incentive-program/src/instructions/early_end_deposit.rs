@@ -0,0 +1,102 @@
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+
+use crate::{
+    constants::MAX_BPS,
+    errors::LIPError,
+    instructions::end_deposit::{settle_deposit, EndDeposit},
+};
+
+/// Before the lockup period of its tier has ended, closes a deposit and returns the initial deposit + marginfi yield to the liquidity depositor.
+///
+/// The deposit earns `early_withdrawal_reward_bps` of its tier rewards pro-rated over the elapsed lockup,
+/// the forfeited rewards are returned to the campaign remaining capacity.
+///
+/// # Arguments
+/// * ctx: Context of the deposit to be closed
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the deposit is closed and tokens are transferred successfully.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The lockup period has already been reached, `end_deposit` must be used instead
+/// * Bank redeem shares operation fails
+/// * Transferring the earned reward or the deposit to the depositor fails
+/// * The campaign bank has emissions, and the emissions accounts are missing
+pub fn process<'info>(ctx: Context<'_, '_, '_, 'info, EndDeposit<'info>>) -> Result<()> {
+    let current_timestamp = Clock::get()?.unix_timestamp;
+    let lockup_tier = ctx.accounts.deposit.lockup_tier;
+    let lockup_period = ctx
+        .accounts
+        .deposit
+        .lockup_period(&ctx.accounts.campaign)
+        .ok_or(LIPError::InvalidLockupTier)?;
+    let deposit_amount = ctx.accounts.deposit.amount;
+
+    let elapsed = current_timestamp.saturating_sub(ctx.accounts.deposit.start_time);
+
+    require_gt!(
+        lockup_period as i64,
+        elapsed,
+        LIPError::DepositAlreadyMature
+    );
+
+    let max_reward_for_deposit = ctx
+        .accounts
+        .campaign
+        .max_reward_for_tier_deposit(deposit_amount, lockup_tier)
+        .unwrap();
+
+    let reward_for_deposit: u64 = I80F48::from_num(max_reward_for_deposit)
+        .checked_mul(I80F48::from_num(elapsed.max(0)))
+        .unwrap()
+        .checked_div(I80F48::from_num(lockup_period))
+        .unwrap()
+        .checked_mul(I80F48::from_num(
+            ctx.accounts.campaign.early_withdrawal_reward_bps,
+        ))
+        .unwrap()
+        .checked_div(I80F48::from_num(MAX_BPS))
+        .unwrap()
+        .checked_to_num()
+        .unwrap();
+
+    // Capacity backed by the forfeited rewards can be filled by new deposits
+    let returned_capacity: u64 = if max_reward_for_deposit == 0 {
+        deposit_amount
+    } else {
+        I80F48::from_num(deposit_amount)
+            .checked_mul(I80F48::from_num(
+                max_reward_for_deposit - reward_for_deposit,
+            ))
+            .unwrap()
+            .checked_div(I80F48::from_num(max_reward_for_deposit))
+            .unwrap()
+            .checked_to_num()
+            .unwrap()
+    };
+
+    msg!(
+        "Early withdrawal after {}s of {}s lockup, reward: {} of {}, returned capacity: {}",
+        elapsed,
+        lockup_period,
+        reward_for_deposit,
+        max_reward_for_deposit,
+        returned_capacity
+    );
+
+    let campaign = &mut ctx.accounts.campaign;
+
+    campaign.remaining_capacity = campaign
+        .remaining_capacity
+        .checked_add(returned_capacity)
+        .unwrap();
+    campaign.exited_deposits = campaign
+        .exited_deposits
+        .checked_add(deposit_amount - returned_capacity)
+        .unwrap();
+
+    settle_deposit(ctx, reward_for_deposit)
+}
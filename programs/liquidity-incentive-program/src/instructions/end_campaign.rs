@@ -0,0 +1,113 @@
+use crate::{
+    constants::{CAMPAIGN_AUTH_SEED, CAMPAIGN_SEED},
+    state::Campaign,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{close_account, transfer, CloseAccount, Token, TokenAccount, Transfer};
+
+/// Deactivates a liquidity incentive campaign (LIP), and once every deposit has been closed,
+/// sweeps the leftover rewards to the admin and closes the reward vault.
+///
+/// # Arguments
+/// * ctx: Context of the campaign to be ended
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the campaign is deactivated, and the rewards swept when no deposits are outstanding.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the campaign admin
+/// * Transferring the leftover rewards or closing the reward vault fails
+pub fn process(ctx: Context<EndCampaign>) -> Result<()> {
+    ctx.accounts.campaign.active = false;
+
+    let outstanding_deposits = ctx.accounts.campaign.outstanding_deposits();
+
+    if outstanding_deposits > 0 {
+        msg!(
+            "Campaign deactivated, {} tokens of deposits outstanding, rewards can be swept once they are closed",
+            outstanding_deposits
+        );
+
+        return Ok(());
+    }
+
+    let leftover_rewards = ctx.accounts.campaign_reward_vault.amount;
+
+    msg!("Sweeping {} reward tokens to admin", leftover_rewards);
+
+    let signer_seeds: &[&[u8]] = &[
+        CAMPAIGN_AUTH_SEED.as_bytes(),
+        &ctx.accounts.campaign.key().to_bytes(),
+        &[*ctx.bumps.get("campaign_reward_vault_authority").unwrap()],
+    ];
+
+    if leftover_rewards > 0 {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.campaign_reward_vault.to_account_info(),
+                    to: ctx.accounts.destination_account.to_account_info(),
+                    authority: ctx
+                        .accounts
+                        .campaign_reward_vault_authority
+                        .to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            leftover_rewards,
+        )?;
+    }
+
+    close_account(CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        CloseAccount {
+            account: ctx.accounts.campaign_reward_vault.to_account_info(),
+            destination: ctx.accounts.admin.to_account_info(),
+            authority: ctx
+                .accounts
+                .campaign_reward_vault_authority
+                .to_account_info(),
+        },
+        &[signer_seeds],
+    ))?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct EndCampaign<'info> {
+    #[account(mut, has_one = admin)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(
+        mut,
+        seeds = [
+            CAMPAIGN_SEED.as_bytes(),
+            campaign.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub campaign_reward_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [
+            CAMPAIGN_AUTH_SEED.as_bytes(),
+            campaign.key().as_ref(),
+        ],
+        bump,
+    )]
+    /// CHECK: Asserted by PDA derivation
+    pub campaign_reward_vault_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    /// CHECK: Asserted by token transfer
+    pub destination_account: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -1,6 +1,5 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{close_account, transfer, Token, TokenAccount, Transfer};
-use fixed::types::I80F48;
 use marginfi::{program::Marginfi, state::marginfi_group::Bank};
 
 use crate::{
@@ -9,6 +8,7 @@ use crate::{
         TEMP_TOKEN_ACCOUNT_AUTH_SEED,
     },
     errors::LIPError,
+    instructions::claim_deposit_emissions::withdraw_deposit_emissions,
     state::{Campaign, Deposit},
 };
 
@@ -23,22 +23,90 @@ use crate::{
 /// # Errors
 /// Returns an error if:
 ///
-/// * Solana clock timestamp is less than the deposit start time plus the lockup period (i.e. the lockup has not been reached)
+/// * Solana clock timestamp is less than the deposit start time plus the lockup period of its tier (i.e. the lockup has not been reached)
 /// * Bank redeem shares operation fails
 /// * Reloading ephemeral token account fails
 /// * Transferring additional reward to ephemeral token account fails
 /// * Reloading ephemeral token account after transfer fails
-pub fn process(ctx: Context<EndDeposit>) -> Result<()> {
+/// * The campaign bank has emissions, and the emissions accounts are missing
+pub fn process<'info>(ctx: Context<'_, '_, '_, 'info, EndDeposit<'info>>) -> Result<()> {
+    let lockup_tier = ctx.accounts.deposit.lockup_tier;
+    let lockup_period = ctx
+        .accounts
+        .deposit
+        .lockup_period(&ctx.accounts.campaign)
+        .ok_or(LIPError::InvalidLockupTier)?;
+
     // Solana clock isn't the most precise, but an offset of a few hours on a half year lockup is fine
     //
     // Check if the lockup period has passed
     require_gte!(
         Clock::get()?.unix_timestamp,
         // Skipping checked math here as numbers should be small enough to not overflow
-        ctx.accounts.deposit.start_time + ctx.accounts.campaign.lockup_period as i64,
+        ctx.accounts.deposit.start_time + lockup_period as i64,
         LIPError::DepositNotMature
     );
 
+    let reward_for_deposit = ctx
+        .accounts
+        .campaign
+        .max_reward_for_tier_deposit(ctx.accounts.deposit.amount, lockup_tier)
+        .unwrap();
+
+    ctx.accounts.campaign.exited_deposits = ctx
+        .accounts
+        .campaign
+        .exited_deposits
+        .checked_add(ctx.accounts.deposit.amount)
+        .unwrap();
+
+    settle_deposit(ctx, reward_for_deposit)
+}
+
+/// Withdraws the deposit from marginfi, tops up the marginfi yield to `reward_for_deposit`
+/// from the reward vault, and transfers everything to the depositor.
+///
+/// If the campaign bank has emissions, the emissions accrued by the deposit are forwarded
+/// to the depositor as well, `remaining_accounts` must then be
+/// `[emissions_mint, emissions_auth, emissions_vault, emissions_destination_account]`.
+pub(crate) fn settle_deposit<'info>(
+    ctx: Context<'_, '_, '_, 'info, EndDeposit<'info>>,
+    reward_for_deposit: u64,
+) -> Result<()> {
+    // A balance with outstanding emissions can't be withdrawn in full
+    let emissions_mint = ctx.accounts.marginfi_bank.load()?.emissions_mint;
+    if emissions_mint != Pubkey::default() {
+        let (emissions_mint, emissions_auth, emissions_vault, emissions_destination_account) =
+            match ctx.remaining_accounts {
+                [emissions_mint, emissions_auth, emissions_vault, emissions_destination_account, ..] => {
+                    (
+                        emissions_mint,
+                        emissions_auth,
+                        emissions_vault,
+                        emissions_destination_account,
+                    )
+                }
+                _ => return err!(LIPError::MissingEmissionsAccounts),
+            };
+
+        withdraw_deposit_emissions(
+            ctx.accounts.marginfi_program.to_account_info(),
+            marginfi::cpi::accounts::LendingAccountWithdrawEmissions {
+                marginfi_group: ctx.accounts.marginfi_group.to_account_info(),
+                marginfi_account: ctx.accounts.marginfi_account.to_account_info(),
+                signer: ctx.accounts.mfi_pda_signer.to_account_info(),
+                bank: ctx.accounts.marginfi_bank.to_account_info(),
+                emissions_mint: emissions_mint.clone(),
+                emissions_auth: emissions_auth.clone(),
+                emissions_vault: emissions_vault.clone(),
+                destination_account: emissions_destination_account.clone(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+            },
+            ctx.accounts.deposit.key(),
+            *ctx.bumps.get("mfi_pda_signer").unwrap(),
+        )?;
+    }
+
     marginfi::cpi::lending_account_withdraw(
         CpiContext::new_with_signer(
             ctx.accounts.marginfi_program.to_account_info(),
@@ -76,25 +144,13 @@ pub fn process(ctx: Context<EndDeposit>) -> Result<()> {
 
         let base_yield = end_deposit.saturating_sub(initial_deposit);
 
-        let max_rewards_pre_campaign = I80F48::from_num(ctx.accounts.campaign.max_rewards);
-        let max_deposits_pre_campaign = I80F48::from_num(ctx.accounts.campaign.max_deposits);
-        let deposit_amount = I80F48::from_num(ctx.accounts.deposit.amount);
-
-        let max_reward_for_deposit = deposit_amount
-            .checked_div(max_deposits_pre_campaign)
-            .unwrap()
-            .checked_mul(max_rewards_pre_campaign)
-            .unwrap()
-            .checked_to_num::<u64>()
-            .unwrap();
-
         msg!(
-            "Base yield: {}, max reward for deposit: {}",
+            "Base yield: {}, reward for deposit: {}",
             base_yield,
-            max_reward_for_deposit
+            reward_for_deposit
         );
 
-        max_reward_for_deposit.saturating_sub(base_yield)
+        reward_for_deposit.saturating_sub(base_yield)
     };
 
     msg!("Additional reward amount: {}", additional_reward_amount);
@@ -167,7 +223,7 @@ pub fn process(ctx: Context<EndDeposit>) -> Result<()> {
 
 #[derive(Accounts)]
 pub struct EndDeposit<'info> {
-    #[account(address = deposit.campaign)]
+    #[account(mut, address = deposit.campaign)]
     pub campaign: Box<Account<'info, Campaign>>,
 
     #[account(
@@ -1,7 +1,23 @@
+pub mod claim_deposit_emissions;
 pub mod create_campaign;
 pub mod create_deposit;
+pub mod early_end_deposit;
+pub mod end_campaign;
 pub mod end_deposit;
+pub mod pause_campaign;
+pub mod reconcile_exited_deposits;
+pub mod resume_campaign;
+pub mod transfer_deposit;
+pub mod update_campaign;
 
+pub use claim_deposit_emissions::*;
 pub use create_campaign::*;
 pub use create_deposit::*;
+pub use early_end_deposit::*;
+pub use end_campaign::*;
 pub use end_deposit::*;
+pub use pause_campaign::*;
+pub use reconcile_exited_deposits::*;
+pub use resume_campaign::*;
+pub use transfer_deposit::*;
+pub use update_campaign::*;
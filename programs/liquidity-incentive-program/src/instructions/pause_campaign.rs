@@ -0,0 +1,36 @@
+use crate::{errors::LIPError, state::Campaign};
+use anchor_lang::prelude::*;
+
+/// Pauses an active liquidity incentive campaign (LIP), halting new deposits until the admin
+/// resumes it, e.g. during an incident. Outstanding deposits can still be closed.
+///
+/// # Arguments
+/// * ctx: Context of the campaign to be paused
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the campaign is paused.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the campaign admin
+/// * The campaign has been ended
+pub fn process(ctx: Context<PauseCampaign>) -> Result<()> {
+    let campaign = &mut ctx.accounts.campaign;
+
+    require!(campaign.active, LIPError::CampaignNotActive);
+
+    campaign.paused = true;
+
+    msg!("Campaign paused");
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct PauseCampaign<'info> {
+    #[account(mut, has_one = admin)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    pub admin: Signer<'info>,
+}
@@ -0,0 +1,86 @@
+use crate::{
+    errors::LIPError,
+    state::{Campaign, Deposit},
+};
+use anchor_lang::prelude::*;
+use std::collections::BTreeSet;
+
+/// Recomputes the deposits exited from an ended liquidity incentive campaign (LIP),
+/// from the deposits of the campaign still open.
+///
+/// Deposits closed before `exited_deposits` was tracked are otherwise counted as outstanding,
+/// and `end_campaign` never sweeps the leftover rewards. Ended campaigns don't accept new deposits,
+/// so the open deposits passed by the admin are the only ones the reward vault still backs.
+/// Open deposits left out would be counted as exited, the admin is trusted to pass all of them.
+///
+/// # Arguments
+/// * ctx: Context of the campaign to be reconciled, with every open deposit of the campaign
+///   as remaining accounts
+///
+/// # Returns
+/// * A Result object which is Ok(()) if `exited_deposits` is reconciled.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the campaign admin
+/// * The campaign is still active
+/// * A remaining account is not a deposit of the campaign, or is passed twice
+/// * The open deposits exceed the deposits of the campaign not tracked as exited
+pub fn process(ctx: Context<ReconcileExitedDeposits>) -> Result<()> {
+    let campaign = &ctx.accounts.campaign;
+
+    require!(!campaign.active, LIPError::CampaignActive);
+
+    let mut deposit_keys = BTreeSet::new();
+    let mut open_deposits: u64 = 0;
+
+    for deposit_ai in ctx.remaining_accounts {
+        let deposit = Account::<Deposit>::try_from(deposit_ai)?;
+
+        require_keys_eq!(
+            deposit.campaign,
+            campaign.key(),
+            LIPError::InvalidDepositAccounts
+        );
+        require!(
+            deposit_keys.insert(deposit_ai.key()),
+            LIPError::InvalidDepositAccounts
+        );
+
+        open_deposits = open_deposits
+            .checked_add(deposit.amount)
+            .ok_or(LIPError::InvalidDepositAccounts)?;
+    }
+
+    // Closed deposits already tracked can't be counted as open again
+    require_gte!(
+        campaign.outstanding_deposits(),
+        open_deposits,
+        LIPError::InvalidDepositAccounts
+    );
+
+    let exited_deposits = campaign
+        .max_deposits
+        .saturating_sub(campaign.remaining_capacity)
+        - open_deposits;
+
+    msg!(
+        "Open deposits: {}, exited deposits: {} -> {}",
+        open_deposits,
+        campaign.exited_deposits,
+        exited_deposits
+    );
+
+    ctx.accounts.campaign.exited_deposits = exited_deposits;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ReconcileExitedDeposits<'info> {
+    #[account(mut, has_one = admin)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    pub admin: Signer<'info>,
+}
@@ -0,0 +1,37 @@
+use crate::{errors::LIPError, state::Campaign};
+use anchor_lang::prelude::*;
+
+/// Resumes the deposits of a paused liquidity incentive campaign (LIP).
+///
+/// The deposit deadline of the campaign, if any, still applies.
+///
+/// # Arguments
+/// * ctx: Context of the campaign to be resumed
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the campaign is resumed.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the campaign admin
+/// * The campaign has been ended
+pub fn process(ctx: Context<ResumeCampaign>) -> Result<()> {
+    let campaign = &mut ctx.accounts.campaign;
+
+    require!(campaign.active, LIPError::CampaignNotActive);
+
+    campaign.paused = false;
+
+    msg!("Campaign resumed");
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ResumeCampaign<'info> {
+    #[account(mut, has_one = admin)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    pub admin: Signer<'info>,
+}
@@ -0,0 +1,44 @@
+use crate::state::Deposit;
+use anchor_lang::prelude::*;
+
+/// Transfers the ownership of a deposit to a new wallet, before or after the end of its lockup.
+///
+/// The new owner closes the deposit and receives the initial deposit + rewards.
+/// Transferred deposits don't count towards the per-user cap of the new owner.
+///
+/// # Arguments
+/// * ctx: Context of the deposit to be transferred
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the deposit owner is updated.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the deposit owner
+/// * The new owner is the current owner
+pub fn process(ctx: Context<TransferDeposit>) -> Result<()> {
+    require_keys_neq!(ctx.accounts.new_owner.key(), ctx.accounts.owner.key());
+
+    msg!(
+        "Transferring deposit {} from {} to {}",
+        ctx.accounts.deposit.key(),
+        ctx.accounts.owner.key(),
+        ctx.accounts.new_owner.key()
+    );
+
+    ctx.accounts.deposit.owner = ctx.accounts.new_owner.key();
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct TransferDeposit<'info> {
+    #[account(mut, has_one = owner)]
+    pub deposit: Box<Account<'info, Deposit>>,
+
+    pub owner: Signer<'info>,
+
+    /// CHECK: Any wallet can own a deposit
+    pub new_owner: AccountInfo<'info>,
+}
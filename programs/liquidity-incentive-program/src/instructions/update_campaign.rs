@@ -0,0 +1,147 @@
+use crate::{
+    constants::{CAMPAIGN_SEED, MAX_CAMPAIGN_APR, MIN_CAMPAIGN_APR},
+    errors::LIPError,
+    state::{calc_campaign_apr, Campaign},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Token, TokenAccount, Transfer};
+
+/// Tops up the rewards of an active liquidity incentive campaign (LIP), and optionally raises
+/// its deposit capacity or extends its lockup period.
+///
+/// The lockup extension only applies to new deposits, outstanding ones keep the lockup period
+/// recorded at their creation, see `Deposit::lockup_period`. Deposits created before it was recorded
+/// keep the lockup period preceding the first extension, recorded in `original_lockup_period`.
+///
+/// Rewards are computed pro-rata of `max_rewards / max_deposits`, the update must not lower that
+/// rate so that outstanding deposits keep the rewards they were promised.
+///
+/// # Arguments
+/// * ctx: Context of the campaign to be updated
+/// * additional_rewards: Amount of reward tokens transferred from the funding account into the reward vault
+/// * max_deposits: New deposit capacity of the campaign, must not be lower than the current one
+/// * lockup_period: New lockup period of the campaign, must not be shorter than the current one
+///
+/// # Returns
+/// * A Result object which is Ok(()) if the campaign is updated.
+///
+/// # Errors
+/// Returns an error if:
+///
+/// * The signer is not the campaign admin
+/// * The campaign is not active
+/// * The capacity is reduced or the lockup period shortened
+/// * The rewards per deposited token would decrease
+/// * The resulting APR of the campaign or of one of its lockup tiers is out of bounds
+pub fn process(
+    ctx: Context<UpdateCampaign>,
+    additional_rewards: u64,
+    max_deposits: Option<u64>,
+    lockup_period: Option<u64>,
+) -> Result<()> {
+    let campaign = &ctx.accounts.campaign;
+
+    require!(campaign.active, LIPError::CampaignNotActive);
+
+    let new_max_rewards = campaign
+        .max_rewards
+        .checked_add(additional_rewards)
+        .ok_or(LIPError::InvalidCampaignParameters)?;
+    let new_max_deposits = max_deposits.unwrap_or(campaign.max_deposits);
+    let new_lockup_period = lockup_period.unwrap_or(campaign.lockup_period);
+
+    require_gte!(
+        new_max_deposits,
+        campaign.max_deposits,
+        LIPError::InvalidCampaignParameters
+    );
+    require_gte!(
+        new_lockup_period,
+        campaign.lockup_period,
+        LIPError::InvalidCampaignParameters
+    );
+
+    // new_max_rewards / new_max_deposits >= max_rewards / max_deposits
+    require!(
+        new_max_rewards as u128 * campaign.max_deposits as u128
+            >= campaign.max_rewards as u128 * new_max_deposits as u128,
+        LIPError::CampaignRewardsBelowCommitted
+    );
+
+    let apr = calc_campaign_apr(new_max_rewards, new_max_deposits, new_lockup_period)
+        .ok_or(LIPError::InvalidCampaignParameters)?;
+
+    msg!("Campaign APR: {}", apr);
+
+    require!(
+        (MIN_CAMPAIGN_APR..=MAX_CAMPAIGN_APR).contains(&apr),
+        LIPError::CampaignAprOutOfBounds
+    );
+
+    for tier in campaign
+        .lockup_tiers
+        .iter()
+        .filter(|tier| tier.lockup_period > 0)
+    {
+        let tier_apr = tier
+            .apr(new_max_rewards, new_max_deposits)
+            .ok_or(LIPError::InvalidLockupTier)?;
+
+        require!(
+            (MIN_CAMPAIGN_APR..=MAX_CAMPAIGN_APR).contains(&tier_apr),
+            LIPError::CampaignAprOutOfBounds
+        );
+    }
+
+    if additional_rewards > 0 {
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.funding_account.to_account_info(),
+                    to: ctx.accounts.campaign_reward_vault.to_account_info(),
+                    authority: ctx.accounts.admin.to_account_info(),
+                },
+            ),
+            additional_rewards,
+        )?;
+    }
+
+    let campaign = &mut ctx.accounts.campaign;
+
+    campaign.remaining_capacity += new_max_deposits - campaign.max_deposits;
+    campaign.max_deposits = new_max_deposits;
+    campaign.max_rewards = new_max_rewards;
+
+    if new_lockup_period > campaign.lockup_period && campaign.original_lockup_period == 0 {
+        campaign.original_lockup_period = campaign.lockup_period;
+    }
+    campaign.lockup_period = new_lockup_period;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateCampaign<'info> {
+    #[account(mut, has_one = admin)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(
+        mut,
+        seeds = [
+            CAMPAIGN_SEED.as_bytes(),
+            campaign.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub campaign_reward_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(mut)]
+    /// CHECK: Asserted by token transfer
+    pub funding_account: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
 use instructions::*;
+use state::LockupTier;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "mainnet-beta")] {
@@ -27,16 +28,34 @@ pub mod liquidity_incentive_program {
     /// * `lockup_period`: The length of time (in seconds) that a deposit must be locked up for in order to earn the full reward.
     /// * `max_deposits`: The maximum number of tokens that can be deposited into the campaign by liquidity providers.
     /// * `max_rewards`: The maximum amount of rewards that will be distributed to depositors, and also the amount of token rewards transferred into the vault by the campaign creator.
+    /// * `early_withdrawal_reward_bps`: The share (in basis points) of the pro-rated rewards kept by deposits closed before the end of the lockup, 0 forfeits all their rewards.
+    /// * `max_deposit_per_user`: The maximum number of tokens a single wallet can deposit into the campaign over its lifetime, 0 for no per-user cap.
+    /// * `lockup_tiers`: Up to `MAX_LOCKUP_TIERS` lockups shorter than `lockup_period` depositors can choose instead, each earning `reward_bps` of the full rewards.
+    /// * `deposit_deadline`: The timestamp after which the campaign stops accepting deposits, 0 to accept deposits until the campaign is ended.
     ///
     /// # Returns
     /// * `Ok(())` if the campaign was successfully created, or an error otherwise.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_campaign(
         ctx: Context<CreateCampaign>,
         lockup_period: u64,
         max_deposits: u64,
         max_rewards: u64,
+        early_withdrawal_reward_bps: u64,
+        max_deposit_per_user: u64,
+        lockup_tiers: Vec<LockupTier>,
+        deposit_deadline: i64,
     ) -> Result<()> {
-        create_campaign::process(ctx, lockup_period, max_deposits, max_rewards)
+        create_campaign::process(
+            ctx,
+            lockup_period,
+            max_deposits,
+            max_rewards,
+            early_withdrawal_reward_bps,
+            max_deposit_per_user,
+            lockup_tiers,
+            deposit_deadline,
+        )
     }
 
     /// Creates a new deposit in an active liquidity incentive campaign (LIP).
@@ -44,15 +63,23 @@ pub mod liquidity_incentive_program {
     /// # Arguments
     /// * `ctx`: Context struct containing the relevant accounts for the new deposit
     /// * `amount`: The amount of tokens to be deposited.
+    /// * `lockup_tier`: The lockup tier chosen for the deposit, 0 for the campaign lockup period with the full rewards.
     ///
     /// # Returns
     /// * `Ok(())` if the deposit was successfully made, or an error otherwise.
     ///
     /// # Errors
     /// * `LIPError::CampaignNotActive` if the relevant campaign is not active.
+    /// * `LIPError::CampaignPaused` if the campaign is paused.
+    /// * `LIPError::DepositDeadlinePassed` if the campaign deposit deadline has passed.
     /// * `LIPError::DepositAmountTooLarge` is the deposit amount exceeds the amount of remaining deposits that can be made into the campaign.
-    pub fn create_deposit(ctx: Context<CreateDeposit>, amount: u64) -> Result<()> {
-        instructions::create_deposit::process(ctx, amount)
+    /// * `LIPError::InvalidLockupTier` if the lockup tier isn't set on the campaign.
+    pub fn create_deposit(
+        ctx: Context<CreateDeposit>,
+        amount: u64,
+        lockup_tier: u64,
+    ) -> Result<()> {
+        instructions::create_deposit::process(ctx, amount, lockup_tier)
     }
 
     /// After a lockup period has ended, closes a deposit and returns the initial deposit + earned rewards from a liquidity incentive campaign back to the liquidity depositor.
@@ -71,7 +98,142 @@ pub mod liquidity_incentive_program {
     /// * Reloading ephemeral token account fails
     /// * Transferring additional reward to ephemeral token account fails
     /// * Reloading ephemeral token account after transfer fails
-    pub fn end_deposit(ctx: Context<EndDeposit>) -> Result<()> {
+    /// * `LIPError::MissingEmissionsAccounts` if the campaign bank has emissions and the emissions accounts
+    ///   `[emissions_mint, emissions_auth, emissions_vault, emissions_destination_account]` aren't passed as remaining accounts.
+    pub fn end_deposit<'info>(ctx: Context<'_, '_, '_, 'info, EndDeposit<'info>>) -> Result<()> {
         instructions::end_deposit::process(ctx)
     }
+
+    /// Before a lockup period has ended, closes a deposit and returns the initial deposit + marginfi yield back to the liquidity depositor.
+    /// The depositor keeps `early_withdrawal_reward_bps` of the rewards pro-rated over the elapsed lockup, the rest is forfeited back to the campaign remaining capacity.
+    ///
+    /// # Arguments
+    /// * ctx: Context of the deposit to be closed
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the deposit is closed and tokens are transferred successfully.
+    ///
+    /// # Errors
+    /// * `LIPError::DepositAlreadyMature` if the lockup period has been reached, `end_deposit` must be used instead.
+    /// * `LIPError::MissingEmissionsAccounts` if the campaign bank has emissions and the emissions accounts aren't passed, as in `end_deposit`.
+    pub fn early_end_deposit<'info>(
+        ctx: Context<'_, '_, '_, 'info, EndDeposit<'info>>,
+    ) -> Result<()> {
+        instructions::early_end_deposit::process(ctx)
+    }
+
+    /// Claims the marginfi bank emissions accrued by the principal of a deposit, which sits in a marginfi account owned by the program.
+    ///
+    /// # Arguments
+    /// * ctx: Context of the deposit whose emissions are claimed
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the emissions are transferred to the destination account.
+    ///
+    /// # Errors
+    /// * The signer is not the deposit owner, or the emissions accounts don't match the campaign bank.
+    pub fn claim_deposit_emissions(ctx: Context<ClaimDepositEmissions>) -> Result<()> {
+        instructions::claim_deposit_emissions::process(ctx)
+    }
+
+    /// Transfers the ownership of a deposit to a new wallet, which can then close it once the lockup has ended.
+    ///
+    /// # Arguments
+    /// * ctx: Context of the deposit to be transferred
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the deposit owner is updated.
+    ///
+    /// # Errors
+    /// * Anchor `ConstraintHasOne` if the signer is not the deposit owner.
+    pub fn transfer_deposit(ctx: Context<TransferDeposit>) -> Result<()> {
+        instructions::transfer_deposit::process(ctx)
+    }
+
+    /// Deactivates a liquidity incentive campaign (LIP), no new deposits can be made into it.
+    /// Once all deposits have been closed, sweeps the rewards left in the reward vault to the admin and closes the vault.
+    ///
+    /// Deposits outstanding when the campaign is deactivated keep their guaranteed rewards,
+    /// the admin calls `end_campaign` again after they are closed to sweep the leftover.
+    ///
+    /// # Arguments
+    /// * ctx: Context of the campaign to be ended
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the campaign is deactivated.
+    pub fn end_campaign(ctx: Context<EndCampaign>) -> Result<()> {
+        instructions::end_campaign::process(ctx)
+    }
+
+    /// Recomputes the deposits exited from an ended liquidity incentive campaign (LIP) from its open deposits,
+    /// so that deposits closed before exits were tracked don't keep `end_campaign` from sweeping the leftover rewards.
+    ///
+    /// # Arguments
+    /// * ctx: Context of the campaign to be reconciled, with every open deposit of the campaign as remaining accounts
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the exited deposits are reconciled.
+    ///
+    /// # Errors
+    /// * `LIPError::CampaignActive` if the campaign hasn't been ended.
+    /// * `LIPError::InvalidDepositAccounts` if a remaining account isn't a distinct deposit of the campaign,
+    ///   or the open deposits exceed the outstanding deposits of the campaign.
+    pub fn reconcile_exited_deposits(ctx: Context<ReconcileExitedDeposits>) -> Result<()> {
+        instructions::reconcile_exited_deposits::process(ctx)
+    }
+
+    /// Pauses an active liquidity incentive campaign (LIP), no new deposits can be made into it until it is resumed.
+    /// Outstanding deposits can still be closed.
+    ///
+    /// # Arguments
+    /// * ctx: Context of the campaign to be paused
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the campaign is paused.
+    ///
+    /// # Errors
+    /// * `LIPError::CampaignNotActive` if the campaign has been ended.
+    pub fn pause_campaign(ctx: Context<PauseCampaign>) -> Result<()> {
+        instructions::pause_campaign::process(ctx)
+    }
+
+    /// Resumes the deposits of a paused liquidity incentive campaign (LIP).
+    ///
+    /// # Arguments
+    /// * ctx: Context of the campaign to be resumed
+    ///
+    /// # Returns
+    /// * A Result object which is Ok(()) if the campaign is resumed.
+    ///
+    /// # Errors
+    /// * `LIPError::CampaignNotActive` if the campaign has been ended.
+    pub fn resume_campaign(ctx: Context<ResumeCampaign>) -> Result<()> {
+        instructions::resume_campaign::process(ctx)
+    }
+
+    /// Tops up the rewards of an active liquidity incentive campaign (LIP), and optionally raises its deposit capacity or extends its lockup period.
+    /// The lockup extension only applies to new deposits, outstanding ones keep the lockup they were created with.
+    ///
+    /// # Arguments
+    /// * `ctx`: Context of the campaign to be updated
+    /// * `additional_rewards`: The amount of reward tokens transferred into the vault by the campaign admin.
+    /// * `max_deposits`: The new maximum number of tokens that can be deposited into the campaign, `None` keeps the current one.
+    /// * `lockup_period`: The new lockup period (in seconds), `None` keeps the current one.
+    ///
+    /// # Returns
+    /// * `Ok(())` if the campaign was successfully updated, or an error otherwise.
+    ///
+    /// # Errors
+    /// * `LIPError::CampaignNotActive` if the campaign is not active.
+    /// * `LIPError::InvalidCampaignParameters` if the capacity is reduced or the lockup period shortened.
+    /// * `LIPError::CampaignRewardsBelowCommitted` if the rewards per deposited token would decrease.
+    /// * `LIPError::CampaignAprOutOfBounds` if the resulting APR is out of bounds.
+    pub fn update_campaign(
+        ctx: Context<UpdateCampaign>,
+        additional_rewards: u64,
+        max_deposits: Option<u64>,
+        lockup_period: Option<u64>,
+    ) -> Result<()> {
+        instructions::update_campaign::process(ctx, additional_rewards, max_deposits, lockup_period)
+    }
 }
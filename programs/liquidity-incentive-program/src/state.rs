@@ -1,4 +1,11 @@
 use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use marginfi::constants::SECONDS_PER_YEAR;
+
+use crate::{
+    constants::{MAX_BPS, MAX_LOCKUP_TIERS},
+    errors::LIPError,
+};
 
 #[account]
 #[derive(Debug)]
@@ -10,7 +17,139 @@ pub struct Campaign {
     pub remaining_capacity: u64,
     pub max_rewards: u64,
     pub marginfi_bank_pk: Pubkey,
-    pub _padding: [u64; 16],
+    /// Deposit capacity used by closed deposits,
+    /// early withdrawals return the capacity backed by their forfeited rewards instead.
+    pub exited_deposits: u64,
+    /// Share of the pro-rated rewards kept by deposits closed before the end of the lockup,
+    /// in basis points. 0 forfeits all rewards of early withdrawals.
+    pub early_withdrawal_reward_bps: u64,
+    /// Cap on the cumulative deposits of a single wallet, 0 if deposits are only capped
+    /// by `remaining_capacity`.
+    pub max_deposit_per_user: u64,
+    /// Shorter lockups depositors can choose instead of `lockup_period`, for a share of the rewards.
+    /// Unused tiers have a zero lockup period.
+    pub lockup_tiers: [LockupTier; MAX_LOCKUP_TIERS],
+    /// Timestamp after which the campaign stops accepting deposits,
+    /// 0 if deposits are accepted until the campaign is ended.
+    pub deposit_deadline: i64,
+    /// Set by the admin to halt new deposits, e.g. during an incident, until the campaign is resumed.
+    /// Outstanding deposits can still be closed.
+    pub paused: bool,
+    /// Lockup period of the campaign before its first extension, 0 if it was never extended.
+    /// Kept by the deposits created before their lockup period was recorded.
+    pub original_lockup_period: u64,
+    pub _padding: [u64; 2],
+}
+
+/// Alternative lockup of a campaign, earning `reward_bps` of the rewards of `lockup_period`
+/// deposits of the campaign.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockupTier {
+    pub lockup_period: u64,
+    pub reward_bps: u64,
+}
+
+impl LockupTier {
+    /// Guaranteed APR of deposits in the tier, see [`calc_campaign_apr`].
+    pub fn apr(&self, max_rewards: u64, max_deposits: u64) -> Option<I80F48> {
+        let tier_rewards = I80F48::from_num(max_rewards)
+            .checked_mul(I80F48::from_num(self.reward_bps))?
+            .checked_div(I80F48::from_num(MAX_BPS))?
+            .checked_to_num()?;
+
+        calc_campaign_apr(tier_rewards, max_deposits, self.lockup_period)
+    }
+}
+
+impl Campaign {
+    /// Amount of deposits that haven't been closed yet.
+    ///
+    /// Deposits closed before `exited_deposits` was tracked are counted until the admin
+    /// reconciles it from the open deposits, see `reconcile_exited_deposits`.
+    pub fn outstanding_deposits(&self) -> u64 {
+        self.max_deposits
+            .saturating_sub(self.remaining_capacity)
+            .saturating_sub(self.exited_deposits)
+    }
+
+    /// Rewards guaranteed to a deposit of `amount` held for the whole lockup period.
+    pub fn max_reward_for_deposit(&self, amount: u64) -> Option<u64> {
+        I80F48::from_num(amount)
+            .checked_div(I80F48::from_num(self.max_deposits))?
+            .checked_mul(I80F48::from_num(self.max_rewards))?
+            .checked_to_num()
+    }
+
+    /// Lockup period and reward share (in basis points) of `lockup_tier`,
+    /// 0 being the campaign `lockup_period` with the full rewards.
+    ///
+    /// Returns `None` if the tier isn't set.
+    pub fn lockup_tier(&self, lockup_tier: u64) -> Option<(u64, u64)> {
+        if lockup_tier == 0 {
+            return Some((self.lockup_period, MAX_BPS));
+        }
+
+        let tier = self.lockup_tiers.get(lockup_tier as usize - 1)?;
+
+        if tier.lockup_period == 0 {
+            return None;
+        }
+
+        Some((tier.lockup_period, tier.reward_bps))
+    }
+
+    /// Rewards guaranteed to a deposit of `amount` in `lockup_tier` held for the whole tier lockup.
+    pub fn max_reward_for_tier_deposit(&self, amount: u64, lockup_tier: u64) -> Option<u64> {
+        let (_, reward_bps) = self.lockup_tier(lockup_tier)?;
+
+        I80F48::from_num(self.max_reward_for_deposit(amount)?)
+            .checked_mul(I80F48::from_num(reward_bps))?
+            .checked_div(I80F48::from_num(MAX_BPS))?
+            .checked_to_num()
+    }
+
+    /// Checks that the campaign accepts new deposits at `current_timestamp`.
+    pub fn check_deposits_open(&self, current_timestamp: i64) -> Result<()> {
+        require!(self.active, LIPError::CampaignNotActive);
+        require!(!self.paused, LIPError::CampaignPaused);
+
+        if self.deposit_deadline > 0 {
+            require_gte!(
+                self.deposit_deadline,
+                current_timestamp,
+                LIPError::DepositDeadlinePassed
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Guaranteed APR of the campaign, see [`calc_campaign_apr`].
+    pub fn apr(&self) -> Option<I80F48> {
+        calc_campaign_apr(self.max_rewards, self.max_deposits, self.lockup_period)
+    }
+}
+
+/// Guaranteed APR earned by a campaign deposit, as a fraction (1 = 100%).
+///
+/// Every deposit is guaranteed `max_rewards / max_deposits` of its amount over `lockup_period`,
+/// annualized without compounding. This is the floor of what a depositor earns,
+/// the actual yield is higher when the marginfi lending yield exceeds the rewards.
+///
+/// Returns `None` if `max_deposits` or `lockup_period` is zero.
+pub fn calc_campaign_apr(
+    max_rewards: u64,
+    max_deposits: u64,
+    lockup_period: u64,
+) -> Option<I80F48> {
+    if max_deposits == 0 || lockup_period == 0 {
+        return None;
+    }
+
+    I80F48::from_num(max_rewards)
+        .checked_div(I80F48::from_num(max_deposits))?
+        .checked_mul(SECONDS_PER_YEAR)?
+        .checked_div(I80F48::from_num(lockup_period))
 }
 
 #[account]
@@ -19,5 +158,41 @@ pub struct Deposit {
     pub amount: u64,
     pub start_time: i64,
     pub campaign: Pubkey,
-    pub _padding: [u64; 16],
+    /// Lockup tier chosen by the depositor, see [`Campaign::lockup_tier`].
+    pub lockup_tier: u64,
+    /// Lockup period of the tier when the deposit was created, 0 for deposits created before
+    /// it was recorded, see [`Deposit::lockup_period`].
+    pub lockup_period: u64,
+    pub _padding: [u64; 14],
+}
+
+impl Deposit {
+    /// Lockup period of the deposit, fixed at its creation so that extending the campaign
+    /// lockup only applies to new deposits.
+    ///
+    /// Deposits created before the lockup period was recorded follow their campaign tier,
+    /// with the lockup period of the campaign before its first extension, see
+    /// [`Campaign::original_lockup_period`].
+    pub fn lockup_period(&self, campaign: &Campaign) -> Option<u64> {
+        if self.lockup_period > 0 {
+            return Some(self.lockup_period);
+        }
+
+        if self.lockup_tier == 0 && campaign.original_lockup_period > 0 {
+            return Some(campaign.original_lockup_period);
+        }
+
+        campaign
+            .lockup_tier(self.lockup_tier)
+            .map(|(lockup_period, _)| lockup_period)
+    }
+}
+
+/// Cumulative deposits of `owner` into `campaign`, checked against `max_deposit_per_user`.
+#[account]
+pub struct UserDeposits {
+    pub campaign: Pubkey,
+    pub owner: Pubkey,
+    pub total_deposits: u64,
+    pub _padding: [u64; 8],
 }
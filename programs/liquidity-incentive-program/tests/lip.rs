@@ -1,15 +1,23 @@
+use anchor_lang::error::ErrorCode;
 use anyhow::Result;
 use fixed::types::I80F48;
 use fixtures::{
-    assert_custom_error, native,
-    spl::{balance_of, TokenAccountFixture},
+    assert_anchor_error, assert_custom_error, native,
+    spl::{balance_of, MintFixture, TokenAccountFixture},
     test::{TestFixture, DEFAULT_SOL_TEST_BANK_CONFIG, DEFAULT_USDC_TEST_BANK_CONFIG},
     time,
     utils::lip::get_reward_vault_address,
 };
-use liquidity_incentive_program::errors::LIPError;
-use marginfi::assert_eq_with_tolerance;
+use liquidity_incentive_program::{
+    errors::LIPError,
+    state::{calc_campaign_apr, LockupTier},
+};
+use marginfi::{
+    assert_eq_with_tolerance,
+    constants::{EMISSIONS_FLAG_LENDING_ACTIVE, MIN_EMISSIONS_START_TIME},
+};
 use solana_program_test::tokio;
+use solana_sdk::{signature::Keypair, signer::Signer};
 
 #[tokio::test]
 async fn campaign_no_yield() -> Result<()> {
@@ -29,7 +37,7 @@ async fn campaign_no_yield() -> Result<()> {
 
     let campaign_res = usdc_bank
         .try_create_campaign(
-            time!(1, "s"),
+            time!(1, "y"),
             native!(1000, "USDC"),
             native!(1000, "USDC"),
             campaign_reward_funding_account.key,
@@ -74,7 +82,7 @@ async fn campaign_no_yield() -> Result<()> {
 
     assert!(res.is_err());
 
-    test_f.advance_time(time!(1, "s")).await;
+    test_f.advance_time(time!(1, "y")).await;
 
     let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
     let res = campaign_f
@@ -123,7 +131,7 @@ async fn campaign_mixed_yield() -> Result<()> {
 
     let campaign_res = usdc_bank
         .try_create_campaign(
-            time!(1, "s"),
+            time!(1, "y"),
             native!(1000, "USDC"),
             native!(1000, "USDC"),
             campaign_reward_funding_account.key,
@@ -214,7 +222,7 @@ async fn campaign_max_yield() -> Result<()> {
 
     let campaign_res = usdc_bank
         .try_create_campaign(
-            time!(1, "s"),
+            time!(1, "y"),
             native!(1000, "USDC"),
             native!(1000, "USDC"),
             campaign_reward_funding_account.key,
@@ -339,3 +347,1519 @@ async fn campaign_neg_yield() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn campaign_apr_bounds() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000)
+        .await;
+
+    // No rewards, below the APR floor
+    let res = usdc_bank
+        .try_create_campaign(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            0,
+            campaign_reward_funding_account.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::CampaignAprOutOfBounds);
+
+    // Zero lockup, APR is undefined
+    let res = usdc_bank
+        .try_create_campaign(
+            0,
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::InvalidCampaignParameters);
+
+    // 11x rewards over one year, above the 1000% APR ceiling
+    let res = usdc_bank
+        .try_create_campaign(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(11_000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::CampaignAprOutOfBounds);
+
+    // 10x rewards over one year, at the APR ceiling
+    usdc_bank
+        .try_create_campaign(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(10_000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(50, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let campaign = campaign_f.load().await;
+    let apr = campaign.apr().unwrap();
+
+    assert_eq!(
+        apr,
+        calc_campaign_apr(native!(50, "USDC"), native!(1000, "USDC"), time!(180, "d")).unwrap()
+    );
+    // 5% over 180 days
+    assert_eq_with_tolerance!(
+        apr,
+        I80F48::from_num(0.05 * 365. / 180.),
+        I80F48::from_num(0.0001)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_end_and_sweep() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    // Only half of the campaign capacity is filled
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(500, "USDC"))
+        .await?;
+
+    // Deposit is outstanding, the campaign is only deactivated
+    let sweep_destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_campaign(sweep_destination_account.key)
+        .await?;
+
+    let campaign = campaign_f.load().await;
+    assert!(!campaign.active);
+    assert_eq!(campaign.outstanding_deposits(), native!(500, "USDC"));
+    assert_eq!(
+        balance_of(test_f.context.clone(), sweep_destination_account.key).await,
+        0
+    );
+
+    let res = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(500, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::CampaignNotActive);
+
+    // Outstanding deposit keeps its guaranteed rewards
+    test_f.advance_time(time!(1, "y")).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(1000, "USDC")
+    );
+
+    test_f.refresh_blockhash().await;
+    campaign_f
+        .try_end_campaign(sweep_destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), sweep_destination_account.key).await,
+        native!(500, "USDC")
+    );
+
+    let reward_vault = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(get_reward_vault_address(campaign_f.key).0)
+        .await?;
+
+    assert!(reward_vault.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_reconcile_exited_deposits() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let closed_deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(300, "USDC"))
+        .await?;
+    let open_deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(200, "USDC"))
+        .await?;
+
+    // Active campaigns still take deposits, they can't be reconciled
+    let res = campaign_f
+        .try_reconcile_exited_deposits(&[closed_deposit_key, open_deposit_key])
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::CampaignActive);
+
+    // Deposit closed before exited deposits were tracked
+    test_f.advance_time(time!(1, "y")).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(closed_deposit_key, destination_account.key)
+        .await?;
+
+    let mut campaign = campaign_f.load().await;
+    campaign.exited_deposits = 0;
+    campaign_f.set_campaign(&campaign).await?;
+
+    let sweep_destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_campaign(sweep_destination_account.key)
+        .await?;
+
+    assert_eq!(
+        campaign_f.load().await.outstanding_deposits(),
+        native!(500, "USDC")
+    );
+
+    // Open deposits are only counted once
+    let res = campaign_f
+        .try_reconcile_exited_deposits(&[open_deposit_key, open_deposit_key])
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::InvalidDepositAccounts);
+
+    campaign_f
+        .try_reconcile_exited_deposits(&[open_deposit_key])
+        .await?;
+
+    let campaign = campaign_f.load().await;
+    assert_eq!(campaign.exited_deposits, native!(300, "USDC"));
+    assert_eq!(campaign.outstanding_deposits(), native!(200, "USDC"));
+
+    // The leftover rewards are swept once the open deposit is closed
+    campaign_f
+        .try_end_deposit(open_deposit_key, destination_account.key)
+        .await?;
+
+    test_f.refresh_blockhash().await;
+    campaign_f
+        .try_end_campaign(sweep_destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), sweep_destination_account.key).await,
+        native!(500, "USDC")
+    );
+
+    let reward_vault = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(get_reward_vault_address(campaign_f.key).0)
+        .await?;
+
+    assert!(reward_vault.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_early_end_deposit_forfeit() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(100, "d"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    assert_eq!(campaign_f.load().await.remaining_capacity, 0);
+
+    test_f.advance_time(time!(50, "d")).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_early_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    // All rewards are forfeited, only the principal is returned
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(1000, "USDC")
+    );
+
+    let campaign = campaign_f.load().await;
+    assert_eq!(campaign.remaining_capacity, native!(1000, "USDC"));
+    assert_eq!(campaign.exited_deposits, 0);
+    assert_eq!(campaign.outstanding_deposits(), 0);
+
+    let reward_vault = TokenAccountFixture::fetch(
+        test_f.context.clone(),
+        get_reward_vault_address(campaign_f.key).0,
+    )
+    .await;
+
+    assert_eq!(reward_vault.balance().await, native!(1000, "USDC"));
+
+    // Restored capacity can be deposited again, mature deposits can't exit early
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    test_f.advance_time(time!(100, "d")).await;
+
+    let res = campaign_f
+        .try_early_end_deposit(deposit_key, destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositAlreadyMature);
+
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(3000, "USDC")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_early_end_deposit_partial_reward() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let res = usdc_bank
+        .try_create_campaign_with_options(
+            time!(100, "d"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+            10_001,
+            0,
+            vec![],
+            0,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::InvalidCampaignParameters);
+
+    // Early withdrawals keep half of their pro-rated rewards
+    let campaign_f = usdc_bank
+        .try_create_campaign_with_options(
+            time!(100, "d"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+            5_000,
+            0,
+            vec![],
+            0,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    test_f.advance_time(time!(50, "d")).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_early_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(1250, "USDC")
+    );
+
+    let campaign = campaign_f.load().await;
+    assert_eq!(campaign.remaining_capacity, native!(750, "USDC"));
+    assert_eq!(campaign.exited_deposits, native!(250, "USDC"));
+    assert_eq!(campaign.outstanding_deposits(), 0);
+
+    let reward_vault = TokenAccountFixture::fetch(
+        test_f.context.clone(),
+        get_reward_vault_address(campaign_f.key).0,
+    )
+    .await;
+
+    assert_eq!(reward_vault.balance().await, native!(750, "USDC"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_lockup_maturity_boundary() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(60, "d"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    let deposit = campaign_f.load_deposit(deposit_key).await;
+    assert_eq!(deposit.start_time, test_f.get_clock().await.unix_timestamp);
+
+    // Last second of the lockup
+    campaign_f.warp_to_deposit_maturity(deposit_key, -1).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositNotMature);
+
+    campaign_f.warp_to_deposit_maturity(deposit_key, 0).await;
+
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(2000, "USDC")
+    );
+    assert_eq!(campaign_f.reward_vault_balance().await, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_staggered_deposits_exhaust_rewards() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account =
+        test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(30, "d"),
+            native!(1000, "USDC"),
+            native!(100, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1001)
+        .await;
+
+    let deposit_a_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(400, "USDC"))
+        .await?;
+
+    test_f.advance_time(time!(15, "d")).await;
+
+    let deposit_b_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(600, "USDC"))
+        .await?;
+
+    let deposit_a = campaign_f.load_deposit(deposit_a_key).await;
+    let deposit_b = campaign_f.load_deposit(deposit_b_key).await;
+    assert_eq!(deposit_b.start_time - deposit_a.start_time, time!(15, "d"));
+
+    // Campaign capacity is filled
+    assert_eq!(campaign_f.load().await.remaining_capacity, 0);
+
+    let res = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositAmountTooLarge);
+
+    // Each deposit matures on its own schedule
+    campaign_f.warp_to_deposit_maturity(deposit_a_key, 0).await;
+
+    let destination_account_a = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let destination_account_b = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    let res = campaign_f
+        .try_end_deposit(deposit_b_key, destination_account_b.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositNotMature);
+
+    campaign_f
+        .try_end_deposit(deposit_a_key, destination_account_a.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account_a.key).await,
+        native!(440, "USDC")
+    );
+    assert_eq!(campaign_f.reward_vault_balance().await, native!(60, "USDC"));
+
+    campaign_f.warp_to_deposit_maturity(deposit_b_key, 0).await;
+
+    campaign_f
+        .try_end_deposit(deposit_b_key, destination_account_b.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account_b.key).await,
+        native!(660, "USDC")
+    );
+
+    // All guaranteed rewards were paid out
+    assert_eq!(campaign_f.reward_vault_balance().await, 0);
+
+    let campaign = campaign_f.load().await;
+    assert_eq!(campaign.exited_deposits, native!(1000, "USDC"));
+    assert_eq!(campaign.outstanding_deposits(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_rewards_with_interest_accrual() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let sol_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.sol_mint, *DEFAULT_SOL_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    // Plain lender earning the same marginfi yield as the campaign deposit
+    let lender = test_f.create_marginfi_account().await;
+    let lender_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    lender
+        .try_bank_deposit(lender_token_account.key, &usdc_bank, 1000)
+        .await?;
+
+    let borrower = test_f.create_marginfi_account().await;
+    let sol_funding_account = test_f.sol_mint.create_token_account_and_mint_to(1000).await;
+
+    borrower
+        .try_bank_deposit(sol_funding_account.key, &sol_bank, 1000)
+        .await?;
+
+    let usdc_borrowing_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(10000)
+        .await;
+
+    borrower
+        .try_bank_borrow(usdc_borrowing_account.key, &usdc_bank, 500)
+        .await?;
+
+    // Interest is accrued quarterly over the lockup
+    for _ in 0..4 {
+        test_f.advance_time(time!(1, "y") / 4).await;
+        test_f.refresh_blockhash().await;
+        test_f
+            .marginfi_group
+            .try_accrue_interest(&usdc_bank)
+            .await?;
+    }
+
+    borrower
+        .try_bank_repay(usdc_borrowing_account.key, &usdc_bank, 500, Some(true))
+        .await?;
+
+    lender
+        .try_bank_withdraw(lender_token_account.key, &usdc_bank, 0, Some(true))
+        .await?;
+
+    let base_yield =
+        balance_of(test_f.context.clone(), lender_token_account.key).await - native!(1000, "USDC");
+    assert!(base_yield > 0);
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    // Rewards only top up the marginfi yield to the guaranteed amount
+    let guaranteed_reward = native!(1000, "USDC");
+
+    assert_eq_with_tolerance!(
+        balance_of(test_f.context.clone(), destination_account.key).await as i64,
+        (native!(1000, "USDC") + base_yield.max(guaranteed_reward)) as i64,
+        1
+    );
+    assert_eq_with_tolerance!(
+        campaign_f.reward_vault_balance().await as i64,
+        (guaranteed_reward - base_yield.min(guaranteed_reward)) as i64,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_max_deposit_per_user() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign_with_options(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            native!(300, "USDC"),
+            vec![],
+            0,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(200, "USDC"))
+        .await?;
+
+    // The cap applies to the cumulative deposits of a wallet
+    let res = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(101, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::UserDepositCapExceeded);
+
+    campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(100, "USDC"))
+        .await?;
+
+    let user_deposits = campaign_f.load_user_deposits(test_f.payer()).await;
+    assert_eq!(user_deposits.campaign, campaign_f.key);
+    assert_eq!(user_deposits.owner, test_f.payer());
+    assert_eq!(user_deposits.total_deposits, native!(300, "USDC"));
+
+    assert_eq!(
+        campaign_f.load().await.remaining_capacity,
+        native!(700, "USDC")
+    );
+
+    // Closed deposits still count towards the cap
+    test_f.advance_time(time!(1, "y")).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    let res = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::UserDepositCapExceeded);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_update() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(50, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f.usdc_mint.create_token_account_and_mint_to(500).await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(500, "USDC"))
+        .await?;
+
+    // Raising the capacity without adding rewards dilutes the outstanding deposit
+    let res = campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            0,
+            Some(native!(2000, "USDC")),
+            None,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::CampaignRewardsBelowCommitted);
+
+    // Capacity can't be reduced
+    let res = campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            0,
+            Some(native!(500, "USDC")),
+            None,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::InvalidCampaignParameters);
+
+    // Lockup can't be shortened
+    let res = campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            0,
+            None,
+            Some(time!(90, "d")),
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::InvalidCampaignParameters);
+
+    // Double the capacity and the rewards, and the lockup
+    campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            native!(50, "USDC"),
+            Some(native!(2000, "USDC")),
+            Some(time!(360, "d")),
+        )
+        .await?;
+
+    let campaign = campaign_f.load().await;
+
+    assert!(campaign.active);
+    assert_eq!(campaign.max_deposits, native!(2000, "USDC"));
+    assert_eq!(campaign.remaining_capacity, native!(1500, "USDC"));
+    assert_eq!(campaign.max_rewards, native!(100, "USDC"));
+    assert_eq!(campaign.lockup_period, time!(360, "d"));
+    assert_eq!(
+        campaign_f.reward_vault_balance().await,
+        native!(100, "USDC")
+    );
+    assert_eq!(
+        balance_of(test_f.context.clone(), campaign_reward_funding_account.key).await,
+        native!(900, "USDC")
+    );
+
+    // The extended lockup only applies to new deposits
+    let new_deposit_funding_account = test_f.usdc_mint.create_token_account_and_mint_to(500).await;
+
+    let new_deposit_key = campaign_f
+        .try_create_deposit(new_deposit_funding_account.key, native!(500, "USDC"))
+        .await?;
+
+    assert_eq!(
+        campaign_f.load_deposit(deposit_key).await.lockup_period,
+        time!(180, "d")
+    );
+    assert_eq!(
+        campaign_f.load_deposit(new_deposit_key).await.lockup_period,
+        time!(360, "d")
+    );
+
+    // Deactivated campaigns can't be updated
+    let sweep_destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_campaign(sweep_destination_account.key)
+        .await?;
+
+    let res = campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            native!(50, "USDC"),
+            None,
+            None,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::CampaignNotActive);
+
+    // The outstanding deposit matures at the end of its original lockup
+    campaign_f.warp_to_deposit_maturity(deposit_key, -1).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositNotMature);
+
+    campaign_f.warp_to_deposit_maturity(deposit_key, 0).await;
+
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    // The deposit earns its share of the topped up rewards
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(525, "USDC")
+    );
+
+    // The new deposit is locked for the extended lockup
+    campaign_f
+        .warp_to_deposit_maturity(new_deposit_key, -(time!(180, "d") as i64))
+        .await;
+
+    let new_destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(new_deposit_key, new_destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositNotMature);
+
+    campaign_f
+        .warp_to_deposit_maturity(new_deposit_key, 0)
+        .await;
+
+    campaign_f
+        .try_end_deposit(new_deposit_key, new_destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), new_destination_account.key).await,
+        native!(525, "USDC")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_update_legacy_deposit() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(50, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f.usdc_mint.create_token_account_and_mint_to(500).await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(500, "USDC"))
+        .await?;
+
+    // Legacy deposit, created before its lockup period was recorded
+    let mut deposit = campaign_f.load_deposit(deposit_key).await;
+    deposit.lockup_period = 0;
+    campaign_f.set_deposit(deposit_key, &deposit).await?;
+
+    campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            native!(50, "USDC"),
+            None,
+            Some(time!(360, "d")),
+        )
+        .await?;
+
+    let campaign = campaign_f.load().await;
+
+    assert_eq!(campaign.lockup_period, time!(360, "d"));
+    assert_eq!(campaign.original_lockup_period, time!(180, "d"));
+
+    // Further extensions keep the lockup preceding the first one
+    campaign_f
+        .try_update_campaign(
+            campaign_reward_funding_account.key,
+            native!(100, "USDC"),
+            None,
+            Some(time!(720, "d")),
+        )
+        .await?;
+
+    let campaign = campaign_f.load().await;
+
+    assert_eq!(campaign.lockup_period, time!(720, "d"));
+    assert_eq!(campaign.original_lockup_period, time!(180, "d"));
+    assert_eq!(
+        campaign_f
+            .load_deposit(deposit_key)
+            .await
+            .lockup_period(&campaign),
+        Some(time!(180, "d"))
+    );
+
+    // The legacy deposit matures at the end of the lockup it was created with
+    campaign_f.warp_to_deposit_maturity(deposit_key, -1).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositNotMature);
+
+    campaign_f.warp_to_deposit_maturity(deposit_key, 0).await;
+
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(600, "USDC")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_lockup_tiers() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let tier = |days: u64, reward_bps: u64| LockupTier {
+        lockup_period: time!(days, "d"),
+        reward_bps,
+    };
+
+    // Tier lockups must be shorter than the campaign lockup
+    let res = usdc_bank
+        .try_create_campaign_with_options(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(100, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            0,
+            vec![tier(180, 5_000)],
+            0,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::InvalidLockupTier);
+
+    // Tiers can't earn more than the full rewards
+    let res = usdc_bank
+        .try_create_campaign_with_options(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(100, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            0,
+            vec![tier(30, 10_001)],
+            0,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::InvalidLockupTier);
+
+    let res = usdc_bank
+        .try_create_campaign_with_options(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(100, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            0,
+            (1..=5).map(|month| tier(month * 30, 1_000)).collect(),
+            0,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::InvalidLockupTier);
+
+    let campaign_f = usdc_bank
+        .try_create_campaign_with_options(
+            time!(180, "d"),
+            native!(1000, "USDC"),
+            native!(100, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            0,
+            vec![tier(30, 2_000), tier(90, 6_000)],
+            0,
+        )
+        .await?;
+
+    let campaign = campaign_f.load().await;
+    assert_eq!(campaign.lockup_tier(0), Some((time!(180, "d"), 10_000)));
+    assert_eq!(campaign.lockup_tier(1), Some((time!(30, "d"), 2_000)));
+    assert_eq!(campaign.lockup_tier(2), Some((time!(90, "d"), 6_000)));
+    assert_eq!(campaign.lockup_tier(3), None);
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let res = campaign_f
+        .try_create_deposit_in_tier(deposit_funding_account.key, native!(250, "USDC"), 3)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::InvalidLockupTier);
+
+    let one_month_deposit = campaign_f
+        .try_create_deposit_in_tier(deposit_funding_account.key, native!(250, "USDC"), 1)
+        .await?;
+    let three_months_deposit = campaign_f
+        .try_create_deposit_in_tier(deposit_funding_account.key, native!(250, "USDC"), 2)
+        .await?;
+    let six_months_deposit = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(250, "USDC"))
+        .await?;
+
+    assert_eq!(
+        campaign_f
+            .load_deposit(three_months_deposit)
+            .await
+            .lockup_tier,
+        2
+    );
+
+    // Each deposit matures at the end of its own tier lockup
+    campaign_f
+        .warp_to_deposit_maturity(one_month_deposit, -1)
+        .await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(one_month_deposit, destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositNotMature);
+
+    campaign_f
+        .warp_to_deposit_maturity(one_month_deposit, 0)
+        .await;
+    campaign_f
+        .try_end_deposit(one_month_deposit, destination_account.key)
+        .await?;
+
+    // 20% of the 25 USDC full rewards of the deposit
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(255, "USDC")
+    );
+
+    campaign_f
+        .warp_to_deposit_maturity(three_months_deposit, 0)
+        .await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(three_months_deposit, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(265, "USDC")
+    );
+
+    campaign_f
+        .warp_to_deposit_maturity(six_months_deposit, 0)
+        .await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(six_months_deposit, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(275, "USDC")
+    );
+    assert_eq!(campaign_f.reward_vault_balance().await, native!(55, "USDC"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_transfer_deposit() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(60, "d"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    let payer = test_f.payer_keypair();
+    let alice = Keypair::new();
+
+    campaign_f
+        .try_transfer_deposit(deposit_key, &payer, alice.pubkey())
+        .await?;
+
+    let deposit = campaign_f.load_deposit(deposit_key).await;
+    assert_eq!(deposit.owner, alice.pubkey());
+    assert_eq!(deposit.amount, native!(1000, "USDC"));
+
+    // The previous owner can neither close nor transfer the deposit anymore
+    campaign_f.warp_to_deposit_maturity(deposit_key, 0).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await;
+    assert!(res.is_err());
+
+    let res = campaign_f
+        .try_transfer_deposit(deposit_key, &payer, payer.pubkey())
+        .await;
+    assert!(res.is_err());
+    assert_anchor_error!(res.unwrap_err(), ErrorCode::ConstraintHasOne);
+
+    // Transferring back lets the payer close the deposit
+    campaign_f
+        .try_transfer_deposit(deposit_key, &alice, payer.pubkey())
+        .await?;
+
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(2000, "USDC")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_deposit_emissions() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    // 1 emissions token per deposited USDC per year
+    let emissions_mint = MintFixture::new(test_f.context.clone(), None, Some(6)).await;
+    let emissions_funding_account = emissions_mint.create_token_account_and_mint_to(2_000).await;
+
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(2_000, 6),
+            emissions_mint.key,
+            emissions_funding_account.key,
+        )
+        .await?;
+
+    test_f.set_time(MIN_EMISSIONS_START_TIME as i64);
+
+    let campaign_reward_funding_account =
+        test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+
+    let campaign_f = usdc_bank
+        .try_create_campaign(
+            time!(365, "d"),
+            native!(1000, "USDC"),
+            native!(100, "USDC"),
+            campaign_reward_funding_account.key,
+        )
+        .await?;
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(1000, "USDC"))
+        .await?;
+
+    // Emissions can be claimed during the lockup
+    test_f.advance_time(time!(365, "d") / 2).await;
+
+    let emissions_destination_account = emissions_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_claim_deposit_emissions(deposit_key, emissions_destination_account.key)
+        .await?;
+
+    assert_eq_with_tolerance!(
+        emissions_destination_account.balance().await as i64,
+        native!(500, 6) as i64,
+        native!(1, 6) as i64
+    );
+
+    campaign_f.warp_to_deposit_maturity(deposit_key, 0).await;
+
+    // The balance can't be closed without forwarding the remaining emissions
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let res = campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::MissingEmissionsAccounts);
+
+    campaign_f
+        .try_end_deposit_with_emissions(
+            deposit_key,
+            destination_account.key,
+            emissions_destination_account.key,
+        )
+        .await?;
+
+    assert_eq!(
+        balance_of(test_f.context.clone(), destination_account.key).await,
+        native!(1100, "USDC")
+    );
+    assert_eq_with_tolerance!(
+        emissions_destination_account.balance().await as i64,
+        native!(1000, 6) as i64,
+        native!(1, 6) as i64
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn campaign_pause_and_deposit_deadline() -> Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let usdc_bank = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&test_f.usdc_mint, *DEFAULT_USDC_TEST_BANK_CONFIG)
+        .await?;
+
+    let campaign_reward_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    // Deadline must be in the future
+    let now = test_f.get_clock().await.unix_timestamp;
+    let res = usdc_bank
+        .try_create_campaign_with_options(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            0,
+            vec![],
+            now,
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), LIPError::InvalidCampaignParameters);
+
+    let deposit_deadline = now + time!(2, "y") as i64;
+    let campaign_f = usdc_bank
+        .try_create_campaign_with_options(
+            time!(1, "y"),
+            native!(1000, "USDC"),
+            native!(1000, "USDC"),
+            campaign_reward_funding_account.key,
+            0,
+            0,
+            vec![],
+            deposit_deadline,
+        )
+        .await?;
+
+    let campaign = campaign_f.load().await;
+    assert_eq!(campaign.deposit_deadline, deposit_deadline);
+    assert!(!campaign.paused);
+
+    let deposit_funding_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1000)
+        .await;
+
+    let deposit_key = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(100, "USDC"))
+        .await?;
+
+    // Paused campaigns reject deposits
+    campaign_f.try_pause_campaign().await?;
+    assert!(campaign_f.load().await.paused);
+
+    let res = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(101, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::CampaignPaused);
+
+    // Outstanding deposits can still be closed
+    test_f.advance_time(time!(1, "y")).await;
+
+    let destination_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    campaign_f
+        .try_end_deposit(deposit_key, destination_account.key)
+        .await?;
+
+    // Resumed campaigns accept deposits until the deadline
+    campaign_f.try_resume_campaign().await?;
+    assert!(!campaign_f.load().await.paused);
+
+    campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(102, "USDC"))
+        .await?;
+
+    test_f.set_time(deposit_deadline + 1);
+
+    let res = campaign_f
+        .try_create_deposit(deposit_funding_account.key, native!(103, "USDC"))
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::DepositDeadlinePassed);
+
+    // Ended campaigns can't be resumed
+    campaign_f.try_end_campaign(destination_account.key).await?;
+
+    let res = campaign_f.try_resume_campaign().await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), LIPError::CampaignNotActive);
+
+    Ok(())
+}
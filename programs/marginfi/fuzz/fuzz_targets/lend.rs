@@ -6,7 +6,10 @@ use arbitrary::Arbitrary;
 use fixed::types::I80F48;
 use lazy_static::lazy_static;
 use libfuzzer_sys::fuzz_target;
-use marginfi::{assert_eq_with_tolerance, state::marginfi_group::Bank};
+use marginfi::{
+    assert_eq_with_tolerance,
+    state::{amounts::ShareAmount, marginfi_group::Bank},
+};
 use marginfi_fuzz::{
     account_state::AccountsState, arbitrary_helpers::*, metrics::Metrics, MarginfiFuzzContext,
 };
@@ -150,10 +153,10 @@ fn verify_end_state(mga: &MarginfiFuzzContext) -> anyhow::Result<()> {
         let outstanding_fees = I80F48::from(bank_data.collected_group_fees_outstanding)
             + I80F48::from(bank_data.collected_insurance_fees_outstanding);
 
-        let total_deposits = bank_data.get_asset_amount(bank_data.total_asset_shares.into())?;
+        let total_deposits = bank_data.asset_amount(ShareAmount::new(bank_data.total_asset_shares.into()))?.value();
 
         let total_liabilities =
-            bank_data.get_liability_amount(bank_data.total_liability_shares.into())?;
+            bank_data.liability_amount(ShareAmount::new(bank_data.total_liability_shares.into()))?.value();
 
         let net_accounted_balance = total_deposits - total_liabilities;
 
@@ -14,6 +14,7 @@ use fixed_macro::types::I80F48;
 use marginfi::{
     prelude::MarginfiGroup,
     state::{
+        fee_state::FeeState,
         marginfi_account::MarginfiAccount,
         marginfi_group::{Bank, BankConfig, BankVaultType, InterestRateConfig},
     },
@@ -43,6 +44,7 @@ type SplAccount = spl_token::state::Account;
 
 pub struct MarginfiFuzzContext<'info> {
     pub marginfi_group: AccountInfo<'info>,
+    pub fee_state: AccountInfo<'info>,
     pub banks: Vec<BankAccounts<'info>>,
     pub marginfi_accounts: Vec<UserAccount<'info>>,
     pub owner: AccountInfo<'info>,
@@ -66,9 +68,11 @@ impl<'bump> MarginfiFuzzContext<'bump> {
         let rent_sysvar = state.new_rent_sysvar_account(Rent::free());
         let marginfi_group =
             initialize_marginfi_group(state, admin.clone(), system_program.clone());
+        let fee_state = initialize_fee_state(state, admin.clone());
 
         let mut marginfi_state = MarginfiFuzzContext {
             marginfi_group,
+            fee_state,
             banks: vec![],
             owner: admin,
             system_program,
@@ -384,6 +388,7 @@ impl<'bump> MarginfiFuzzContext<'bump> {
         let bank = &self.banks[bank_idx.0 as usize];
 
         let cache = AccountInfoCache::new(&[
+            self.marginfi_group.clone(),
             marginfi_account.margin_account.clone(),
             bank.bank.clone(),
             marginfi_account.token_accounts[bank_idx.0 as usize].clone(),
@@ -500,6 +505,7 @@ impl<'bump> MarginfiFuzzContext<'bump> {
         let marginfi_account = &self.marginfi_accounts[account_idx.0 as usize];
         let bank = &self.banks[bank_idx.0 as usize];
         let cache = AccountInfoCache::new(&[
+            self.marginfi_group.clone(),
             marginfi_account.margin_account.clone(),
             bank.bank.clone(),
             marginfi_account.token_accounts[bank_idx.0 as usize].clone(),
@@ -520,6 +526,9 @@ impl<'bump> MarginfiFuzzContext<'bump> {
                     )?,
                     bank_liquidity_vault_authority: bank.liquidity_vault_authority.clone(),
                     bank_liquidity_vault: Account::try_from(&bank.liquidity_vault)?,
+                    fee_state: self.fee_state.clone(),
+                    global_fee_wallet: self.owner.clone(),
+                    system_program: Program::try_from(&self.system_program)?,
                 },
                 &marginfi_account.get_remaining_accounts(
                     &self.get_bank_map(),
@@ -614,6 +623,7 @@ impl<'bump> MarginfiFuzzContext<'bump> {
                     )?),
                     bank_insurance_vault: liab_bank.insurance_vault.clone(),
                     token_program: Program::try_from(&self.token_program)?,
+                    fee_state: self.fee_state.clone(),
                 },
                 &remaining_accounts,
                 BTreeMap::new(),
@@ -649,6 +659,7 @@ impl<'bump> MarginfiFuzzContext<'bump> {
         let bank = &self.banks[bank_idx.0 as usize];
 
         let cache = AccountInfoCache::new(&[
+            self.marginfi_group.clone(),
             bank.bank.clone(),
             marginfi_account.margin_account.clone(),
             bank.liquidity_vault.clone(),
@@ -739,6 +750,31 @@ fn initialize_marginfi_group<'bump>(
     marginfi_group
 }
 
+fn initialize_fee_state<'bump>(
+    state: &'bump AccountsState,
+    admin: AccountInfo<'bump>,
+) -> AccountInfo<'bump> {
+    let program_id = marginfi::id();
+    let fee_state = state.new_owned_account(size_of::<FeeState>(), program_id, Rent::free());
+
+    let (_, fee_state_bump) = Pubkey::find_program_address(
+        &[marginfi::constants::FEE_STATE_SEED.as_bytes()],
+        &program_id,
+    );
+
+    // Written directly, initializing needs the program data of an upgradeable deploy.
+    // No program fees, the fuzzer exercises the group accounting
+    AccountLoader::<FeeState>::try_from_unchecked(&program_id, &fee_state)
+        .unwrap()
+        .load_init()
+        .unwrap()
+        .initialize(*admin.key, *admin.key, fee_state_bump);
+
+    set_discriminator::<FeeState>(fee_state.clone());
+
+    fee_state
+}
+
 #[cfg(test)]
 mod tests {
     use fixed::types::I80F48;
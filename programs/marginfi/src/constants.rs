@@ -14,6 +14,14 @@ pub const FEE_VAULT_SEED: &str = "fee_vault";
 pub const EMISSIONS_AUTH_SEED: &str = "emissions_auth_seed";
 pub const EMISSIONS_TOKEN_ACCOUNT_SEED: &str = "emissions_token_account_seed";
 
+pub const RECEIPT_MINT_SEED: &str = "receipt_mint";
+pub const RECEIPT_MINT_AUTHORITY_SEED: &str = "receipt_mint_auth";
+
+pub const MARGINFI_ACCOUNT_SEED: &str = "marginfi_account";
+pub const SUB_ACCOUNT_COUNTER_SEED: &str = "sub_account_counter";
+
+pub const FEE_STATE_SEED: &str = "fee_state";
+
 cfg_if::cfg_if! {
     if #[cfg(feature = "devnet")] {
         pub const PYTH_ID: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
@@ -24,7 +32,8 @@ cfg_if::cfg_if! {
     }
 }
 
-/// TODO: Make these variable per bank
+/// Default liquidation fees of a bank, see `BankConfig::liquidation_liquidator_fee`
+/// and `BankConfig::liquidation_insurance_fee`.
 pub const LIQUIDATION_LIQUIDATOR_FEE: I80F48 = I80F48!(0.025);
 pub const LIQUIDATION_INSURANCE_FEE: I80F48 = I80F48!(0.025);
 
@@ -32,6 +41,9 @@ pub const SECONDS_PER_YEAR: I80F48 = I80F48!(31_536_000);
 
 pub const MAX_PRICE_AGE_SEC: u64 = 60;
 
+/// Fee vault, fee vault authority, keeper token account and token program, see `Bank::pay_keeper_tip`.
+pub const KEEPER_TIP_ACCOUNTS_LEN: usize = 4;
+
 /// Range that contains 95% price data distribution
 ///
 /// https://docs.pyth.network/pythnet-price-feeds/best-practices#confidence-intervals
@@ -41,6 +53,11 @@ pub const USDC_EXPONENT: i32 = 6;
 
 pub const MAX_ORACLE_KEYS: usize = 5;
 
+pub const MAX_LIQUIDATOR_ALLOWLIST_LEN: usize = 8;
+
+/// Max referrers registered in a group, each bank keeps the referral fees of each of them.
+pub const MAX_REFERRERS: usize = 8;
+
 /// Any balance below 1 SPL token amount is treated as none,
 /// this is to account for any artifacts resulting from binary fraction arithemtic.
 pub const EMPTY_BALANCE_THRESHOLD: I80F48 = I80F48!(1);
@@ -116,3 +133,11 @@ pub const EXP_10: [i128; MAX_EXP_10] = [
 
 /// Value where total_asset_value_init_limit is considered inactive
 pub const TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE: u64 = 0;
+
+/// Basis points in one, the scale of `origination_fee_bps`
+pub const BPS_SCALE: u64 = 10_000;
+
+/// Current layout versions of `MarginfiAccount` and `Bank`, older layouts are upgraded
+/// with `migrate_account`.
+pub const MARGINFI_ACCOUNT_VERSION: u64 = 1;
+pub const BANK_VERSION: u64 = 2;
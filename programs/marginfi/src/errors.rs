@@ -83,6 +83,46 @@ pub enum MarginfiError {
     AccountTempActiveBalanceLimitExceeded,
     #[msg("Illegal balance state")] // 6038
     IllegalBalanceState,
+    #[msg("Liquidator is not in the group liquidator allowlist")] // 6040
+    LiquidatorNotAllowed,
+    #[msg("Receipt mint is not setup for this bank")] // 6041
+    ReceiptMintNotSetup,
+    #[msg("Receipt mint already setup")] // 6042
+    ReceiptMintAlreadySetup,
+    #[msg("Invalid native SOL wrap/unwrap")] // 6043
+    InvalidNativeSolOperation,
+    #[msg("Invalid emissions destination account")] // 6044
+    InvalidEmissionsDestinationAccount,
+    #[msg("Balance is not empty")] // 6045
+    BalanceNotEmpty,
+    #[msg("Bank outflow limit exceeded")] // 6046
+    OutflowLimitExceeded,
+    #[msg("Signer is not the pending group admin")] // 6047
+    InvalidPendingAdmin,
+    #[msg("Invalid keeper tip accounts")] // 6048
+    InvalidKeeperTipAccounts,
+    #[msg("Invalid position split")] // 6049
+    InvalidPositionSplit,
+    #[msg("Sub-account index is not the next index of the authority")] // 6050
+    InvalidSubAccountIndex,
+    #[msg("Group total borrow value limit exceeded")] // 6051
+    GroupBorrowValueLimitExceeded,
+    #[msg("Automatic deleveraging is not enabled for this keeper")] // 6052
+    DeleverageNotAllowed,
+    #[msg("Illegal deleverage")] // 6053
+    IllegalDeleverage,
+    #[msg("Deleverage health buffer must be above 1")] // 6054
+    InvalidDeleverageHealthBuffer,
+    #[msg("Oracle price outside of the bank price band")] // 6055
+    OraclePriceOutOfBand,
+    #[msg("Account can't be migrated")] // 6056
+    AccountMigrationError,
+    #[msg("Unauthorized")] // 6057
+    Unauthorized,
+    #[msg("Invalid global fee wallet")] // 6058
+    InvalidGlobalFeeWallet,
+    #[msg("Referrer is not registered in the group")] // 6059
+    InvalidReferrer,
 }
 
 impl From<MarginfiError> for ProgramError {
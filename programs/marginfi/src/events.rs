@@ -1,4 +1,10 @@
-use crate::{prelude::*, state::marginfi_group::BankConfigOpt};
+use crate::{
+    prelude::*,
+    state::{
+        fee_state::FeeStateConfig,
+        marginfi_group::{BankConfigOpt, WrappedI80F48},
+    },
+};
 use anchor_lang::prelude::*;
 
 // Event headers
@@ -30,6 +36,19 @@ pub struct MarginfiGroupConfigureEvent {
     pub config: GroupConfig,
 }
 
+#[event]
+pub struct MarginfiGroupSetPendingAdminEvent {
+    pub header: GroupEventHeader,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct MarginfiGroupAcceptAdminEvent {
+    pub header: GroupEventHeader,
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
 #[event]
 pub struct LendingPoolBankCreateEvent {
     pub header: GroupEventHeader,
@@ -55,6 +74,25 @@ pub struct LendingPoolBankAccrueInterestEvent {
     pub insurance_collected: f64,
 }
 
+#[event]
+pub struct LendingPoolBankFundEmissionsEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub amount: u64,
+    pub emissions_remaining: f64,
+    pub emissions_end_timestamp: u64,
+}
+
+#[event]
+pub struct LendingPoolBankKeeperTipEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub keeper_token_account: Pubkey,
+    pub amount: u64,
+}
+
 #[event]
 pub struct LendingPoolBankCollectFeesEvent {
     pub header: GroupEventHeader,
@@ -66,14 +104,43 @@ pub struct LendingPoolBankCollectFeesEvent {
     pub insurance_fees_outstanding: f64,
 }
 
+#[event]
+pub struct LendingPoolBankWithdrawReferralFeesEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
 #[event]
 pub struct LendingPoolBankHandleBankruptcyEvent {
     pub header: AccountEventHeader,
     pub bank: Pubkey,
     pub mint: Pubkey,
-    pub bad_debt: f64,
-    pub covered_amount: f64,
-    pub socialized_amount: f64,
+    /// Native amount of the liability written off.
+    pub bad_debt: WrappedI80F48,
+    /// Native amount of `bad_debt` covered by the insurance vault.
+    pub covered_amount: WrappedI80F48,
+    /// Native amount of `bad_debt` socialized between the bank depositors.
+    pub socialized_amount: WrappedI80F48,
+    /// USD value of `socialized_amount` at the bank oracle price.
+    pub socialized_value: f64,
+    /// Cumulative bad debt of the bank covered by its insurance vault, in native tokens.
+    pub bank_insured_bad_debt: f64,
+    /// Cumulative loss socialized between the bank depositors, in native tokens.
+    pub bank_socialized_loss: f64,
+    pub group_bankruptcy_count: u64,
+    /// Cumulative loss socialized between the group depositors, in USD.
+    pub group_socialized_loss_value: f64,
+}
+
+#[event]
+pub struct LendingPoolBankForceCloseBalanceEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub asset_shares: f64,
+    pub liability_shares: f64,
 }
 
 // marginfi account events
@@ -106,6 +173,12 @@ pub struct LendingAccountBorrowEvent {
     pub bank: Pubkey,
     pub mint: Pubkey,
     pub amount: u64,
+    /// Added to the liability on top of `amount`
+    pub origination_fee: u64,
+    /// Added to the liability on top of `amount`, owed to the global fee wallet
+    pub program_fee: u64,
+    /// Lamports paid by the signer to the global fee wallet
+    pub flat_fee: u64,
 }
 
 #[event]
@@ -117,6 +190,14 @@ pub struct LendingAccountWithdrawEvent {
     pub close_balance: bool,
 }
 
+#[event]
+pub struct LendingAccountWithdrawEmissionsEvent {
+    pub header: AccountEventHeader,
+    pub bank: Pubkey,
+    pub emissions_mint: Pubkey,
+    pub amount: u64,
+}
+
 #[derive(AnchorSerialize, AnchorDeserialize)]
 pub struct LiquidationBalances {
     pub liquidatee_asset_balance: f64,
@@ -125,6 +206,15 @@ pub struct LiquidationBalances {
     pub liquidator_liability_balance: f64,
 }
 
+#[event]
+pub struct LendingAccountSplitPositionEvent {
+    pub header: AccountEventHeader,
+    pub destination_marginfi_account: Pubkey,
+    pub liability_bank: Pubkey,
+    pub liability_mint: Pubkey,
+    pub collateral_banks: Vec<Pubkey>,
+}
+
 #[event]
 pub struct LendingAccountLiquidateEvent {
     pub header: AccountEventHeader,
@@ -134,8 +224,71 @@ pub struct LendingAccountLiquidateEvent {
     pub asset_mint: Pubkey,
     pub liability_bank: Pubkey,
     pub liability_mint: Pubkey,
+    /// Low biased oracle price of the asset used in the liquidation.
+    pub asset_price: f64,
+    /// High biased oracle price of the liability used in the liquidation.
+    pub liability_price: f64,
+    /// Native amount of the asset seized from the liquidatee.
+    pub asset_amount: u64,
+    /// Native amount of the liability paid by the liquidator.
+    pub liability_amount_liquidator: WrappedI80F48,
+    /// Native amount of the liability repaid for the liquidatee.
+    pub liability_amount_liquidatee: WrappedI80F48,
+    /// Native amount of the liability paid to the insurance vault.
+    pub insurance_fee: WrappedI80F48,
     pub liquidatee_pre_health: f64,
     pub liquidatee_post_health: f64,
     pub pre_balances: LiquidationBalances,
     pub post_balances: LiquidationBalances,
+    /// Native amount of the seized asset kept as program fee, the liquidator receives the rest.
+    pub program_fee: u64,
+}
+
+#[event]
+pub struct MarginfiAccountConfigureAutoDeleverageEvent {
+    pub header: AccountEventHeader,
+    pub enabled: bool,
+    pub keeper: Pubkey,
+    pub health_buffer: f64,
+}
+
+#[event]
+pub struct LendingAccountDeleverageEvent {
+    pub header: AccountEventHeader,
+    pub keeper_marginfi_account: Pubkey,
+    pub asset_bank: Pubkey,
+    pub asset_mint: Pubkey,
+    pub asset_amount: u64,
+    pub liability_bank: Pubkey,
+    pub liability_mint: Pubkey,
+    pub liability_amount: f64,
+    pub pre_health_ratio: f64,
+    pub post_health_ratio: f64,
+}
+
+// fee state events
+
+#[event]
+pub struct FeeStateConfigureEvent {
+    pub signer: Pubkey,
+    pub config: FeeStateConfig,
+}
+
+#[event]
+pub struct LendingPoolSweepProgramFeesEvent {
+    pub header: GroupEventHeader,
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub program_fees_swept: f64,
+    pub program_fees_outstanding: f64,
+}
+
+// migration events
+
+#[event]
+pub struct MigrateAccountEvent {
+    pub header: GroupEventHeader,
+    pub account: Pubkey,
+    pub previous_version: u64,
+    pub version: u64,
 }
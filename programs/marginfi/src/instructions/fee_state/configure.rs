@@ -0,0 +1,37 @@
+use crate::constants::FEE_STATE_SEED;
+use crate::events::FeeStateConfigureEvent;
+use crate::state::fee_state::{FeeState, FeeStateConfig};
+use crate::MarginfiResult;
+use anchor_lang::prelude::*;
+
+/// Configure the program fees, or hand them over to another fee admin
+///
+/// Fee admin only
+pub fn configure_fee_state(
+    ctx: Context<FeeStateConfigure>,
+    config: FeeStateConfig,
+) -> MarginfiResult {
+    ctx.accounts.fee_state.load_mut()?.configure(&config)?;
+
+    emit!(FeeStateConfigureEvent {
+        signer: ctx.accounts.global_fee_admin.key(),
+        config,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct FeeStateConfigure<'info> {
+    #[account(
+        mut,
+        seeds = [FEE_STATE_SEED.as_bytes()],
+        bump = fee_state.load()?.bump_seed,
+    )]
+    pub fee_state: AccountLoader<'info, FeeState>,
+
+    #[account(
+        address = fee_state.load()?.global_fee_admin,
+    )]
+    pub global_fee_admin: Signer<'info>,
+}
@@ -0,0 +1,71 @@
+use crate::constants::FEE_STATE_SEED;
+use crate::events::FeeStateConfigureEvent;
+use crate::state::fee_state::{FeeState, FeeStateConfig};
+use crate::{prelude::MarginfiError, MarginfiResult};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+
+/// Initialize the program fee state, with `global_fee_admin` administering the program fees
+/// from then on.
+///
+/// The fee state is a single PDA, so this can only succeed once.
+/// Upgrade authority only, the fee admin is then independent of the upgrade authority.
+pub fn initialize_fee_state(
+    ctx: Context<FeeStateInitialize>,
+    global_fee_admin: Pubkey,
+    global_fee_wallet: Pubkey,
+    flat_sol_fee: u64,
+    program_fee_bps: u64,
+    fee_flags: u64,
+) -> MarginfiResult {
+    let mut fee_state = ctx.accounts.fee_state.load_init()?;
+
+    fee_state.initialize(
+        global_fee_admin,
+        global_fee_wallet,
+        *ctx.bumps.get("fee_state").unwrap(),
+    );
+
+    let config = FeeStateConfig {
+        global_fee_admin: Some(global_fee_admin),
+        global_fee_wallet: Some(global_fee_wallet),
+        flat_sol_fee: Some(flat_sol_fee),
+        program_fee_bps: Some(program_fee_bps),
+        fee_flags: Some(fee_flags),
+    };
+
+    fee_state.configure(&config)?;
+
+    emit!(FeeStateConfigureEvent {
+        signer: ctx.accounts.payer.key(),
+        config,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct FeeStateInitialize<'info> {
+    #[account(
+        init,
+        seeds = [FEE_STATE_SEED.as_bytes()],
+        bump,
+        payer = payer,
+        space = 8 + std::mem::size_of::<FeeState>(),
+    )]
+    pub fee_state: AccountLoader<'info, FeeState>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [crate::ID.as_ref()],
+        bump,
+        seeds::program = bpf_loader_upgradeable::ID,
+        constraint = program_data.upgrade_authority_address == Some(payer.key())
+            @ MarginfiError::Unauthorized,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,7 @@
+mod configure;
+mod initialize;
+mod sweep_program_fees;
+
+pub use configure::*;
+pub use initialize::*;
+pub use sweep_program_fees::*;
@@ -0,0 +1,119 @@
+use crate::constants::{FEE_STATE_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED};
+use crate::events::{GroupEventHeader, LendingPoolSweepProgramFeesEvent};
+use crate::{
+    bank_signer, math_error,
+    state::{
+        fee_state::FeeState,
+        marginfi_group::{Bank, BankVaultType, MarginfiGroup},
+    },
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Transfer};
+use fixed::types::I80F48;
+use std::cmp::{max, min};
+
+/// Move the outstanding program fees of a bank from the liquidity vault
+/// to a token account of the global fee wallet.
+///
+/// Permissionless, the fees can only go to the global fee wallet.
+pub fn lending_pool_sweep_program_fees(
+    ctx: Context<LendingPoolSweepProgramFees>,
+) -> MarginfiResult {
+    let LendingPoolSweepProgramFees {
+        liquidity_vault_authority,
+        liquidity_vault,
+        fee_wallet_token_account,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    // Referral fees are left in the liquidity vault for the referrers to withdraw
+    let available_liquidity = max(
+        I80F48::from_num(liquidity_vault.amount)
+            - I80F48::from(bank.collected_referral_fees_outstanding),
+        I80F48::ZERO,
+    );
+
+    let outstanding_fees = I80F48::from(bank.collected_program_fees_outstanding);
+    let transfer_amount = min(outstanding_fees, available_liquidity).int();
+    let new_outstanding_fees = outstanding_fees - transfer_amount;
+
+    bank.collected_program_fees_outstanding = new_outstanding_fees.into();
+
+    bank.withdraw_spl_transfer(
+        transfer_amount.checked_to_num().ok_or_else(math_error!())?,
+        Transfer {
+            from: liquidity_vault.to_account_info(),
+            to: fee_wallet_token_account.to_account_info(),
+            authority: liquidity_vault_authority.to_account_info(),
+        },
+        token_program.to_account_info(),
+        bank_signer!(
+            BankVaultType::Liquidity,
+            ctx.accounts.bank.key(),
+            bank.liquidity_vault_authority_bump
+        ),
+    )?;
+
+    emit!(LendingPoolSweepProgramFeesEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: None
+        },
+        bank: ctx.accounts.bank.key(),
+        mint: liquidity_vault.mint,
+        program_fees_swept: transfer_amount.to_num::<f64>(),
+        program_fees_outstanding: new_outstanding_fees.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSweepProgramFees<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        seeds = [FEE_STATE_SEED.as_bytes()],
+        bump = fee_state.load()?.bump_seed,
+    )]
+    pub fee_state: AccountLoader<'info, FeeState>,
+
+    /// CHECK: Seed constraint
+    #[account(
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump
+    )]
+    pub liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump
+    )]
+    pub liquidity_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = fee_wallet_token_account.mint == bank.load()?.mint,
+        constraint = fee_wallet_token_account.owner == fee_state.load()?.global_fee_wallet,
+    )]
+    pub fee_wallet_token_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -1,9 +1,12 @@
 use crate::{
     bank_signer, check,
-    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
+    constants::{FEE_STATE_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
     events::{AccountEventHeader, LendingAccountBorrowEvent},
+    math_error,
     prelude::{MarginfiError, MarginfiGroup, MarginfiResult},
     state::{
+        amounts::ShareAmount,
+        fee_state::{FeeState, FEE_STATE_BORROW_FLAG},
         marginfi_account::{
             BankAccountWrapper, MarginfiAccount, RiskEngine, RiskRequirementType, DISABLED_FLAG,
         },
@@ -11,17 +14,24 @@ use crate::{
     },
 };
 use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer as SystemTransfer};
 use anchor_spl::token::{Token, TokenAccount, Transfer};
 use fixed::types::I80F48;
 use solana_program::{clock::Clock, sysvar::Sysvar};
 
 /// 1. Accrue interest
-/// 2. Create the user's bank account for the asset borrowed if it does not exist yet
-/// 3. Record liability increase in the bank account
-/// 4. Transfer funds from the bank's liquidity vault to the signer's token account
-/// 5. Verify that the user account is in a healthy state
+/// 2. Verify that the account has no other liabilities if the asset borrowed is isolated
+/// 3. Create the user's bank account for the asset borrowed if it does not exist yet
+/// 4. Record liability increase in the bank account, with the bank's origination fee and the program fee
+/// 5. Record the outflow against the bank's outflow limit
+/// 6. Transfer funds from the bank's liquidity vault to the signer's token account
+/// 7. Transfer the flat program fee from the signer to the global fee wallet, if borrows are charged (see `FeeState`)
+/// 8. Verify that the user account is in a healthy state
+/// 9. Record the USD value of the new liability against the group total borrow value limit,
+///    syncing the borrow value released in the bank since its last borrow
 ///
-/// Will error if there is an existing asset <=> withdrawing is not allowed.
+/// Any existing asset in the bank is withdrawn first and only the remainder is borrowed,
+/// a balance never holds both an asset and a liability.
 pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -> MarginfiResult {
     let LendingAccountBorrow {
         marginfi_account: marginfi_account_loader,
@@ -30,9 +40,15 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
         token_program,
         bank_liquidity_vault_authority,
         bank: bank_loader,
+        fee_state,
+        global_fee_wallet,
+        signer,
+        system_program,
         ..
     } = ctx.accounts;
 
+    let fee_state = FeeState::load_or_default(fee_state)?;
+
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
 
     check!(
@@ -40,23 +56,41 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
         MarginfiError::AccountDisabled
     );
 
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
     bank_loader.load_mut()?.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        current_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
 
-    {
+    let liability_increase = {
         let mut bank = bank_loader.load_mut()?;
         let liquidity_vault_authority_bump = bank.liquidity_vault_authority_bump;
 
+        marginfi_account
+            .lending_account
+            .check_isolated_borrow(&bank_loader.key(), &bank)?;
+
         let mut bank_account = BankAccountWrapper::find_or_create(
             &bank_loader.key(),
             &mut bank,
             &mut marginfi_account.lending_account,
         )?;
 
-        bank_account.borrow(I80F48::from_num(amount))?;
+        let liability_shares_before = I80F48::from(bank_account.balance.liability_shares);
+        let (origination_fee, program_fee) = bank_account.borrow_with_fees(amount, &fee_state)?;
+        let liability_increase = bank_account
+            .bank
+            .liability_amount(ShareAmount::new(
+                I80F48::from(bank_account.balance.liability_shares)
+                    .checked_sub(liability_shares_before)
+                    .ok_or_else(math_error!())?,
+            ))?
+            .value();
+        bank_account
+            .bank
+            .record_outflow(amount, current_timestamp)?;
         bank_account.withdraw_spl_transfer(
             amount,
             Transfer {
@@ -72,9 +106,29 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
             ),
         )?;
 
+        let flat_fee = fee_state.flat_fee(FEE_STATE_BORROW_FLAG);
+
+        if flat_fee > 0 {
+            check!(
+                global_fee_wallet.key() == fee_state.global_fee_wallet,
+                MarginfiError::InvalidGlobalFeeWallet
+            );
+
+            transfer(
+                CpiContext::new(
+                    system_program.to_account_info(),
+                    SystemTransfer {
+                        from: signer.to_account_info(),
+                        to: global_fee_wallet.to_account_info(),
+                    },
+                ),
+                flat_fee,
+            )?;
+        }
+
         emit!(LendingAccountBorrowEvent {
             header: AccountEventHeader {
-                signer: Some(ctx.accounts.signer.key()),
+                signer: Some(signer.key()),
                 marginfi_account: marginfi_account_loader.key(),
                 marginfi_account_authority: marginfi_account.authority,
                 marginfi_group: marginfi_account.group,
@@ -82,19 +136,42 @@ pub fn lending_account_borrow(ctx: Context<LendingAccountBorrow>, amount: u64) -
             bank: bank_loader.key(),
             mint: bank.mint,
             amount,
+            origination_fee,
+            program_fee,
+            flat_fee,
         });
-    }
+
+        liability_increase
+    };
 
     // Check account health, if below threshold fail transaction
     // Assuming `ctx.remaining_accounts` holds only oracle accounts
-    RiskEngine::new(&marginfi_account, ctx.remaining_accounts)?
-        .check_account_health(RiskRequirementType::Initial)?;
+    let risk_engine = RiskEngine::new(&marginfi_account, ctx.remaining_accounts)?;
+    risk_engine.check_account_health(RiskRequirementType::Initial)?;
+
+    let mut bank = bank_loader.load_mut()?;
+    let mut marginfi_group = ctx.accounts.marginfi_group.load_mut()?;
+
+    if liability_increase > I80F48::ZERO {
+        let price = risk_engine.get_bank_price(&bank_loader.key())?;
+        bank.increase_borrow_value(liability_increase, price)?;
+    }
+
+    marginfi_group.sync_bank_borrow_value(&mut bank)?;
+
+    if liability_increase > I80F48::ZERO {
+        marginfi_group.check_total_borrow_value_limit()?;
+    }
 
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct LendingAccountBorrow<'info> {
+    /// Writable to enforce the group total borrow value limit, borrows from the banks of a group
+    /// are therefore processed sequentially. Instructions removing liabilities leave the group
+    /// read-only, see `MarginfiGroup::sync_bank_borrow_value`.
+    #[account(mut)]
     pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
 
     #[account(
@@ -103,7 +180,9 @@ pub struct LendingAccountBorrow<'info> {
     )]
     pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
 
+    /// Pays the flat program fee, and must then be writable
     #[account(
+        mut,
         address = marginfi_account.load() ?.authority,
     )]
     pub signer: Signer<'info>,
@@ -139,4 +218,17 @@ pub struct LendingAccountBorrow<'info> {
     pub bank_liquidity_vault: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
+
+    /// CHECK: Seed constraint, may not be initialized yet (see `FeeState::load_or_default`)
+    #[account(
+        seeds = [FEE_STATE_SEED.as_bytes()],
+        bump,
+    )]
+    pub fee_state: AccountInfo<'info>,
+
+    /// CHECK: Checked against the fee state when a flat fee is charged
+    #[account(mut)]
+    pub global_fee_wallet: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
 }
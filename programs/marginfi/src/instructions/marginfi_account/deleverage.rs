@@ -0,0 +1,298 @@
+use crate::constants::MAX_PRICE_AGE_SEC;
+use crate::events::{
+    AccountEventHeader, LendingAccountDeleverageEvent, MarginfiAccountConfigureAutoDeleverageEvent,
+};
+use crate::state::amounts::{ShareAmount, TokenAmount};
+use crate::state::marginfi_account::{
+    BankAccountWrapper, MarginfiAccount, RiskEngine, RiskRequirementType, DISABLED_FLAG,
+};
+use crate::state::marginfi_group::{Bank, WrappedI80F48};
+use crate::state::price::{OraclePriceFeedAdapter, PriceAdapter};
+use crate::{check, math_error, prelude::*};
+use anchor_lang::prelude::*;
+use fixed::types::I80F48;
+use solana_program::clock::Clock;
+use solana_program::sysvar::Sysvar;
+
+/// Automatic deleveraging settings of a marginfi account.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct AutoDeleverageConfig {
+    /// Signer allowed to deleverage the account.
+    pub keeper: Pubkey,
+    /// Maintenance health ratio (weighted assets / weighted liabilities) below which the account
+    /// can be deleveraged, must be above 1.
+    pub health_buffer: WrappedI80F48,
+}
+
+/// Opt the account in to automatic deleveraging by a keeper, or opt out with `None`.
+pub fn marginfi_account_configure_auto_deleverage(
+    ctx: Context<MarginfiAccountConfigureAutoDeleverage>,
+    config: Option<AutoDeleverageConfig>,
+) -> MarginfiResult {
+    let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
+
+    marginfi_account.configure_auto_deleverage(
+        config.map(|config| (config.keeper, config.health_buffer.into())),
+    )?;
+
+    emit!(MarginfiAccountConfigureAutoDeleverageEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: ctx.accounts.marginfi_account.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        enabled: config.is_some(),
+        keeper: marginfi_account.deleverage_keeper,
+        health_buffer: I80F48::from(marginfi_account.deleverage_health_buffer).to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MarginfiAccountConfigureAutoDeleverage<'info> {
+    #[account(mut)]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+}
+
+/// Partially repay a liability of an account that opted in to automatic deleveraging,
+/// with its own collateral, once its maintenance health ratio drops below its health buffer.
+///
+/// The keeper of the account takes over `q_a` of the asset `A` with its own marginfi account,
+/// and pays `q_l` of the liability `L` in exchange, at oracle prices and without any fee:
+///
+/// `q_l = q_a * p_a / p_l`
+///
+/// The deleverage must improve the account health ratio without bringing it above the health
+/// buffer, and must leave part of the liability outstanding.
+///
+/// Remaining accounts are the asset bank oracle, the liability bank oracle, the observation
+/// accounts of the keeper account, followed by those of the deleveraged account.
+pub fn lending_account_deleverage(
+    ctx: Context<LendingAccountDeleverage>,
+    asset_amount: u64,
+) -> MarginfiResult {
+    check!(
+        asset_amount > 0,
+        MarginfiError::IllegalDeleverage,
+        "Asset amount must be positive"
+    );
+
+    check!(
+        ctx.accounts.asset_bank.key() != ctx.accounts.liab_bank.key(),
+        MarginfiError::IllegalDeleverage,
+        "Asset and liability bank cannot be the same"
+    );
+
+    let LendingAccountDeleverage {
+        keeper_marginfi_account: keeper_marginfi_account_loader,
+        marginfi_account: marginfi_account_loader,
+        ..
+    } = ctx.accounts;
+
+    let mut keeper_marginfi_account = keeper_marginfi_account_loader.load_mut()?;
+    let mut marginfi_account = marginfi_account_loader.load_mut()?;
+
+    check!(
+        !marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    check!(
+        marginfi_account.is_deleverage_keeper(ctx.accounts.signer.key),
+        MarginfiError::DeleverageNotAllowed
+    );
+
+    let health_buffer = I80F48::from(marginfi_account.deleverage_health_buffer);
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    {
+        ctx.accounts.asset_bank.load_mut()?.accrue_interest(
+            current_timestamp,
+            #[cfg(not(feature = "client"))]
+            ctx.accounts.asset_bank.key(),
+        )?;
+        ctx.accounts.liab_bank.load_mut()?.accrue_interest(
+            current_timestamp,
+            #[cfg(not(feature = "client"))]
+            ctx.accounts.liab_bank.key(),
+        )?;
+    }
+
+    let pre_health_ratio = {
+        let accounts_starting_pos =
+            ctx.remaining_accounts.len() - marginfi_account.get_remaining_accounts_len();
+
+        RiskEngine::new(
+            &marginfi_account,
+            &ctx.remaining_accounts[accounts_starting_pos..],
+        )?
+        .check_pre_deleverage_condition_and_get_health_ratio(
+            &ctx.accounts.liab_bank.key(),
+            health_buffer,
+        )?
+    };
+
+    // ##Accounting changes##
+
+    let liab_amount = {
+        let asset_amount = TokenAmount::from_native(asset_amount);
+
+        let mut asset_bank = ctx.accounts.asset_bank.load_mut()?;
+        let asset_price = OraclePriceFeedAdapter::try_from_bank_config(
+            &asset_bank.config,
+            &ctx.remaining_accounts[0..1],
+            current_timestamp,
+            MAX_PRICE_AGE_SEC,
+        )?
+        .get_price_non_weighted(None)?;
+
+        let mut liab_bank = ctx.accounts.liab_bank.load_mut()?;
+        let liab_price = OraclePriceFeedAdapter::try_from_bank_config(
+            &liab_bank.config,
+            &ctx.remaining_accounts[1..2],
+            current_timestamp,
+            MAX_PRICE_AGE_SEC,
+        )?
+        .get_price_non_weighted(None)?;
+
+        let liab_amount = asset_amount
+            .to_usd_value(asset_price, asset_bank.mint_decimals, None)?
+            .to_token_amount(liab_price, liab_bank.mint_decimals)?;
+
+        msg!(
+            "asset_amount: {}, liab_amount: {}",
+            asset_amount,
+            liab_amount
+        );
+
+        // Keeper takes over the liability, counted towards the group borrow value like a borrow
+        {
+            let mut bank_account = BankAccountWrapper::find_or_create(
+                &ctx.accounts.liab_bank.key(),
+                &mut liab_bank,
+                &mut keeper_marginfi_account.lending_account,
+            )?;
+
+            let liability_shares_before = I80F48::from(bank_account.balance.liability_shares);
+            bank_account.decrease_balance(liab_amount)?;
+            let liability_increase = bank_account
+                .bank
+                .liability_amount(ShareAmount::new(
+                    I80F48::from(bank_account.balance.liability_shares)
+                        .checked_sub(liability_shares_before)
+                        .ok_or_else(math_error!())?,
+                ))?
+                .value();
+
+            if liability_increase > I80F48::ZERO {
+                bank_account
+                    .bank
+                    .increase_borrow_value(liability_increase, liab_price)?;
+            }
+        }
+
+        // Account pays `asset_amount` of collateral
+        BankAccountWrapper::find(
+            &ctx.accounts.asset_bank.key(),
+            &mut asset_bank,
+            &mut marginfi_account.lending_account,
+        )?
+        .withdraw(asset_amount)
+        .map_err(|_| MarginfiError::IllegalDeleverage)?;
+
+        // Keeper receives `asset_amount` of collateral
+        BankAccountWrapper::find_or_create(
+            &ctx.accounts.asset_bank.key(),
+            &mut asset_bank,
+            &mut keeper_marginfi_account.lending_account,
+        )?
+        .increase_balance(asset_amount)?;
+
+        // Account liability is repaid
+        BankAccountWrapper::find(
+            &ctx.accounts.liab_bank.key(),
+            &mut liab_bank,
+            &mut marginfi_account.lending_account,
+        )?
+        .repay(liab_amount)
+        .map_err(|_| MarginfiError::IllegalDeleverage)?;
+
+        liab_amount
+    };
+
+    // ## Risk checks ##
+
+    let (keeper_remaining_accounts, remaining_accounts) =
+        ctx.remaining_accounts[2..].split_at(keeper_marginfi_account.get_remaining_accounts_len());
+
+    let post_health_ratio = RiskEngine::new(&marginfi_account, remaining_accounts)?
+        .check_post_deleverage_condition_and_get_health_ratio(
+            &ctx.accounts.liab_bank.key(),
+            health_buffer,
+            pre_health_ratio,
+        )?;
+
+    RiskEngine::new(&keeper_marginfi_account, keeper_remaining_accounts)?
+        .check_account_health(RiskRequirementType::Initial)?;
+
+    emit!(LendingAccountDeleverageEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+        },
+        keeper_marginfi_account: keeper_marginfi_account_loader.key(),
+        asset_bank: ctx.accounts.asset_bank.key(),
+        asset_mint: ctx.accounts.asset_bank.load()?.mint,
+        asset_amount,
+        liability_bank: ctx.accounts.liab_bank.key(),
+        liability_mint: ctx.accounts.liab_bank.load()?.mint,
+        liability_amount: liab_amount.value().to_num::<f64>(),
+        pre_health_ratio: pre_health_ratio.to_num::<f64>(),
+        post_health_ratio: post_health_ratio.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountDeleverage<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = asset_bank.load()?.group == marginfi_group.key()
+    )]
+    pub asset_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = liab_bank.load()?.group == marginfi_group.key()
+    )]
+    pub liab_bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = keeper_marginfi_account.load()?.group == marginfi_group.key()
+    )]
+    pub keeper_marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        address = keeper_marginfi_account.load()?.authority
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key()
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+}
@@ -4,13 +4,17 @@ use crate::{
     events::{AccountEventHeader, LendingAccountDepositEvent},
     prelude::*,
     state::{
+        amounts::TokenAmount,
         marginfi_account::{BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
         marginfi_group::Bank,
     },
+    utils::wrap_native_sol,
 };
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, Transfer};
-use fixed::types::I80F48;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token::{spl_token::native_mint, Mint, Token, TokenAccount, Transfer},
+};
 use solana_program::clock::Clock;
 use solana_program::sysvar::Sysvar;
 
@@ -19,18 +23,77 @@ use solana_program::sysvar::Sysvar;
 /// 3. Record asset increase in the bank account
 /// 4. Transfer funds from the signer's token account to the bank's liquidity vault
 ///
-/// Will error if there is an existing liability <=> repaying is not allowed.
+/// Any existing liability in the bank is repaid first and only the remainder is deposited,
+/// a balance never holds both an asset and a liability.
 pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64) -> MarginfiResult {
     let LendingAccountDeposit {
-        marginfi_account: marginfi_account_loader,
+        marginfi_account,
         signer,
+        bank,
         signer_token_account,
         bank_liquidity_vault,
         token_program,
-        bank: bank_loader,
         ..
     } = ctx.accounts;
 
+    deposit(
+        marginfi_account,
+        bank,
+        signer,
+        signer_token_account.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        token_program.to_account_info(),
+        amount,
+    )
+}
+
+/// Deposit native SOL into a native SOL bank, see `lending_account_deposit`.
+///
+/// The SOL is wrapped from the signer into its wSOL associated token account,
+/// created if needed, before the transfer.
+pub fn lending_account_deposit_native(
+    ctx: Context<LendingAccountDepositNative>,
+    amount: u64,
+) -> MarginfiResult {
+    let LendingAccountDepositNative {
+        marginfi_account,
+        signer,
+        bank,
+        signer_token_account,
+        bank_liquidity_vault,
+        token_program,
+        system_program,
+        ..
+    } = ctx.accounts;
+
+    wrap_native_sol(
+        amount,
+        &signer.to_account_info(),
+        &signer_token_account.to_account_info(),
+        &token_program.to_account_info(),
+        &system_program.to_account_info(),
+    )?;
+
+    deposit(
+        marginfi_account,
+        bank,
+        signer,
+        signer_token_account.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        token_program.to_account_info(),
+        amount,
+    )
+}
+
+fn deposit<'info>(
+    marginfi_account_loader: &AccountLoader<'info, MarginfiAccount>,
+    bank_loader: &AccountLoader<'info, Bank>,
+    signer: &Signer<'info>,
+    signer_token_account: AccountInfo<'info>,
+    bank_liquidity_vault: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    amount: u64,
+) -> MarginfiResult {
     let mut bank = bank_loader.load_mut()?;
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
 
@@ -39,8 +102,10 @@ pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64)
         MarginfiError::AccountDisabled
     );
 
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
     bank.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        current_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
@@ -51,15 +116,17 @@ pub fn lending_account_deposit(ctx: Context<LendingAccountDeposit>, amount: u64)
         &mut marginfi_account.lending_account,
     )?;
 
-    bank_account.deposit(I80F48::from_num(amount))?;
+    bank_account.deposit(TokenAmount::from_native(amount))?;
+    bank_account.bank.record_inflow(amount, current_timestamp)?;
+
     bank_account.deposit_spl_transfer(
         amount,
         Transfer {
-            from: signer_token_account.to_account_info(),
-            to: bank_liquidity_vault.to_account_info(),
+            from: signer_token_account,
+            to: bank_liquidity_vault,
             authority: signer.to_account_info(),
         },
-        token_program.to_account_info(),
+        token_program,
     )?;
 
     emit!(LendingAccountDepositEvent {
@@ -115,3 +182,53 @@ pub struct LendingAccountDeposit<'info> {
 
     pub token_program: Program<'info, Token>,
 }
+
+#[derive(Accounts)]
+pub struct LendingAccountDepositNative<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+        constraint = bank.load()?.mint == native_mint::ID @ MarginfiError::InvalidNativeSolOperation,
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(address = native_mint::ID)]
+    pub native_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = native_mint,
+        associated_token::authority = signer,
+    )]
+    pub signer_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
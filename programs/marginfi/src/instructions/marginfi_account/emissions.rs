@@ -1,9 +1,13 @@
-use anchor_lang::{prelude::*, Accounts, ToAccountInfo};
-use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use anchor_lang::{prelude::*, system_program, Accounts, ToAccountInfo};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address, AssociatedToken, Create},
+    token::{transfer, Mint, Token, TokenAccount, Transfer},
+};
 
 use crate::{
     check,
     constants::{EMISSIONS_AUTH_SEED, EMISSIONS_TOKEN_ACCOUNT_SEED},
+    events::{AccountEventHeader, LendingAccountWithdrawEmissionsEvent},
     prelude::{MarginfiError, MarginfiResult},
     state::{
         marginfi_account::{BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
@@ -11,9 +15,29 @@ use crate::{
     },
 };
 
-pub fn lending_account_withdraw_emissions(
-    ctx: Context<LendingAccountWithdrawEmissions>,
+/// Withdraw settled emissions to `destination_account`, any token account of the emissions mint.
+///
+/// With `create_destination_ata`, `destination_account` must be the associated token account
+/// of a destination wallet, and is created, paid by the signer, if it doesn't exist yet.
+/// `remaining_accounts` must then be `[destination_wallet, associated_token_program, system_program]`.
+pub fn lending_account_withdraw_emissions<'info>(
+    ctx: Context<'_, '_, '_, 'info, LendingAccountWithdrawEmissions<'info>>,
+    create_destination_ata: Option<bool>,
 ) -> MarginfiResult {
+    if create_destination_ata.unwrap_or(false) {
+        create_destination_ata_if_needed(&ctx)?;
+    }
+
+    {
+        let destination_account =
+            Account::<TokenAccount>::try_from(&ctx.accounts.destination_account.to_account_info())?;
+
+        check!(
+            destination_account.mint == ctx.accounts.emissions_mint.key(),
+            MarginfiError::InvalidEmissionsDestinationAccount
+        );
+    }
+
     let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
 
     check!(
@@ -52,8 +76,65 @@ pub fn lending_account_withdraw_emissions(
             ),
             emissions_settle_amount,
         )?;
+
+        emit!(LendingAccountWithdrawEmissionsEvent {
+            header: AccountEventHeader {
+                signer: Some(ctx.accounts.signer.key()),
+                marginfi_account: ctx.accounts.marginfi_account.key(),
+                marginfi_account_authority: marginfi_account.authority,
+                marginfi_group: marginfi_account.group,
+            },
+            bank: ctx.accounts.bank.key(),
+            emissions_mint: ctx.accounts.emissions_mint.key(),
+            amount: emissions_settle_amount,
+        });
+    }
+
+    Ok(())
+}
+
+fn create_destination_ata_if_needed<'info>(
+    ctx: &Context<'_, '_, '_, 'info, LendingAccountWithdrawEmissions<'info>>,
+) -> MarginfiResult {
+    let (destination_wallet, associated_token_program, system_program) =
+        match ctx.remaining_accounts {
+            [destination_wallet, associated_token_program, system_program, ..] => {
+                (destination_wallet, associated_token_program, system_program)
+            }
+            _ => return err!(MarginfiError::InvalidEmissionsDestinationAccount),
+        };
+
+    check!(
+        associated_token_program.key() == AssociatedToken::id()
+            && system_program.key() == system_program::ID,
+        MarginfiError::InvalidEmissionsDestinationAccount
+    );
+
+    check!(
+        ctx.accounts.destination_account.key()
+            == get_associated_token_address(
+                destination_wallet.key,
+                &ctx.accounts.emissions_mint.key()
+            ),
+        MarginfiError::InvalidEmissionsDestinationAccount
+    );
+
+    if !ctx.accounts.destination_account.data_is_empty() {
+        return Ok(());
     }
 
+    associated_token::create(CpiContext::new(
+        associated_token_program.clone(),
+        Create {
+            payer: ctx.accounts.signer.to_account_info(),
+            associated_token: ctx.accounts.destination_account.to_account_info(),
+            authority: destination_wallet.clone(),
+            mint: ctx.accounts.emissions_mint.to_account_info(),
+            system_program: system_program.clone(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+        },
+    ))?;
+
     Ok(())
 }
 
@@ -105,8 +186,9 @@ pub struct LendingAccountWithdrawEmissions<'info> {
     )]
     pub emissions_vault: Box<Account<'info, TokenAccount>>,
 
+    /// CHECK: Token account of the emissions mint, optionally created, asserted in the handler
     #[account(mut)]
-    pub destination_account: Box<Account<'info, TokenAccount>>,
+    pub destination_account: UncheckedAccount<'info>,
     pub token_program: Program<'info, Token>,
 }
 
@@ -1,12 +1,17 @@
 use crate::{
+    check,
+    constants::{MARGINFI_ACCOUNT_SEED, SUB_ACCOUNT_COUNTER_SEED},
     events::{AccountEventHeader, MarginfiAccountCreateEvent},
     prelude::*,
-    state::marginfi_account::MarginfiAccount,
+    state::marginfi_account::{MarginfiAccount, SubAccountCounter},
 };
 use anchor_lang::prelude::*;
 use solana_program::sysvar::Sysvar;
 
-pub fn initialize_account(ctx: Context<MarginfiAccountInitialize>) -> MarginfiResult {
+pub fn initialize_account(
+    ctx: Context<MarginfiAccountInitialize>,
+    referrer: Option<Pubkey>,
+) -> MarginfiResult {
     let MarginfiAccountInitialize {
         authority,
         marginfi_group,
@@ -17,6 +22,8 @@ pub fn initialize_account(ctx: Context<MarginfiAccountInitialize>) -> MarginfiRe
     let mut marginfi_account = marginfi_account_loader.load_init()?;
 
     marginfi_account.initialize(marginfi_group.key(), authority.key());
+    marginfi_account.lending_account.referrer =
+        validate_referrer(&*marginfi_group.load()?, &authority.key(), referrer)?;
 
     emit!(MarginfiAccountCreateEvent {
         header: AccountEventHeader {
@@ -30,6 +37,25 @@ pub fn initialize_account(ctx: Context<MarginfiAccountInitialize>) -> MarginfiRe
     Ok(())
 }
 
+/// The referrer of a new account must be registered in the group, and can't be its authority.
+fn validate_referrer(
+    marginfi_group: &MarginfiGroup,
+    authority: &Pubkey,
+    referrer: Option<Pubkey>,
+) -> MarginfiResult<Pubkey> {
+    let referrer = match referrer {
+        Some(referrer) => referrer,
+        None => return Ok(Pubkey::default()),
+    };
+
+    check!(
+        marginfi_group.is_referrer_registered(&referrer) && referrer.ne(authority),
+        MarginfiError::InvalidReferrer
+    );
+
+    Ok(referrer)
+}
+
 #[derive(Accounts)]
 pub struct MarginfiAccountInitialize<'info> {
     pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
@@ -48,3 +74,97 @@ pub struct MarginfiAccountInitialize<'info> {
 
     pub system_program: Program<'info, System>,
 }
+
+/// Initialize the sub-account `account_index` of the authority, a marginfi account at a PDA of
+/// the group, the authority and the index, which no extra keypair has to sign for.
+///
+/// Sub-accounts are created in order: `account_index` must be the number of sub-accounts already
+/// created, tracked by the `SubAccountCounter` of the authority, which is created along with the
+/// first one.
+pub fn initialize_sub_account(
+    ctx: Context<MarginfiAccountInitializeSubAccount>,
+    account_index: u16,
+    referrer: Option<Pubkey>,
+) -> MarginfiResult {
+    let MarginfiAccountInitializeSubAccount {
+        authority,
+        marginfi_group,
+        marginfi_account: marginfi_account_loader,
+        sub_account_counter: sub_account_counter_loader,
+        ..
+    } = ctx.accounts;
+
+    // A counter created by this instruction has no discriminator yet
+    let mut sub_account_counter = match sub_account_counter_loader.load_mut() {
+        Ok(sub_account_counter) => sub_account_counter,
+        Err(_) => {
+            let mut sub_account_counter = sub_account_counter_loader.load_init()?;
+            sub_account_counter.initialize(marginfi_group.key(), authority.key());
+            sub_account_counter
+        }
+    };
+
+    check!(
+        u64::from(account_index) == sub_account_counter.account_count,
+        MarginfiError::InvalidSubAccountIndex
+    );
+
+    sub_account_counter.account_count += 1;
+
+    let mut marginfi_account = marginfi_account_loader.load_init()?;
+
+    marginfi_account.initialize(marginfi_group.key(), authority.key());
+    marginfi_account.lending_account.referrer =
+        validate_referrer(&*marginfi_group.load()?, &authority.key(), referrer)?;
+
+    emit!(MarginfiAccountCreateEvent {
+        header: AccountEventHeader {
+            signer: Some(authority.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        }
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(account_index: u16)]
+pub struct MarginfiAccountInitializeSubAccount<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        init,
+        payer = fee_payer,
+        space = 8 + std::mem::size_of::<MarginfiAccount>(),
+        seeds = [
+            MARGINFI_ACCOUNT_SEED.as_bytes(),
+            marginfi_group.key().as_ref(),
+            authority.key().as_ref(),
+            &account_index.to_le_bytes(),
+        ],
+        bump,
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        space = 8 + std::mem::size_of::<SubAccountCounter>(),
+        seeds = [
+            SUB_ACCOUNT_COUNTER_SEED.as_bytes(),
+            marginfi_group.key().as_ref(),
+            authority.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub sub_account_counter: AccountLoader<'info, SubAccountCounter>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub fee_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
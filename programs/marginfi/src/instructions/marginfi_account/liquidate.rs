@@ -1,10 +1,8 @@
-use crate::constants::{
-    INSURANCE_VAULT_SEED, LIQUIDATION_INSURANCE_FEE, LIQUIDATION_LIQUIDATOR_FEE, MAX_PRICE_AGE_SEC,
-};
+use crate::constants::{FEE_STATE_SEED, INSURANCE_VAULT_SEED, MAX_PRICE_AGE_SEC};
 use crate::events::{AccountEventHeader, LendingAccountLiquidateEvent, LiquidationBalances};
-use crate::state::marginfi_account::{
-    calc_asset_amount, calc_asset_value, RiskEngine, RiskRequirementType,
-};
+use crate::state::amounts::{ShareAmount, TokenAmount};
+use crate::state::fee_state::{FeeState, FEE_STATE_LIQUIDATE_FLAG};
+use crate::state::marginfi_account::{RiskEngine, RiskRequirementType};
 use crate::state::marginfi_group::{Bank, BankVaultType};
 use crate::state::price::{OraclePriceFeedAdapter, PriceAdapter, PriceBias};
 use crate::{
@@ -31,8 +29,8 @@ use solana_program::sysvar::Sysvar;
 /// - `q_a`: Quantity of `A` to be liquidated
 /// - `p_l`: Price of `L`
 /// - `p_a`: Price of `A`
-/// - `f_l`: Liquidation fee
-/// - `f_i`: Insurance fee
+/// - `f_l`: Liquidation fee, `liquidation_liquidator_fee` of the asset bank config
+/// - `f_i`: Insurance fee, `liquidation_insurance_fee` of the asset bank config
 ///
 /// The liquidator invokes this instruction with `q_a` as input (the total amount of collateral to be liquidated).
 /// This is done because `q_a` is the most bounded variable in this process, as if the `q_a` is larger than what the liquidatee has, the instruction will fail.
@@ -49,7 +47,7 @@ use solana_program::sysvar::Sysvar;
 /// 1. The liquidator removes `q_ll` of `L`
 /// 2. The liquidatee receives `q_lf` of `L`
 /// 3. The liquidatee removes `q_a` of `A`
-/// 4. The liquidator receives `q_a` of `A`
+/// 4. The liquidator receives `q_a` of `A`, minus the program fee if liquidations are charged (see `FeeState`)
 /// 5. The insurance fund receives `q_ll - q_lf` of `L`
 ///
 /// Calculations:
@@ -83,12 +81,22 @@ pub fn lending_account_liquidate(
         "Asset and liability bank cannot be the same"
     );
 
+    check!(
+        ctx.accounts
+            .marginfi_group
+            .load()?
+            .is_liquidator_allowed(ctx.accounts.signer.key),
+        MarginfiError::LiquidatorNotAllowed
+    );
+
     let LendingAccountLiquidate {
         liquidator_marginfi_account: liquidator_marginfi_account_loader,
         liquidatee_marginfi_account: liquidatee_marginfi_account_loader,
         ..
     } = ctx.accounts;
 
+    let fee_state = FeeState::load_or_default(&ctx.accounts.fee_state)?;
+
     let mut liquidator_marginfi_account = liquidator_marginfi_account_loader.load_mut()?;
     let mut liquidatee_marginfi_account = liquidatee_marginfi_account_loader.load_mut()?;
     let current_timestamp = Clock::get()?.unix_timestamp;
@@ -118,8 +126,18 @@ pub fn lending_account_liquidate(
 
     // ##Accounting changes##
 
-    let (pre_balances, post_balances) = {
-        let asset_amount = I80F48::from_num(asset_amount);
+    let (
+        pre_balances,
+        post_balances,
+        asset_price,
+        liab_price,
+        liab_amount_liquidator,
+        liab_amount_final,
+        insurance_fund_fee,
+        program_fee,
+    ) = {
+        let asset_amount = TokenAmount::from_native(asset_amount);
+        let program_fee = fee_state.calc_program_fee(FEE_STATE_LIQUIDATE_FLAG, asset_amount)?;
 
         let mut asset_bank = ctx.accounts.asset_bank.load_mut()?;
         let asset_price = {
@@ -146,35 +164,31 @@ pub fn lending_account_liquidate(
             liab_pf.get_price_non_weighted(Some(PriceBias::High))?
         };
 
-        let final_discount = I80F48::ONE - (LIQUIDATION_INSURANCE_FEE + LIQUIDATION_LIQUIDATOR_FEE);
-        let liquidator_discount = I80F48::ONE - LIQUIDATION_LIQUIDATOR_FEE;
+        // Liquidation fees are set by the asset bank
+        asset_bank.migrate()?;
+        let (liquidator_fee, insurance_fee) = asset_bank.config.get_liquidation_fees();
+
+        let final_discount = I80F48::ONE - (insurance_fee + liquidator_fee);
+        let liquidator_discount = I80F48::ONE - liquidator_fee;
 
         // Quantity of liability to be paid off by liquidator
-        let liab_amount_liquidator = calc_asset_amount(
-            calc_asset_value(
-                asset_amount,
+        let liab_amount_liquidator = asset_amount
+            .to_usd_value(
                 asset_price,
                 asset_bank.mint_decimals,
                 Some(liquidator_discount),
-            )?,
-            liab_price,
-            liab_bank.mint_decimals,
-        )?;
+            )?
+            .to_token_amount(liab_price, liab_bank.mint_decimals)?;
 
         // Quantity of liability to be received by liquidatee
-        let liab_amount_final = calc_asset_amount(
-            calc_asset_value(
-                asset_amount,
-                asset_price,
-                asset_bank.mint_decimals,
-                Some(final_discount),
-            )?,
-            liab_price,
-            liab_bank.mint_decimals,
-        )?;
+        let liab_amount_final = asset_amount
+            .to_usd_value(asset_price, asset_bank.mint_decimals, Some(final_discount))?
+            .to_token_amount(liab_price, liab_bank.mint_decimals)?;
 
         // Insurance fund fee
-        let insurance_fund_fee = liab_amount_liquidator - liab_amount_final;
+        let insurance_fund_fee = liab_amount_liquidator
+            .checked_sub(liab_amount_final)?
+            .value();
 
         assert!(
             insurance_fund_fee >= I80F48::ZERO,
@@ -199,13 +213,30 @@ pub fn lending_account_liquidate(
 
             let pre_balance = bank_account
                 .bank
-                .get_liability_amount(bank_account.balance.liability_shares.into())?;
+                .liability_amount(ShareAmount::new(
+                    bank_account.balance.liability_shares.into(),
+                ))?
+                .value();
 
             bank_account.decrease_balance_in_liquidation(liab_amount_liquidator)?;
 
             let post_balance = bank_account
                 .bank
-                .get_liability_amount(bank_account.balance.liability_shares.into())?;
+                .liability_amount(ShareAmount::new(
+                    bank_account.balance.liability_shares.into(),
+                ))?
+                .value();
+
+            // The liability taken over by the liquidator counts towards the group borrow value
+            // like a borrow, while the one repaid by the liquidatee is released
+            if post_balance > pre_balance {
+                bank_account.bank.increase_borrow_value(
+                    post_balance
+                        .checked_sub(pre_balance)
+                        .ok_or(MarginfiError::MathError)?,
+                    liab_price,
+                )?;
+            }
 
             (pre_balance, post_balance)
         };
@@ -220,7 +251,8 @@ pub fn lending_account_liquidate(
 
             let pre_balance = bank_account
                 .bank
-                .get_asset_amount(bank_account.balance.asset_shares.into())?;
+                .asset_amount(ShareAmount::new(bank_account.balance.asset_shares.into()))?
+                .value();
 
             bank_account
                 .withdraw(asset_amount)
@@ -228,12 +260,14 @@ pub fn lending_account_liquidate(
 
             let post_balance = bank_account
                 .bank
-                .get_asset_amount(bank_account.balance.asset_shares.into())?;
+                .asset_amount(ShareAmount::new(bank_account.balance.asset_shares.into()))?
+                .value();
 
             (pre_balance, post_balance)
         };
 
-        // Liquidator receives `asset_quantity` amount of collateral
+        // Liquidator receives `asset_quantity` amount of collateral, minus the program fee
+        // left in the liquidity vault for the global fee wallet
         let (liquidator_asset_pre_balance, liquidator_asset_post_balance) = {
             let mut bank_account = BankAccountWrapper::find_or_create(
                 &ctx.accounts.asset_bank.key(),
@@ -243,13 +277,18 @@ pub fn lending_account_liquidate(
 
             let pre_balance = bank_account
                 .bank
-                .get_asset_amount(bank_account.balance.asset_shares.into())?;
+                .asset_amount(ShareAmount::new(bank_account.balance.asset_shares.into()))?
+                .value();
 
-            bank_account.increase_balance(asset_amount)?;
+            bank_account.increase_balance(
+                asset_amount.checked_sub(TokenAmount::from_native(program_fee))?,
+            )?;
+            bank_account.bank.record_program_fee(program_fee)?;
 
             let post_balance = bank_account
                 .bank
-                .get_asset_amount(bank_account.balance.asset_shares.into())?;
+                .asset_amount(ShareAmount::new(bank_account.balance.asset_shares.into()))?
+                .value();
 
             (pre_balance, post_balance)
         };
@@ -271,17 +310,21 @@ pub fn lending_account_liquidate(
                 &mut liquidatee_marginfi_account.lending_account,
             )?;
 
-            let liquidatee_liability_pre_balance =
-                liquidatee_liab_bank_account.bank.get_liability_amount(
+            let liquidatee_liability_pre_balance = liquidatee_liab_bank_account
+                .bank
+                .liability_amount(ShareAmount::new(
                     liquidatee_liab_bank_account.balance.liability_shares.into(),
-                )?;
+                ))?
+                .value();
 
             liquidatee_liab_bank_account.increase_balance(liab_amount_final)?;
 
-            let liquidatee_liability_post_balance =
-                liquidatee_liab_bank_account.bank.get_liability_amount(
+            let liquidatee_liability_post_balance = liquidatee_liab_bank_account
+                .bank
+                .liability_amount(ShareAmount::new(
                     liquidatee_liab_bank_account.balance.liability_shares.into(),
-                )?;
+                ))?
+                .value();
 
             // ## SPL transfer ##
             // Insurance fund receives fee
@@ -315,6 +358,11 @@ pub fn lending_account_liquidate(
                 .ok_or(MarginfiError::MathError)?
                 .into();
 
+        liab_bank.liquidation_insurance_fees = I80F48::from(liab_bank.liquidation_insurance_fees)
+            .checked_add(insurance_fund_fee)
+            .ok_or(MarginfiError::MathError)?
+            .into();
+
         (
             LiquidationBalances {
                 liquidatee_asset_balance: liquidatee_asset_pre_balance.to_num::<f64>(),
@@ -328,6 +376,12 @@ pub fn lending_account_liquidate(
                 liquidator_asset_balance: liquidator_asset_post_balance.to_num::<f64>(),
                 liquidator_liability_balance: liquidator_liability_post_balance.to_num::<f64>(),
             },
+            asset_price,
+            liab_price,
+            liab_amount_liquidator,
+            liab_amount_final,
+            insurance_fund_fee,
+            program_fee,
         )
     };
 
@@ -362,10 +416,17 @@ pub fn lending_account_liquidate(
         asset_mint: ctx.accounts.asset_bank.load_mut()?.mint,
         liability_bank: ctx.accounts.liab_bank.key(),
         liability_mint: ctx.accounts.liab_bank.load_mut()?.mint,
+        asset_price: asset_price.to_num::<f64>(),
+        liability_price: liab_price.to_num::<f64>(),
+        asset_amount,
+        liability_amount_liquidator: liab_amount_liquidator.value().into(),
+        liability_amount_liquidatee: liab_amount_final.value().into(),
+        insurance_fee: insurance_fund_fee.into(),
         liquidatee_pre_health: pre_liquidation_health.to_num::<f64>(),
         liquidatee_post_health: post_liquidation_health.to_num::<f64>(),
         pre_balances,
         post_balances,
+        program_fee,
     });
 
     Ok(())
@@ -438,4 +499,11 @@ pub struct LendingAccountLiquidate<'info> {
     pub bank_insurance_vault: AccountInfo<'info>,
 
     pub token_program: Program<'info, Token>,
+
+    /// CHECK: Seed constraint, may not be initialized yet (see `FeeState::load_or_default`)
+    #[account(
+        seeds = [FEE_STATE_SEED.as_bytes()],
+        bump,
+    )]
+    pub fee_state: AccountInfo<'info>,
 }
@@ -1,17 +1,25 @@
 mod borrow;
 mod close_balance;
+mod deleverage;
 mod deposit;
 mod emissions;
 mod initialize;
 mod liquidate;
+mod pulse_health;
+mod receipt;
 mod repay;
+mod split_position;
 mod withdraw;
 
 pub use borrow::*;
 pub use close_balance::*;
+pub use deleverage::*;
 pub use deposit::*;
 pub use emissions::*;
 pub use initialize::*;
 pub use liquidate::*;
+pub use pulse_health::*;
+pub use receipt::*;
 pub use repay::*;
+pub use split_position::*;
 pub use withdraw::*;
@@ -0,0 +1,37 @@
+use crate::{
+    prelude::*,
+    state::marginfi_account::{HealthCache, MarginfiAccount, RiskEngine, RiskRequirementType},
+};
+use anchor_lang::prelude::*;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// Compute the account health with the on-chain risk engine and store it in the account health cache,
+/// so off-chain consumers can read the exact health without reimplementing the risk engine.
+///
+/// Permissionless, expects the same (bank, oracle) remaining accounts as a health check.
+pub fn lending_account_pulse_health(ctx: Context<LendingAccountPulseHealth>) -> MarginfiResult {
+    let mut marginfi_account = ctx.accounts.marginfi_account.load_mut()?;
+
+    let (initial_components, maintenance_components) = {
+        let risk_engine = RiskEngine::new(&marginfi_account, ctx.remaining_accounts)?;
+
+        (
+            risk_engine.get_account_health_components(RiskRequirementType::Initial)?,
+            risk_engine.get_account_health_components(RiskRequirementType::Maintenance)?,
+        )
+    };
+
+    marginfi_account.health_cache = HealthCache::new(
+        initial_components,
+        maintenance_components,
+        Clock::get()?.unix_timestamp,
+    );
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountPulseHealth<'info> {
+    #[account(mut)]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+}
@@ -0,0 +1,292 @@
+use crate::{
+    bank_signer, check,
+    constants::{
+        LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED, RECEIPT_MINT_AUTHORITY_SEED,
+    },
+    math_error,
+    prelude::*,
+    state::{
+        amounts::{ShareAmount, TokenAmount},
+        marginfi_account::{
+            BankAccountWrapper, MarginfiAccount, RiskEngine, RiskRequirementType, DISABLED_FLAG,
+        },
+        marginfi_group::{Bank, BankVaultType},
+    },
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{burn, mint_to, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
+use fixed::types::I80F48;
+use solana_program::{clock::Clock, sysvar::Sysvar};
+
+/// 1. Accrue interest
+/// 2. Find the user's existing bank account for the asset
+/// 3. Record asset decrease in the bank account
+/// 4. Move the withdrawn asset shares (floored) back into the bank, now backing the receipts
+/// 5. Mint one receipt token per share to the destination token account
+/// 6. Verify that the user account is in a healthy state
+///
+/// Receipt backed shares are not part of any marginfi account, so they can't be used as collateral
+/// and are not subject to liquidation — the risk engine only sees the remaining account balance.
+/// The shares keep accruing interest and are socialized with other depositors on bankruptcy.
+///
+/// Will error if the bank has no receipt mint or if there is no existing asset.
+pub fn lending_account_mint_receipt(
+    ctx: Context<LendingAccountMintReceipt>,
+    amount: u64,
+) -> MarginfiResult {
+    let LendingAccountMintReceipt {
+        marginfi_account: marginfi_account_loader,
+        bank: bank_loader,
+        receipt_mint,
+        receipt_mint_authority,
+        destination_receipt_account,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let mut marginfi_account = marginfi_account_loader.load_mut()?;
+
+    check!(
+        !marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    bank_loader.load_mut()?.accrue_interest(
+        Clock::get()?.unix_timestamp,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+    )?;
+
+    {
+        let mut bank = bank_loader.load_mut()?;
+        let receipt_mint_authority_bump = bank.receipt_mint_authority_bump;
+
+        let receipt_shares = bank
+            .asset_shares(TokenAmount::from_native(amount))?
+            .value()
+            .checked_floor()
+            .ok_or_else(math_error!())?;
+
+        BankAccountWrapper::find(
+            &bank_loader.key(),
+            &mut bank,
+            &mut marginfi_account.lending_account,
+        )?
+        .withdraw(TokenAmount::from_native(amount))?;
+
+        bank.change_asset_shares(ShareAmount::new(receipt_shares))?;
+
+        mint_to(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                MintTo {
+                    mint: receipt_mint.to_account_info(),
+                    to: destination_receipt_account.to_account_info(),
+                    authority: receipt_mint_authority.to_account_info(),
+                },
+                &[&[
+                    RECEIPT_MINT_AUTHORITY_SEED.as_bytes(),
+                    &bank_loader.key().to_bytes(),
+                    &[receipt_mint_authority_bump],
+                ]],
+            ),
+            receipt_shares
+                .checked_to_num::<u64>()
+                .ok_or_else(math_error!())?,
+        )?;
+    }
+
+    // Check account health, if below threshold fail transaction
+    // Assuming `ctx.remaining_accounts` holds only oracle accounts
+    RiskEngine::new(&marginfi_account, ctx.remaining_accounts)?
+        .check_account_health(RiskRequirementType::Initial)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountMintReceipt<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+        constraint = bank.load()?.is_receipt_mint_setup() @ MarginfiError::ReceiptMintNotSetup,
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        address = bank.load()?.receipt_mint,
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        seeds = [
+            RECEIPT_MINT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.receipt_mint_authority_bump,
+    )]
+    pub receipt_mint_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+    )]
+    pub destination_receipt_account: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// 1. Accrue interest
+/// 2. Burn the receipt tokens from the signer's token account
+/// 3. Remove the backing asset shares from the bank
+/// 4. Transfer the underlying amount (floored) from the bank's liquidity vault to the destination token account
+///
+/// Permissionless, any receipt holder can redeem without a marginfi account.
+pub fn lending_account_redeem_receipt(
+    ctx: Context<LendingAccountRedeemReceipt>,
+    amount: u64,
+) -> MarginfiResult {
+    let LendingAccountRedeemReceipt {
+        signer,
+        bank: bank_loader,
+        receipt_mint,
+        source_receipt_account,
+        destination_token_account,
+        bank_liquidity_vault,
+        bank_liquidity_vault_authority,
+        token_program,
+        ..
+    } = ctx.accounts;
+
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    bank_loader.load_mut()?.accrue_interest(
+        current_timestamp,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+    )?;
+
+    burn(
+        CpiContext::new(
+            token_program.to_account_info(),
+            Burn {
+                mint: receipt_mint.to_account_info(),
+                from: source_receipt_account.to_account_info(),
+                authority: signer.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    let mut bank = bank_loader.load_mut()?;
+
+    bank.assert_operational_mode(None)?;
+
+    let shares = I80F48::from_num(amount);
+    let redeem_amount = bank.asset_amount(ShareAmount::new(shares))?.value();
+    let spl_redeem_amount = redeem_amount.checked_floor().ok_or_else(math_error!())?;
+
+    // Rounding dust goes to the insurance fund, same as in `withdraw_all`
+    bank.collected_insurance_fees_outstanding = {
+        redeem_amount
+            .checked_sub(spl_redeem_amount)
+            .ok_or_else(math_error!())?
+            .checked_add(bank.collected_insurance_fees_outstanding.into())
+            .ok_or_else(math_error!())?
+            .into()
+    };
+
+    bank.change_asset_shares(-ShareAmount::new(shares))?;
+    bank.check_utilization_ratio()?;
+
+    let spl_redeem_amount = spl_redeem_amount
+        .checked_to_num()
+        .ok_or_else(math_error!())?;
+
+    // Redemptions move tokens out of the vault like withdrawals do
+    bank.record_outflow(spl_redeem_amount, current_timestamp)?;
+
+    bank.withdraw_spl_transfer(
+        spl_redeem_amount,
+        Transfer {
+            from: bank_liquidity_vault.to_account_info(),
+            to: destination_token_account.to_account_info(),
+            authority: bank_liquidity_vault_authority.to_account_info(),
+        },
+        token_program.to_account_info(),
+        bank_signer!(
+            BankVaultType::Liquidity,
+            bank_loader.key(),
+            bank.liquidity_vault_authority_bump
+        ),
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountRedeemReceipt<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+        constraint = bank.load()?.is_receipt_mint_setup() @ MarginfiError::ReceiptMintNotSetup,
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        address = bank.load()?.receipt_mint,
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+    )]
+    pub source_receipt_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -4,13 +4,17 @@ use crate::{
     events::{AccountEventHeader, LendingAccountRepayEvent},
     prelude::{MarginfiError, MarginfiGroup, MarginfiResult},
     state::{
+        amounts::TokenAmount,
         marginfi_account::{BankAccountWrapper, MarginfiAccount, DISABLED_FLAG},
         marginfi_group::Bank,
     },
+    utils::wrap_native_sol,
 };
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, Transfer};
-use fixed::types::I80F48;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token::{spl_token::native_mint, Mint, Token, TokenAccount, Transfer},
+};
 use solana_program::{clock::Clock, sysvar::Sysvar};
 
 /// 1. Accrue interest
@@ -25,16 +29,74 @@ pub fn lending_account_repay(
     repay_all: Option<bool>,
 ) -> MarginfiResult {
     let LendingAccountRepay {
-        marginfi_account: marginfi_account_loader,
+        marginfi_account,
         signer,
+        bank,
         signer_token_account,
         bank_liquidity_vault,
         token_program,
-        bank: bank_loader,
         ..
     } = ctx.accounts;
 
-    let repay_all = repay_all.unwrap_or(false);
+    repay(
+        marginfi_account,
+        bank,
+        signer,
+        signer_token_account.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        token_program.to_account_info(),
+        None,
+        amount,
+        repay_all.unwrap_or(false),
+    )
+}
+
+/// Repay a liability of a native SOL bank with native SOL, see `lending_account_repay`.
+///
+/// The repaid amount is wrapped from the signer into its wSOL associated token account,
+/// created if needed, before the transfer.
+pub fn lending_account_repay_native(
+    ctx: Context<LendingAccountRepayNative>,
+    amount: u64,
+    repay_all: Option<bool>,
+) -> MarginfiResult {
+    let LendingAccountRepayNative {
+        marginfi_account,
+        signer,
+        bank,
+        signer_token_account,
+        bank_liquidity_vault,
+        token_program,
+        system_program,
+        ..
+    } = ctx.accounts;
+
+    repay(
+        marginfi_account,
+        bank,
+        signer,
+        signer_token_account.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        token_program.to_account_info(),
+        Some(system_program.to_account_info()),
+        amount,
+        repay_all.unwrap_or(false),
+    )
+}
+
+/// With a `system_program`, the repaid amount is wrapped from the signer's native SOL first.
+#[allow(clippy::too_many_arguments)]
+fn repay<'info>(
+    marginfi_account_loader: &AccountLoader<'info, MarginfiAccount>,
+    bank_loader: &AccountLoader<'info, Bank>,
+    signer: &Signer<'info>,
+    signer_token_account: AccountInfo<'info>,
+    bank_liquidity_vault: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    system_program: Option<AccountInfo<'info>>,
+    amount: u64,
+    repay_all: bool,
+) -> MarginfiResult {
     let mut bank = bank_loader.load_mut()?;
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
 
@@ -43,8 +105,10 @@ pub fn lending_account_repay(
         MarginfiError::AccountDisabled
     );
 
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
     bank.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        current_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
@@ -58,24 +122,38 @@ pub fn lending_account_repay(
     let spl_deposit_amount = if repay_all {
         bank_account.repay_all()?
     } else {
-        bank_account.repay(I80F48::from_num(amount))?;
+        bank_account.repay(TokenAmount::from_native(amount))?;
 
         amount
     };
 
+    bank_account
+        .bank
+        .record_inflow(spl_deposit_amount, current_timestamp)?;
+
+    if let Some(system_program) = system_program {
+        wrap_native_sol(
+            spl_deposit_amount,
+            &signer.to_account_info(),
+            &signer_token_account,
+            &token_program,
+            &system_program,
+        )?;
+    }
+
     bank_account.deposit_spl_transfer(
         spl_deposit_amount,
         Transfer {
-            from: signer_token_account.to_account_info(),
-            to: bank_liquidity_vault.to_account_info(),
+            from: signer_token_account,
+            to: bank_liquidity_vault,
             authority: signer.to_account_info(),
         },
-        token_program.to_account_info(),
+        token_program,
     )?;
 
     emit!(LendingAccountRepayEvent {
         header: AccountEventHeader {
-            signer: Some(ctx.accounts.signer.key()),
+            signer: Some(signer.key()),
             marginfi_account: marginfi_account_loader.key(),
             marginfi_account_authority: marginfi_account.authority,
             marginfi_group: marginfi_account.group,
@@ -127,3 +205,53 @@ pub struct LendingAccountRepay<'info> {
 
     pub token_program: Program<'info, Token>,
 }
+
+#[derive(Accounts)]
+pub struct LendingAccountRepayNative<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+        constraint = bank.load()?.mint == native_mint::ID @ MarginfiError::InvalidNativeSolOperation,
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(address = native_mint::ID)]
+    pub native_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = native_mint,
+        associated_token::authority = signer,
+    )]
+    pub signer_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
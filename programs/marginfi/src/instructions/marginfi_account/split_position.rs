@@ -0,0 +1,133 @@
+use crate::{
+    check,
+    events::{AccountEventHeader, LendingAccountSplitPositionEvent},
+    prelude::*,
+    state::{
+        marginfi_account::{MarginfiAccount, RiskEngine, RiskRequirementType, DISABLED_FLAG},
+        marginfi_group::{Bank, WrappedI80F48},
+    },
+};
+use anchor_lang::prelude::*;
+
+/// Collateral moved along with the liability by `lending_account_split_position`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct SplitPositionCollateral {
+    pub bank: Pubkey,
+    pub asset_shares: WrappedI80F48,
+}
+
+/// 1. Move the liability balance in `liability_bank` to the destination account
+/// 2. Move the requested asset shares of each collateral bank to the destination account
+/// 3. Verify that both accounts are in a healthy state
+///
+/// Balances move as shares, banks and vaults are left untouched. The destination must be an empty
+/// account of the same authority, left holding a single liability — an isolated position whose
+/// liquidation doesn't affect the rest of the source account.
+///
+/// Remaining accounts are the observation accounts of the source account, followed by those
+/// of the destination account, both as of after the split.
+pub fn lending_account_split_position(
+    ctx: Context<LendingAccountSplitPosition>,
+    collateral: Vec<SplitPositionCollateral>,
+) -> MarginfiResult {
+    let LendingAccountSplitPosition {
+        marginfi_account: marginfi_account_loader,
+        destination_marginfi_account: destination_marginfi_account_loader,
+        liability_bank: liability_bank_loader,
+        ..
+    } = ctx.accounts;
+
+    let mut marginfi_account = marginfi_account_loader.load_mut()?;
+    let mut destination_marginfi_account = destination_marginfi_account_loader.load_mut()?;
+
+    check!(
+        !marginfi_account.get_flag(DISABLED_FLAG)
+            && !destination_marginfi_account.get_flag(DISABLED_FLAG),
+        MarginfiError::AccountDisabled
+    );
+
+    check!(
+        destination_marginfi_account
+            .lending_account
+            .balances
+            .iter()
+            .all(|balance| !balance.active),
+        MarginfiError::InvalidPositionSplit,
+        "Destination account must be empty"
+    );
+
+    marginfi_account.lending_account.split_liability(
+        &mut destination_marginfi_account.lending_account,
+        &liability_bank_loader.key(),
+    )?;
+
+    for SplitPositionCollateral { bank, asset_shares } in collateral.iter() {
+        marginfi_account.lending_account.split_asset_shares(
+            &mut destination_marginfi_account.lending_account,
+            bank,
+            (*asset_shares).into(),
+        )?;
+    }
+
+    check!(
+        ctx.remaining_accounts.len() >= marginfi_account.get_remaining_accounts_len(),
+        MarginfiError::MissingPythOrBankAccount
+    );
+
+    let (remaining_accounts, destination_remaining_accounts) = ctx
+        .remaining_accounts
+        .split_at(marginfi_account.get_remaining_accounts_len());
+
+    RiskEngine::new(&marginfi_account, remaining_accounts)?
+        .check_account_health(RiskRequirementType::Initial)?;
+
+    RiskEngine::new(
+        &destination_marginfi_account,
+        destination_remaining_accounts,
+    )?
+    .check_account_health(RiskRequirementType::Initial)?;
+
+    emit!(LendingAccountSplitPositionEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.signer.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        destination_marginfi_account: destination_marginfi_account_loader.key(),
+        liability_bank: liability_bank_loader.key(),
+        liability_mint: liability_bank_loader.load()?.mint,
+        collateral_banks: collateral.iter().map(|c| c.bank).collect(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingAccountSplitPosition<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        constraint = destination_marginfi_account.key() != marginfi_account.key(),
+        constraint = destination_marginfi_account.load()?.group == marginfi_group.key(),
+        constraint = destination_marginfi_account.load()?.authority == marginfi_account.load()?.authority,
+    )]
+    pub destination_marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        constraint = liability_bank.load()?.group == marginfi_group.key(),
+    )]
+    pub liability_bank: AccountLoader<'info, Bank>,
+}
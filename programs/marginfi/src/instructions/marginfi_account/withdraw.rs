@@ -4,40 +4,112 @@ use crate::{
     events::{AccountEventHeader, LendingAccountWithdrawEvent},
     prelude::*,
     state::{
+        amounts::TokenAmount,
         marginfi_account::{
             BankAccountWrapper, MarginfiAccount, RiskEngine, RiskRequirementType, DISABLED_FLAG,
         },
         marginfi_group::{Bank, BankVaultType},
     },
+    utils::unwrap_native_sol,
 };
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Token, TokenAccount, Transfer};
-use fixed::types::I80F48;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token::{spl_token::native_mint, Mint, Token, TokenAccount, Transfer},
+};
 use solana_program::{clock::Clock, sysvar::Sysvar};
 
 /// 1. Accrue interest
 /// 2. Find the user's existing bank account for the asset withdrawn
 /// 3. Record asset decrease in the bank account
-/// 4. Transfer funds from the bank's liquidity vault to the signer's token account
-/// 5. Verify that the user account is in a healthy state
+/// 4. Record the outflow against the bank's outflow limit
+/// 5. Transfer funds from the bank's liquidity vault to the signer's token account
+/// 6. Verify that the user account is in a healthy state
 ///
 /// Will error if there is no existing asset <=> borrowing is not allowed.
-pub fn lending_account_withdraw(
-    ctx: Context<LendingAccountWithdraw>,
+pub fn lending_account_withdraw<'info>(
+    ctx: Context<'_, '_, '_, 'info, LendingAccountWithdraw<'info>>,
     amount: u64,
     withdraw_all: Option<bool>,
 ) -> MarginfiResult {
     let LendingAccountWithdraw {
-        marginfi_account: marginfi_account_loader,
+        marginfi_account,
+        signer,
+        bank,
         destination_token_account,
+        bank_liquidity_vault_authority,
         bank_liquidity_vault,
         token_program,
+        ..
+    } = ctx.accounts;
+
+    withdraw(
+        marginfi_account,
+        bank,
+        signer,
+        destination_token_account.to_account_info(),
+        bank_liquidity_vault_authority.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+        amount,
+        withdraw_all.unwrap_or(false),
+    )
+}
+
+/// Withdraw from a native SOL bank as native SOL, see `lending_account_withdraw`.
+///
+/// The withdrawn amount is transferred to the signer's wSOL associated token account,
+/// created if needed, which is then closed: all of its lamports are released to the signer.
+pub fn lending_account_withdraw_native<'info>(
+    ctx: Context<'_, '_, '_, 'info, LendingAccountWithdrawNative<'info>>,
+    amount: u64,
+    withdraw_all: Option<bool>,
+) -> MarginfiResult {
+    let LendingAccountWithdrawNative {
+        marginfi_account,
+        signer,
+        bank,
+        destination_token_account,
         bank_liquidity_vault_authority,
-        bank: bank_loader,
+        bank_liquidity_vault,
+        token_program,
         ..
     } = ctx.accounts;
 
-    let withdraw_all = withdraw_all.unwrap_or(false);
+    withdraw(
+        marginfi_account,
+        bank,
+        signer,
+        destination_token_account.to_account_info(),
+        bank_liquidity_vault_authority.to_account_info(),
+        bank_liquidity_vault.to_account_info(),
+        token_program.to_account_info(),
+        ctx.remaining_accounts,
+        amount,
+        withdraw_all.unwrap_or(false),
+    )?;
+
+    unwrap_native_sol(
+        &signer.to_account_info(),
+        destination_token_account,
+        &token_program.to_account_info(),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn withdraw<'info>(
+    marginfi_account_loader: &AccountLoader<'info, MarginfiAccount>,
+    bank_loader: &AccountLoader<'info, Bank>,
+    signer: &Signer<'info>,
+    destination_token_account: AccountInfo<'info>,
+    bank_liquidity_vault_authority: AccountInfo<'info>,
+    bank_liquidity_vault: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    withdraw_all: bool,
+) -> MarginfiResult {
     let mut marginfi_account = marginfi_account_loader.load_mut()?;
 
     check!(
@@ -45,8 +117,10 @@ pub fn lending_account_withdraw(
         MarginfiError::AccountDisabled
     );
 
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
     bank_loader.load_mut()?.accrue_interest(
-        Clock::get()?.unix_timestamp,
+        current_timestamp,
         #[cfg(not(feature = "client"))]
         bank_loader.key(),
     )?;
@@ -64,19 +138,23 @@ pub fn lending_account_withdraw(
         let spl_withdraw_amount = if withdraw_all {
             bank_account.withdraw_all()?
         } else {
-            bank_account.withdraw(I80F48::from_num(amount))?;
+            bank_account.withdraw(TokenAmount::from_native(amount))?;
 
             amount
         };
 
+        bank_account
+            .bank
+            .record_outflow(spl_withdraw_amount, current_timestamp)?;
+
         bank_account.withdraw_spl_transfer(
             spl_withdraw_amount,
             Transfer {
-                from: bank_liquidity_vault.to_account_info(),
-                to: destination_token_account.to_account_info(),
-                authority: bank_liquidity_vault_authority.to_account_info(),
+                from: bank_liquidity_vault,
+                to: destination_token_account,
+                authority: bank_liquidity_vault_authority,
             },
-            token_program.to_account_info(),
+            token_program,
             bank_signer!(
                 BankVaultType::Liquidity,
                 bank_loader.key(),
@@ -86,7 +164,7 @@ pub fn lending_account_withdraw(
 
         emit!(LendingAccountWithdrawEvent {
             header: AccountEventHeader {
-                signer: Some(ctx.accounts.signer.key()),
+                signer: Some(signer.key()),
                 marginfi_account: marginfi_account_loader.key(),
                 marginfi_account_authority: marginfi_account.authority,
                 marginfi_group: marginfi_account.group,
@@ -99,8 +177,8 @@ pub fn lending_account_withdraw(
     }
 
     // Check account health, if below threshold fail transaction
-    // Assuming `ctx.remaining_accounts` holds only oracle accounts
-    RiskEngine::new(&marginfi_account, ctx.remaining_accounts)?
+    // Assuming `remaining_accounts` holds only oracle accounts
+    RiskEngine::new(&marginfi_account, remaining_accounts)?
         .check_account_health(RiskRequirementType::Initial)?;
 
     Ok(())
@@ -153,3 +231,63 @@ pub struct LendingAccountWithdraw<'info> {
 
     pub token_program: Program<'info, Token>,
 }
+
+#[derive(Accounts)]
+pub struct LendingAccountWithdrawNative<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+
+    #[account(
+        mut,
+        address = marginfi_account.load()?.authority,
+    )]
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+        constraint = bank.load()?.mint == native_mint::ID @ MarginfiError::InvalidNativeSolOperation,
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(address = native_mint::ID)]
+    pub native_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = native_mint,
+        associated_token::authority = signer,
+    )]
+    pub destination_token_account: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,21 +1,39 @@
 use crate::{
+    check,
+    constants::KEEPER_TIP_ACCOUNTS_LEN,
+    prelude::MarginfiError,
     state::marginfi_group::{Bank, MarginfiGroup},
     MarginfiResult,
 };
 use anchor_lang::prelude::*;
+use std::cmp::min;
 
+/// Accrue bank interest up to the current timestamp.
+///
+/// Permissionless, the keeper is tipped when interest is accrued and the keeper tip accounts
+/// are passed as remaining accounts (see `Bank::pay_keeper_tip`).
 pub fn lending_pool_accrue_bank_interest(
     ctx: Context<LendingPoolAccrueBankInterest>,
 ) -> MarginfiResult {
     let clock = Clock::get()?;
     let mut bank = ctx.accounts.bank.load_mut()?;
 
+    let accrued = bank.last_update < clock.unix_timestamp;
+
     bank.accrue_interest(
         clock.unix_timestamp,
         #[cfg(not(feature = "client"))]
         ctx.accounts.bank.key(),
     )?;
 
+    if accrued {
+        bank.pay_keeper_tip(
+            ctx.accounts.bank.key(),
+            ctx.remaining_accounts,
+            clock.unix_timestamp,
+        )?;
+    }
+
     Ok(())
 }
 
@@ -29,3 +47,62 @@ pub struct LendingPoolAccrueBankInterest<'info> {
     )]
     pub bank: AccountLoader<'info, Bank>,
 }
+
+/// Accrue the interest of the banks passed as (writable) remaining accounts up to the current
+/// timestamp, so that keepers can crank many banks in a single transaction.
+///
+/// Banks already accrued at the current timestamp are skipped. Each bank can be followed by its
+/// keeper tip accounts (see `Bank::pay_keeper_tip`), starting with its fee vault, to tip the keeper
+/// when the bank is accrued.
+pub fn lending_pool_accrue_banks<'info>(
+    ctx: Context<'_, '_, '_, 'info, LendingPoolAccrueBanks<'info>>,
+) -> MarginfiResult {
+    check!(
+        !ctx.remaining_accounts.is_empty(),
+        MarginfiError::InvalidBankAccount
+    );
+
+    let clock = Clock::get()?;
+    let marginfi_group = ctx.accounts.marginfi_group.key();
+    let mut remaining_ais = ctx.remaining_accounts;
+    let (mut banks, mut accrued) = (0, 0);
+
+    while let Some((bank_account, rest)) = remaining_ais.split_first() {
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_account)?;
+        let mut bank = bank_loader.load_mut()?;
+
+        check!(
+            bank.group == marginfi_group,
+            MarginfiError::InvalidBankAccount
+        );
+
+        let tip_accounts_len = match rest.first() {
+            Some(ai) if ai.key.eq(&bank.fee_vault) => min(KEEPER_TIP_ACCOUNTS_LEN, rest.len()),
+            _ => 0,
+        };
+        let (tip_accounts, rest) = rest.split_at(tip_accounts_len);
+        remaining_ais = rest;
+        banks += 1;
+
+        if bank.last_update >= clock.unix_timestamp {
+            continue;
+        }
+
+        bank.accrue_interest(
+            clock.unix_timestamp,
+            #[cfg(not(feature = "client"))]
+            bank_account.key(),
+        )?;
+        bank.pay_keeper_tip(bank_account.key(), tip_accounts, clock.unix_timestamp)?;
+        accrued += 1;
+    }
+
+    msg!("Accrued {} of {} banks", accrued, banks);
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolAccrueBanks<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+}
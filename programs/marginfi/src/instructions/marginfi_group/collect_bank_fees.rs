@@ -11,8 +11,12 @@ use crate::{
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount, Transfer};
 use fixed::types::I80F48;
-use std::cmp::min;
+use std::cmp::{max, min};
 
+/// Move outstanding group and insurance fees from the liquidity vault to the fee and insurance vaults.
+///
+/// Permissionless, the keeper is tipped when fees are collected and the keeper tip accounts
+/// are passed as remaining accounts (see `Bank::pay_keeper_tip`).
 pub fn lending_pool_collect_bank_fees(ctx: Context<LendingPoolCollectBankFees>) -> MarginfiResult {
     let LendingPoolCollectBankFees {
         liquidity_vault_authority,
@@ -25,7 +29,12 @@ pub fn lending_pool_collect_bank_fees(ctx: Context<LendingPoolCollectBankFees>)
 
     let mut bank = ctx.accounts.bank.load_mut()?;
 
-    let mut available_liquidity = I80F48::from_num(liquidity_vault.amount);
+    // Referral fees are left in the liquidity vault for the referrers to withdraw
+    let mut available_liquidity = max(
+        I80F48::from_num(liquidity_vault.amount)
+            - I80F48::from(bank.collected_referral_fees_outstanding),
+        I80F48::ZERO,
+    );
 
     let (insurance_fee_transfer_amount, new_outstanding_insurance_fees) = {
         let outstanding_fees = I80F48::from(bank.collected_insurance_fees_outstanding);
@@ -39,8 +48,9 @@ pub fn lending_pool_collect_bank_fees(ctx: Context<LendingPoolCollectBankFees>)
     available_liquidity -= insurance_fee_transfer_amount;
 
     let (group_fee_transfer_amount, new_outstanding_group_fees) = {
+        // Negative while referral fees exceed the group fees accrued since the last collection
         let outstanding_fees = I80F48::from(bank.collected_group_fees_outstanding);
-        let transfer_amount = min(outstanding_fees, available_liquidity).int();
+        let transfer_amount = max(min(outstanding_fees, available_liquidity), I80F48::ZERO).int();
 
         (transfer_amount.int(), outstanding_fees - transfer_amount)
     };
@@ -87,6 +97,14 @@ pub fn lending_pool_collect_bank_fees(ctx: Context<LendingPoolCollectBankFees>)
         ),
     )?;
 
+    if group_fee_transfer_amount > I80F48::ZERO || insurance_fee_transfer_amount > I80F48::ZERO {
+        bank.pay_keeper_tip(
+            ctx.accounts.bank.key(),
+            ctx.remaining_accounts,
+            Clock::get()?.unix_timestamp,
+        )?;
+    }
+
     emit!(LendingPoolBankCollectFeesEvent {
         header: GroupEventHeader {
             marginfi_group: ctx.accounts.marginfi_group.key(),
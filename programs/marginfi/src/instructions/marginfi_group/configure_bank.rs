@@ -135,11 +135,15 @@ pub struct LendingPoolSetupEmissions<'info> {
     pub system_program: Program<'info, System>,
 }
 
+/// Update emissions mid-flight: flags, rate, end time and vault top-up.
+///
+/// `emissions_end_timestamp` of 0 removes the end time, otherwise it must be in the future.
 pub fn lending_pool_update_emissions_parameters(
     ctx: Context<LendingPoolUpdateEmissionsParameters>,
     emissions_flags: Option<u64>,
     emissions_rate: Option<u64>,
     additional_emissions: Option<u64>,
+    emissions_end_timestamp: Option<u64>,
 ) -> MarginfiResult {
     let mut bank = ctx.accounts.bank.load_mut()?;
 
@@ -163,6 +167,16 @@ pub fn lending_pool_update_emissions_parameters(
         bank.emissions_rate = rate;
     }
 
+    if let Some(end_timestamp) = emissions_end_timestamp {
+        check!(
+            end_timestamp == 0 || end_timestamp > Clock::get()?.unix_timestamp as u64,
+            MarginfiError::EmissionsUpdateError
+        );
+
+        msg!("Updating emissions end timestamp to {}", end_timestamp);
+        bank.emissions_end_timestamp = end_timestamp;
+    }
+
     if let Some(additional_emissions) = additional_emissions {
         transfer(
             CpiContext::new(
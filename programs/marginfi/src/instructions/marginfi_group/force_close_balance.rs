@@ -0,0 +1,75 @@
+use crate::events::{AccountEventHeader, LendingPoolBankForceCloseBalanceEvent};
+use crate::{
+    state::{
+        marginfi_account::{BankAccountWrapper, MarginfiAccount},
+        marginfi_group::{Bank, MarginfiGroup},
+    },
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+
+/// Force close a user balance holding at most dust shares on both sides.
+///
+/// Used by the group admin to clean up dust or corrupted balances blocking account closure.
+/// Residual shares are removed from the bank totals, balances with outstanding emissions
+/// can't be force closed.
+pub fn lending_pool_force_close_balance(
+    ctx: Context<LendingPoolForceCloseBalance>,
+) -> MarginfiResult {
+    let LendingPoolForceCloseBalance {
+        marginfi_account: marginfi_account_loader,
+        bank: bank_loader,
+        ..
+    } = ctx.accounts;
+
+    let mut marginfi_account = marginfi_account_loader.load_mut()?;
+    let mut bank = bank_loader.load_mut()?;
+
+    bank.accrue_interest(
+        Clock::get()?.unix_timestamp,
+        #[cfg(not(feature = "client"))]
+        bank_loader.key(),
+    )?;
+
+    let (asset_shares, liability_shares) = BankAccountWrapper::find(
+        &bank_loader.key(),
+        &mut bank,
+        &mut marginfi_account.lending_account,
+    )?
+    .force_close_dust_balance()?;
+
+    emit!(LendingPoolBankForceCloseBalanceEvent {
+        header: AccountEventHeader {
+            signer: Some(ctx.accounts.admin.key()),
+            marginfi_account: marginfi_account_loader.key(),
+            marginfi_account_authority: marginfi_account.authority,
+            marginfi_group: marginfi_account.group,
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        asset_shares: asset_shares.to_num::<f64>(),
+        liability_shares: liability_shares.to_num::<f64>(),
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolForceCloseBalance<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(address = marginfi_group.load()?.admin)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = marginfi_account.load()?.group == marginfi_group.key(),
+    )]
+    pub marginfi_account: AccountLoader<'info, MarginfiAccount>,
+}
@@ -0,0 +1,103 @@
+use crate::constants::EMISSIONS_TOKEN_ACCOUNT_SEED;
+use crate::events::{GroupEventHeader, LendingPoolBankFundEmissionsEvent};
+use crate::prelude::MarginfiError;
+use crate::{
+    check,
+    state::marginfi_group::{Bank, MarginfiGroup},
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use fixed::types::I80F48;
+
+/// Fund the emissions of a bank, extending the emissions end time proportionally (see `Bank::fund_emissions`).
+///
+/// Permissionless, so that partners can co-incentivize a bank without the group admin.
+/// The emissions rate and flags are left to the group admin.
+pub fn lending_pool_fund_emissions(
+    ctx: Context<LendingPoolFundEmissions>,
+    amount: u64,
+) -> MarginfiResult {
+    check!(amount > 0, MarginfiError::EmissionsUpdateError);
+
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    check!(
+        bank.emissions_mint.ne(&Pubkey::default()),
+        MarginfiError::EmissionsUpdateError
+    );
+
+    check!(
+        bank.emissions_mint.eq(&ctx.accounts.emissions_mint.key()),
+        MarginfiError::EmissionsUpdateError
+    );
+
+    let current_timestamp = Clock::get()?.unix_timestamp;
+
+    bank.fund_emissions(amount, current_timestamp as u64)?;
+
+    transfer(
+        CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.emissions_funding_account.to_account_info(),
+                to: ctx.accounts.emissions_token_account.to_account_info(),
+                authority: ctx.accounts.funder.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+
+    msg!(
+        "Funded {} emissions, total {}, end timestamp {}",
+        amount,
+        I80F48::from(bank.emissions_remaining),
+        bank.emissions_end_timestamp
+    );
+
+    emit!(LendingPoolBankFundEmissionsEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(ctx.accounts.funder.key())
+        },
+        bank: ctx.accounts.bank.key(),
+        emissions_mint: bank.emissions_mint,
+        amount,
+        emissions_remaining: I80F48::from(bank.emissions_remaining).to_num::<f64>(),
+        emissions_end_timestamp: bank.emissions_end_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolFundEmissions<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    pub emissions_mint: Account<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [
+            EMISSIONS_TOKEN_ACCOUNT_SEED.as_bytes(),
+            bank.key().as_ref(),
+            emissions_mint.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub emissions_token_account: Box<Account<'info, TokenAccount>>,
+
+    pub funder: Signer<'info>,
+
+    /// CHECK: Account provided only for funding rewards
+    #[account(mut)]
+    pub emissions_funding_account: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
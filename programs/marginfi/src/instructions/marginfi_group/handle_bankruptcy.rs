@@ -1,4 +1,4 @@
-use crate::constants::ZERO_AMOUNT_THRESHOLD;
+use crate::constants::{EXP_10_I80F48, MAX_PRICE_AGE_SEC, ZERO_AMOUNT_THRESHOLD};
 use crate::events::{AccountEventHeader, LendingPoolBankHandleBankruptcyEvent};
 use crate::state::marginfi_account::DISABLED_FLAG;
 use crate::{
@@ -7,8 +7,10 @@ use crate::{
     math_error,
     prelude::MarginfiError,
     state::{
+        amounts::{ShareAmount, TokenAmount},
         marginfi_account::{BankAccountWrapper, MarginfiAccount, RiskEngine},
         marginfi_group::{Bank, BankVaultType, MarginfiGroup},
+        price::PriceAdapter,
     },
     MarginfiResult,
 };
@@ -23,6 +25,8 @@ use std::cmp::{max, min};
 /// 3. Cover the bad debt of the bankrupt account.
 /// 4. Transfer the insured amount from the insurance fund.
 /// 5. Socialize the loss between lenders if any.
+/// 6. Record the insured and socialized bad debt in the bank and group totals.
+/// 7. Sync the borrow value released by the written off liability into the group total borrow value.
 pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>) -> MarginfiResult {
     let LendingPoolHandleBankruptcy {
         marginfi_account: marginfi_account_loader,
@@ -57,7 +61,11 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
 
     let lending_account_balance = lending_account_balance.unwrap();
 
-    let bad_debt = bank.get_liability_amount(lending_account_balance.liability_shares.into())?;
+    let bad_debt = bank
+        .liability_amount(ShareAmount::new(
+            lending_account_balance.liability_shares.into(),
+        ))?
+        .value();
 
     check!(
         bad_debt > ZERO_AMOUNT_THRESHOLD,
@@ -91,9 +99,29 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
         ),
     )?;
 
+    bank.insured_bad_debt = I80F48::from(bank.insured_bad_debt)
+        .checked_add(covered_by_insurance)
+        .ok_or_else(math_error!())?
+        .into();
+
     // Socialize bad debt among depositors.
     bank.socialize_loss(socialized_loss)?;
 
+    let socialized_loss_value = if socialized_loss > I80F48::ZERO {
+        calc_socialized_loss_value(
+            &bank,
+            &bank_loader.key(),
+            &marginfi_account,
+            ctx.remaining_accounts,
+            socialized_loss,
+        )?
+    } else {
+        I80F48::ZERO
+    };
+
+    let mut marginfi_group = ctx.accounts.marginfi_group.load_mut()?;
+    marginfi_group.record_bankruptcy(socialized_loss_value)?;
+
     // Settle bad debt.
     // The liabilities of this account and global total liabilities are reduced by `bad_debt`
     BankAccountWrapper::find_or_create(
@@ -101,7 +129,9 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
         &mut bank,
         &mut marginfi_account.lending_account,
     )?
-    .repay(bad_debt)?;
+    .repay(TokenAmount::new(bad_debt))?;
+
+    marginfi_group.sync_bank_borrow_value(&mut bank)?;
 
     marginfi_account.set_flag(DISABLED_FLAG);
 
@@ -114,16 +144,57 @@ pub fn lending_pool_handle_bankruptcy(ctx: Context<LendingPoolHandleBankruptcy>)
         },
         bank: bank_loader.key(),
         mint: bank.mint,
-        bad_debt: bad_debt.to_num::<f64>(),
-        covered_amount: covered_by_insurance.to_num::<f64>(),
-        socialized_amount: socialized_loss.to_num::<f64>(),
+        bad_debt: bad_debt.into(),
+        covered_amount: covered_by_insurance.into(),
+        socialized_amount: socialized_loss.into(),
+        socialized_value: socialized_loss_value.to_num::<f64>(),
+        bank_insured_bad_debt: I80F48::from(bank.insured_bad_debt).to_num::<f64>(),
+        bank_socialized_loss: I80F48::from(bank.socialized_loss).to_num::<f64>(),
+        group_bankruptcy_count: marginfi_group.bankruptcy_count,
+        group_socialized_loss_value: I80F48::from(marginfi_group.socialized_loss_value)
+            .to_num::<f64>(),
     });
 
     Ok(())
 }
 
+/// USD value of `socialized_loss` at the price of the bank oracle passed with the account
+/// health check accounts.
+fn calc_socialized_loss_value(
+    bank: &Bank,
+    bank_pk: &Pubkey,
+    marginfi_account: &MarginfiAccount,
+    remaining_ais: &[AccountInfo],
+    socialized_loss: I80F48,
+) -> MarginfiResult<I80F48> {
+    // Remaining accounts are (bank, oracle) pairs of the active balances, as checked by the risk engine
+    let bank_index = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| balance.active)
+        .position(|balance| balance.bank_pk == *bank_pk)
+        .ok_or(MarginfiError::LendingAccountBalanceNotFound)?;
+    let oracle_ai_idx = bank_index * 2 + 1;
+
+    let price = bank
+        .load_price_feed_from_account_info(
+            &remaining_ais[oracle_ai_idx..oracle_ai_idx + 1],
+            Clock::get()?.unix_timestamp,
+            MAX_PRICE_AGE_SEC,
+        )?
+        .get_price()?;
+
+    Ok(socialized_loss
+        .checked_mul(price)
+        .ok_or_else(math_error!())?
+        .checked_div(EXP_10_I80F48[bank.mint_decimals as usize])
+        .ok_or_else(math_error!())?)
+}
+
 #[derive(Accounts)]
 pub struct LendingPoolHandleBankruptcy<'info> {
+    #[account(mut)]
     pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
 
     #[account(address = marginfi_group.load()?.admin)]
@@ -3,13 +3,25 @@ mod add_pool;
 mod collect_bank_fees;
 mod configure;
 mod configure_bank;
+mod force_close_balance;
+mod fund_emissions;
 mod handle_bankruptcy;
 mod initialize;
+mod setup_receipt_mint;
+mod sync_borrow_value;
+mod transfer_admin;
+mod withdraw_referral_fees;
 
 pub use accrue_bank_interest::*;
 pub use add_pool::*;
 pub use collect_bank_fees::*;
 pub use configure::*;
 pub use configure_bank::*;
+pub use force_close_balance::*;
+pub use fund_emissions::*;
 pub use handle_bankruptcy::*;
 pub use initialize::*;
+pub use setup_receipt_mint::*;
+pub use sync_borrow_value::*;
+pub use transfer_admin::*;
+pub use withdraw_referral_fees::*;
@@ -0,0 +1,73 @@
+use crate::{
+    check,
+    constants::{RECEIPT_MINT_AUTHORITY_SEED, RECEIPT_MINT_SEED},
+    prelude::{MarginfiError, MarginfiResult},
+    state::marginfi_group::{Bank, MarginfiGroup},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token};
+
+/// Opt a bank into transferable deposit receipts.
+///
+/// Creates the bank's receipt mint, a PDA with the same decimals as the bank mint,
+/// whose mint authority is a program owned PDA. Can only be done once per bank.
+pub fn lending_pool_setup_receipt_mint(
+    ctx: Context<LendingPoolSetupReceiptMint>,
+) -> MarginfiResult {
+    let mut bank = ctx.accounts.bank.load_mut()?;
+
+    check!(
+        !bank.is_receipt_mint_setup(),
+        MarginfiError::ReceiptMintAlreadySetup
+    );
+
+    bank.receipt_mint = ctx.accounts.receipt_mint.key();
+    bank.receipt_mint_bump = *ctx.bumps.get("receipt_mint").unwrap();
+    bank.receipt_mint_authority_bump = *ctx.bumps.get("receipt_mint_authority").unwrap();
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSetupReceiptMint<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        mut,
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    /// CHECK: Asserted by PDA constraints
+    #[account(
+        seeds = [
+            RECEIPT_MINT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump
+    )]
+    pub receipt_mint_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        mint::decimals = bank.load()?.mint_decimals,
+        mint::authority = receipt_mint_authority,
+        seeds = [
+            RECEIPT_MINT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub receipt_mint: Box<Account<'info, Mint>>,
+
+    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,44 @@
+use crate::{
+    check,
+    prelude::MarginfiError,
+    state::marginfi_group::{Bank, MarginfiGroup},
+    MarginfiResult,
+};
+use anchor_lang::prelude::*;
+
+/// Sync the borrow value of the banks passed as (writable) remaining accounts into the group
+/// total borrow value, e.g. to release the value of liabilities that left the banks since their
+/// last borrow (see `MarginfiGroup::sync_bank_borrow_value`).
+///
+/// Permissionless, the group total only catches up with the borrow value recorded by the banks.
+pub fn lending_pool_sync_borrow_value<'info>(
+    ctx: Context<'_, '_, '_, 'info, LendingPoolSyncBorrowValue<'info>>,
+) -> MarginfiResult {
+    check!(
+        !ctx.remaining_accounts.is_empty(),
+        MarginfiError::InvalidBankAccount
+    );
+
+    let marginfi_group_pk = ctx.accounts.marginfi_group.key();
+    let mut marginfi_group = ctx.accounts.marginfi_group.load_mut()?;
+
+    for bank_account in ctx.remaining_accounts.iter() {
+        let bank_loader = AccountLoader::<Bank>::try_from(bank_account)?;
+        let mut bank = bank_loader.load_mut()?;
+
+        check!(
+            bank.group == marginfi_group_pk,
+            MarginfiError::InvalidBankAccount
+        );
+
+        marginfi_group.sync_bank_borrow_value(&mut bank)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolSyncBorrowValue<'info> {
+    #[account(mut)]
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+}
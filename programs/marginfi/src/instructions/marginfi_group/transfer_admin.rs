@@ -0,0 +1,68 @@
+use crate::events::{
+    GroupEventHeader, MarginfiGroupAcceptAdminEvent, MarginfiGroupSetPendingAdminEvent,
+};
+use crate::{state::marginfi_group::MarginfiGroup, MarginfiResult};
+use anchor_lang::prelude::*;
+
+/// Propose a new group admin, the transfer completes once it signs `marginfi_group_accept_admin`.
+/// Setting the default pubkey cancels a pending transfer.
+///
+/// Admin only
+pub fn set_pending_admin(
+    ctx: Context<MarginfiGroupSetPendingAdmin>,
+    pending_admin: Pubkey,
+) -> MarginfiResult {
+    let marginfi_group = &mut ctx.accounts.marginfi_group.load_mut()?;
+
+    marginfi_group.set_pending_admin(pending_admin)?;
+
+    emit!(MarginfiGroupSetPendingAdminEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.admin.key)
+        },
+        pending_admin,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MarginfiGroupSetPendingAdmin<'info> {
+    #[account(mut)]
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    #[account(
+        address = marginfi_group.load()?.admin,
+    )]
+    pub admin: Signer<'info>,
+}
+
+/// Accept the group admin role proposed with `marginfi_group_set_pending_admin`.
+///
+/// Pending admin only
+pub fn accept_admin(ctx: Context<MarginfiGroupAcceptAdmin>) -> MarginfiResult {
+    let marginfi_group = &mut ctx.accounts.marginfi_group.load_mut()?;
+
+    let previous_admin = marginfi_group.admin;
+    marginfi_group.accept_admin(ctx.accounts.pending_admin.key)?;
+
+    emit!(MarginfiGroupAcceptAdminEvent {
+        header: GroupEventHeader {
+            marginfi_group: ctx.accounts.marginfi_group.key(),
+            signer: Some(*ctx.accounts.pending_admin.key)
+        },
+        previous_admin,
+        admin: marginfi_group.admin,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MarginfiGroupAcceptAdmin<'info> {
+    #[account(mut)]
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    pub pending_admin: Signer<'info>,
+}
@@ -0,0 +1,103 @@
+use crate::{
+    bank_signer,
+    constants::{LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED},
+    events::{GroupEventHeader, LendingPoolBankWithdrawReferralFeesEvent},
+    prelude::*,
+    state::marginfi_group::{Bank, BankVaultType},
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount, Transfer};
+
+/// Transfer the referral fees owed by the bank to the signing referrer, from the bank's
+/// liquidity vault to the referrer's token account.
+///
+/// Referral fees are credited as the balances of referred accounts are updated, see
+/// `Bank::credit_referral_fees`. The referrer may have been removed from the group since.
+pub fn lending_pool_withdraw_referral_fees(
+    ctx: Context<LendingPoolWithdrawReferralFees>,
+) -> MarginfiResult {
+    let LendingPoolWithdrawReferralFees {
+        marginfi_group,
+        referrer,
+        bank: bank_loader,
+        destination_token_account,
+        bank_liquidity_vault_authority,
+        bank_liquidity_vault,
+        token_program,
+    } = ctx.accounts;
+
+    let mut bank = bank_loader.load_mut()?;
+
+    let referral_fees = bank.withdraw_referral_fees(referrer.key)?;
+
+    if referral_fees > 0 {
+        bank.withdraw_spl_transfer(
+            referral_fees,
+            Transfer {
+                from: bank_liquidity_vault.to_account_info(),
+                to: destination_token_account.to_account_info(),
+                authority: bank_liquidity_vault_authority.to_account_info(),
+            },
+            token_program.to_account_info(),
+            bank_signer!(
+                BankVaultType::Liquidity,
+                bank_loader.key(),
+                bank.liquidity_vault_authority_bump
+            ),
+        )?;
+    }
+
+    emit!(LendingPoolBankWithdrawReferralFeesEvent {
+        header: GroupEventHeader {
+            signer: Some(referrer.key()),
+            marginfi_group: marginfi_group.key(),
+        },
+        bank: bank_loader.key(),
+        mint: bank.mint,
+        amount: referral_fees,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct LendingPoolWithdrawReferralFees<'info> {
+    pub marginfi_group: AccountLoader<'info, MarginfiGroup>,
+
+    pub referrer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = bank.load()?.group == marginfi_group.key(),
+    )]
+    pub bank: AccountLoader<'info, Bank>,
+
+    #[account(
+        mut,
+        constraint = destination_token_account.mint == bank.load()?.mint,
+    )]
+    pub destination_token_account: Account<'info, TokenAccount>,
+
+    /// CHECK: Seed constraint check
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_AUTHORITY_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_authority_bump,
+    )]
+    pub bank_liquidity_vault_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            LIQUIDITY_VAULT_SEED.as_bytes(),
+            bank.key().as_ref(),
+        ],
+        bump = bank.load()?.liquidity_vault_bump,
+    )]
+    pub bank_liquidity_vault: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
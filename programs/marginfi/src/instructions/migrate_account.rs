@@ -0,0 +1,130 @@
+use crate::events::{GroupEventHeader, MigrateAccountEvent};
+use crate::prelude::*;
+use crate::state::{marginfi_account::MarginfiAccount, marginfi_group::Bank};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_lang::Discriminator;
+use std::mem::size_of;
+
+/// Upgrade a `MarginfiAccount` or a `Bank` to its latest layout version, in place.
+///
+/// The account is first reallocated to the size of the latest layout, if smaller, with the rent
+/// difference paid by `payer`, then upgraded one version at a time (see `MarginfiAccount::migrate`
+/// and `Bank::migrate`). New fields can thus be appended to the layouts, and initialized by
+/// a migration step, instead of being carved out of padding.
+///
+/// Permissionless, the upgrade doesn't depend on the signer.
+pub fn migrate_account(ctx: Context<MigrateAccount>) -> MarginfiResult {
+    let account = &ctx.accounts.account;
+
+    let discriminator: [u8; 8] = account
+        .try_borrow_data()?
+        .get(..8)
+        .and_then(|discriminator| discriminator.try_into().ok())
+        .ok_or(MarginfiError::AccountMigrationError)?;
+
+    let (group, previous_version, version) = if discriminator == MarginfiAccount::discriminator() {
+        realloc_account(
+            account,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            8 + size_of::<MarginfiAccount>(),
+        )?;
+
+        let marginfi_account_loader = AccountLoader::<MarginfiAccount>::try_from(account)?;
+        let mut marginfi_account = marginfi_account_loader.load_mut()?;
+        let previous_version = marginfi_account.version;
+
+        marginfi_account.migrate()?;
+
+        (
+            marginfi_account.group,
+            previous_version,
+            marginfi_account.version,
+        )
+    } else if discriminator == Bank::discriminator() {
+        realloc_account(
+            account,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            8 + size_of::<Bank>(),
+        )?;
+
+        let bank_loader = AccountLoader::<Bank>::try_from(account)?;
+        let mut bank = bank_loader.load_mut()?;
+        let previous_version = bank.version;
+
+        bank.migrate()?;
+
+        (bank.group, previous_version, bank.version)
+    } else {
+        return err!(MarginfiError::AccountMigrationError);
+    };
+
+    msg!(
+        "Migrated account {} from version {} to {}",
+        account.key(),
+        previous_version,
+        version
+    );
+
+    emit!(MigrateAccountEvent {
+        header: GroupEventHeader {
+            marginfi_group: group,
+            signer: Some(ctx.accounts.payer.key()),
+        },
+        account: account.key(),
+        previous_version,
+        version,
+    });
+
+    Ok(())
+}
+
+/// Grow `account` to `len` bytes, topping up its rent from `payer`.
+fn realloc_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    len: usize,
+) -> MarginfiResult {
+    if account.data_len() >= len {
+        return Ok(());
+    }
+
+    let rent_due = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+
+    if rent_due > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            rent_due,
+        )?;
+    }
+
+    account.realloc(len, true)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// CHECK: Marginfi account or bank, asserted by discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+    )]
+    pub account: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
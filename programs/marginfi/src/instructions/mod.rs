@@ -1,5 +1,9 @@
+pub mod fee_state;
 pub mod marginfi_account;
 pub mod marginfi_group;
+pub mod migrate_account;
 
+pub use fee_state::*;
 pub use marginfi_account::*;
 pub use marginfi_group::*;
+pub use migrate_account::*;
@@ -10,7 +10,10 @@ pub mod utils;
 use anchor_lang::prelude::*;
 use instructions::*;
 use prelude::*;
-use state::marginfi_group::{BankConfigCompact, BankConfigOpt};
+use state::{
+    fee_state::FeeStateConfig,
+    marginfi_group::{BankConfigCompact, BankConfigOpt},
+};
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "mainnet-beta")] {
@@ -37,6 +40,17 @@ pub mod marginfi {
         marginfi_group::configure(ctx, config)
     }
 
+    pub fn marginfi_group_set_pending_admin(
+        ctx: Context<MarginfiGroupSetPendingAdmin>,
+        pending_admin: Pubkey,
+    ) -> MarginfiResult {
+        marginfi_group::set_pending_admin(ctx, pending_admin)
+    }
+
+    pub fn marginfi_group_accept_admin(ctx: Context<MarginfiGroupAcceptAdmin>) -> MarginfiResult {
+        marginfi_group::accept_admin(ctx)
+    }
+
     pub fn lending_pool_add_bank(
         ctx: Context<LendingPoolAddBank>,
         bank_config: BankConfigCompact,
@@ -65,15 +79,32 @@ pub mod marginfi {
         emissions_flags: Option<u64>,
         emissions_rate: Option<u64>,
         additional_emissions: Option<u64>,
+        emissions_end_timestamp: Option<u64>,
     ) -> MarginfiResult {
         marginfi_group::lending_pool_update_emissions_parameters(
             ctx,
             emissions_flags,
             emissions_rate,
             additional_emissions,
+            emissions_end_timestamp,
         )
     }
 
+    /// Permissionless emissions vault top-up, extending the emissions end time proportionally.
+    pub fn lending_pool_fund_emissions(
+        ctx: Context<LendingPoolFundEmissions>,
+        amount: u64,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_fund_emissions(ctx, amount)
+    }
+
+    /// Opt a bank into transferable deposit receipts by creating its receipt mint.
+    pub fn lending_pool_setup_receipt_mint(
+        ctx: Context<LendingPoolSetupReceiptMint>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_setup_receipt_mint(ctx)
+    }
+
     /// Handle bad debt of a bankrupt marginfi account for a given bank.
     pub fn lending_pool_handle_bankruptcy(
         ctx: Context<LendingPoolHandleBankruptcy>,
@@ -81,11 +112,32 @@ pub mod marginfi {
         marginfi_group::lending_pool_handle_bankruptcy(ctx)
     }
 
+    /// Force close a dust balance of a marginfi account for a given bank.
+    pub fn lending_pool_force_close_balance(
+        ctx: Context<LendingPoolForceCloseBalance>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_force_close_balance(ctx)
+    }
+
     // User instructions
 
-    /// Initialize a marginfi account for a given group
-    pub fn marginfi_account_initialize(ctx: Context<MarginfiAccountInitialize>) -> MarginfiResult {
-        marginfi_account::initialize_account(ctx)
+    /// Initialize a marginfi account for a given group, optionally recording the referrer,
+    /// registered in the group, earning a share of the protocol fees paid by its liabilities
+    pub fn marginfi_account_initialize(
+        ctx: Context<MarginfiAccountInitialize>,
+        referrer: Option<Pubkey>,
+    ) -> MarginfiResult {
+        marginfi_account::initialize_account(ctx, referrer)
+    }
+
+    /// Initialize the next sub-account of the authority for a given group, at an address
+    /// derived from the group, the authority and `account_index`
+    pub fn marginfi_account_initialize_sub_account(
+        ctx: Context<MarginfiAccountInitializeSubAccount>,
+        account_index: u16,
+        referrer: Option<Pubkey>,
+    ) -> MarginfiResult {
+        marginfi_account::initialize_sub_account(ctx, account_index, referrer)
     }
 
     pub fn lending_account_deposit(
@@ -95,6 +147,14 @@ pub mod marginfi {
         marginfi_account::lending_account_deposit(ctx, amount)
     }
 
+    /// Deposit native SOL into a native SOL bank, wrapped into the signer's wSOL ATA
+    pub fn lending_account_deposit_native(
+        ctx: Context<LendingAccountDepositNative>,
+        amount: u64,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_deposit_native(ctx, amount)
+    }
+
     pub fn lending_account_repay(
         ctx: Context<LendingAccountRepay>,
         amount: u64,
@@ -103,14 +163,32 @@ pub mod marginfi {
         marginfi_account::lending_account_repay(ctx, amount, repay_all)
     }
 
-    pub fn lending_account_withdraw(
-        ctx: Context<LendingAccountWithdraw>,
+    /// Repay a native SOL bank liability with native SOL, wrapped into the signer's wSOL ATA
+    pub fn lending_account_repay_native(
+        ctx: Context<LendingAccountRepayNative>,
+        amount: u64,
+        repay_all: Option<bool>,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_repay_native(ctx, amount, repay_all)
+    }
+
+    pub fn lending_account_withdraw<'info>(
+        ctx: Context<'_, '_, '_, 'info, LendingAccountWithdraw<'info>>,
         amount: u64,
         withdraw_all: Option<bool>,
     ) -> MarginfiResult {
         marginfi_account::lending_account_withdraw(ctx, amount, withdraw_all)
     }
 
+    /// Withdraw from a native SOL bank as native SOL, through the signer's wSOL ATA which is closed
+    pub fn lending_account_withdraw_native<'info>(
+        ctx: Context<'_, '_, '_, 'info, LendingAccountWithdrawNative<'info>>,
+        amount: u64,
+        withdraw_all: Option<bool>,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_withdraw_native(ctx, amount, withdraw_all)
+    }
+
     pub fn lending_account_borrow(
         ctx: Context<LendingAccountBorrow>,
         amount: u64,
@@ -124,10 +202,15 @@ pub mod marginfi {
         marginfi_account::lending_account_close_balance(ctx)
     }
 
-    pub fn lending_account_withdraw_emissions(
-        ctx: Context<LendingAccountWithdrawEmissions>,
+    pub fn lending_account_pulse_health(ctx: Context<LendingAccountPulseHealth>) -> MarginfiResult {
+        marginfi_account::lending_account_pulse_health(ctx)
+    }
+
+    pub fn lending_account_withdraw_emissions<'info>(
+        ctx: Context<'_, '_, '_, 'info, LendingAccountWithdrawEmissions<'info>>,
+        create_destination_ata: Option<bool>,
     ) -> MarginfiResult {
-        marginfi_account::lending_account_withdraw_emissions(ctx)
+        marginfi_account::lending_account_withdraw_emissions(ctx, create_destination_ata)
     }
 
     pub fn lending_account_settle_emissions(
@@ -136,6 +219,31 @@ pub mod marginfi {
         marginfi_account::lending_account_settle_emissions(ctx)
     }
 
+    /// Convert part of a deposit balance into transferable receipt tokens
+    pub fn lending_account_mint_receipt(
+        ctx: Context<LendingAccountMintReceipt>,
+        amount: u64,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_mint_receipt(ctx, amount)
+    }
+
+    /// Burn receipt tokens and withdraw the underlying deposit
+    pub fn lending_account_redeem_receipt(
+        ctx: Context<LendingAccountRedeemReceipt>,
+        amount: u64,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_redeem_receipt(ctx, amount)
+    }
+
+    /// Move a liability balance and part of the collateral backing it into an empty account
+    /// of the same authority, isolating the position from the rest of the account
+    pub fn lending_account_split_position(
+        ctx: Context<LendingAccountSplitPosition>,
+        collateral: Vec<SplitPositionCollateral>,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_split_position(ctx, collateral)
+    }
+
     /// Liquidate a lending account balance of an unhealthy marginfi account
     pub fn lending_account_liquidate(
         ctx: Context<LendingAccountLiquidate>,
@@ -144,6 +252,22 @@ pub mod marginfi {
         marginfi_account::lending_account_liquidate(ctx, asset_amount)
     }
 
+    /// Opt a marginfi account in to automatic deleveraging by a keeper, or opt out with `None`
+    pub fn marginfi_account_configure_auto_deleverage(
+        ctx: Context<MarginfiAccountConfigureAutoDeleverage>,
+        config: Option<AutoDeleverageConfig>,
+    ) -> MarginfiResult {
+        marginfi_account::marginfi_account_configure_auto_deleverage(ctx, config)
+    }
+
+    /// Partially repay a liability of an account below its deleverage health buffer with its collateral
+    pub fn lending_account_deleverage(
+        ctx: Context<LendingAccountDeleverage>,
+        asset_amount: u64,
+    ) -> MarginfiResult {
+        marginfi_account::lending_account_deleverage(ctx, asset_amount)
+    }
+
     // Operational instructions
     pub fn lending_pool_accrue_bank_interest(
         ctx: Context<LendingPoolAccrueBankInterest>,
@@ -151,9 +275,73 @@ pub mod marginfi {
         marginfi_group::lending_pool_accrue_bank_interest(ctx)
     }
 
+    /// Accrue the interest of the banks passed as remaining accounts, skipping those already
+    /// accrued at the current timestamp. Each bank can be followed by its keeper tip accounts.
+    pub fn lending_pool_accrue_banks<'info>(
+        ctx: Context<'_, '_, '_, 'info, LendingPoolAccrueBanks<'info>>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_accrue_banks(ctx)
+    }
+
+    /// Sync the borrow value of the banks passed as remaining accounts into the group total
+    /// borrow value.
+    pub fn lending_pool_sync_borrow_value<'info>(
+        ctx: Context<'_, '_, '_, 'info, LendingPoolSyncBorrowValue<'info>>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_sync_borrow_value(ctx)
+    }
+
+    /// Withdraw the referral fees owed by a bank to the signing referrer
+    pub fn lending_pool_withdraw_referral_fees(
+        ctx: Context<LendingPoolWithdrawReferralFees>,
+    ) -> MarginfiResult {
+        marginfi_group::lending_pool_withdraw_referral_fees(ctx)
+    }
+
     pub fn lending_pool_collect_bank_fees(
         ctx: Context<LendingPoolCollectBankFees>,
     ) -> MarginfiResult {
         marginfi_group::lending_pool_collect_bank_fees(ctx)
     }
+
+    // Program fee instructions
+
+    /// Initialize the program fee state, administered by `global_fee_admin` independently
+    /// of the group admins. Upgrade authority only.
+    pub fn fee_state_initialize(
+        ctx: Context<FeeStateInitialize>,
+        global_fee_admin: Pubkey,
+        global_fee_wallet: Pubkey,
+        flat_sol_fee: u64,
+        program_fee_bps: u64,
+        fee_flags: u64,
+    ) -> MarginfiResult {
+        fee_state::initialize_fee_state(
+            ctx,
+            global_fee_admin,
+            global_fee_wallet,
+            flat_sol_fee,
+            program_fee_bps,
+            fee_flags,
+        )
+    }
+
+    pub fn fee_state_configure(
+        ctx: Context<FeeStateConfigure>,
+        config: FeeStateConfig,
+    ) -> MarginfiResult {
+        fee_state::configure_fee_state(ctx, config)
+    }
+
+    /// Move the program fees of a bank to a token account of the global fee wallet.
+    pub fn lending_pool_sweep_program_fees(
+        ctx: Context<LendingPoolSweepProgramFees>,
+    ) -> MarginfiResult {
+        fee_state::lending_pool_sweep_program_fees(ctx)
+    }
+
+    /// Upgrade a marginfi account or a bank to its latest layout version, reallocating it if needed.
+    pub fn migrate_account(ctx: Context<MigrateAccount>) -> MarginfiResult {
+        instructions::migrate_account(ctx)
+    }
 }
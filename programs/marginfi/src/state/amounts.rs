@@ -0,0 +1,211 @@
+//! Typed quantities used by the lending and risk math.
+//!
+//! Native token amounts, bank shares and USD values are all represented as `I80F48`,
+//! which makes it easy to pass a share quantity where a token amount is expected.
+//! The newtypes below only convert into each other through explicit, named conversions:
+//! token amounts and shares through the bank share values (`Bank::asset_amount`,
+//! `Bank::liability_shares`, ...), token amounts and USD values at an oracle price.
+
+use crate::{
+    math_error,
+    prelude::MarginfiResult,
+    state::marginfi_account::{calc_asset_amount, calc_asset_value},
+};
+use fixed::types::I80F48;
+use std::fmt::{Display, Formatter};
+
+macro_rules! impl_amount {
+    ($name:ident) => {
+        impl $name {
+            pub const ZERO: Self = Self(I80F48::ZERO);
+
+            #[inline]
+            pub fn new(value: I80F48) -> Self {
+                Self(value)
+            }
+
+            #[inline]
+            pub fn value(&self) -> I80F48 {
+                self.0
+            }
+
+            #[inline]
+            pub fn checked_add(self, other: Self) -> MarginfiResult<Self> {
+                Ok(Self(self.0.checked_add(other.0).ok_or_else(math_error!())?))
+            }
+
+            #[inline]
+            pub fn checked_sub(self, other: Self) -> MarginfiResult<Self> {
+                Ok(Self(self.0.checked_sub(other.0).ok_or_else(math_error!())?))
+            }
+
+            #[inline]
+            pub fn is_zero_with_tolerance(&self, tolerance: I80F48) -> bool {
+                self.0.abs() < tolerance
+            }
+
+            #[inline]
+            pub fn is_positive_with_tolerance(&self, tolerance: I80F48) -> bool {
+                self.0 > tolerance
+            }
+        }
+
+        impl std::ops::Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        impl From<$name> for I80F48 {
+            fn from(amount: $name) -> Self {
+                amount.0
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+/// Token amount in native units of a mint (e.g. lamports for SOL).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenAmount(I80F48);
+
+/// Quantity of bank asset or liability shares.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ShareAmount(I80F48);
+
+/// USD value of a token amount, optionally weighted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UsdValue(I80F48);
+
+impl_amount!(TokenAmount);
+impl_amount!(ShareAmount);
+impl_amount!(UsdValue);
+
+impl TokenAmount {
+    /// Instruction input amount, in native token units.
+    #[inline]
+    pub fn from_native(amount: u64) -> Self {
+        Self(I80F48::from_num(amount))
+    }
+
+    /// Native amount to transfer out of the protocol, rounded down.
+    /// Errors on negative or overflowing amounts.
+    #[inline]
+    pub fn to_native_floor(&self) -> MarginfiResult<u64> {
+        Ok(self
+            .0
+            .checked_floor()
+            .ok_or_else(math_error!())?
+            .checked_to_num()
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Native amount to transfer into the protocol, rounded up.
+    /// Errors on negative or overflowing amounts.
+    #[inline]
+    pub fn to_native_ceil(&self) -> MarginfiResult<u64> {
+        Ok(self
+            .0
+            .checked_ceil()
+            .ok_or_else(math_error!())?
+            .checked_to_num()
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Value in USD at `price` (per whole token), optionally weighted.
+    #[inline]
+    pub fn to_usd_value(
+        &self,
+        price: I80F48,
+        mint_decimals: u8,
+        weight: Option<I80F48>,
+    ) -> MarginfiResult<UsdValue> {
+        Ok(UsdValue(calc_asset_value(
+            self.0,
+            price,
+            mint_decimals,
+            weight,
+        )?))
+    }
+}
+
+impl UsdValue {
+    /// Native token amount worth this value at `price` (per whole token).
+    #[inline]
+    pub fn to_token_amount(&self, price: I80F48, mint_decimals: u8) -> MarginfiResult<TokenAmount> {
+        Ok(TokenAmount(calc_asset_amount(
+            self.0,
+            price,
+            mint_decimals,
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::marginfi_group::Bank;
+    use fixed_macro::types::I80F48;
+
+    #[test]
+    fn token_amount_share_round_trip() {
+        let bank = Bank {
+            asset_share_value: I80F48!(1.25).into(),
+            liability_share_value: I80F48!(2).into(),
+            ..Default::default()
+        };
+        let amount = TokenAmount::from_native(1_000_000);
+
+        let shares = bank.asset_shares(amount).unwrap();
+        assert_eq!(shares.value(), I80F48!(800_000));
+        assert_eq!(bank.asset_amount(shares).unwrap(), amount);
+
+        let shares = bank.liability_shares(amount).unwrap();
+        assert_eq!(shares.value(), I80F48!(500_000));
+        assert_eq!(bank.liability_amount(shares).unwrap(), amount);
+    }
+
+    #[test]
+    fn token_amount_native_rounding() {
+        let amount = TokenAmount::new(I80F48!(10.5));
+
+        assert_eq!(amount.to_native_floor().unwrap(), 10);
+        assert_eq!(amount.to_native_ceil().unwrap(), 11);
+
+        assert!(TokenAmount::new(I80F48!(-1)).to_native_floor().is_err());
+    }
+
+    #[test]
+    fn token_amount_usd_round_trip() {
+        // 1.5 whole tokens with 6 decimals at $20
+        let amount = TokenAmount::from_native(1_500_000);
+        let price = I80F48!(20);
+
+        let value = amount.to_usd_value(price, 6, None).unwrap();
+        assert_eq!(value.value(), I80F48!(30));
+
+        let weighted_value = amount.to_usd_value(price, 6, Some(I80F48!(0.5))).unwrap();
+        assert_eq!(weighted_value.value(), I80F48!(15));
+
+        assert_eq!(value.to_token_amount(price, 6).unwrap(), amount);
+    }
+
+    #[test]
+    fn amount_arithmetic() {
+        let a = TokenAmount::from_native(5);
+        let b = TokenAmount::from_native(3);
+
+        assert_eq!(a.checked_sub(b).unwrap(), TokenAmount::from_native(2));
+        assert_eq!(a.checked_add(b).unwrap(), TokenAmount::from_native(8));
+        assert!(b < a);
+        assert_eq!((-a).value(), I80F48!(-5));
+        assert!(TokenAmount::new(I80F48!(0.00001)).is_zero_with_tolerance(I80F48!(0.0001)));
+    }
+}
@@ -0,0 +1,200 @@
+use super::amounts::TokenAmount;
+use crate::{
+    assert_struct_size, check, constants::BPS_SCALE, math_error, prelude::MarginfiError,
+    set_if_some, MarginfiResult,
+};
+use anchor_lang::prelude::*;
+use bytemuck::Zeroable;
+use fixed::types::I80F48;
+
+#[cfg(any(feature = "test", feature = "client"))]
+use type_layout::TypeLayout;
+
+assert_struct_size!(FeeState, 256);
+
+/// Program wide fees, charged on top of the group and bank fees and owed to `global_fee_wallet`.
+///
+/// Single PDA at `[FEE_STATE_SEED]`, administered by `global_fee_admin` independently
+/// of the group admins. Read-only in the charged instructions, which charge no fees
+/// until it is initialized.
+#[account(zero_copy)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct FeeState {
+    /// Can update the program fees, has no power over the groups.
+    pub global_fee_admin: Pubkey, // 32
+    /// Receives the flat fees, and owns the token accounts the program fees are swept to.
+    pub global_fee_wallet: Pubkey, // 32
+    /// Lamports paid by the signer of each charged borrow, straight to `global_fee_wallet`.
+    pub flat_sol_fee: u64, // 8
+    /// Fee on the amount of each charged instruction, in bps.
+    /// Held in the bank liquidity vault until swept with `lending_pool_sweep_program_fees`.
+    pub program_fee_bps: u64, // 8
+    /// The instructions charged with the program fees.
+    /// This is u64 bitfield, where each bit represents a flag.
+    ///
+    /// Flags:
+    /// - FEE_STATE_BORROW_FLAG = 1 << 0 = 1 - `lending_account_borrow`,
+    /// the bps fee is added to the new liability and the flat fee is charged.
+    /// - FEE_STATE_LIQUIDATE_FLAG = 1 << 1 = 2 - `lending_account_liquidate`,
+    /// the bps fee is taken from the collateral received by the liquidator, no flat fee.
+    pub fee_flags: u64, // 8
+    pub bump_seed: u8,
+    pub _padding_bump: [u8; 7],
+    pub _padding: [u64; 20],
+}
+
+pub const FEE_STATE_BORROW_FLAG: u64 = 1 << 0;
+pub const FEE_STATE_LIQUIDATE_FLAG: u64 = 1 << 1;
+
+const FEE_STATE_FLAGS: u64 = FEE_STATE_BORROW_FLAG | FEE_STATE_LIQUIDATE_FLAG;
+
+impl FeeState {
+    /// Program fees of the fee state account, none if it isn't initialized yet.
+    pub fn load_or_default(fee_state: &AccountInfo) -> MarginfiResult<FeeState> {
+        if fee_state.data_is_empty() {
+            return Ok(FeeState::zeroed());
+        }
+
+        Ok(*AccountLoader::<FeeState>::try_from(fee_state)?.load()?)
+    }
+
+    pub fn initialize(
+        &mut self,
+        global_fee_admin: Pubkey,
+        global_fee_wallet: Pubkey,
+        bump_seed: u8,
+    ) {
+        self.global_fee_admin = global_fee_admin;
+        self.global_fee_wallet = global_fee_wallet;
+        self.bump_seed = bump_seed;
+    }
+
+    /// Configure the program fees.
+    /// This function validates config values so the fee state remains in a valid state.
+    /// Any modification of the fee state should happen through this function.
+    pub fn configure(&mut self, config: &FeeStateConfig) -> MarginfiResult {
+        set_if_some!(self.global_fee_admin, config.global_fee_admin);
+        set_if_some!(self.global_fee_wallet, config.global_fee_wallet);
+        set_if_some!(self.flat_sol_fee, config.flat_sol_fee);
+        set_if_some!(self.program_fee_bps, config.program_fee_bps);
+        set_if_some!(self.fee_flags, config.fee_flags);
+
+        check!(
+            self.global_fee_admin.ne(&Pubkey::default())
+                && self.global_fee_wallet.ne(&Pubkey::default()),
+            MarginfiError::InvalidConfig
+        );
+        check!(
+            self.program_fee_bps <= BPS_SCALE,
+            MarginfiError::InvalidConfig
+        );
+        check!(
+            self.fee_flags & !FEE_STATE_FLAGS == 0,
+            MarginfiError::InvalidConfig
+        );
+
+        Ok(())
+    }
+
+    pub fn get_flag(&self, flag: u64) -> bool {
+        self.fee_flags & flag != 0
+    }
+
+    /// Lamports owed by the signer of the instruction charged with `flag`.
+    pub fn flat_fee(&self, flag: u64) -> u64 {
+        if self.get_flag(flag) {
+            self.flat_sol_fee
+        } else {
+            0
+        }
+    }
+
+    /// Program fee on `amount` for the instruction charged with `flag`, rounded up.
+    pub fn calc_program_fee(&self, flag: u64, amount: TokenAmount) -> MarginfiResult<u64> {
+        if !self.get_flag(flag) || self.program_fee_bps == 0 {
+            return Ok(0);
+        }
+
+        Ok(amount
+            .value()
+            .checked_mul(I80F48::from_num(self.program_fee_bps))
+            .and_then(|fee| fee.checked_div(I80F48::from_num(BPS_SCALE)))
+            .and_then(|fee| fee.checked_ceil())
+            .and_then(|fee| fee.checked_to_num())
+            .ok_or_else(math_error!())?)
+    }
+}
+
+#[cfg_attr(any(feature = "test", feature = "client"), derive(TypeLayout))]
+#[derive(AnchorSerialize, AnchorDeserialize, Default, Debug, Clone)]
+pub struct FeeStateConfig {
+    pub global_fee_admin: Option<Pubkey>,
+    pub global_fee_wallet: Option<Pubkey>,
+    /// In lamports.
+    pub flat_sol_fee: Option<u64>,
+    pub program_fee_bps: Option<u64>,
+    pub fee_flags: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_fee_is_only_charged_on_flagged_instructions() {
+        let mut fee_state = FeeState::zeroed();
+        fee_state.initialize(Pubkey::new_unique(), Pubkey::new_unique(), 255);
+        fee_state
+            .configure(&FeeStateConfig {
+                flat_sol_fee: Some(5_000),
+                program_fee_bps: Some(25),
+                fee_flags: Some(FEE_STATE_BORROW_FLAG),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let amount = TokenAmount::from_native(1_000_001);
+
+        assert_eq!(fee_state.flat_fee(FEE_STATE_BORROW_FLAG), 5_000);
+        assert_eq!(fee_state.flat_fee(FEE_STATE_LIQUIDATE_FLAG), 0);
+        // 2500.0025 rounded up
+        assert_eq!(
+            fee_state
+                .calc_program_fee(FEE_STATE_BORROW_FLAG, amount)
+                .unwrap(),
+            2_501
+        );
+        assert_eq!(
+            fee_state
+                .calc_program_fee(FEE_STATE_LIQUIDATE_FLAG, amount)
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn configure_rejects_invalid_fees() {
+        let mut fee_state = FeeState::zeroed();
+        fee_state.initialize(Pubkey::new_unique(), Pubkey::new_unique(), 255);
+
+        for config in [
+            FeeStateConfig {
+                program_fee_bps: Some(BPS_SCALE + 1),
+                ..Default::default()
+            },
+            FeeStateConfig {
+                fee_flags: Some(1 << 2),
+                ..Default::default()
+            },
+            FeeStateConfig {
+                global_fee_wallet: Some(Pubkey::default()),
+                ..Default::default()
+            },
+        ] {
+            assert!(fee_state.clone().configure(&config).is_err());
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use super::{
+    amounts::{ShareAmount, TokenAmount},
+    fee_state::{FeeState, FEE_STATE_BORROW_FLAG},
     marginfi_group::{Bank, RiskTier, WrappedI80F48},
     price::{OraclePriceFeedAdapter, PriceAdapter},
 };
@@ -6,12 +8,12 @@ use crate::{
     assert_struct_size, check,
     constants::{
         BANKRUPT_THRESHOLD, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
-        EMPTY_BALANCE_THRESHOLD, EXP_10_I80F48, MAX_PRICE_AGE_SEC, MIN_EMISSIONS_START_TIME,
-        SECONDS_PER_YEAR, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE, ZERO_AMOUNT_THRESHOLD,
+        EMPTY_BALANCE_THRESHOLD, EXP_10_I80F48, MARGINFI_ACCOUNT_VERSION, MAX_PRICE_AGE_SEC,
+        MIN_EMISSIONS_START_TIME, SECONDS_PER_YEAR, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
+        ZERO_AMOUNT_THRESHOLD,
     },
     debug, math_error,
     prelude::{MarginfiError, MarginfiResult},
-    utils::NumTraitsWithTolerance,
 };
 use anchor_lang::prelude::*;
 use anchor_spl::token::Transfer;
@@ -39,17 +41,147 @@ pub struct MarginfiAccount {
     /// Flags:
     /// - DISABLED_FLAG = 1 << 0 = 1 - This flag indicates that the account is disabled,
     /// and no further actions can be taken on it.
+    /// - AUTO_DELEVERAGE_FLAG = 1 << 1 = 2 - This flag indicates that the account opted in to
+    /// automatic deleveraging by `deleverage_keeper`, see `lending_account_deleverage`.
     pub account_flags: u64, // 8
-    pub _padding: [u64; 63],             // 8 * 63 = 512
+    /// Account health computed by the risk engine at `health_cache.timestamp`,
+    /// refreshed by `lending_account_pulse_health`.
+    pub health_cache: HealthCache, // 80
+    /// Keeper allowed to deleverage the account when `AUTO_DELEVERAGE_FLAG` is set.
+    pub deleverage_keeper: Pubkey, // 32
+    /// Maintenance health ratio (weighted assets / weighted liabilities) below which
+    /// the keeper can deleverage the account, e.g. 1.05.
+    pub deleverage_health_buffer: WrappedI80F48, // 16
+    /// Layout version, 0 for accounts created before layout versioning, see `migrate_account`.
+    pub version: u64, // 8
+    pub _padding: [u64; 46],             // 8 * 46 = 368
 }
 
 pub const DISABLED_FLAG: u64 = 1 << 0;
+pub const AUTO_DELEVERAGE_FLAG: u64 = 1 << 1;
+
+assert_struct_size!(SubAccountCounter, 128);
+/// Sub-accounts created by an authority in a group, see `marginfi_account_initialize_sub_account`.
+///
+/// Sub-accounts are derived from `[MARGINFI_ACCOUNT_SEED, group, authority, index]`, with indexes
+/// `0..account_count`.
+#[account(zero_copy)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+pub struct SubAccountCounter {
+    pub group: Pubkey,
+    pub authority: Pubkey,
+    pub account_count: u64,
+    pub _padding: [u64; 7],
+}
+
+impl SubAccountCounter {
+    pub fn initialize(&mut self, group: Pubkey, authority: Pubkey) {
+        self.group = group;
+        self.authority = authority;
+    }
+}
+
+assert_struct_size!(HealthCache, 80);
+#[zero_copy]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+pub struct HealthCache {
+    /// Initial weighted asset value in USD
+    pub asset_value: WrappedI80F48,
+    /// Initial weighted liability value in USD
+    pub liability_value: WrappedI80F48,
+    /// Maintenance weighted asset value in USD
+    pub asset_value_maint: WrappedI80F48,
+    /// Maintenance weighted liability value in USD
+    pub liability_value_maint: WrappedI80F48,
+    pub timestamp: i64,
+    /// Flags:
+    /// - HEALTH_CACHE_HEALTHY_FLAG = 1 << 0 = 1 - The account passes the initial health check.
+    /// - HEALTH_CACHE_LIQUIDATABLE_FLAG = 1 << 1 = 2 - The account fails the maintenance health check.
+    pub flags: u64,
+}
+
+pub const HEALTH_CACHE_HEALTHY_FLAG: u64 = 1 << 0;
+pub const HEALTH_CACHE_LIQUIDATABLE_FLAG: u64 = 1 << 1;
+
+impl HealthCache {
+    pub fn new(
+        (asset_value, liability_value): (I80F48, I80F48),
+        (asset_value_maint, liability_value_maint): (I80F48, I80F48),
+        timestamp: i64,
+    ) -> Self {
+        let mut flags = 0;
+
+        if asset_value >= liability_value {
+            flags |= HEALTH_CACHE_HEALTHY_FLAG;
+        }
+
+        if asset_value_maint < liability_value_maint {
+            flags |= HEALTH_CACHE_LIQUIDATABLE_FLAG;
+        }
+
+        Self {
+            asset_value: asset_value.into(),
+            liability_value: liability_value.into(),
+            asset_value_maint: asset_value_maint.into(),
+            liability_value_maint: liability_value_maint.into(),
+            timestamp,
+            flags,
+        }
+    }
+
+    pub fn get_flag(&self, flag: u64) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Ratio of maintenance weighted assets to maintenance weighted liabilities,
+    /// the account is liquidatable below 1. `None` if the account has no liabilities.
+    pub fn health_factor(&self) -> Option<I80F48> {
+        let liability_value_maint: I80F48 = self.liability_value_maint.into();
+
+        if liability_value_maint == I80F48::ZERO {
+            return None;
+        }
+
+        I80F48::from(self.asset_value_maint).checked_div(liability_value_maint)
+    }
+}
 
 impl MarginfiAccount {
     /// Set the initial data for the marginfi account.
     pub fn initialize(&mut self, group: Pubkey, authority: Pubkey) {
         self.authority = authority;
         self.group = group;
+        self.version = MARGINFI_ACCOUNT_VERSION;
+    }
+
+    /// Upgrade the account layout to `MARGINFI_ACCOUNT_VERSION`, one version at a time.
+    pub fn migrate(&mut self) -> MarginfiResult {
+        check!(
+            self.version <= MARGINFI_ACCOUNT_VERSION,
+            MarginfiError::AccountMigrationError,
+            "Unknown account version {}",
+            self.version
+        );
+
+        while self.version < MARGINFI_ACCOUNT_VERSION {
+            match self.version {
+                // Created before layout versioning, same layout as version 1
+                0 => {}
+                _ => return err!(MarginfiError::AccountMigrationError),
+            }
+
+            self.version += 1;
+        }
+
+        Ok(())
     }
 
     pub fn get_remaining_accounts_len(&self) -> usize {
@@ -66,9 +198,44 @@ impl MarginfiAccount {
         self.account_flags |= flag;
     }
 
+    pub fn unset_flag(&mut self, flag: u64) {
+        msg!("Unsetting account flag {:b}", flag);
+        self.account_flags &= !flag;
+    }
+
     pub fn get_flag(&self, flag: u64) -> bool {
         self.account_flags & flag != 0
     }
+
+    /// Opt in to automatic deleveraging by `keeper` below `health_buffer`, or opt out with `None`.
+    pub fn configure_auto_deleverage(
+        &mut self,
+        config: Option<(Pubkey, I80F48)>,
+    ) -> MarginfiResult {
+        match config {
+            Some((keeper, health_buffer)) => {
+                check!(
+                    health_buffer > I80F48::ONE,
+                    MarginfiError::InvalidDeleverageHealthBuffer
+                );
+
+                self.deleverage_keeper = keeper;
+                self.deleverage_health_buffer = health_buffer.into();
+                self.set_flag(AUTO_DELEVERAGE_FLAG);
+            }
+            None => {
+                self.deleverage_keeper = Pubkey::default();
+                self.deleverage_health_buffer = I80F48::ZERO.into();
+                self.unset_flag(AUTO_DELEVERAGE_FLAG);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn is_deleverage_keeper(&self, signer: &Pubkey) -> bool {
+        self.get_flag(AUTO_DELEVERAGE_FLAG) && self.deleverage_keeper == *signer
+    }
 }
 
 #[derive(Debug)]
@@ -93,97 +260,109 @@ pub enum WeightType {
     Equity,
 }
 
-pub struct BankAccountWithPriceFeed<'a, 'b> {
-    bank: AccountInfo<'b>,
-    price_feed: Box<OraclePriceFeedAdapter>,
+/// Price and risk components of an active balance, loaded once from its bank and oracle.
+///
+/// The risk engine checks only do arithmetic on these, instead of re-loading the bank and
+/// re-deriving the oracle price range of every balance for every check.
+#[derive(Clone, Copy)]
+pub struct BankAccountWithPriceFeed<'a> {
     balance: &'a Balance,
+    price: I80F48,
+    lowest_price: I80F48,
+    highest_price: I80F48,
+    asset_amount: TokenAmount,
+    liability_amount: TokenAmount,
+    mint_decimals: u8,
+    /// Discounted when the bank total asset value is over its init limit.
+    asset_weight_init: I80F48,
+    liability_weight_init: I80F48,
+    asset_weight_maint: I80F48,
+    liability_weight_maint: I80F48,
+    risk_tier: RiskTier,
 }
 
+/// Stack allocated cache of the active balances of an account, keyed by bank index,
+/// i.e. the position of the bank of the balance in the remaining accounts.
+pub type PriceCache<'a> = [Option<BankAccountWithPriceFeed<'a>>; MAX_LENDING_ACCOUNT_BALANCES];
+
 pub enum BalanceSide {
     Assets,
     Liabilities,
 }
 
-impl<'a, 'b> BankAccountWithPriceFeed<'a, 'b> {
+impl<'a> BankAccountWithPriceFeed<'a> {
     pub fn load(
         lending_account: &'a LendingAccount,
-        remaining_ais: &[AccountInfo<'b>],
-    ) -> MarginfiResult<Vec<BankAccountWithPriceFeed<'a, 'b>>> {
-        let active_balances = lending_account
+        remaining_ais: &[AccountInfo],
+    ) -> MarginfiResult<PriceCache<'a>> {
+        let active_balances_len = lending_account
             .balances
             .iter()
             .filter(|balance| balance.active)
-            .collect::<Vec<_>>();
+            .count();
 
-        msg!("Expecting {} remaining accounts", active_balances.len() * 2);
+        msg!("Expecting {} remaining accounts", active_balances_len * 2);
         msg!("Got {} remaining accounts", remaining_ais.len());
 
         check!(
-            active_balances.len() * 2 == remaining_ais.len(),
+            active_balances_len * 2 == remaining_ais.len(),
             MarginfiError::MissingPythOrBankAccount
         );
 
         let current_timestamp = Clock::get()?.unix_timestamp;
+        let mut price_cache: PriceCache<'a> = [None; MAX_LENDING_ACCOUNT_BALANCES];
 
-        active_balances
+        for (bank_index, (balance, ais)) in lending_account
+            .balances
             .iter()
+            .filter(|balance| balance.active)
+            .zip(remaining_ais.chunks_exact(2))
             .enumerate()
-            .map(|(i, balance)| {
-                let bank_index = i * 2;
-                let oracle_ai_idx = bank_index + 1;
-
-                let bank_ai = remaining_ais.get(bank_index).unwrap();
-
-                check!(
-                    balance.bank_pk.eq(bank_ai.key),
-                    MarginfiError::InvalidBankAccount
-                );
-
-                let price_adapter = {
-                    let oracle_ais = &remaining_ais[oracle_ai_idx..oracle_ai_idx + 1];
-                    let bank_al = AccountLoader::<Bank>::try_from(bank_ai)?;
-                    let bank = bank_al.load()?;
-
-                    Box::new(OraclePriceFeedAdapter::try_from_bank_config(
-                        &bank.config,
-                        oracle_ais,
-                        current_timestamp,
-                        MAX_PRICE_AGE_SEC,
-                    )?)
-                };
+        {
+            price_cache[bank_index] = Some(Self::load_balance(
+                balance,
+                &ais[0],
+                &ais[1..],
+                current_timestamp,
+            )?);
+        }
 
-                Ok(BankAccountWithPriceFeed {
-                    bank: bank_ai.clone(),
-                    price_feed: price_adapter,
-                    balance,
-                })
-            })
-            .collect::<Result<Vec<_>>>()
+        Ok(price_cache)
     }
 
-    #[inline(always)]
-    pub fn calc_weighted_assets_and_liabilities_values(
-        &self,
-        weight_type: WeightType,
-    ) -> MarginfiResult<(I80F48, I80F48)> {
-        let (worst_price, best_price) = self.price_feed.get_price_range()?;
-        let bank_al = AccountLoader::<Bank>::try_from(&self.bank)?;
+    fn load_balance(
+        balance: &'a Balance,
+        bank_ai: &AccountInfo,
+        oracle_ais: &[AccountInfo],
+        current_timestamp: i64,
+    ) -> MarginfiResult<Self> {
+        check!(
+            balance.bank_pk.eq(bank_ai.key),
+            MarginfiError::InvalidBankAccount
+        );
+
+        let bank_al = AccountLoader::<Bank>::try_from(bank_ai)?;
         let bank = bank_al.load()?;
-        let (mut asset_weight, liability_weight) = bank.config.get_weights(weight_type);
-        let mint_decimals = bank.mint_decimals;
 
-        let asset_amount = bank.get_asset_amount(self.balance.asset_shares.into())?;
-        let liability_amount = bank.get_liability_amount(self.balance.liability_shares.into())?;
+        let price_feed = OraclePriceFeedAdapter::try_from_bank_config(
+            &bank.config,
+            oracle_ais,
+            current_timestamp,
+            MAX_PRICE_AGE_SEC,
+        )?;
+        let price = price_feed.get_price()?;
+        let (lowest_price, highest_price) = price_feed.get_price_range()?;
 
-        if matches!(weight_type, WeightType::Initial)
-            && bank.config.total_asset_value_init_limit != TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE
-        {
-            let bank_total_assets_value = calc_asset_value(
-                bank.get_asset_amount(bank.total_asset_shares.into())?,
-                worst_price,
-                mint_decimals,
-                None,
-            )?;
+        let (mut asset_weight_init, liability_weight_init) =
+            bank.config.get_weights(WeightType::Initial);
+        let (asset_weight_maint, liability_weight_maint) =
+            bank.config.get_weights(WeightType::Maintenance);
+
+        if bank.config.total_asset_value_init_limit != TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE {
+            let bank_total_assets_value = bank
+                .asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+                .to_usd_value(lowest_price, bank.mint_decimals, None)?
+                .value();
 
             let total_asset_value_init_limit =
                 I80F48::from_num(bank.config.total_asset_value_init_limit);
@@ -206,20 +385,51 @@ impl<'a, 'b> BankAccountWithPriceFeed<'a, 'b> {
                     total_asset_value_init_limit
                 );
 
-                asset_weight = asset_weight
+                asset_weight_init = asset_weight_init
                     .checked_mul(discount)
                     .ok_or_else(math_error!())?;
             }
         }
 
+        Ok(Self {
+            balance,
+            price,
+            lowest_price,
+            highest_price,
+            asset_amount: bank.asset_amount(ShareAmount::new(balance.asset_shares.into()))?,
+            liability_amount: bank
+                .liability_amount(ShareAmount::new(balance.liability_shares.into()))?,
+            mint_decimals: bank.mint_decimals,
+            asset_weight_init,
+            liability_weight_init,
+            asset_weight_maint,
+            liability_weight_maint,
+            risk_tier: bank.config.risk_tier,
+        })
+    }
+
+    #[inline(always)]
+    pub fn calc_weighted_assets_and_liabilities_values(
+        &self,
+        weight_type: WeightType,
+    ) -> MarginfiResult<(I80F48, I80F48)> {
+        let (asset_weight, liability_weight) = match weight_type {
+            WeightType::Initial => (self.asset_weight_init, self.liability_weight_init),
+            WeightType::Maintenance => (self.asset_weight_maint, self.liability_weight_maint),
+            WeightType::Equity => (I80F48::ONE, I80F48::ONE),
+        };
+
         Ok((
-            calc_asset_value(asset_amount, worst_price, mint_decimals, Some(asset_weight))?,
-            calc_asset_value(
-                liability_amount,
-                best_price,
-                mint_decimals,
-                Some(liability_weight),
-            )?,
+            self.asset_amount
+                .to_usd_value(self.lowest_price, self.mint_decimals, Some(asset_weight))?
+                .value(),
+            self.liability_amount
+                .to_usd_value(
+                    self.highest_price,
+                    self.mint_decimals,
+                    Some(liability_weight),
+                )?
+                .value(),
         ))
     }
 
@@ -249,12 +459,9 @@ pub fn calc_asset_value(
         asset_amount
     };
 
-    #[cfg(target_os = "solana")]
-    msg!(
+    debug!(
         "weighted_asset_qt: {}, price: {}, expo: {}",
-        weighted_asset_amount,
-        price,
-        mint_decimals
+        weighted_asset_amount, price, mint_decimals
     );
 
     let asset_value = weighted_asset_amount
@@ -299,21 +506,33 @@ impl RiskRequirementType {
     }
 }
 
-pub struct RiskEngine<'a, 'b> {
-    bank_accounts_with_price: Vec<BankAccountWithPriceFeed<'a, 'b>>,
+pub struct RiskEngine<'a> {
+    price_cache: PriceCache<'a>,
 }
 
-impl<'a, 'b> RiskEngine<'a, 'b> {
+impl<'a> RiskEngine<'a> {
     pub fn new(
         marginfi_account: &'a MarginfiAccount,
-        remaining_ais: &[AccountInfo<'b>],
+        remaining_ais: &[AccountInfo],
     ) -> MarginfiResult<Self> {
-        let bank_accounts_with_price =
+        let price_cache =
             BankAccountWithPriceFeed::load(&marginfi_account.lending_account, remaining_ais)?;
 
-        Ok(Self {
-            bank_accounts_with_price,
-        })
+        Ok(Self { price_cache })
+    }
+
+    fn bank_accounts_with_price(&self) -> impl Iterator<Item = &BankAccountWithPriceFeed<'a>> {
+        self.price_cache.iter().flatten()
+    }
+
+    fn find_bank_account_with_price(
+        &self,
+        bank_pk: &Pubkey,
+    ) -> MarginfiResult<&BankAccountWithPriceFeed<'a>> {
+        Ok(self
+            .bank_accounts_with_price()
+            .find(|a| a.balance.bank_pk == *bank_pk)
+            .ok_or(MarginfiError::LendingAccountBalanceNotFound)?)
     }
 
     /// Returns the total assets and liabilities of the account in the form of (assets, liabilities)
@@ -324,7 +543,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         let mut total_assets = I80F48::ZERO;
         let mut total_liabilities = I80F48::ZERO;
 
-        for a in &self.bank_accounts_with_price {
+        for a in self.bank_accounts_with_price() {
             let (assets, liabilities) =
                 a.calc_weighted_assets_and_liabilities_values(requirement_type.to_weight_type())?;
 
@@ -349,6 +568,11 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
             .ok_or_else(math_error!())?)
     }
 
+    /// Oracle price of the bank of the account balance in `bank_pk`.
+    pub fn get_bank_price(&self, bank_pk: &Pubkey) -> MarginfiResult<I80F48> {
+        Ok(self.find_bank_account_with_price(bank_pk)?.price)
+    }
+
     pub fn check_account_health(&self, requirement_type: RiskRequirementType) -> MarginfiResult {
         let (total_weighted_assets, total_weighted_liabilities) =
             self.get_account_health_components(requirement_type)?;
@@ -376,11 +600,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         &self,
         bank_pk: &Pubkey,
     ) -> MarginfiResult<I80F48> {
-        let liability_bank_balance = self
-            .bank_accounts_with_price
-            .iter()
-            .find(|a| a.balance.bank_pk == *bank_pk)
-            .ok_or(MarginfiError::LendingAccountBalanceNotFound)?;
+        let liability_bank_balance = self.find_bank_account_with_price(bank_pk)?;
 
         check!(
             liability_bank_balance
@@ -429,11 +649,7 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         bank_pk: &Pubkey,
         pre_liquidation_health: I80F48,
     ) -> MarginfiResult<I80F48> {
-        let liability_bank_balance = self
-            .bank_accounts_with_price
-            .iter()
-            .find(|a| a.balance.bank_pk == *bank_pk)
-            .unwrap();
+        let liability_bank_balance = self.find_bank_account_with_price(bank_pk)?;
 
         check!(
             liability_bank_balance
@@ -477,6 +693,91 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         Ok(account_health)
     }
 
+    /// Maintenance health ratio of the account, weighted assets over weighted liabilities.
+    fn get_maintenance_health_ratio(&self) -> MarginfiResult<I80F48> {
+        let (assets, liabs) =
+            self.get_account_health_components(RiskRequirementType::Maintenance)?;
+
+        check!(liabs > I80F48::ZERO, MarginfiError::IllegalDeleverage);
+
+        Ok(assets.checked_div(liabs).ok_or_else(math_error!())?)
+    }
+
+    /// Checks
+    /// 1. Account has an outstanding liability for the provided liability bank
+    /// 2. Account maintenance health ratio is below the deleverage health buffer
+    pub fn check_pre_deleverage_condition_and_get_health_ratio(
+        &self,
+        bank_pk: &Pubkey,
+        health_buffer: I80F48,
+    ) -> MarginfiResult<I80F48> {
+        let liability_bank_balance = self.find_bank_account_with_price(bank_pk)?;
+
+        check!(
+            liability_bank_balance
+                .is_empty(BalanceSide::Liabilities)
+                .not(),
+            MarginfiError::IllegalDeleverage
+        );
+
+        let health_ratio = self.get_maintenance_health_ratio()?;
+
+        msg!(
+            "pre_deleverage_health_ratio: {}, health_buffer: {}",
+            health_ratio,
+            health_buffer
+        );
+
+        check!(
+            health_ratio < health_buffer,
+            MarginfiError::IllegalDeleverage,
+            "Account above deleverage health buffer"
+        );
+
+        Ok(health_ratio)
+    }
+
+    /// Check that the deleverage improved the account health ratio without bringing it
+    /// above the health buffer, and that the liability was only partially repaid.
+    pub fn check_post_deleverage_condition_and_get_health_ratio(
+        &self,
+        bank_pk: &Pubkey,
+        health_buffer: I80F48,
+        pre_health_ratio: I80F48,
+    ) -> MarginfiResult<I80F48> {
+        let liability_bank_balance = self.find_bank_account_with_price(bank_pk)?;
+
+        check!(
+            liability_bank_balance
+                .is_empty(BalanceSide::Liabilities)
+                .not(),
+            MarginfiError::IllegalDeleverage,
+            "Liability payoff too severe"
+        );
+
+        let health_ratio = self.get_maintenance_health_ratio()?;
+
+        msg!(
+            "health_ratio: {}, pre_deleverage_health_ratio: {}",
+            health_ratio,
+            pre_health_ratio
+        );
+
+        check!(
+            health_ratio <= health_buffer,
+            MarginfiError::IllegalDeleverage,
+            "Deleverage too severe"
+        );
+
+        check!(
+            health_ratio > pre_health_ratio,
+            MarginfiError::IllegalDeleverage,
+            "Post deleverage health worse"
+        );
+
+        Ok(health_ratio)
+    }
+
     /// Check that the account is in a bankrupt state.
     /// Account needs to be insolvent and total value of assets need to be below the bankruptcy threshold.
     pub fn check_account_bankrupt(&self) -> MarginfiResult {
@@ -501,27 +802,27 @@ impl<'a, 'b> RiskEngine<'a, 'b> {
         Ok(())
     }
 
+    /// An account borrowing an isolated asset can't have any other liabilities.
     fn check_account_risk_tiers(&self) -> MarginfiResult {
-        let balances_with_liablities = self
-            .bank_accounts_with_price
-            .iter()
-            .filter(|a| a.balance.is_empty(BalanceSide::Liabilities).not());
+        let mut n_balances_with_liablities = 0;
+        let mut is_in_isolated_risk_tier = false;
 
-        let n_balances_with_liablities = balances_with_liablities.clone().count();
+        for a in self
+            .bank_accounts_with_price()
+            .filter(|a| a.is_empty(BalanceSide::Liabilities).not())
+        {
+            n_balances_with_liablities += 1;
 
-        let is_in_isolated_risk_tier = balances_with_liablities.clone().any(|a| {
-            AccountLoader::<Bank>::try_from(&a.bank)
-                .unwrap()
-                .load()
-                .unwrap()
-                .config
-                .risk_tier
-                == RiskTier::Isolated
-        });
+            if a.risk_tier == RiskTier::Isolated {
+                is_in_isolated_risk_tier = true;
+            }
+        }
 
         check!(
             !is_in_isolated_risk_tier || n_balances_with_liablities == 1,
-            MarginfiError::IsolatedAccountIllegalState
+            MarginfiError::IsolatedAccountIllegalState,
+            "Isolated liability held together with {} other liabilities",
+            n_balances_with_liablities - 1
         );
 
         Ok(())
@@ -538,13 +839,129 @@ assert_struct_size!(LendingAccount, 1728);
 )]
 pub struct LendingAccount {
     pub balances: [Balance; MAX_LENDING_ACCOUNT_BALANCES], // 104 * 16 = 1664
-    pub _padding: [u64; 8],                                // 8 * 8 = 64
+    /// Referrer of the account, registered in the group and set at initialization, earning a share
+    /// of the protocol fees paid by its liabilities. `Pubkey::default()` when the account has no
+    /// referrer.
+    pub referrer: Pubkey, // 32
+    pub _padding: [u64; 4],                                // 8 * 4 = 32
 }
 
 impl LendingAccount {
     pub fn get_first_empty_balance(&self) -> Option<usize> {
         self.balances.iter().position(|b| !b.active)
     }
+
+    /// Borrowing from an isolated bank requires the bank to be the account's only liability.
+    ///
+    /// Liabilities already held in other isolated banks are caught by the health check,
+    /// which loads every bank the account has a balance in.
+    pub fn check_isolated_borrow(&self, bank_pk: &Pubkey, bank: &Bank) -> MarginfiResult {
+        if bank.config.risk_tier != RiskTier::Isolated {
+            return Ok(());
+        }
+
+        let has_other_liabilities = self
+            .balances
+            .iter()
+            .any(|b| b.active && !b.bank_pk.eq(bank_pk) && !b.is_empty(BalanceSide::Liabilities));
+
+        check!(
+            !has_other_liabilities,
+            MarginfiError::IsolatedAccountIllegalState,
+            "Can't borrow an isolated asset with other outstanding liabilities"
+        );
+
+        Ok(())
+    }
+
+    /// Moves the liability balance in `bank_pk` to `destination`, along with its outstanding emissions.
+    pub fn split_liability(
+        &mut self,
+        destination: &mut LendingAccount,
+        bank_pk: &Pubkey,
+    ) -> MarginfiResult {
+        let balance = self.find_balance_mut(bank_pk)?;
+
+        check!(
+            matches!(balance.get_side(), Some(BalanceSide::Liabilities)),
+            MarginfiError::InvalidPositionSplit,
+            "Only a liability balance can be split out"
+        );
+
+        let moved_balance = *balance;
+        *balance = Balance::empty_deactivated();
+
+        destination.insert_balance(moved_balance)
+    }
+
+    /// Moves `asset_shares` of the asset balance in `bank_pk` to `destination`.
+    ///
+    /// Both balances keep the same `last_update`, so emissions accrued since then on the moved
+    /// shares are claimed by `destination`, already outstanding emissions stay on the source balance.
+    /// A balance left with dust is moved as a whole.
+    pub fn split_asset_shares(
+        &mut self,
+        destination: &mut LendingAccount,
+        bank_pk: &Pubkey,
+        asset_shares: I80F48,
+    ) -> MarginfiResult {
+        let balance = self.find_balance_mut(bank_pk)?;
+        let current_asset_shares = I80F48::from(balance.asset_shares);
+
+        check!(
+            matches!(balance.get_side(), Some(BalanceSide::Assets))
+                && asset_shares > I80F48::ZERO
+                && asset_shares <= current_asset_shares,
+            MarginfiError::InvalidPositionSplit,
+            "Invalid asset shares to split out"
+        );
+
+        let remaining_asset_shares = current_asset_shares
+            .checked_sub(asset_shares)
+            .ok_or_else(math_error!())?;
+
+        let moved_balance = if remaining_asset_shares < EMPTY_BALANCE_THRESHOLD {
+            let moved_balance = *balance;
+            *balance = Balance::empty_deactivated();
+            moved_balance
+        } else {
+            balance.asset_shares = remaining_asset_shares.into();
+
+            Balance {
+                asset_shares: asset_shares.into(),
+                emissions_outstanding: I80F48::ZERO.into(),
+                ..*balance
+            }
+        };
+
+        destination.insert_balance(moved_balance)
+    }
+
+    fn find_balance_mut(&mut self, bank_pk: &Pubkey) -> MarginfiResult<&mut Balance> {
+        self.balances
+            .iter_mut()
+            .find(|balance| balance.active && balance.bank_pk.eq(bank_pk))
+            .ok_or_else(|| error!(MarginfiError::BankAccoutNotFound))
+    }
+
+    fn insert_balance(&mut self, balance: Balance) -> MarginfiResult {
+        check!(
+            !self
+                .balances
+                .iter()
+                .any(|b| b.active && b.bank_pk.eq(&balance.bank_pk)),
+            MarginfiError::InvalidPositionSplit,
+            "Balance already split out"
+        );
+
+        let empty_index = self
+            .get_first_empty_balance()
+            .ok_or_else(|| error!(MarginfiError::LendingAccountBalanceSlotsFull))?;
+
+        self.balances[empty_index] = balance;
+
+        Ok(())
+    }
 }
 
 #[cfg(any(feature = "test", feature = "client"))]
@@ -590,24 +1007,36 @@ impl Balance {
         shares < EMPTY_BALANCE_THRESHOLD
     }
 
-    pub fn change_asset_shares(&mut self, delta: I80F48) -> MarginfiResult {
+    pub fn change_asset_shares(&mut self, delta: ShareAmount) -> MarginfiResult {
         let asset_shares: I80F48 = self.asset_shares.into();
         self.asset_shares = asset_shares
-            .checked_add(delta)
+            .checked_add(delta.value())
             .ok_or_else(math_error!())?
             .into();
         Ok(())
     }
 
-    pub fn change_liability_shares(&mut self, delta: I80F48) -> MarginfiResult {
+    pub fn change_liability_shares(&mut self, delta: ShareAmount) -> MarginfiResult {
         let liability_shares: I80F48 = self.liability_shares.into();
         self.liability_shares = liability_shares
-            .checked_add(delta)
+            .checked_add(delta.value())
             .ok_or_else(math_error!())?
             .into();
         Ok(())
     }
 
+    /// A balance can hold either an asset or a liability, never both at the same time.
+    /// Deposits and borrows net against the opposite side of the balance first.
+    pub fn check_netted(&self) -> MarginfiResult {
+        check!(
+            self.is_empty(BalanceSide::Assets) || self.is_empty(BalanceSide::Liabilities),
+            MarginfiError::IllegalBalanceState,
+            "Balance holds both assets and liabilities"
+        );
+
+        Ok(())
+    }
+
     pub fn close(&mut self) -> MarginfiResult {
         check!(
             I80F48::from(self.emissions_outstanding) < I80F48::ONE,
@@ -645,6 +1074,9 @@ impl Balance {
 pub struct BankAccountWrapper<'a> {
     pub balance: &'a mut Balance,
     pub bank: &'a mut Bank,
+    /// Referrer of the account, accruing referral fees on its liabilities,
+    /// `Pubkey::default()` if it has none.
+    pub referrer: Pubkey,
 }
 
 impl<'a> BankAccountWrapper<'a> {
@@ -654,13 +1086,18 @@ impl<'a> BankAccountWrapper<'a> {
         bank: &'a mut Bank,
         lending_account: &'a mut LendingAccount,
     ) -> MarginfiResult<BankAccountWrapper<'a>> {
+        let referrer = lending_account.referrer;
         let balance = lending_account
             .balances
             .iter_mut()
             .find(|balance| balance.active && balance.bank_pk.eq(bank_pk))
             .ok_or_else(|| error!(MarginfiError::BankAccoutNotFound))?;
 
-        Ok(Self { balance, bank })
+        Ok(Self {
+            balance,
+            bank,
+            referrer,
+        })
     }
 
     // Find existing user lending account balance by bank address.
@@ -670,6 +1107,7 @@ impl<'a> BankAccountWrapper<'a> {
         bank: &'a mut Bank,
         lending_account: &'a mut LendingAccount,
     ) -> MarginfiResult<BankAccountWrapper<'a>> {
+        let referrer = lending_account.referrer;
         let balance_index = lending_account
             .balances
             .iter()
@@ -682,7 +1120,11 @@ impl<'a> BankAccountWrapper<'a> {
                     .get_mut(balance_index)
                     .ok_or_else(|| error!(MarginfiError::BankAccoutNotFound))?;
 
-                Ok(Self { balance, bank })
+                Ok(Self {
+                    balance,
+                    bank,
+                    referrer,
+                })
             }
             None => {
                 let empty_index = lending_account
@@ -702,6 +1144,7 @@ impl<'a> BankAccountWrapper<'a> {
                 Ok(Self {
                     balance: lending_account.balances.get_mut(empty_index).unwrap(),
                     bank,
+                    referrer,
                 })
             }
         }
@@ -710,36 +1153,78 @@ impl<'a> BankAccountWrapper<'a> {
     // ------------ Borrow / Lend primitives
 
     /// Deposit an asset, will repay any outstanding liabilities.
-    pub fn deposit(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn deposit(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.increase_balance_internal(amount, BalanceIncreaseType::Any)
     }
 
     /// Repay a liability, will error if there is not enough liability - depositing is not allowed.
-    pub fn repay(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn repay(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.increase_balance_internal(amount, BalanceIncreaseType::RepayOnly)
     }
 
     /// Withdraw an asset, will error if there is not enough asset - borrowing is not allowed.
-    pub fn withdraw(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn withdraw(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::WithdrawOnly)
     }
 
     /// Incur a borrow, will withdraw any existing assets.
-    pub fn borrow(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn borrow(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::Any)
     }
 
+    /// Borrow `amount`, charging the origination fee of the bank and the program fee of
+    /// `fee_state` on the part of it which is not covered by an existing asset and becomes a liability.
+    ///
+    /// The fees are added to the liability, and to the outstanding group and program fees.
+    /// They stay in the liquidity vault until collected and swept.
+    /// Returns the origination fee and the program fee.
+    pub fn borrow_with_fees(
+        &mut self,
+        amount: u64,
+        fee_state: &FeeState,
+    ) -> MarginfiResult<(u64, u64)> {
+        let current_asset_amount = self
+            .bank
+            .asset_amount(ShareAmount::new(self.balance.asset_shares.into()))?;
+        let liability_amount = max(
+            TokenAmount::from_native(amount).checked_sub(current_asset_amount)?,
+            TokenAmount::ZERO,
+        );
+        let origination_fee = self.bank.calc_origination_fee(liability_amount)?;
+        let program_fee = fee_state.calc_program_fee(FEE_STATE_BORROW_FLAG, liability_amount)?;
+
+        self.borrow(TokenAmount::from_native(
+            amount
+                .checked_add(origination_fee)
+                .and_then(|amount| amount.checked_add(program_fee))
+                .ok_or_else(math_error!())?,
+        ))?;
+
+        if origination_fee > 0 {
+            self.bank.collected_group_fees_outstanding = {
+                I80F48::from(self.bank.collected_group_fees_outstanding)
+                    .checked_add(I80F48::from_num(origination_fee))
+                    .ok_or_else(math_error!())?
+            }
+            .into();
+        }
+
+        self.bank.record_program_fee(program_fee)?;
+
+        Ok((origination_fee, program_fee))
+    }
+
     // ------------ Hybrid operations for seamless repay + deposit / withdraw + borrow
 
     /// Repay liability and deposit/increase asset depending on
     /// the specified deposit amount and the existing balance.
-    pub fn increase_balance(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn increase_balance(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.increase_balance_internal(amount, BalanceIncreaseType::Any)
     }
 
     /// Withdraw asset and create/increase liability depending on
     /// the specified deposit amount and the existing balance.
-    pub fn decrease_balance(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn decrease_balance(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::Any)
     }
 
@@ -748,7 +1233,7 @@ impl<'a> BankAccountWrapper<'a> {
     ///
     /// This function will also bypass borrow limits
     /// so liquidations can happen in banks with maxed out borrows.
-    pub fn decrease_balance_in_liquidation(&mut self, amount: I80F48) -> MarginfiResult {
+    pub fn decrease_balance_in_liquidation(&mut self, amount: TokenAmount) -> MarginfiResult {
         self.decrease_balance_internal(amount, BalanceDecreaseType::BypassBorrowLimit)
     }
 
@@ -761,10 +1246,10 @@ impl<'a> BankAccountWrapper<'a> {
 
         bank.assert_operational_mode(None)?;
 
-        let total_asset_shares: I80F48 = balance.asset_shares.into();
-        let current_asset_amount = bank.get_asset_amount(total_asset_shares)?;
+        let total_asset_shares = ShareAmount::new(balance.asset_shares.into());
+        let current_asset_amount = bank.asset_amount(total_asset_shares)?;
         let current_liability_amount =
-            bank.get_liability_amount(balance.liability_shares.into())?;
+            bank.liability_amount(ShareAmount::new(balance.liability_shares.into()))?;
 
         debug!(
             "Withdrawing all: {} of {} in {}",
@@ -786,22 +1271,18 @@ impl<'a> BankAccountWrapper<'a> {
 
         bank.check_utilization_ratio()?;
 
-        let spl_withdraw_amount = current_asset_amount
-            .checked_floor()
-            .ok_or_else(math_error!())?;
+        let spl_withdraw_amount = current_asset_amount.to_native_floor()?;
 
         bank.collected_insurance_fees_outstanding = {
             current_asset_amount
-                .checked_sub(spl_withdraw_amount)
-                .ok_or_else(math_error!())?
+                .checked_sub(TokenAmount::from_native(spl_withdraw_amount))?
+                .value()
                 .checked_add(bank.collected_insurance_fees_outstanding.into())
                 .ok_or_else(math_error!())?
                 .into()
         };
 
-        Ok(spl_withdraw_amount
-            .checked_to_num()
-            .ok_or_else(math_error!())?)
+        Ok(spl_withdraw_amount)
     }
 
     /// Repay existing liability in full - will error if there is no liability.
@@ -813,9 +1294,10 @@ impl<'a> BankAccountWrapper<'a> {
 
         bank.assert_operational_mode(None)?;
 
-        let total_liability_shares: I80F48 = balance.liability_shares.into();
-        let current_liability_amount = bank.get_liability_amount(total_liability_shares)?;
-        let current_asset_amount = bank.get_asset_amount(balance.asset_shares.into())?;
+        let total_liability_shares = ShareAmount::new(balance.liability_shares.into());
+        let current_liability_amount = bank.liability_amount(total_liability_shares)?;
+        let current_asset_amount =
+            bank.asset_amount(ShareAmount::new(balance.asset_shares.into()))?;
 
         debug!(
             "Repaying all: {} of {} in {}",
@@ -835,22 +1317,18 @@ impl<'a> BankAccountWrapper<'a> {
         balance.close()?;
         bank.change_liability_shares(-total_liability_shares, false)?;
 
-        let spl_deposit_amount = current_liability_amount
-            .checked_ceil()
-            .ok_or_else(math_error!())?;
+        let spl_deposit_amount = current_liability_amount.to_native_ceil()?;
 
         bank.collected_insurance_fees_outstanding = {
-            spl_deposit_amount
-                .checked_sub(current_liability_amount)
-                .ok_or_else(math_error!())?
+            TokenAmount::from_native(spl_deposit_amount)
+                .checked_sub(current_liability_amount)?
+                .value()
                 .checked_add(bank.collected_insurance_fees_outstanding.into())
                 .ok_or_else(math_error!())?
                 .into()
         };
 
-        Ok(spl_deposit_amount
-            .checked_to_num()
-            .ok_or_else(math_error!())?)
+        Ok(spl_deposit_amount)
     }
 
     pub fn close_balance(&mut self) -> MarginfiResult<()> {
@@ -860,8 +1338,9 @@ impl<'a> BankAccountWrapper<'a> {
         let bank = &mut self.bank;
 
         let current_liability_amount =
-            bank.get_liability_amount(balance.liability_shares.into())?;
-        let current_asset_amount = bank.get_asset_amount(balance.asset_shares.into())?;
+            bank.liability_amount(ShareAmount::new(balance.liability_shares.into()))?;
+        let current_asset_amount =
+            bank.asset_amount(ShareAmount::new(balance.asset_shares.into()))?;
 
         check!(
             current_liability_amount.is_zero_with_tolerance(ZERO_AMOUNT_THRESHOLD),
@@ -880,11 +1359,35 @@ impl<'a> BankAccountWrapper<'a> {
         Ok(())
     }
 
+    /// Admin cleanup of a balance holding at most dust shares on both sides,
+    /// the residual shares are removed from the bank totals.
+    ///
+    /// Returns the removed `(asset_shares, liability_shares)`.
+    pub fn force_close_dust_balance(&mut self) -> MarginfiResult<(I80F48, I80F48)> {
+        let balance = &mut self.balance;
+        let bank = &mut self.bank;
+
+        check!(
+            balance.is_empty(BalanceSide::Assets) && balance.is_empty(BalanceSide::Liabilities),
+            MarginfiError::BalanceNotEmpty
+        );
+
+        let asset_shares: I80F48 = balance.asset_shares.into();
+        let liability_shares: I80F48 = balance.liability_shares.into();
+
+        balance.close()?;
+
+        bank.change_asset_shares(-ShareAmount::new(asset_shares))?;
+        bank.change_liability_shares(-ShareAmount::new(liability_shares), true)?;
+
+        Ok((asset_shares, liability_shares))
+    }
+
     // ------------ Internal accounting logic
 
     fn increase_balance_internal(
         &mut self,
-        balance_delta: I80F48,
+        balance_delta: TokenAmount,
         operation_type: BalanceIncreaseType,
     ) -> MarginfiResult {
         msg!(
@@ -900,16 +1403,14 @@ impl<'a> BankAccountWrapper<'a> {
         let balance = &mut self.balance;
         let bank = &mut self.bank;
 
-        let current_liability_shares: I80F48 = balance.liability_shares.into();
-        let current_liability_amount = bank.get_liability_amount(current_liability_shares)?;
+        let current_liability_shares = ShareAmount::new(balance.liability_shares.into());
+        let current_liability_amount = bank.liability_amount(current_liability_shares)?;
 
         let (liability_amount_decrease, asset_amount_increase) = (
             min(current_liability_amount, balance_delta),
             max(
-                balance_delta
-                    .checked_sub(current_liability_amount)
-                    .ok_or_else(math_error!())?,
-                I80F48::ZERO,
+                balance_delta.checked_sub(current_liability_amount)?,
+                TokenAmount::ZERO,
             ),
         );
 
@@ -935,21 +1436,30 @@ impl<'a> BankAccountWrapper<'a> {
             bank.assert_operational_mode(Some(is_asset_amount_increasing))?;
         }
 
-        let asset_shares_increase = bank.get_asset_shares(asset_amount_increase)?;
+        let asset_shares_increase = bank.asset_shares(asset_amount_increase)?;
         balance.change_asset_shares(asset_shares_increase)?;
         bank.change_asset_shares(asset_shares_increase)?;
 
-        let liability_shares_decrease = bank.get_liability_shares(liability_amount_decrease)?;
+        // When the liability is repaid in full, clear all liability shares instead of
+        // converting the repaid amount back to shares, so no liability dust is left
+        // next to the newly deposited asset.
+        let liability_shares_decrease = if balance_delta >= current_liability_amount {
+            current_liability_shares
+        } else {
+            bank.liability_shares(liability_amount_decrease)?
+        };
         // TODO: Use `IncreaseType` to skip certain balance updates, and save on compute.
         balance.change_liability_shares(-liability_shares_decrease)?;
         bank.change_liability_shares(-liability_shares_decrease, true)?;
 
+        balance.check_netted()?;
+
         Ok(())
     }
 
     fn decrease_balance_internal(
         &mut self,
-        balance_delta: I80F48,
+        balance_delta: TokenAmount,
         operation_type: BalanceDecreaseType,
     ) -> MarginfiResult {
         msg!(
@@ -965,16 +1475,14 @@ impl<'a> BankAccountWrapper<'a> {
         let balance = &mut self.balance;
         let bank = &mut self.bank;
 
-        let current_asset_shares: I80F48 = balance.asset_shares.into();
-        let current_asset_amount = bank.get_asset_amount(current_asset_shares)?;
+        let current_asset_shares = ShareAmount::new(balance.asset_shares.into());
+        let current_asset_amount = bank.asset_amount(current_asset_shares)?;
 
         let (asset_amount_decrease, liability_amount_increase) = (
             min(current_asset_amount, balance_delta),
             max(
-                balance_delta
-                    .checked_sub(current_asset_amount)
-                    .ok_or_else(math_error!())?,
-                I80F48::ZERO,
+                balance_delta.checked_sub(current_asset_amount)?,
+                TokenAmount::ZERO,
             ),
         );
 
@@ -1000,17 +1508,26 @@ impl<'a> BankAccountWrapper<'a> {
             bank.assert_operational_mode(Some(is_liability_amount_increasing))?;
         }
 
-        let asset_shares_decrease = bank.get_asset_shares(asset_amount_decrease)?;
+        // When the asset is withdrawn in full, clear all asset shares instead of
+        // converting the withdrawn amount back to shares, so no asset dust is left
+        // next to the newly opened liability.
+        let asset_shares_decrease = if balance_delta >= current_asset_amount {
+            current_asset_shares
+        } else {
+            bank.asset_shares(asset_amount_decrease)?
+        };
         balance.change_asset_shares(-asset_shares_decrease)?;
         bank.change_asset_shares(-asset_shares_decrease)?;
 
-        let liability_shares_increase = bank.get_liability_shares(liability_amount_increase)?;
+        let liability_shares_increase = bank.liability_shares(liability_amount_increase)?;
         balance.change_liability_shares(liability_shares_increase)?;
         bank.change_liability_shares(
             liability_shares_increase,
             matches!(operation_type, BalanceDecreaseType::BypassBorrowLimit),
         )?;
 
+        balance.check_netted()?;
+
         bank.check_utilization_ratio()?;
 
         Ok(())
@@ -1025,11 +1542,11 @@ impl<'a> BankAccountWrapper<'a> {
         ) {
             (Some(BalanceSide::Assets), true, _) => Some(
                 self.bank
-                    .get_asset_amount(self.balance.asset_shares.into())?,
+                    .asset_amount(ShareAmount::new(self.balance.asset_shares.into()))?,
             ),
             (Some(BalanceSide::Liabilities), _, true) => Some(
                 self.bank
-                    .get_liability_amount(self.balance.liability_shares.into())?,
+                    .liability_amount(ShareAmount::new(self.balance.liability_shares.into()))?,
             ),
             _ => None,
         } {
@@ -1038,14 +1555,15 @@ impl<'a> BankAccountWrapper<'a> {
             } else {
                 self.balance.last_update
             };
+            // No emissions accrue past the emissions end time
             let period = I80F48::from_num(
-                current_timestamp
-                    .checked_sub(last_update)
-                    .ok_or_else(math_error!())?,
+                self.bank
+                    .get_emissions_accrual_end(current_timestamp)
+                    .saturating_sub(last_update),
             );
             let emissions_rate = I80F48::from_num(self.bank.emissions_rate);
             let emissions = period
-                .checked_mul(balance_amount)
+                .checked_mul(balance_amount.value())
                 .ok_or_else(math_error!())?
                 .checked_div(EXP_10_I80F48[self.bank.mint_decimals as usize])
                 .ok_or_else(math_error!())?
@@ -1082,11 +1600,45 @@ impl<'a> BankAccountWrapper<'a> {
             .into();
         }
 
+        self.accrue_referral_fees(current_timestamp)?;
+
         self.balance.last_update = current_timestamp;
 
         Ok(())
     }
 
+    /// Credit the referrer of the account with its share of the protocol fees paid by the
+    /// liability of the balance since its last update, see `Bank::credit_referral_fees`.
+    fn accrue_referral_fees(&mut self, current_timestamp: u64) -> MarginfiResult {
+        if self.referrer == Pubkey::default()
+            || self.balance.last_update < MIN_EMISSIONS_START_TIME
+            || !matches!(self.balance.get_side(), Some(BalanceSide::Liabilities))
+        {
+            return Ok(());
+        }
+
+        let liability_amount = self
+            .bank
+            .liability_amount(ShareAmount::new(self.balance.liability_shares.into()))?;
+        let referral_fees = self
+            .bank
+            .calc_referral_fees(
+                liability_amount.value(),
+                current_timestamp.saturating_sub(self.balance.last_update),
+            )?
+            .checked_floor()
+            .ok_or_else(math_error!())?;
+
+        if referral_fees == I80F48::ZERO {
+            return Ok(());
+        }
+
+        self.bank.credit_referral_fees(
+            &self.referrer,
+            referral_fees.checked_to_num().ok_or_else(math_error!())?,
+        )
+    }
+
     /// Claim any outstanding emissions, and return the max amount that can be withdrawn.
     pub fn settle_emissions_and_get_transfer_amount(&mut self) -> MarginfiResult<u64> {
         self.claim_emissions(Clock::get()?.unix_timestamp as u64)?;
@@ -1131,6 +1683,7 @@ impl<'a> BankAccountWrapper<'a> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::state::marginfi_group::BankConfig;
     use fixed_macro::types::I80F48;
 
     #[test]
@@ -1150,4 +1703,166 @@ mod test {
             I80F48!(10_000_000)
         );
     }
+
+    #[test]
+    fn test_balance_check_netted() {
+        let mut balance = Balance {
+            active: true,
+            bank_pk: Pubkey::new_unique(),
+            asset_shares: I80F48!(100).into(),
+            liability_shares: I80F48!(0.5).into(),
+            emissions_outstanding: I80F48::ZERO.into(),
+            last_update: 0,
+            _padding: [0; 1],
+        };
+
+        // Dust below the empty balance threshold is tolerated
+        assert!(balance.check_netted().is_ok());
+
+        balance.liability_shares = I80F48!(10).into();
+        assert!(balance.check_netted().is_err());
+
+        balance.asset_shares = I80F48::ZERO.into();
+        assert!(balance.check_netted().is_ok());
+    }
+
+    #[test]
+    fn test_check_isolated_borrow() {
+        let isolated_bank_pk = Pubkey::new_unique();
+        let isolated_bank = Bank {
+            config: BankConfig {
+                risk_tier: RiskTier::Isolated,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let collateral_bank_pk = Pubkey::new_unique();
+        let collateral_bank = Bank {
+            config: BankConfig {
+                risk_tier: RiskTier::Collateral,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut lending_account = LendingAccount {
+            balances: [Balance::empty_deactivated(); MAX_LENDING_ACCOUNT_BALANCES],
+            referrer: Pubkey::default(),
+            _padding: [0; 4],
+        };
+
+        // Existing liability in the isolated bank itself
+        lending_account.balances[0] = Balance {
+            active: true,
+            bank_pk: isolated_bank_pk,
+            asset_shares: I80F48::ZERO.into(),
+            liability_shares: I80F48!(10).into(),
+            emissions_outstanding: I80F48::ZERO.into(),
+            last_update: 0,
+            _padding: [0; 1],
+        };
+        assert!(lending_account
+            .check_isolated_borrow(&isolated_bank_pk, &isolated_bank)
+            .is_ok());
+
+        // Liability in another bank
+        lending_account.balances[1] = Balance {
+            bank_pk: collateral_bank_pk,
+            ..lending_account.balances[0]
+        };
+        assert!(lending_account
+            .check_isolated_borrow(&isolated_bank_pk, &isolated_bank)
+            .is_err());
+        assert!(lending_account
+            .check_isolated_borrow(&collateral_bank_pk, &collateral_bank)
+            .is_ok());
+
+        // Assets in other banks don't count
+        lending_account.balances[1].liability_shares = I80F48::ZERO.into();
+        lending_account.balances[1].asset_shares = I80F48!(10).into();
+        assert!(lending_account
+            .check_isolated_borrow(&isolated_bank_pk, &isolated_bank)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_split_position_balances() {
+        let liability_bank_pk = Pubkey::new_unique();
+        let collateral_bank_pk = Pubkey::new_unique();
+
+        let mut source = LendingAccount {
+            balances: [Balance::empty_deactivated(); MAX_LENDING_ACCOUNT_BALANCES],
+            referrer: Pubkey::default(),
+            _padding: [0; 4],
+        };
+        let mut destination = source;
+
+        source.balances[0] = Balance {
+            active: true,
+            bank_pk: collateral_bank_pk,
+            asset_shares: I80F48!(100).into(),
+            liability_shares: I80F48::ZERO.into(),
+            emissions_outstanding: I80F48!(5).into(),
+            last_update: 10,
+            _padding: [0; 1],
+        };
+        source.balances[1] = Balance {
+            active: true,
+            bank_pk: liability_bank_pk,
+            asset_shares: I80F48::ZERO.into(),
+            liability_shares: I80F48!(50).into(),
+            emissions_outstanding: I80F48!(1).into(),
+            last_update: 20,
+            _padding: [0; 1],
+        };
+
+        // Assets can't be split out as a liability
+        assert!(source
+            .split_liability(&mut destination, &collateral_bank_pk)
+            .is_err());
+
+        source
+            .split_liability(&mut destination, &liability_bank_pk)
+            .unwrap();
+
+        assert!(!source.balances[1].active);
+        assert_eq!(destination.balances[0].bank_pk, liability_bank_pk);
+        assert_eq!(
+            I80F48::from(destination.balances[0].liability_shares),
+            I80F48!(50)
+        );
+        assert_eq!(
+            I80F48::from(destination.balances[0].emissions_outstanding),
+            I80F48!(1)
+        );
+        assert_eq!(destination.balances[0].last_update, 20);
+
+        assert!(source
+            .split_asset_shares(&mut destination, &collateral_bank_pk, I80F48!(101))
+            .is_err());
+
+        source
+            .split_asset_shares(&mut destination, &collateral_bank_pk, I80F48!(40))
+            .unwrap();
+
+        assert_eq!(I80F48::from(source.balances[0].asset_shares), I80F48!(60));
+        assert_eq!(
+            I80F48::from(source.balances[0].emissions_outstanding),
+            I80F48!(5)
+        );
+        assert_eq!(
+            I80F48::from(destination.balances[1].asset_shares),
+            I80F48!(40)
+        );
+        assert_eq!(
+            I80F48::from(destination.balances[1].emissions_outstanding),
+            I80F48::ZERO
+        );
+        assert_eq!(destination.balances[1].last_update, 10);
+
+        // The destination can only receive one balance per bank
+        assert!(source
+            .split_asset_shares(&mut destination, &collateral_bank_pk, I80F48!(10))
+            .is_err());
+    }
 }
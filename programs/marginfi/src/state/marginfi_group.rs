@@ -1,15 +1,20 @@
 use super::{
+    amounts::{ShareAmount, TokenAmount},
     marginfi_account::WeightType,
     price::{OraclePriceFeedAdapter, OracleSetup},
 };
 #[cfg(not(feature = "client"))]
-use crate::events::{GroupEventHeader, LendingPoolBankAccrueInterestEvent};
+use crate::events::{
+    GroupEventHeader, LendingPoolBankAccrueInterestEvent, LendingPoolBankKeeperTipEvent,
+};
 use crate::{
-    assert_struct_size, check,
+    assert_struct_size, bank_signer, check,
     constants::{
-        FEE_VAULT_AUTHORITY_SEED, FEE_VAULT_SEED, INSURANCE_VAULT_AUTHORITY_SEED,
-        INSURANCE_VAULT_SEED, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
-        MAX_ORACLE_KEYS, PYTH_ID, SECONDS_PER_YEAR, TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
+        BANK_VERSION, BPS_SCALE, EXP_10_I80F48, FEE_VAULT_AUTHORITY_SEED, FEE_VAULT_SEED,
+        INSURANCE_VAULT_AUTHORITY_SEED, INSURANCE_VAULT_SEED, LIQUIDATION_INSURANCE_FEE,
+        LIQUIDATION_LIQUIDATOR_FEE, LIQUIDITY_VAULT_AUTHORITY_SEED, LIQUIDITY_VAULT_SEED,
+        MAX_LIQUIDATOR_ALLOWLIST_LEN, MAX_ORACLE_KEYS, MAX_REFERRERS, PYTH_ID, SECONDS_PER_YEAR,
+        TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
     },
     debug, math_error,
     prelude::MarginfiError,
@@ -20,6 +25,7 @@ use anchor_spl::token::{transfer, Transfer};
 use fixed::types::I80F48;
 use pyth_sdk_solana::{load_price_feed_from_account_info, PriceFeed};
 use std::{
+    cmp::{max, min},
     fmt::{Debug, Display, Formatter},
     ops::Not,
 };
@@ -35,16 +41,120 @@ use type_layout::TypeLayout;
 #[derive(Default)]
 pub struct MarginfiGroup {
     pub admin: Pubkey,
-    pub _padding_0: [u128; 32],
-    pub _padding_1: [u128; 32],
+    /// Liquidators allowed to invoke `lending_account_liquidate`,
+    /// only enforced when `LIQUIDATOR_ALLOWLIST_FLAG` is set.
+    /// Empty slots are set to `Pubkey::default()`.
+    pub liquidator_allowlist: [Pubkey; MAX_LIQUIDATOR_ALLOWLIST_LEN], // 32 * 8 = 256
+    /// The flags that indicate the state of the group.
+    /// This is u64 bitfield, where each bit represents a flag.
+    ///
+    /// Flags:
+    /// - LIQUIDATOR_ALLOWLIST_FLAG = 1 << 0 = 1 - When set, only liquidators
+    /// in `liquidator_allowlist` can liquidate accounts in this group.
+    pub group_flags: u64, // 8
+    /// Admin proposed with `marginfi_group_set_pending_admin`,
+    /// becomes `admin` once it signs `marginfi_group_accept_admin`.
+    /// Set to `Pubkey::default()` when no transfer is pending.
+    pub pending_admin: Pubkey, // 32
+    /// Number of bankruptcies handled in the group.
+    pub bankruptcy_count: u64, // 8
+    /// Cumulative bad debt socialized between depositors of the group banks,
+    /// valued in USD at the oracle price of each bankruptcy.
+    pub socialized_loss_value: WrappedI80F48, // 16
+    /// USD value of the liabilities of the group banks, each valued at the oracle price
+    /// it was borrowed at. Sum of the `borrow_value` of the banks.
+    pub total_borrow_value: WrappedI80F48, // 16
+    /// Max `total_borrow_value` after a borrow, in UI USD, 0 if borrows are not limited.
+    pub total_borrow_value_limit: u64, // 8
+    pub _padding_0: [u64; 21],
+    /// Referrers that accounts of the group can be initialized with, earning a share of the protocol
+    /// fees paid by their liabilities. Empty slots are set to `Pubkey::default()`.
+    pub referrers: [Pubkey; MAX_REFERRERS], // 32 * 8 = 256
+    pub _padding_1: [u128; 16],
 }
 
+assert_struct_size!(MarginfiGroup, 1056);
+
+pub const LIQUIDATOR_ALLOWLIST_FLAG: u64 = 1 << 0;
+
 impl MarginfiGroup {
     /// Configure the group parameters.
     /// This function validates config values so the group remains in a valid state.
     /// Any modification of group config should happen through this function.
     pub fn configure(&mut self, config: &GroupConfig) -> MarginfiResult {
+        if config.admin.is_some() {
+            // A direct admin change supersedes any pending handoff
+            self.pending_admin = Pubkey::default();
+        }
+
         set_if_some!(self.admin, config.admin);
+        set_if_some!(self.liquidator_allowlist, config.liquidator_allowlist);
+        set_if_some!(self.referrers, config.referrers);
+
+        set_if_some!(
+            self.total_borrow_value_limit,
+            config.total_borrow_value_limit
+        );
+
+        if let Some(enabled) = config.liquidator_allowlist_enabled {
+            if enabled {
+                self.set_flag(LIQUIDATOR_ALLOWLIST_FLAG);
+            } else {
+                self.unset_flag(LIQUIDATOR_ALLOWLIST_FLAG);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_flag(&mut self, flag: u64) {
+        msg!("Setting group flag {:b}", flag);
+        self.group_flags |= flag;
+    }
+
+    pub fn unset_flag(&mut self, flag: u64) {
+        msg!("Unsetting group flag {:b}", flag);
+        self.group_flags &= !flag;
+    }
+
+    pub fn get_flag(&self, flag: u64) -> bool {
+        self.group_flags & flag != 0
+    }
+
+    /// Check if `liquidator` is allowed to liquidate accounts in this group.
+    /// Anyone can liquidate when the allowlist is disabled.
+    pub fn is_liquidator_allowed(&self, liquidator: &Pubkey) -> bool {
+        if !self.get_flag(LIQUIDATOR_ALLOWLIST_FLAG) {
+            return true;
+        }
+
+        liquidator.ne(&Pubkey::default()) && self.liquidator_allowlist.contains(liquidator)
+    }
+
+    /// Check if accounts of this group can be initialized with `referrer`.
+    pub fn is_referrer_registered(&self, referrer: &Pubkey) -> bool {
+        referrer.ne(&Pubkey::default()) && self.referrers.contains(referrer)
+    }
+
+    /// Propose `pending_admin` as the next group admin, it takes over once it accepts.
+    /// Setting `Pubkey::default()` cancels a pending transfer.
+    pub fn set_pending_admin(&mut self, pending_admin: Pubkey) -> MarginfiResult {
+        check!(pending_admin.ne(&self.admin), MarginfiError::InvalidConfig);
+
+        self.pending_admin = pending_admin;
+
+        Ok(())
+    }
+
+    /// Complete the admin transfer, `signer` must be the pending admin.
+    pub fn accept_admin(&mut self, signer: &Pubkey) -> MarginfiResult {
+        check!(
+            self.pending_admin.ne(&Pubkey::default()) && self.pending_admin.eq(signer),
+            MarginfiError::InvalidPendingAdmin
+        );
+
+        self.admin = self.pending_admin;
+        self.pending_admin = Pubkey::default();
 
         Ok(())
     }
@@ -56,12 +166,66 @@ impl MarginfiGroup {
     pub fn set_initial_configuration(&mut self, admin_pk: Pubkey) {
         self.admin = admin_pk;
     }
+
+    /// Record a bankruptcy socializing a loss worth `socialized_loss_value` USD.
+    pub fn record_bankruptcy(&mut self, socialized_loss_value: I80F48) -> MarginfiResult {
+        self.bankruptcy_count = self
+            .bankruptcy_count
+            .checked_add(1)
+            .ok_or_else(math_error!())?;
+        self.socialized_loss_value = I80F48::from(self.socialized_loss_value)
+            .checked_add(socialized_loss_value)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(())
+    }
+
+    /// Bring the group total borrow value up to date with the borrow value of `bank`.
+    ///
+    /// Liabilities leave a bank through repayments, deposits netting a liability, liquidations,
+    /// deleveraging, bankruptcies and admin cleanups, which only release borrow value in the bank
+    /// (see `Bank::change_liability_shares`) so that they don't write lock the group.
+    /// The released value reaches the group total when the bank is synced, on its next borrow
+    /// or with `lending_pool_sync_borrow_value`.
+    pub fn sync_bank_borrow_value(&mut self, bank: &mut Bank) -> MarginfiResult {
+        self.total_borrow_value = max(
+            I80F48::from(self.total_borrow_value)
+                .checked_add(bank.borrow_value.into())
+                .ok_or_else(math_error!())?
+                .checked_sub(bank.synced_borrow_value.into())
+                .ok_or_else(math_error!())?,
+            I80F48::ZERO,
+        )
+        .into();
+        bank.synced_borrow_value = bank.borrow_value;
+
+        Ok(())
+    }
+
+    /// Fail if the group total borrow value is over its limit.
+    pub fn check_total_borrow_value_limit(&self) -> MarginfiResult {
+        if self.total_borrow_value_limit != 0 {
+            check!(
+                I80F48::from(self.total_borrow_value)
+                    <= I80F48::from_num(self.total_borrow_value_limit),
+                MarginfiError::GroupBorrowValueLimitExceeded
+            );
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg_attr(any(feature = "test", feature = "client"), derive(TypeLayout))]
 #[derive(AnchorSerialize, AnchorDeserialize, Default, Debug, Clone)]
 pub struct GroupConfig {
     pub admin: Option<Pubkey>,
+    pub liquidator_allowlist_enabled: Option<bool>,
+    pub liquidator_allowlist: Option<[Pubkey; MAX_LIQUIDATOR_ALLOWLIST_LEN]>,
+    /// In UI USD, 0 to disable the limit.
+    pub total_borrow_value_limit: Option<u64>,
+    pub referrers: Option<[Pubkey; MAX_REFERRERS]>,
 }
 
 /// Load and validate a pyth price feed account.
@@ -102,7 +266,8 @@ impl From<InterestRateConfigCompact> for InterestRateConfig {
             insurance_ir_fee: ir_config.insurance_ir_fee,
             protocol_fixed_fee_apr: ir_config.protocol_fixed_fee_apr,
             protocol_ir_fee: ir_config.protocol_ir_fee,
-            _padding: [0; 8],
+            referral_fee_share: I80F48::ZERO.into(),
+            _padding: [0; 3],
         }
     }
 }
@@ -139,8 +304,11 @@ pub struct InterestRateConfig {
     pub insurance_ir_fee: WrappedI80F48,
     pub protocol_fixed_fee_apr: WrappedI80F48,
     pub protocol_ir_fee: WrappedI80F48,
+    /// Share of the protocol fees paid by the liabilities of referred accounts which goes to
+    /// their referrer, between 0 and 1.
+    pub referral_fee_share: WrappedI80F48,
 
-    pub _padding: [u128; 8], // 16 * 8 = 128 bytes
+    pub _padding: [u128; 3], // 16 * 3 = 48 bytes
 }
 
 impl InterestRateConfig {
@@ -233,6 +401,12 @@ impl InterestRateConfig {
         check!(max_ir > I80F48::ZERO, MarginfiError::InvalidConfig);
         check!(plateau_ir < max_ir, MarginfiError::InvalidConfig);
 
+        let referral_fee_share: I80F48 = self.referral_fee_share.into();
+        check!(
+            referral_fee_share >= I80F48::ZERO && referral_fee_share <= I80F48::ONE,
+            MarginfiError::InvalidConfig
+        );
+
         Ok(())
     }
 
@@ -253,6 +427,7 @@ impl InterestRateConfig {
             ir_config.protocol_fixed_fee_apr
         );
         set_if_some!(self.protocol_ir_fee, ir_config.protocol_ir_fee);
+        set_if_some!(self.referral_fee_share, ir_config.referral_fee_share);
     }
 }
 
@@ -270,6 +445,21 @@ pub struct InterestRateConfigOpt {
     pub insurance_ir_fee: Option<WrappedI80F48>,
     pub protocol_fixed_fee_apr: Option<WrappedI80F48>,
     pub protocol_ir_fee: Option<WrappedI80F48>,
+    pub referral_fee_share: Option<WrappedI80F48>,
+}
+
+#[zero_copy]
+#[repr(C)]
+#[cfg_attr(
+    any(feature = "test", feature = "client"),
+    derive(Debug, PartialEq, Eq, TypeLayout)
+)]
+#[derive(Default)]
+pub struct ReferralFees {
+    /// `Pubkey::default()` for an empty slot.
+    pub referrer: Pubkey,
+    /// In native tokens.
+    pub fees_outstanding: u64,
 }
 
 assert_struct_size!(Bank, 1856);
@@ -322,8 +512,64 @@ pub struct Bank {
     pub emissions_remaining: WrappedI80F48,
     pub emissions_mint: Pubkey,
 
-    pub _padding_0: [u128; 28],
-    pub _padding_1: [u128; 32], // 16 * 2 * 32 = 1024B
+    /// Optional SPL mint of transferable deposit receipts, `Pubkey::default()` if not setup.
+    ///
+    /// One receipt token represents one bank asset share. Shares backing receipts are held
+    /// outside of any marginfi account, they keep accruing interest but are never counted
+    /// as collateral by the risk engine.
+    pub receipt_mint: Pubkey,
+    pub receipt_mint_bump: u8,
+    pub receipt_mint_authority_bump: u8,
+
+    pub _padding_receipt: [u8; 14],
+
+    /// Unix timestamp after which emissions stop accruing, 0 if emissions have no end.
+    pub emissions_end_timestamp: u64,
+    pub _padding_emissions: [u8; 8],
+
+    /// Net outflow tracked by the outflow rate limiter, in native tokens.
+    /// Decays linearly to zero over `config.outflow_window`.
+    pub outflow_accumulator: WrappedI80F48,
+    pub outflow_last_update: i64,
+    pub _padding_outflow: [u8; 8],
+
+    /// Unix timestamp of the last keeper tip paid by this bank.
+    pub keeper_tip_last_paid: i64,
+    pub _padding_keeper: [u8; 8],
+
+    /// Cumulative bad debt of bankrupt accounts covered by the insurance vault, in native tokens.
+    pub insured_bad_debt: WrappedI80F48,
+    /// Cumulative bad debt socialized between the depositors of the bank, in native tokens.
+    pub socialized_loss: WrappedI80F48,
+
+    /// Referral fees owed to the referrers of accounts, in native tokens, sum of `referral_fees`.
+    /// They are held in the liquidity vault until withdrawn with `lending_pool_withdraw_referral_fees`.
+    pub collected_referral_fees_outstanding: WrappedI80F48,
+
+    /// USD value of the bank liabilities, at the oracle price of each borrow.
+    /// Reduced pro rata of the liability shares leaving the bank.
+    pub borrow_value: WrappedI80F48,
+
+    /// Cumulative insurance fees charged on liquidations of the bank liabilities, in native tokens.
+    pub liquidation_insurance_fees: WrappedI80F48,
+
+    /// Layout version, 0 for banks created before layout versioning, see `migrate_account`.
+    pub version: u64,
+    pub _padding_version: [u8; 8],
+
+    /// Program fees owed to the global fee wallet (see `FeeState`), in native tokens. They are held
+    /// in the liquidity vault until swept with `lending_pool_sweep_program_fees`.
+    pub collected_program_fees_outstanding: WrappedI80F48,
+
+    /// Part of `borrow_value` counted in the group `total_borrow_value`,
+    /// see `MarginfiGroup::sync_bank_borrow_value`.
+    pub synced_borrow_value: WrappedI80F48,
+
+    pub _padding_0: [u128; 13],
+
+    /// Referral fees owed to each referrer, see `Bank::credit_referral_fees`.
+    pub referral_fees: [ReferralFees; MAX_REFERRERS], // 40 * 8 = 320B
+    pub _padding_1: [u128; 12], // 16 * 12 = 192B
 }
 
 impl Bank {
@@ -369,36 +615,70 @@ impl Bank {
             emissions_rate: 0,
             emissions_remaining: I80F48::ZERO.into(),
             emissions_mint: Pubkey::default(),
-            _padding_0: [0; 28],
-            _padding_1: [0; 32],
+            receipt_mint: Pubkey::default(),
+            receipt_mint_bump: 0,
+            receipt_mint_authority_bump: 0,
+            _padding_receipt: [0; 14],
+            emissions_end_timestamp: 0,
+            _padding_emissions: [0; 8],
+            outflow_accumulator: I80F48::ZERO.into(),
+            outflow_last_update: current_timestamp,
+            _padding_outflow: [0; 8],
+            keeper_tip_last_paid: 0,
+            _padding_keeper: [0; 8],
+            insured_bad_debt: I80F48::ZERO.into(),
+            socialized_loss: I80F48::ZERO.into(),
+            collected_referral_fees_outstanding: I80F48::ZERO.into(),
+            borrow_value: I80F48::ZERO.into(),
+            liquidation_insurance_fees: I80F48::ZERO.into(),
+            version: BANK_VERSION,
+            _padding_version: [0; 8],
+            collected_program_fees_outstanding: I80F48::ZERO.into(),
+            synced_borrow_value: I80F48::ZERO.into(),
+            _padding_0: [0; 13],
+            referral_fees: [ReferralFees::default(); MAX_REFERRERS],
+            _padding_1: [0; 12],
         }
     }
 
-    pub fn get_liability_amount(&self, shares: I80F48) -> MarginfiResult<I80F48> {
-        Ok(shares
-            .checked_mul(self.liability_share_value.into())
-            .ok_or_else(math_error!())?)
+    pub fn liability_amount(&self, shares: ShareAmount) -> MarginfiResult<TokenAmount> {
+        Ok(TokenAmount::new(
+            shares
+                .value()
+                .checked_mul(self.liability_share_value.into())
+                .ok_or_else(math_error!())?,
+        ))
     }
 
-    pub fn get_asset_amount(&self, shares: I80F48) -> MarginfiResult<I80F48> {
-        Ok(shares
-            .checked_mul(self.asset_share_value.into())
-            .ok_or_else(math_error!())?)
+    pub fn asset_amount(&self, shares: ShareAmount) -> MarginfiResult<TokenAmount> {
+        Ok(TokenAmount::new(
+            shares
+                .value()
+                .checked_mul(self.asset_share_value.into())
+                .ok_or_else(math_error!())?,
+        ))
     }
 
-    pub fn get_liability_shares(&self, value: I80F48) -> MarginfiResult<I80F48> {
-        Ok(value
-            .checked_div(self.liability_share_value.into())
-            .ok_or_else(math_error!())?)
+    pub fn liability_shares(&self, amount: TokenAmount) -> MarginfiResult<ShareAmount> {
+        Ok(ShareAmount::new(
+            amount
+                .value()
+                .checked_div(self.liability_share_value.into())
+                .ok_or_else(math_error!())?,
+        ))
     }
 
-    pub fn get_asset_shares(&self, value: I80F48) -> MarginfiResult<I80F48> {
-        Ok(value
-            .checked_div(self.asset_share_value.into())
-            .ok_or_else(math_error!())?)
+    pub fn asset_shares(&self, amount: TokenAmount) -> MarginfiResult<ShareAmount> {
+        Ok(ShareAmount::new(
+            amount
+                .value()
+                .checked_div(self.asset_share_value.into())
+                .ok_or_else(math_error!())?,
+        ))
     }
 
-    pub fn change_asset_shares(&mut self, shares: I80F48) -> MarginfiResult {
+    pub fn change_asset_shares(&mut self, shares: ShareAmount) -> MarginfiResult {
+        let shares = shares.value();
         let total_asset_shares: I80F48 = self.total_asset_shares.into();
         self.total_asset_shares = total_asset_shares
             .checked_add(shares)
@@ -406,7 +686,9 @@ impl Bank {
             .into();
 
         if shares.is_positive() && self.config.is_deposit_limit_active() {
-            let total_deposits_amount = self.get_asset_amount(self.total_asset_shares.into())?;
+            let total_deposits_amount = self
+                .asset_amount(ShareAmount::new(self.total_asset_shares.into()))?
+                .value();
             let deposit_limit = I80F48::from_num(self.config.deposit_limit);
 
             check!(
@@ -420,19 +702,25 @@ impl Bank {
 
     pub fn change_liability_shares(
         &mut self,
-        shares: I80F48,
+        shares: ShareAmount,
         bypass_borrow_limit: bool,
     ) -> MarginfiResult {
+        let shares = shares.value();
         let total_liability_shares: I80F48 = self.total_liability_shares.into();
         self.total_liability_shares = total_liability_shares
             .checked_add(shares)
             .ok_or_else(math_error!())?
             .into();
 
+        if shares.is_negative() {
+            self.release_borrow_value(-shares, total_liability_shares)?;
+        }
+
         if bypass_borrow_limit.not() && shares.is_positive() && self.config.is_borrow_limit_active()
         {
-            let total_liability_amount =
-                self.get_liability_amount(self.total_liability_shares.into())?;
+            let total_liability_amount = self
+                .liability_amount(ShareAmount::new(self.total_liability_shares.into()))?
+                .value();
             let borrow_limit = I80F48::from_num(self.config.borrow_limit);
 
             check!(
@@ -445,8 +733,12 @@ impl Bank {
     }
 
     pub fn check_utilization_ratio(&self) -> MarginfiResult {
-        let total_assets = self.get_asset_amount(self.total_asset_shares.into())?;
-        let total_liabilities = self.get_liability_amount(self.total_liability_shares.into())?;
+        let total_assets = self
+            .asset_amount(ShareAmount::new(self.total_asset_shares.into()))?
+            .value();
+        let total_liabilities = self
+            .liability_amount(ShareAmount::new(self.total_liability_shares.into()))?
+            .value();
 
         check!(
             total_assets >= total_liabilities,
@@ -457,6 +749,9 @@ impl Bank {
     }
 
     pub fn configure(&mut self, config: &BankConfigOpt) -> MarginfiResult {
+        // Config fields introduced by a migration must be initialized before being updated
+        self.migrate()?;
+
         set_if_some!(self.config.asset_weight_init, config.asset_weight_init);
         set_if_some!(self.config.asset_weight_maint, config.asset_weight_maint);
         set_if_some!(
@@ -488,6 +783,29 @@ impl Bank {
             config.total_asset_value_init_limit
         );
 
+        set_if_some!(self.config.outflow_limit, config.outflow_limit);
+        set_if_some!(self.config.outflow_window, config.outflow_window);
+
+        set_if_some!(self.config.keeper_tip, config.keeper_tip);
+        set_if_some!(self.config.keeper_tip_interval, config.keeper_tip_interval);
+
+        set_if_some!(self.config.origination_fee_bps, config.origination_fee_bps);
+
+        set_if_some!(self.config.oracle_price_floor, config.oracle_price_floor);
+        set_if_some!(
+            self.config.oracle_price_ceiling,
+            config.oracle_price_ceiling
+        );
+
+        set_if_some!(
+            self.config.liquidation_liquidator_fee,
+            config.liquidation_liquidator_fee
+        );
+        set_if_some!(
+            self.config.liquidation_insurance_fee,
+            config.liquidation_insurance_fee
+        );
+
         self.config.validate()?;
 
         Ok(())
@@ -521,8 +839,12 @@ impl Bank {
             return Ok(());
         }
 
-        let total_assets = self.get_asset_amount(self.total_asset_shares.into())?;
-        let total_liabilities = self.get_liability_amount(self.total_liability_shares.into())?;
+        let total_assets = self
+            .asset_amount(ShareAmount::new(self.total_asset_shares.into()))?
+            .value();
+        let total_liabilities = self
+            .liability_amount(ShareAmount::new(self.total_liability_shares.into()))?
+            .value();
 
         self.last_update = current_timestamp;
 
@@ -616,6 +938,185 @@ impl Bank {
         transfer(CpiContext::new(program, accounts), amount)
     }
 
+    /// Referrer share of the protocol fees paid by `liability_amount` over `time_delta` seconds,
+    /// at the current utilization of the bank.
+    pub fn calc_referral_fees(
+        &self,
+        liability_amount: I80F48,
+        time_delta: u64,
+    ) -> MarginfiResult<I80F48> {
+        let referral_fee_share: I80F48 = self.config.interest_rate_config.referral_fee_share.into();
+        let total_assets = self
+            .asset_amount(ShareAmount::new(self.total_asset_shares.into()))?
+            .value();
+        let total_liabilities = self
+            .liability_amount(ShareAmount::new(self.total_liability_shares.into()))?
+            .value();
+
+        if referral_fee_share == I80F48::ZERO || total_assets == I80F48::ZERO {
+            return Ok(I80F48::ZERO);
+        }
+
+        let utilization_rate = total_liabilities
+            .checked_div(total_assets)
+            .ok_or_else(math_error!())?;
+        let (_, _, group_fee_apr, _) = self
+            .config
+            .interest_rate_config
+            .calc_interest_rate(utilization_rate)
+            .ok_or_else(math_error!())?;
+
+        Ok(
+            calc_interest_payment_for_period(group_fee_apr, time_delta, liability_amount)
+                .and_then(|group_fees| group_fees.checked_mul(referral_fee_share))
+                .ok_or_else(math_error!())?,
+        )
+    }
+
+    /// Move `referral_fees` from the outstanding group fees to the fees owed to `referrer`,
+    /// left in the liquidity vault. Group fees already collected to the fee vault leave the
+    /// outstanding group fees negative, until covered by the next accruals.
+    ///
+    /// The fees stay with the group when the bank already owes fees to `MAX_REFERRERS` other
+    /// referrers, e.g. after referrers were replaced in the group.
+    pub fn credit_referral_fees(
+        &mut self,
+        referrer: &Pubkey,
+        referral_fees: u64,
+    ) -> MarginfiResult {
+        let referrer_fees = match self
+            .referral_fees
+            .iter()
+            .position(|fees| fees.referrer.eq(referrer))
+            .or_else(|| {
+                self.referral_fees
+                    .iter()
+                    .position(|fees| fees.referrer.eq(&Pubkey::default()))
+            }) {
+            Some(index) => &mut self.referral_fees[index],
+            None => return Ok(()),
+        };
+
+        referrer_fees.referrer = *referrer;
+        referrer_fees.fees_outstanding = referrer_fees
+            .fees_outstanding
+            .checked_add(referral_fees)
+            .ok_or_else(math_error!())?;
+
+        self.collected_referral_fees_outstanding = {
+            I80F48::from(self.collected_referral_fees_outstanding)
+                .checked_add(I80F48::from_num(referral_fees))
+                .ok_or_else(math_error!())?
+        }
+        .into();
+        self.collected_group_fees_outstanding = {
+            I80F48::from(self.collected_group_fees_outstanding)
+                .checked_sub(I80F48::from_num(referral_fees))
+                .ok_or_else(math_error!())?
+        }
+        .into();
+
+        Ok(())
+    }
+
+    /// Take the referral fees owed to `referrer`, freeing its slot.
+    ///
+    /// Returns the amount to transfer to the referrer.
+    pub fn withdraw_referral_fees(&mut self, referrer: &Pubkey) -> MarginfiResult<u64> {
+        let referrer_fees = self
+            .referral_fees
+            .iter_mut()
+            .find(|fees| fees.referrer.ne(&Pubkey::default()) && fees.referrer.eq(referrer))
+            .ok_or(MarginfiError::InvalidReferrer)?;
+
+        let referral_fees = referrer_fees.fees_outstanding;
+        *referrer_fees = ReferralFees::default();
+
+        self.collected_referral_fees_outstanding = {
+            I80F48::from(self.collected_referral_fees_outstanding)
+                .checked_sub(I80F48::from_num(referral_fees))
+                .ok_or_else(math_error!())?
+        }
+        .into();
+
+        Ok(referral_fees)
+    }
+
+    /// Origination fee of a new liability of `liability_amount`, rounded up.
+    pub fn calc_origination_fee(&self, liability_amount: TokenAmount) -> MarginfiResult<u64> {
+        if self.config.origination_fee_bps == 0 {
+            return Ok(0);
+        }
+
+        Ok(liability_amount
+            .value()
+            .checked_mul(I80F48::from_num(self.config.origination_fee_bps))
+            .and_then(|fee| fee.checked_div(I80F48::from_num(BPS_SCALE)))
+            .and_then(|fee| fee.checked_ceil())
+            .and_then(|fee| fee.checked_to_num())
+            .ok_or_else(math_error!())?)
+    }
+
+    /// Owe `program_fee` native tokens of the liquidity vault to the global fee wallet.
+    pub fn record_program_fee(&mut self, program_fee: u64) -> MarginfiResult {
+        if program_fee > 0 {
+            self.collected_program_fees_outstanding = {
+                I80F48::from(self.collected_program_fees_outstanding)
+                    .checked_add(I80F48::from_num(program_fee))
+                    .ok_or_else(math_error!())?
+            }
+            .into();
+        }
+
+        Ok(())
+    }
+
+    /// Record `liability_amount` borrowed at `price`, returning its USD value.
+    pub fn increase_borrow_value(
+        &mut self,
+        liability_amount: I80F48,
+        price: I80F48,
+    ) -> MarginfiResult<I80F48> {
+        let borrow_value = liability_amount
+            .checked_mul(price)
+            .ok_or_else(math_error!())?
+            .checked_div(EXP_10_I80F48[self.mint_decimals as usize])
+            .ok_or_else(math_error!())?;
+
+        self.borrow_value = I80F48::from(self.borrow_value)
+            .checked_add(borrow_value)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(borrow_value)
+    }
+
+    /// Release the borrow value of `shares` liability shares leaving the bank,
+    /// pro rata of the `total_shares` liability shares before they left.
+    fn release_borrow_value(&mut self, shares: I80F48, total_shares: I80F48) -> MarginfiResult {
+        let borrow_value = I80F48::from(self.borrow_value);
+
+        if borrow_value == I80F48::ZERO || total_shares <= I80F48::ZERO {
+            return Ok(());
+        }
+
+        let released_value = min(
+            shares
+                .checked_div(total_shares)
+                .ok_or_else(math_error!())?
+                .checked_mul(borrow_value)
+                .ok_or_else(math_error!())?,
+            borrow_value,
+        );
+
+        self.borrow_value = borrow_value
+            .checked_sub(released_value)
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(())
+    }
+
     pub fn withdraw_spl_transfer<'b: 'c, 'c: 'b>(
         &self,
         amount: u64,
@@ -653,6 +1154,10 @@ impl Bank {
             .ok_or_else(math_error!())?;
 
         self.asset_share_value = new_share_value.into();
+        self.socialized_loss = I80F48::from(self.socialized_loss)
+            .checked_add(loss_amount)
+            .ok_or_else(math_error!())?
+            .into();
 
         Ok(())
     }
@@ -679,9 +1184,268 @@ impl Bank {
         }
     }
 
+    /// Upgrade the bank layout to `BANK_VERSION`, one version at a time.
+    ///
+    /// Also applied in place by the instructions reading fields introduced by a migration,
+    /// and by off-chain readers to their copy of the bank.
+    pub fn migrate(&mut self) -> MarginfiResult {
+        check!(
+            self.version <= BANK_VERSION,
+            MarginfiError::AccountMigrationError,
+            "Unknown bank version {}",
+            self.version
+        );
+
+        while self.version < BANK_VERSION {
+            match self.version {
+                // Created before layout versioning, same layout as version 1
+                0 => {}
+                // Liquidation fees moved from program constants to the bank config
+                1 => {
+                    self.config.liquidation_liquidator_fee = LIQUIDATION_LIQUIDATOR_FEE.into();
+                    self.config.liquidation_insurance_fee = LIQUIDATION_INSURANCE_FEE.into();
+                }
+                _ => return err!(MarginfiError::AccountMigrationError),
+            }
+
+            self.version += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Clamp `timestamp` to the emissions end time, if any.
+    pub fn get_emissions_accrual_end(&self, timestamp: u64) -> u64 {
+        if self.emissions_end_timestamp == 0 {
+            timestamp
+        } else {
+            min(timestamp, self.emissions_end_timestamp)
+        }
+    }
+
+    /// Add `amount` of emissions, extending the end time, if any, by the share of the remaining
+    /// emissions added, so that they keep being emitted over the same proportion of time.
+    pub fn fund_emissions(&mut self, amount: u64, current_timestamp: u64) -> MarginfiResult {
+        check!(
+            self.emissions_end_timestamp == 0 || self.emissions_end_timestamp > current_timestamp,
+            MarginfiError::EmissionsUpdateError,
+            "Emissions have ended"
+        );
+
+        let emissions_remaining: I80F48 = self.emissions_remaining.into();
+
+        if self.emissions_end_timestamp != 0 && emissions_remaining >= I80F48::ONE {
+            let duration = (self.emissions_end_timestamp - current_timestamp) as u128;
+            let extension = duration
+                .checked_mul(amount as u128)
+                .ok_or_else(math_error!())?
+                .checked_div(emissions_remaining.to_num::<u128>())
+                .ok_or_else(math_error!())?;
+
+            self.emissions_end_timestamp = u64::try_from(extension)
+                .ok()
+                .and_then(|extension| self.emissions_end_timestamp.checked_add(extension))
+                .ok_or_else(math_error!())?;
+        }
+
+        self.emissions_remaining = emissions_remaining
+            .checked_add(I80F48::from_num(amount))
+            .ok_or_else(math_error!())?
+            .into();
+
+        Ok(())
+    }
+
     pub fn get_emissions_flag(&self, flag: u64) -> bool {
         (self.emissions_flags & flag) == flag
     }
+
+    pub fn is_receipt_mint_setup(&self) -> bool {
+        self.receipt_mint != Pubkey::default()
+    }
+
+    /// Net outflow at `current_timestamp`, after decaying the accumulator
+    /// by `outflow_limit` per `outflow_window` since the last update.
+    fn get_decayed_outflow(&self, current_timestamp: i64) -> MarginfiResult<I80F48> {
+        if self.config.outflow_window == 0 {
+            return Ok(I80F48::ZERO);
+        }
+
+        let outflow: I80F48 = self.outflow_accumulator.into();
+
+        // The accumulator is fully drained after one window
+        let elapsed = current_timestamp
+            .saturating_sub(self.outflow_last_update)
+            .clamp(0, self.config.outflow_window.min(i64::MAX as u64) as i64);
+        let decay = I80F48::from_num(self.config.outflow_limit)
+            .checked_mul(I80F48::from_num(elapsed))
+            .ok_or_else(math_error!())?
+            .checked_div(I80F48::from_num(self.config.outflow_window))
+            .ok_or_else(math_error!())?;
+
+        Ok(max(
+            outflow.checked_sub(decay).ok_or_else(math_error!())?,
+            I80F48::ZERO,
+        ))
+    }
+
+    /// Record a withdrawal or borrow of `amount` native tokens, erroring if the net outflow
+    /// over the rolling window would exceed `config.outflow_limit`.
+    ///
+    /// The accumulator leaks `outflow_limit` over every `outflow_window`, so sustained outflow
+    /// is capped at the limit per window, with bursts of at most the limit.
+    pub fn record_outflow(&mut self, amount: u64, current_timestamp: i64) -> MarginfiResult {
+        if self.config.outflow_limit == 0 {
+            return Ok(());
+        }
+
+        let outflow = self
+            .get_decayed_outflow(current_timestamp)?
+            .checked_add(I80F48::from_num(amount))
+            .ok_or_else(math_error!())?;
+
+        check!(
+            outflow <= I80F48::from_num(self.config.outflow_limit),
+            MarginfiError::OutflowLimitExceeded,
+            "Net outflow {} over limit {} per {}s",
+            outflow,
+            self.config.outflow_limit,
+            self.config.outflow_window
+        );
+
+        self.outflow_accumulator = outflow.into();
+        self.outflow_last_update = current_timestamp;
+
+        Ok(())
+    }
+
+    /// Record a deposit or repayment of `amount` native tokens, which offsets earlier outflows.
+    pub fn record_inflow(&mut self, amount: u64, current_timestamp: i64) -> MarginfiResult {
+        if self.config.outflow_limit == 0 {
+            return Ok(());
+        }
+
+        let outflow = max(
+            self.get_decayed_outflow(current_timestamp)?
+                .checked_sub(I80F48::from_num(amount))
+                .ok_or_else(math_error!())?,
+            I80F48::ZERO,
+        );
+
+        self.outflow_accumulator = outflow.into();
+        self.outflow_last_update = current_timestamp;
+
+        Ok(())
+    }
+
+    /// Pay `config.keeper_tip` from the fee vault to the keeper of a permissionless crank,
+    /// at most once per `config.keeper_tip_interval`. Returns the tip amount paid.
+    ///
+    /// Tip accounts are passed as remaining accounts, in order:
+    /// fee vault, fee vault authority, keeper token account, token program.
+    /// Cranks sent without them are not tipped, and the tip is capped by the fee vault balance.
+    ///
+    /// Only the permissionless bank cranks tip: interest accrual, single or batched, and fee collection.
+    /// Bankruptcies are handled by the group admin, and health pulses don't write to any bank.
+    pub fn pay_keeper_tip(
+        &mut self,
+        bank_pk: Pubkey,
+        tip_accounts: &[AccountInfo],
+        current_timestamp: i64,
+    ) -> MarginfiResult<u64> {
+        if self.config.keeper_tip == 0 || tip_accounts.is_empty() {
+            return Ok(0);
+        }
+
+        let (fee_vault, fee_vault_authority, keeper_token_account, token_program) =
+            match tip_accounts {
+                [fee_vault, fee_vault_authority, keeper_token_account, token_program] => (
+                    fee_vault,
+                    fee_vault_authority,
+                    keeper_token_account,
+                    token_program,
+                ),
+                _ => return err!(MarginfiError::InvalidKeeperTipAccounts),
+            };
+
+        check!(
+            fee_vault.key.eq(&self.fee_vault),
+            MarginfiError::InvalidKeeperTipAccounts
+        );
+        check!(
+            fee_vault_authority
+                .key
+                .eq(&self.find_vault_authority(BankVaultType::Fee, &bank_pk)?),
+            MarginfiError::InvalidKeeperTipAccounts
+        );
+        check!(
+            token_program.key.eq(&anchor_spl::token::ID),
+            MarginfiError::InvalidKeeperTipAccounts
+        );
+
+        let time_since_last_tip = current_timestamp.saturating_sub(self.keeper_tip_last_paid);
+        if time_since_last_tip < min(self.config.keeper_tip_interval, i64::MAX as u64) as i64 {
+            return Ok(0);
+        }
+
+        let tip = min(
+            self.config.keeper_tip,
+            anchor_spl::token::accessor::amount(fee_vault)?,
+        );
+
+        if tip == 0 {
+            return Ok(0);
+        }
+
+        self.keeper_tip_last_paid = current_timestamp;
+
+        self.withdraw_spl_transfer(
+            tip,
+            Transfer {
+                from: fee_vault.clone(),
+                to: keeper_token_account.clone(),
+                authority: fee_vault_authority.clone(),
+            },
+            token_program.clone(),
+            bank_signer!(BankVaultType::Fee, bank_pk, self.fee_vault_authority_bump),
+        )?;
+
+        #[cfg(not(feature = "client"))]
+        emit!(LendingPoolBankKeeperTipEvent {
+            header: GroupEventHeader {
+                marginfi_group: self.group,
+                signer: None
+            },
+            bank: bank_pk,
+            mint: self.mint,
+            keeper_token_account: *keeper_token_account.key,
+            amount: tip,
+        });
+
+        Ok(tip)
+    }
+
+    fn find_vault_authority(
+        &self,
+        vault_type: BankVaultType,
+        bank_pk: &Pubkey,
+    ) -> MarginfiResult<Pubkey> {
+        let bump = match vault_type {
+            BankVaultType::Liquidity => self.liquidity_vault_authority_bump,
+            BankVaultType::Insurance => self.insurance_vault_authority_bump,
+            BankVaultType::Fee => self.fee_vault_authority_bump,
+        };
+
+        Pubkey::create_program_address(
+            &[
+                vault_type.get_authority_seed(),
+                &bank_pk.to_bytes(),
+                &[bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| error!(MarginfiError::InvalidKeeperTipAccounts))
+    }
 }
 
 /// We use a simple interest rate model that auto settles the accrued interest into the lending account balances.
@@ -856,13 +1620,21 @@ impl From<BankConfigCompact> for BankConfig {
             liability_weight_maint: config.liability_weight_maint,
             deposit_limit: config.deposit_limit,
             interest_rate_config: config.interest_rate_config.into(),
+            liquidation_liquidator_fee: LIQUIDATION_LIQUIDATOR_FEE.into(),
+            liquidation_insurance_fee: LIQUIDATION_INSURANCE_FEE.into(),
+            oracle_price_floor: I80F48::ZERO.into(),
+            oracle_price_ceiling: I80F48::ZERO.into(),
             operational_state: config.operational_state,
             oracle_setup: config.oracle_setup,
             oracle_keys: config.oracle_keys,
             borrow_limit: config.borrow_limit,
             risk_tier: config.risk_tier,
             total_asset_value_init_limit: config.total_asset_value_init_limit,
-            _padding: [0; 5],
+            outflow_limit: 0,
+            outflow_window: 0,
+            keeper_tip: 0,
+            keeper_tip_interval: 0,
+            origination_fee_bps: 0,
         }
     }
 }
@@ -905,6 +1677,21 @@ pub struct BankConfig {
     pub deposit_limit: u64,
 
     pub interest_rate_config: InterestRateConfig,
+    /// Share of the liquidated collateral value kept by the liquidator, when this bank is the
+    /// asset bank of a liquidation.
+    pub liquidation_liquidator_fee: WrappedI80F48,
+    /// Share of the liquidated collateral value paid to the insurance fund of the liability bank,
+    /// when this bank is the asset bank of a liquidation.
+    pub liquidation_insurance_fee: WrappedI80F48,
+    /// Oracle prices below the floor are rejected by the risk engine, 0 if prices are not bounded
+    /// below. Price in USD of one UI token.
+    ///
+    /// Price bands protect stablecoin and LST banks from transient oracle misprints, a genuine
+    /// depeg out of the band halts the bank until the band is updated.
+    pub oracle_price_floor: WrappedI80F48,
+    /// Oracle prices above the ceiling are rejected by the risk engine, 0 if prices are not
+    /// bounded above.
+    pub oracle_price_ceiling: WrappedI80F48,
     pub operational_state: BankOperationalState,
 
     pub oracle_setup: OracleSetup,
@@ -924,7 +1711,24 @@ pub struct BankConfig {
     /// Value is UI USD value, for example value 100 -> $100
     pub total_asset_value_init_limit: u64,
 
-    pub _padding: [u64; 5], // 16 * 4 = 64 bytes
+    /// Max net outflow (withdrawals and borrows, minus deposits and repayments)
+    /// in native tokens per `outflow_window`, 0 if outflows are not limited.
+    ///
+    /// This bounds the damage of an oracle or pricing exploit to the limit per window.
+    pub outflow_limit: u64,
+    /// Outflow rate limiter window, in seconds.
+    pub outflow_window: u64,
+
+    /// Tip in native tokens paid from the fee vault to the signer of a permissionless crank
+    /// (interest accrual, fee collection), 0 if cranks are not tipped.
+    pub keeper_tip: u64,
+    /// Min number of seconds between two keeper tips paid by the bank.
+    pub keeper_tip_interval: u64,
+
+    /// One-time fee on new liabilities, in basis points of the amount borrowed.
+    ///
+    /// The fee is added to the liability and accrues to the group fees.
+    pub origination_fee_bps: u64,
 }
 
 impl Default for BankConfig {
@@ -937,12 +1741,20 @@ impl Default for BankConfig {
             deposit_limit: 0,
             borrow_limit: 0,
             interest_rate_config: Default::default(),
+            liquidation_liquidator_fee: LIQUIDATION_LIQUIDATOR_FEE.into(),
+            liquidation_insurance_fee: LIQUIDATION_INSURANCE_FEE.into(),
+            oracle_price_floor: I80F48::ZERO.into(),
+            oracle_price_ceiling: I80F48::ZERO.into(),
             operational_state: BankOperationalState::Paused,
             oracle_setup: OracleSetup::None,
             oracle_keys: [Pubkey::default(); MAX_ORACLE_KEYS],
             risk_tier: RiskTier::Isolated,
             total_asset_value_init_limit: TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE,
-            _padding: [0; 5],
+            outflow_limit: 0,
+            outflow_window: 0,
+            keeper_tip: 0,
+            keeper_tip_interval: 0,
+            origination_fee_bps: 0,
         }
     }
 }
@@ -989,6 +1801,74 @@ impl BankConfig {
             check!(asset_maint_w == I80F48::ZERO, MarginfiError::InvalidConfig);
         }
 
+        if self.outflow_limit != 0 {
+            check!(self.outflow_window > 0, MarginfiError::InvalidConfig);
+        }
+
+        // Without an interval the fee vault could be drained by repeated cranks
+        if self.keeper_tip != 0 {
+            check!(self.keeper_tip_interval > 0, MarginfiError::InvalidConfig);
+        }
+
+        check!(
+            self.origination_fee_bps <= BPS_SCALE,
+            MarginfiError::InvalidConfig
+        );
+
+        let (liquidator_fee, insurance_fee) = self.get_liquidation_fees();
+
+        check!(
+            liquidator_fee >= I80F48::ZERO
+                && insurance_fee >= I80F48::ZERO
+                && liquidator_fee + insurance_fee < I80F48::ONE,
+            MarginfiError::InvalidConfig
+        );
+
+        let oracle_price_floor = I80F48::from(self.oracle_price_floor);
+        let oracle_price_ceiling = I80F48::from(self.oracle_price_ceiling);
+
+        check!(
+            oracle_price_floor >= I80F48::ZERO && oracle_price_ceiling >= I80F48::ZERO,
+            MarginfiError::InvalidConfig
+        );
+        if oracle_price_ceiling != I80F48::ZERO {
+            check!(
+                oracle_price_floor < oracle_price_ceiling,
+                MarginfiError::InvalidConfig
+            );
+        }
+
+        Ok(())
+    }
+
+    /// (liquidator fee, insurance fee) of liquidations with this bank as the asset bank.
+    #[inline]
+    pub fn get_liquidation_fees(&self) -> (I80F48, I80F48) {
+        (
+            self.liquidation_liquidator_fee.into(),
+            self.liquidation_insurance_fee.into(),
+        )
+    }
+
+    /// Check that `price` is within the oracle price band of the bank, if any.
+    pub fn check_oracle_price_band(&self, price: I80F48) -> MarginfiResult {
+        let oracle_price_floor = I80F48::from(self.oracle_price_floor);
+        let oracle_price_ceiling = I80F48::from(self.oracle_price_ceiling);
+
+        if oracle_price_floor != I80F48::ZERO && price < oracle_price_floor {
+            msg!("Oracle price {} below floor {}", price, oracle_price_floor);
+            return err!(MarginfiError::OraclePriceOutOfBand);
+        }
+
+        if oracle_price_ceiling != I80F48::ZERO && price > oracle_price_ceiling {
+            msg!(
+                "Oracle price {} above ceiling {}",
+                price,
+                oracle_price_ceiling
+            );
+            return err!(MarginfiError::OraclePriceOutOfBand);
+        }
+
         Ok(())
     }
 
@@ -1061,6 +1941,20 @@ pub struct BankConfigOpt {
     pub risk_tier: Option<RiskTier>,
 
     pub total_asset_value_init_limit: Option<u64>,
+
+    pub outflow_limit: Option<u64>,
+    pub outflow_window: Option<u64>,
+
+    pub keeper_tip: Option<u64>,
+    pub keeper_tip_interval: Option<u64>,
+
+    pub origination_fee_bps: Option<u64>,
+
+    pub oracle_price_floor: Option<WrappedI80F48>,
+    pub oracle_price_ceiling: Option<WrappedI80F48>,
+
+    pub liquidation_liquidator_fee: Option<WrappedI80F48>,
+    pub liquidation_insurance_fee: Option<WrappedI80F48>,
 }
 
 #[cfg_attr(
@@ -1304,8 +2198,12 @@ mod tests {
             ..Default::default()
         };
 
-        let pre_net_assets = bank.get_asset_amount(bank.total_asset_shares.into())?
-            - bank.get_liability_amount(bank.total_liability_shares.into())?;
+        let pre_net_assets = bank
+            .asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+            .value()
+            - bank
+                .liability_amount(ShareAmount::new(bank.total_liability_shares.into()))?
+                .value();
 
         let mut clock = Clock::default();
 
@@ -1321,9 +2219,13 @@ mod tests {
         let post_collected_fees = I80F48::from(bank.collected_group_fees_outstanding)
             + I80F48::from(bank.collected_insurance_fees_outstanding);
 
-        let post_net_assets = bank.get_asset_amount(bank.total_asset_shares.into())?
+        let post_net_assets = bank
+            .asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+            .value()
             + post_collected_fees
-            - bank.get_liability_amount(bank.total_liability_shares.into())?;
+            - bank
+                .liability_amount(ShareAmount::new(bank.total_liability_shares.into()))?
+                .value();
 
         assert_eq_with_tolerance!(pre_net_assets, post_net_assets, I80F48!(1));
 
@@ -1430,4 +2332,71 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn referral_fees_share_of_protocol_fees() -> anyhow::Result<()> {
+        let mut bank = Bank {
+            asset_share_value: I80F48!(1).into(),
+            liability_share_value: I80F48!(1).into(),
+            total_asset_shares: I80F48!(2_000).into(),
+            total_liability_shares: I80F48!(1_000).into(),
+            config: BankConfig {
+                interest_rate_config: InterestRateConfig {
+                    optimal_utilization_rate: I80F48!(0.5).into(),
+                    plateau_interest_rate: I80F48!(0.1).into(),
+                    max_interest_rate: I80F48!(1).into(),
+                    protocol_fixed_fee_apr: I80F48!(0.02).into(),
+                    protocol_ir_fee: I80F48!(0.1).into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // No share configured
+        assert_eq!(bank.calc_referral_fees(I80F48!(100), 3600)?, I80F48::ZERO);
+
+        bank.config.interest_rate_config.referral_fee_share = I80F48!(0.5).into();
+
+        // 50% utilization, on the plateau: group fees APR = 0.1 * 0.1 + 0.02
+        let referral_fees = bank.calc_referral_fees(I80F48!(100), SECONDS_PER_YEAR.to_num())?;
+        assert_eq_with_tolerance!(referral_fees, I80F48!(1.5), I80F48!(0.0001));
+
+        Ok(())
+    }
+
+    #[test]
+    fn group_admin_handoff() {
+        let admin = Pubkey::new_unique();
+        let new_admin = Pubkey::new_unique();
+
+        let mut group = MarginfiGroup::default();
+        group.set_initial_configuration(admin);
+
+        assert!(group.accept_admin(&new_admin).is_err());
+        assert!(group.set_pending_admin(admin).is_err());
+
+        group.set_pending_admin(new_admin).unwrap();
+        assert_eq!(group.admin, admin);
+
+        assert!(group.accept_admin(&Pubkey::new_unique()).is_err());
+
+        group.accept_admin(&new_admin).unwrap();
+        assert_eq!(group.admin, new_admin);
+        assert_eq!(group.pending_admin, Pubkey::default());
+
+        // Direct admin changes drop the pending transfer
+        group.set_pending_admin(admin).unwrap();
+        group
+            .configure(&GroupConfig {
+                admin: Some(Pubkey::new_unique()),
+                liquidator_allowlist_enabled: None,
+                liquidator_allowlist: None,
+                total_borrow_value_limit: None,
+                referrers: None,
+            })
+            .unwrap();
+        assert!(group.accept_admin(&admin).is_err());
+    }
 }
@@ -1,3 +1,5 @@
+pub mod amounts;
+pub mod fee_state;
 pub mod marginfi_account;
 pub mod marginfi_group;
 pub mod price;
@@ -51,11 +51,33 @@ pub enum OraclePriceFeedAdapter {
 }
 
 impl OraclePriceFeedAdapter {
+    /// Load the price feed of a bank, rejecting prices outside of the bank oracle price band.
     pub fn try_from_bank_config(
         bank_config: &BankConfig,
         ais: &[AccountInfo],
         current_timestamp: i64,
         max_age: u64,
+    ) -> MarginfiResult<Self> {
+        let price_feed = Self::try_from_bank_config_ignoring_price_band(
+            bank_config,
+            ais,
+            current_timestamp,
+            max_age,
+        )?;
+
+        // Both the price used for health checks and the one used for liquidations must be in band
+        bank_config.check_oracle_price_band(price_feed.get_price()?)?;
+        bank_config.check_oracle_price_band(price_feed.get_price_non_weighted(None)?)?;
+
+        Ok(price_feed)
+    }
+
+    /// Load the price feed of a bank regardless of its oracle price band, to inspect out of band prices.
+    pub fn try_from_bank_config_ignoring_price_band(
+        bank_config: &BankConfig,
+        ais: &[AccountInfo],
+        current_timestamp: i64,
+        max_age: u64,
     ) -> MarginfiResult<Self> {
         match bank_config.oracle_setup {
             OracleSetup::None => Err(MarginfiError::OracleNotSetup.into()),
@@ -1,5 +1,12 @@
-use crate::{bank_authority_seed, bank_seed, state::marginfi_group::BankVaultType};
-use anchor_lang::prelude::Pubkey;
+use crate::{
+    bank_authority_seed, bank_seed, check,
+    prelude::{MarginfiError, MarginfiResult},
+    state::marginfi_group::BankVaultType,
+};
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::token::{
+    close_account, spl_token::native_mint, sync_native, CloseAccount, SyncNative, TokenAccount,
+};
 use fixed::types::I80F48;
 
 pub fn find_bank_vault_pda(bank_pk: &Pubkey, vault_type: BankVaultType) -> (Pubkey, u8) {
@@ -10,6 +17,75 @@ pub fn find_bank_vault_authority_pda(bank_pk: &Pubkey, vault_type: BankVaultType
     Pubkey::find_program_address(bank_authority_seed!(vault_type, bank_pk), &crate::id())
 }
 
+/// Wrap native SOL from `signer` into its wSOL `token_account`, so that it holds at least `amount`.
+///
+/// Only the missing lamports are transferred, an already funded wSOL account is just synced.
+pub fn wrap_native_sol<'info>(
+    amount: u64,
+    signer: &AccountInfo<'info>,
+    token_account: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> MarginfiResult {
+    let current_amount = {
+        let account = TokenAccount::try_deserialize(&mut &token_account.data.borrow()[..])?;
+
+        check!(
+            account.mint == native_mint::ID && account.owner == signer.key(),
+            MarginfiError::InvalidNativeSolOperation
+        );
+
+        account.amount
+    };
+
+    let wrap_amount = amount.saturating_sub(current_amount);
+
+    if wrap_amount > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: signer.clone(),
+                    to: token_account.clone(),
+                },
+            ),
+            wrap_amount,
+        )?;
+    }
+
+    sync_native(CpiContext::new(
+        token_program.clone(),
+        SyncNative {
+            account: token_account.clone(),
+        },
+    ))?;
+
+    Ok(())
+}
+
+/// Close the signer's wSOL `token_account`, releasing all of its lamports as native SOL to the signer.
+pub fn unwrap_native_sol<'info>(
+    signer: &AccountInfo<'info>,
+    token_account: &Account<'info, TokenAccount>,
+    token_program: &AccountInfo<'info>,
+) -> MarginfiResult {
+    check!(
+        token_account.mint == native_mint::ID && token_account.owner == signer.key(),
+        MarginfiError::InvalidNativeSolOperation
+    );
+
+    close_account(CpiContext::new(
+        token_program.clone(),
+        CloseAccount {
+            account: token_account.to_account_info(),
+            destination: signer.clone(),
+            authority: signer.clone(),
+        },
+    ))?;
+
+    Ok(())
+}
+
 pub trait NumTraitsWithTolerance<T> {
     fn is_zero_with_tolerance(&self, t: T) -> bool;
     fn is_positive_with_tolerance(&self, t: T) -> bool;
@@ -1,16 +1,30 @@
-use anchor_lang::prelude::Clock;
+use anchor_lang::prelude::{Clock, Pubkey};
 use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
 use fixtures::prelude::*;
-use fixtures::{assert_custom_error, assert_eq_noise, native};
+use fixtures::{
+    assert_custom_error, assert_eq_noise,
+    bank::BankFixture,
+    marginfi_account::{find_sub_account_pdas, MarginfiAccountFixture},
+    native,
+    snapshot::{export_snapshot, load_snapshot},
+};
 use marginfi::constants::{
-    EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, MIN_EMISSIONS_START_TIME,
+    BANK_VERSION, EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE,
+    LIQUIDATION_INSURANCE_FEE, LIQUIDATION_LIQUIDATOR_FEE, MARGINFI_ACCOUNT_VERSION,
+    MAX_LIQUIDATOR_ALLOWLIST_LEN, MAX_REFERRERS, MIN_EMISSIONS_START_TIME,
+};
+use marginfi::state::marginfi_account::{
+    BankAccountWrapper, AUTO_DELEVERAGE_FLAG, HEALTH_CACHE_HEALTHY_FLAG,
+    HEALTH_CACHE_LIQUIDATABLE_FLAG,
 };
-use marginfi::state::marginfi_account::BankAccountWrapper;
 use marginfi::state::{
-    marginfi_account::MarginfiAccount,
-    marginfi_group::{Bank, BankConfig, BankConfigOpt, BankVaultType},
+    amounts::ShareAmount,
+    fee_state::{FeeStateConfig, FEE_STATE_BORROW_FLAG},
+    marginfi_account::{MarginfiAccount, SubAccountCounter},
+    marginfi_group::{Bank, BankConfig, BankConfigOpt, BankVaultType, LIQUIDATOR_ALLOWLIST_FLAG},
 };
 use marginfi::{assert_eq_with_tolerance, prelude::*};
 use pretty_assertions::assert_eq;
@@ -19,6 +33,7 @@ use solana_program::{instruction::Instruction, system_program};
 use solana_program_test::*;
 use solana_sdk::timing::SECONDS_PER_YEAR;
 use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
+use std::fs;
 
 // Feature baseline
 
@@ -38,7 +53,7 @@ async fn marginfi_account_create_success() -> anyhow::Result<()> {
     let init_marginfi_account_ix = Instruction {
         program_id: marginfi::id(),
         accounts: accounts.to_account_metas(Some(true)),
-        data: marginfi::instruction::MarginfiAccountInitialize {}.data(),
+        data: marginfi::instruction::MarginfiAccountInitialize { referrer: None }.data(),
     };
 
     let tx = Transaction::new_signed_with_payer(
@@ -73,6 +88,76 @@ async fn marginfi_account_create_success() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn marginfi_account_create_sub_accounts_success() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![TestBankSetting {
+            mint: BankMint::USDC,
+            ..TestBankSetting::default()
+        }],
+        ..TestSettings::default()
+    }))
+    .await;
+
+    let marginfi_group = test_f.marginfi_group.key;
+    let owner = test_f.payer();
+
+    let sub_account_0_f =
+        MarginfiAccountFixture::try_new_sub_account(test_f.context.clone(), &marginfi_group, 0)
+            .await?;
+
+    // Indexes can't be skipped
+    let res =
+        MarginfiAccountFixture::try_new_sub_account(test_f.context.clone(), &marginfi_group, 2)
+            .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.err().unwrap(), MarginfiError::InvalidSubAccountIndex);
+
+    let sub_account_1_f =
+        MarginfiAccountFixture::try_new_sub_account(test_f.context.clone(), &marginfi_group, 1)
+            .await?;
+
+    let (sub_account_0, sub_account_counter) = find_sub_account_pdas(&marginfi_group, &owner, 0);
+    let (sub_account_1, _) = find_sub_account_pdas(&marginfi_group, &owner, 1);
+    assert_eq!(sub_account_0_f.key, sub_account_0);
+    assert_eq!(sub_account_1_f.key, sub_account_1);
+
+    let sub_account_counter: SubAccountCounter =
+        test_f.load_and_deserialize(&sub_account_counter).await;
+    assert_eq!(sub_account_counter.group, marginfi_group);
+    assert_eq!(sub_account_counter.authority, owner);
+    assert_eq!(sub_account_counter.account_count, 2);
+
+    // Sub-accounts are regular accounts of the authority
+    let marginfi_account = sub_account_1_f.load().await;
+    assert_eq!(marginfi_account.group, marginfi_group);
+    assert_eq!(marginfi_account.authority, owner);
+
+    let token_account_f =
+        TokenAccountFixture::new(test_f.context.clone(), &test_f.usdc_mint.key, &owner).await;
+    test_f.usdc_mint.mint_to(&token_account_f.key, 1_000).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    sub_account_1_f
+        .try_bank_deposit(token_account_f.key, usdc_bank_f, 1_000)
+        .await?;
+
+    assert!(sub_account_1_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank_f.key)
+        .is_some());
+    assert!(sub_account_0_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&usdc_bank_f.key)
+        .is_none());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn marginfi_account_deposit_success() -> anyhow::Result<()> {
     let mut test_f = TestFixture::new(Some(TestSettings {
@@ -128,7 +213,7 @@ async fn marginfi_account_deposit_failure_capacity_exceeded() -> anyhow::Result<
                 ..*DEFAULT_USDC_TEST_BANK_CONFIG
             }),
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -195,15 +280,16 @@ async fn marginfi_account_withdraw_success() -> anyhow::Result<()> {
     let usdc_bank = usdc_bank_f.load().await;
 
     let usdc_balance = usdc_bank
-        .get_asset_amount(
+        .asset_amount(ShareAmount::new(
             marginfi_account
                 .lending_account
                 .get_balance(&usdc_bank_f.key)
                 .unwrap()
                 .asset_shares
                 .into(),
-        )
-        .unwrap();
+        ))
+        .unwrap()
+        .value();
 
     assert_eq!(usdc_balance, I80F48::from(native!(100, "USDC")));
 
@@ -578,12 +664,28 @@ async fn marginfi_account_borrow_success() -> anyhow::Result<()> {
 }
 
 #[tokio::test]
-async fn marginfi_account_borrow_success_swb() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::all_banks_swb_payer_not_admin())).await;
+async fn marginfi_account_borrow_origination_fee() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
     let sol_bank = test_f.get_bank(&BankMint::SOL);
 
+    // Fees above 100% are rejected
+    let res = sol_bank
+        .update_config(BankConfigOpt {
+            origination_fee_bps: Some(10_001),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+
+    sol_bank
+        .update_config(BankConfigOpt {
+            origination_fee_bps: Some(50),
+            ..Default::default()
+        })
+        .await?;
+
     // Fund SOL lender
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
     let lender_token_account_sol = test_f
@@ -605,76 +707,334 @@ async fn marginfi_account_borrow_success_swb() -> anyhow::Result<()> {
         .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
         .await?;
 
-    // Borrow SOL
-    let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 100)
+        .await?;
+
+    // The borrowed amount is received in full, the 0.5% fee is added to the liability
+    assert_eq!(
+        borrower_token_account_f_sol.balance().await,
+        native!(100, "SOL")
+    );
+
+    let sol_bank_state = sol_bank.load().await;
+    let borrower_mfi_account = borrower_mfi_account_f.load().await;
+    assert_eq_noise!(
+        sol_bank_state
+            .liability_amount(ShareAmount::new(
+                borrower_mfi_account.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(100.5, "SOL", f64)),
+        native!(0.00001, "SOL", f64)
+    );
+    assert_eq!(
+        I80F48::from(sol_bank_state.collected_group_fees_outstanding),
+        I80F48::from(native!(0.5, "SOL", f64))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_program_fees() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
         .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
 
-    assert!(res.is_ok());
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
 
-    // Check token balances are correct
+    // No fees are charged while the fee state isn't initialized
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 10)
+        .await?;
     assert_eq!(
-        borrower_token_account_f_usdc.balance().await,
-        native!(0, "USDC")
+        I80F48::from(sol_bank.load().await.collected_program_fees_outstanding),
+        I80F48::ZERO
     );
 
+    // The fee wallet must be rent exempt to receive the flat fees
+    let fee_wallet = Keypair::new();
+    let rent = test_f.get_minimum_rent_for_size(0).await;
+    test_f
+        .try_transfer_lamports(&fee_wallet.pubkey(), rent)
+        .await?;
+
+    // Only the upgrade authority can initialize the fee state
+    let outsider = Keypair::new();
+    test_f
+        .try_transfer_lamports(&outsider.pubkey(), native!(1, "SOL"))
+        .await?;
+    let res = test_f
+        .try_initialize_fee_state(
+            &outsider,
+            fee_wallet.pubkey(),
+            5_000,
+            25,
+            FEE_STATE_BORROW_FLAG,
+        )
+        .await;
+    assert_custom_error!(res.unwrap_err(), MarginfiError::Unauthorized);
+
+    test_f
+        .try_initialize_fee_state(
+            &test_f.payer_keypair(),
+            fee_wallet.pubkey(),
+            5_000,
+            25,
+            FEE_STATE_BORROW_FLAG,
+        )
+        .await?;
+
+    let fee_state_pk = get_fee_state_address().0;
+    let fee_state_lamports = test_f.get_lamports(&fee_state_pk).await;
+
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 90)
+        .await?;
+
+    // The 0.25% program fee is added to the liability,
+    // the flat fee goes from the signer to the fee wallet
     assert_eq!(
         borrower_token_account_f_sol.balance().await,
-        native!(99, "SOL")
+        native!(100, "SOL")
+    );
+    assert_eq!(
+        test_f.get_lamports(&fee_wallet.pubkey()).await,
+        rent + 5_000
     );
+    assert_eq!(test_f.get_lamports(&fee_state_pk).await, fee_state_lamports);
 
-    // TODO: check health is sane
+    let sol_bank_state = sol_bank.load().await;
+    let borrower_mfi_account = borrower_mfi_account_f.load().await;
+    assert_eq_noise!(
+        sol_bank_state
+            .liability_amount(ShareAmount::new(
+                borrower_mfi_account.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(100.225, "SOL", f64)),
+        native!(0.00001, "SOL", f64)
+    );
+    assert_eq!(
+        I80F48::from(sol_bank_state.collected_program_fees_outstanding),
+        I80F48::from(native!(0.225, "SOL", f64))
+    );
+    assert_eq!(
+        I80F48::from(sol_bank_state.collected_group_fees_outstanding),
+        I80F48::ZERO
+    );
+
+    // The program fees are swept to a token account of the global fee wallet
+    let fee_wallet_token_account_sol = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &test_f.sol_mint.key,
+        &fee_wallet.pubkey(),
+    )
+    .await;
+    test_f
+        .marginfi_group
+        .try_sweep_program_fees(sol_bank, fee_wallet_token_account_sol.key)
+        .await?;
+
+    assert_eq!(
+        fee_wallet_token_account_sol.balance().await,
+        native!(0.225, "SOL", f64)
+    );
+    assert_eq!(
+        I80F48::from(sol_bank.load().await.collected_program_fees_outstanding),
+        I80F48::ZERO
+    );
+
+    // Hand over the fee state, the previous admin can't configure it anymore
+    test_f
+        .try_configure_fee_state(FeeStateConfig {
+            global_fee_admin: Some(Keypair::new().pubkey()),
+            ..Default::default()
+        })
+        .await?;
+
+    let res = test_f
+        .try_configure_fee_state(FeeStateConfig {
+            flat_sol_fee: Some(0),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_borrow_failure_not_enough_collateral() -> anyhow::Result<()> {
-    // Setup test executor with non-admin payer
+async fn marginfi_account_borrow_flat_fee_authority_not_fee_payer() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank = test_f.get_bank(&BankMint::USDC);
     let sol_bank = test_f.get_bank(&BankMint::SOL);
 
-    // Fund SOL lender
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_f_sol = test_f
+    let lender_token_account_sol = test_f
         .sol_mint
         .create_token_account_and_mint_to(1_000)
         .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
         .await?;
 
-    // Fund SOL borrower
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
     let borrower_token_account_f_usdc = test_f
         .usdc_mint
         .create_token_account_and_mint_to(1_000)
         .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
     borrower_mfi_account_f
         .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
         .await?;
 
-    // Borrow SOL
-    let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 101)
+    let fee_wallet = Keypair::new();
+    let rent = test_f.get_minimum_rent_for_size(0).await;
+    test_f
+        .try_transfer_lamports(&fee_wallet.pubkey(), rent)
+        .await?;
+    test_f
+        .try_initialize_fee_state(
+            &test_f.payer_keypair(),
+            fee_wallet.pubkey(),
+            5_000,
+            0,
+            FEE_STATE_BORROW_FLAG,
+        )
+        .await?;
+
+    // The authority signs the borrow but doesn't pay the transaction fees,
+    // the flat fee is still debited from it
+    let fee_payer = Keypair::new();
+    test_f
+        .try_transfer_lamports(&fee_payer.pubkey(), native!(1, "SOL"))
+        .await?;
+    let authority_lamports = test_f.get_lamports(&test_f.payer()).await;
+
+    borrower_mfi_account_f
+        .try_bank_borrow_with_fee_payer(borrower_token_account_f_sol.key, sol_bank, 10, &fee_payer)
+        .await?;
+
+    assert_eq!(
+        borrower_token_account_f_sol.balance().await,
+        native!(10, "SOL")
+    );
+    assert_eq!(
+        test_f.get_lamports(&test_f.payer()).await,
+        authority_lamports - 5_000
+    );
+    assert_eq!(
+        test_f.get_lamports(&fee_wallet.pubkey()).await,
+        rent + 5_000
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_failure_group_borrow_value_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            total_borrow_value_limit: Some(500),
+            ..GroupConfig::default()
+        })
+        .await?;
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
         .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
 
-    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // $490 borrowed, under the $500 limit
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 49)
+        .await?;
 
     let res = borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 100)
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 2)
         .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::GroupBorrowValueLimitExceeded
+    );
 
-    assert!(res.is_ok());
+    // Repaying releases borrow value for new borrows
+    borrower_mfi_account_f
+        .try_bank_repay(borrower_token_account_f_sol.key, sol_bank, 10, None)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 2)
+        .await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+    assert_eq_noise!(
+        I80F48::from(marginfi_group.total_borrow_value),
+        I80F48!(410),
+        I80F48!(0.0001)
+    );
+    assert_eq!(
+        marginfi_group.total_borrow_value,
+        sol_bank.load().await.borrow_value
+    );
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
+async fn marginfi_account_borrow_success_group_borrow_value_released_by_liquidation(
+) -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
         banks: vec![
             TestBankSetting {
@@ -690,13 +1050,21 @@ async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
     let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
 
+    test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            total_borrow_value_limit: Some(1_000),
+            ..GroupConfig::default()
+        })
+        .await?;
+
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
     let lender_token_account_usdc = test_f
         .usdc_mint
@@ -709,18 +1077,31 @@ async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
     let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
     let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
-
-    // Borrower deposits 100 SOL worth of $1000
     borrower_mfi_account_f
         .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
         .await?;
 
-    // Borrower borrows $999
+    let other_mfi_account_f = test_f.create_marginfi_account().await;
+    let other_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let other_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    other_mfi_account_f
+        .try_bank_deposit(other_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+
+    // $999 borrowed, the limit leaves no room for another $2
     borrower_mfi_account_f
         .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
         .await?;
 
-    // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
+    let res = other_mfi_account_f
+        .try_bank_borrow(other_token_account_usdc.key, usdc_bank_f, 2)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::GroupBorrowValueLimitExceeded
+    );
+
     sol_bank_f
         .update_config(BankConfigOpt {
             asset_weight_init: Some(I80F48!(0.25).into()),
@@ -729,83 +1110,240 @@ async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
         })
         .await?;
 
+    // The liquidator pays $9.75 from its USDC deposit and the liquidatee
+    // liability goes down to $989.5, releasing $9.5 of borrow value in the bank
     lender_mfi_account_f
         .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
         .await?;
 
-    // Checks
-    let sol_bank: Bank = sol_bank_f.load().await;
-    let usdc_bank: Bank = usdc_bank_f.load().await;
+    assert_eq_noise!(
+        I80F48::from(usdc_bank_f.load().await.borrow_value),
+        I80F48!(989.5),
+        I80F48!(0.0001)
+    );
+    assert_eq_noise!(
+        I80F48::from(test_f.marginfi_group.load().await.total_borrow_value),
+        I80F48!(999),
+        I80F48!(0.0001)
+    );
 
-    let depositor_ma = lender_mfi_account_f.load().await;
-    let borrower_ma = borrower_mfi_account_f.load().await;
+    test_f
+        .marginfi_group
+        .try_sync_borrow_value(&[usdc_bank_f])
+        .await?;
 
-    // Depositors should have 1 SOL
-    assert_eq!(
-        sol_bank
-            .get_asset_amount(depositor_ma.lending_account.balances[1].asset_shares.into())
-            .unwrap(),
-        I80F48::from(native!(1, "SOL"))
+    assert_eq_noise!(
+        I80F48::from(test_f.marginfi_group.load().await.total_borrow_value),
+        I80F48!(989.5),
+        I80F48!(0.0001)
     );
 
-    // Depositors should have 1990.25 USDC
+    // The released value is available to new borrows
+    other_mfi_account_f
+        .try_bank_borrow(other_token_account_usdc.key, usdc_bank_f, 3)
+        .await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
     assert_eq_noise!(
-        usdc_bank
-            .get_asset_amount(depositor_ma.lending_account.balances[0].asset_shares.into())
-            .unwrap(),
-        I80F48::from(native!(1990.25, "USDC", f64)),
-        native!(0.00001, "USDC", f64)
+        I80F48::from(marginfi_group.total_borrow_value),
+        I80F48!(992.5),
+        I80F48!(0.0001)
+    );
+    assert_eq!(
+        marginfi_group.total_borrow_value,
+        usdc_bank_f.load().await.borrow_value
     );
 
-    // Borrower should have 99 SOL
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_failure_oracle_price_out_of_band() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Floors above the ceiling are rejected
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_price_floor: Some(I80F48!(1.05).into()),
+            oracle_price_ceiling: Some(I80F48!(0.95).into()),
+            ..Default::default()
+        })
+        .await;
+    assert!(res.is_err());
+
+    // USDC is priced at $1, below the band
+    usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_price_floor: Some(I80F48!(1.01).into()),
+            oracle_price_ceiling: Some(I80F48!(1.05).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 10)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OraclePriceOutOfBand);
+
+    // Back in band
+    usdc_bank
+        .update_config(BankConfigOpt {
+            oracle_price_floor: Some(I80F48!(0.95).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 10)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_success_swb() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_swb_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrow SOL
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 99)
+        .await;
+
+    assert!(res.is_ok());
+
+    // Check token balances are correct
     assert_eq!(
-        sol_bank
-            .get_asset_amount(borrower_ma.lending_account.balances[0].asset_shares.into())
-            .unwrap(),
-        I80F48::from(native!(99, "SOL"))
+        borrower_token_account_f_usdc.balance().await,
+        native!(0, "USDC")
     );
 
-    // Borrower should have 989.50 USDC
-    assert_eq_noise!(
-        usdc_bank
-            .get_liability_amount(
-                borrower_ma.lending_account.balances[1]
-                    .liability_shares
-                    .into()
-            )
-            .unwrap(),
-        I80F48::from(native!(989.50, "USDC", f64)),
-        native!(0.00001, "USDC", f64)
+    assert_eq!(
+        borrower_token_account_f_sol.balance().await,
+        native!(99, "SOL")
     );
 
-    // Check insurance fund fee
-    let insurance_fund_usdc = usdc_bank_f
-        .get_vault_token_account(BankVaultType::Insurance)
+    // TODO: check health is sane
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_borrow_failure_not_enough_collateral() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_f_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
         .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_f_sol.key, sol_bank, 1_000)
+        .await?;
 
-    assert_eq_noise!(
-        insurance_fund_usdc.balance().await as i64,
-        native!(0.25, "USDC", f64) as i64,
-        1
-    );
+    // Fund SOL borrower
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+
+    // Borrow SOL
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 101)
+        .await;
+
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+
+    let res = borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 100)
+        .await;
+
+    assert!(res.is_ok());
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::many_banks_10())).await;
+async fn marginfi_account_liquidation_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig::default()),
+    }))
+    .await;
 
     let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
-    let sol_eq_bank_f = test_f.get_bank(&BankMint::SolEquivalent);
-    let sol_eq1_bank_f = test_f.get_bank(&BankMint::SolEquivalent1);
-    let sol_eq2_bank_f = test_f.get_bank(&BankMint::SolEquivalent2);
-    let sol_eq3_bank_f = test_f.get_bank(&BankMint::SolEquivalent3);
-    let sol_eq4_bank_f = test_f.get_bank(&BankMint::SolEquivalent4);
-    let sol_eq5_bank_f = test_f.get_bank(&BankMint::SolEquivalent5);
-    let sol_eq6_bank_f = test_f.get_bank(&BankMint::SolEquivalent6);
-    let sol_eq7_bank_f = test_f.get_bank(&BankMint::SolEquivalent7);
 
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
     let lender_token_account_usdc = test_f
@@ -819,10 +1357,6 @@ async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
     let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
     let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
-    let borrower_token_account_sol_eq = test_f
-        .sol_equivalent_mint
-        .create_token_account_and_mint_to(5000)
-        .await;
 
     // Borrower deposits 100 SOL worth of $1000
     borrower_mfi_account_f
@@ -834,31 +1368,6 @@ async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<
         .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
         .await?;
 
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq1_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq2_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq3_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq4_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq5_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq6_bank_f, 0)
-        .await?;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq7_bank_f, 0)
-        .await?;
-
     // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
     sol_bank_f
         .update_config(BankConfigOpt {
@@ -882,16 +1391,22 @@ async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<
     // Depositors should have 1 SOL
     assert_eq!(
         sol_bank
-            .get_asset_amount(depositor_ma.lending_account.balances[1].asset_shares.into())
-            .unwrap(),
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[1].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(1, "SOL"))
     );
 
     // Depositors should have 1990.25 USDC
     assert_eq_noise!(
         usdc_bank
-            .get_asset_amount(depositor_ma.lending_account.balances[0].asset_shares.into())
-            .unwrap(),
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(1990.25, "USDC", f64)),
         native!(0.00001, "USDC", f64)
     );
@@ -899,20 +1414,24 @@ async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<
     // Borrower should have 99 SOL
     assert_eq!(
         sol_bank
-            .get_asset_amount(borrower_ma.lending_account.balances[0].asset_shares.into())
-            .unwrap(),
+            .asset_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(99, "SOL"))
     );
 
     // Borrower should have 989.50 USDC
     assert_eq_noise!(
         usdc_bank
-            .get_liability_amount(
+            .liability_amount(ShareAmount::new(
                 borrower_ma.lending_account.balances[1]
                     .liability_shares
                     .into()
-            )
-            .unwrap(),
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(989.50, "USDC", f64)),
         native!(0.00001, "USDC", f64)
     );
@@ -925,14 +1444,25 @@ async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<
     assert_eq_noise!(
         insurance_fund_usdc.balance().await as i64,
         native!(0.25, "USDC", f64) as i64,
-        native!(0.001, "USDC", f64) as i64
+        1
+    );
+
+    // Insurance fee is recorded in the liability bank
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.liquidation_insurance_fees),
+        I80F48::from(native!(0.25, "USDC", f64)),
+        native!(0.00001, "USDC", f64)
+    );
+    assert_eq!(
+        I80F48::from(sol_bank.liquidation_insurance_fees),
+        I80F48::ZERO
     );
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
+async fn marginfi_account_liquidation_success_bank_liquidation_fees() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
         banks: vec![
             TestBankSetting {
@@ -944,17 +1474,130 @@ async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
                 config: Some(BankConfig {
                     asset_weight_init: I80F48!(1).into(),
                     asset_weight_maint: I80F48!(1).into(),
-                    ..*DEFAULT_SOL_TEST_SW_BANK_CONFIG
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
     let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
 
+    // Banks are created with the default liquidation fees
+    let (liquidator_fee, insurance_fee) = sol_bank_f.load().await.config.get_liquidation_fees();
+    assert_eq!(liquidator_fee, LIQUIDATION_LIQUIDATOR_FEE);
+    assert_eq!(insurance_fee, LIQUIDATION_INSURANCE_FEE);
+
+    // Fees must leave part of the collateral value to the liquidatee
+    let res = test_f
+        .marginfi_group
+        .try_lending_pool_configure_bank(
+            sol_bank_f,
+            BankConfigOpt {
+                liquidation_liquidator_fee: Some(I80F48!(0.6).into()),
+                liquidation_insurance_fee: Some(I80F48!(0.4).into()),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidConfig);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
+
+    // Liquidations of SOL collateral pay 5% to the liquidator and 1% to the insurance fund
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            liquidation_liquidator_fee: Some(I80F48!(0.05).into()),
+            liquidation_insurance_fee: Some(I80F48!(0.01).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await?;
+
+    let usdc_bank: Bank = usdc_bank_f.load().await;
+    let depositor_ma = lender_mfi_account_f.load().await;
+    let borrower_ma = borrower_mfi_account_f.load().await;
+
+    // Liquidator pays $10 * (1 - 0.05) = 9.5 USDC for 1 SOL
+    assert_eq_noise!(
+        usdc_bank
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(1990.5, "USDC", f64)),
+        native!(0.00001, "USDC", f64)
+    );
+
+    // Liquidatee is repaid $10 * (1 - 0.05 - 0.01) = 9.4 USDC
+    assert_eq_noise!(
+        usdc_bank
+            .liability_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(989.6, "USDC", f64)),
+        native!(0.00001, "USDC", f64)
+    );
+
+    let insurance_fund_usdc = usdc_bank_f
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+
+    assert_eq_noise!(
+        insurance_fund_usdc.balance().await as i64,
+        native!(0.1, "USDC", f64) as i64,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_liquidation_success_many_balances() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::many_banks_10())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+    let sol_eq_bank_f = test_f.get_bank(&BankMint::SolEquivalent);
+    let sol_eq1_bank_f = test_f.get_bank(&BankMint::SolEquivalent1);
+    let sol_eq2_bank_f = test_f.get_bank(&BankMint::SolEquivalent2);
+    let sol_eq3_bank_f = test_f.get_bank(&BankMint::SolEquivalent3);
+    let sol_eq4_bank_f = test_f.get_bank(&BankMint::SolEquivalent4);
+    let sol_eq5_bank_f = test_f.get_bank(&BankMint::SolEquivalent5);
+    let sol_eq6_bank_f = test_f.get_bank(&BankMint::SolEquivalent6);
+    let sol_eq7_bank_f = test_f.get_bank(&BankMint::SolEquivalent7);
+
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
     let lender_token_account_usdc = test_f
         .usdc_mint
@@ -967,6 +1610,10 @@ async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
     let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
     let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let borrower_token_account_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(5000)
+        .await;
 
     // Borrower deposits 100 SOL worth of $1000
     borrower_mfi_account_f
@@ -978,6 +1625,31 @@ async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
         .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
         .await?;
 
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq1_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq2_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq3_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq4_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq5_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq6_bank_f, 0)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq7_bank_f, 0)
+        .await?;
+
     // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
     sol_bank_f
         .update_config(BankConfigOpt {
@@ -1001,39 +1673,49 @@ async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
     // Depositors should have 1 SOL
     assert_eq!(
         sol_bank
-            .get_asset_amount(depositor_ma.lending_account.balances[1].asset_shares.into())
-            .unwrap(),
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[1].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(1, "SOL"))
     );
 
     // Depositors should have 1990.25 USDC
     assert_eq_noise!(
         usdc_bank
-            .get_asset_amount(depositor_ma.lending_account.balances[0].asset_shares.into())
-            .unwrap(),
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(1990.25, "USDC", f64)),
-        native!(0.01, "USDC", f64)
+        native!(0.00001, "USDC", f64)
     );
 
     // Borrower should have 99 SOL
     assert_eq!(
         sol_bank
-            .get_asset_amount(borrower_ma.lending_account.balances[0].asset_shares.into())
-            .unwrap(),
+            .asset_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(99, "SOL"))
     );
 
     // Borrower should have 989.50 USDC
     assert_eq_noise!(
         usdc_bank
-            .get_liability_amount(
+            .liability_amount(ShareAmount::new(
                 borrower_ma.lending_account.balances[1]
                     .liability_shares
                     .into()
-            )
-            .unwrap(),
+            ))
+            .unwrap()
+            .value(),
         I80F48::from(native!(989.50, "USDC", f64)),
-        native!(0.01, "USDC", f64)
+        native!(0.00001, "USDC", f64)
     );
 
     // Check insurance fund fee
@@ -1050,85 +1732,150 @@ async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Compute units a borrow opening a 10th balance may consume, health check included.
+/// Raising it must be justified by the change that made the instruction more expensive.
+const BORROW_10_BALANCES_COMPUTE_UNITS: u64 = 140_000;
+/// Compute units a partial withdraw from an account with 10 balances may consume.
+const WITHDRAW_10_BALANCES_COMPUTE_UNITS: u64 = 130_000;
+
 #[tokio::test]
-async fn marginfi_account_liquidation_failure_liquidatee_not_unhealthy() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings {
-        banks: vec![
-            TestBankSetting {
-                mint: BankMint::USDC,
-                config: Some(BankConfig {
-                    asset_weight_maint: I80F48!(1).into(),
-                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
-                }),
-            },
-            TestBankSetting {
-                mint: BankMint::SOL,
-                config: Some(BankConfig {
-                    asset_weight_init: I80F48!(1).into(),
-                    asset_weight_maint: I80F48!(1).into(),
-                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
-                }),
-            },
-        ],
-        group_config: Some(GroupConfig { admin: None }),
-    }))
-    .await;
+async fn marginfi_account_compute_units_many_balances() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::many_banks_10())).await;
 
     let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+    let sol_eq_bank_fs = [
+        BankMint::SolEquivalent,
+        BankMint::SolEquivalent1,
+        BankMint::SolEquivalent2,
+        BankMint::SolEquivalent3,
+        BankMint::SolEquivalent4,
+        BankMint::SolEquivalent5,
+        BankMint::SolEquivalent6,
+        BankMint::SolEquivalent7,
+    ]
+    .map(|bank_mint| test_f.get_bank(&bank_mint));
 
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 200)
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
         .await?;
 
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
     let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
     let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    let borrower_token_account_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(5_000)
+        .await;
 
+    // Borrower holds 9 collateral balances
     borrower_mfi_account_f
         .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
         .await?;
+    for sol_eq_bank_f in sol_eq_bank_fs {
+        borrower_mfi_account_f
+            .try_bank_deposit(borrower_token_account_sol_eq.key, sol_eq_bank_f, 100)
+            .await?;
+    }
 
-    borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 100)
-        .await?;
+    // Borrow opening a 10th balance, the health check loads all 10 banks and oracles
+    let borrow_ix = borrower_mfi_account_f
+        .make_bank_borrow_ix(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await;
+    let borrow_compute_units = test_f.try_get_compute_units_consumed(&[borrow_ix]).await?;
 
-    let res = lender_mfi_account_f
-        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+    if let Some(borrow_compute_units) = borrow_compute_units {
+        assert!(
+            borrow_compute_units <= BORROW_10_BALANCES_COMPUTE_UNITS,
+            "Borrow with 10 balances consumed {} compute units, budget is {}",
+            borrow_compute_units,
+            BORROW_10_BALANCES_COMPUTE_UNITS
+        );
+    }
+
+    let withdraw_ix = borrower_mfi_account_f
+        .make_bank_withdraw_ix(borrower_token_account_sol.key, sol_bank_f, 10, None)
         .await;
+    let withdraw_compute_units = test_f
+        .try_get_compute_units_consumed(&[withdraw_ix])
+        .await?;
 
-    assert!(res.is_err());
+    if let Some(withdraw_compute_units) = withdraw_compute_units {
+        assert!(
+            withdraw_compute_units <= WITHDRAW_10_BALANCES_COMPUTE_UNITS,
+            "Withdraw with 10 balances consumed {} compute units, budget is {}",
+            withdraw_compute_units,
+            WITHDRAW_10_BALANCES_COMPUTE_UNITS
+        );
+    }
 
-    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalLiquidation);
+    let borrower_ma = borrower_mfi_account_f.load().await;
+    assert_eq!(
+        borrower_ma
+            .lending_account
+            .balances
+            .iter()
+            .filter(|balance| balance.active)
+            .count(),
+        10
+    );
 
     Ok(())
 }
 
 #[tokio::test]
-async fn marginfi_account_liquidation_failure_liquidation_too_severe() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+async fn marginfi_account_liquidation_success_swb() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_SW_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig::default()),
+    }))
+    .await;
 
     let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
 
     let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
     lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 200)
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
         .await?;
 
     let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
     let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    // Borrower deposits 100 SOL worth of $1000
     borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 10)
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
         .await?;
+
+    // Borrower borrows $999
     borrower_mfi_account_f
-        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 61)
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
         .await?;
 
+    // Synthetically bring down the borrower account health by reducing the asset weights of the SOL bank
     sol_bank_f
         .update_config(BankConfigOpt {
             asset_weight_init: Some(I80F48!(0.25).into()),
@@ -1137,26 +1884,186 @@ async fn marginfi_account_liquidation_failure_liquidation_too_severe() -> anyhow
         })
         .await?;
 
-    let res = lender_mfi_account_f
-        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 10, usdc_bank_f)
-        .await;
+    lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await?;
 
-    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalLiquidation);
+    // Checks
+    let sol_bank: Bank = sol_bank_f.load().await;
+    let usdc_bank: Bank = usdc_bank_f.load().await;
 
-    let res = lender_mfi_account_f
-        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
-        .await;
+    let depositor_ma = lender_mfi_account_f.load().await;
+    let borrower_ma = borrower_mfi_account_f.load().await;
 
-    assert!(res.is_ok());
+    // Depositors should have 1 SOL
+    assert_eq!(
+        sol_bank
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[1].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(1, "SOL"))
+    );
 
-    Ok(())
-}
+    // Depositors should have 1990.25 USDC
+    assert_eq_noise!(
+        usdc_bank
+            .asset_amount(ShareAmount::new(
+                depositor_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(1990.25, "USDC", f64)),
+        native!(0.01, "USDC", f64)
+    );
 
-#[tokio::test]
-async fn marginfi_account_liquidation_failure_liquidator_no_collateral() -> anyhow::Result<()> {
-    let test_f = TestFixture::new(Some(TestSettings {
-        banks: vec![
-            TestBankSetting {
+    // Borrower should have 99 SOL
+    assert_eq!(
+        sol_bank
+            .asset_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(99, "SOL"))
+    );
+
+    // Borrower should have 989.50 USDC
+    assert_eq_noise!(
+        usdc_bank
+            .liability_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(989.50, "USDC", f64)),
+        native!(0.01, "USDC", f64)
+    );
+
+    // Check insurance fund fee
+    let insurance_fund_usdc = usdc_bank_f
+        .get_vault_token_account(BankVaultType::Insurance)
+        .await;
+
+    assert_eq_noise!(
+        insurance_fund_usdc.balance().await as i64,
+        native!(0.25, "USDC", f64) as i64,
+        native!(0.001, "USDC", f64) as i64
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_liquidation_failure_liquidatee_not_unhealthy() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: Some(BankConfig {
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
+                }),
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig::default()),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 200)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 100)
+        .await?;
+
+    let res = lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert!(res.is_err());
+
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalLiquidation);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_liquidation_failure_liquidation_too_severe() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 200)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(20).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 10)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 61)
+        .await?;
+
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    let res = lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 10, usdc_bank_f)
+        .await;
+
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalLiquidation);
+
+    let res = lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert!(res.is_ok());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_liquidation_failure_liquidator_no_collateral() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
                 mint: BankMint::USDC,
                 config: Some(BankConfig {
                     liability_weight_init: I80F48!(1.2).into(),
@@ -1173,7 +2080,7 @@ async fn marginfi_account_liquidation_failure_liquidator_no_collateral() -> anyh
                 config: None,
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -1282,81 +2189,765 @@ async fn marginfi_account_liquidation_failure_bank_not_liquidatable() -> anyhow:
 }
 
 #[tokio::test]
-async fn automatic_interest_payments() -> anyhow::Result<()> {
-    // Setup test executor with non-admin payer
+async fn marginfi_account_liquidation_liquidator_allowlist() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                ..TestBankSetting::default()
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    asset_weight_maint: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig::default()),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 999)
+        .await?;
+
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.5).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    // Enable the allowlist without the liquidator in it
+    let mut liquidator_allowlist = [Pubkey::default(); MAX_LIQUIDATOR_ALLOWLIST_LEN];
+    liquidator_allowlist[0] = Pubkey::new_unique();
+
+    test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            liquidator_allowlist_enabled: Some(true),
+            liquidator_allowlist: Some(liquidator_allowlist),
+            ..GroupConfig::default()
+        })
+        .await?;
+
+    let res = lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::LiquidatorNotAllowed);
+
+    // Add the liquidator to the allowlist
+    liquidator_allowlist[1] = test_f.payer();
+
+    test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            liquidator_allowlist: Some(liquidator_allowlist),
+            ..GroupConfig::default()
+        })
+        .await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+    assert!(marginfi_group.get_flag(LIQUIDATOR_ALLOWLIST_FLAG));
+    assert!(marginfi_group.is_liquidator_allowed(&test_f.payer()));
+
+    lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await?;
+
+    // Disabling the allowlist opens liquidations to anyone
+    test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            liquidator_allowlist_enabled: Some(false),
+            liquidator_allowlist: Some([Pubkey::default(); MAX_LIQUIDATOR_ALLOWLIST_LEN]),
+            ..GroupConfig::default()
+        })
+        .await?;
+
+    lender_mfi_account_f
+        .try_liquidate(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_auto_deleverage() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
 
     let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
     let sol_bank_f = test_f.get_bank(&BankMint::SOL);
 
-    // Create lender user accounts and deposit SOL asset
-    let lender_mfi_account_f = test_f.create_marginfi_account().await;
-    let lender_token_account_sol = test_f
-        .sol_mint
-        .create_token_account_and_mint_to(1_000)
-        .await;
-    lender_mfi_account_f
-        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
-        .await?;
+    let keeper_mfi_account_f = test_f.create_marginfi_account().await;
+    let keeper_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(2_000)
+        .await;
+    keeper_mfi_account_f
+        .try_bank_deposit(keeper_token_account_usdc.key, usdc_bank_f, 2_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    // Borrower deposits 100 SOL worth of $1000 and borrows $900, health ratio 1.11
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 900)
+        .await?;
+
+    // Accounts can't be deleveraged without opting in
+    let res = keeper_mfi_account_f
+        .try_deleverage(&borrower_mfi_account_f, sol_bank_f, 10, usdc_bank_f)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::DeleverageNotAllowed);
+
+    let res = borrower_mfi_account_f
+        .try_configure_auto_deleverage(Some(1.))
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::InvalidDeleverageHealthBuffer
+    );
+
+    borrower_mfi_account_f
+        .try_configure_auto_deleverage(Some(1.2))
+        .await?;
+
+    let borrower_ma = borrower_mfi_account_f.load().await;
+    assert!(borrower_ma.get_flag(AUTO_DELEVERAGE_FLAG));
+    assert_eq!(
+        borrower_ma.deleverage_keeper,
+        test_f.context.borrow().payer.pubkey()
+    );
+
+    // Repaying $500 would bring the health ratio to 1.25, above the buffer
+    let res = keeper_mfi_account_f
+        .try_deleverage(&borrower_mfi_account_f, sol_bank_f, 50, usdc_bank_f)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::IllegalDeleverage);
+
+    // Repaying $100 brings the health ratio to 1.125
+    keeper_mfi_account_f
+        .try_deleverage(&borrower_mfi_account_f, sol_bank_f, 10, usdc_bank_f)
+        .await?;
+
+    let sol_bank: Bank = sol_bank_f.load().await;
+    let usdc_bank: Bank = usdc_bank_f.load().await;
+    let keeper_ma = keeper_mfi_account_f.load().await;
+    let borrower_ma = borrower_mfi_account_f.load().await;
+
+    assert_eq!(
+        sol_bank
+            .asset_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(90, "SOL"))
+    );
+    assert_eq_noise!(
+        usdc_bank
+            .liability_amount(ShareAmount::new(
+                borrower_ma.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(800, "USDC")),
+        native!(0.001, "USDC", f64)
+    );
+    assert_eq_noise!(
+        usdc_bank
+            .asset_amount(ShareAmount::new(
+                keeper_ma.lending_account.balances[0].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(1_900, "USDC")),
+        native!(0.001, "USDC", f64)
+    );
+    assert_eq!(
+        sol_bank
+            .asset_amount(ShareAmount::new(
+                keeper_ma.lending_account.balances[1].asset_shares.into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(10, "SOL"))
+    );
+
+    // Opting out disables the keeper
+    borrower_mfi_account_f
+        .try_configure_auto_deleverage(None)
+        .await?;
+
+    let res = keeper_mfi_account_f
+        .try_deleverage(&borrower_mfi_account_f, sol_bank_f, 1, usdc_bank_f)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::DeleverageNotAllowed);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn automatic_interest_payments() -> anyhow::Result<()> {
+    // Setup test executor with non-admin payer
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    // Create lender user accounts and deposit SOL asset
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
+        .await?;
+
+    // Create borrower user accounts and deposit USDC asset
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await?;
+
+    // Borrow SOL from borrower mfi account
+    borrower_mfi_account_f
+        .try_bank_borrow(lender_token_account_sol.key, sol_bank_f, 99)
+        .await?;
+
+    // Let a year go by
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
+        // Advance clock by 1 year
+        clock.unix_timestamp += 365 * 24 * 60 * 60;
+        ctx.set_sysvar(&clock);
+    }
+
+    // Repay principal, leaving only the accrued interest
+    borrower_mfi_account_f
+        .try_bank_repay(lender_token_account_sol.key, sol_bank_f, 99, None)
+        .await?;
+
+    let sol_bank = sol_bank_f.load().await;
+    let borrower_mfi_account = borrower_mfi_account_f.load().await;
+    let lender_mfi_account = lender_mfi_account_f.load().await;
+
+    // Verify that interest accrued matches on both sides
+    assert_eq_noise!(
+        sol_bank
+            .liability_amount(ShareAmount::new(
+                borrower_mfi_account.lending_account.balances[1]
+                    .liability_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(11.761, "SOL", f64)),
+        native!(0.0002, "SOL", f64)
+    );
+
+    assert_eq_noise!(
+        sol_bank
+            .asset_amount(ShareAmount::new(
+                lender_mfi_account.lending_account.balances[0]
+                    .asset_shares
+                    .into()
+            ))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(1011.761, "SOL", f64)),
+        native!(0.0002, "SOL", f64)
+    );
+    // TODO: check health is sane
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_referral_fees() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    // Half of the 1% group fee on SOL liabilities goes to referrers
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            interest_rate_config: Some(marginfi::state::marginfi_group::InterestRateConfigOpt {
+                protocol_fixed_fee_apr: Some(I80F48!(0.01).into()),
+                referral_fee_share: Some(I80F48!(0.5).into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+        .await?;
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
+        .await?;
+
+    let referrer = Keypair::new();
+    let payer = test_f.payer();
+    let mut referrers = [Pubkey::default(); MAX_REFERRERS];
+    referrers[0] = referrer.pubkey();
+    referrers[1] = payer;
+    test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            referrers: Some(referrers),
+            ..GroupConfig::default()
+        })
+        .await?;
+
+    // Referrers must be registered in the group, and can't refer their own accounts
+    for invalid_referrer in [Keypair::new().pubkey(), payer] {
+        let res = MarginfiAccountFixture::try_new_with_referrer(
+            test_f.context.clone(),
+            &test_f.marginfi_group.key,
+            Some(invalid_referrer),
+        )
+        .await;
+        assert!(res.is_err());
+        assert_custom_error!(res.err().unwrap(), MarginfiError::InvalidReferrer);
+    }
+
+    let borrower_mfi_account_f = MarginfiAccountFixture::try_new_with_referrer(
+        test_f.context.clone(),
+        &test_f.marginfi_group.key,
+        Some(referrer.pubkey()),
+    )
+    .await?;
+    let borrower_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(20).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank_f, 99)
+        .await?;
+
+    assert_eq!(
+        borrower_mfi_account_f.load().await.lending_account.referrer,
+        referrer.pubkey()
+    );
+
+    // Let a year go by
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
+        clock.unix_timestamp += 365 * 24 * 60 * 60;
+        ctx.set_sysvar(&clock);
+    }
+
+    // Repaying credits the referral fees to the referrer in the bank, the closed balance
+    // frees its slot
+    borrower_mfi_account_f
+        .try_bank_repay(borrower_token_account_sol.key, sol_bank_f, 0, Some(true))
+        .await?;
+    assert!(borrower_mfi_account_f
+        .load()
+        .await
+        .lending_account
+        .get_balance(&sol_bank_f.key)
+        .is_none());
+
+    let referrer_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+
+    // Only the referrer can withdraw its fees
+    let res = sol_bank_f
+        .try_withdraw_referral_fees(&Keypair::new(), referrer_token_account_sol.key)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidReferrer);
+
+    sol_bank_f
+        .try_withdraw_referral_fees(&referrer, referrer_token_account_sol.key)
+        .await?;
+
+    // Between the share of the fees on the principal and on the principal with a year of interest
+    let referral_fees = referrer_token_account_sol.balance().await;
+    assert!(referral_fees >= native!(0.495, "SOL", f64));
+    assert!(referral_fees <= native!(0.56, "SOL", f64));
+
+    let sol_bank = sol_bank_f.load().await;
+    assert_eq!(
+        I80F48::from(sol_bank.collected_referral_fees_outstanding),
+        I80F48::ZERO
+    );
+    assert!(sol_bank
+        .referral_fees
+        .iter()
+        .all(|fees| fees.referrer == Pubkey::default() && fees.fees_outstanding == 0));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_deposit_and_borrow_net_same_bank_balance() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(200).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank_f, 99)
+        .await?;
+
+    // Let interest accrue so share values are no longer 1:1
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
+        clock.unix_timestamp += 365 * 24 * 60 * 60;
+        ctx.set_sysvar(&clock);
+    }
+
+    // Depositing more than the liability repays it in full and deposits the rest
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 150)
+        .await?;
+
+    let sol_bank = sol_bank_f.load().await;
+    let balance = borrower_mfi_account_f.load().await.lending_account.balances[1];
+
+    assert_eq!(I80F48::from(balance.liability_shares), I80F48::ZERO);
+    assert_eq_noise!(
+        sol_bank
+            .asset_amount(ShareAmount::new(balance.asset_shares.into()))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(39.239, "SOL", f64)),
+        native!(0.0002, "SOL", f64)
+    );
+
+    // Borrowing more than the asset withdraws it in full and borrows the rest
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank_f, 50)
+        .await?;
+
+    let sol_bank = sol_bank_f.load().await;
+    let balance = borrower_mfi_account_f.load().await.lending_account.balances[1];
+
+    assert_eq!(I80F48::from(balance.asset_shares), I80F48::ZERO);
+    assert_eq_noise!(
+        sol_bank
+            .liability_amount(ShareAmount::new(balance.liability_shares.into()))
+            .unwrap()
+            .value(),
+        I80F48::from(native!(10.761, "SOL", f64)),
+        native!(0.0002, "SOL", f64)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_pulse_health() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 100)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 500)
+        .await?;
+
+    borrower_mfi_account_f.try_pulse_health().await?;
+
+    let health_cache = borrower_mfi_account_f.load().await.health_cache;
+
+    assert_eq_noise!(
+        I80F48::from(health_cache.asset_value),
+        I80F48!(1_000),
+        I80F48!(1)
+    );
+    assert_eq_noise!(
+        I80F48::from(health_cache.liability_value),
+        I80F48!(500),
+        I80F48!(1)
+    );
+    assert!(health_cache.get_flag(HEALTH_CACHE_HEALTHY_FLAG));
+    assert!(!health_cache.get_flag(HEALTH_CACHE_LIQUIDATABLE_FLAG));
+    assert_eq_noise!(
+        health_cache.health_factor().unwrap(),
+        I80F48!(2),
+        I80F48!(0.01)
+    );
+
+    // Bring the account below maintenance requirements
+    sol_bank_f
+        .update_config(BankConfigOpt {
+            asset_weight_init: Some(I80F48!(0.25).into()),
+            asset_weight_maint: Some(I80F48!(0.4).into()),
+            ..Default::default()
+        })
+        .await?;
+
+    borrower_mfi_account_f.try_pulse_health().await?;
+
+    let health_cache = borrower_mfi_account_f.load().await.health_cache;
+
+    assert_eq_noise!(
+        I80F48::from(health_cache.asset_value_maint),
+        I80F48!(400),
+        I80F48!(1)
+    );
+    assert!(!health_cache.get_flag(HEALTH_CACHE_HEALTHY_FLAG));
+    assert!(health_cache.get_flag(HEALTH_CACHE_LIQUIDATABLE_FLAG));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_deposit_receipt_mint_and_redeem() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    sol_bank_f.try_setup_receipt_mint().await?;
+
+    let (receipt_mint, _) = get_receipt_mint_address(sol_bank_f.key);
+    assert_eq!(sol_bank_f.load().await.receipt_mint, receipt_mint);
+
+    let mfi_account_f = test_f.create_marginfi_account().await;
+    let token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(10).await;
+    let token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    mfi_account_f
+        .try_bank_deposit(token_account_sol.key, sol_bank_f, 10)
+        .await?;
+    mfi_account_f
+        .try_bank_deposit(token_account_usdc.key, usdc_bank_f, 100)
+        .await?;
+
+    let receipt_account =
+        TokenAccountFixture::new(test_f.context.clone(), &receipt_mint, &test_f.payer()).await;
+
+    // Banks without a receipt mint can't issue receipts
+    let res = mfi_account_f
+        .try_mint_receipt(receipt_account.key, usdc_bank_f, 10)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::ReceiptMintNotSetup);
+
+    mfi_account_f
+        .try_mint_receipt(receipt_account.key, sol_bank_f, 4)
+        .await?;
+
+    assert_eq!(receipt_account.balance().await, native!(4, "SOL"));
+
+    let marginfi_account = mfi_account_f.load().await;
+    let sol_bank = sol_bank_f.load().await;
+    let balance = marginfi_account
+        .lending_account
+        .get_balance(&sol_bank_f.key)
+        .unwrap();
+    assert_eq_noise!(
+        sol_bank
+            .asset_amount(ShareAmount::new(balance.asset_shares.into()))?
+            .value(),
+        I80F48::from(native!(6, "SOL")),
+        I80F48!(1)
+    );
+    // Receipt backed shares remain in the bank
+    assert_eq_noise!(
+        I80F48::from(sol_bank.total_asset_shares),
+        I80F48::from(native!(10, "SOL")),
+        I80F48!(1)
+    );
+
+    // Receipts can't be minted beyond the deposited balance
+    let res = mfi_account_f
+        .try_mint_receipt(receipt_account.key, sol_bank_f, 7)
+        .await;
+    assert!(res.is_err());
+
+    let destination_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    sol_bank_f
+        .try_redeem_receipt(receipt_account.key, destination_sol.key, native!(4, "SOL"))
+        .await?;
+
+    assert_eq!(receipt_account.balance().await, 0);
+    assert_eq!(destination_sol.balance().await, native!(4, "SOL"));
+    assert_eq_noise!(
+        I80F48::from(sol_bank_f.load().await.total_asset_shares),
+        I80F48::from(native!(6, "SOL")),
+        I80F48!(1)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_native_sol_deposit_and_withdraw() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let native_mint_f = MintFixture::fetch(
+        test_f.context.clone(),
+        anchor_spl::token::spl_token::native_mint::ID,
+    )
+    .await;
+    let native_sol_bank_f = test_f
+        .marginfi_group
+        .try_lending_pool_add_bank(&native_mint_f, *DEFAULT_SOL_TEST_BANK_CONFIG)
+        .await?;
+
+    let mfi_account_f = test_f.create_marginfi_account().await;
+    let payer = test_f.payer();
+
+    // Wrapping is only allowed for native SOL banks
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+    let res = mfi_account_f.try_bank_deposit_native(sol_bank_f, 1).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidNativeSolOperation);
+
+    let wsol_ata = get_associated_token_address(&payer, &native_mint_f.key);
+    let lamports_before = test_f.get_lamports(&payer).await;
 
-    // Create borrower user accounts and deposit USDC asset
-    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
-    let borrower_token_account_usdc = test_f
-        .usdc_mint
-        .create_token_account_and_mint_to(1_000)
-        .await;
-    borrower_mfi_account_f
-        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+    // The wSOL ATA of the payer is created by the program
+    mfi_account_f
+        .try_bank_deposit_native(&native_sol_bank_f, 2)
         .await?;
 
-    // Borrow SOL from borrower mfi account
-    borrower_mfi_account_f
-        .try_bank_borrow(lender_token_account_sol.key, sol_bank_f, 99)
-        .await?;
+    assert_eq!(
+        TokenAccountFixture::fetch(test_f.context.clone(), wsol_ata)
+            .await
+            .balance()
+            .await,
+        0
+    );
+    assert_eq!(
+        native_sol_bank_f
+            .get_vault_token_account(BankVaultType::Liquidity)
+            .await
+            .balance()
+            .await,
+        native!(2, "SOL")
+    );
+    // Transaction fees and the ATA rent are paid on top of the 2 wrapped SOL
+    assert!(lamports_before - test_f.get_lamports(&payer).await >= native!(2, "SOL"));
 
-    // Let a year go by
-    {
-        let mut ctx = test_f.context.borrow_mut();
-        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
-        // Advance clock by 1 year
-        clock.unix_timestamp += 365 * 24 * 60 * 60;
-        ctx.set_sysvar(&clock);
-    }
+    let lamports_before = test_f.get_lamports(&payer).await;
 
-    // Repay principal, leaving only the accrued interest
-    borrower_mfi_account_f
-        .try_bank_repay(lender_token_account_sol.key, sol_bank_f, 99, None)
+    mfi_account_f
+        .try_bank_withdraw_native(&native_sol_bank_f, 1)
         .await?;
 
-    let sol_bank = sol_bank_f.load().await;
-    let borrower_mfi_account = borrower_mfi_account_f.load().await;
-    let lender_mfi_account = lender_mfi_account_f.load().await;
+    // The wSOL ATA is closed and the withdrawn SOL is unwrapped to the payer
+    assert!(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(wsol_ata)
+        .await?
+        .is_none());
+    assert!(test_f.get_lamports(&payer).await > lamports_before + native!(0.99, "SOL", f64));
 
-    // Verify that interest accrued matches on both sides
-    assert_eq_noise!(
-        sol_bank
-            .get_liability_amount(
-                borrower_mfi_account.lending_account.balances[1]
-                    .liability_shares
-                    .into()
-            )
-            .unwrap(),
-        I80F48::from(native!(11.761, "SOL", f64)),
-        native!(0.0002, "SOL", f64)
-    );
+    // Without a wSOL ATA, the program creates it before unwrapping
+    let lamports_before = test_f.get_lamports(&payer).await;
 
-    assert_eq_noise!(
-        sol_bank
-            .get_asset_amount(
-                lender_mfi_account.lending_account.balances[0]
-                    .asset_shares
-                    .into()
-            )
-            .unwrap(),
-        I80F48::from(native!(1011.761, "SOL", f64)),
-        native!(0.0002, "SOL", f64)
+    mfi_account_f
+        .try_bank_withdraw_native(&native_sol_bank_f, 0.5)
+        .await?;
+
+    assert!(test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(wsol_ata)
+        .await?
+        .is_none());
+    assert!(test_f.get_lamports(&payer).await > lamports_before + native!(0.49, "SOL", f64));
+    assert_eq!(
+        native_sol_bank_f
+            .get_vault_token_account(BankVaultType::Liquidity)
+            .await
+            .balance()
+            .await,
+        native!(0.5, "SOL", f64)
     );
-    // TODO: check health is sane
 
     Ok(())
 }
@@ -1407,8 +2998,9 @@ async fn marginfi_account_correct_balance_selection_after_closing_position() ->
     assert_eq!(
         bank_account
             .bank
-            .get_asset_amount(bank_account.balance.asset_shares.into())
+            .asset_amount(ShareAmount::new(bank_account.balance.asset_shares.into()))
             .unwrap()
+            .value()
             .to_num::<u64>(),
         native!(2_000, "USDC")
     );
@@ -1698,37 +3290,304 @@ async fn emissions_test_2() -> anyhow::Result<()> {
         )
         .await?;
 
-    let usdc_bank_data = usdc_bank.load().await;
+    let usdc_bank_data = usdc_bank.load().await;
+
+    assert_eq!(
+        usdc_bank_data.emissions_flags,
+        EMISSIONS_FLAG_LENDING_ACTIVE
+    );
+
+    assert_eq!(usdc_bank_data.emissions_rate, 1_000_000);
+
+    assert_eq!(
+        I80F48::from(usdc_bank_data.emissions_remaining),
+        I80F48::from_num(native!(50, "USDC"))
+    );
+
+    usdc_bank
+        .try_update_emissions(
+            Some(EMISSIONS_FLAG_BORROW_ACTIVE),
+            Some(500_000),
+            Some((native!(25, "USDC"), funding_account.key)),
+            None,
+        )
+        .await?;
+
+    let usdc_bank_data = usdc_bank.load().await;
+
+    assert_eq!(usdc_bank_data.emissions_flags, EMISSIONS_FLAG_BORROW_ACTIVE);
+
+    assert_eq!(usdc_bank_data.emissions_rate, 500_000);
+
+    assert_eq!(
+        I80F48::from(usdc_bank_data.emissions_remaining),
+        I80F48::from_num(native!(75, "USDC"))
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emissions_end_timestamp() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_one_isolated())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+
+    let funding_account = test_f.usdc_mint.create_token_account_and_mint_to(200).await;
+
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(100, "USDC"),
+            usdc_bank.mint.key,
+            funding_account.key,
+        )
+        .await?;
+
+    test_f.set_time(MIN_EMISSIONS_START_TIME as i64);
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_USDC_FEED, MIN_EMISSIONS_START_TIME as i64)
+        .await;
+
+    // End time must be in the future
+    let res = usdc_bank
+        .try_update_emissions(None, None, None, Some(MIN_EMISSIONS_START_TIME - 1))
+        .await;
+
+    assert_custom_error!(res.unwrap_err(), MarginfiError::EmissionsUpdateError);
+
+    // Emissions end after half a year
+    let end_timestamp = MIN_EMISSIONS_START_TIME + (SECONDS_PER_YEAR / 2.0) as u64;
+
+    usdc_bank
+        .try_update_emissions(None, None, None, Some(end_timestamp))
+        .await?;
+
+    assert_eq!(
+        usdc_bank.load().await.emissions_end_timestamp,
+        end_timestamp
+    );
+
+    let mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(50).await;
+
+    mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank, 50)
+        .await?;
+
+    // Claiming after a year only pays out emissions up to the end time
+    test_f.advance_time(SECONDS_PER_YEAR as i64).await;
+
+    let emissions_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    mfi_account_f
+        .try_withdraw_emissions(usdc_bank, emissions_account.key)
+        .await?;
+
+    assert_eq_with_tolerance!(
+        emissions_account.balance().await as i64,
+        native!(25, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    // Top up and extend mid-flight by another year, at double the rate
+    let clock = test_f.get_clock().await;
+    let end_timestamp = clock.unix_timestamp as u64 + SECONDS_PER_YEAR as u64;
+
+    usdc_bank
+        .try_update_emissions(
+            None,
+            Some(2_000_000),
+            Some((native!(50, "USDC"), funding_account.key)),
+            Some(end_timestamp),
+        )
+        .await?;
+
+    assert_eq_with_tolerance!(
+        I80F48::from(usdc_bank.load().await.emissions_remaining),
+        I80F48::from_num(native!(125, "USDC")),
+        I80F48::from_num(native!(1, "USDC"))
+    );
+
+    test_f.advance_time((SECONDS_PER_YEAR / 4.0) as i64).await;
+
+    {
+        let slot = test_f.get_slot().await;
+        test_f
+            .context
+            .borrow_mut()
+            .warp_to_slot(slot + 100)
+            .unwrap();
+    }
+
+    mfi_account_f
+        .try_withdraw_emissions(usdc_bank, emissions_account.key)
+        .await?;
+
+    assert_eq_with_tolerance!(
+        emissions_account.balance().await as i64,
+        native!(50, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emissions_permissionless_funding() -> anyhow::Result<()> {
+    let mut test_f = TestFixture::new(Some(TestSettings::all_banks_one_isolated())).await;
+
+    let admin_funding_account = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+
+    // Emissions can only be funded once setup by the group admin
+    let funder = Keypair::new();
+    let funding_account = TokenAccountFixture::new(
+        test_f.context.clone(),
+        &test_f.usdc_mint.key,
+        &funder.pubkey(),
+    )
+    .await;
+    test_f.usdc_mint.mint_to(&funding_account.key, 100).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+
+    let res = usdc_bank
+        .try_fund_emissions(native!(10, "USDC"), &funder, funding_account.key)
+        .await;
+    assert!(res.is_err());
+
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(50, "USDC"),
+            usdc_bank.mint.key,
+            admin_funding_account.key,
+        )
+        .await?;
+
+    test_f.set_time(MIN_EMISSIONS_START_TIME as i64);
+
+    let end_timestamp = MIN_EMISSIONS_START_TIME + 1_000;
+    usdc_bank
+        .try_update_emissions(None, None, None, Some(end_timestamp))
+        .await?;
+
+    // Anyone can top up the emissions vault, extending the end time by the share added
+    usdc_bank
+        .try_fund_emissions(native!(25, "USDC"), &funder, funding_account.key)
+        .await?;
+
+    let usdc_bank_data = usdc_bank.load().await;
+
+    assert_eq!(
+        I80F48::from(usdc_bank_data.emissions_remaining),
+        I80F48::from_num(native!(75, "USDC"))
+    );
+    assert_eq!(
+        usdc_bank_data.emissions_end_timestamp,
+        MIN_EMISSIONS_START_TIME + 1_500
+    );
+    assert_eq!(
+        usdc_bank_data.emissions_rate, 1_000_000,
+        "Funding doesn't change the emissions rate"
+    );
+    assert_eq!(funding_account.balance().await, native!(75, "USDC"));
+
+    // Emissions can't be funded once ended
+    test_f.set_time((MIN_EMISSIONS_START_TIME + 1_500) as i64);
+
+    let res = usdc_bank
+        .try_fund_emissions(native!(10, "USDC"), &funder, funding_account.key)
+        .await;
+    assert_custom_error!(res.unwrap_err(), MarginfiError::EmissionsUpdateError);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn emissions_withdraw_to_destination_wallet() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_one_isolated())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+
+    let funding_account = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+
+    usdc_bank
+        .try_setup_emissions(
+            EMISSIONS_FLAG_LENDING_ACTIVE,
+            1_000_000,
+            native!(50, "USDC"),
+            usdc_bank.mint.key,
+            funding_account.key,
+        )
+        .await?;
+
+    test_f.set_time(MIN_EMISSIONS_START_TIME as i64);
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_USDC_FEED, MIN_EMISSIONS_START_TIME as i64)
+        .await;
+
+    let mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(50).await;
+
+    mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank, 50)
+        .await?;
+
+    test_f.advance_time((SECONDS_PER_YEAR / 2.0) as i64).await;
 
-    assert_eq!(
-        usdc_bank_data.emissions_flags,
-        EMISSIONS_FLAG_LENDING_ACTIVE
-    );
+    // Destination of the wrong mint is rejected
+    let sol_account = test_f.sol_mint.create_token_account_and_mint_to(0).await;
 
-    assert_eq!(usdc_bank_data.emissions_rate, 1_000_000);
+    let res = mfi_account_f
+        .try_withdraw_emissions(usdc_bank, sol_account.key)
+        .await;
 
-    assert_eq!(
-        I80F48::from(usdc_bank_data.emissions_remaining),
-        I80F48::from_num(native!(50, "USDC"))
+    assert_custom_error!(
+        res.unwrap_err(),
+        MarginfiError::InvalidEmissionsDestinationAccount
     );
 
-    usdc_bank
-        .try_update_emissions(
-            Some(EMISSIONS_FLAG_BORROW_ACTIVE),
-            Some(500_000),
-            Some((native!(25, "USDC"), funding_account.key)),
-        )
+    // Claim to the ATA of a treasury wallet, which doesn't exist yet
+    let treasury = Keypair::new().pubkey();
+    let treasury_ata =
+        anchor_spl::associated_token::get_associated_token_address(&treasury, &usdc_bank.mint.key);
+
+    mfi_account_f
+        .try_withdraw_emissions_to_ata(usdc_bank, treasury)
         .await?;
 
-    let usdc_bank_data = usdc_bank.load().await;
+    let treasury_ata_f = TokenAccountFixture::fetch(test_f.context.clone(), treasury_ata).await;
 
-    assert_eq!(usdc_bank_data.emissions_flags, EMISSIONS_FLAG_BORROW_ACTIVE);
+    assert_eq!(treasury_ata_f.token.owner, treasury);
+    assert_eq_with_tolerance!(
+        treasury_ata_f.balance().await as i64,
+        native!(25, "USDC") as i64,
+        native!(1, "USDC") as i64
+    );
 
-    assert_eq!(usdc_bank_data.emissions_rate, 500_000);
+    // Claiming again reuses the existing ATA
+    test_f.advance_time((SECONDS_PER_YEAR / 4.0) as i64).await;
 
-    assert_eq!(
-        I80F48::from(usdc_bank_data.emissions_remaining),
-        I80F48::from_num(native!(75, "USDC"))
+    {
+        let slot = test_f.get_slot().await;
+        test_f
+            .context
+            .borrow_mut()
+            .warp_to_slot(slot + 100)
+            .unwrap();
+    }
+
+    mfi_account_f
+        .try_withdraw_emissions_to_ata(usdc_bank, treasury)
+        .await?;
+
+    assert_eq_with_tolerance!(
+        treasury_ata_f.balance().await as i64,
+        native!(37.5, "USDC", f64) as i64,
+        native!(1, "USDC") as i64
     );
 
     Ok(())
@@ -1840,3 +3699,370 @@ async fn lending_account_close_balance() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn lending_pool_force_close_dust_balance() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_one_isolated())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+
+    let mfi_account_f = test_f.create_marginfi_account().await;
+    let token_account_f = test_f.usdc_mint.create_token_account_and_mint_to(10).await;
+
+    mfi_account_f
+        .try_bank_deposit(token_account_f.key, usdc_bank, 1)
+        .await?;
+
+    // Balance with assets can't be force closed
+    let res = usdc_bank.try_force_close_balance(mfi_account_f.key).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BalanceNotEmpty);
+
+    // Leave dust asset shares in the balance
+    let pre_total_asset_shares = I80F48::from(usdc_bank.load().await.total_asset_shares);
+    let dust_shares = I80F48!(0.5);
+
+    let mut marginfi_account = mfi_account_f.load().await;
+    marginfi_account.lending_account.balances[0].asset_shares = dust_shares.into();
+    mfi_account_f.set_account(&marginfi_account).await?;
+
+    usdc_bank.try_force_close_balance(mfi_account_f.key).await?;
+
+    let marginfi_account = mfi_account_f.load().await;
+
+    assert!(!marginfi_account.lending_account.balances[0].active);
+    assert_eq!(
+        I80F48::from(usdc_bank.load().await.total_asset_shares),
+        pre_total_asset_shares - dust_shares
+    );
+
+    // Balance is gone
+    let res = usdc_bank.try_force_close_balance(mfi_account_f.key).await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BankAccoutNotFound);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_split_position() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+    let sol_eq_bank_f = test_f.get_bank(&BankMint::SolEquivalent);
+
+    // Fund SOL and SOL EQ lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank_f, 1_000)
+        .await?;
+    let lender_token_account_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol_eq.key, sol_eq_bank_f, 1_000)
+        .await?;
+
+    // Borrower holds two liabilities against the same USDC collateral
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_usdc.key, usdc_bank_f, 1_000)
+        .await?;
+    let borrower_token_account_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol.key, sol_bank_f, 10)
+        .await?;
+    let borrower_token_account_sol_eq = test_f
+        .sol_equivalent_mint
+        .create_token_account_and_mint_to(0)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_sol_eq.key, sol_eq_bank_f, 10)
+        .await?;
+
+    let borrower_mfi_account = borrower_mfi_account_f.load().await;
+    let usdc_asset_shares = I80F48::from(
+        borrower_mfi_account
+            .lending_account
+            .get_balance(&usdc_bank_f.key)
+            .unwrap()
+            .asset_shares,
+    );
+    let sol_liability_shares = I80F48::from(
+        borrower_mfi_account
+            .lending_account
+            .get_balance(&sol_bank_f.key)
+            .unwrap()
+            .liability_shares,
+    );
+
+    let isolated_mfi_account_f = test_f.create_marginfi_account().await;
+
+    // Not enough collateral moved along with the SOL liability
+    let res = borrower_mfi_account_f
+        .try_split_position(
+            &isolated_mfi_account_f,
+            sol_bank_f,
+            vec![(usdc_bank_f, usdc_asset_shares / 100)],
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+
+    // Destination must be a fresh account
+    let res = borrower_mfi_account_f
+        .try_split_position(
+            &lender_mfi_account_f,
+            sol_bank_f,
+            vec![(usdc_bank_f, usdc_asset_shares / 2)],
+        )
+        .await;
+
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidPositionSplit);
+
+    borrower_mfi_account_f
+        .try_split_position(
+            &isolated_mfi_account_f,
+            sol_bank_f,
+            vec![(usdc_bank_f, usdc_asset_shares / 2)],
+        )
+        .await?;
+
+    let borrower_mfi_account = borrower_mfi_account_f.load().await;
+    assert!(borrower_mfi_account
+        .lending_account
+        .get_balance(&sol_bank_f.key)
+        .is_none());
+    assert!(borrower_mfi_account
+        .lending_account
+        .get_balance(&sol_eq_bank_f.key)
+        .is_some());
+    assert_eq!(
+        I80F48::from(
+            borrower_mfi_account
+                .lending_account
+                .get_balance(&usdc_bank_f.key)
+                .unwrap()
+                .asset_shares
+        ),
+        usdc_asset_shares - usdc_asset_shares / 2
+    );
+
+    let isolated_mfi_account = isolated_mfi_account_f.load().await;
+    assert_eq!(
+        isolated_mfi_account
+            .lending_account
+            .get_active_balances_iter()
+            .count(),
+        2
+    );
+    assert_eq!(
+        I80F48::from(
+            isolated_mfi_account
+                .lending_account
+                .get_balance(&sol_bank_f.key)
+                .unwrap()
+                .liability_shares
+        ),
+        sol_liability_shares
+    );
+    assert_eq!(
+        I80F48::from(
+            isolated_mfi_account
+                .lending_account
+                .get_balance(&usdc_bank_f.key)
+                .unwrap()
+                .asset_shares
+        ),
+        usdc_asset_shares / 2
+    );
+
+    // The split out position is managed on its own
+    isolated_mfi_account_f
+        .try_bank_repay(borrower_token_account_sol.key, sol_bank_f, 0, Some(true))
+        .await?;
+
+    let isolated_mfi_account = isolated_mfi_account_f.load().await;
+    assert!(isolated_mfi_account
+        .lending_account
+        .get_balance(&sol_bank_f.key)
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_account_snapshot_export_and_load() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+
+    // Fund SOL lender
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_sol.key, sol_bank, 1_000)
+        .await?;
+
+    // Borrow SOL against USDC
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_f_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    let borrower_token_account_f_sol = test_f.sol_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_f_usdc.key, usdc_bank, 1_000)
+        .await?;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_f_sol.key, sol_bank, 50)
+        .await?;
+
+    let dir = std::env::temp_dir().join(format!("mfi-snapshot-{}", borrower_mfi_account_f.key));
+    let exported =
+        export_snapshot(test_f.context.clone(), borrower_mfi_account_f.key, &dir).await?;
+
+    assert_eq!(exported.banks(), vec![usdc_bank.key, sol_bank.key]);
+
+    // Load the snapshot into a fresh harness, with a different payer
+    let replay_f = TestFixture::new(None).await;
+    let manifest = load_snapshot(replay_f.context.clone(), &dir).await?;
+    fs::remove_dir_all(&dir)?;
+
+    let original = borrower_mfi_account_f.load().await;
+    let replay_mfi_account_f =
+        MarginfiAccountFixture::from_key(replay_f.context.clone(), manifest.marginfi_account());
+    let loaded = replay_mfi_account_f.load().await;
+
+    assert_eq!(loaded.authority, replay_f.payer());
+    assert_eq!(loaded.group, original.group);
+    assert_eq!(loaded.lending_account, original.lending_account);
+
+    // The replayed account behaves as the original one
+    let usdc_mint_f = MintFixture::fetch(replay_f.context.clone(), test_f.usdc_mint.key).await;
+    let replay_usdc_bank = BankFixture::new(replay_f.context.clone(), usdc_bank.key, &usdc_mint_f);
+    let destination_account = TokenAccountFixture::new(
+        replay_f.context.clone(),
+        &usdc_mint_f.key,
+        &replay_f.payer(),
+    )
+    .await;
+
+    let res = replay_mfi_account_f
+        .try_bank_withdraw(destination_account.key, &replay_usdc_bank, 1_000, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::BadAccountHealth);
+
+    replay_mfi_account_f
+        .try_bank_withdraw(destination_account.key, &replay_usdc_bank, 100, None)
+        .await?;
+
+    assert_eq!(destination_account.balance().await, native!(100, "USDC"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn migrate_account_legacy_layouts() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let mfi_account_f = test_f.create_marginfi_account().await;
+
+    // New accounts are created at the latest layout version
+    assert_eq!(mfi_account_f.load().await.version, MARGINFI_ACCOUNT_VERSION);
+    assert_eq!(usdc_bank_f.load().await.version, BANK_VERSION);
+
+    // Legacy marginfi account, unversioned and without the trailing padding
+    let mut marginfi_account = mfi_account_f.load().await;
+    marginfi_account.version = 0;
+    mfi_account_f.set_account(&marginfi_account).await?;
+
+    let legacy_len = MarginfiAccountFixture::get_size() - 368;
+    let legacy_rent = test_f.get_minimum_rent_for_size(legacy_len).await;
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut account = ctx
+            .banks_client
+            .get_account(mfi_account_f.key)
+            .await?
+            .unwrap();
+        account.data.truncate(legacy_len);
+        account.lamports = legacy_rent;
+        ctx.set_account(&mfi_account_f.key, &account.into());
+    }
+
+    test_f.try_migrate_account(mfi_account_f.key).await?;
+
+    // Reallocated to the latest layout, with the rent topped up by the payer
+    let account = test_f
+        .context
+        .borrow_mut()
+        .banks_client
+        .get_account(mfi_account_f.key)
+        .await?
+        .unwrap();
+    assert_eq!(account.data.len(), MarginfiAccountFixture::get_size());
+    assert_eq!(
+        account.lamports,
+        test_f
+            .get_minimum_rent_for_size(MarginfiAccountFixture::get_size())
+            .await
+    );
+
+    let migrated_account = mfi_account_f.load().await;
+    assert_eq!(migrated_account.version, MARGINFI_ACCOUNT_VERSION);
+    assert_eq!(migrated_account.group, marginfi_account.group);
+    assert_eq!(migrated_account.authority, marginfi_account.authority);
+
+    // Legacy bank, unversioned and without liquidation fees in its config
+    {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut account = ctx
+            .banks_client
+            .get_account(usdc_bank_f.key)
+            .await?
+            .unwrap();
+        let bank = bytemuck::from_bytes_mut::<Bank>(&mut account.data[8..]);
+        bank.version = 0;
+        bank.config.liquidation_liquidator_fee = I80F48::ZERO.into();
+        bank.config.liquidation_insurance_fee = I80F48::ZERO.into();
+        ctx.set_account(&usdc_bank_f.key, &account.into());
+    }
+
+    test_f.try_migrate_account(usdc_bank_f.key).await?;
+
+    // Liquidation fees are initialized to the former program constants
+    let usdc_bank = usdc_bank_f.load().await;
+    assert_eq!(usdc_bank.version, BANK_VERSION);
+    assert_eq!(
+        usdc_bank.config.get_liquidation_fees(),
+        (LIQUIDATION_LIQUIDATOR_FEE, LIQUIDATION_INSURANCE_FEE)
+    );
+
+    // Other accounts can't be migrated
+    let res = test_f.try_migrate_account(test_f.marginfi_group.key).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::AccountMigrationError);
+
+    Ok(())
+}
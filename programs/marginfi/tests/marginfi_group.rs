@@ -1,4 +1,7 @@
-use anchor_lang::{prelude::Clock, InstructionData, ToAccountMetas};
+use anchor_lang::{
+    prelude::{Clock, Pubkey},
+    InstructionData, ToAccountMetas,
+};
 
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
@@ -6,6 +9,7 @@ use fixtures::prelude::*;
 use fixtures::{assert_custom_error, assert_eq_noise, native};
 use marginfi::constants::TOTAL_ASSET_VALUE_INIT_LIMIT_INACTIVE;
 use marginfi::prelude::GroupConfig;
+use marginfi::state::amounts::ShareAmount;
 use marginfi::state::marginfi_group::{BankVaultType, InterestRateConfig};
 use marginfi::{
     prelude::{MarginfiError, MarginfiGroup},
@@ -107,6 +111,7 @@ async fn marginfi_group_config_check() -> anyhow::Result<()> {
                 insurance_ir_fee: Some(I80F48::from_num(0.11).into()),
                 protocol_fixed_fee_apr: Some(I80F48::from_num(0.51).into()),
                 protocol_ir_fee: Some(I80F48::from_num(0.011).into()),
+                referral_fee_share: Some(I80F48::from_num(0.25).into()),
             }),
             ..BankConfigOpt::default()
         })
@@ -153,6 +158,11 @@ async fn marginfi_group_config_check() -> anyhow::Result<()> {
         I80F48::from_num(0.011)
     );
 
+    assert_eq!(
+        I80F48::from(bank.config.interest_rate_config.referral_fee_share),
+        I80F48::from_num(0.25)
+    );
+
     Ok(())
 }
 // #[tokio::test]
@@ -160,6 +170,55 @@ async fn marginfi_group_config_check() -> anyhow::Result<()> {
 //     todo!()
 // }
 
+#[tokio::test]
+async fn marginfi_group_admin_handoff() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(None).await;
+
+    let new_admin = Keypair::new();
+
+    // Nothing to accept before a pending admin is set
+    let res = test_f.marginfi_group.try_accept_admin(&new_admin).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidPendingAdmin);
+
+    test_f
+        .marginfi_group
+        .try_set_pending_admin(new_admin.pubkey())
+        .await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+    assert_eq!(marginfi_group.admin, test_f.payer());
+    assert_eq!(marginfi_group.pending_admin, new_admin.pubkey());
+
+    // Only the pending admin can accept
+    let res = test_f
+        .marginfi_group
+        .try_accept_admin(&Keypair::new())
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidPendingAdmin);
+
+    // Same transaction as the first rejected accept
+    test_f.refresh_blockhash().await;
+    test_f.marginfi_group.try_accept_admin(&new_admin).await?;
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+    assert_eq!(marginfi_group.admin, new_admin.pubkey());
+    assert_eq!(marginfi_group.pending_admin, Pubkey::default());
+
+    // The previous admin lost its rights
+    let res = test_f
+        .marginfi_group
+        .try_update(GroupConfig {
+            admin: Some(test_f.payer()),
+            ..GroupConfig::default()
+        })
+        .await;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn marginfi_group_add_bank_success() -> anyhow::Result<()> {
     // Setup test executor with non-admin payer
@@ -209,7 +268,7 @@ async fn marginfi_group_add_bank_failure_fake_pyth_feed() -> anyhow::Result<()>
 #[tokio::test]
 async fn marginfi_group_accrue_interest_rates_success_1() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
         banks: vec![
             TestBankSetting {
                 mint: BankMint::USDC,
@@ -271,12 +330,17 @@ async fn marginfi_group_accrue_interest_rates_success_1() -> anyhow::Result<()>
     let borrower_mfi_account = borrower_mfi_account_f.load().await;
     let borrower_bank_account = borrower_mfi_account.lending_account.balances[1];
     let usdc_bank: Bank = usdc_bank_f.load().await;
-    let liabilities =
-        usdc_bank.get_liability_amount(borrower_bank_account.liability_shares.into())?;
+    let liabilities = usdc_bank
+        .liability_amount(ShareAmount::new(
+            borrower_bank_account.liability_shares.into(),
+        ))?
+        .value();
 
     let lender_mfi_account = lender_mfi_account_f.load().await;
     let lender_bank_account = lender_mfi_account.lending_account.balances[0];
-    let assets = usdc_bank.get_asset_amount(lender_bank_account.asset_shares.into())?;
+    let assets = usdc_bank
+        .asset_amount(ShareAmount::new(lender_bank_account.asset_shares.into()))?
+        .value();
 
     assert_eq_noise!(
         liabilities,
@@ -315,7 +379,7 @@ async fn marginfi_group_accrue_interest_rates_success_2() -> anyhow::Result<()>
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -362,12 +426,17 @@ async fn marginfi_group_accrue_interest_rates_success_2() -> anyhow::Result<()>
     let borrower_mfi_account = borrower_mfi_account_f.load().await;
     let borrower_bank_account = borrower_mfi_account.lending_account.balances[1];
     let usdc_bank = usdc_bank_f.load().await;
-    let liabilities =
-        usdc_bank.get_liability_amount(borrower_bank_account.liability_shares.into())?;
+    let liabilities = usdc_bank
+        .liability_amount(ShareAmount::new(
+            borrower_bank_account.liability_shares.into(),
+        ))?
+        .value();
 
     let lender_mfi_account = lender_mfi_account_f.load().await;
     let lender_bank_account = lender_mfi_account.lending_account.balances[0];
-    let assets = usdc_bank.get_asset_amount(lender_bank_account.asset_shares.into())?;
+    let assets = usdc_bank
+        .asset_amount(ShareAmount::new(lender_bank_account.asset_shares.into()))?
+        .value();
 
     assert_eq_noise!(liabilities, I80F48!(90000174657530), I80F48!(10));
     assert_eq_noise!(assets, I80F48!(100000171232876), I80F48!(10));
@@ -385,6 +454,224 @@ async fn marginfi_group_accrue_interest_rates_success_2() -> anyhow::Result<()>
     Ok(())
 }
 
+#[tokio::test]
+async fn marginfi_group_accrue_banks_success() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        group_config: Some(GroupConfig::default()),
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: Some(BankConfig {
+                    interest_rate_config: InterestRateConfig {
+                        optimal_utilization_rate: I80F48!(0.9).into(),
+                        plateau_interest_rate: I80F48!(1).into(),
+                        ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                    },
+                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
+                }),
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(100).await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 999)
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 90)
+        .await?;
+
+    // No banks to accrue
+    let res = test_f.marginfi_group.try_accrue_banks(&[]).await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::InvalidBankAccount);
+
+    let current_timestamp = {
+        let mut ctx = test_f.context.borrow_mut();
+        let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
+        // Advance clock by 1 year
+        clock.unix_timestamp += 365 * 24 * 60 * 60;
+        ctx.set_sysvar(&clock);
+        clock.unix_timestamp
+    };
+
+    test_f
+        .marginfi_group
+        .try_accrue_banks(&[usdc_bank_f, sol_bank_f])
+        .await?;
+
+    let usdc_bank: Bank = usdc_bank_f.load().await;
+    let sol_bank: Bank = sol_bank_f.load().await;
+    assert_eq!(usdc_bank.last_update, current_timestamp);
+    assert_eq!(sol_bank.last_update, current_timestamp);
+
+    let borrower_mfi_account = borrower_mfi_account_f.load().await;
+    let liabilities = usdc_bank
+        .liability_amount(ShareAmount::new(
+            borrower_mfi_account.lending_account.balances[1]
+                .liability_shares
+                .into(),
+        ))?
+        .value();
+    assert_eq_noise!(
+        liabilities,
+        I80F48::from(native!(180, "USDC")),
+        I80F48!(100)
+    );
+
+    // Banks already accrued at the current timestamp are skipped
+    test_f
+        .marginfi_group
+        .try_accrue_banks(&[sol_bank_f, usdc_bank_f])
+        .await?;
+
+    let usdc_bank_after: Bank = usdc_bank_f.load().await;
+    assert_eq!(
+        I80F48::from(usdc_bank_after.liability_share_value),
+        I80F48::from(usdc_bank.liability_share_value)
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn marginfi_group_keeper_tip() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings {
+        banks: vec![
+            TestBankSetting {
+                mint: BankMint::USDC,
+                config: Some(BankConfig {
+                    deposit_limit: native!(1_000_000_000, "USDC"),
+                    interest_rate_config: InterestRateConfig {
+                        optimal_utilization_rate: I80F48!(0.9).into(),
+                        plateau_interest_rate: I80F48!(1).into(),
+                        protocol_fixed_fee_apr: I80F48!(0.01).into(),
+                        insurance_fee_fixed_apr: I80F48!(0.01).into(),
+                        ..*DEFAULT_TEST_BANK_INTEREST_RATE_CONFIG
+                    },
+                    keeper_tip: 1_000,
+                    keeper_tip_interval: 60,
+                    ..*DEFAULT_USDC_TEST_BANK_CONFIG
+                }),
+            },
+            TestBankSetting {
+                mint: BankMint::SOL,
+                config: Some(BankConfig {
+                    asset_weight_init: I80F48!(1).into(),
+                    deposit_limit: native!(200_000_000, "SOL"),
+                    ..*DEFAULT_SOL_TEST_BANK_CONFIG
+                }),
+            },
+        ],
+        group_config: Some(GroupConfig::default()),
+    }))
+    .await;
+
+    let usdc_bank_f = test_f.get_bank(&BankMint::USDC);
+    let sol_bank_f = test_f.get_bank(&BankMint::SOL);
+
+    let lender_mfi_account_f = test_f.create_marginfi_account().await;
+    let lender_token_account_usdc = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(100_000_000)
+        .await;
+    lender_mfi_account_f
+        .try_bank_deposit(lender_token_account_usdc.key, usdc_bank_f, 100_000_000)
+        .await?;
+
+    let borrower_mfi_account_f = test_f.create_marginfi_account().await;
+    let borrower_token_account_sol = test_f
+        .sol_mint
+        .create_token_account_and_mint_to(10_000_000)
+        .await;
+    borrower_mfi_account_f
+        .try_bank_deposit(borrower_token_account_sol.key, sol_bank_f, 10_000_000)
+        .await?;
+    let borrower_token_account_usdc = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+    borrower_mfi_account_f
+        .try_bank_borrow(borrower_token_account_usdc.key, usdc_bank_f, 90_000_000)
+        .await?;
+
+    let keeper_token_account = test_f.usdc_mint.create_token_account_and_mint_to(0).await;
+
+    test_f.advance_time(60).await;
+
+    // Fee vault is still empty, nothing to tip
+    test_f
+        .marginfi_group
+        .try_accrue_interest_with_keeper_tip(usdc_bank_f, Some(keeper_token_account.key))
+        .await?;
+    assert_eq!(keeper_token_account.balance().await, 0);
+
+    test_f
+        .marginfi_group
+        .try_collect_fees_with_keeper_tip(usdc_bank_f, Some(keeper_token_account.key))
+        .await?;
+    assert_eq!(keeper_token_account.balance().await, 1_000);
+
+    let protocol_fees = usdc_bank_f
+        .get_vault_token_account(BankVaultType::Fee)
+        .await;
+    assert_eq!(protocol_fees.balance().await, 1712328 - 1_000);
+
+    // Tips are paid at most once per interval
+    test_f.advance_time(30).await;
+    test_f.refresh_blockhash().await;
+    test_f
+        .marginfi_group
+        .try_accrue_interest_with_keeper_tip(usdc_bank_f, Some(keeper_token_account.key))
+        .await?;
+    assert_eq!(keeper_token_account.balance().await, 1_000);
+
+    test_f.advance_time(30).await;
+    test_f.refresh_blockhash().await;
+    test_f
+        .marginfi_group
+        .try_accrue_interest_with_keeper_tip(usdc_bank_f, Some(keeper_token_account.key))
+        .await?;
+    assert_eq!(keeper_token_account.balance().await, 2_000);
+
+    // Batched accruals tip the banks followed by their tip accounts
+    test_f.advance_time(60).await;
+    test_f.refresh_blockhash().await;
+    test_f
+        .marginfi_group
+        .try_accrue_banks_with_keeper_tips(&[
+            (sol_bank_f, None),
+            (usdc_bank_f, Some(keeper_token_account.key)),
+        ])
+        .await?;
+    assert_eq!(keeper_token_account.balance().await, 3_000);
+    assert_eq!(
+        sol_bank_f.load().await.last_update,
+        usdc_bank_f.load().await.last_update
+    );
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn marginfi_group_handle_bankruptcy_failure_not_bankrupt() -> anyhow::Result<()> {
     let test_f = TestFixture::new(Some(TestSettings {
@@ -401,7 +688,7 @@ async fn marginfi_group_handle_bankruptcy_failure_not_bankrupt() -> anyhow::Resu
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -457,7 +744,7 @@ async fn marginfi_group_handle_bankruptcy_success_no_debt() -> anyhow::Result<()
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -508,7 +795,7 @@ async fn marginfi_group_handle_bankruptcy_success_no_debt() -> anyhow::Result<()
 #[tokio::test]
 async fn marginfi_group_handle_bankruptcy_success_fully_insured() -> anyhow::Result<()> {
     let mut test_f = TestFixture::new(Some(TestSettings {
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
         banks: vec![
             TestBankSetting {
                 mint: BankMint::USDC,
@@ -595,11 +882,13 @@ async fn marginfi_group_handle_bankruptcy_success_fully_insured() -> anyhow::Res
     let lender_mfi_account = lender_mfi_account_f.load().await;
     let usdc_bank = test_f.get_bank(&BankMint::USDC).load().await;
 
-    let lender_usdc_value = usdc_bank.get_asset_amount(
-        lender_mfi_account.lending_account.balances[0]
-            .asset_shares
-            .into(),
-    )?;
+    let lender_usdc_value = usdc_bank
+        .asset_amount(ShareAmount::new(
+            lender_mfi_account.lending_account.balances[0]
+                .asset_shares
+                .into(),
+        ))?
+        .value();
 
     assert_eq_noise!(
         lender_usdc_value,
@@ -614,6 +903,21 @@ async fn marginfi_group_handle_bankruptcy_success_fully_insured() -> anyhow::Res
 
     assert_eq!(insurance_amount.balance().await, 0);
 
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.insured_bad_debt),
+        I80F48::from(native!(10_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq!(I80F48::from(usdc_bank.socialized_loss), I80F48::ZERO);
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+
+    assert_eq!(marginfi_group.bankruptcy_count, 1);
+    assert_eq!(
+        I80F48::from(marginfi_group.socialized_loss_value),
+        I80F48::ZERO
+    );
+
     // Test account is disabled
 
     // Deposit 1 SOL
@@ -685,7 +989,7 @@ async fn marginfi_group_handle_bankruptcy_success_partially_insured() -> anyhow:
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -757,11 +1061,13 @@ async fn marginfi_group_handle_bankruptcy_success_partially_insured() -> anyhow:
     let lender_mfi_account = lender_mfi_account_f.load().await;
     let usdc_bank = test_f.get_bank(&BankMint::USDC).load().await;
 
-    let lender_usdc_value = usdc_bank.get_asset_amount(
-        lender_mfi_account.lending_account.balances[0]
-            .asset_shares
-            .into(),
-    )?;
+    let lender_usdc_value = usdc_bank
+        .asset_amount(ShareAmount::new(
+            lender_mfi_account.lending_account.balances[0]
+                .asset_shares
+                .into(),
+        ))?
+        .value();
 
     assert_eq_noise!(
         lender_usdc_value,
@@ -776,6 +1082,27 @@ async fn marginfi_group_handle_bankruptcy_success_partially_insured() -> anyhow:
 
     assert_eq!(insurance_amount.balance().await, 0);
 
+    // Realized loss is tracked in the bank and group totals
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.insured_bad_debt),
+        I80F48::from(native!(5_000, "USDC")),
+        I80F48::ONE
+    );
+    assert_eq_noise!(
+        I80F48::from(usdc_bank.socialized_loss),
+        I80F48::from(native!(5_000, "USDC")),
+        I80F48::ONE
+    );
+
+    let marginfi_group = test_f.marginfi_group.load().await;
+
+    assert_eq!(marginfi_group.bankruptcy_count, 1);
+    assert_eq_noise!(
+        I80F48::from(marginfi_group.socialized_loss_value),
+        I80F48::from_num(5_000),
+        I80F48!(0.001)
+    );
+
     Ok(())
 }
 
@@ -795,7 +1122,7 @@ async fn marginfi_group_handle_bankruptcy_success_not_insured() -> anyhow::Resul
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -847,11 +1174,13 @@ async fn marginfi_group_handle_bankruptcy_success_not_insured() -> anyhow::Resul
     let lender_mfi_account = lender_mfi_account_f.load().await;
     let usdc_bank = usdc_bank_f.load().await;
 
-    let lender_usdc_value = usdc_bank.get_asset_amount(
-        lender_mfi_account.lending_account.balances[0]
-            .asset_shares
-            .into(),
-    )?;
+    let lender_usdc_value = usdc_bank
+        .asset_amount(ShareAmount::new(
+            lender_mfi_account.lending_account.balances[0]
+                .asset_shares
+                .into(),
+        ))?
+        .value();
 
     assert_eq_noise!(
         lender_usdc_value,
@@ -878,7 +1207,7 @@ async fn marginfi_group_handle_bankruptcy_success_not_insured_3_depositors() ->
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -950,11 +1279,13 @@ async fn marginfi_group_handle_bankruptcy_success_not_insured_3_depositors() ->
     let lender_1_mfi_account = lender_1_mfi_account_f.load().await;
     let usdc_bank = usdc_bank_f.load().await;
 
-    let lender_usdc_value = usdc_bank.get_asset_amount(
-        lender_1_mfi_account.lending_account.balances[0]
-            .asset_shares
-            .into(),
-    )?;
+    let lender_usdc_value = usdc_bank
+        .asset_amount(ShareAmount::new(
+            lender_1_mfi_account.lending_account.balances[0]
+                .asset_shares
+                .into(),
+        ))?
+        .value();
 
     assert_eq_noise!(
         lender_usdc_value,
@@ -972,7 +1303,7 @@ async fn marginfi_group_bank_paused_should_error() -> anyhow::Result<()> {
             mint: BankMint::USDC,
             config: None,
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -1011,7 +1342,7 @@ async fn marginfi_group_bank_reduce_only_withdraw_success() -> anyhow::Result<()
             mint: BankMint::USDC,
             config: None,
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -1056,7 +1387,7 @@ async fn marginfi_group_bank_reduce_only_deposit_success() -> anyhow::Result<()>
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -1113,7 +1444,7 @@ async fn marginfi_group_bank_reduce_only_borrow_failure() -> anyhow::Result<()>
                 }),
             },
         ],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -1158,7 +1489,7 @@ async fn marginfi_group_bank_reduce_only_deposit_failure() -> anyhow::Result<()>
             mint: BankMint::USDC,
             config: None,
         }],
-        group_config: Some(GroupConfig { admin: None }),
+        group_config: Some(GroupConfig::default()),
     }))
     .await;
 
@@ -1265,3 +1596,95 @@ async fn marginfi_group_init_limit_0() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn marginfi_group_bank_outflow_limit() -> anyhow::Result<()> {
+    let test_f = TestFixture::new(Some(TestSettings::all_banks_payer_not_admin())).await;
+
+    let start_time = 1_700_000_000;
+    test_f.set_time(start_time);
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_USDC_FEED, start_time)
+        .await;
+
+    let usdc_bank = test_f.get_bank(&BankMint::USDC);
+
+    let depositor = test_f.create_marginfi_account().await;
+    let usdc_token_account = test_f
+        .usdc_mint
+        .create_token_account_and_mint_to(1_000)
+        .await;
+    depositor
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 1_000)
+        .await?;
+
+    // Outflow limit without a window is invalid
+    let res = usdc_bank
+        .update_config(BankConfigOpt {
+            outflow_limit: Some(native!(100, "USDC")),
+            ..BankConfigOpt::default()
+        })
+        .await;
+    assert!(res.is_err());
+
+    usdc_bank
+        .update_config(BankConfigOpt {
+            outflow_limit: Some(native!(100, "USDC")),
+            outflow_window: Some(3600),
+            ..BankConfigOpt::default()
+        })
+        .await?;
+
+    depositor
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 60, None)
+        .await?;
+
+    let res = depositor
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 50, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OutflowLimitExceeded);
+
+    // Deposits offset earlier outflows, net outflow is now 40
+    depositor
+        .try_bank_deposit(usdc_token_account.key, usdc_bank, 20)
+        .await?;
+    depositor
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 50, None)
+        .await?;
+
+    // Half a window later, half of the limit is available again
+    test_f.advance_time(1800).await;
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_USDC_FEED, start_time + 1800)
+        .await;
+
+    depositor
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 60, None)
+        .await?;
+
+    let res = depositor
+        .try_bank_withdraw(usdc_token_account.key, usdc_bank, 1, None)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OutflowLimitExceeded);
+
+    // Borrows count towards the same limit
+    let sol_bank = test_f.get_bank(&BankMint::SOL);
+    let sol_depositor = test_f.create_marginfi_account().await;
+    let sol_token_account = test_f.sol_mint.create_token_account_and_mint_to(100).await;
+    test_f
+        .set_pyth_oracle_timestamp(PYTH_SOL_FEED, start_time + 1800)
+        .await;
+    sol_depositor
+        .try_bank_deposit(sol_token_account.key, sol_bank, 100)
+        .await?;
+
+    let res = sol_depositor
+        .try_bank_borrow(usdc_token_account.key, usdc_bank, 1)
+        .await;
+    assert!(res.is_err());
+    assert_custom_error!(res.unwrap_err(), MarginfiError::OutflowLimitExceeded);
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use super::utils::load_and_deserialize;
 use crate::prelude::{
-    get_emissions_authority_address, get_emissions_token_account_address, MintFixture,
-    TokenAccountFixture,
+    get_emissions_authority_address, get_emissions_token_account_address, get_receipt_mint_address,
+    get_receipt_mint_authority_address, MintFixture, TokenAccountFixture,
 };
 use anchor_lang::{
     prelude::{AccountMeta, Pubkey},
@@ -15,9 +15,7 @@ use marginfi::{
 use solana_program::instruction::Instruction;
 use solana_program_test::BanksClientError;
 use solana_program_test::ProgramTestContext;
-#[cfg(feature = "lip")]
-use solana_sdk::signature::Keypair;
-use solana_sdk::{signer::Signer, transaction::Transaction};
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
 use std::{cell::RefCell, fmt::Debug, rc::Rc};
 
 #[derive(Clone)]
@@ -48,6 +46,16 @@ impl BankFixture {
         find_bank_vault_authority_pda(&self.key, vault_type)
     }
 
+    /// Remaining accounts tipping `keeper_token_account` on permissionless cranks.
+    pub fn get_keeper_tip_account_metas(&self, keeper_token_account: Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.get_vault(BankVaultType::Fee).0, false),
+            AccountMeta::new_readonly(self.get_vault_authority(BankVaultType::Fee).0, false),
+            AccountMeta::new(keeper_token_account, false),
+            AccountMeta::new_readonly(anchor_spl::token::ID, false),
+        ]
+    }
+
     pub async fn load(&self) -> Bank {
         load_and_deserialize::<Bank>(self.ctx.clone(), &self.key).await
     }
@@ -101,6 +109,32 @@ impl BankFixture {
         max_deposits: u64,
         max_rewards: u64,
         reward_funding_account: Pubkey,
+    ) -> Result<crate::lip::LipCampaignFixture, BanksClientError> {
+        self.try_create_campaign_with_options(
+            lockup_period,
+            max_deposits,
+            max_rewards,
+            reward_funding_account,
+            0,
+            0,
+            vec![],
+            0,
+        )
+        .await
+    }
+
+    #[cfg(feature = "lip")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn try_create_campaign_with_options(
+        &self,
+        lockup_period: u64,
+        max_deposits: u64,
+        max_rewards: u64,
+        reward_funding_account: Pubkey,
+        early_withdrawal_reward_bps: u64,
+        max_deposit_per_user: u64,
+        lockup_tiers: Vec<liquidity_incentive_program::state::LockupTier>,
+        deposit_deadline: i64,
     ) -> Result<crate::lip::LipCampaignFixture, BanksClientError> {
         use crate::prelude::lip::*;
 
@@ -128,6 +162,10 @@ impl BankFixture {
                 lockup_period,
                 max_deposits,
                 max_rewards,
+                early_withdrawal_reward_bps,
+                max_deposit_per_user,
+                lockup_tiers,
+                deposit_deadline,
             }
             .data(),
         };
@@ -215,6 +253,7 @@ impl BankFixture {
         emissions_flags: Option<u64>,
         emissions_rate: Option<u64>,
         additional_emissions: Option<(u64, Pubkey)>,
+        emissions_end_timestamp: Option<u64>,
     ) -> Result<(), BanksClientError> {
         let bank = self.load().await;
 
@@ -238,6 +277,7 @@ impl BankFixture {
                 emissions_flags,
                 emissions_rate,
                 additional_emissions: additional_emissions.map(|(a, _)| a),
+                emissions_end_timestamp,
             }
             .data(),
         };
@@ -262,6 +302,203 @@ impl BankFixture {
         Ok(())
     }
 
+    pub async fn try_fund_emissions(
+        &self,
+        amount: u64,
+        funder: &Keypair,
+        funding_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.load().await;
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolFundEmissions {
+                marginfi_group: bank.group,
+                bank: self.key,
+                emissions_mint: bank.emissions_mint,
+                emissions_token_account: get_emissions_token_account_address(
+                    self.key,
+                    bank.emissions_mint,
+                )
+                .0,
+                funder: funder.pubkey(),
+                emissions_funding_account: funding_account,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolFundEmissions { amount }.data(),
+        };
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer, funder],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_setup_receipt_mint(&self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolSetupReceiptMint {
+                marginfi_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                receipt_mint_authority: get_receipt_mint_authority_address(self.key).0,
+                receipt_mint: get_receipt_mint_address(self.key).0,
+                rent: solana_program::sysvar::rent::id(),
+                token_program: anchor_spl::token::ID,
+                system_program: solana_program::system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolSetupReceiptMint {}.data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Redeem `amount` native receipt tokens held by the payer
+    pub async fn try_redeem_receipt(
+        &self,
+        source_receipt_account: Pubkey,
+        destination_account: Pubkey,
+        amount: u64,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountRedeemReceipt {
+                marginfi_group: self.load().await.group,
+                signer: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                receipt_mint: get_receipt_mint_address(self.key).0,
+                source_receipt_account,
+                destination_token_account: destination_account,
+                bank_liquidity_vault_authority: self
+                    .get_vault_authority(BankVaultType::Liquidity)
+                    .0,
+                bank_liquidity_vault: self.get_vault(BankVaultType::Liquidity).0,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountRedeemReceipt { amount }.data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_force_close_balance(
+        &self,
+        marginfi_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolForceCloseBalance {
+                marginfi_group: self.load().await.group,
+                admin: self.ctx.borrow().payer.pubkey(),
+                bank: self.key,
+                marginfi_account,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolForceCloseBalance {}.data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn try_withdraw_referral_fees(
+        &self,
+        referrer: &Keypair,
+        destination_token_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolWithdrawReferralFees {
+                marginfi_group: self.load().await.group,
+                referrer: referrer.pubkey(),
+                bank: self.key,
+                destination_token_account,
+                bank_liquidity_vault_authority: self
+                    .get_vault_authority(BankVaultType::Liquidity)
+                    .0,
+                bank_liquidity_vault: self.get_vault(BankVaultType::Liquidity).0,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingPoolWithdrawReferralFees {}.data(),
+        };
+
+        let tx = {
+            let ctx = self.ctx.borrow_mut();
+
+            Transaction::new_signed_with_payer(
+                &[ix],
+                Some(&ctx.payer.pubkey()),
+                &[&ctx.payer, referrer],
+                ctx.last_blockhash,
+            )
+        };
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
     pub async fn get_vault_token_account(&self, vault_type: BankVaultType) -> TokenAccountFixture {
         let (vault, _) = self.get_vault(vault_type);
 
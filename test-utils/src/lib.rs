@@ -4,6 +4,7 @@ pub mod lip;
 pub mod marginfi_account;
 pub mod marginfi_group;
 pub mod prelude;
+pub mod snapshot;
 pub mod spl;
 pub mod test;
 pub mod utils;
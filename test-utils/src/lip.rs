@@ -1,19 +1,19 @@
 #![cfg(feature = "lip")]
 
-use crate::utils::lip::*;
-use anchor_lang::AnchorDeserialize;
+use crate::utils::{get_emissions_authority_address, get_emissions_token_account_address, lip::*};
 use anchor_lang::{
-    prelude::{Pubkey, ToAccountMetas},
+    prelude::{AccountMeta, Pubkey, ToAccountMetas},
     InstructionData,
 };
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
 use anyhow::Result;
 use liquidity_incentive_program as lip;
-use solana_program::instruction::Instruction;
+use solana_program::{clock::Clock, instruction::Instruction};
 use solana_program_test::{BanksClientError, ProgramTestContext};
 use solana_sdk::{signature::Keypair, signer::Signer, transaction::Transaction};
 use std::{cell::RefCell, rc::Rc};
 
-use crate::bank::BankFixture;
+use crate::{bank::BankFixture, spl::balance_of};
 
 pub struct LipCampaignFixture {
     pub key: Pubkey,
@@ -30,6 +30,16 @@ impl LipCampaignFixture {
         &self,
         funding_account: Pubkey,
         amount: u64,
+    ) -> Result<Pubkey, BanksClientError> {
+        self.try_create_deposit_in_tier(funding_account, amount, 0)
+            .await
+    }
+
+    pub async fn try_create_deposit_in_tier(
+        &self,
+        funding_account: Pubkey,
+        amount: u64,
+        lockup_tier: u64,
     ) -> Result<Pubkey, BanksClientError> {
         let bank = self.bank_f.load().await;
         let deposit_key = Keypair::new();
@@ -41,6 +51,11 @@ impl LipCampaignFixture {
                 campaign: self.key,
                 signer: self.ctx.borrow().payer.pubkey(),
                 deposit: deposit_key.pubkey(),
+                user_deposits: get_user_deposits_address(
+                    self.key,
+                    self.ctx.borrow().payer.pubkey(),
+                )
+                .0,
                 mfi_pda_signer: get_deposit_mfi_authority(deposit_key.pubkey()).0,
                 funding_account,
                 temp_token_account: temp_token_account_key.pubkey(),
@@ -55,7 +70,11 @@ impl LipCampaignFixture {
                 system_program: solana_program::system_program::id(),
             }
             .to_account_metas(Some(true)),
-            data: lip::instruction::CreateDeposit { amount }.data(),
+            data: lip::instruction::CreateDeposit {
+                amount,
+                lockup_tier,
+            }
+            .data(),
         };
 
         let tx = Transaction::new_signed_with_payer(
@@ -82,11 +101,57 @@ impl LipCampaignFixture {
         &self,
         deposit_pk: Pubkey,
         destination_account_address: Pubkey,
-    ) -> Result<()> {
+    ) -> Result<(), BanksClientError> {
+        self.try_close_deposit(
+            deposit_pk,
+            destination_account_address,
+            None,
+            lip::instruction::EndDeposit {}.data(),
+        )
+        .await
+    }
+
+    /// Ends a deposit into a campaign whose bank has emissions, forwarding them to `emissions_destination_account`.
+    pub async fn try_end_deposit_with_emissions(
+        &self,
+        deposit_pk: Pubkey,
+        destination_account_address: Pubkey,
+        emissions_destination_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        self.try_close_deposit(
+            deposit_pk,
+            destination_account_address,
+            Some(emissions_destination_account),
+            lip::instruction::EndDeposit {}.data(),
+        )
+        .await
+    }
+
+    pub async fn try_early_end_deposit(
+        &self,
+        deposit_pk: Pubkey,
+        destination_account_address: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        self.try_close_deposit(
+            deposit_pk,
+            destination_account_address,
+            None,
+            lip::instruction::EarlyEndDeposit {}.data(),
+        )
+        .await
+    }
+
+    async fn try_close_deposit(
+        &self,
+        deposit_pk: Pubkey,
+        destination_account_address: Pubkey,
+        emissions_destination_account: Option<Pubkey>,
+        data: Vec<u8>,
+    ) -> Result<(), BanksClientError> {
         let bank = self.bank_f.load().await;
         let temp_token_account_key = Keypair::new();
 
-        let ix = Instruction {
+        let mut ix = Instruction {
             program_id: lip::id(),
             accounts: lip::accounts::EndDeposit {
                 campaign: self.key,
@@ -112,9 +177,24 @@ impl LipCampaignFixture {
                 system_program: solana_program::system_program::id(),
             }
             .to_account_metas(Some(true)),
-            data: lip::instruction::EndDeposit {}.data(),
+            data,
         };
 
+        if let Some(emissions_destination_account) = emissions_destination_account {
+            ix.accounts.extend([
+                AccountMeta::new_readonly(bank.emissions_mint, false),
+                AccountMeta::new_readonly(
+                    get_emissions_authority_address(self.bank_f.key, bank.emissions_mint).0,
+                    false,
+                ),
+                AccountMeta::new(
+                    get_emissions_token_account_address(self.bank_f.key, bank.emissions_mint).0,
+                    false,
+                ),
+                AccountMeta::new(emissions_destination_account, false),
+            ]);
+        }
+
         let tx = Transaction::new_signed_with_payer(
             &[ix],
             Some(&self.ctx.borrow().payer.pubkey()),
@@ -126,9 +206,267 @@ impl LipCampaignFixture {
             .borrow_mut()
             .banks_client
             .process_transaction(tx)
-            .await?;
+            .await
+    }
 
-        Ok(())
+    pub async fn try_claim_deposit_emissions(
+        &self,
+        deposit_pk: Pubkey,
+        destination_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let bank = self.bank_f.load().await;
+
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts: lip::accounts::ClaimDepositEmissions {
+                campaign: self.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                deposit: deposit_pk,
+                mfi_pda_signer: get_deposit_mfi_authority(deposit_pk).0,
+                marginfi_account: get_marginfi_account_address(deposit_pk).0,
+                marginfi_group: bank.group,
+                marginfi_bank: self.bank_f.key,
+                emissions_mint: bank.emissions_mint,
+                emissions_auth: get_emissions_authority_address(
+                    self.bank_f.key,
+                    bank.emissions_mint,
+                )
+                .0,
+                emissions_vault: get_emissions_token_account_address(
+                    self.bank_f.key,
+                    bank.emissions_mint,
+                )
+                .0,
+                destination_account,
+                marginfi_program: marginfi::id(),
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: lip::instruction::ClaimDepositEmissions {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_end_campaign(
+        &self,
+        destination_account_address: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts: lip::accounts::EndCampaign {
+                campaign: self.key,
+                campaign_reward_vault: get_reward_vault_address(self.key).0,
+                campaign_reward_vault_authority: get_reward_vault_authority(self.key).0,
+                admin: self.ctx.borrow().payer.pubkey(),
+                destination_account: destination_account_address,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: lip::instruction::EndCampaign {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_pause_campaign(&self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts: lip::accounts::PauseCampaign {
+                campaign: self.key,
+                admin: self.ctx.borrow().payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: lip::instruction::PauseCampaign {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_resume_campaign(&self) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts: lip::accounts::ResumeCampaign {
+                campaign: self.key,
+                admin: self.ctx.borrow().payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: lip::instruction::ResumeCampaign {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    /// Reconcile the exited deposits of the campaign, `open_deposit_keys` being its open deposits.
+    pub async fn try_reconcile_exited_deposits(
+        &self,
+        open_deposit_keys: &[Pubkey],
+    ) -> Result<(), BanksClientError> {
+        let mut accounts = lip::accounts::ReconcileExitedDeposits {
+            campaign: self.key,
+            admin: self.ctx.borrow().payer.pubkey(),
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(
+            open_deposit_keys
+                .iter()
+                .map(|deposit_key| AccountMeta::new_readonly(*deposit_key, false)),
+        );
+
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts,
+            data: lip::instruction::ReconcileExitedDeposits {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_update_campaign(
+        &self,
+        funding_account: Pubkey,
+        additional_rewards: u64,
+        max_deposits: Option<u64>,
+        lockup_period: Option<u64>,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts: lip::accounts::UpdateCampaign {
+                campaign: self.key,
+                campaign_reward_vault: get_reward_vault_address(self.key).0,
+                admin: self.ctx.borrow().payer.pubkey(),
+                funding_account,
+                token_program: anchor_spl::token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: lip::instruction::UpdateCampaign {
+                additional_rewards,
+                max_deposits,
+                lockup_period,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    pub async fn try_transfer_deposit(
+        &self,
+        deposit_key: Pubkey,
+        owner: &Keypair,
+        new_owner: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let ix = Instruction {
+            program_id: lip::id(),
+            accounts: lip::accounts::TransferDeposit {
+                deposit: deposit_key,
+                owner: owner.pubkey(),
+                new_owner,
+            }
+            .to_account_metas(Some(true)),
+            data: lip::instruction::TransferDeposit {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&self.ctx.borrow().payer.pubkey()),
+            &[&self.ctx.borrow().payer, owner],
+            self.ctx.borrow().last_blockhash,
+        );
+
+        self.ctx
+            .borrow_mut()
+            .banks_client
+            .process_transaction(tx)
+            .await
+    }
+
+    /// Sets the clock `offset` seconds away from the end of the lockup of `deposit_key`,
+    /// e.g. `-1` for the last second before maturity.
+    pub async fn warp_to_deposit_maturity(&self, deposit_key: Pubkey, offset: i64) {
+        let campaign = self.load().await;
+        let deposit = self.load_deposit(deposit_key).await;
+        let lockup_period = deposit.lockup_period(&campaign).unwrap();
+
+        let mut clock: Clock = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_sysvar()
+            .await
+            .unwrap();
+        clock.unix_timestamp = deposit.start_time + lockup_period as i64 + offset;
+        self.ctx.borrow_mut().set_sysvar(&clock);
+    }
+
+    pub async fn reward_vault_balance(&self) -> u64 {
+        balance_of(self.ctx.clone(), get_reward_vault_address(self.key).0).await
     }
 
     pub async fn load(&self) -> lip::state::Campaign {
@@ -144,6 +482,30 @@ impl LipCampaignFixture {
         lip::state::Campaign::deserialize(&mut &account.data[8..]).unwrap()
     }
 
+    pub async fn set_campaign(&self, campaign: &lip::state::Campaign) -> Result<()> {
+        let mut ctx = self.ctx.borrow_mut();
+        let mut account = ctx.banks_client.get_account(self.key).await?.unwrap();
+        let mut new_data = account.data[..8].to_vec();
+        campaign.serialize(&mut new_data)?;
+        account.data = new_data;
+        ctx.set_account(&self.key, &account.into());
+
+        Ok(())
+    }
+
+    pub async fn load_user_deposits(&self, owner: Pubkey) -> lip::state::UserDeposits {
+        let account = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(get_user_deposits_address(self.key, owner).0)
+            .await
+            .unwrap()
+            .unwrap();
+
+        lip::state::UserDeposits::deserialize(&mut &account.data[8..]).unwrap()
+    }
+
     pub async fn load_deposit(&self, deposit_key: Pubkey) -> lip::state::Deposit {
         let account = self
             .ctx
@@ -156,4 +518,19 @@ impl LipCampaignFixture {
 
         lip::state::Deposit::deserialize(&mut &account.data[8..]).unwrap()
     }
+
+    pub async fn set_deposit(
+        &self,
+        deposit_key: Pubkey,
+        deposit: &lip::state::Deposit,
+    ) -> Result<()> {
+        let mut ctx = self.ctx.borrow_mut();
+        let mut account = ctx.banks_client.get_account(deposit_key).await?.unwrap();
+        let mut new_data = account.data[..8].to_vec();
+        deposit.serialize(&mut new_data)?;
+        account.data = new_data;
+        ctx.set_account(&deposit_key, &account.into());
+
+        Ok(())
+    }
 }
@@ -1,10 +1,19 @@
 use super::{bank::BankFixture, prelude::*};
 use crate::ui_to_native;
 use anchor_lang::{prelude::*, system_program, InstructionData, ToAccountMetas};
-use anchor_spl::token;
-use marginfi::state::{
-    marginfi_account::MarginfiAccount,
-    marginfi_group::{Bank, BankVaultType},
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address},
+    token,
+};
+use fixed::types::I80F48;
+use marginfi::{
+    constants::{EMPTY_BALANCE_THRESHOLD, MARGINFI_ACCOUNT_SEED, SUB_ACCOUNT_COUNTER_SEED},
+    instructions::{AutoDeleverageConfig, SplitPositionCollateral},
+    state::{
+        fee_state::FeeState,
+        marginfi_account::MarginfiAccount,
+        marginfi_group::{Bank, BankVaultType},
+    },
 };
 use solana_program::instruction::Instruction;
 use solana_program_test::{BanksClientError, ProgramTestContext};
@@ -14,6 +23,34 @@ use solana_sdk::{
 };
 use std::{cell::RefCell, mem, rc::Rc};
 
+/// Sub-account `account_index` of `authority` in `marginfi_group`, and the counter of its
+/// sub-accounts.
+pub fn find_sub_account_pdas(
+    marginfi_group: &Pubkey,
+    authority: &Pubkey,
+    account_index: u16,
+) -> (Pubkey, Pubkey) {
+    let (marginfi_account, _) = Pubkey::find_program_address(
+        &[
+            MARGINFI_ACCOUNT_SEED.as_bytes(),
+            marginfi_group.as_ref(),
+            authority.as_ref(),
+            &account_index.to_le_bytes(),
+        ],
+        &marginfi::id(),
+    );
+    let (sub_account_counter, _) = Pubkey::find_program_address(
+        &[
+            SUB_ACCOUNT_COUNTER_SEED.as_bytes(),
+            marginfi_group.as_ref(),
+            authority.as_ref(),
+        ],
+        &marginfi::id(),
+    );
+
+    (marginfi_account, sub_account_counter)
+}
+
 #[derive(Default, Clone)]
 pub struct MarginfiAccountConfig {}
 
@@ -27,6 +64,16 @@ impl MarginfiAccountFixture {
         ctx: Rc<RefCell<ProgramTestContext>>,
         marginfi_group: &Pubkey,
     ) -> MarginfiAccountFixture {
+        Self::try_new_with_referrer(ctx, marginfi_group, None)
+            .await
+            .unwrap()
+    }
+
+    pub async fn try_new_with_referrer(
+        ctx: Rc<RefCell<ProgramTestContext>>,
+        marginfi_group: &Pubkey,
+        referrer: Option<Pubkey>,
+    ) -> anyhow::Result<MarginfiAccountFixture, BanksClientError> {
         let ctx_ref = ctx.clone();
         let account_key = Keypair::new();
 
@@ -43,7 +90,7 @@ impl MarginfiAccountFixture {
             let init_marginfi_account_ix = Instruction {
                 program_id: marginfi::id(),
                 accounts: accounts.to_account_metas(Some(true)),
-                data: marginfi::instruction::MarginfiAccountInitialize {}.data(),
+                data: marginfi::instruction::MarginfiAccountInitialize { referrer }.data(),
             };
 
             let tx = Transaction::new_signed_with_payer(
@@ -52,13 +99,62 @@ impl MarginfiAccountFixture {
                 &[&ctx.payer, &account_key],
                 ctx.last_blockhash,
             );
-            ctx.banks_client.process_transaction(tx).await.unwrap();
+            ctx.banks_client.process_transaction(tx).await?;
         }
 
-        MarginfiAccountFixture {
+        Ok(MarginfiAccountFixture {
             ctx: ctx_ref,
             key: account_key.pubkey(),
-        }
+        })
+    }
+
+    /// Sub-account `account_index` of the payer, at its PDA
+    pub async fn try_new_sub_account(
+        ctx: Rc<RefCell<ProgramTestContext>>,
+        marginfi_group: &Pubkey,
+        account_index: u16,
+    ) -> anyhow::Result<MarginfiAccountFixture, BanksClientError> {
+        let ctx_ref = ctx.clone();
+        let mut ctx = ctx.borrow_mut();
+
+        let (marginfi_account, sub_account_counter) =
+            find_sub_account_pdas(marginfi_group, &ctx.payer.pubkey(), account_index);
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiAccountInitializeSubAccount {
+                marginfi_group: *marginfi_group,
+                marginfi_account,
+                sub_account_counter,
+                authority: ctx.payer.pubkey(),
+                fee_payer: ctx.payer.pubkey(),
+                system_program: system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiAccountInitializeSubAccount {
+                account_index,
+                referrer: None,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(MarginfiAccountFixture {
+            ctx: ctx_ref,
+            key: marginfi_account,
+        })
+    }
+
+    /// Fixture for an existing marginfi account, e.g. loaded from a snapshot
+    pub fn from_key(ctx: Rc<RefCell<ProgramTestContext>>, key: Pubkey) -> MarginfiAccountFixture {
+        MarginfiAccountFixture { ctx, key }
     }
 
     pub async fn try_bank_deposit<T: Into<f64>>(
@@ -100,6 +196,104 @@ impl MarginfiAccountFixture {
         Ok(())
     }
 
+    /// Deposit native SOL, wrapped into the payer's wSOL ATA by the program
+    pub async fn try_bank_deposit_native<T: Into<f64>>(
+        &self,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountDepositNative {
+                marginfi_group: marginfi_account.group,
+                marginfi_account: self.key,
+                signer: ctx.payer.pubkey(),
+                bank: bank.key,
+                native_mint: token::spl_token::native_mint::ID,
+                signer_token_account: get_associated_token_address(
+                    &ctx.payer.pubkey(),
+                    &token::spl_token::native_mint::ID,
+                ),
+                bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountDepositNative {
+                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    /// Withdraw native SOL through the payer's wSOL ATA, which is closed by the program
+    pub async fn try_bank_withdraw_native<T: Into<f64>>(
+        &self,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+        let payer = self.ctx.borrow().payer.pubkey();
+
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountWithdrawNative {
+                marginfi_group: marginfi_account.group,
+                marginfi_account: self.key,
+                signer: payer,
+                bank: bank.key,
+                native_mint: token::spl_token::native_mint::ID,
+                destination_token_account: get_associated_token_address(
+                    &payer,
+                    &token::spl_token::native_mint::ID,
+                ),
+                bank_liquidity_vault_authority: bank
+                    .get_vault_authority(BankVaultType::Liquidity)
+                    .0,
+                bank_liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
+                token_program: token::ID,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountWithdrawNative {
+                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+                withdraw_all: None,
+            }
+            .data(),
+        };
+
+        ix.accounts
+            .extend_from_slice(&self.load_observation_account_metas(vec![], vec![]).await);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
     pub async fn try_bank_withdraw<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
@@ -107,6 +301,30 @@ impl MarginfiAccountFixture {
         ui_amount: T,
         withdraw_all: Option<bool>,
     ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_withdraw_ix(destination_account, bank, ui_amount, withdraw_all)
+            .await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn make_bank_withdraw_ix<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+        withdraw_all: Option<bool>,
+    ) -> Instruction {
         let marginfi_account = self.load().await;
 
         let mut ix = Instruction {
@@ -141,9 +359,23 @@ impl MarginfiAccountFixture {
                 .await,
         );
 
+        ix
+    }
+
+    pub async fn try_bank_borrow<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_borrow_ix(destination_account, bank, ui_amount)
+            .await;
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
         let mut ctx = self.ctx.borrow_mut();
         let tx = Transaction::new_signed_with_payer(
-            &[ix],
+            &[compute_budget_ix, ix],
             Some(&ctx.payer.pubkey().clone()),
             &[&ctx.payer],
             ctx.last_blockhash,
@@ -154,13 +386,40 @@ impl MarginfiAccountFixture {
         Ok(())
     }
 
-    pub async fn try_bank_borrow<T: Into<f64>>(
+    /// Borrow signed by the authority, the payer, with `fee_payer` paying the transaction fees.
+    pub async fn try_bank_borrow_with_fee_payer<T: Into<f64>>(
         &self,
         destination_account: Pubkey,
         bank: &BankFixture,
         ui_amount: T,
+        fee_payer: &Keypair,
     ) -> anyhow::Result<(), BanksClientError> {
+        let ix = self
+            .make_bank_borrow_ix(destination_account, bank, ui_amount)
+            .await;
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[compute_budget_ix, ix],
+            Some(&fee_payer.pubkey()),
+            &[fee_payer, &ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn make_bank_borrow_ix<T: Into<f64>>(
+        &self,
+        destination_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> Instruction {
         let marginfi_account = self.load().await;
+        let global_fee_wallet = self.load_global_fee_wallet().await;
 
         let mut ix = Instruction {
             program_id: marginfi::id(),
@@ -175,6 +434,9 @@ impl MarginfiAccountFixture {
                     .get_vault_authority(BankVaultType::Liquidity)
                     .0,
                 token_program: token::ID,
+                fee_state: get_fee_state_address().0,
+                global_fee_wallet,
+                system_program: system_program::ID,
             }
             .to_account_metas(Some(true)),
             data: marginfi::instruction::LendingAccountBorrow {
@@ -189,19 +451,7 @@ impl MarginfiAccountFixture {
                 .await,
         );
 
-        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
-
-        let mut ctx = self.ctx.borrow_mut();
-        let tx = Transaction::new_signed_with_payer(
-            &[compute_budget_ix, ix],
-            Some(&ctx.payer.pubkey().clone()),
-            &[&ctx.payer],
-            ctx.last_blockhash,
-        );
-
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        ix
     }
 
     pub async fn try_bank_repay<T: Into<f64>>(
@@ -301,6 +551,7 @@ impl MarginfiAccountFixture {
             bank_liquidity_vault: liab_bank_fixture.get_vault(BankVaultType::Liquidity).0,
             bank_insurance_vault: liab_bank_fixture.get_vault(BankVaultType::Insurance).0,
             token_program: token::ID,
+            fee_state: get_fee_state_address().0,
         }
         .to_account_metas(Some(true));
 
@@ -349,13 +600,235 @@ impl MarginfiAccountFixture {
         ctx.banks_client.process_transaction(tx).await
     }
 
+    /// Allows the payer to deleverage the account below `health_buffer`, or disallows it with `None`.
+    pub async fn try_configure_auto_deleverage(
+        &self,
+        health_buffer: Option<f64>,
+    ) -> std::result::Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiAccountConfigureAutoDeleverage {
+                marginfi_account: self.key,
+                signer: ctx.payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiAccountConfigureAutoDeleverage {
+                config: health_buffer.map(|health_buffer| AutoDeleverageConfig {
+                    keeper: ctx.payer.pubkey(),
+                    health_buffer: I80F48::from_num(health_buffer).into(),
+                }),
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Deleverages `account` as its keeper, taking over `asset_ui_amount` of its assets.
+    pub async fn try_deleverage<T: Into<f64>>(
+        &self,
+        account: &MarginfiAccountFixture,
+        asset_bank_fixture: &BankFixture,
+        asset_ui_amount: T,
+        liab_bank_fixture: &BankFixture,
+    ) -> std::result::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+
+        let asset_bank = asset_bank_fixture.load().await;
+        let liab_bank = liab_bank_fixture.load().await;
+
+        let mut accounts = marginfi::accounts::LendingAccountDeleverage {
+            marginfi_group: marginfi_account.group,
+            asset_bank: asset_bank_fixture.key,
+            liab_bank: liab_bank_fixture.key,
+            keeper_marginfi_account: self.key,
+            signer: self.ctx.borrow().payer.pubkey(),
+            marginfi_account: account.key,
+        }
+        .to_account_metas(Some(true));
+
+        accounts.extend(vec![
+            AccountMeta::new_readonly(asset_bank.config.oracle_keys[0], false),
+            AccountMeta::new_readonly(liab_bank.config.oracle_keys[0], false),
+        ]);
+
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts,
+            data: marginfi::instruction::LendingAccountDeleverage {
+                asset_amount: ui_to_native!(
+                    asset_ui_amount.into(),
+                    asset_bank_fixture.mint.mint.decimals
+                ),
+            }
+            .data(),
+        };
+
+        ix.accounts.extend_from_slice(
+            &self
+                .load_observation_account_metas(
+                    vec![asset_bank_fixture.key, liab_bank_fixture.key],
+                    vec![],
+                )
+                .await,
+        );
+
+        ix.accounts
+            .extend_from_slice(&account.load_observation_account_metas(vec![], vec![]).await);
+
+        let compute_budget_ix = ComputeBudgetInstruction::set_compute_unit_limit(1_400_000);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[compute_budget_ix, ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Splits the liability in `liability_bank` and `collateral` asset shares out into `destination`.
+    pub async fn try_split_position(
+        &self,
+        destination: &MarginfiAccountFixture,
+        liability_bank: &BankFixture,
+        collateral: Vec<(&BankFixture, I80F48)>,
+    ) -> std::result::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+
+        let mut fully_split_banks = vec![liability_bank.key];
+        for (bank, asset_shares) in collateral.iter() {
+            let remaining_asset_shares = marginfi_account
+                .lending_account
+                .balances
+                .iter()
+                .find(|balance| balance.active && balance.bank_pk == bank.key)
+                .map(|balance| I80F48::from(balance.asset_shares) - *asset_shares)
+                .unwrap_or_default();
+
+            if remaining_asset_shares < EMPTY_BALANCE_THRESHOLD {
+                fully_split_banks.push(bank.key);
+            }
+        }
+
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountSplitPosition {
+                marginfi_group: marginfi_account.group,
+                marginfi_account: self.key,
+                destination_marginfi_account: destination.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                liability_bank: liability_bank.key,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountSplitPosition {
+                collateral: collateral
+                    .iter()
+                    .map(|(bank, asset_shares)| SplitPositionCollateral {
+                        bank: bank.key,
+                        asset_shares: (*asset_shares).into(),
+                    })
+                    .collect(),
+            }
+            .data(),
+        };
+
+        ix.accounts.extend_from_slice(
+            &self
+                .load_observation_account_metas(vec![], fully_split_banks)
+                .await,
+        );
+
+        let mut destination_banks = vec![liability_bank.key];
+        destination_banks.extend(collateral.iter().map(|(bank, _)| bank.key));
+
+        ix.accounts.extend_from_slice(
+            &destination
+                .load_observation_account_metas(destination_banks, vec![])
+                .await,
+        );
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
     pub async fn try_withdraw_emissions(
         &self,
         bank: &BankFixture,
         recv_account: Pubkey,
+    ) -> std::result::Result<(), BanksClientError> {
+        let ix = self
+            .make_withdraw_emissions_ix(bank, recv_account, None)
+            .await;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Withdraw emissions to the ATA of `recv_wallet`, creating it if needed.
+    pub async fn try_withdraw_emissions_to_ata(
+        &self,
+        bank: &BankFixture,
+        recv_wallet: Pubkey,
     ) -> std::result::Result<(), BanksClientError> {
         let emissions_mint = bank.load().await.emissions_mint;
-        let ix = Instruction {
+        let recv_ata = get_associated_token_address(&recv_wallet, &emissions_mint);
+
+        let mut ix = self
+            .make_withdraw_emissions_ix(bank, recv_ata, Some(true))
+            .await;
+
+        ix.accounts.extend_from_slice(&[
+            AccountMeta::new_readonly(recv_wallet, false),
+            AccountMeta::new_readonly(associated_token::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ]);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    async fn make_withdraw_emissions_ix(
+        &self,
+        bank: &BankFixture,
+        recv_account: Pubkey,
+        create_destination_ata: Option<bool>,
+    ) -> Instruction {
+        let emissions_mint = bank.load().await.emissions_mint;
+
+        Instruction {
             program_id: marginfi::id(),
             accounts: marginfi::accounts::LendingAccountWithdrawEmissions {
                 marginfi_group: self.load().await.group,
@@ -369,9 +842,67 @@ impl MarginfiAccountFixture {
                 token_program: token::ID,
             }
             .to_account_metas(Some(true)),
-            data: marginfi::instruction::LendingAccountWithdrawEmissions {}.data(),
+            data: marginfi::instruction::LendingAccountWithdrawEmissions {
+                create_destination_ata,
+            }
+            .data(),
+        }
+    }
+
+    pub async fn try_pulse_health(&self) -> std::result::Result<(), BanksClientError> {
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountPulseHealth {
+                marginfi_account: self.key,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountPulseHealth {}.data(),
         };
 
+        ix.accounts
+            .extend_from_slice(&self.load_observation_account_metas(vec![], vec![]).await);
+
+        let mut ctx = self.ctx.borrow_mut();
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_mint_receipt<T: Into<f64>>(
+        &self,
+        destination_receipt_account: Pubkey,
+        bank: &BankFixture,
+        ui_amount: T,
+    ) -> std::result::Result<(), BanksClientError> {
+        let marginfi_account = self.load().await;
+
+        let mut ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingAccountMintReceipt {
+                marginfi_group: marginfi_account.group,
+                marginfi_account: self.key,
+                signer: self.ctx.borrow().payer.pubkey(),
+                bank: bank.key,
+                receipt_mint: get_receipt_mint_address(bank.key).0,
+                receipt_mint_authority: get_receipt_mint_authority_address(bank.key).0,
+                destination_receipt_account,
+                token_program: token::ID,
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::LendingAccountMintReceipt {
+                amount: ui_to_native!(ui_amount.into(), bank.mint.mint.decimals),
+            }
+            .data(),
+        };
+
+        ix.accounts
+            .extend_from_slice(&self.load_observation_account_metas(vec![], vec![]).await);
+
         let mut ctx = self.ctx.borrow_mut();
         let tx = Transaction::new_signed_with_payer(
             &[ix],
@@ -453,6 +984,26 @@ impl MarginfiAccountFixture {
         load_and_deserialize::<MarginfiAccount>(self.ctx.clone(), &self.key).await
     }
 
+    /// Wallet the flat program fee of a borrow goes to, the payer while there is no fee state.
+    async fn load_global_fee_wallet(&self) -> Pubkey {
+        let fee_state = self
+            .ctx
+            .borrow_mut()
+            .banks_client
+            .get_account(get_fee_state_address().0)
+            .await
+            .unwrap();
+
+        match fee_state {
+            Some(account) => {
+                FeeState::try_deserialize(&mut account.data.as_slice())
+                    .unwrap()
+                    .global_fee_wallet
+            }
+            None => self.ctx.borrow().payer.pubkey(),
+        }
+    }
+
     pub fn get_size() -> usize {
         mem::size_of::<MarginfiAccount>() + 8
     }
@@ -172,16 +172,172 @@ impl MarginfiGroupFixture {
         Ok(())
     }
 
-    pub async fn try_accrue_interest(&self, bank: &BankFixture) -> Result<()> {
+    pub async fn try_update(&self, config: GroupConfig) -> Result<(), BanksClientError> {
         let mut ctx = self.ctx.borrow_mut();
 
         let ix = Instruction {
             program_id: marginfi::id(),
-            accounts: marginfi::accounts::LendingPoolAccrueBankInterest {
+            accounts: marginfi::accounts::MarginfiGroupConfigure {
                 marginfi_group: self.key,
-                bank: bank.key,
+                admin: ctx.payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiGroupConfigure { config }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_set_pending_admin(
+        &self,
+        pending_admin: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiGroupSetPendingAdmin {
+                marginfi_group: self.key,
+                admin: ctx.payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiGroupSetPendingAdmin { pending_admin }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_accept_admin(&self, pending_admin: &Keypair) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MarginfiGroupAcceptAdmin {
+                marginfi_group: self.key,
+                pending_admin: pending_admin.pubkey(),
             }
             .to_account_metas(Some(true)),
+            data: marginfi::instruction::MarginfiGroupAcceptAdmin {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer, pending_admin],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn try_accrue_interest(&self, bank: &BankFixture) -> Result<()> {
+        self.try_accrue_interest_with_keeper_tip(bank, None).await
+    }
+
+    pub async fn try_accrue_banks(&self, banks: &[&BankFixture]) -> Result<(), BanksClientError> {
+        self.try_accrue_banks_with_keeper_tips(
+            &banks.iter().map(|bank| (*bank, None)).collect::<Vec<_>>(),
+        )
+        .await
+    }
+
+    /// Accrue `banks`, each followed by the tip accounts of its keeper token account if any.
+    pub async fn try_accrue_banks_with_keeper_tips(
+        &self,
+        banks: &[(&BankFixture, Option<Pubkey>)],
+    ) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let mut accounts = marginfi::accounts::LendingPoolAccrueBanks {
+            marginfi_group: self.key,
+        }
+        .to_account_metas(Some(true));
+        for (bank, keeper_token_account) in banks {
+            accounts.push(AccountMeta::new(bank.key, false));
+            if let Some(keeper_token_account) = keeper_token_account {
+                accounts.extend(bank.get_keeper_tip_account_metas(*keeper_token_account));
+            }
+        }
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts,
+            data: marginfi::instruction::LendingPoolAccrueBanks {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn try_sync_borrow_value(&self, banks: &[&BankFixture]) -> Result<()> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let mut accounts = marginfi::accounts::LendingPoolSyncBorrowValue {
+            marginfi_group: self.key,
+        }
+        .to_account_metas(Some(true));
+        accounts.extend(banks.iter().map(|bank| AccountMeta::new(bank.key, false)));
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts,
+            data: marginfi::instruction::LendingPoolSyncBorrowValue {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn try_accrue_interest_with_keeper_tip(
+        &self,
+        bank: &BankFixture,
+        keeper_token_account: Option<Pubkey>,
+    ) -> Result<()> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let mut accounts = marginfi::accounts::LendingPoolAccrueBankInterest {
+            marginfi_group: self.key,
+            bank: bank.key,
+        }
+        .to_account_metas(Some(true));
+
+        if let Some(keeper_token_account) = keeper_token_account {
+            accounts.extend(bank.get_keeper_tip_account_metas(keeper_token_account));
+        }
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts,
             data: marginfi::instruction::LendingPoolAccrueBankInterest {}.data(),
         };
 
@@ -198,21 +354,69 @@ impl MarginfiGroupFixture {
     }
 
     pub async fn try_collect_fees(&self, bank: &BankFixture) -> Result<()> {
+        self.try_collect_fees_with_keeper_tip(bank, None).await
+    }
+
+    pub async fn try_collect_fees_with_keeper_tip(
+        &self,
+        bank: &BankFixture,
+        keeper_token_account: Option<Pubkey>,
+    ) -> Result<()> {
         let mut ctx = self.ctx.borrow_mut();
 
+        let mut accounts = marginfi::accounts::LendingPoolCollectBankFees {
+            marginfi_group: self.key,
+            bank: bank.key,
+            liquidity_vault_authority: bank.get_vault_authority(BankVaultType::Liquidity).0,
+            liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
+            insurance_vault: bank.get_vault(BankVaultType::Insurance).0,
+            fee_vault: bank.get_vault(BankVaultType::Fee).0,
+            token_program: token::ID,
+        }
+        .to_account_metas(Some(true));
+
+        if let Some(keeper_token_account) = keeper_token_account {
+            accounts.extend(bank.get_keeper_tip_account_metas(keeper_token_account));
+        }
+
         let ix = Instruction {
             program_id: marginfi::id(),
-            accounts: marginfi::accounts::LendingPoolCollectBankFees {
+            accounts,
+            data: marginfi::instruction::LendingPoolCollectBankFees {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey().clone()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(())
+    }
+
+    pub async fn try_sweep_program_fees(
+        &self,
+        bank: &BankFixture,
+        fee_wallet_token_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let mut ctx = self.ctx.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::LendingPoolSweepProgramFees {
                 marginfi_group: self.key,
                 bank: bank.key,
+                fee_state: get_fee_state_address().0,
                 liquidity_vault_authority: bank.get_vault_authority(BankVaultType::Liquidity).0,
                 liquidity_vault: bank.get_vault(BankVaultType::Liquidity).0,
-                insurance_vault: bank.get_vault(BankVaultType::Insurance).0,
-                fee_vault: bank.get_vault(BankVaultType::Fee).0,
+                fee_wallet_token_account,
                 token_program: token::ID,
             }
             .to_account_metas(Some(true)),
-            data: marginfi::instruction::LendingPoolCollectBankFees {}.data(),
+            data: marginfi::instruction::LendingPoolSweepProgramFees {}.data(),
         };
 
         let tx = Transaction::new_signed_with_payer(
@@ -222,9 +426,7 @@ impl MarginfiGroupFixture {
             ctx.last_blockhash,
         );
 
-        ctx.banks_client.process_transaction(tx).await?;
-
-        Ok(())
+        ctx.banks_client.process_transaction(tx).await
     }
 
     pub async fn try_handle_bankruptcy(
@@ -0,0 +1,198 @@
+use crate::utils::load_and_deserialize;
+use anchor_lang::prelude::*;
+use anyhow::{anyhow, bail, Result};
+use marginfi::state::{marginfi_account::MarginfiAccount, marginfi_group::Bank};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_program_test::ProgramTestContext;
+use solana_sdk::{
+    account::{AccountSharedData, WritableAccount},
+    signer::Signer,
+};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+};
+
+/// Manifest written next to the account fixtures by `mfi account export`.
+pub const SNAPSHOT_MANIFEST_FILE: &str = "manifest.json";
+
+/// Account fixture, in the format of `solana account --output json`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountFixture {
+    pub pubkey: String,
+    pub account: UiAccount,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub program_id: String,
+    pub slot: u64,
+    pub unix_timestamp: i64,
+    pub marginfi_account: String,
+    pub marginfi_group: String,
+    pub banks: Vec<String>,
+    pub accounts: Vec<String>,
+}
+
+impl SnapshotManifest {
+    pub fn marginfi_account(&self) -> Pubkey {
+        Pubkey::from_str(&self.marginfi_account).unwrap()
+    }
+
+    pub fn marginfi_group(&self) -> Pubkey {
+        Pubkey::from_str(&self.marginfi_group).unwrap()
+    }
+
+    pub fn banks(&self) -> Vec<Pubkey> {
+        self.banks
+            .iter()
+            .map(|bank| Pubkey::from_str(bank).unwrap())
+            .collect()
+    }
+}
+
+/// Dump a marginfi account of the test harness in the same layout as `mfi account export`.
+pub async fn export_snapshot(
+    ctx: Rc<RefCell<ProgramTestContext>>,
+    marginfi_account_pk: Pubkey,
+    dir: &Path,
+) -> Result<SnapshotManifest> {
+    let marginfi_account =
+        load_and_deserialize::<MarginfiAccount>(ctx.clone(), &marginfi_account_pk).await;
+
+    let bank_pks = marginfi_account
+        .lending_account
+        .balances
+        .iter()
+        .filter(|balance| balance.active)
+        .map(|balance| balance.bank_pk)
+        .collect::<Vec<_>>();
+
+    let mut addresses = vec![marginfi_account_pk, marginfi_account.group];
+    for bank_pk in bank_pks.iter() {
+        let bank = load_and_deserialize::<Bank>(ctx.clone(), bank_pk).await;
+
+        addresses.extend([
+            *bank_pk,
+            bank.mint,
+            bank.liquidity_vault,
+            bank.insurance_vault,
+            bank.fee_vault,
+        ]);
+        addresses.extend(
+            bank.config
+                .oracle_keys
+                .iter()
+                .filter(|key| **key != Pubkey::default()),
+        );
+    }
+
+    let mut unique = vec![];
+    addresses.retain(|address| {
+        if unique.contains(address) {
+            false
+        } else {
+            unique.push(*address);
+            true
+        }
+    });
+
+    fs::create_dir_all(dir)?;
+
+    let mut ctx = ctx.borrow_mut();
+    for address in addresses.iter() {
+        let account = ctx
+            .banks_client
+            .get_account(*address)
+            .await?
+            .ok_or_else(|| anyhow!("Account {} not found", address))?;
+
+        let fixture = AccountFixture {
+            pubkey: address.to_string(),
+            account: UiAccount::encode(address, &account, UiAccountEncoding::Base64, None, None),
+        };
+
+        fs::write(
+            fixture_path(dir, address),
+            serde_json::to_string_pretty(&fixture)?,
+        )?;
+    }
+
+    let clock: Clock = ctx.banks_client.get_sysvar().await?;
+
+    let manifest = SnapshotManifest {
+        program_id: marginfi::id().to_string(),
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+        marginfi_account: marginfi_account_pk.to_string(),
+        marginfi_group: marginfi_account.group.to_string(),
+        banks: bank_pks.iter().map(|pk| pk.to_string()).collect(),
+        accounts: addresses.iter().map(|pk| pk.to_string()).collect(),
+    };
+
+    fs::write(
+        dir.join(SNAPSHOT_MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(manifest)
+}
+
+/// Load a snapshot written by `mfi account export` into the test harness.
+///
+/// The marginfi account authority is replaced with the payer so that the test can act on its behalf,
+/// and the clock is set to the time of the snapshot so that its oracles aren't stale.
+/// Bank vaults are PDAs of the program, so the snapshot must come from the program id the tests are built with
+/// (e.g. `--features mainnet-beta` for mainnet snapshots).
+pub async fn load_snapshot(
+    ctx: Rc<RefCell<ProgramTestContext>>,
+    dir: &Path,
+) -> Result<SnapshotManifest> {
+    let manifest: SnapshotManifest =
+        serde_json::from_str(&fs::read_to_string(dir.join(SNAPSHOT_MANIFEST_FILE))?)?;
+
+    if Pubkey::from_str(&manifest.program_id)? != marginfi::id() {
+        bail!(
+            "Snapshot taken from program {}, tests are built for {}",
+            manifest.program_id,
+            marginfi::id()
+        );
+    }
+
+    let marginfi_account_pk = manifest.marginfi_account();
+
+    let mut ctx = ctx.borrow_mut();
+    for address in manifest.accounts.iter() {
+        let address = Pubkey::from_str(address)?;
+        let path = fixture_path(dir, &address);
+
+        let fixture: AccountFixture = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        let mut account = fixture
+            .account
+            .decode::<AccountSharedData>()
+            .ok_or_else(|| anyhow!("Failed to decode fixture {}", path.display()))?;
+
+        if address == marginfi_account_pk {
+            let data = &mut account.data_as_mut_slice()[8..];
+            let mut marginfi_account = bytemuck::pod_read_unaligned::<MarginfiAccount>(data);
+            marginfi_account.authority = ctx.payer.pubkey();
+            data.copy_from_slice(bytemuck::bytes_of(&marginfi_account));
+        }
+
+        ctx.set_account(&address, &account);
+    }
+
+    let mut clock: Clock = ctx.banks_client.get_sysvar().await?;
+    clock.unix_timestamp = manifest.unix_timestamp;
+    ctx.set_sysvar(&clock);
+
+    Ok(manifest)
+}
+
+fn fixture_path(dir: &Path, address: &Pubkey) -> PathBuf {
+    dir.join(format!("{address}.json"))
+}
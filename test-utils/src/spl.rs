@@ -76,6 +76,13 @@ impl MintFixture {
         }
     }
 
+    /// Fixture for an existing mint, e.g. the native SOL mint
+    pub async fn fetch(ctx: Rc<RefCell<ProgramTestContext>>, key: Pubkey) -> MintFixture {
+        let mint = get_and_deserialize(ctx.clone(), key).await;
+
+        MintFixture { ctx, key, mint }
+    }
+
     #[allow(unused)]
     pub async fn reload(&mut self) {
         let mint_account = self
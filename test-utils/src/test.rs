@@ -1,5 +1,6 @@
 use crate::{marginfi_group::*, native, spl::*, utils::*};
 use anchor_lang::prelude::*;
+use anchor_lang::{InstructionData, ToAccountMetas};
 use bincode::deserialize;
 use solana_sdk::account::AccountSharedData;
 
@@ -11,13 +12,21 @@ use marginfi::state::marginfi_group::{BankConfigOpt, BankOperationalState};
 use marginfi::{
     constants::MAX_ORACLE_KEYS,
     state::{
+        fee_state::{FeeState, FeeStateConfig},
         marginfi_group::{BankConfig, GroupConfig, InterestRateConfig, RiskTier},
         price::OracleSetup,
     },
 };
-use solana_program::{hash::Hash, sysvar};
+use solana_program::{
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    hash::Hash,
+    instruction::Instruction,
+    system_instruction, system_program, sysvar,
+};
 use solana_program_test::*;
-use solana_sdk::{account::Account, pubkey, signature::Keypair, signer::Signer};
+use solana_sdk::{
+    account::Account, pubkey, signature::Keypair, signer::Signer, transaction::Transaction,
+};
 use std::collections::HashMap;
 use std::{cell::RefCell, rc::Rc};
 
@@ -44,7 +53,7 @@ impl TestSettings {
                     ..TestBankSetting::default()
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 
@@ -61,7 +70,7 @@ impl TestSettings {
                     config: Some(*DEFAULT_SOL_TEST_SW_BANK_CONFIG),
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 
@@ -86,7 +95,7 @@ impl TestSettings {
                     }),
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 
@@ -134,7 +143,7 @@ impl TestSettings {
                     ..TestBankSetting::default()
                 },
             ],
-            group_config: Some(GroupConfig { admin: None }),
+            group_config: Some(GroupConfig::default()),
         }
     }
 }
@@ -331,6 +340,27 @@ impl TestFixture {
 
         solana_logger::setup_with_default(RUST_LOG_DEFAULT);
 
+        // The program runs as a builtin, mock the program data of an upgradeable deploy
+        // so the payer can initialize the fee state as the upgrade authority
+        {
+            let mut ctx = context.borrow_mut();
+            let program_data = UpgradeableLoaderState::ProgramData {
+                slot: 0,
+                upgrade_authority_address: Some(ctx.payer.pubkey()),
+            };
+
+            ctx.set_account(
+                &get_program_data_address(),
+                &AccountSharedData::from(Account {
+                    lamports: 1_000_000_000,
+                    data: bincode::serialize(&program_data).unwrap(),
+                    owner: bpf_loader_upgradeable::id(),
+                    executable: false,
+                    rent_epoch: 0,
+                }),
+            );
+        }
+
         let usdc_mint_f = MintFixture::new(
             Rc::clone(&context),
             Some(usdc_keypair),
@@ -360,8 +390,8 @@ impl TestFixture {
             Rc::clone(&context),
             test_settings
                 .clone()
-                .map(|ts| ts.group_config.unwrap_or(GroupConfig { admin: None }))
-                .unwrap_or(GroupConfig { admin: None }),
+                .map(|ts| ts.group_config.unwrap_or_default())
+                .unwrap_or_default(),
         )
         .await;
 
@@ -542,6 +572,166 @@ impl TestFixture {
         self.context.borrow_mut().set_sysvar(&clock);
     }
 
+    pub async fn try_migrate_account(
+        &self,
+        address: Pubkey,
+    ) -> std::result::Result<(), BanksClientError> {
+        let mut ctx = self.context.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::MigrateAccount {
+                account: address,
+                payer: ctx.payer.pubkey(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::MigrateAccount {}.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Initialize the program fee state, with the payer as the global fee admin.
+    /// `signer` pays for the fee state and must be the upgrade authority, the payer.
+    pub async fn try_initialize_fee_state(
+        &self,
+        signer: &Keypair,
+        global_fee_wallet: Pubkey,
+        flat_sol_fee: u64,
+        program_fee_bps: u64,
+        fee_flags: u64,
+    ) -> std::result::Result<(), BanksClientError> {
+        let mut ctx = self.context.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::FeeStateInitialize {
+                fee_state: get_fee_state_address().0,
+                payer: signer.pubkey(),
+                program_data: get_program_data_address(),
+                system_program: system_program::id(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::FeeStateInitialize {
+                global_fee_admin: ctx.payer.pubkey(),
+                global_fee_wallet,
+                flat_sol_fee,
+                program_fee_bps,
+                fee_flags,
+            }
+            .data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer, signer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Configure the program fee state, signed by the payer as the global fee admin.
+    pub async fn try_configure_fee_state(
+        &self,
+        config: FeeStateConfig,
+    ) -> std::result::Result<(), BanksClientError> {
+        let mut ctx = self.context.borrow_mut();
+
+        let ix = Instruction {
+            program_id: marginfi::id(),
+            accounts: marginfi::accounts::FeeStateConfigure {
+                fee_state: get_fee_state_address().0,
+                global_fee_admin: ctx.payer.pubkey(),
+            }
+            .to_account_metas(Some(true)),
+            data: marginfi::instruction::FeeStateConfigure { config }.data(),
+        };
+
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    /// Transfer `lamports` from the payer to `to`.
+    pub async fn try_transfer_lamports(
+        &self,
+        to: &Pubkey,
+        lamports: u64,
+    ) -> std::result::Result<(), BanksClientError> {
+        let mut ctx = self.context.borrow_mut();
+
+        let tx = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &ctx.payer.pubkey(),
+                to,
+                lamports,
+            )],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn load_fee_state(&self) -> FeeState {
+        self.load_and_deserialize::<FeeState>(&get_fee_state_address().0)
+            .await
+    }
+
+    /// Process `ixs` signed by the payer, returning the compute units consumed by the marginfi
+    /// program, as logged by a simulation of the transaction before it is processed.
+    ///
+    /// Only BPF programs log their consumption, `None` if marginfi runs natively
+    /// (tests not run with `cargo test-sbf`).
+    pub async fn try_get_compute_units_consumed(
+        &self,
+        ixs: &[Instruction],
+    ) -> std::result::Result<Option<u64>, BanksClientError> {
+        let mut ctx = self.context.borrow_mut();
+
+        let tx = Transaction::new_signed_with_payer(
+            ixs,
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer],
+            ctx.last_blockhash,
+        );
+
+        let simulation = ctx.banks_client.simulate_transaction(tx.clone()).await?;
+        if let Some(Err(err)) = simulation.result {
+            return Err(BanksClientError::TransactionError(err));
+        }
+
+        let consumed_log_prefix = format!("Program {} consumed ", marginfi::id());
+        let compute_units = simulation.simulation_details.and_then(|details| {
+            details
+                .logs
+                .iter()
+                .filter_map(|log| log.strip_prefix(&consumed_log_prefix))
+                .filter_map(|log| log.split_whitespace().next()?.parse::<u64>().ok())
+                .reduce(|total, compute_units| total + compute_units)
+        });
+
+        ctx.banks_client.process_transaction(tx).await?;
+
+        Ok(compute_units)
+    }
+
     pub async fn get_minimum_rent_for_size(&self, size: usize) -> u64 {
         self.context
             .borrow_mut()
@@ -561,6 +751,18 @@ impl TestFixture {
             .unwrap()
     }
 
+    /// Fetches a new blockhash for subsequent transactions, so that repeating an identical
+    /// instruction isn't rejected as already processed.
+    pub async fn refresh_blockhash(&self) {
+        let mut ctx = self.context.borrow_mut();
+        let last_blockhash = ctx.last_blockhash;
+        ctx.last_blockhash = ctx
+            .banks_client
+            .get_new_latest_blockhash(&last_blockhash)
+            .await
+            .unwrap();
+    }
+
     pub async fn get_slot(&self) -> u64 {
         self.context
             .borrow_mut()
@@ -570,6 +772,15 @@ impl TestFixture {
             .unwrap()
     }
 
+    pub async fn get_lamports(&self, address: &Pubkey) -> u64 {
+        self.context
+            .borrow_mut()
+            .banks_client
+            .get_balance(*address)
+            .await
+            .unwrap()
+    }
+
     pub async fn get_clock(&self) -> Clock {
         deserialize::<Clock>(
             &self
@@ -546,6 +546,41 @@ pub fn get_emissions_token_account_address(
     )
 }
 
+pub fn get_fee_state_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[marginfi::constants::FEE_STATE_SEED.as_bytes()],
+        &marginfi::id(),
+    )
+}
+
+pub fn get_program_data_address() -> Pubkey {
+    Pubkey::find_program_address(
+        &[marginfi::id().as_ref()],
+        &solana_program::bpf_loader_upgradeable::id(),
+    )
+    .0
+}
+
+pub fn get_receipt_mint_address(bank_pk: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            marginfi::constants::RECEIPT_MINT_SEED.as_bytes(),
+            bank_pk.as_ref(),
+        ],
+        &marginfi::id(),
+    )
+}
+
+pub fn get_receipt_mint_authority_address(bank_pk: Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            marginfi::constants::RECEIPT_MINT_AUTHORITY_SEED.as_bytes(),
+            bank_pk.as_ref(),
+        ],
+        &marginfi::id(),
+    )
+}
+
 #[cfg(feature = "lip")]
 pub mod lip {
     use super::*;
@@ -598,4 +633,15 @@ pub mod lip {
             &liquidity_incentive_program::id(),
         )
     }
+
+    pub fn get_user_deposits_address(campaign_key: Pubkey, owner: Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                liquidity_incentive_program::constants::USER_DEPOSITS_SEED.as_bytes(),
+                campaign_key.as_ref(),
+                owner.as_ref(),
+            ],
+            &liquidity_incentive_program::id(),
+        )
+    }
 }
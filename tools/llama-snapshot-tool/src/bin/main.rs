@@ -7,7 +7,7 @@ use futures::future::join_all;
 use lazy_static::lazy_static;
 use marginfi::{
     constants::{EMISSIONS_FLAG_BORROW_ACTIVE, EMISSIONS_FLAG_LENDING_ACTIVE, SECONDS_PER_YEAR},
-    state::marginfi_group::Bank,
+    state::{amounts::ShareAmount, marginfi_group::Bank},
 };
 use reqwest::header::CONTENT_TYPE;
 use s3::{creds::Credentials, Bucket, Region};
@@ -133,8 +133,14 @@ impl DefiLammaPoolInfo {
         let token_price = fetch_price_from_birdeye(&bank.mint).await?;
         let scale = I80F48::from_num(10_i32.pow(bank.mint_decimals as u32));
 
-        let total_deposits = bank.get_asset_amount(bank.total_asset_shares.into())? / scale;
-        let total_borrows = bank.get_liability_amount(bank.total_liability_shares.into())? / scale;
+        let total_deposits = bank
+            .asset_amount(ShareAmount::new(bank.total_asset_shares.into()))?
+            .value()
+            / scale;
+        let total_borrows = bank
+            .liability_amount(ShareAmount::new(bank.total_liability_shares.into()))?
+            .value()
+            / scale;
 
         let net_supply = total_deposits - total_borrows;
 